@@ -0,0 +1,60 @@
+//! Direct onchain escape of a user once the orderbook has stalled. The proofs of the user's
+//! leaves are served by the server alongside its prover, and the escape transaction is sent to
+//! the node and proven by the user, without the server.
+
+use anyhow::{bail, Context, Result};
+use orderbook::{utils, zk::smt::GetKey};
+use sdk::{BlobTransaction, ContractName};
+
+use crate::{rest::OrderbookClient, types::EscapeProof};
+
+/// Escape transaction of a user, with the private input of its orderbook blob
+pub struct EscapeTransaction {
+    pub tx: BlobTransaction,
+    /// Borsh encoded `EscapePrivateInput`, proving the user info leaf
+    pub private_input: Vec<u8>,
+    /// Commitment the proofs are against, which must be the onchain one for the escape to settle
+    pub state_commitment: Vec<u8>,
+}
+
+/// Builds the escape transaction of the user of `proof`: the escape action, then the transfer
+/// of each escaped balance from the orderbook account
+pub fn escape_transaction(
+    proof: &EscapeProof,
+    orderbook_cn: ContractName,
+    action_id: u32,
+) -> Result<EscapeTransaction> {
+    let user_key = <[u8; 32]>::from(proof.user_info.get_key());
+    if hex::encode(user_key) != proof.user_key {
+        bail!(
+            "Escape proof of user key {} does not match the user info of {}",
+            proof.user_key,
+            proof.user_info.user
+        );
+    }
+    let private_input =
+        hex::decode(&proof.private_input).context("Private input is not hex encoded")?;
+    let state_commitment =
+        hex::decode(&proof.state_commitment).context("State commitment is not hex encoded")?;
+
+    let transfers: Vec<(ContractName, u64)> = proof
+        .balances
+        .iter()
+        .map(|balance| (ContractName(balance.contract_name.clone()), balance.escaped))
+        .collect();
+
+    Ok(EscapeTransaction {
+        tx: utils::escape_transaction(orderbook_cn, &proof.user_info, &transfers, action_id),
+        private_input,
+        state_commitment,
+    })
+}
+
+impl OrderbookClient {
+    /// Fetches the escape proof of the acting identity and builds its escape transaction
+    pub async fn escape_transaction(&self, action_id: u32) -> Result<EscapeTransaction> {
+        let orderbook_cn = ContractName(self.config().await?.contract_name);
+        let proof = self.escape_proof(self.acting_identity()).await?;
+        escape_transaction(&proof, orderbook_cn, action_id)
+    }
+}
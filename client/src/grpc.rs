@@ -0,0 +1,101 @@
+//! gRPC API of the server, generated from `proto/orderbook.proto`. The generated
+//! [`proto::orderbook_client::OrderbookClient`] connects to the gRPC port of the server, and
+//! [`OrderbookClient`] signs the actions into its requests as it does for the REST endpoints.
+
+use anyhow::{Context, Result};
+use orderbook::model::{Order, OrderSide, OrderType};
+use tonic::{
+    metadata::{Ascii, MetadataValue},
+    Request,
+};
+
+use crate::{
+    rest::{OrderbookClient, PUBLIC_KEY_HEADER, SIGNATURE_HEADER},
+    types::CreateOrderRequest,
+};
+
+pub mod proto {
+    tonic::include_proto!("hyliquid.orderbook.v1");
+}
+
+impl From<&Order> for proto::Order {
+    fn from(order: &Order) -> Self {
+        let order_side = match order.order_side {
+            OrderSide::Bid => proto::OrderSide::Bid,
+            OrderSide::Ask => proto::OrderSide::Ask,
+        };
+        let order_type = match order.order_type {
+            OrderType::Market => proto::OrderType::Market,
+            OrderType::Limit => proto::OrderType::Limit,
+            OrderType::Stop => proto::OrderType::Stop,
+            OrderType::StopLimit => proto::OrderType::StopLimit,
+            OrderType::StopMarket => proto::OrderType::StopMarket,
+        };
+        proto::Order {
+            order_id: order.order_id.clone(),
+            order_side: order_side.into(),
+            order_type: order_type.into(),
+            price: order.price,
+            base: order.pair.0.clone(),
+            quote: order.pair.1.clone(),
+            quantity: order.quantity,
+        }
+    }
+}
+
+impl OrderbookClient {
+    /// `CreateOrder` request, signed at the current nonce
+    pub async fn grpc_create_order_request(
+        &self,
+        request: &CreateOrderRequest,
+    ) -> Result<Request<proto::CreateOrderRequest>> {
+        let nonce = self.nonce().await?;
+        let signature = self.require_session_key()?.sign_create_order(
+            self.acting_identity(),
+            nonce,
+            &request.order,
+        );
+        let message = proto::CreateOrderRequest {
+            order: Some((&request.order).into()),
+            client_order_id: request.client_order_id.clone(),
+            tag: request.tag.clone(),
+        };
+        self.grpc_signed_request(message, signature)
+    }
+
+    /// `CancelOrder` request, signed at the current nonce
+    pub async fn grpc_cancel_order_request(
+        &self,
+        order_id: &str,
+    ) -> Result<Request<proto::CancelOrderRequest>> {
+        let nonce = self.nonce().await?;
+        let signature =
+            self.require_session_key()?
+                .sign_cancel(self.acting_identity(), nonce, order_id);
+        let message = proto::CancelOrderRequest {
+            order_id: order_id.to_string(),
+        };
+        self.grpc_signed_request(message, signature)
+    }
+
+    fn grpc_signed_request<T>(&self, message: T, signature: String) -> Result<Request<T>> {
+        let session_key = self.require_session_key()?;
+        let mut request = Request::new(message);
+        let metadata = request.metadata_mut();
+        for (name, value) in self.identity_headers() {
+            metadata.insert(name, metadata_value(value)?);
+        }
+        metadata.insert(
+            PUBLIC_KEY_HEADER,
+            metadata_value(&session_key.public_key_hex())?,
+        );
+        metadata.insert(SIGNATURE_HEADER, metadata_value(&signature)?);
+        Ok(request)
+    }
+}
+
+fn metadata_value(value: &str) -> Result<MetadataValue<Ascii>> {
+    value
+        .parse()
+        .with_context(|| format!("Invalid gRPC metadata value {value:?}"))
+}
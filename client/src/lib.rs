@@ -0,0 +1,20 @@
+//! Typed Rust client of the orderbook API.
+//!
+//! - [`OrderbookClient`] binds the REST endpoints of the server and of the read API, signing the
+//!   actions with a [`SessionKey`]. The admin endpoints, authenticated by the operator secret,
+//!   are left to the operator tooling.
+//! - [`ws`] connects to the market data feed, the balances feed and the cancel-on-disconnect
+//!   session.
+//! - [`escape`] builds the escape transaction of a user from the proofs served by the server.
+//! - [`grpc`] is the generated client of the gRPC API, whose order entry requests are signed by
+//!   [`OrderbookClient`].
+
+pub mod escape;
+pub mod grpc;
+pub mod rest;
+pub mod signer;
+pub mod types;
+pub mod ws;
+
+pub use rest::{ApiError, OrderbookClient};
+pub use signer::SessionKey;
@@ -0,0 +1,639 @@
+use std::fmt;
+
+use anyhow::{Context, Result};
+use orderbook::{
+    model::{
+        DustSweep, MarginAccount, Order, Pair, PendingWithdrawal, Position, WithdrawDestination,
+    },
+    utils,
+};
+use reqwest::{Method, RequestBuilder};
+use sdk::TxHash;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{signer::SessionKey, types::*};
+
+pub const IDENTITY_HEADER: &str = "x-identity";
+pub const PUBLIC_KEY_HEADER: &str = "x-public-key";
+pub const SIGNATURE_HEADER: &str = "x-signature";
+pub const SESSION_PERMISSIONS_HEADER: &str = "x-session-permissions";
+pub const SESSION_PAIR_HEADER: &str = "x-session-pair";
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const SUB_ACCOUNT_HEADER: &str = "x-sub-account";
+
+/// Non-success response of the API, kept in the returned error so that callers can tell a
+/// rate limit or a missing resource apart, e.g. with `error.downcast_ref::<ApiError>()`
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub status: u16,
+    pub body: String,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API returned {}: {}", self.status, self.body)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// Client of the orderbook API of an identity. Actions go to the server, which executes them
+/// and returns the hash of their transaction; balances, orders, trades and books are read from
+/// the read API, as indexed from the database.
+///
+/// Actions are signed with the session key of the client, at the nonce read from the server
+/// just before. Reads are authenticated by the identity header, or the API key if set.
+#[derive(Clone)]
+pub struct OrderbookClient {
+    http: reqwest::Client,
+    server_url: String,
+    api_url: String,
+    identity: String,
+    acting_identity: String,
+    session_key: Option<SessionKey>,
+    api_key: Option<String>,
+    sub_account: Option<String>,
+}
+
+impl OrderbookClient {
+    pub fn new(server_url: &str, api_url: &str, identity: &str) -> Self {
+        OrderbookClient {
+            http: reqwest::Client::new(),
+            server_url: server_url.trim_end_matches('/').to_string(),
+            api_url: api_url.trim_end_matches('/').to_string(),
+            identity: identity.to_string(),
+            acting_identity: identity.to_string(),
+            session_key: None,
+            api_key: None,
+            sub_account: None,
+        }
+    }
+
+    /// Session key signing the actions, which must be registered with `add_session_key`
+    pub fn with_session_key(mut self, session_key: SessionKey) -> Self {
+        self.session_key = Some(session_key);
+        self
+    }
+
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// Acts as the named sub-account of the identity
+    pub fn with_sub_account(mut self, name: &str) -> Self {
+        self.acting_identity = utils::sub_account_identity(&self.identity, name);
+        self.sub_account = Some(name.to_string());
+        self
+    }
+
+    /// Http client the requests are sent with, e.g. to set timeouts
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    pub fn identity(&self) -> &str {
+        &self.identity
+    }
+
+    /// Identity the actions are signed for: the selected sub-account, or the identity itself
+    pub fn acting_identity(&self) -> &str {
+        &self.acting_identity
+    }
+
+    pub fn session_key(&self) -> Option<&SessionKey> {
+        self.session_key.as_ref()
+    }
+
+    pub fn server_url(&self) -> &str {
+        &self.server_url
+    }
+
+    pub fn api_url(&self) -> &str {
+        &self.api_url
+    }
+
+    // Actions
+
+    /// Registers the session key of the client on the identity, with all permissions unless
+    /// restricted to e.g. "trade_only" or "cancel_only", and optionally to a single pair
+    pub async fn add_session_key(
+        &self,
+        permissions: Option<&str>,
+        pair: Option<&Pair>,
+    ) -> Result<TxHash> {
+        let session_key = self.require_session_key()?;
+        let mut request = self
+            .request(Method::POST, &self.server_url, "/add_session_key")
+            .header(PUBLIC_KEY_HEADER, session_key.public_key_hex());
+        if let Some(permissions) = permissions {
+            request = request.header(SESSION_PERMISSIONS_HEADER, permissions);
+        }
+        if let Some((base, quote)) = pair {
+            request = request.header(SESSION_PAIR_HEADER, format!("{base}/{quote}"));
+        }
+        Self::send(request).await
+    }
+
+    /// Credits a balance without any transfer. Only served by local setups.
+    pub async fn deposit(&self, symbol: &str, amount: u64) -> Result<TxHash> {
+        let body = DepositRequest {
+            symbol: symbol.to_string(),
+            amount,
+        };
+        self.post(&self.server_url, "/deposit", &body, None).await
+    }
+
+    pub async fn create_order(&self, request: CreateOrderRequest) -> Result<TxHash> {
+        let nonce = self.nonce().await?;
+        let signature = self.require_session_key()?.sign_create_order(
+            self.acting_identity(),
+            nonce,
+            &request.order,
+        );
+        self.post(&self.server_url, "/create_order", &request, Some(signature))
+            .await
+    }
+
+    /// Places orders on several pairs atomically, with a single signature
+    pub async fn create_orders(&self, requests: Vec<CreateOrderRequest>) -> Result<TxHash> {
+        let nonce = self.nonce().await?;
+        let orders: Vec<Order> = requests.iter().map(|r| r.order.clone()).collect();
+        let signature =
+            self.require_session_key()?
+                .sign_create_orders(self.acting_identity(), nonce, &orders);
+        let body = CreateOrdersRequest { orders: requests };
+        self.post(&self.server_url, "/create_orders", &body, Some(signature))
+            .await
+    }
+
+    pub async fn cancel_order(&self, order_id: &str) -> Result<TxHash> {
+        let nonce = self.nonce().await?;
+        let signature =
+            self.require_session_key()?
+                .sign_cancel(self.acting_identity(), nonce, order_id);
+        let body = CancelOrderRequest {
+            order_id: order_id.to_string(),
+        };
+        self.post(&self.server_url, "/cancel_order", &body, Some(signature))
+            .await
+    }
+
+    pub async fn withdraw(
+        &self,
+        symbol: &str,
+        amount: u64,
+        destination: WithdrawDestination,
+    ) -> Result<TxHash> {
+        let nonce = self.nonce().await?;
+        let signature = self.require_session_key()?.sign_withdraw(
+            self.acting_identity(),
+            nonce,
+            symbol,
+            amount,
+        );
+        let body = WithdrawRequest {
+            symbol: symbol.to_string(),
+            amount,
+            destination,
+        };
+        self.post(&self.server_url, "/withdraw", &body, Some(signature))
+            .await
+    }
+
+    /// Moves a balance to another account of the orderbook, such as a sub-account
+    pub async fn transfer(&self, to: &str, symbol: &str, amount: u64) -> Result<TxHash> {
+        let nonce = self.nonce().await?;
+        let signature = self.require_session_key()?.sign_transfer(
+            self.acting_identity(),
+            nonce,
+            to,
+            symbol,
+            amount,
+        );
+        let body = TransferRequest {
+            to: to.to_string(),
+            symbol: symbol.to_string(),
+            amount,
+        };
+        self.post(&self.server_url, "/transfer", &body, Some(signature))
+            .await
+    }
+
+    pub async fn sweep_dust(&self, quote: &str, sweeps: Vec<DustSweep>) -> Result<TxHash> {
+        let nonce = self.nonce().await?;
+        let signature = self.require_session_key()?.sign_sweep_dust(
+            self.acting_identity(),
+            nonce,
+            quote,
+            &sweeps,
+        );
+        let body = SweepDustRequest {
+            quote: quote.to_string(),
+            sweeps,
+        };
+        self.post(&self.server_url, "/sweep_dust", &body, Some(signature))
+            .await
+    }
+
+    // Account
+
+    /// Nonce the next action of the identity must be signed with
+    pub async fn nonce(&self) -> Result<u32> {
+        self.get(&self.server_url, "/nonce").await
+    }
+
+    pub async fn saved_addresses(&self) -> Result<Vec<SavedAddress>> {
+        self.get(&self.server_url, "/account/addresses").await
+    }
+
+    pub async fn save_address(
+        &self,
+        destination: WithdrawDestination,
+        label: &str,
+    ) -> Result<SavedAddress> {
+        let signature = self.require_session_key()?.sign_save_address(
+            self.acting_identity(),
+            &destination,
+            label,
+        );
+        let body = SaveAddressRequest {
+            destination,
+            label: label.to_string(),
+        };
+        self.post(
+            &self.server_url,
+            "/account/addresses",
+            &body,
+            Some(signature),
+        )
+        .await
+    }
+
+    pub async fn delete_address(&self, destination: WithdrawDestination) -> Result<()> {
+        let signature = self
+            .require_session_key()?
+            .sign_delete_address(self.acting_identity(), &destination);
+        let request = self
+            .signed_request(
+                Method::DELETE,
+                &self.server_url,
+                "/account/addresses",
+                signature,
+            )?
+            .json(&DeleteAddressRequest { destination });
+        Self::send(request).await
+    }
+
+    pub async fn api_keys(&self) -> Result<Vec<ApiKey>> {
+        self.get(&self.server_url, "/api_keys").await
+    }
+
+    /// Creates an API key, optionally bound to the session key of the client. The key itself
+    /// is only returned here.
+    pub async fn create_api_key(
+        &self,
+        label: &str,
+        bind_session_key: bool,
+    ) -> Result<CreatedApiKey> {
+        let signature = self
+            .require_session_key()?
+            .sign_create_api_key(self.acting_identity(), label);
+        let body = CreateApiKeyRequest {
+            label: label.to_string(),
+            bind_session_key,
+        };
+        self.post(&self.server_url, "/api_keys", &body, Some(signature))
+            .await
+    }
+
+    pub async fn revoke_api_key(&self, key_id: i64) -> Result<()> {
+        let signature = self
+            .require_session_key()?
+            .sign_revoke_api_key(self.acting_identity(), key_id);
+        let body = RevokeApiKeyRequest { key_id };
+        self.post(&self.server_url, "/api_keys/revoke", &body, Some(signature))
+            .await
+    }
+
+    pub async fn risk_limits(&self) -> Result<RiskLimits> {
+        self.get(&self.server_url, "/risk_limits").await
+    }
+
+    pub async fn pending_withdrawals(&self) -> Result<Vec<PendingWithdrawal>> {
+        self.get(&self.server_url, "/withdrawals/pending").await
+    }
+
+    /// Ledger of an asset, oldest first, over at most `limit` commits after `after_commit_id`
+    pub async fn ledger(
+        &self,
+        asset: &str,
+        after_commit_id: Option<i64>,
+        limit: Option<i64>,
+    ) -> Result<Vec<LedgerEntry>> {
+        self.get_query(
+            &self.server_url,
+            "/my/ledger",
+            &[
+                ("asset", Some(asset.to_string())),
+                ("after_commit_id", after_commit_id.map(|id| id.to_string())),
+                ("limit", limit.map(|limit| limit.to_string())),
+            ],
+        )
+        .await
+    }
+
+    pub async fn positions(&self) -> Result<Vec<Position>> {
+        self.get(&self.server_url, "/positions").await
+    }
+
+    pub async fn margin_accounts(&self) -> Result<Vec<MarginAccount>> {
+        self.get(&self.server_url, "/margin").await
+    }
+
+    pub async fn sub_accounts(&self) -> Result<Vec<String>> {
+        self.get(&self.server_url, "/sub_accounts").await
+    }
+
+    // Read API
+
+    pub async fn balances(&self) -> Result<UserBalances> {
+        self.get(&self.api_url, "/api/user/balances").await
+    }
+
+    pub async fn orders(
+        &self,
+        filter: &OrdersFilter,
+        page: &PageQuery,
+    ) -> Result<Paginated<ApiOrder>> {
+        self.get_page("/api/user/orders", filter, page).await
+    }
+
+    pub async fn pair_orders(
+        &self,
+        pair: &Pair,
+        filter: &OrdersFilter,
+        page: &PageQuery,
+    ) -> Result<Paginated<ApiOrder>> {
+        let path = format!("/api/user/orders/{}/{}", pair.0, pair.1);
+        self.get_page(&path, filter, page).await
+    }
+
+    pub async fn trades(
+        &self,
+        filter: &TradesFilter,
+        page: &PageQuery,
+    ) -> Result<Paginated<ApiTrade>> {
+        self.get_page("/api/user/trades", filter, page).await
+    }
+
+    pub async fn pair_trades(
+        &self,
+        pair: &Pair,
+        filter: &TradesFilter,
+        page: &PageQuery,
+    ) -> Result<Paginated<ApiTrade>> {
+        let path = format!("/api/user/trades/{}/{}", pair.0, pair.1);
+        self.get_page(&path, filter, page).await
+    }
+
+    pub async fn fills(
+        &self,
+        filter: &TradesFilter,
+        page: &PageQuery,
+    ) -> Result<Paginated<ApiFill>> {
+        self.get_page("/api/user/fills", filter, page).await
+    }
+
+    pub async fn pair_fills(
+        &self,
+        pair: &Pair,
+        filter: &TradesFilter,
+        page: &PageQuery,
+    ) -> Result<Paginated<ApiFill>> {
+        let path = format!("/api/user/fills/{}/{}", pair.0, pair.1);
+        self.get_page(&path, filter, page).await
+    }
+
+    pub async fn balance_history(
+        &self,
+        filter: &BalanceHistoryFilter,
+        page: &PageQuery,
+    ) -> Result<Paginated<BalanceHistoryEntry>> {
+        self.get_page("/api/user/balance_history", filter, page)
+            .await
+    }
+
+    /// Levels of the book of a pair, `group_ticks` price ticks per level
+    pub async fn book(
+        &self,
+        pair: &Pair,
+        levels: u32,
+        group_ticks: u32,
+    ) -> Result<OrderbookLevels> {
+        let path = format!("/api/book/{}/{}", pair.0, pair.1);
+        self.get_query(
+            &self.api_url,
+            &path,
+            &[("levels", levels), ("group_ticks", group_ticks)],
+        )
+        .await
+    }
+
+    pub async fn market_info(&self) -> Result<MarketInfo> {
+        self.get(&self.api_url, "/api/info").await
+    }
+
+    // Exchange status
+
+    pub async fn config(&self) -> Result<ConfigResponse> {
+        self.get(&self.server_url, "/api/config").await
+    }
+
+    pub async fn insurance_fund(&self) -> Result<InsuranceFund> {
+        self.get(&self.server_url, "/insurance_fund").await
+    }
+
+    pub async fn circuit_breakers(&self) -> Result<CircuitBreakers> {
+        self.get(&self.server_url, "/circuit_breakers").await
+    }
+
+    pub async fn bridge_status(&self) -> Result<BridgeStatus> {
+        self.get(&self.server_url, "/bridge/status").await
+    }
+
+    pub async fn bridge_limits(&self) -> Result<Vec<BridgeLimitUsage>> {
+        self.get(&self.server_url, "/bridge/limits").await
+    }
+
+    pub async fn reconciliation(&self) -> Result<ReconciliationReport> {
+        self.get(&self.server_url, "/reconciliation").await
+    }
+
+    pub async fn index_price(&self, pair: &Pair) -> Result<IndexPrice> {
+        let path = format!("/index_price/{}-{}", pair.0, pair.1);
+        self.get(&self.server_url, &path).await
+    }
+
+    pub async fn pair_analytics(&self, pair: &Pair) -> Result<PairAnalytics> {
+        let path = format!("/analytics/pair/{}-{}", pair.0, pair.1);
+        self.get(&self.server_url, &path).await
+    }
+
+    pub async fn ticker(&self, pair: &Pair) -> Result<Ticker> {
+        let path = format!("/ticker/{}-{}", pair.0, pair.1);
+        self.get(&self.server_url, &path).await
+    }
+
+    pub async fn tickers(&self) -> Result<Vec<Ticker>> {
+        self.get(&self.server_url, "/tickers").await
+    }
+
+    pub async fn node_health(&self) -> Result<NodeClientHealth> {
+        self.get(&self.server_url, "/node_health").await
+    }
+
+    /// Liveness report, returned whether the checks passed or not
+    pub async fn healthz(&self) -> Result<HealthReport> {
+        self.health_report("/healthz").await
+    }
+
+    /// Readiness report, returned whether the checks passed or not
+    pub async fn readyz(&self) -> Result<HealthReport> {
+        self.health_report("/readyz").await
+    }
+
+    /// Settlement status of the latest actions, or of the action of `tx_hash`
+    pub async fn prover_status(&self, tx_hash: Option<&str>) -> Result<ProverStatus> {
+        self.get_query(&self.server_url, "/prover/status", &[("tx_hash", tx_hash)])
+            .await
+    }
+
+    /// Latest checkpoints, or the ones of commits before `before_commit_id`
+    pub async fn checkpoints(
+        &self,
+        before_commit_id: Option<i64>,
+        limit: Option<i64>,
+    ) -> Result<Vec<StateCheckpoint>> {
+        self.get_query(
+            &self.server_url,
+            "/checkpoints",
+            &[("before_commit_id", before_commit_id), ("limit", limit)],
+        )
+        .await
+    }
+
+    pub async fn latest_checkpoint(&self) -> Result<StateCheckpoint> {
+        self.get(&self.server_url, "/checkpoints/latest").await
+    }
+
+    /// Escape proofs of an identity against the proven state, see [`crate::escape`]
+    pub async fn escape_proof(&self, identity: &str) -> Result<EscapeProof> {
+        // Sub-account identities are `parent/name`
+        let path = format!("/escape_proof/{}", identity.replace('/', "%2F"));
+        self.get(&self.server_url, &path).await
+    }
+
+    // Requests
+
+    pub(crate) fn require_session_key(&self) -> Result<&SessionKey> {
+        self.session_key
+            .as_ref()
+            .context("A session key is required to sign actions")
+    }
+
+    /// Headers identifying the client, also sent as metadata of the gRPC requests
+    pub(crate) fn identity_headers(&self) -> Vec<(&'static str, &str)> {
+        let mut headers = vec![(IDENTITY_HEADER, self.identity.as_str())];
+        if let Some(api_key) = &self.api_key {
+            headers.push((API_KEY_HEADER, api_key));
+        }
+        if let Some(sub_account) = &self.sub_account {
+            headers.push((SUB_ACCOUNT_HEADER, sub_account));
+        }
+        headers
+    }
+
+    fn request(&self, method: Method, base_url: &str, path: &str) -> RequestBuilder {
+        let mut request = self.http.request(method, format!("{base_url}{path}"));
+        for (name, value) in self.identity_headers() {
+            request = request.header(name, value);
+        }
+        request
+    }
+
+    fn signed_request(
+        &self,
+        method: Method,
+        base_url: &str,
+        path: &str,
+        signature: String,
+    ) -> Result<RequestBuilder> {
+        let session_key = self.require_session_key()?;
+        Ok(self
+            .request(method, base_url, path)
+            .header(PUBLIC_KEY_HEADER, session_key.public_key_hex())
+            .header(SIGNATURE_HEADER, signature))
+    }
+
+    async fn get<T: DeserializeOwned>(&self, base_url: &str, path: &str) -> Result<T> {
+        Self::send(self.request(Method::GET, base_url, path)).await
+    }
+
+    async fn get_query<T: DeserializeOwned, Q: Serialize + ?Sized>(
+        &self,
+        base_url: &str,
+        path: &str,
+        query: &Q,
+    ) -> Result<T> {
+        Self::send(self.request(Method::GET, base_url, path).query(query)).await
+    }
+
+    /// Page of a query endpoint of the read API
+    async fn get_page<T: DeserializeOwned, F: Serialize>(
+        &self,
+        path: &str,
+        filter: &F,
+        page: &PageQuery,
+    ) -> Result<Paginated<T>> {
+        let request = self.request(Method::GET, &self.api_url, path);
+        Self::send(request.query(filter).query(page)).await
+    }
+
+    async fn post<T: DeserializeOwned, B: Serialize>(
+        &self,
+        base_url: &str,
+        path: &str,
+        body: &B,
+        signature: Option<String>,
+    ) -> Result<T> {
+        let request = match signature {
+            Some(signature) => self.signed_request(Method::POST, base_url, path, signature)?,
+            None => self.request(Method::POST, base_url, path),
+        };
+        Self::send(request.json(body)).await
+    }
+
+    async fn health_report(&self, path: &str) -> Result<HealthReport> {
+        let response = self
+            .request(Method::GET, &self.server_url, path)
+            .send()
+            .await?;
+        Ok(response.json().await?)
+    }
+
+    async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(ApiError {
+                status: status.as_u16(),
+                body,
+            }
+            .into());
+        }
+        Ok(response.json().await?)
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::{Context, Result};
+use k256::{
+    ecdsa::{signature::DigestSigner, Signature, SigningKey},
+    SecretKey,
+};
+use orderbook::{
+    model::{DustSweep, Order, WithdrawDestination},
+    utils::{self, SignedAction},
+};
+use sha3::{Digest, Sha3_256};
+
+/// secp256k1 session key of a user, signing the messages of the actions it authorizes.
+/// Messages are signed as the server and the contract verify them: ECDSA over the SHA3-256 of
+/// the message.
+#[derive(Clone)]
+pub struct SessionKey {
+    signing_key: SigningKey,
+    public_key: Vec<u8>,
+}
+
+impl SessionKey {
+    pub fn new(signing_key: SigningKey) -> Self {
+        let public_key = signing_key
+            .verifying_key()
+            .to_encoded_point(false)
+            .as_bytes()
+            .to_vec();
+        SessionKey {
+            signing_key,
+            public_key,
+        }
+    }
+
+    /// Session key from a 32 bytes hex encoded secret key
+    pub fn from_hex(secret_key: &str) -> Result<Self> {
+        let bytes = hex::decode(secret_key.trim_start_matches("0x"))
+            .context("Secret key is not hex encoded")?;
+        let secret_key = SecretKey::from_slice(&bytes).context("Invalid secret key")?;
+        Ok(Self::new(SigningKey::from(secret_key)))
+    }
+
+    /// Session key derived from the SHA3-256 of a seed. Deterministic, for test setups only.
+    pub fn from_seed(seed: &str) -> Result<Self> {
+        let derived_key = Sha3_256::digest(seed.as_bytes());
+        let secret_key =
+            SecretKey::from_slice(&derived_key).context("Invalid private key derived from seed")?;
+        Ok(Self::new(SigningKey::from(secret_key)))
+    }
+
+    /// SEC1 uncompressed public key, as registered on the user
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(&self.public_key)
+    }
+
+    /// Hex encoded signature of a message
+    pub fn sign(&self, message: &str) -> String {
+        let mut hasher = Sha3_256::new();
+        hasher.update(message.as_bytes());
+        let signature: Signature = self.signing_key.sign_digest(hasher);
+        hex::encode(signature.to_bytes())
+    }
+
+    pub fn sign_action(&self, action: &SignedAction) -> String {
+        self.sign(&action.message())
+    }
+
+    /// Format: `{user}:{nonce}:create_order:{order_id}`
+    pub fn sign_create_order(&self, user: &str, nonce: u32, order: &Order) -> String {
+        self.sign_action(&SignedAction::CreateOrder { user, nonce, order })
+    }
+
+    /// Format: `{user}:{nonce}:create_orders:{order_id},...`
+    pub fn sign_create_orders(&self, user: &str, nonce: u32, orders: &[Order]) -> String {
+        self.sign_action(&SignedAction::CreateOrders {
+            user,
+            nonce,
+            orders,
+        })
+    }
+
+    /// Format: `{user}:{nonce}:cancel:{order_id}`
+    pub fn sign_cancel(&self, user: &str, nonce: u32, order_id: &str) -> String {
+        self.sign_action(&SignedAction::CancelOrder {
+            user,
+            nonce,
+            order_id,
+        })
+    }
+
+    /// Format: `{user}:{nonce}:withdraw:{symbol}:{amount}`
+    pub fn sign_withdraw(&self, user: &str, nonce: u32, symbol: &str, amount: u64) -> String {
+        self.sign_action(&SignedAction::Withdraw {
+            user,
+            nonce,
+            symbol,
+            amount,
+        })
+    }
+
+    /// Format: `{user}:{nonce}:transfer:{to}:{symbol}:{amount}`
+    pub fn sign_transfer(
+        &self,
+        user: &str,
+        nonce: u32,
+        to: &str,
+        symbol: &str,
+        amount: u64,
+    ) -> String {
+        self.sign_action(&SignedAction::Transfer {
+            user,
+            nonce,
+            to,
+            symbol,
+            amount,
+        })
+    }
+
+    pub fn sign_sweep_dust(
+        &self,
+        user: &str,
+        nonce: u32,
+        quote: &str,
+        sweeps: &[DustSweep],
+    ) -> String {
+        self.sign_action(&SignedAction::SweepDust {
+            user,
+            nonce,
+            quote,
+            sweeps,
+        })
+    }
+
+    /// Format: `{user}:{nonce}:cancel_on_disconnect:{timeout_secs}:{order_ids joined by commas}`
+    pub fn sign_cancel_on_disconnect(
+        &self,
+        user: &str,
+        nonce: u32,
+        timeout_secs: u64,
+        order_ids: &[String],
+    ) -> String {
+        self.sign(&utils::cancel_on_disconnect_message(
+            user,
+            nonce,
+            timeout_secs,
+            order_ids,
+        ))
+    }
+
+    pub fn sign_save_address(
+        &self,
+        user: &str,
+        destination: &WithdrawDestination,
+        label: &str,
+    ) -> String {
+        self.sign(&utils::save_address_message(user, destination, label))
+    }
+
+    pub fn sign_delete_address(&self, user: &str, destination: &WithdrawDestination) -> String {
+        self.sign(&utils::delete_address_message(user, destination))
+    }
+
+    pub fn sign_create_api_key(&self, user: &str, label: &str) -> String {
+        self.sign(&utils::create_api_key_message(user, label))
+    }
+
+    pub fn sign_revoke_api_key(&self, user: &str, key_id: i64) -> String {
+        self.sign(&utils::revoke_api_key_message(user, key_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use orderbook::model::{OrderSide, OrderType};
+
+    use super::*;
+
+    #[test]
+    fn test_signatures_verify_against_the_action_messages() {
+        let session_key = SessionKey::from_seed("client_user").unwrap();
+        let order = Order {
+            order_id: "order_1".to_string(),
+            order_side: OrderSide::Bid,
+            order_type: OrderType::Limit,
+            price: Some(100),
+            pair: ("BTC".to_string(), "USDC".to_string()),
+            quantity: 1,
+        };
+
+        let signature =
+            hex::decode(session_key.sign_create_order("client_user", 3, &order)).unwrap();
+        assert!(utils::verify_signature(
+            &signature,
+            "client_user:3:create_order:order_1",
+            &session_key.public_key().to_vec(),
+        ));
+
+        let order_ids = vec!["order_1".to_string()];
+        let signature =
+            hex::decode(session_key.sign_cancel_on_disconnect("client_user", 4, 30, &order_ids))
+                .unwrap();
+        assert!(utils::verify_signature(
+            &signature,
+            "client_user:4:cancel_on_disconnect:30:order_1",
+            &session_key.public_key().to_vec(),
+        ));
+    }
+}
@@ -0,0 +1,541 @@
+//! Request and response bodies of the orderbook API, mirroring the JSON served by the server and
+//! the read API. Fields the client has no use for are ignored when deserializing.
+
+use std::collections::BTreeMap;
+
+use orderbook::model::{
+    CircuitBreakerState, DustSweep, Order, OrderSide, OrderType, Pair, Symbol, UserInfo,
+    WithdrawDestination,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DepositRequest {
+    pub symbol: String,
+    pub amount: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateOrderRequest {
+    #[serde(flatten)]
+    pub order: Order,
+    /// Identifier of the order in the client's own systems, echoed in order events
+    pub client_order_id: Option<String>,
+    /// Free-form label, echoed in order events
+    pub tag: Option<String>,
+}
+
+impl From<Order> for CreateOrderRequest {
+    fn from(order: Order) -> Self {
+        CreateOrderRequest {
+            order,
+            client_order_id: None,
+            tag: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateOrdersRequest {
+    pub orders: Vec<CreateOrderRequest>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CancelOrderRequest {
+    pub order_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WithdrawRequest {
+    pub symbol: String,
+    pub amount: u64,
+    pub destination: WithdrawDestination,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SweepDustRequest {
+    pub quote: Symbol,
+    pub sweeps: Vec<DustSweep>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TransferRequest {
+    pub to: String,
+    pub symbol: String,
+    pub amount: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SaveAddressRequest {
+    pub destination: WithdrawDestination,
+    pub label: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeleteAddressRequest {
+    pub destination: WithdrawDestination,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateApiKeyRequest {
+    pub label: String,
+    #[serde(default)]
+    pub bind_session_key: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RevokeApiKeyRequest {
+    pub key_id: i64,
+}
+
+/// Balance of a user in an asset, from the read API
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Balance {
+    pub symbol: String,
+    pub total: i64,
+    pub reserved: i64,
+    pub available: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserBalances {
+    pub balances: Vec<Balance>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    Open,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+/// Order of a user, from the read API
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiOrder {
+    pub order_id: String,
+    /// Commit that created the order
+    pub commit_id: u64,
+    pub instrument_id: i64,
+    pub side: OrderSide,
+    pub r#type: OrderType,
+    pub price: Option<u64>,
+    pub qty: u64,
+    pub qty_filled: u64,
+    pub qty_remaining: u64,
+    pub status: OrderStatus,
+    pub client_order_id: Option<String>,
+    pub tag: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pagination {
+    pub limit: u32,
+    /// Cursor of the next page, None on the last one
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+/// Page of a query endpoint of the read API, rows in the order of the commits that wrote them
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Paginated<T> {
+    pub data: Vec<T>,
+    pub pagination: Pagination,
+}
+
+impl<T> Paginated<T> {
+    /// Query of the page following this one, fetched with `query`. None on the last page.
+    pub fn next_page(&self, query: &PageQuery) -> Option<PageQuery> {
+        let cursor = self.pagination.next_cursor.clone()?;
+        Some(PageQuery {
+            cursor: Some(cursor),
+            ..query.clone()
+        })
+    }
+}
+
+/// Page of a query endpoint, unset fields using the defaults of the read API: the first page
+/// of 20 rows, latest commits first
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PageQuery {
+    /// `next_cursor` of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+    /// "desc" for the latest commits first, or "asc"
+    pub sort_order: Option<String>,
+    /// Range of commits of the rows, both ends included
+    pub from_commit: Option<u64>,
+    pub to_commit: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OrdersFilter {
+    pub status: Option<OrderStatus>,
+    pub side: Option<OrderSide>,
+}
+
+/// Side of the taker for trades, of the order of the user for fills
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TradesFilter {
+    pub side: Option<OrderSide>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BalanceHistoryFilter {
+    pub symbol: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiTrade {
+    pub trade_id: i64,
+    pub commit_id: u64,
+    pub instrument_id: i64,
+    pub price: u64,
+    pub qty: u64,
+    pub trade_time: String,
+    /// Side of the taker
+    pub side: OrderSide,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+/// Trade of the user from their side: `order_id` and `side` are those of their order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiFill {
+    pub trade_id: i64,
+    pub commit_id: u64,
+    pub instrument_id: i64,
+    pub order_id: String,
+    pub side: OrderSide,
+    pub liquidity: Liquidity,
+    pub price: u64,
+    pub qty: u64,
+    pub trade_time: String,
+}
+
+/// Balance of an asset after a commit changed it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BalanceHistoryEntry {
+    pub event_id: i64,
+    pub commit_id: u64,
+    pub symbol: String,
+    pub total: u64,
+    pub reserved: u64,
+    pub event_time: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerEntryKind {
+    Deposit,
+    Withdrawal,
+    Reserve,
+    Fill,
+    Fee,
+    Transfer,
+    Funding,
+    DustSweep,
+}
+
+/// Change of the available balance of an asset, with the balance once it applied
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LedgerEntry {
+    pub commit_id: i64,
+    pub kind: LedgerEntryKind,
+    pub amount: i64,
+    pub balance: i64,
+    pub trade_id: Option<i64>,
+    pub order_id: Option<String>,
+    pub time: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BookLevel {
+    pub price: u64,
+    pub quantity: u64,
+}
+
+/// Aggregated levels of a book, bids and asks as served by the read API
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OrderbookLevels {
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiAsset {
+    pub symbol: String,
+    pub contract_name: String,
+    pub scale: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiInstrument {
+    pub instrument_id: i64,
+    pub symbol: String,
+    pub tick_size: u64,
+    pub qty_step: u64,
+    pub status: String,
+}
+
+/// Assets and active instruments of the exchange
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MarketInfo {
+    pub assets: Vec<ApiAsset>,
+    pub instruments: Vec<ApiInstrument>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ConfigResponse {
+    pub contract_name: String,
+}
+
+/// Saved withdrawal destination of a user
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedAddress {
+    pub network: String,
+    pub address: String,
+    pub label: String,
+    /// Unix timestamp in milliseconds
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub use_count: i32,
+}
+
+/// API key of a user, without the key itself
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiKey {
+    pub key_id: i64,
+    pub prefix: String,
+    pub label: String,
+    pub session_public_key: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+/// API key, as returned once on creation
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct RiskLimits {
+    pub max_order_notional: Option<u64>,
+    pub max_open_notional: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InsuranceFund {
+    pub identity: String,
+    pub balances: BTreeMap<Symbol, u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PairCircuitBreaker {
+    pub pair: Pair,
+    #[serde(flatten)]
+    pub state: CircuitBreakerState,
+    pub halted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CircuitBreakers {
+    pub block_height: u64,
+    pub circuit_breakers: Vec<PairCircuitBreaker>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BridgeStatus {
+    pub paused: bool,
+    pub contract_paused: bool,
+    pub forced: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BridgeLimitUsage {
+    pub contract_name: String,
+    /// "deposit" or "withdraw"
+    pub direction: String,
+    pub window_secs: u64,
+    pub max: u64,
+    pub used: u128,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReconciliationReport {
+    /// "ok" or "drift"
+    pub status: String,
+    pub checked_at_ms: u64,
+    pub max_drift_bps: u64,
+    pub assets: Vec<AssetReconciliation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AssetReconciliation {
+    pub symbol: String,
+    pub contract_name: String,
+    pub owed: u128,
+    pub held: Option<u128>,
+    pub drift: Option<i128>,
+    pub shortfall_bps: Option<u64>,
+    pub alert: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IndexPrice {
+    pub symbol: String,
+    pub price: i64,
+    pub sources: i32,
+    /// Unix timestamp in milliseconds
+    pub updated_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NodeClientHealth {
+    /// "closed", "open" or "half_open"
+    pub circuit: String,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HealthReport {
+    /// "ok" or "fail"
+    pub status: String,
+    pub checks: Vec<HealthCheck>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HealthCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: Option<String>,
+    pub duration_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProverStatus {
+    pub latest_commit_id: i64,
+    pub latest_proven_commit_id: Option<i64>,
+    pub latest_settled_commit_id: Option<i64>,
+    pub settlement_lag_commits: Option<i64>,
+    pub pending_txs: i64,
+    pub proving_txs: i64,
+    pub avg_proving_time_ms: Option<f64>,
+    pub txs: Vec<TxSettlementStatus>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TxSettlementStatus {
+    pub commit_id: i64,
+    pub tx_hash: String,
+    /// "pending", "proving", "settled" or "failed"
+    pub status: String,
+    pub proof_tx_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PairAnalytics {
+    pub symbol: String,
+    pub book: BookDepth,
+    pub trades_24h: PairTradeStats,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BookDepth {
+    pub bid_orders: usize,
+    pub ask_orders: usize,
+    pub bid_quantity: u64,
+    pub ask_quantity: u64,
+    pub best_bid: Option<u64>,
+    pub best_ask: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PairTradeStats {
+    pub trade_count: i64,
+    pub volume: i64,
+    pub last_price: Option<i64>,
+    pub last_trade_at: Option<i64>,
+    pub avg_fill_latency_ms: Option<f64>,
+}
+
+/// Trading statistics of a pair over the last 24 hours
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Ticker {
+    pub symbol: String,
+    pub last_price: Option<i64>,
+    pub open_price: Option<i64>,
+    pub high_price: Option<i64>,
+    pub low_price: Option<i64>,
+    pub price_change: Option<i64>,
+    pub price_change_percent: Option<f64>,
+    pub volume: i64,
+    pub trade_count: i64,
+    pub last_trade_at: Option<i64>,
+}
+
+/// Signed checkpoint of the state commitment of the orderbook
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StateCheckpoint {
+    pub orderbook: String,
+    pub commit_id: i64,
+    /// Hex encoded
+    pub state_commitment: String,
+    pub da_height: i64,
+    pub timestamp_ms: i64,
+    /// Hex encoded SEC1 compressed secp256k1 public key
+    pub public_key: String,
+    /// Hex encoded `r ‖ s`
+    pub signature: String,
+    pub da_tx_hash: Option<String>,
+}
+
+/// Proofs of the leaves of a user against the proven state, to escape a stalled orderbook
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EscapeProof {
+    /// Commitment the proofs are against, to compare with the onchain state commitment
+    pub state_commitment: String,
+    pub user_key: String,
+    pub user_info: UserInfo,
+    /// Hex of the borsh encoded `EscapePrivateInput` the escape transaction is proven with
+    pub private_input: String,
+    pub balances: Vec<EscapeBalance>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EscapeBalance {
+    pub symbol: Symbol,
+    pub contract_name: String,
+    pub balance: u64,
+    /// Balance plus the quantity of the open orders, transferred by the escape
+    pub escaped: u64,
+    pub proof: Option<String>,
+}
+
+/// Update of the `balances@{identity}` channel
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BalanceUpdate {
+    pub channel: String,
+    pub symbol: String,
+    pub available: u64,
+    pub locked: u64,
+    /// Whether the commit this balance was last changed in has settled
+    pub settled: bool,
+    pub commit_id: i64,
+    pub tx_hash: Option<String>,
+}
@@ -0,0 +1,306 @@
+use anyhow::{bail, Context, Result};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+use crate::{rest::OrderbookClient, types::BalanceUpdate};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// WebSocket url of an http url, e.g. of the server
+pub fn ws_url(http_url: &str) -> String {
+    if let Some(rest) = http_url.strip_prefix("https://") {
+        format!("wss://{rest}")
+    } else if let Some(rest) = http_url.strip_prefix("http://") {
+        format!("ws://{rest}")
+    } else {
+        http_url.to_string()
+    }
+}
+
+/// Channel of the market data feed. Instruments are pairs as `BASE/QUOTE`.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedSubscription {
+    /// "l2Book", "trades", "orders", "instruments", "liquidations" or "candlestick"
+    pub r#type: String,
+    pub instrument: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_ticks: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step_sec: Option<u32>,
+    /// Identity of the trades and orders channels
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+impl FeedSubscription {
+    fn new(r#type: &str, instrument: &str) -> Self {
+        FeedSubscription {
+            r#type: r#type.to_string(),
+            instrument: instrument.to_string(),
+            group_ticks: None,
+            step_sec: None,
+            user: None,
+        }
+    }
+
+    pub fn l2_book(instrument: &str, group_ticks: u32) -> Self {
+        FeedSubscription {
+            group_ticks: Some(group_ticks),
+            ..Self::new("l2Book", instrument)
+        }
+    }
+
+    pub fn trades(instrument: &str, user: &str) -> Self {
+        FeedSubscription {
+            user: Some(user.to_string()),
+            ..Self::new("trades", instrument)
+        }
+    }
+
+    pub fn orders(instrument: &str, user: &str) -> Self {
+        FeedSubscription {
+            user: Some(user.to_string()),
+            ..Self::new("orders", instrument)
+        }
+    }
+
+    pub fn instruments(instrument: &str) -> Self {
+        Self::new("instruments", instrument)
+    }
+
+    pub fn liquidations(instrument: &str) -> Self {
+        Self::new("liquidations", instrument)
+    }
+
+    pub fn candlestick(instrument: &str, step_sec: u32) -> Self {
+        FeedSubscription {
+            step_sec: Some(step_sec),
+            ..Self::new("candlestick", instrument)
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct FeedRequest<'a> {
+    method: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subscription: Option<&'a FeedSubscription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_seq: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    epoch: Option<&'a str>,
+}
+
+/// Message of the market data feed: an update or snapshot of a channel, or a heartbeat,
+/// resync, pong or error message, told apart by `kind`
+#[derive(Deserialize, Debug, Clone)]
+pub struct FeedMessage {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub instrument: Option<String>,
+    #[serde(default)]
+    pub data: serde_json::Value,
+    pub timestamp: Option<u64>,
+    /// Sequence number of the update on its channel, to detect gaps and resync
+    pub seq: Option<u64>,
+    pub epoch: Option<String>,
+    /// Full state of the channel as of `seq`, rather than a single update
+    #[serde(default)]
+    pub snapshot: bool,
+    /// Update sent again in response to a resync
+    #[serde(default)]
+    pub replay: bool,
+    /// Rest of the message, such as the channels of a heartbeat or the status of a resync
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Connection to the market data feed of the read API
+pub struct MarketFeed {
+    socket: Socket,
+}
+
+impl MarketFeed {
+    pub async fn connect(url: &str) -> Result<Self> {
+        let (socket, _) = connect_async(url)
+            .await
+            .with_context(|| format!("connecting to the market feed at {url}"))?;
+        Ok(MarketFeed { socket })
+    }
+
+    pub async fn subscribe(&mut self, subscription: &FeedSubscription) -> Result<()> {
+        self.send(FeedRequest {
+            method: "subscribe",
+            subscription: Some(subscription),
+            last_seq: None,
+            epoch: None,
+        })
+        .await
+    }
+
+    pub async fn unsubscribe(&mut self, subscription: &FeedSubscription) -> Result<()> {
+        self.send(FeedRequest {
+            method: "unsubscribe",
+            subscription: Some(subscription),
+            last_seq: None,
+            epoch: None,
+        })
+        .await
+    }
+
+    /// Asks for the updates of a channel after `last_seq`, or a fresh snapshot if they are gone
+    pub async fn resync(
+        &mut self,
+        subscription: &FeedSubscription,
+        last_seq: u64,
+        epoch: &str,
+    ) -> Result<()> {
+        self.send(FeedRequest {
+            method: "resync",
+            subscription: Some(subscription),
+            last_seq: Some(last_seq),
+            epoch: Some(epoch),
+        })
+        .await
+    }
+
+    pub async fn ping(&mut self) -> Result<()> {
+        self.send(FeedRequest {
+            method: "ping",
+            subscription: None,
+            last_seq: None,
+            epoch: None,
+        })
+        .await
+    }
+
+    /// Next message of the feed, None once the connection is closed
+    pub async fn next_message(&mut self) -> Result<Option<FeedMessage>> {
+        next_json(&mut self.socket).await
+    }
+
+    async fn send(&mut self, request: FeedRequest<'_>) -> Result<()> {
+        let text = serde_json::to_string(&request)?;
+        self.socket.send(Message::text(text)).await?;
+        Ok(())
+    }
+}
+
+/// Balances of an identity streamed by the server: the current balances first, then each
+/// change once applied, and again once settled
+pub struct BalancesFeed {
+    socket: Socket,
+}
+
+impl BalancesFeed {
+    pub async fn connect(client: &OrderbookClient, identity: &str) -> Result<Self> {
+        let url = format!("{}/balances", ws_url(client.server_url()));
+        let url = reqwest::Url::parse_with_params(&url, &[("identity", identity)])?;
+        let (socket, _) = connect_async(url.as_str())
+            .await
+            .with_context(|| format!("connecting to the balances feed at {url}"))?;
+        Ok(BalancesFeed { socket })
+    }
+
+    /// Next balance update, None once the connection is closed
+    pub async fn next_update(&mut self) -> Result<Option<BalanceUpdate>> {
+        next_json(&mut self.socket).await
+    }
+}
+
+/// Cancel-on-disconnect session: the given open orders of the client are cancelled by the
+/// server when no heartbeat is received for `timeout_secs`. The authorization is bound to the
+/// nonce of the identity, so the session has to be re-armed with `rearm` after each action.
+/// Dropping the session does not disarm it.
+pub struct CancelOnDisconnect {
+    socket: Socket,
+    timeout_secs: u64,
+}
+
+#[derive(Serialize)]
+struct CancelOnDisconnectUpdate<'a> {
+    nonce: u32,
+    order_ids: &'a [String],
+    signature: String,
+}
+
+impl CancelOnDisconnect {
+    pub async fn arm(
+        client: &OrderbookClient,
+        timeout_secs: u64,
+        order_ids: &[String],
+    ) -> Result<Self> {
+        let Some(session_key) = client.session_key() else {
+            bail!("A session key is required to arm cancel on disconnect");
+        };
+        let identity = client.acting_identity();
+        let nonce = client.nonce().await?;
+        let signature =
+            session_key.sign_cancel_on_disconnect(identity, nonce, timeout_secs, order_ids);
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/cancel_on_disconnect", ws_url(client.server_url())),
+            &[
+                ("identity", identity.to_string()),
+                ("public_key", session_key.public_key_hex()),
+                ("signature", signature),
+                ("timeout_secs", timeout_secs.to_string()),
+                ("nonce", nonce.to_string()),
+                ("order_ids", order_ids.join(",")),
+            ],
+        )?;
+        let (socket, _) = connect_async(url.as_str())
+            .await
+            .context("arming cancel on disconnect")?;
+        Ok(CancelOnDisconnect {
+            socket,
+            timeout_secs,
+        })
+    }
+
+    /// Authorizes the cancellation of the current open orders at the current nonce, to be
+    /// called after each action of the identity. Counts as a heartbeat.
+    pub async fn rearm(&mut self, client: &OrderbookClient, order_ids: &[String]) -> Result<()> {
+        let Some(session_key) = client.session_key() else {
+            bail!("A session key is required to arm cancel on disconnect");
+        };
+        let nonce = client.nonce().await?;
+        let update = CancelOnDisconnectUpdate {
+            nonce,
+            order_ids,
+            signature: session_key.sign_cancel_on_disconnect(
+                client.acting_identity(),
+                nonce,
+                self.timeout_secs,
+                order_ids,
+            ),
+        };
+        self.socket
+            .send(Message::text(serde_json::to_string(&update)?))
+            .await
+            .context("cancel on disconnect session closed")
+    }
+
+    /// Keeps the orders alive for another timeout. Fails once the server closed the session,
+    /// as it does when a heartbeat lapsed and the orders were cancelled.
+    pub async fn heartbeat(&mut self) -> Result<()> {
+        self.socket
+            .send(Message::text("heartbeat"))
+            .await
+            .context("cancel on disconnect session closed")
+    }
+}
+
+async fn next_json<T: serde::de::DeserializeOwned>(socket: &mut Socket) -> Result<Option<T>> {
+    while let Some(message) = socket.next().await {
+        match message? {
+            Message::Text(text) => return Ok(Some(serde_json::from_str(&text)?)),
+            Message::Close(_) => return Ok(None),
+            _ => continue,
+        }
+    }
+    Ok(None)
+}
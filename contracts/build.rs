@@ -10,11 +10,19 @@ fn main() {
 
     use sp1_sdk::{Prover, ProverClient};
 
+    let mut features = vec!["sp1".to_string()];
+    if cfg!(feature = "ed25519") {
+        features.push("ed25519".to_string());
+    }
+    if cfg!(feature = "passkey") {
+        features.push("passkey".to_string());
+    }
+
     build_program_with_args(
         "./orderbook",
         BuildArgs {
             docker: !cfg!(feature = "nonreproducible"),
-            features: vec!["sp1".to_string()],
+            features,
             output_directory: Some("../elf".to_string()),
             ..Default::default()
         },
@@ -31,4 +39,47 @@ fn main() {
     let vk = serde_json::to_vec(&vk).expect("Failed to serialize SP1 Proving Key");
 
     std::fs::write("../elf/orderbook_vk", vk).expect("Failed to write verification key");
+
+    #[cfg(feature = "risc0")]
+    build_risc0();
+}
+
+/// Builds the `orderbook_risc0` guest, and writes its ELF and image id next to the SP1 ones
+#[cfg(all(not(feature = "nobuild"), feature = "risc0"))]
+fn build_risc0() {
+    use std::collections::HashMap;
+
+    use risc0_build::{embed_methods_with_options, DockerOptionsBuilder, GuestOptionsBuilder};
+
+    let mut features = vec!["risc0".to_string()];
+    if cfg!(feature = "ed25519") {
+        features.push("ed25519".to_string());
+    }
+    if cfg!(feature = "passkey") {
+        features.push("passkey".to_string());
+    }
+
+    let mut options = GuestOptionsBuilder::default();
+    options.features(features);
+    if !cfg!(feature = "nonreproducible") {
+        options.use_docker(
+            DockerOptionsBuilder::default()
+                .root_dir("..")
+                .build()
+                .expect("Failed to build Risc0 docker options"),
+        );
+    }
+    let options = options
+        .build()
+        .expect("Failed to build Risc0 guest options");
+
+    let guests = embed_methods_with_options(HashMap::from([("orderbook", options)]));
+    let guest = guests
+        .iter()
+        .find(|guest| guest.name == "orderbook_risc0")
+        .expect("Risc0 guest orderbook_risc0 was not built");
+
+    std::fs::write("../elf/orderbook_risc0", &guest.elf).expect("Failed to write Risc0 ELF");
+    std::fs::write("../elf/orderbook_risc0_vk", guest.image_id.as_bytes())
+        .expect("Failed to write Risc0 image id");
 }
@@ -1,6 +1,12 @@
 mod metadata {
     pub const ORDERBOOK_ELF: &[u8] = include_bytes!("../elf/orderbook");
     pub const ORDERBOOK_VK: &[u8] = include_bytes!("../elf/orderbook_vk");
+
+    #[cfg(feature = "risc0")]
+    pub const ORDERBOOK_RISC0_ELF: &[u8] = include_bytes!("../elf/orderbook_risc0");
+    /// Image id of the Risc0 guest, which is its program id
+    #[cfg(feature = "risc0")]
+    pub const ORDERBOOK_RISC0_VK: &[u8] = include_bytes!("../elf/orderbook_risc0_vk");
 }
 
 pub use metadata::*;
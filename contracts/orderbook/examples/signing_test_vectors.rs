@@ -0,0 +1,11 @@
+//! Prints the canonical signing test vectors of the orderbook contract as JSON.
+//!
+//! `cargo run -p orderbook --example signing_test_vectors > signing_test_vectors.json`
+
+fn main() {
+    let vectors = orderbook::test_vectors::signing_test_vectors();
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&vectors).expect("serialize signing test vectors")
+    );
+}
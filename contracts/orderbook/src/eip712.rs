@@ -0,0 +1,236 @@
+//! EIP-712 typed data signatures, so that Ethereum wallets can sign orderbook actions directly.
+//! A wallet is registered as a session key using its 20 bytes address.
+
+use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
+use sha3::{Digest, Keccak256};
+
+use crate::{
+    model::{DustSweep, Order, OrderSide, OrderType},
+    utils::SignedAction,
+};
+
+pub const EIP712_DOMAIN_NAME: &str = "Hyliquid";
+pub const EIP712_DOMAIN_VERSION: &str = "1";
+
+/// Length of an Ethereum address registered as a session key
+pub const ETH_ADDRESS_LEN: usize = 20;
+
+const EIP712_DOMAIN_TYPE: &str = "EIP712Domain(string name,string version)";
+const CREATE_ORDER_TYPE: &str = "CreateOrder(string user,uint32 nonce,string orderId,string side,string orderType,uint64 price,string base,string quote,uint64 quantity)";
+// Orders of an atomic batch are encoded as an array of structs, whose type is appended
+// to the primary type as required by EIP-712
+const ORDER_PARAMS_TYPE: &str = "OrderParams(string orderId,string side,string orderType,uint64 price,string base,string quote,uint64 quantity)";
+const CREATE_ORDERS_TYPE: &str = "CreateOrders(string user,uint32 nonce,OrderParams[] orders)OrderParams(string orderId,string side,string orderType,uint64 price,string base,string quote,uint64 quantity)";
+const CANCEL_ORDER_TYPE: &str = "CancelOrder(string user,uint32 nonce,string orderId)";
+const WITHDRAW_TYPE: &str = "Withdraw(string user,uint32 nonce,string symbol,uint64 amount)";
+const TRANSFER_TYPE: &str =
+    "Transfer(string user,uint32 nonce,string to,string symbol,uint64 amount)";
+const DUST_SWEEP_TYPE: &str = "DustSweep(string symbol,string orderId)";
+const SWEEP_DUST_TYPE: &str = "SweepDust(string user,uint32 nonce,string quote,DustSweep[] sweeps)DustSweep(string symbol,string orderId)";
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+fn encode_string(value: &str) -> [u8; 32] {
+    keccak256(value.as_bytes())
+}
+
+fn encode_uint(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+fn hash_struct(type_string: &str, fields: &[[u8; 32]]) -> [u8; 32] {
+    let mut encoded = Vec::with_capacity(32 * (fields.len() + 1));
+    encoded.extend_from_slice(&keccak256(type_string.as_bytes()));
+    for field in fields {
+        encoded.extend_from_slice(field);
+    }
+    keccak256(&encoded)
+}
+
+fn order_side_name(side: &OrderSide) -> &'static str {
+    match side {
+        OrderSide::Bid => "bid",
+        OrderSide::Ask => "ask",
+    }
+}
+
+fn order_type_name(order_type: &OrderType) -> &'static str {
+    match order_type {
+        OrderType::Market => "market",
+        OrderType::Limit => "limit",
+        OrderType::Stop => "stop",
+        OrderType::StopLimit => "stop_limit",
+        OrderType::StopMarket => "stop_market",
+    }
+}
+
+fn order_params_hash(order: &Order) -> [u8; 32] {
+    hash_struct(
+        ORDER_PARAMS_TYPE,
+        &[
+            encode_string(&order.order_id),
+            encode_string(order_side_name(&order.order_side)),
+            encode_string(order_type_name(&order.order_type)),
+            encode_uint(order.price.unwrap_or_default()),
+            encode_string(&order.pair.0),
+            encode_string(&order.pair.1),
+            encode_uint(order.quantity),
+        ],
+    )
+}
+
+fn dust_sweep_hash(sweep: &DustSweep) -> [u8; 32] {
+    hash_struct(
+        DUST_SWEEP_TYPE,
+        &[encode_string(&sweep.symbol), encode_string(&sweep.order_id)],
+    )
+}
+
+pub fn domain_separator() -> [u8; 32] {
+    hash_struct(
+        EIP712_DOMAIN_TYPE,
+        &[
+            encode_string(EIP712_DOMAIN_NAME),
+            encode_string(EIP712_DOMAIN_VERSION),
+        ],
+    )
+}
+
+/// Hash of the typed struct of an action, as defined by `hashStruct` in EIP-712
+pub fn struct_hash(action: &SignedAction) -> [u8; 32] {
+    match action {
+        SignedAction::CreateOrder { user, nonce, order } => hash_struct(
+            CREATE_ORDER_TYPE,
+            &[
+                encode_string(user),
+                encode_uint(*nonce as u64),
+                encode_string(&order.order_id),
+                encode_string(order_side_name(&order.order_side)),
+                encode_string(order_type_name(&order.order_type)),
+                // Market orders are signed with a zero price
+                encode_uint(order.price.unwrap_or_default()),
+                encode_string(&order.pair.0),
+                encode_string(&order.pair.1),
+                encode_uint(order.quantity),
+            ],
+        ),
+        SignedAction::CreateOrders {
+            user,
+            nonce,
+            orders,
+        } => {
+            // Arrays are encoded as the hash of the concatenation of their elements' struct hashes
+            let encoded_orders: Vec<u8> = orders.iter().flat_map(order_params_hash).collect();
+            hash_struct(
+                CREATE_ORDERS_TYPE,
+                &[
+                    encode_string(user),
+                    encode_uint(*nonce as u64),
+                    keccak256(&encoded_orders),
+                ],
+            )
+        }
+        SignedAction::CancelOrder {
+            user,
+            nonce,
+            order_id,
+        } => hash_struct(
+            CANCEL_ORDER_TYPE,
+            &[
+                encode_string(user),
+                encode_uint(*nonce as u64),
+                encode_string(order_id),
+            ],
+        ),
+        SignedAction::Withdraw {
+            user,
+            nonce,
+            symbol,
+            amount,
+        } => hash_struct(
+            WITHDRAW_TYPE,
+            &[
+                encode_string(user),
+                encode_uint(*nonce as u64),
+                encode_string(symbol),
+                encode_uint(*amount),
+            ],
+        ),
+        SignedAction::Transfer {
+            user,
+            nonce,
+            to,
+            symbol,
+            amount,
+        } => hash_struct(
+            TRANSFER_TYPE,
+            &[
+                encode_string(user),
+                encode_uint(*nonce as u64),
+                encode_string(to),
+                encode_string(symbol),
+                encode_uint(*amount),
+            ],
+        ),
+        SignedAction::SweepDust {
+            user,
+            nonce,
+            quote,
+            sweeps,
+        } => {
+            let encoded_sweeps: Vec<u8> = sweeps.iter().flat_map(dust_sweep_hash).collect();
+            hash_struct(
+                SWEEP_DUST_TYPE,
+                &[
+                    encode_string(user),
+                    encode_uint(*nonce as u64),
+                    encode_string(quote),
+                    keccak256(&encoded_sweeps),
+                ],
+            )
+        }
+    }
+}
+
+/// Digest signed by the wallet: `keccak256("\x19\x01" ‖ domainSeparator ‖ hashStruct(action))`
+pub fn signing_hash(action: &SignedAction) -> [u8; 32] {
+    let mut encoded = Vec::with_capacity(66);
+    encoded.extend_from_slice(b"\x19\x01");
+    encoded.extend_from_slice(&domain_separator());
+    encoded.extend_from_slice(&struct_hash(action));
+    keccak256(&encoded)
+}
+
+/// Recovers the Ethereum address that produced a 65 bytes `r ‖ s ‖ v` signature of `hash`
+pub fn recover_address(hash: &[u8; 32], signature: &[u8]) -> Result<[u8; ETH_ADDRESS_LEN], String> {
+    if signature.len() != 65 {
+        return Err(format!(
+            "EIP-712 signatures must be 65 bytes long, got {}",
+            signature.len()
+        ));
+    }
+
+    let parsed_signature = Signature::from_slice(&signature[..64])
+        .map_err(|e| format!("Invalid EIP-712 signature: {e}"))?;
+    // Wallets use 27/28 for v, while the recovery id is 0/1
+    let v = match signature[64] {
+        27 | 28 => signature[64] - 27,
+        v => v,
+    };
+    let recovery_id =
+        RecoveryId::from_byte(v).ok_or_else(|| format!("Invalid EIP-712 recovery id: {v}"))?;
+
+    let verifying_key = VerifyingKey::recover_from_prehash(hash, &parsed_signature, recovery_id)
+        .map_err(|e| format!("Could not recover EIP-712 signer: {e}"))?;
+
+    // The address is the last 20 bytes of the keccak hash of the uncompressed public key
+    let public_key = verifying_key.to_encoded_point(false);
+    let public_key_hash = keccak256(&public_key.as_bytes()[1..]);
+    let mut address = [0u8; ETH_ADDRESS_LEN];
+    address.copy_from_slice(&public_key_hash[32 - ETH_ADDRESS_LEN..]);
+    Ok(address)
+}
@@ -1,10 +1,16 @@
+pub mod eip712;
 pub mod model;
 pub mod order_manager;
+pub mod test_vectors;
 pub mod transaction;
 pub mod utils;
+pub mod webauthn;
 pub mod zk;
 
 pub const ORDERBOOK_ACCOUNT_IDENTITY: &str = "orderbook@orderbook";
+/// Account of the insurance fund, collecting liquidation penalties and absorbing the losses of
+/// bankrupt accounts. It has no session key, so that nobody can sign for it.
+pub const INSURANCE_FUND_IDENTITY: &str = "insurance@orderbook";
 
 pub mod test {
     mod orderbook_tests;
@@ -1,32 +1,58 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use hyli_smt_token::SmtTokenAction;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use crate::{
     order_manager::OrderManager,
-    transaction::{OrderbookAction, PermissionedOrderbookAction},
+    transaction::{OnboardedUser, OrderbookAction},
+    utils,
     zk::smt::GetKey,
-    ORDERBOOK_ACCOUNT_IDENTITY,
+    INSURANCE_FUND_IDENTITY, ORDERBOOK_ACCOUNT_IDENTITY,
 };
 use sdk::{BlockHeight, ContractName, StructuredBlob};
 
 use crate::zk::H256;
 
+/// Maximum number of orders placed atomically by a single action
+pub const MAX_ATOMIC_ORDERS: usize = 8;
+
+/// Maximum number of identities registered by a single onboarding action
+pub const MAX_ONBOARDED_USERS: usize = 256;
+
 #[derive(Debug, Default, Clone, Serialize, BorshDeserialize, BorshSerialize)]
 pub struct ExecuteState {
     pub assets_info: HashMap<Symbol, AssetInfo>, // symbol -> (decimals, precision)
     pub users_info: HashMap<String, UserInfo>,
     pub balances: HashMap<Symbol, HashMap<H256, Balance>>,
     pub order_manager: OrderManager,
+    /// Status of every created pair, set by the operator
+    pub pairs_status: HashMap<Pair, MarketStatus>,
+    /// Circuit breakers of the pairs that have one, with the recent prices they track
+    pub circuit_breakers: HashMap<Pair, CircuitBreakerState>,
+    /// Pairs traded as perpetual futures rather than spot
+    pub perp_markets: HashMap<Pair, PerpMarket>,
+    /// Withdrawals to other networks than Hyli are rejected while the operator pauses the bridge
+    pub bridge_paused: bool,
 }
 
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 #[derive(
     Default, BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq,
 )]
 pub struct AssetInfo {
     pub scale: u64,
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
     pub contract_name: ContractName,
+    /// Maximum amount each user can withdraw per window, set by the operator
+    #[serde(default)]
+    pub withdraw_limit: Option<WithdrawLimit>,
+    /// Value of the asset in the margin accounts, when the operator accepts it as collateral
+    #[serde(default)]
+    pub collateral: Option<Collateral>,
+    /// Delay of the large withdrawals of the asset, set by the operator
+    #[serde(default)]
+    pub withdraw_delay: Option<WithdrawDelay>,
 }
 
 impl AssetInfo {
@@ -34,10 +60,57 @@ impl AssetInfo {
         AssetInfo {
             scale,
             contract_name,
+            withdraw_limit: None,
+            collateral: None,
+            withdraw_delay: None,
         }
     }
 }
 
+/// Acceptance of an asset as collateral of the margin accounts in a quote asset
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CollateralConfig {
+    /// Quote asset the collateral is valued in, through the index price of the (asset, quote) pair
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
+    pub quote: Symbol,
+    /// Share of the value of the asset counted in the account equity, in basis points
+    pub weight_bps: u64,
+}
+
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Collateral {
+    pub config: CollateralConfig,
+    /// Latest index price of the (asset, quote) pair. The asset is not valued until it is fed.
+    pub price: u64,
+}
+
+/// Cap on the amount of an asset a user can withdraw during a window of `window_blocks` blocks.
+/// Windows are aligned on multiples of `window_blocks`.
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawLimit {
+    pub max_amount: u64,
+    pub window_blocks: u64,
+}
+
+impl WithdrawLimit {
+    pub fn window_start(&self, block_height: u64) -> u64 {
+        block_height - block_height % self.window_blocks
+    }
+}
+
+/// Withdrawals of more than `threshold` of an asset are queued for `delay_blocks` blocks before
+/// they are paid out, and wait for the approval of the operator when `requires_approval` is set.
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawDelay {
+    pub threshold: u64,
+    pub delay_blocks: u64,
+    pub requires_approval: bool,
+}
+
 #[derive(
     BorshSerialize, BorshDeserialize, Serialize, Deserialize, Default, Debug, Clone, PartialEq,
 )]
@@ -46,11 +119,319 @@ pub struct PairInfo {
     pub quote: AssetInfo,
 }
 
+/// Lifecycle of a pair. Orders are only placed on active pairs, while resting orders can
+/// always be cancelled. A delisted pair cannot be resumed.
+#[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
+#[cfg_attr(
+    feature = "sqlx",
+    sqlx(type_name = "market_status", rename_all = "lowercase")
+)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Serialize,
+    Deserialize,
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketStatus {
+    #[default]
+    Active,
+    Halted,
+    Delisted,
+}
+
+impl MarketStatus {
+    pub fn can_transition_to(&self, status: MarketStatus) -> bool {
+        matches!(
+            (self, status),
+            (MarketStatus::Active, MarketStatus::Halted)
+                | (MarketStatus::Halted, MarketStatus::Active)
+                | (
+                    MarketStatus::Active | MarketStatus::Halted,
+                    MarketStatus::Delisted
+                )
+        )
+    }
+}
+
+/// Halts the orders that would trade on a pair for `halt_blocks` blocks, once its price moved
+/// by more than `max_move_bps` from the first trade of a window of `window_blocks` blocks.
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CircuitBreaker {
+    pub max_move_bps: u64,
+    pub window_blocks: u64,
+    pub halt_blocks: u64,
+}
+
+/// Circuit breaker of a pair, with the prices it tracks. A window starts on the first trade
+/// after the previous one ended, and a new one starts when the breaker trips.
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CircuitBreakerState {
+    pub config: CircuitBreaker,
+    /// Price of the first trade of the current window
+    pub reference_price: Option<u64>,
+    pub window_start: u64,
+    /// Orders that would trade are rejected before this block
+    pub halted_until: u64,
+}
+
+impl CircuitBreakerState {
+    pub fn new(config: CircuitBreaker) -> Self {
+        CircuitBreakerState {
+            config,
+            reference_price: None,
+            window_start: 0,
+            halted_until: 0,
+        }
+    }
+
+    pub fn is_halted(&self, block_height: u64) -> bool {
+        block_height < self.halted_until
+    }
+
+    /// Event recording a trade at `price`: the start of a new window, or the breaker tripping
+    /// when the price moved too much within the current one
+    pub fn track_price(
+        &self,
+        pair: &Pair,
+        price: u64,
+        block_height: u64,
+    ) -> Option<OrderbookEvent> {
+        let window_end = self.window_start.saturating_add(self.config.window_blocks);
+        match self.reference_price {
+            Some(reference) if reference > 0 && block_height < window_end => {
+                let moved_bps = reference.abs_diff(price) as u128 * 10_000 / reference as u128;
+                (moved_bps > self.config.max_move_bps as u128).then(|| {
+                    OrderbookEvent::CircuitBreakerTripped {
+                        pair: pair.clone(),
+                        reference_price: reference,
+                        price,
+                        block_height,
+                        halted_until: block_height.saturating_add(self.config.halt_blocks),
+                    }
+                })
+            }
+            _ => Some(OrderbookEvent::PriceWindowStarted {
+                pair: pair.clone(),
+                reference_price: price,
+                block_height,
+            }),
+        }
+    }
+}
+
+/// Margin requirements of a perpetual futures market, in basis points of the notional
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PerpConfig {
+    /// Margin locked when opening a position, or by a resting order
+    pub initial_margin_bps: u64,
+    /// Margin below which a position can be liquidated
+    pub maintenance_margin_bps: u64,
+    /// Penalty charged on the notional of a liquidated position, paid to the insurance fund
+    #[serde(default)]
+    pub liquidation_penalty_bps: u64,
+}
+
+/// Perpetual futures market on a pair whose base asset is never delivered: fills open and close
+/// positions, margined and settled in the quote asset.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PerpMarket {
+    pub config: PerpConfig,
+    /// Cumulative funding paid by a long position of one base unit, in quote amount.
+    /// Shorts receive what longs pay, and pay when it decreases.
+    pub funding_index: i64,
+    /// Latest index price of the pair, positions are valued at. Zero until it is fed.
+    pub index_price: u64,
+}
+
+impl PerpMarket {
+    pub fn new(config: PerpConfig) -> Self {
+        PerpMarket {
+            config,
+            funding_index: 0,
+            index_price: 0,
+        }
+    }
+
+    /// Initial margin of `quantity` at `price`, rounded up
+    pub fn margin(&self, quantity: u64, price: u64, base_scale: u64) -> u64 {
+        let notional = quantity as u128 * price as u128 / base_scale as u128;
+        let margin = (notional * self.config.initial_margin_bps as u128).div_ceil(10_000);
+        u64::try_from(margin).unwrap_or(u64::MAX)
+    }
+
+    /// Maintenance margin of `quantity` at `price`, rounded up
+    pub fn maintenance_margin(&self, quantity: u64, price: u64, base_scale: u64) -> u64 {
+        let notional = quantity as u128 * price as u128 / base_scale as u128;
+        let margin = (notional * self.config.maintenance_margin_bps as u128).div_ceil(10_000);
+        u64::try_from(margin).unwrap_or(u64::MAX)
+    }
+
+    /// Liquidation penalty of `quantity` at `price`, rounded down
+    pub fn liquidation_penalty(&self, quantity: u64, price: u64, base_scale: u64) -> u64 {
+        let notional = quantity as u128 * price as u128 / base_scale as u128;
+        let penalty = notional * self.config.liquidation_penalty_bps as u128 / 10_000;
+        u64::try_from(penalty).unwrap_or(u64::MAX)
+    }
+
+    /// Funding owed by a position since it was last settled, negative when it is owed funding
+    pub fn pending_funding(&self, position: &Position, base_scale: u64) -> i128 {
+        (self.funding_index as i128 - position.funding_index as i128) * position.size as i128
+            / base_scale as i128
+    }
+
+    /// Applies a fill of `quantity` at `price` to a position. Returns the updated position and
+    /// the change of its owner's quote balance: the pending funding, the margin moved out of or
+    /// into the position, and the pnl realized on the closed size.
+    pub fn apply_fill(
+        &self,
+        mut position: Position,
+        side: &OrderSide,
+        quantity: u64,
+        price: u64,
+        base_scale: u64,
+    ) -> Result<(Position, i128), String> {
+        let mut change = -self.pending_funding(&position, base_scale);
+        position.funding_index = self.funding_index;
+
+        let direction: i128 = match side {
+            OrderSide::Bid => 1,
+            OrderSide::Ask => -1,
+        };
+        let size = position.size as i128;
+        let closed = if size.signum() == -direction {
+            size.abs().min(quantity as i128)
+        } else {
+            0
+        };
+        if closed > 0 {
+            let released = position.margin as i128 * closed / size.abs();
+            let pnl = (price as i128 - position.entry_price as i128) * closed * size.signum()
+                / base_scale as i128;
+            change += released + pnl;
+            position.margin -= released as u64;
+            position.size = (size + direction * closed) as i64;
+        }
+
+        let opened = quantity as i128 - closed;
+        if opened > 0 {
+            let size = position.size as i128;
+            let new_size = size + direction * opened;
+            position.entry_price = ((size.abs() * position.entry_price as i128
+                + opened * price as i128)
+                / new_size.abs()) as u64;
+            let margin = self.margin(opened as u64, price, base_scale);
+            position.margin = position
+                .margin
+                .checked_add(margin)
+                .ok_or("Position margin overflow")?;
+            position.size = i64::try_from(new_size).map_err(|_| "Position size overflow")?;
+            change -= margin as i128;
+        }
+
+        if position.size == 0 {
+            position.entry_price = 0;
+        }
+        Ok((position, change))
+    }
+}
+
+/// Position of a user on a perpetual futures market
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct Position {
+    #[cfg_attr(feature = "utoipa", schema(value_type = (String, String)))]
+    pub pair: Pair,
+    /// Base quantity, positive when long and negative when short
+    pub size: i64,
+    /// Average price the current size was opened at
+    pub entry_price: u64,
+    /// Quote amount backing the position
+    pub margin: u64,
+    /// Funding index of the market when the funding of the position was last settled
+    pub funding_index: i64,
+}
+
+impl Position {
+    pub fn new(pair: Pair, funding_index: i64) -> Self {
+        Position {
+            pair,
+            size: 0,
+            entry_price: 0,
+            margin: 0,
+            funding_index,
+        }
+    }
+}
+
+/// Quote amount a user borrowed against its margin account, when its free balance did not
+/// cover the margin, pnl or funding it had to pay
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct Debt {
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
+    pub symbol: Symbol,
+    pub amount: u64,
+}
+
+/// Cross-margin account of a user in a quote asset: every position settled in the quote and
+/// every collateral valued in it back each other
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MarginAccount {
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
+    pub quote: Symbol,
+    /// Free balance, position margins, unrealized pnl net of pending funding, and weighted
+    /// value of the collaterals, minus the debt
+    pub equity: i64,
+    /// Equity required to open the current positions
+    pub initial_margin: u64,
+    /// Equity under which the account is called
+    pub maintenance_margin: u64,
+}
+
 #[cfg_attr(feature = "sqlx", derive(sqlx::Type))]
 #[cfg_attr(
     feature = "sqlx",
     sqlx(type_name = "order_side", rename_all = "lowercase")
 )]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 #[derive(
     Debug,
     Serialize,
@@ -75,6 +456,7 @@ pub enum OrderSide {
     feature = "sqlx",
     sqlx(type_name = "order_type", rename_all = "lowercase")
 )]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 #[derive(
     Debug,
     Serialize,
@@ -97,6 +479,7 @@ pub enum OrderType {
     StopMarket,
 }
 
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 #[derive(
     Debug,
     Serialize,
@@ -111,10 +494,12 @@ pub enum OrderType {
     Hash,
 )]
 pub struct Order {
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
     pub order_id: OrderId,
     pub order_type: OrderType,
     pub order_side: OrderSide,
     pub price: Option<u64>,
+    #[cfg_attr(feature = "utoipa", schema(value_type = (String, String)))]
     pub pair: Pair,
     pub quantity: u64,
 }
@@ -125,6 +510,16 @@ impl std::fmt::Display for Order {
     }
 }
 
+/// Asset sold for the target quote asset by a dust sweep, with the id of its market order
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DustSweep {
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
+    pub symbol: Symbol,
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
+    pub order_id: OrderId,
+}
+
 /// Defines how the order manager should retain or clean zeroed orders and how events should be
 /// reflected in auxiliary structures (e.g. SMT updates).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -152,7 +547,20 @@ pub type OrderId = String;
 pub type Symbol = String;
 pub type Pair = (Symbol, Symbol);
 
-#[derive(Debug, Clone, Serialize, Deserialize, BorshDeserialize, BorshSerialize, PartialEq, Eq)]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    BorshDeserialize,
+    BorshSerialize,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
 pub struct WithdrawDestination {
     pub network: String,
     pub address: String,
@@ -193,20 +601,195 @@ pub enum OrderbookEvent {
         salt: Vec<u8>,
         nonce: u32,
         session_keys: Vec<Vec<u8>>,
+        session_key_scopes: Vec<SessionKeyScope>,
     },
     NonceIncremented {
         user: String,
         nonce: u32,
     },
+    FeeTierUpdated {
+        user: String,
+        fee_tier: FeeTier,
+    },
+    WithdrawLimitUpdated {
+        symbol: Symbol,
+        limit: Option<WithdrawLimit>,
+    },
+    WithdrawalRecorded {
+        user: String,
+        window: WithdrawalWindow,
+    },
+    WithdrawDelayUpdated {
+        symbol: Symbol,
+        delay: Option<WithdrawDelay>,
+    },
+    /// Large withdrawal deducted from the balance of the user, paid out once released
+    WithdrawalQueued {
+        user: String,
+        withdrawal: PendingWithdrawal,
+    },
+    /// Queued withdrawal approved by the operator, or rejected and refunded by the balance events
+    WithdrawalReviewed {
+        user: String,
+        withdrawal_id: u32,
+        approved: bool,
+    },
+    /// Queued withdrawal leaving the queue to be paid out
+    WithdrawalReleased {
+        user: String,
+        withdrawal: PendingWithdrawal,
+    },
+    /// Withdrawals to other networks than Hyli paused or resumed by the operator
+    BridgePauseUpdated {
+        paused: bool,
+    },
+    PairStatusUpdated {
+        pair: Pair,
+        status: MarketStatus,
+    },
+    CircuitBreakerUpdated {
+        pair: Pair,
+        config: Option<CircuitBreaker>,
+    },
+    PriceWindowStarted {
+        pair: Pair,
+        reference_price: u64,
+        block_height: u64,
+    },
+    CircuitBreakerTripped {
+        pair: Pair,
+        reference_price: u64,
+        price: u64,
+        block_height: u64,
+        halted_until: u64,
+    },
+    PerpMarketCreated {
+        pair: Pair,
+        config: PerpConfig,
+    },
+    FundingRateAccrued {
+        pair: Pair,
+        funding_index: i64,
+    },
+    PositionUpdated {
+        user: String,
+        position: Position,
+    },
+    /// Funding paid by a user on a perp market, negative when received. The payment itself is
+    /// applied by the `BalanceUpdated` and `PositionUpdated` events of the settlement.
+    FundingPaid {
+        user: String,
+        pair: Pair,
+        amount: i64,
+    },
+    CollateralUpdated {
+        symbol: Symbol,
+        collateral: Option<CollateralConfig>,
+    },
+    /// Index price of a perp market, or of a pair valuing a collateral
+    IndexPriceUpdated {
+        pair: Pair,
+        price: u64,
+    },
+    DebtUpdated {
+        user: String,
+        debt: Debt,
+    },
+    /// Margin account whose equity fell under its maintenance margin
+    MarginCalled {
+        user: String,
+        account: MarginAccount,
+    },
+    /// Position of an under-margined account force-closed on the book, with the account before
+    /// the liquidation. The fills are applied by the order and position events that follow.
+    Liquidation {
+        user: String,
+        pair: Pair,
+        size: i64,
+        account: MarginAccount,
+    },
+    /// Insurance fund movement on the liquidation of `user`: a penalty collected when positive,
+    /// a loss of the bankrupt account absorbed when negative. The balances are updated by the
+    /// balance and debt events.
+    InsuranceFundUpdated {
+        user: String,
+        symbol: Symbol,
+        change: i64,
+    },
+    /// Dust balances of a user sold for `quote`, crediting `proceeds` net of fees. The balances
+    /// are updated by the order events of the sweep.
+    DustSwept {
+        user: String,
+        quote: Symbol,
+        symbols: Vec<Symbol>,
+        proceeds: u64,
+    },
+    /// Balance moved between two accounts of the orderbook. The balances are updated by the
+    /// balance and debt events.
+    Transferred {
+        from: String,
+        to: String,
+        symbol: Symbol,
+        amount: u64,
+    },
+}
+
+impl OrderbookEvent {
+    /// Whether the event is kept out of the contract outputs, for privacy
+    pub fn is_private(&self) -> bool {
+        matches!(
+            self,
+            OrderbookEvent::BalanceUpdated { .. }
+                | OrderbookEvent::SessionKeyAdded { .. }
+                | OrderbookEvent::NonceIncremented { .. }
+                | OrderbookEvent::FeeTierUpdated { .. }
+                | OrderbookEvent::WithdrawalRecorded { .. }
+                | OrderbookEvent::WithdrawalQueued { .. }
+                | OrderbookEvent::WithdrawalReviewed { .. }
+                | OrderbookEvent::WithdrawalReleased { .. }
+                | OrderbookEvent::PositionUpdated { .. }
+                | OrderbookEvent::FundingPaid { .. }
+                | OrderbookEvent::DebtUpdated { .. }
+                | OrderbookEvent::MarginCalled { .. }
+                | OrderbookEvent::Liquidation { .. }
+                | OrderbookEvent::InsuranceFundUpdated { .. }
+                | OrderbookEvent::Transferred { .. }
+                | OrderbookEvent::DustSwept { .. }
+        )
+    }
 }
 
 impl std::fmt::Display for OrderbookEvent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             OrderbookEvent::BalanceUpdated { user, symbol, amount } => write!(f, "Balance updated for user {user} and symbol {symbol} to {amount}"),
-            OrderbookEvent::SessionKeyAdded { user, salt:  _, nonce, session_keys: _ } => write!(f, "Session key added for user {user} with nonce {nonce}"),
+            OrderbookEvent::SessionKeyAdded { user, nonce, .. } => write!(f, "Session key added for user {user} with nonce {nonce}"),
             OrderbookEvent::NonceIncremented { user, nonce } => write!(f, "Nonce incremented for user {user} to {nonce}"),
+            OrderbookEvent::FeeTierUpdated { user, fee_tier } => write!(f, "Fee tier updated for user {user} to {fee_tier:?}"),
+            OrderbookEvent::WithdrawLimitUpdated { symbol, limit } => write!(f, "Withdraw limit updated for symbol {symbol} to {limit:?}"),
+            OrderbookEvent::WithdrawalRecorded { user, window } => write!(f, "Withdrawal recorded for user {user} in window {window:?}"),
+            OrderbookEvent::WithdrawDelayUpdated { symbol, delay } => write!(f, "Withdraw delay updated for symbol {symbol} to {delay:?}"),
+            OrderbookEvent::WithdrawalQueued { user, withdrawal } => write!(f, "Withdrawal queued for user {user}: {withdrawal:?}"),
+            OrderbookEvent::WithdrawalReviewed { user, withdrawal_id, approved } => write!(f, "Withdrawal {withdrawal_id} of user {user} reviewed, approved: {approved}"),
+            OrderbookEvent::WithdrawalReleased { user, withdrawal } => write!(f, "Withdrawal released for user {user}: {withdrawal:?}"),
+            OrderbookEvent::BridgePauseUpdated { paused } => write!(f, "Bridge paused: {paused}"),
             OrderbookEvent::PairCreated { pair, info } => write!(f, "Pair created for {pair:?} with info {info:?}"),
+            OrderbookEvent::PairStatusUpdated { pair, status } => write!(f, "Pair status updated for {pair:?} to {status:?}"),
+            OrderbookEvent::CircuitBreakerUpdated { pair, config } => write!(f, "Circuit breaker updated for {pair:?} to {config:?}"),
+            OrderbookEvent::PriceWindowStarted { pair, reference_price, block_height } => write!(f, "Price window started for {pair:?} at block {block_height} with reference price {reference_price}"),
+            OrderbookEvent::CircuitBreakerTripped { pair, reference_price, price, block_height, halted_until } => write!(f, "Circuit breaker tripped for {pair:?} at block {block_height} as price moved from {reference_price} to {price}, halted until block {halted_until}"),
+            OrderbookEvent::PerpMarketCreated { pair, config } => write!(f, "Perp market created for {pair:?} with config {config:?}"),
+            OrderbookEvent::FundingRateAccrued { pair, funding_index } => write!(f, "Funding accrued for {pair:?} to index {funding_index}"),
+            OrderbookEvent::PositionUpdated { user, position } => write!(f, "Position updated for user {user} to {position:?}"),
+            OrderbookEvent::FundingPaid { user, pair, amount } => write!(f, "Funding of {amount} paid by user {user} on {pair:?}"),
+            OrderbookEvent::CollateralUpdated { symbol, collateral } => write!(f, "Collateral updated for symbol {symbol} to {collateral:?}"),
+            OrderbookEvent::IndexPriceUpdated { pair, price } => write!(f, "Index price updated for {pair:?} to {price}"),
+            OrderbookEvent::DebtUpdated { user, debt } => write!(f, "Debt updated for user {user} to {debt:?}"),
+            OrderbookEvent::MarginCalled { user, account } => write!(f, "Margin called for user {user} with account {account:?}"),
+            OrderbookEvent::Liquidation { user, pair, size, account } => write!(f, "Position of {size} of user {user} on {pair:?} liquidated with account {account:?}"),
+            OrderbookEvent::InsuranceFundUpdated { user, symbol, change } => write!(f, "Insurance fund updated by {change} {symbol} on the liquidation of user {user}"),
+            OrderbookEvent::Transferred { from, to, symbol, amount } => write!(f, "Transfer of {amount} {symbol} from user {from} to user {to}"),
+            OrderbookEvent::DustSwept { user, quote, symbols, proceeds } => write!(f, "Dust of {symbols:?} of user {user} swept for {proceeds} {quote}"),
             OrderbookEvent::OrderCreated { order } => write!(f, "Order created for {order}"),
             OrderbookEvent::OrderCancelled { order_id, pair } => write!(f, "Order cancelled for {order_id} and pair {pair:?}"),
             OrderbookEvent::OrderExecuted { order_id, taker_order_id, pair } => write!(f, "Order executed for {order_id} and taker order {taker_order_id} and pair {pair:?}"),
@@ -272,19 +855,56 @@ impl ExecuteState {
         &self,
         user_info: UserInfo,
         pubkey: &Vec<u8>,
+        permissions: SessionKeyPermissions,
+        pair: Option<Pair>,
     ) -> Result<Vec<OrderbookEvent>, String> {
         if user_info.session_keys.contains(pubkey) {
             return Err("Session key already exists".to_string());
         }
+        if user_info.nonce == 0 {
+            // First session key of the user, registering its identity
+            if user_info.sub_account {
+                utils::validate_new_sub_account(&user_info.user)?;
+            } else {
+                utils::validate_new_identity(&user_info.user)?;
+            }
+        }
+        if permissions.is_empty() {
+            return Err("Session key must be granted at least one permission".to_string());
+        }
+        match utils::SessionKeyScheme::of(pubkey) {
+            utils::SessionKeyScheme::Ed25519 if !cfg!(feature = "ed25519") => {
+                return Err("Ed25519 session keys are not supported by this orderbook".to_string());
+            }
+            utils::SessionKeyScheme::Passkey => crate::webauthn::validate_passkey(pubkey)?,
+            _ => {}
+        }
+        if let Some(pair) = &pair {
+            if !self.assets_info.contains_key(&pair.0) || !self.assets_info.contains_key(&pair.1) {
+                return Err(format!(
+                    "Cannot restrict session key to unknown pair {}/{}",
+                    pair.0, pair.1
+                ));
+            }
+        }
 
         let mut updated_user_info = user_info.clone();
         updated_user_info.session_keys.push(pubkey.clone());
 
+        if permissions != SessionKeyPermissions::ALL || pair.is_some() {
+            updated_user_info.session_key_scopes.push(SessionKeyScope {
+                public_key: pubkey.clone(),
+                permissions,
+                pair,
+            });
+        }
+
         let mut events = vec![OrderbookEvent::SessionKeyAdded {
             user: updated_user_info.user.to_string(),
             salt: updated_user_info.salt.clone(),
             nonce: updated_user_info.nonce,
             session_keys: updated_user_info.session_keys.clone(),
+            session_key_scopes: updated_user_info.session_key_scopes.clone(),
         }];
 
         if updated_user_info.nonce == 0 {
@@ -294,74 +914,1185 @@ impl ExecuteState {
         Ok(events)
     }
 
+    /// Assigns the fee tiers computed offchain by the operator from users' rolling volume
     #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
-    pub fn deposit(
+    pub fn update_fee_tiers(
         &self,
-        symbol: &str,
-        amount: u64,
-        user_info: &UserInfo,
+        operator: &UserInfo,
+        updates: &[(String, FeeTier)],
     ) -> Result<Vec<OrderbookEvent>, String> {
-        // Compute the new balance
-        let _ = self.get_user_info(&user_info.user)?; // Ensure user exists
-        let balance = self.get_balance(user_info, symbol);
-        let new_balance = Balance(balance.0.checked_add(amount).ok_or("Balance overflow")?);
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can update fee tiers, got {}",
+                operator.user
+            ));
+        }
 
-        Ok(vec![OrderbookEvent::BalanceUpdated {
-            user: user_info.user.clone(),
-            symbol: symbol.to_string(),
-            amount: new_balance.0,
-        }])
+        let mut events = Vec::with_capacity(updates.len() + 1);
+        for (user, fee_tier) in updates {
+            if fee_tier.maker_fee_bps > MAX_FEE_BPS || fee_tier.taker_fee_bps > MAX_FEE_BPS {
+                return Err(format!(
+                    "Fee tier {} for user {user} exceeds the maximum fee of {MAX_FEE_BPS} bps",
+                    fee_tier.tier
+                ));
+            }
+            // Ensure user exists
+            let _ = self.get_user_info(user)?;
+            events.push(OrderbookEvent::FeeTierUpdated {
+                user: user.clone(),
+                fee_tier: fee_tier.clone(),
+            });
+        }
+
+        events.push(Self::nonce_increment_event(operator)?);
+
+        Ok(events)
     }
 
-    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
-    pub fn withdraw(
+    /// Registers new identities on behalf of the operator, each with a pre-approved session key,
+    /// so that a client's accounts are migrated in a single action
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self, users)))]
+    pub fn onboard_users(
         &self,
-        symbol: &str,
-        amount: &u64,
-        user_info: &UserInfo,
+        operator: &UserInfo,
+        users: &[OnboardedUser],
     ) -> Result<Vec<OrderbookEvent>, String> {
-        let balance = self.get_balance(user_info, symbol);
-
-        if balance.0 < *amount {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
             return Err(format!(
-                "Could not withdraw: Insufficient balance: user {} has {balance:?} {symbol} symbols, trying to withdraw {amount}", user_info.user
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can onboard users, got {}",
+                operator.user
+            ));
+        }
+        if users.is_empty() {
+            return Err("No users to onboard".to_string());
+        }
+        if users.len() > MAX_ONBOARDED_USERS {
+            return Err(format!(
+                "Cannot onboard more than {MAX_ONBOARDED_USERS} users at once, got {}",
+                users.len()
             ));
         }
 
-        let new_total = balance.0 - *amount;
+        let mut onboarded = HashSet::new();
+        let mut events = Vec::with_capacity(2 * users.len() + 1);
+        for user in users {
+            if !onboarded.insert(&user.user) {
+                return Err(format!("User {} is onboarded twice", user.user));
+            }
+            // Sub-accounts are keyed with the salt of their parent, so only their parent adds them
+            if utils::split_sub_account(&user.user).is_some() {
+                return Err(format!(
+                    "Sub-account {} cannot be onboarded, it is registered by its parent",
+                    user.user
+                ));
+            }
+            // Users not registered yet are only known with a zero nonce in the zkvm
+            if self
+                .users_info
+                .get(&user.user)
+                .is_some_and(|info| info.nonce > 0)
+            {
+                return Err(format!("User {} is already registered", user.user));
+            }
 
-        let mut events = vec![OrderbookEvent::BalanceUpdated {
-            user: user_info.user.clone(),
-            symbol: symbol.to_string(),
-            amount: new_total,
-        }];
+            let user_events = self
+                .add_session_key(
+                    UserInfo::new(user.user.clone(), user.salt.clone()),
+                    &user.public_key,
+                    user.permissions,
+                    user.pair.clone(),
+                )
+                .map_err(|e| format!("Cannot onboard {}: {e}", user.user))?;
+            events.extend(user_events);
+        }
 
-        events.push(Self::nonce_increment_event(user_info)?);
+        events.push(Self::nonce_increment_event(operator)?);
 
         Ok(events)
     }
 
+    /// Sets the withdraw limits of assets, `None` removing the limit of an asset
     #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
-    pub fn cancel_order(
+    pub fn update_withdraw_limits(
         &self,
-        order_id: OrderId,
-        user_info: &UserInfo,
+        operator: &UserInfo,
+        updates: &[(Symbol, Option<WithdrawLimit>)],
     ) -> Result<Vec<OrderbookEvent>, String> {
-        let order = self
-            .order_manager
-            .orders
-            .get(&order_id)
-            .ok_or(format!("Order {order_id} not found"))?
-            .clone();
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can update withdraw limits, got {}",
+                operator.user
+            ));
+        }
 
-        let required_symbol = match &order.order_side {
-            OrderSide::Bid => order.pair.1.clone(),
-            OrderSide::Ask => order.pair.0.clone(),
+        let mut events = Vec::with_capacity(updates.len() + 1);
+        for (symbol, limit) in updates {
+            if !self.assets_info.contains_key(symbol) {
+                return Err(format!("Symbol {symbol} is not registered"));
+            }
+            if limit.as_ref().is_some_and(|limit| limit.window_blocks == 0) {
+                return Err(format!(
+                    "Withdraw limit window of {symbol} must be at least one block"
+                ));
+            }
+            events.push(OrderbookEvent::WithdrawLimitUpdated {
+                symbol: symbol.clone(),
+                limit: limit.clone(),
+            });
+        }
+
+        events.push(Self::nonce_increment_event(operator)?);
+
+        Ok(events)
+    }
+
+    /// Sets the delay of the large withdrawals of assets, or removes it when `None`
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn update_withdraw_delays(
+        &self,
+        operator: &UserInfo,
+        updates: &[(Symbol, Option<WithdrawDelay>)],
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can update withdraw delays, got {}",
+                operator.user
+            ));
+        }
+
+        let mut events = Vec::with_capacity(updates.len() + 1);
+        for (symbol, delay) in updates {
+            if !self.assets_info.contains_key(symbol) {
+                return Err(format!("Symbol {symbol} is not registered"));
+            }
+            events.push(OrderbookEvent::WithdrawDelayUpdated {
+                symbol: symbol.clone(),
+                delay: delay.clone(),
+            });
+        }
+
+        events.push(Self::nonce_increment_event(operator)?);
+
+        Ok(events)
+    }
+
+    /// Accepts assets as collateral of the margin accounts, or stops accepting them when `None`
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn update_collaterals(
+        &self,
+        operator: &UserInfo,
+        updates: &[(Symbol, Option<CollateralConfig>)],
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can update collaterals, got {}",
+                operator.user
+            ));
+        }
+
+        let mut events = Vec::with_capacity(updates.len() + 1);
+        for (symbol, collateral) in updates {
+            if !self.assets_info.contains_key(symbol) {
+                return Err(format!("Symbol {symbol} is not registered"));
+            }
+            if let Some(config) = collateral {
+                if !self
+                    .pairs_status
+                    .contains_key(&(symbol.clone(), config.quote.clone()))
+                {
+                    return Err(format!(
+                        "Collateral {symbol} is valued through the {symbol}/{} pair, which does not exist",
+                        config.quote
+                    ));
+                }
+                if self.perp_markets.keys().any(|pair| &pair.0 == symbol) {
+                    return Err(format!(
+                        "Synthetic asset {symbol} of a perp market cannot be a collateral"
+                    ));
+                }
+                if config.weight_bps == 0 || config.weight_bps > 10_000 {
+                    return Err(format!(
+                        "Collateral weight of {symbol} must be between 1 and 10000 bps, got {}",
+                        config.weight_bps
+                    ));
+                }
+            }
+            events.push(OrderbookEvent::CollateralUpdated {
+                symbol: symbol.clone(),
+                collateral: collateral.clone(),
+            });
+        }
+
+        events.push(Self::nonce_increment_event(operator)?);
+
+        Ok(events)
+    }
+
+    /// Moves a pair to another status of its lifecycle. Resting orders must be cancelled
+    /// before a pair is delisted.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn update_pair_status(
+        &self,
+        operator: &UserInfo,
+        pair: &Pair,
+        status: MarketStatus,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can update pair status, got {}",
+                operator.user
+            ));
+        }
+
+        let current = self
+            .pairs_status
+            .get(pair)
+            .ok_or_else(|| format!("Pair {}/{} does not exist", pair.0, pair.1))?;
+        if !current.can_transition_to(status) {
+            return Err(format!(
+                "Pair {}/{} cannot go from {current:?} to {status:?}",
+                pair.0, pair.1
+            ));
+        }
+        if status == MarketStatus::Delisted {
+            let resting_orders = self
+                .order_manager
+                .orders
+                .values()
+                .filter(|order| &order.pair == pair)
+                .count();
+            if resting_orders > 0 {
+                return Err(format!(
+                    "Pair {}/{} still has {resting_orders} resting orders",
+                    pair.0, pair.1
+                ));
+            }
+        }
+
+        Ok(vec![
+            OrderbookEvent::PairStatusUpdated {
+                pair: pair.clone(),
+                status,
+            },
+            Self::nonce_increment_event(operator)?,
+        ])
+    }
+
+    /// Sets or removes the circuit breakers of pairs. Setting a breaker resets the prices it
+    /// tracks, and lifts a halt in progress.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn update_circuit_breakers(
+        &self,
+        operator: &UserInfo,
+        updates: &[(Pair, Option<CircuitBreaker>)],
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can update circuit breakers, got {}",
+                operator.user
+            ));
+        }
+
+        let mut events = Vec::with_capacity(updates.len() + 1);
+        for (pair, config) in updates {
+            if !self.pairs_status.contains_key(pair) {
+                return Err(format!("Pair {}/{} does not exist", pair.0, pair.1));
+            }
+            if let Some(config) = config {
+                if config.window_blocks == 0 || config.halt_blocks == 0 {
+                    return Err(format!(
+                        "Circuit breaker window and halt of {}/{} must be at least one block",
+                        pair.0, pair.1
+                    ));
+                }
+            }
+            events.push(OrderbookEvent::CircuitBreakerUpdated {
+                pair: pair.clone(),
+                config: config.clone(),
+            });
+        }
+
+        events.push(Self::nonce_increment_event(operator)?);
+
+        Ok(events)
+    }
+
+    /// Feeds index prices to the circuit breakers of pairs. Each index price is the reference
+    /// of a new window, unless it moved too much from the current reference, which trips the
+    /// breaker. Pairs without a breaker, or halted, are left untouched.
+    /// Index prices of perp markets and collaterals also value the margin accounts, which are
+    /// called when their equity falls under their maintenance margin.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn update_index_prices(
+        &self,
+        operator: &UserInfo,
+        prices: &[(Pair, u64)],
+        block_height: u64,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can update index prices, got {}",
+                operator.user
+            ));
+        }
+
+        let mut events = Vec::with_capacity(prices.len() + 1);
+        let mut repriced: HashMap<Pair, u64> = HashMap::new();
+        for (pair, price) in prices {
+            if !self.pairs_status.contains_key(pair) {
+                return Err(format!("Pair {}/{} does not exist", pair.0, pair.1));
+            }
+            if *price == 0 {
+                return Err(format!(
+                    "Index price of {}/{} cannot be zero",
+                    pair.0, pair.1
+                ));
+            }
+            let values_collateral = self
+                .assets_info
+                .get(&pair.0)
+                .and_then(|asset_info| asset_info.collateral.as_ref())
+                .is_some_and(|collateral| collateral.config.quote == pair.1);
+            if self.perp_markets.contains_key(pair) || values_collateral {
+                repriced.insert(pair.clone(), *price);
+                events.push(OrderbookEvent::IndexPriceUpdated {
+                    pair: pair.clone(),
+                    price: *price,
+                });
+            }
+            let Some(breaker) = self.circuit_breakers.get(pair) else {
+                continue;
+            };
+            if breaker.is_halted(block_height) {
+                continue;
+            }
+            events.push(match breaker.track_price(pair, *price, block_height) {
+                Some(tripped @ OrderbookEvent::CircuitBreakerTripped { .. }) => tripped,
+                _ => OrderbookEvent::PriceWindowStarted {
+                    pair: pair.clone(),
+                    reference_price: *price,
+                    block_height,
+                },
+            });
+        }
+
+        let quotes: BTreeSet<Symbol> = repriced.keys().map(|pair| pair.1.clone()).collect();
+        let mut users: Vec<&UserInfo> = self
+            .users_info
+            .values()
+            .filter(|user| {
+                user.margin_quotes()
+                    .iter()
+                    .any(|quote| quotes.contains(quote))
+            })
+            .collect();
+        users.sort_by(|a, b| a.user.cmp(&b.user));
+        for user in users {
+            for quote in user.margin_quotes() {
+                if !quotes.contains(&quote) {
+                    continue;
+                }
+                let account = self.margin_account(
+                    user,
+                    &quote,
+                    &|symbol| self.get_balance(user, symbol).0,
+                    &repriced,
+                )?;
+                if account.equity < account.maintenance_margin as i64 {
+                    events.push(OrderbookEvent::MarginCalled {
+                        user: user.user.clone(),
+                        account,
+                    });
+                }
+            }
+        }
+
+        events.push(Self::nonce_increment_event(operator)?);
+
+        Ok(events)
+    }
+
+    /// Creates a pair traded as perpetual futures. Its base asset is synthetic: it is only
+    /// registered by this pair, and never deposited nor withdrawn.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn create_perp_market(
+        &self,
+        operator: &UserInfo,
+        pair: &Pair,
+        info: &PairInfo,
+        config: &PerpConfig,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can create perp markets, got {}",
+                operator.user
+            ));
+        }
+        if self.pairs_status.contains_key(pair) {
+            return Err(format!("Pair {}/{} already exists", pair.0, pair.1));
+        }
+        if self.assets_info.contains_key(&pair.0) {
+            return Err(format!(
+                "Base asset {} of a perp market cannot be an existing asset",
+                pair.0
+            ));
+        }
+        if config.maintenance_margin_bps == 0
+            || config.maintenance_margin_bps > config.initial_margin_bps
+            || config.initial_margin_bps > 10_000
+        {
+            return Err(format!(
+                "Margins of {}/{} must satisfy 0 < maintenance <= initial <= 10000 bps, got {} and {}",
+                pair.0, pair.1, config.maintenance_margin_bps, config.initial_margin_bps
+            ));
+        }
+        if config.liquidation_penalty_bps > config.maintenance_margin_bps {
+            return Err(format!(
+                "Liquidation penalty of {}/{} cannot exceed its maintenance margin of {} bps, got {}",
+                pair.0, pair.1, config.maintenance_margin_bps, config.liquidation_penalty_bps
+            ));
+        }
+
+        let mut events = self.create_pair(pair, info)?;
+        events.push(OrderbookEvent::PerpMarketCreated {
+            pair: pair.clone(),
+            config: config.clone(),
+        });
+        events.push(Self::nonce_increment_event(operator)?);
+
+        Ok(events)
+    }
+
+    /// Accrues `payment`, the funding paid by a long position of one base unit, to a perp market
+    /// and settles it on every position of the market. A payer short of free balance pays the
+    /// rest from the margin of its position; what its margin cannot cover is not paid.
+    /// Accounts left under their maintenance margin are called.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn settle_funding(
+        &self,
+        operator: &UserInfo,
+        pair: &Pair,
+        payment: i64,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can settle funding, got {}",
+                operator.user
+            ));
+        }
+        let current = self
+            .perp_markets
+            .get(pair)
+            .ok_or_else(|| format!("Pair {}/{} is not a perp market", pair.0, pair.1))?;
+        let market = PerpMarket {
+            funding_index: current
+                .funding_index
+                .checked_add(payment)
+                .ok_or("Funding index overflow")?,
+            ..current.clone()
         };
+        let base_scale = self.base_scale(pair)?;
+        let quote_symbol = &pair.1;
+
+        let mut events = vec![OrderbookEvent::FundingRateAccrued {
+            pair: pair.clone(),
+            funding_index: market.funding_index,
+        }];
+
+        let mut holders: Vec<(&UserInfo, &Position)> = self
+            .users_info
+            .values()
+            .filter_map(|info| info.get_position(pair).map(|position| (info, position)))
+            .collect();
+        holders.sort_by(|(a, _), (b, _)| a.user.cmp(&b.user));
+
+        for (holder, position) in holders {
+            let owed = market.pending_funding(position, base_scale);
+            let balance = self.get_balance(holder, quote_symbol).0;
+            let account = self.get_margin_account(holder, quote_symbol)?;
+            // The account was valued with the funding pending at the current index
+            let equity = account.equity as i128 + current.pending_funding(position, base_scale);
+            let mut position = position.clone();
+            position.funding_index = market.funding_index;
+
+            let mut new_balance = balance as i128 - owed;
+            let mut paid = owed;
+            if new_balance < 0 {
+                let from_margin = (-new_balance).min(position.margin as i128);
+                position.margin -= from_margin as u64;
+                paid = balance as i128 + from_margin;
+                new_balance = 0;
+            }
+            // Funding received repays the debt first
+            let debt = holder.get_debt(quote_symbol);
+            let repaid = if owed < 0 {
+                (debt as i128).min(-owed)
+            } else {
+                0
+            };
+            let new_balance =
+                u64::try_from(new_balance - repaid).map_err(|_| "Balance overflow")?;
+
+            events.push(OrderbookEvent::PositionUpdated {
+                user: holder.user.clone(),
+                position,
+            });
+            if new_balance != balance {
+                events.push(OrderbookEvent::BalanceUpdated {
+                    user: holder.user.clone(),
+                    symbol: quote_symbol.clone(),
+                    amount: new_balance,
+                });
+            }
+            if repaid > 0 {
+                events.push(OrderbookEvent::DebtUpdated {
+                    user: holder.user.clone(),
+                    debt: Debt {
+                        symbol: quote_symbol.clone(),
+                        amount: debt - repaid as u64,
+                    },
+                });
+            }
+            if paid != 0 {
+                events.push(OrderbookEvent::FundingPaid {
+                    user: holder.user.clone(),
+                    pair: pair.clone(),
+                    amount: i64::try_from(paid).map_err(|_| "Funding payment overflow")?,
+                });
+            }
+
+            let equity = equity - paid;
+            if equity < account.maintenance_margin as i128 {
+                events.push(OrderbookEvent::MarginCalled {
+                    user: holder.user.clone(),
+                    account: MarginAccount {
+                        equity: equity.clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+                        ..account
+                    },
+                });
+            }
+        }
+        events.push(Self::nonce_increment_event(operator)?);
+
+        Ok(events)
+    }
+
+    /// Force-closes the position of an under-margined account on a perp market with a market
+    /// order against the book, on behalf of the operator. The resting orders of the account on
+    /// the market are cancelled first; the losses its margin account cannot cover are left as
+    /// debt. Only the part of the position the book can absorb is closed.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn liquidate(
+        &self,
+        operator: &UserInfo,
+        user: &str,
+        pair: &Pair,
+        order_id: &OrderId,
+        block_height: u64,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can liquidate positions, got {}",
+                operator.user
+            ));
+        }
+        if !self.perp_markets.contains_key(pair) {
+            return Err(format!("Pair {}/{} is not a perp market", pair.0, pair.1));
+        }
+        let user_info = self.get_user_info(user)?;
+        let position = user_info
+            .get_position(pair)
+            .cloned()
+            .ok_or_else(|| format!("User {user} has no position on {}/{}", pair.0, pair.1))?;
+        let account = self.get_margin_account(&user_info, &pair.1)?;
+        if account.equity >= account.maintenance_margin as i64 {
+            return Err(format!(
+                "User {user} cannot be liquidated: equity of {} is not under the maintenance margin of {}",
+                account.equity, account.maintenance_margin
+            ));
+        }
+
+        let mut events = vec![OrderbookEvent::Liquidation {
+            user: user.to_string(),
+            pair: pair.clone(),
+            size: position.size,
+            account,
+        }];
+
+        // The orders are executed on a scratch copy of the state, so that the position is
+        // closed once the resting orders released their margin
+        let mut scratch = self.clone();
+        let user_key = user_info.get_key();
+        let resting: Vec<OrderId> = self
+            .order_manager
+            .orders_owner
+            .iter()
+            .filter(|(id, owner)| {
+                **owner == user_key
+                    && self
+                        .order_manager
+                        .orders
+                        .get(*id)
+                        .is_some_and(|order| &order.pair == pair)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        if !resting.is_empty() {
+            let cancel_events: Vec<OrderbookEvent> = scratch
+                .cancel_orders(&resting, &user_info)?
+                .into_iter()
+                .filter(|event| !matches!(event, OrderbookEvent::NonceIncremented { .. }))
+                .collect();
+            scratch.apply_events_preserving_zeroed_orders(&user_info, &cancel_events)?;
+            events.extend(cancel_events);
+        }
+
+        let order = Order {
+            order_id: order_id.clone(),
+            order_type: OrderType::Market,
+            order_side: if position.size > 0 {
+                OrderSide::Ask
+            } else {
+                OrderSide::Bid
+            },
+            price: None,
+            pair: pair.clone(),
+            quantity: position.size.unsigned_abs(),
+        };
+        let fill_events: Vec<OrderbookEvent> = scratch
+            .execute_order_inner(&user_info, order, block_height, true)
+            .map_err(|e| format!("Liquidation of user {user} failed: {e}"))?
+            .into_iter()
+            .filter(|event| !matches!(event, OrderbookEvent::NonceIncremented { .. }))
+            .collect();
+        scratch.apply_events_preserving_zeroed_orders(&user_info, &fill_events)?;
+        events.extend(fill_events);
+        events.extend(scratch.settle_insurance_fund(&user_info.user, pair, &position)?);
+
+        events.push(Self::nonce_increment_event(operator)?);
+
+        Ok(events)
+    }
+
+    /// Moves the insurance fund once a position is liquidated, on the state after the fills:
+    /// the penalty on the closed size is collected from the quote balance left to the account,
+    /// and the debt of an account left with a negative equity is repaid by the fund, as far as
+    /// its balance allows.
+    fn settle_insurance_fund(
+        &self,
+        user: &str,
+        pair: &Pair,
+        position: &Position,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        let market = self
+            .perp_markets
+            .get(pair)
+            .ok_or_else(|| format!("Pair {}/{} is not a perp market", pair.0, pair.1))?;
+        let quote = &pair.1;
+        let user_info = self.get_user_info(user)?;
+        let remaining = user_info
+            .get_position(pair)
+            .map_or(0, |position| position.size.unsigned_abs());
+        let closed = position.size.unsigned_abs().saturating_sub(remaining);
+        let price = if market.index_price > 0 {
+            market.index_price
+        } else {
+            position.entry_price
+        };
+        let penalty = market.liquidation_penalty(closed, price, self.base_scale(pair)?);
+
+        // The fund account is registered on its first penalty
+        let fund = self
+            .get_user_info(INSURANCE_FUND_IDENTITY)
+            .unwrap_or_else(|_| UserInfo::new(INSURANCE_FUND_IDENTITY.to_string(), Vec::new()));
+        let fund_balance = self.get_balance(&fund, quote).0;
+        let balance = self.get_balance(&user_info, quote).0;
+
+        let mut events = Vec::new();
+        let collected = penalty.min(balance);
+        if collected > 0 {
+            if !self.users_info.contains_key(INSURANCE_FUND_IDENTITY) {
+                events.push(OrderbookEvent::SessionKeyAdded {
+                    user: fund.user.clone(),
+                    salt: fund.salt.clone(),
+                    nonce: 0,
+                    session_keys: Vec::new(),
+                    session_key_scopes: Vec::new(),
+                });
+            }
+            events.push(OrderbookEvent::BalanceUpdated {
+                user: user.to_string(),
+                symbol: quote.clone(),
+                amount: balance - collected,
+            });
+            events.push(OrderbookEvent::BalanceUpdated {
+                user: fund.user.clone(),
+                symbol: quote.clone(),
+                amount: fund_balance
+                    .checked_add(collected)
+                    .ok_or("Balance overflow")?,
+            });
+            events.push(OrderbookEvent::InsuranceFundUpdated {
+                user: user.to_string(),
+                symbol: quote.clone(),
+                change: collected as i64,
+            });
+            return Ok(events);
+        }
+
+        let debt = user_info.get_debt(quote);
+        let equity = self.get_margin_account(&user_info, quote)?.equity;
+        let covered = debt.min(equity.min(0).unsigned_abs()).min(fund_balance);
+        if covered > 0 {
+            events.push(OrderbookEvent::DebtUpdated {
+                user: user.to_string(),
+                debt: Debt {
+                    symbol: quote.clone(),
+                    amount: debt - covered,
+                },
+            });
+            events.push(OrderbookEvent::BalanceUpdated {
+                user: fund.user.clone(),
+                symbol: quote.clone(),
+                amount: fund_balance - covered,
+            });
+            events.push(OrderbookEvent::InsuranceFundUpdated {
+                user: user.to_string(),
+                symbol: quote.clone(),
+                change: -(covered as i64),
+            });
+        }
+
+        Ok(events)
+    }
+
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn deposit(
+        &self,
+        symbol: &str,
+        amount: u64,
+        user_info: &UserInfo,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        // Compute the new balance
+        let user = self.get_user_info(&user_info.user)?; // Ensure user exists
+        let balance = self.get_balance(user_info, symbol);
+        // Deposits repay the debt of the asset first
+        let debt = user.get_debt(symbol);
+        let repaid = debt.min(amount);
+        let new_balance = Balance(
+            balance
+                .0
+                .checked_add(amount - repaid)
+                .ok_or("Balance overflow")?,
+        );
+
+        let mut events = vec![OrderbookEvent::BalanceUpdated {
+            user: user_info.user.clone(),
+            symbol: symbol.to_string(),
+            amount: new_balance.0,
+        }];
+        if repaid > 0 {
+            events.push(OrderbookEvent::DebtUpdated {
+                user: user_info.user.clone(),
+                debt: Debt {
+                    symbol: symbol.to_string(),
+                    amount: debt - repaid,
+                },
+            });
+        }
+
+        Ok(events)
+    }
+
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn withdraw(
+        &self,
+        symbol: &str,
+        amount: &u64,
+        block_height: u64,
+        user_info: &UserInfo,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if let Some(delay) = self.withdraw_delay(symbol, *amount) {
+            return Err(format!(
+                "Could not withdraw: withdrawals of more than {} {symbol} must be requested and go through the queue",
+                delay.threshold
+            ));
+        }
+
+        let mut events = self.withdrawal_events(symbol, *amount, block_height, user_info)?;
+        events.push(Self::nonce_increment_event(user_info)?);
+
+        Ok(events)
+    }
+
+    /// Queues a withdrawal above the threshold of the withdraw delay of the asset. The amount is
+    /// deducted from the balance right away, and paid out once the withdrawal is released.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn request_withdrawal(
+        &self,
+        symbol: &str,
+        amount: u64,
+        destination: &WithdrawDestination,
+        block_height: u64,
+        user_info: &UserInfo,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        let Some(delay) = self.withdraw_delay(symbol, amount) else {
+            return Err(format!(
+                "Could not request withdrawal: {amount} {symbol} can be withdrawn directly"
+            ));
+        };
+
+        let mut events = self.withdrawal_events(symbol, amount, block_height, user_info)?;
+        events.push(OrderbookEvent::WithdrawalQueued {
+            user: user_info.user.clone(),
+            withdrawal: PendingWithdrawal {
+                // Nonces are never reused, so they identify the withdrawals of a user
+                id: user_info.nonce,
+                symbol: symbol.to_string(),
+                amount,
+                destination: destination.clone(),
+                available_at: block_height
+                    .checked_add(delay.delay_blocks)
+                    .ok_or("Withdrawal delay overflow")?,
+                approved: !delay.requires_approval,
+            },
+        });
+        events.push(Self::nonce_increment_event(user_info)?);
+
+        Ok(events)
+    }
+
+    /// Approves a queued withdrawal, or rejects it and refunds its amount, on behalf of the operator
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn review_withdrawal(
+        &self,
+        operator: &UserInfo,
+        user: &str,
+        withdrawal_id: u32,
+        approve: bool,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can review withdrawals, got {}",
+                operator.user
+            ));
+        }
+        let user_info = self.get_user_info(user)?;
+        let withdrawal = user_info
+            .get_pending_withdrawal(withdrawal_id)
+            .ok_or_else(|| format!("User {user} has no pending withdrawal {withdrawal_id}"))?;
+
+        let mut events = Vec::new();
+        if approve {
+            if withdrawal.approved {
+                return Err(format!(
+                    "Withdrawal {withdrawal_id} of user {user} is already approved"
+                ));
+            }
+        } else {
+            let balance = self.get_balance(&user_info, &withdrawal.symbol);
+            events.push(OrderbookEvent::BalanceUpdated {
+                user: user.to_string(),
+                symbol: withdrawal.symbol.clone(),
+                amount: balance
+                    .0
+                    .checked_add(withdrawal.amount)
+                    .ok_or("Balance overflow")?,
+            });
+        }
+        events.push(OrderbookEvent::WithdrawalReviewed {
+            user: user.to_string(),
+            withdrawal_id,
+            approved: approve,
+        });
+        events.push(Self::nonce_increment_event(operator)?);
+
+        Ok(events)
+    }
+
+    /// Releases a queued withdrawal once its delay is over and it is approved, on behalf of the
+    /// operator. The withdrawal is paid out like a direct one once the release settles.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn release_withdrawal(
+        &self,
+        operator: &UserInfo,
+        user: &str,
+        withdrawal: &PendingWithdrawal,
+        block_height: u64,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can release withdrawals, got {}",
+                operator.user
+            ));
+        }
+        let user_info = self.get_user_info(user)?;
+        let pending = user_info
+            .get_pending_withdrawal(withdrawal.id)
+            .ok_or_else(|| format!("User {user} has no pending withdrawal {}", withdrawal.id))?;
+        if pending != withdrawal {
+            return Err(format!(
+                "Withdrawal {} of user {user} does not match the queued one: {pending:?}",
+                withdrawal.id
+            ));
+        }
+        if block_height < pending.available_at {
+            return Err(format!(
+                "Withdrawal {} of user {user} is delayed until block {}, got block {block_height}",
+                pending.id, pending.available_at
+            ));
+        }
+        if !pending.approved {
+            return Err(format!(
+                "Withdrawal {} of user {user} is waiting for the approval of the operator",
+                pending.id
+            ));
+        }
+        self.ensure_bridge_open(&pending.destination)?;
+
+        Ok(vec![
+            OrderbookEvent::WithdrawalReleased {
+                user: user.to_string(),
+                withdrawal: pending.clone(),
+            },
+            Self::nonce_increment_event(operator)?,
+        ])
+    }
+
+    /// Pauses or resumes the withdrawals to other networks than Hyli. Deposits from the bridge
+    /// are credited by the operator, that holds them while the bridge is paused.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn update_bridge_pause(
+        &self,
+        operator: &UserInfo,
+        paused: bool,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if operator.user != ORDERBOOK_ACCOUNT_IDENTITY {
+            return Err(format!(
+                "Only {ORDERBOOK_ACCOUNT_IDENTITY} can pause the bridge, got {}",
+                operator.user
+            ));
+        }
+        if self.bridge_paused == paused {
+            return Err(format!("Bridge is already paused: {paused}"));
+        }
+
+        Ok(vec![
+            OrderbookEvent::BridgePauseUpdated { paused },
+            Self::nonce_increment_event(operator)?,
+        ])
+    }
+
+    /// Rejects the withdrawals to other networks than Hyli while the bridge is paused
+    pub fn ensure_bridge_open(&self, destination: &WithdrawDestination) -> Result<(), String> {
+        if self.bridge_paused && destination.network != "hyli" {
+            return Err(format!(
+                "Could not withdraw: withdrawals to {} are paused",
+                destination.network
+            ));
+        }
+        Ok(())
+    }
+
+    /// Withdraw delay applying to a withdrawal of `amount` of `symbol`, if it is large enough
+    pub fn withdraw_delay(&self, symbol: &str, amount: u64) -> Option<&WithdrawDelay> {
+        self.assets_info
+            .get(symbol)
+            .and_then(|asset_info| asset_info.withdraw_delay.as_ref())
+            .filter(|delay| amount > delay.threshold)
+    }
+
+    /// Balance and withdraw limit events of a withdrawal, direct or queued
+    fn withdrawal_events(
+        &self,
+        symbol: &str,
+        amount: u64,
+        block_height: u64,
+        user_info: &UserInfo,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        let balance = self.get_balance(user_info, symbol);
+
+        if balance.0 < amount {
+            return Err(format!(
+                "Could not withdraw: Insufficient balance: user {} has {balance:?} {symbol} symbols, trying to withdraw {amount}", user_info.user
+            ));
+        }
+
+        let new_total = balance.0 - amount;
+
+        // The withdrawn asset may back a margin account, that must keep its initial margin
+        self.ensure_initial_margin_kept("withdraw", symbol, new_total, user_info)?;
+
+        let mut events = vec![OrderbookEvent::BalanceUpdated {
+            user: user_info.user.clone(),
+            symbol: symbol.to_string(),
+            amount: new_total,
+        }];
+
+        if let Some(window) = self.withdrawal_window(symbol, amount, block_height, user_info)? {
+            events.push(OrderbookEvent::WithdrawalRecorded {
+                user: user_info.user.clone(),
+                window,
+            });
+        }
+
+        Ok(events)
+    }
+
+    /// Moves `amount` of `symbol` from the balance of the user to the account `to`. Like a
+    /// deposit, the amount received repays the debt of the recipient in that asset first.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn transfer(
+        &self,
+        to: &str,
+        symbol: &str,
+        amount: u64,
+        user_info: &UserInfo,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if amount == 0 {
+            return Err("Could not transfer: amount must be positive".to_string());
+        }
+        if to == user_info.user {
+            return Err(format!(
+                "Could not transfer: user {to} cannot transfer to itself"
+            ));
+        }
+        if utils::is_reserved_identity(to) {
+            return Err(format!(
+                "Could not transfer: identity {to} is reserved to the orderbook"
+            ));
+        }
+        let recipient = self
+            .get_user_info(to)
+            .map_err(|_| format!("Could not transfer: user {to} is not registered"))?;
+
+        let balance = self.get_balance(user_info, symbol);
+        if balance.0 < amount {
+            return Err(format!(
+                "Could not transfer: Insufficient balance: user {} has {balance:?} {symbol} symbols, trying to transfer {amount}", user_info.user
+            ));
+        }
+        let new_total = balance.0 - amount;
+
+        // Like a withdrawal, the transfer cannot leave a margin account under its initial margin
+        self.ensure_initial_margin_kept("transfer", symbol, new_total, user_info)?;
+
+        let mut events = vec![
+            OrderbookEvent::BalanceUpdated {
+                user: user_info.user.clone(),
+                symbol: symbol.to_string(),
+                amount: new_total,
+            },
+            OrderbookEvent::Transferred {
+                from: user_info.user.clone(),
+                to: to.to_string(),
+                symbol: symbol.to_string(),
+                amount,
+            },
+        ];
+        events.extend(self.deposit(symbol, amount, &recipient)?);
+        events.push(Self::nonce_increment_event(user_info)?);
+
+        Ok(events)
+    }
+
+    /// Checks that the margin accounts of the user keep their initial margin once its balance of
+    /// `symbol` is lowered to `new_total` by `action`
+    fn ensure_initial_margin_kept(
+        &self,
+        action: &str,
+        symbol: &str,
+        new_total: u64,
+        user_info: &UserInfo,
+    ) -> Result<(), String> {
+        let margin_user = self.users_info.get(&user_info.user).unwrap_or(user_info);
+        for quote in margin_user.margin_quotes() {
+            let account = self.margin_account(
+                margin_user,
+                &quote,
+                &|balance_symbol| {
+                    if balance_symbol == symbol {
+                        new_total
+                    } else {
+                        self.get_balance(user_info, balance_symbol).0
+                    }
+                },
+                &HashMap::new(),
+            )?;
+            if account.equity < account.initial_margin as i64 {
+                return Err(format!(
+                    "Could not {action}: the {quote} margin account of user {} would have an equity of {}, under its initial margin of {}",
+                    user_info.user, account.equity, account.initial_margin
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Usage of the user's withdrawal window of `symbol` once `amount` is withdrawn at `block_height`.
+    /// Returns `None` if the asset has no withdraw limit.
+    fn withdrawal_window(
+        &self,
+        symbol: &str,
+        amount: u64,
+        block_height: u64,
+        user_info: &UserInfo,
+    ) -> Result<Option<WithdrawalWindow>, String> {
+        let Some(limit) = self
+            .assets_info
+            .get(symbol)
+            .and_then(|asset_info| asset_info.withdraw_limit.as_ref())
+        else {
+            return Ok(None);
+        };
+
+        let window_start = limit.window_start(block_height);
+        let withdrawn = match user_info.get_withdrawal_window(symbol) {
+            Some(window) if window.window_start > window_start => {
+                return Err(format!(
+                    "Could not withdraw: block {block_height} is before the current withdrawal window of user {} starting at block {}",
+                    user_info.user, window.window_start
+                ));
+            }
+            Some(window) if window.window_start == window_start => window.withdrawn,
+            _ => 0,
+        };
+
+        let withdrawn = withdrawn
+            .checked_add(amount)
+            .ok_or("Withdrawn amount overflow")?;
+        if withdrawn > limit.max_amount {
+            return Err(format!(
+                "Could not withdraw: user {} would withdraw {withdrawn} {symbol} in the window starting at block {window_start}, while the limit is {}",
+                user_info.user, limit.max_amount
+            ));
+        }
+
+        Ok(Some(WithdrawalWindow {
+            symbol: symbol.to_string(),
+            window_start,
+            withdrawn,
+        }))
+    }
+
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn cancel_order(
+        &self,
+        order_id: OrderId,
+        user_info: &UserInfo,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        let order = self
+            .order_manager
+            .orders
+            .get(&order_id)
+            .ok_or(format!("Order {order_id} not found"))?
+            .clone();
+
+        let (required_symbol, refund) = self.order_refund(&order)?;
 
         let current_balance = self.get_balance(user_info, &required_symbol).0;
         let new_balance = current_balance
-            .checked_add(order.quantity)
+            .checked_add(refund)
             .ok_or("Balance overflow")?;
 
         let events = vec![
@@ -380,6 +2111,93 @@ impl ExecuteState {
         Ok(events)
     }
 
+    /// Cancels several orders of a user at once, refunding each symbol in a single balance update
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn cancel_orders(
+        &self,
+        order_ids: &[OrderId],
+        user_info: &UserInfo,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if order_ids.is_empty() {
+            return Err("No order to cancel".to_string());
+        }
+
+        let user_key = user_info.get_key();
+        let mut seen: HashSet<&OrderId> = HashSet::new();
+        let mut refunds: BTreeMap<Symbol, u64> = BTreeMap::new();
+        let mut events = Vec::with_capacity(order_ids.len() + 2);
+
+        for order_id in order_ids {
+            if !seen.insert(order_id) {
+                return Err(format!("Order {order_id} is cancelled twice"));
+            }
+            let order = self
+                .order_manager
+                .orders
+                .get(order_id)
+                .ok_or(format!("Order {order_id} not found"))?;
+            if self.order_manager.orders_owner.get(order_id) != Some(&user_key) {
+                return Err(format!(
+                    "Order {order_id} is not owned by user {}",
+                    user_info.user
+                ));
+            }
+
+            let (required_symbol, amount) = self.order_refund(order)?;
+            let refund = refunds.entry(required_symbol).or_default();
+            *refund = refund.checked_add(amount).ok_or("Balance overflow")?;
+
+            events.push(OrderbookEvent::OrderCancelled {
+                order_id: order_id.clone(),
+                pair: order.pair.clone(),
+            });
+        }
+
+        for (symbol, refund) in refunds {
+            let new_balance = self
+                .get_balance(user_info, &symbol)
+                .0
+                .checked_add(refund)
+                .ok_or("Balance overflow")?;
+            events.push(OrderbookEvent::BalanceUpdated {
+                user: user_info.user.clone(),
+                symbol,
+                amount: new_balance,
+            });
+        }
+
+        events.push(Self::nonce_increment_event(user_info)?);
+
+        Ok(events)
+    }
+
+    /// Symbol and amount refunded when cancelling an order: the margin it locks on a perp
+    /// market, the quantity otherwise
+    fn order_refund(&self, order: &Order) -> Result<(Symbol, u64), String> {
+        if let Some(market) = self.perp_markets.get(&order.pair) {
+            let base_scale = self.base_scale(&order.pair)?;
+            let price = order
+                .price
+                .ok_or_else(|| format!("Order {} has no price", order.order_id))?;
+            return Ok((
+                order.pair.1.clone(),
+                market.margin(order.quantity, price, base_scale),
+            ));
+        }
+        let required_symbol = match &order.order_side {
+            OrderSide::Bid => order.pair.1.clone(),
+            OrderSide::Ask => order.pair.0.clone(),
+        };
+        Ok((required_symbol, order.quantity))
+    }
+
+    fn base_scale(&self, pair: &Pair) -> Result<u64, String> {
+        self.assets_info
+            .get(&pair.0)
+            .map(|info| POW10[info.scale as usize])
+            .ok_or(format!("Asset info for {} not found", pair.0))
+    }
+
     #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
     pub fn get_user_info_from_key(&self, key: &H256) -> Result<UserInfo, String> {
         self.users_info
@@ -413,6 +2231,10 @@ impl ExecuteState {
             users_info,
             balances,
             order_manager,
+            pairs_status: HashMap::new(),
+            circuit_breakers: HashMap::new(),
+            perp_markets: HashMap::new(),
+            bridge_paused: false,
         };
 
         for (pair, info) in pairs_info {
@@ -435,6 +2257,141 @@ impl ExecuteState {
             .unwrap_or_default()
     }
 
+    /// Cross-margin account of a user in a quote asset, valued at the index prices
+    pub fn get_margin_account(
+        &self,
+        user: &UserInfo,
+        quote: &Symbol,
+    ) -> Result<MarginAccount, String> {
+        self.margin_account(
+            user,
+            quote,
+            &|symbol| self.get_balance(user, symbol).0,
+            &HashMap::new(),
+        )
+    }
+
+    /// Symbols whose balances value the margin accounts in `quotes`: the quotes, and the
+    /// collaterals valued in them
+    pub fn margin_symbols(&self, quotes: &BTreeSet<Symbol>) -> BTreeSet<Symbol> {
+        let collaterals = self.assets_info.iter().filter_map(|(symbol, asset_info)| {
+            asset_info
+                .collateral
+                .as_ref()
+                .filter(|collateral| quotes.contains(&collateral.config.quote))
+                .map(|_| symbol.clone())
+        });
+        quotes.iter().cloned().chain(collaterals).collect()
+    }
+
+    /// Margin account of a user with the balances of `balance_of`, and the index prices of
+    /// `prices` instead of the ones of the state
+    fn margin_account(
+        &self,
+        user: &UserInfo,
+        quote: &Symbol,
+        balance_of: &dyn Fn(&str) -> u64,
+        prices: &HashMap<Pair, u64>,
+    ) -> Result<MarginAccount, String> {
+        let mut equity = balance_of(quote) as i128 - user.get_debt(quote) as i128;
+        let mut initial_margin: u64 = 0;
+        let mut maintenance_margin: u64 = 0;
+
+        for position in user
+            .positions
+            .iter()
+            .filter(|position| &position.pair.1 == quote)
+        {
+            let market = self.perp_markets.get(&position.pair).ok_or_else(|| {
+                format!(
+                    "Pair {}/{} is not a perp market",
+                    position.pair.0, position.pair.1
+                )
+            })?;
+            let base_scale = self.base_scale(&position.pair)?;
+            // A position is valued at its entry price until its market has an index price
+            let price = match prices.get(&position.pair) {
+                Some(price) => *price,
+                None if market.index_price > 0 => market.index_price,
+                None => position.entry_price,
+            };
+            let size = position.size.unsigned_abs();
+            equity += position.margin as i128
+                + (price as i128 - position.entry_price as i128) * position.size as i128
+                    / base_scale as i128
+                - market.pending_funding(position, base_scale);
+            initial_margin = initial_margin.saturating_add(market.margin(size, price, base_scale));
+            maintenance_margin = maintenance_margin
+                .saturating_add(market.maintenance_margin(size, price, base_scale));
+        }
+
+        for (symbol, asset_info) in &self.assets_info {
+            let Some(collateral) = asset_info
+                .collateral
+                .as_ref()
+                .filter(|collateral| &collateral.config.quote == quote)
+            else {
+                continue;
+            };
+            let price = prices
+                .get(&(symbol.clone(), quote.clone()))
+                .copied()
+                .unwrap_or(collateral.price);
+            let value = balance_of(symbol) as u128 * price as u128
+                / POW10[asset_info.scale as usize] as u128;
+            equity += (value * collateral.config.weight_bps as u128 / 10_000) as i128;
+        }
+
+        Ok(MarginAccount {
+            quote: quote.clone(),
+            equity: equity.clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+            initial_margin,
+            maintenance_margin,
+        })
+    }
+
+    /// Amounts locked in the open orders of a user, by symbol: the base quantity of asks and
+    /// the quote notional of bids, or the quote margin of orders on perp markets. They are not
+    /// part of the balances returned by `get_balance`.
+    pub fn get_locked_balances(&self, user: &UserInfo) -> HashMap<Symbol, u64> {
+        let user_key = user.get_key();
+        let mut locked: HashMap<Symbol, u64> = HashMap::new();
+        for (order_id, owner) in &self.order_manager.orders_owner {
+            if *owner != user_key {
+                continue;
+            }
+            let Some(order) = self.order_manager.orders.get(order_id) else {
+                continue;
+            };
+            if let Some(market) = self.perp_markets.get(&order.pair) {
+                let base_scale = self
+                    .assets_info
+                    .get(&order.pair.0)
+                    .map(|info| POW10[info.scale as usize])
+                    .unwrap_or(1);
+                *locked.entry(order.pair.1.clone()).or_default() +=
+                    market.margin(order.quantity, order.price.unwrap_or_default(), base_scale);
+                continue;
+            }
+            let (symbol, amount) = match order.order_side {
+                OrderSide::Ask => (&order.pair.0, order.quantity),
+                OrderSide::Bid => {
+                    let base_scale = self
+                        .assets_info
+                        .get(&order.pair.0)
+                        .map(|info| POW10[info.scale as usize])
+                        .unwrap_or(1);
+                    (
+                        &order.pair.1,
+                        order.quantity * order.price.unwrap_or_default() / base_scale,
+                    )
+                }
+            };
+            *locked.entry(symbol.clone()).or_default() += amount;
+        }
+        locked
+    }
+
     #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
     pub fn get_orders(&self) -> HashMap<String, Order> {
         self.order_manager.orders.clone()
@@ -478,6 +2435,7 @@ impl ExecuteState {
                     self.register_asset(&pair.1, &info.quote)?;
                     self.balances.entry(pair.0.clone()).or_default();
                     self.balances.entry(pair.1.clone()).or_default();
+                    self.pairs_status.entry(pair.clone()).or_default();
                     #[cfg(feature = "instrumentation")]
                     span.exit();
                 }
@@ -506,7 +2464,7 @@ impl ExecuteState {
                     salt,
                     nonce,
                     session_keys,
-                    ..
+                    session_key_scopes,
                 } => {
                     #[cfg(feature = "instrumentation")]
                     let span = sdk::tracing::span!(
@@ -518,15 +2476,15 @@ impl ExecuteState {
                         .users_info
                         .entry(user.clone())
                         .or_insert_with(|| UserInfo {
-                            user: user.clone(),
-                            salt: salt.clone(),
-                            nonce: *nonce,
-                            session_keys: session_keys.clone(),
+                            // Only the user registering itself can be a sub-account
+                            sub_account: user_info.user == *user && user_info.sub_account,
+                            ..UserInfo::new(user.clone(), salt.clone())
                         });
 
                     entry.salt = salt.clone();
                     entry.nonce = *nonce;
                     entry.session_keys = session_keys.clone();
+                    entry.session_key_scopes = session_key_scopes.clone();
                     #[cfg(feature = "instrumentation")]
                     span.exit();
                 }
@@ -545,6 +2503,178 @@ impl ExecuteState {
                     #[cfg(feature = "instrumentation")]
                     span.exit();
                 }
+                OrderbookEvent::FeeTierUpdated { user, fee_tier } => {
+                    let entry = self
+                        .users_info
+                        .get_mut(user)
+                        .ok_or_else(|| format!("User info not found for user '{user}'"))?;
+                    entry.fee_tier = fee_tier.clone();
+                }
+                OrderbookEvent::WithdrawLimitUpdated { symbol, limit } => {
+                    let asset_info = self
+                        .assets_info
+                        .get_mut(symbol)
+                        .ok_or_else(|| format!("Asset info not found for symbol '{symbol}'"))?;
+                    asset_info.withdraw_limit = limit.clone();
+                }
+                OrderbookEvent::WithdrawalRecorded { user, window } => {
+                    let entry = self
+                        .users_info
+                        .get_mut(user)
+                        .ok_or_else(|| format!("User info not found for user '{user}'"))?;
+                    entry.set_withdrawal_window(window.clone());
+                }
+                OrderbookEvent::WithdrawDelayUpdated { symbol, delay } => {
+                    let asset_info = self
+                        .assets_info
+                        .get_mut(symbol)
+                        .ok_or_else(|| format!("Asset info not found for symbol '{symbol}'"))?;
+                    asset_info.withdraw_delay = delay.clone();
+                }
+                OrderbookEvent::WithdrawalQueued { user, withdrawal } => {
+                    let entry = self
+                        .users_info
+                        .get_mut(user)
+                        .ok_or_else(|| format!("User info not found for user '{user}'"))?;
+                    entry.set_pending_withdrawal(withdrawal.clone());
+                }
+                OrderbookEvent::WithdrawalReviewed {
+                    user,
+                    withdrawal_id,
+                    approved,
+                } => {
+                    let entry = self
+                        .users_info
+                        .get_mut(user)
+                        .ok_or_else(|| format!("User info not found for user '{user}'"))?;
+                    match entry.get_pending_withdrawal(*withdrawal_id).cloned() {
+                        Some(withdrawal) if *approved => {
+                            entry.set_pending_withdrawal(PendingWithdrawal {
+                                approved: true,
+                                ..withdrawal
+                            });
+                        }
+                        Some(_) => entry.remove_pending_withdrawal(*withdrawal_id),
+                        None => {
+                            return Err(format!(
+                                "User {user} has no pending withdrawal {withdrawal_id}"
+                            ))
+                        }
+                    }
+                }
+                OrderbookEvent::WithdrawalReleased { user, withdrawal } => {
+                    let entry = self
+                        .users_info
+                        .get_mut(user)
+                        .ok_or_else(|| format!("User info not found for user '{user}'"))?;
+                    entry.remove_pending_withdrawal(withdrawal.id);
+                }
+                OrderbookEvent::BridgePauseUpdated { paused } => {
+                    self.bridge_paused = *paused;
+                }
+                OrderbookEvent::PairStatusUpdated { pair, status } => {
+                    let entry = self
+                        .pairs_status
+                        .get_mut(pair)
+                        .ok_or_else(|| format!("Pair {}/{} does not exist", pair.0, pair.1))?;
+                    *entry = *status;
+                }
+                OrderbookEvent::CircuitBreakerUpdated { pair, config } => match config {
+                    Some(config) => {
+                        self.circuit_breakers
+                            .insert(pair.clone(), CircuitBreakerState::new(config.clone()));
+                    }
+                    None => {
+                        self.circuit_breakers.remove(pair);
+                    }
+                },
+                OrderbookEvent::PriceWindowStarted {
+                    pair,
+                    reference_price,
+                    block_height,
+                } => {
+                    let breaker = self.circuit_breakers.get_mut(pair).ok_or_else(|| {
+                        format!("No circuit breaker on pair {}/{}", pair.0, pair.1)
+                    })?;
+                    breaker.reference_price = Some(*reference_price);
+                    breaker.window_start = *block_height;
+                }
+                OrderbookEvent::CircuitBreakerTripped {
+                    pair,
+                    price,
+                    block_height,
+                    halted_until,
+                    ..
+                } => {
+                    let breaker = self.circuit_breakers.get_mut(pair).ok_or_else(|| {
+                        format!("No circuit breaker on pair {}/{}", pair.0, pair.1)
+                    })?;
+                    // The price the breaker tripped at is the reference of the next window
+                    breaker.reference_price = Some(*price);
+                    breaker.window_start = *block_height;
+                    breaker.halted_until = *halted_until;
+                }
+                OrderbookEvent::PerpMarketCreated { pair, config } => {
+                    self.perp_markets
+                        .insert(pair.clone(), PerpMarket::new(config.clone()));
+                }
+                OrderbookEvent::FundingRateAccrued {
+                    pair,
+                    funding_index,
+                } => {
+                    let market = self.perp_markets.get_mut(pair).ok_or_else(|| {
+                        format!("Pair {}/{} is not a perp market", pair.0, pair.1)
+                    })?;
+                    market.funding_index = *funding_index;
+                }
+                OrderbookEvent::PositionUpdated { user, position } => {
+                    let entry = self
+                        .users_info
+                        .entry(user.clone())
+                        .or_insert_with(|| user_info.clone());
+                    entry.set_position(position.clone());
+                }
+                OrderbookEvent::FundingPaid { .. }
+                | OrderbookEvent::MarginCalled { .. }
+                | OrderbookEvent::Liquidation { .. }
+                | OrderbookEvent::InsuranceFundUpdated { .. }
+                | OrderbookEvent::Transferred { .. }
+                | OrderbookEvent::DustSwept { .. } => {}
+                OrderbookEvent::CollateralUpdated { symbol, collateral } => {
+                    let asset_info = self
+                        .assets_info
+                        .get_mut(symbol)
+                        .ok_or_else(|| format!("Asset info not found for symbol '{symbol}'"))?;
+                    asset_info.collateral = collateral.clone().map(|config| Collateral {
+                        // The price is kept while the collateral is valued in the same quote
+                        price: asset_info
+                            .collateral
+                            .as_ref()
+                            .filter(|current| current.config.quote == config.quote)
+                            .map_or(0, |current| current.price),
+                        config,
+                    });
+                }
+                OrderbookEvent::IndexPriceUpdated { pair, price } => {
+                    if let Some(market) = self.perp_markets.get_mut(pair) {
+                        market.index_price = *price;
+                    }
+                    if let Some(collateral) = self
+                        .assets_info
+                        .get_mut(&pair.0)
+                        .and_then(|asset_info| asset_info.collateral.as_mut())
+                        .filter(|collateral| collateral.config.quote == pair.1)
+                    {
+                        collateral.price = *price;
+                    }
+                }
+                OrderbookEvent::DebtUpdated { user, debt } => {
+                    let entry = self
+                        .users_info
+                        .entry(user.clone())
+                        .or_insert_with(|| user_info.clone());
+                    entry.set_debt(debt.clone());
+                }
                 OrderbookEvent::OrderCancelled { .. }
                 | OrderbookEvent::OrderCreated { .. }
                 | OrderbookEvent::OrderExecuted { .. }
@@ -679,12 +2809,8 @@ impl ExecuteState {
                 // If the action is creating this order, it's expected to not find it in orders
                 && !matches!(
                     action,
-                    OrderbookAction::PermissionedOrderbookAction(
-                        PermissionedOrderbookAction::CreateOrder(Order {
-                            order_id: create_order_id,
-                            ..
-                        }), _
-                    ) if create_order_id == order_id
+                    OrderbookAction::PermissionedOrderbookAction(action, _)
+                        if action.creates_order(order_id)
                 )
             {
                 return Err(format!("Order with id {order_id} does not exist"));
@@ -710,7 +2836,31 @@ impl ExecuteState {
         &self,
         user_info: &UserInfo,
         order: Order,
+        block_height: u64,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        self.execute_order_inner(user_info, order, block_height, false)
+    }
+
+    /// Executes an order, placed to liquidate the position of the user when `liquidation` is set
+    fn execute_order_inner(
+        &self,
+        user_info: &UserInfo,
+        order: Order,
+        block_height: u64,
+        liquidation: bool,
     ) -> Result<Vec<OrderbookEvent>, String> {
+        let status = self
+            .pairs_status
+            .get(&order.pair)
+            .copied()
+            .unwrap_or_default();
+        if status != MarketStatus::Active {
+            return Err(format!(
+                "Pair {}/{} is {status:?}, orders are not accepted",
+                order.pair.0, order.pair.1
+            ));
+        }
+
         let user_info_key = &user_info.get_key();
         let mut events = Vec::new();
 
@@ -723,13 +2873,29 @@ impl ExecuteState {
 
         // Delegate order execution to the manager
         let order_events = self.order_manager.execute_order_dry_run(&order)?;
+        let breaker_event = self.check_circuit_breaker(&order, &order_events, block_height)?;
 
         events.extend(order_events);
+        events.extend(breaker_event);
+
+        if let Some(market) = self.perp_markets.get(&order.pair) {
+            return self.settle_perp_fills(
+                user_info,
+                &order,
+                market,
+                base_scale,
+                events,
+                liquidation,
+            );
+        }
 
         // Balance change aggregation system based on events
         let mut balance_changes: HashMap<Symbol, HashMap<H256, Balance>> = self.get_balances();
         let mut touched_accounts: HashMap<Symbol, HashSet<H256>> = HashMap::new();
         let mut user_keys: HashSet<H256> = HashSet::new();
+        // Amounts received on fills, used to charge trading fees
+        let mut maker_receipts: HashMap<(H256, Symbol), u64> = HashMap::new();
+        let mut taker_receipts: HashMap<Symbol, u64> = HashMap::new();
 
         // Helper function to record balance changes
         fn record_balance_change(
@@ -860,6 +3026,12 @@ impl ExecuteState {
                                     (executed_order.price.unwrap() * executed_order.quantity
                                         / base_scale) as i128,
                                 )?;
+                                *maker_receipts
+                                    .entry((*executed_order_user_info, base_symbol.clone()))
+                                    .or_default() += executed_order.quantity;
+                                *taker_receipts.entry(quote_symbol.clone()).or_default() +=
+                                    executed_order.price.unwrap() * executed_order.quantity
+                                        / base_scale;
                                 touched_accounts
                                     .entry(quote_symbol.clone())
                                     .or_default()
@@ -886,6 +3058,13 @@ impl ExecuteState {
                                     base_symbol,
                                     executed_order.quantity as i128,
                                 )?;
+                                *maker_receipts
+                                    .entry((*executed_order_user_info, quote_symbol.clone()))
+                                    .or_default() += executed_order.price.unwrap()
+                                    * executed_order.quantity
+                                    / base_scale;
+                                *taker_receipts.entry(base_symbol.clone()).or_default() +=
+                                    executed_order.quantity;
                             }
                         }
                     } else {
@@ -931,6 +3110,11 @@ impl ExecuteState {
                                     (updated_order.price.unwrap() * executed_quantity / base_scale)
                                         as i128,
                                 )?;
+                                *maker_receipts
+                                    .entry((*updated_order_user_info, base_symbol.clone()))
+                                    .or_default() += *executed_quantity;
+                                *taker_receipts.entry(quote_symbol.clone()).or_default() +=
+                                    updated_order.price.unwrap() * executed_quantity / base_scale;
                                 touched_accounts
                                     .entry(quote_symbol.clone())
                                     .or_default()
@@ -957,6 +3141,12 @@ impl ExecuteState {
                                     base_symbol,
                                     *executed_quantity as i128,
                                 )?;
+                                *maker_receipts
+                                    .entry((*updated_order_user_info, quote_symbol.clone()))
+                                    .or_default() +=
+                                    updated_order.price.unwrap() * executed_quantity / base_scale;
+                                *taker_receipts.entry(base_symbol.clone()).or_default() +=
+                                    *executed_quantity;
                             }
                         }
                     } else {
@@ -967,42 +3157,475 @@ impl ExecuteState {
             }
         }
 
-        // Load user_name from user_key
-        let user_names = self.get_user_names(&user_keys)?;
+        // Load user_name from user_key. Makers are among them, so their fee tiers are resolved
+        // by name rather than by hashing every user again.
+        let mut user_names = self.get_user_names(&user_keys)?;
+
+        // Charge trading fees on the amounts received by both sides of the fills.
+        // Fees are credited to the orderbook account.
+        let mut fees: Vec<(H256, Symbol, u64)> = Vec::new();
+        for ((maker_key, symbol), amount) in &maker_receipts {
+            let fee = user_names
+                .get(maker_key)
+                .and_then(|maker| self.users_info.get(maker))
+                .map(|maker_info| maker_info.fee_tier.maker_fee(*amount))
+                .unwrap_or_default();
+            if fee > 0 {
+                fees.push((*maker_key, symbol.clone(), fee));
+            }
+        }
+        for (symbol, amount) in &taker_receipts {
+            let fee = user_info.fee_tier.taker_fee(*amount);
+            if fee > 0 {
+                fees.push((*user_info_key, symbol.clone(), fee));
+            }
+        }
+        if !fees.is_empty() {
+            let fee_account_key = self
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .map_err(|_| "Fee account is not registered".to_string())?
+                .get_key();
+            user_names
+                .entry(fee_account_key)
+                .or_insert_with(|| ORDERBOOK_ACCOUNT_IDENTITY.to_string());
+            for (payer, symbol, fee) in fees {
+                record_transfer(
+                    &mut balance_changes,
+                    &mut touched_accounts,
+                    &mut user_keys,
+                    &payer,
+                    &fee_account_key,
+                    &symbol,
+                    fee as i128,
+                )?;
+            }
+        }
+
+        // Updating balances
+        for (symbol, user_keys) in touched_accounts {
+            let symbol_balances = balance_changes
+                .get(&symbol)
+                .ok_or_else(|| format!("{symbol} not found in balance_changes"))?;
+
+            for user_key in user_keys {
+                let amount = symbol_balances.get(&user_key).ok_or_else(|| {
+                    format!(
+                        "User with key {} not found in balance_changes for {symbol}",
+                        hex::encode(user_key.as_slice())
+                    )
+                })?;
+
+                let user_name = user_names
+                    .get(&user_key)
+                    .ok_or_else(|| {
+                        format!(
+                            "User name for key {} not found",
+                            hex::encode(user_key.as_slice())
+                        )
+                    })?
+                    .clone();
+
+                events.push(OrderbookEvent::BalanceUpdated {
+                    user: user_name,
+                    symbol: symbol.clone(),
+                    amount: amount.0,
+                });
+            }
+        }
+
+        events.push(Self::nonce_increment_event(user_info)?);
+
+        Ok(events)
+    }
+
+    /// Settles the fills of an order on a perp market: instead of exchanging the base asset,
+    /// they update the positions of both sides, and move their margin, realized pnl, funding
+    /// and fees in the quote asset. Resting orders lock their initial margin.
+    fn settle_perp_fills(
+        &self,
+        user_info: &UserInfo,
+        order: &Order,
+        market: &PerpMarket,
+        base_scale: u64,
+        mut events: Vec<OrderbookEvent>,
+        liquidation: bool,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        let quote_symbol = &order.pair.1;
+        let user_info_key = user_info.get_key();
+
+        // Fills as (maker key, maker side, filled quantity, price, maker quantity before the fill)
+        let mut fills: Vec<(H256, &OrderSide, u64, u64, u64)> = Vec::new();
+        // Net change of the quote balance of each trader
+        let mut quote_changes: BTreeMap<H256, i128> = BTreeMap::new();
+        for event in &events {
+            match event {
+                OrderbookEvent::OrderCreated {
+                    order: created_order,
+                } => {
+                    let price = created_order
+                        .price
+                        .ok_or_else(|| format!("Order {} has no price", created_order.order_id))?;
+                    *quote_changes.entry(user_info_key).or_default() -=
+                        market.margin(created_order.quantity, price, base_scale) as i128;
+                }
+                OrderbookEvent::OrderExecuted { order_id, .. }
+                | OrderbookEvent::OrderUpdate { order_id, .. }
+                    if order_id != &order.order_id =>
+                {
+                    let maker_order = self
+                        .order_manager
+                        .orders
+                        .get(order_id)
+                        .ok_or_else(|| format!("Could not find {order_id}"))?;
+                    let maker_key = self.order_manager.orders_owner.get(order_id).ok_or_else(|| {
+                        format!("Order owner info (order_id: {order_id}) not found in order manager")
+                    })?;
+                    let price = maker_order
+                        .price
+                        .ok_or_else(|| format!("Order {order_id} has no price"))?;
+                    let quantity = match event {
+                        OrderbookEvent::OrderUpdate {
+                            executed_quantity, ..
+                        } => *executed_quantity,
+                        _ => maker_order.quantity,
+                    };
+                    fills.push((
+                        *maker_key,
+                        &maker_order.order_side,
+                        quantity,
+                        price,
+                        maker_order.quantity,
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        // Traders whose position changes, starting from their current user info
+        let maker_keys: HashSet<H256> = fills.iter().map(|(key, ..)| *key).collect();
+        let mut traders: BTreeMap<H256, UserInfo> = self
+            .users_info
+            .values()
+            .filter(|info| maker_keys.contains(&info.get_key()))
+            .map(|info| (info.get_key(), info.clone()))
+            .collect();
+        traders.insert(
+            user_info_key,
+            self.users_info
+                .get(&user_info.user)
+                .cloned()
+                .unwrap_or_else(|| user_info.clone()),
+        );
+
+        let mut total_fees: u64 = 0;
+        for (maker_key, maker_side, quantity, price, maker_quantity) in fills {
+            // The filled part of the maker order no longer locks its margin
+            let released = market.margin(maker_quantity, price, base_scale)
+                - market.margin(maker_quantity - quantity, price, base_scale);
+            *quote_changes.entry(maker_key).or_default() += released as i128;
+
+            let notional = u64::try_from(quantity as u128 * price as u128 / base_scale as u128)
+                .map_err(|_| "Fill notional overflow")?;
+            for (key, side, is_maker) in [
+                (maker_key, maker_side, true),
+                (user_info_key, &order.order_side, false),
+            ] {
+                let trader = traders.get_mut(&key).ok_or_else(|| {
+                    format!("No user info found for key {}", hex::encode(key.as_slice()))
+                })?;
+                let position = trader
+                    .get_position(&order.pair)
+                    .cloned()
+                    .unwrap_or_else(|| Position::new(order.pair.clone(), market.funding_index));
+                let (position, change) =
+                    market.apply_fill(position, side, quantity, price, base_scale)?;
+                let fee = if is_maker {
+                    trader.fee_tier.maker_fee(notional)
+                } else {
+                    trader.fee_tier.taker_fee(notional)
+                };
+                trader.set_position(position);
+                total_fees = total_fees.checked_add(fee).ok_or("Fee overflow")?;
+                *quote_changes.entry(key).or_default() += change - fee as i128;
+            }
+        }
+
+        for trader in traders.values() {
+            let before = self
+                .users_info
+                .get(&trader.user)
+                .and_then(|info| info.get_position(&order.pair));
+            let after = trader.get_position(&order.pair);
+            if before != after {
+                events.push(OrderbookEvent::PositionUpdated {
+                    user: trader.user.clone(),
+                    // A closed position is sent with a zero size, and removed
+                    position: after
+                        .cloned()
+                        .unwrap_or_else(|| Position::new(order.pair.clone(), market.funding_index)),
+                });
+            }
+        }
+
+        let mut names: HashMap<H256, String> = traders
+            .iter()
+            .map(|(key, trader)| (*key, trader.user.clone()))
+            .collect();
+        if total_fees > 0 {
+            let fee_account = self
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .map_err(|_| "Fee account is not registered".to_string())?;
+            *quote_changes.entry(fee_account.get_key()).or_default() += total_fees as i128;
+            names.insert(fee_account.get_key(), fee_account.user);
+        }
+
+        // Traders short of free balance borrow against their margin account
+        let mut borrowers: Vec<(H256, u64)> = Vec::new();
+        for (key, change) in quote_changes {
+            let user = names.get(&key).ok_or_else(|| {
+                format!(
+                    "User name for key {} not found",
+                    hex::encode(key.as_slice())
+                )
+            })?;
+            let balance = self
+                .balances
+                .get(quote_symbol)
+                .and_then(|balances| balances.get(&key))
+                .map(|balance| balance.0)
+                .unwrap_or_default();
+            let trader_debt = traders
+                .get(&key)
+                .map_or(0, |trader| trader.get_debt(quote_symbol));
+            // Gains repay the debt first
+            let net = balance as i128 - trader_debt as i128 + change;
+            let (amount, new_debt) = if net >= 0 {
+                (u64::try_from(net).map_err(|_| "Balance overflow")?, 0)
+            } else {
+                (0, u64::try_from(-net).map_err(|_| "Debt overflow")?)
+            };
+            events.push(OrderbookEvent::BalanceUpdated {
+                user: user.clone(),
+                symbol: quote_symbol.clone(),
+                amount,
+            });
+            if new_debt != trader_debt {
+                let trader = traders.get_mut(&key).ok_or_else(|| {
+                    format!("User {user} has not enough {quote_symbol} to pay {change}")
+                })?;
+                let debt = Debt {
+                    symbol: quote_symbol.clone(),
+                    amount: new_debt,
+                };
+                trader.set_debt(debt.clone());
+                events.push(OrderbookEvent::DebtUpdated {
+                    user: user.clone(),
+                    debt,
+                });
+                // A liquidated account borrows what its margin cannot cover
+                if new_debt > trader_debt && !(liquidation && key == user_info_key) {
+                    borrowers.push((key, amount));
+                }
+            }
+        }
+
+        // Borrowing is only allowed while the account keeps its initial margin
+        for (key, balance) in borrowers {
+            let trader = traders.get(&key).ok_or_else(|| {
+                format!("No user info found for key {}", hex::encode(key.as_slice()))
+            })?;
+            let account = self.margin_account(
+                trader,
+                quote_symbol,
+                &|symbol| {
+                    if symbol == quote_symbol {
+                        balance
+                    } else {
+                        self.get_balance(trader, symbol).0
+                    }
+                },
+                &HashMap::new(),
+            )?;
+            if account.equity < account.initial_margin as i64 {
+                return Err(format!(
+                    "User {} has not enough {quote_symbol} margin: equity would be {}, under the initial margin of {}",
+                    trader.user, account.equity, account.initial_margin
+                ));
+            }
+        }
+
+        events.push(Self::nonce_increment_event(user_info)?);
+
+        Ok(events)
+    }
+
+    /// Rejects an order that would trade on a pair halted by its circuit breaker, or returns
+    /// the event tracking the price of its last fill
+    fn check_circuit_breaker(
+        &self,
+        order: &Order,
+        order_events: &[OrderbookEvent],
+        block_height: u64,
+    ) -> Result<Option<OrderbookEvent>, String> {
+        let Some(breaker) = self.circuit_breakers.get(&order.pair) else {
+            return Ok(None);
+        };
+        // Fills are at the price of the maker orders, the last one being the furthest away
+        let last_fill_price = order_events.iter().rev().find_map(|event| match event {
+            OrderbookEvent::OrderExecuted { order_id, .. }
+            | OrderbookEvent::OrderUpdate { order_id, .. }
+                if order_id != &order.order_id =>
+            {
+                self.order_manager.orders.get(order_id)?.price
+            }
+            _ => None,
+        });
+        let Some(price) = last_fill_price else {
+            return Ok(None);
+        };
+
+        if breaker.is_halted(block_height) {
+            return Err(format!(
+                "Pair {}/{} is halted by its circuit breaker until block {}, orders that would trade are not accepted",
+                order.pair.0, order.pair.1, breaker.halted_until
+            ));
+        }
+        Ok(breaker.track_price(&order.pair, price, block_height))
+    }
+
+    /// Executes orders on several pairs atomically: if any of them fails, no event is returned.
+    /// Each order executes against the state left by the previous ones, so that funds received
+    /// on one pair can be spent on the next one.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn execute_orders(
+        &self,
+        user_info: &UserInfo,
+        orders: &[Order],
+        block_height: u64,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        if orders.is_empty() {
+            return Err("No order to execute".to_string());
+        }
+        if orders.len() > MAX_ATOMIC_ORDERS {
+            return Err(format!(
+                "Cannot execute more than {MAX_ATOMIC_ORDERS} orders atomically, got {}",
+                orders.len()
+            ));
+        }
 
-        // Updating balances
-        for (symbol, user_keys) in touched_accounts {
-            let symbol_balances = balance_changes
-                .get(&symbol)
-                .ok_or_else(|| format!("{symbol} not found in balance_changes"))?;
+        let mut pairs: HashSet<&Pair> = HashSet::new();
+        let mut order_ids: HashSet<&OrderId> = HashSet::new();
+        for order in orders {
+            if !pairs.insert(&order.pair) {
+                return Err(format!(
+                    "Atomic orders must target distinct pairs, got several orders on {}/{}",
+                    order.pair.0, order.pair.1
+                ));
+            }
+            if !order_ids.insert(&order.order_id) {
+                return Err(format!("Order {} is placed twice", order.order_id));
+            }
+        }
 
-            for user_key in user_keys {
-                let amount = symbol_balances.get(&user_key).ok_or_else(|| {
-                    format!(
-                        "User with key {} not found in balance_changes for {symbol}",
-                        hex::encode(user_key.as_slice())
-                    )
-                })?;
+        // Orders are executed on a scratch copy of the state, dropped as soon as one of them fails
+        let mut scratch = self.clone();
+        let mut events = Vec::new();
+        for order in orders {
+            let order_events: Vec<OrderbookEvent> = scratch
+                .execute_order(user_info, order.clone(), block_height)
+                .map_err(|e| format!("Order {} failed: {e}", order.order_id))?
+                .into_iter()
+                // The nonce is incremented once for the whole batch
+                .filter(|event| !matches!(event, OrderbookEvent::NonceIncremented { .. }))
+                .collect();
+            scratch.apply_events_preserving_zeroed_orders(user_info, &order_events)?;
+            events.extend(order_events);
+        }
 
-                let user_name = user_names
-                    .get(&user_key)
-                    .ok_or_else(|| {
-                        format!(
-                            "User name for key {} not found",
-                            hex::encode(user_key.as_slice())
-                        )
-                    })?
-                    .clone();
+        events.push(Self::nonce_increment_event(user_info)?);
 
-                events.push(OrderbookEvent::BalanceUpdated {
-                    user: user_name,
-                    symbol: symbol.clone(),
-                    amount: amount.0,
-                });
+        Ok(events)
+    }
+
+    /// Sells the dust balances of the user for `quote` with market orders on the spot pairs of
+    /// the swept assets, all of them or none. A balance is dust while it is worth less than one
+    /// unit of `quote` at the best bid of its pair.
+    #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
+    pub fn sweep_dust(
+        &self,
+        user_info: &UserInfo,
+        quote: &Symbol,
+        sweeps: &[DustSweep],
+        block_height: u64,
+    ) -> Result<Vec<OrderbookEvent>, String> {
+        let quote_unit = self
+            .assets_info
+            .get(quote)
+            .map(|info| POW10[info.scale as usize])
+            .ok_or(format!("Asset info for {quote} not found"))?;
+
+        let mut orders = Vec::with_capacity(sweeps.len());
+        for sweep in sweeps {
+            let symbol = &sweep.symbol;
+            let pair = (symbol.clone(), quote.clone());
+            if !self.pairs_status.contains_key(&pair) || self.perp_markets.contains_key(&pair) {
+                return Err(format!(
+                    "Could not sweep {symbol}: {symbol}/{quote} is not a spot pair"
+                ));
+            }
+            let balance = self.get_balance(user_info, symbol).0;
+            if balance == 0 {
+                return Err(format!(
+                    "Could not sweep {symbol}: user {} has no {symbol}",
+                    user_info.user
+                ));
+            }
+            let best_bid = self
+                .order_manager
+                .bid_orders
+                .get(&pair)
+                .and_then(|levels| levels.keys().next_back().copied())
+                .ok_or_else(|| format!("Could not sweep {symbol}: no bid on {symbol}/{quote}"))?;
+            let value = balance as u128 * best_bid as u128 / self.base_scale(&pair)? as u128;
+            if value >= quote_unit as u128 {
+                return Err(format!(
+                    "Could not sweep {symbol}: a balance of {balance} is worth {value} {quote}, it is not dust"
+                ));
             }
+            orders.push(Order {
+                order_id: sweep.order_id.clone(),
+                order_type: OrderType::Market,
+                order_side: OrderSide::Ask,
+                price: None,
+                pair,
+                quantity: balance,
+            });
         }
 
-        events.push(Self::nonce_increment_event(user_info)?);
+        let mut events = self.execute_orders(user_info, &orders, block_height)?;
+
+        let quote_balance = self.get_balance(user_info, quote).0;
+        let swept_quote_balance = events
+            .iter()
+            .rev()
+            .find_map(|event| match event {
+                OrderbookEvent::BalanceUpdated {
+                    user,
+                    symbol,
+                    amount,
+                } if user == &user_info.user && symbol == quote => Some(*amount),
+                _ => None,
+            })
+            .unwrap_or(quote_balance);
+        // The nonce increment stays the last event of the action
+        let nonce_event = events.pop().ok_or("No event for the sweep")?;
+        events.push(OrderbookEvent::DustSwept {
+            user: user_info.user.clone(),
+            quote: quote.clone(),
+            symbols: sweeps.iter().map(|sweep| sweep.symbol.clone()).collect(),
+            proceeds: swept_quote_balance.saturating_sub(quote_balance),
+        });
+        events.push(nonce_event);
 
         Ok(events)
     }
@@ -1029,6 +3652,35 @@ impl ExecuteState {
                 .any(|info| &info.contract_name == contract_name)
     }
 
+    fn user_orders(&self, user_key: &H256) -> Vec<Order> {
+        self.order_manager
+            .orders_owner
+            .iter()
+            .filter_map(|(order_id, owner_key)| {
+                if owner_key == user_key {
+                    self.order_manager.orders.get(order_id)
+                } else {
+                    None
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Balances a user escapes with: their balances, plus the quantity of their open orders
+    pub fn escape_balances(&self, user_key: &H256) -> HashMap<Symbol, Balance> {
+        let mut user_balances = self.get_user_balances(user_key);
+        for order in self.user_orders(user_key) {
+            let required_symbol = match &order.order_side {
+                OrderSide::Bid => order.pair.1.clone(),
+                OrderSide::Ask => order.pair.0.clone(),
+            };
+            let user_balance = user_balances.entry(required_symbol).or_default();
+            *user_balance = Balance(user_balance.0 + order.quantity);
+        }
+        user_balances
+    }
+
     pub fn escape(
         &self,
         last_block_number: &BlockHeight,
@@ -1050,38 +3702,13 @@ impl ExecuteState {
 
         let mut events = Vec::new();
 
-        // Keep track of user's balance for each token
-        let mut user_balances = self.get_user_balances(&user_info.get_key());
-
         // Find and cancel all orders that belong to this user and cancel them
-        let user_orders = self
-            .order_manager
-            .orders_owner
-            .iter()
-            .filter_map(|(order_id, owner_key)| {
-                if owner_key == &user_info.get_key() {
-                    self.order_manager.orders.get(order_id)
-                } else {
-                    None
-                }
-            })
-            .cloned()
-            .collect::<Vec<_>>();
-
-        for order in user_orders {
-            // Cancel order
+        for order in self.user_orders(&user_info.get_key()) {
             events.extend(self.order_manager.cancel_order_dry_run(&order.order_id)?);
-            let required_symbol = match &order.order_side {
-                OrderSide::Bid => order.pair.1.clone(),
-                OrderSide::Ask => order.pair.0.clone(),
-            };
-            // Virtually refund user
-            let user_balance = user_balances.entry(required_symbol).or_default();
-            *user_balance = Balance(user_balance.0 + order.quantity);
         }
 
-        // Update all balances in the SMT
-        for (symbol, balance) in user_balances {
+        // Update all balances in the SMT, the cancelled orders virtually refunded
+        for (symbol, balance) in self.escape_balances(&user_info.get_key()) {
             // Remove all balance from user
             events.push(OrderbookEvent::BalanceUpdated {
                 user: user_info.user.clone(),
@@ -1137,6 +3764,7 @@ impl ExecuteState {
     }
 }
 
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 #[derive(
     Debug,
     Default,
@@ -1153,6 +3781,7 @@ impl ExecuteState {
 )]
 pub struct Balance(pub u64);
 
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
 #[derive(
     BorshSerialize,
     BorshDeserialize,
@@ -1171,6 +3800,296 @@ pub struct UserInfo {
     pub salt: Vec<u8>,
     pub nonce: u32,
     pub session_keys: Vec<Vec<u8>>,
+    /// Restrictions of the session keys that are not allowed to perform every action.
+    /// A key listed in `session_keys` without a scope here is unrestricted.
+    pub session_key_scopes: Vec<SessionKeyScope>,
+    /// Trading fees applied to the user, assigned by the operator
+    pub fee_tier: FeeTier,
+    /// Amounts withdrawn during the latest window of each limited asset, sorted by symbol
+    pub withdrawal_windows: Vec<WithdrawalWindow>,
+    /// Open positions on perp markets, sorted by pair
+    pub positions: Vec<Position>,
+    /// Amounts borrowed against the margin accounts, sorted by symbol
+    pub debts: Vec<Debt>,
+    /// Large withdrawals waiting in the queue, sorted by id
+    pub pending_withdrawals: Vec<PendingWithdrawal>,
+    /// Set on the sub-accounts registered by their parent identity, whose key is derived from
+    /// the key of their parent. Identities containing the separator that were registered before
+    /// sub-accounts existed are not sub-accounts, and keep their key.
+    pub sub_account: bool,
+}
+
+impl UserInfo {
+    pub fn get_session_key_scope(&self, pubkey: &[u8]) -> Option<&SessionKeyScope> {
+        self.session_key_scopes
+            .iter()
+            .find(|scope| scope.public_key == pubkey)
+    }
+
+    pub fn get_withdrawal_window(&self, symbol: &str) -> Option<&WithdrawalWindow> {
+        self.withdrawal_windows
+            .iter()
+            .find(|window| window.symbol == symbol)
+    }
+
+    /// Replaces the window of the same symbol, keeping windows sorted so that the
+    /// committed user info does not depend on the order withdrawals happened in
+    pub fn set_withdrawal_window(&mut self, window: WithdrawalWindow) {
+        match self
+            .withdrawal_windows
+            .binary_search_by(|existing| existing.symbol.cmp(&window.symbol))
+        {
+            Ok(index) => self.withdrawal_windows[index] = window,
+            Err(index) => self.withdrawal_windows.insert(index, window),
+        }
+    }
+
+    pub fn get_position(&self, pair: &Pair) -> Option<&Position> {
+        self.positions
+            .iter()
+            .find(|position| &position.pair == pair)
+    }
+
+    /// Replaces the position on the same pair, removing it once closed, and keeping positions
+    /// sorted like the withdrawal windows
+    pub fn set_position(&mut self, position: Position) {
+        match self
+            .positions
+            .binary_search_by(|existing| existing.pair.cmp(&position.pair))
+        {
+            Ok(index) if position.size == 0 => {
+                self.positions.remove(index);
+            }
+            Ok(index) => self.positions[index] = position,
+            Err(_) if position.size == 0 => {}
+            Err(index) => self.positions.insert(index, position),
+        }
+    }
+
+    pub fn get_debt(&self, symbol: &str) -> u64 {
+        self.debts
+            .iter()
+            .find(|debt| debt.symbol == symbol)
+            .map_or(0, |debt| debt.amount)
+    }
+
+    pub fn get_pending_withdrawal(&self, id: u32) -> Option<&PendingWithdrawal> {
+        self.pending_withdrawals
+            .iter()
+            .find(|withdrawal| withdrawal.id == id)
+    }
+
+    /// Replaces the pending withdrawal of the same id, keeping the queue sorted
+    pub fn set_pending_withdrawal(&mut self, withdrawal: PendingWithdrawal) {
+        match self
+            .pending_withdrawals
+            .binary_search_by(|existing| existing.id.cmp(&withdrawal.id))
+        {
+            Ok(index) => self.pending_withdrawals[index] = withdrawal,
+            Err(index) => self.pending_withdrawals.insert(index, withdrawal),
+        }
+    }
+
+    pub fn remove_pending_withdrawal(&mut self, id: u32) {
+        self.pending_withdrawals
+            .retain(|withdrawal| withdrawal.id != id);
+    }
+
+    /// Replaces the debt of the same symbol, removing it once repaid
+    pub fn set_debt(&mut self, debt: Debt) {
+        match self
+            .debts
+            .binary_search_by(|existing| existing.symbol.cmp(&debt.symbol))
+        {
+            Ok(index) if debt.amount == 0 => {
+                self.debts.remove(index);
+            }
+            Ok(index) => self.debts[index] = debt,
+            Err(_) if debt.amount == 0 => {}
+            Err(index) => self.debts.insert(index, debt),
+        }
+    }
+
+    /// Quote assets of the margin accounts the user has a position or a debt in
+    pub fn margin_quotes(&self) -> BTreeSet<Symbol> {
+        self.positions
+            .iter()
+            .map(|position| position.pair.1.clone())
+            .chain(self.debts.iter().map(|debt| debt.symbol.clone()))
+            .collect()
+    }
+}
+
+/// Amount of an asset withdrawn by a user during the window starting at block `window_start`
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct WithdrawalWindow {
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
+    pub symbol: Symbol,
+    pub window_start: u64,
+    pub withdrawn: u64,
+}
+
+/// Withdrawal of a user waiting in the queue until block `available_at`, and until it is approved
+/// by the operator. Its amount is already deducted from the balance.
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct PendingWithdrawal {
+    /// Nonce of the user when the withdrawal was requested
+    pub id: u32,
+    #[cfg_attr(feature = "utoipa", schema(value_type = String))]
+    pub symbol: Symbol,
+    pub amount: u64,
+    pub destination: WithdrawDestination,
+    pub available_at: u64,
+    pub approved: bool,
+}
+
+/// Maximum fee that can be charged on a fill, in basis points
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// Fee rates of a user, in basis points of the amount received on each fill.
+/// The default tier charges no fee.
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Default,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct FeeTier {
+    pub tier: u8,
+    pub maker_fee_bps: u16,
+    pub taker_fee_bps: u16,
+}
+
+impl FeeTier {
+    pub fn maker_fee(&self, amount: u64) -> u64 {
+        Self::fee(amount, self.maker_fee_bps)
+    }
+
+    pub fn taker_fee(&self, amount: u64) -> u64 {
+        Self::fee(amount, self.taker_fee_bps)
+    }
+
+    fn fee(amount: u64, bps: u16) -> u64 {
+        // bps <= 10_000 so the result always fits in a u64
+        ((amount as u128 * bps as u128) / 10_000) as u64
+    }
+}
+
+/// Bitmask of the actions a session key is allowed to sign
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Debug,
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct SessionKeyPermissions(pub u8);
+
+impl SessionKeyPermissions {
+    pub const NONE: Self = Self(0);
+    pub const CREATE_ORDER: Self = Self(1 << 0);
+    pub const CANCEL: Self = Self(1 << 1);
+    pub const WITHDRAW: Self = Self(1 << 2);
+
+    /// Can create and cancel orders, but not withdraw funds
+    pub const TRADE_ONLY: Self = Self(Self::CREATE_ORDER.0 | Self::CANCEL.0);
+    /// Can only cancel existing orders
+    pub const CANCEL_ONLY: Self = Self::CANCEL;
+    /// Can perform every permissioned action, including withdrawals
+    pub const ALL: Self = Self(Self::CREATE_ORDER.0 | Self::CANCEL.0 | Self::WITHDRAW.0);
+
+    pub fn contains(self, other: SessionKeyPermissions) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 & Self::ALL.0 == 0
+    }
+}
+
+impl Default for SessionKeyPermissions {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl std::str::FromStr for SessionKeyPermissions {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "trade_only" | "trade" => Ok(Self::TRADE_ONLY),
+            "cancel_only" | "cancel" => Ok(Self::CANCEL_ONLY),
+            "withdraw_allowed" | "withdraw" | "all" => Ok(Self::ALL),
+            other => Err(format!("Unknown session key permissions: {other}")),
+        }
+    }
+}
+
+/// Restriction attached to a session key: which actions it can sign and, optionally,
+/// the only pair it is allowed to trade on.
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Debug,
+    Clone,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Serialize,
+    Deserialize,
+)]
+pub struct SessionKeyScope {
+    pub public_key: Vec<u8>,
+    pub permissions: SessionKeyPermissions,
+    #[cfg_attr(feature = "utoipa", schema(value_type = Option<(String, String)>))]
+    pub pair: Option<Pair>,
 }
 
 // To avoid recomputing powers of 10
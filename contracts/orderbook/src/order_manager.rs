@@ -2,24 +2,27 @@ use crate::model::{Order, OrderId, OrderSide, OrderType, OrderbookEvent, Pair};
 use crate::zk::H256;
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::Serialize;
-use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Serialize, BorshSerialize, BorshDeserialize, Default, Debug, Clone, PartialEq, Eq)]
 pub struct OrderManager {
     // All orders indexed by order_id
     pub orders: HashMap<OrderId, Order>,
     // Buy orders sorted by price for each token pair
-    pub bid_orders: HashMap<Pair, BTreeMap<u64, VecDeque<OrderId>>>,
+    pub bid_orders: HashMap<Pair, BTreeMap<u64, PriceLevel>>,
     // Ask orders sorted by price for each token pair
-    pub ask_orders: HashMap<Pair, BTreeMap<u64, VecDeque<OrderId>>>,
+    pub ask_orders: HashMap<Pair, BTreeMap<u64, PriceLevel>>,
 
     // Mapping of order IDs to their owners
     pub orders_owner: HashMap<OrderId, H256>,
 }
 
+mod price_level;
 #[cfg(test)]
 mod tests;
 
+pub use price_level::PriceLevel;
+
 impl OrderManager {
     pub fn new() -> Self {
         Self::default()
@@ -39,7 +42,7 @@ impl OrderManager {
             .unwrap_or(0)
     }
 
-    pub fn side_map(&self, side: &OrderSide) -> &HashMap<Pair, BTreeMap<u64, VecDeque<OrderId>>> {
+    pub fn side_map(&self, side: &OrderSide) -> &HashMap<Pair, BTreeMap<u64, PriceLevel>> {
         match side {
             OrderSide::Bid => &self.bid_orders,
             OrderSide::Ask => &self.ask_orders,
@@ -49,7 +52,7 @@ impl OrderManager {
     pub fn side_map_mut(
         &mut self,
         side: &OrderSide,
-    ) -> &mut HashMap<Pair, BTreeMap<u64, VecDeque<OrderId>>> {
+    ) -> &mut HashMap<Pair, BTreeMap<u64, PriceLevel>> {
         match side {
             OrderSide::Bid => &mut self.bid_orders,
             OrderSide::Ask => &mut self.ask_orders,
@@ -62,7 +65,7 @@ impl OrderManager {
         side: &OrderSide,
         pair: Pair,
         price: u64,
-    ) -> &mut VecDeque<OrderId> {
+    ) -> &mut PriceLevel {
         self.side_map_mut(side)
             .entry(pair)
             .or_default()
@@ -115,23 +118,23 @@ impl OrderManager {
 
         #[cfg(feature = "instrumentation")]
         let span = sdk::tracing::span!(sdk::tracing::Level::INFO, "get_counter_orders").entered();
-        let counter_orders: Box<dyn Iterator<Item = (&u64, &VecDeque<OrderId>)>> =
-            match counter_orders_map {
-                Some(orders) => match order.order_side {
-                    OrderSide::Bid => Box::new(orders.iter()),
-                    OrderSide::Ask => Box::new(orders.iter().rev()),
-                },
-                None => {
-                    return if order.order_type == OrderType::Limit {
-                        Self::simulate_insert_order(order)
-                    } else {
-                        Err(format!(
-                            "No matching {:?} orders for market order {}",
-                            order.order_side, order.order_id
-                        ))
-                    };
-                }
-            };
+        let counter_orders: Box<dyn Iterator<Item = (&u64, &PriceLevel)>> = match counter_orders_map
+        {
+            Some(orders) => match order.order_side {
+                OrderSide::Bid => Box::new(orders.iter()),
+                OrderSide::Ask => Box::new(orders.iter().rev()),
+            },
+            None => {
+                return if order.order_type == OrderType::Limit {
+                    Self::simulate_insert_order(order)
+                } else {
+                    Err(format!(
+                        "No matching {:?} orders for market order {}",
+                        order.order_side, order.order_id
+                    ))
+                };
+            }
+        };
         #[cfg(feature = "instrumentation")]
         span.exit();
 
@@ -311,7 +314,7 @@ impl OrderManager {
                     let order_list =
                         self.get_order_list_mut(&order.order_side, order.pair.clone(), price);
                     // We shall not remove empty price levels from the orderbook here, as it will be needed for computing SMT root later
-                    order_list.retain(|id| id != order_id);
+                    order_list.remove(order_id);
                 }
 
                 // We shall not remove order from the orderbook here, as it will be needed for computing SMT root later
@@ -349,7 +352,7 @@ impl OrderManager {
                         self.get_order_list_mut(&order.order_side, order.pair.clone(), price);
 
                     // We shall not remove empty price levels from the orderbook here, as it will be needed for computing SMT root later
-                    order_list.retain(|id| id != order_id);
+                    order_list.remove(order_id);
                 }
 
                 // We shall not remove order from the orderbook here, as it will be needed for computing SMT root later
@@ -395,17 +398,15 @@ impl OrderManager {
                         continue;
                     }
 
-                    if let Some(stored_order) = self.orders.get(order_id).cloned() {
-                        self.clean_empty_price_levels(&stored_order.order_side, &stored_order.pair);
-                        self.orders.remove(order_id);
+                    if let Some(stored_order) = self.orders.remove(order_id) {
+                        self.clean_empty_price_level(&stored_order);
                     }
 
                     self.orders_owner.remove(order_id);
                 }
                 OrderbookEvent::OrderCancelled { order_id, .. } => {
-                    if let Some(stored_order) = self.orders.get(order_id).cloned() {
-                        self.clean_empty_price_levels(&stored_order.order_side, &stored_order.pair);
-                        self.orders.remove(order_id);
+                    if let Some(stored_order) = self.orders.remove(order_id) {
+                        self.clean_empty_price_level(&stored_order);
                     }
 
                     self.orders_owner.remove(order_id);
@@ -417,18 +418,23 @@ impl OrderManager {
 }
 
 impl OrderManager {
-    fn clean_empty_price_levels(&mut self, side: &OrderSide, pair: &Pair) {
-        let side_book = self.side_map_mut(side);
-        let should_remove_pair = if let Some(price_levels) = side_book.get_mut(pair) {
-            // Remove empty price levels
-            price_levels.retain(|_, order_ids| !order_ids.is_empty());
+    /// Removes the price level of a removed order once empty, and its pair once it has no level
+    fn clean_empty_price_level(&mut self, order: &Order) {
+        let Some(price) = order.price else {
+            return;
+        };
+        let side_book = self.side_map_mut(&order.order_side);
+        let should_remove_pair = if let Some(price_levels) = side_book.get_mut(&order.pair) {
+            if price_levels.get(&price).is_some_and(PriceLevel::is_empty) {
+                price_levels.remove(&price);
+            }
             price_levels.is_empty()
         } else {
             false
         };
 
         if should_remove_pair {
-            side_book.remove(pair);
+            side_book.remove(&order.pair);
         }
     }
 
@@ -450,8 +456,8 @@ impl OrderManager {
     /// Helper function to compare order maps and generate diff entries
     fn diff_order_maps(
         &self,
-        self_orders: &HashMap<Pair, BTreeMap<u64, VecDeque<OrderId>>>,
-        other_orders: &HashMap<Pair, BTreeMap<u64, VecDeque<OrderId>>>,
+        self_orders: &HashMap<Pair, BTreeMap<u64, PriceLevel>>,
+        other_orders: &HashMap<Pair, BTreeMap<u64, PriceLevel>>,
         field_name: &str,
     ) -> BTreeMap<String, String> {
         let mut diff = BTreeMap::new();
@@ -550,7 +556,7 @@ impl OrderManager {
 
         // Remove from order lists
         let order_list = self.get_order_list_mut(&order.order_side, order.pair.clone(), price);
-        order_list.retain(|id| id != order_id);
+        order_list.remove(order_id);
 
         if order_list.is_empty() {
             self.side_map_mut(&order.order_side)
@@ -0,0 +1,305 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{BuildHasherDefault, Hash, Hasher},
+};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{ser::SerializeSeq, Serialize, Serializer};
+
+use crate::model::OrderId;
+
+#[derive(Debug, Clone)]
+struct Slot {
+    order_id: OrderId,
+    prev: Option<u32>,
+    next: Option<u32>,
+    /// Next slot whose order id has the same fingerprint
+    collision: Option<u32>,
+}
+
+/// 64-bit fingerprint of an order id, the key of the index of a level
+fn fingerprint(order_id: &OrderId) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    order_id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Fingerprints are hashes already, so they key the index as they are
+#[derive(Default)]
+struct FingerprintHasher(u64);
+
+impl Hasher for FingerprintHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(*byte);
+        }
+    }
+
+    fn write_u64(&mut self, fingerprint: u64) {
+        self.0 = fingerprint;
+    }
+}
+
+/// Orders resting at a price, in time priority.
+///
+/// An intrusive doubly-linked list over a slab of slots, indexed by the fingerprint of the order
+/// ids: orders are appended, removed from anywhere in the queue and looked up in O(1). The order
+/// id is only stored in its slot, and slots of colliding fingerprints are chained. Slots freed
+/// by removed orders are reused by the next ones, so the slab never outgrows the deepest the
+/// level was.
+///
+/// Serialized as the sequence of its order ids, front first, as the queue it replaces: the
+/// borsh encoding, and so the state commitment, is unchanged.
+#[derive(Clone, Default)]
+pub struct PriceLevel {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    /// First slot of each fingerprint
+    index: HashMap<u64, u32, BuildHasherDefault<FingerprintHasher>>,
+    len: usize,
+    head: Option<u32>,
+    tail: Option<u32>,
+}
+
+impl PriceLevel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, order_id: &OrderId) -> bool {
+        self.find(order_id, fingerprint(order_id)).is_some()
+    }
+
+    /// Order with the time priority
+    pub fn front(&self) -> Option<&OrderId> {
+        self.head.map(|slot| &self.slots[slot as usize].order_id)
+    }
+
+    pub fn back(&self) -> Option<&OrderId> {
+        self.tail.map(|slot| &self.slots[slot as usize].order_id)
+    }
+
+    /// Order ids, front first
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            level: self,
+            next: self.head,
+            remaining: self.len(),
+        }
+    }
+
+    /// Queues an order last. An order already in the level moves to the back.
+    pub fn push_back(&mut self, order_id: OrderId) {
+        self.remove(&order_id);
+        let slot = self.alloc(order_id, self.tail, None);
+        match self.tail {
+            Some(tail) => self.slots[tail as usize].next = Some(slot),
+            None => self.head = Some(slot),
+        }
+        self.tail = Some(slot);
+    }
+
+    /// Queues an order first. An order already in the level moves to the front.
+    pub fn push_front(&mut self, order_id: OrderId) {
+        self.remove(&order_id);
+        let slot = self.alloc(order_id, None, self.head);
+        match self.head {
+            Some(head) => self.slots[head as usize].prev = Some(slot),
+            None => self.tail = Some(slot),
+        }
+        self.head = Some(slot);
+    }
+
+    pub fn pop_front(&mut self) -> Option<OrderId> {
+        let order_id = self.front()?.clone();
+        self.remove(&order_id);
+        Some(order_id)
+    }
+
+    /// Unlinks an order, wherever it is in the queue. Returns whether it was in the level.
+    pub fn remove(&mut self, order_id: &OrderId) -> bool {
+        let fingerprint = fingerprint(order_id);
+        let Some(slot) = self.find(order_id, fingerprint) else {
+            return false;
+        };
+        let collision = self.slots[slot as usize].collision;
+        if self.index.get(&fingerprint) == Some(&slot) {
+            match collision {
+                Some(collision) => self.index.insert(fingerprint, collision),
+                None => self.index.remove(&fingerprint),
+            };
+        } else {
+            let mut chained = self.index[&fingerprint];
+            while self.slots[chained as usize].collision != Some(slot) {
+                chained = self.slots[chained as usize]
+                    .collision
+                    .expect("a found slot is chained to its fingerprint");
+            }
+            self.slots[chained as usize].collision = collision;
+        }
+
+        let (prev, next) = (
+            self.slots[slot as usize].prev,
+            self.slots[slot as usize].next,
+        );
+        match prev {
+            Some(prev) => self.slots[prev as usize].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.slots[next as usize].prev = prev,
+            None => self.tail = prev,
+        }
+
+        let freed = &mut self.slots[slot as usize];
+        freed.order_id = OrderId::new();
+        freed.prev = None;
+        freed.next = None;
+        freed.collision = None;
+        self.free.push(slot);
+        self.len -= 1;
+        true
+    }
+
+    /// Slot of an order, among those of its fingerprint
+    fn find(&self, order_id: &OrderId, fingerprint: u64) -> Option<u32> {
+        let mut slot = self.index.get(&fingerprint).copied();
+        while let Some(candidate) = slot {
+            let candidate_slot = &self.slots[candidate as usize];
+            if candidate_slot.order_id == *order_id {
+                return Some(candidate);
+            }
+            slot = candidate_slot.collision;
+        }
+        None
+    }
+
+    fn alloc(&mut self, order_id: OrderId, prev: Option<u32>, next: Option<u32>) -> u32 {
+        let fingerprint = fingerprint(&order_id);
+        let slot = Slot {
+            order_id,
+            prev,
+            next,
+            collision: None,
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index as usize] = slot;
+                index
+            }
+            None => {
+                self.slots.push(slot);
+                (self.slots.len() - 1) as u32
+            }
+        };
+        self.slots[index as usize].collision = self.index.insert(fingerprint, index);
+        self.len += 1;
+        index
+    }
+}
+
+pub struct Iter<'a> {
+    level: &'a PriceLevel,
+    next: Option<u32>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a OrderId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = &self.level.slots[self.next? as usize];
+        self.next = slot.next;
+        self.remaining -= 1;
+        Some(&slot.order_id)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl<'a> IntoIterator for &'a PriceLevel {
+    type Item = &'a OrderId;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<OrderId> for PriceLevel {
+    fn from_iter<I: IntoIterator<Item = OrderId>>(order_ids: I) -> Self {
+        let mut level = PriceLevel::new();
+        for order_id in order_ids {
+            level.push_back(order_id);
+        }
+        level
+    }
+}
+
+impl From<Vec<OrderId>> for PriceLevel {
+    fn from(order_ids: Vec<OrderId>) -> Self {
+        order_ids.into_iter().collect()
+    }
+}
+
+impl<const N: usize> From<[OrderId; N]> for PriceLevel {
+    fn from(order_ids: [OrderId; N]) -> Self {
+        order_ids.into_iter().collect()
+    }
+}
+
+impl PartialEq for PriceLevel {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for PriceLevel {}
+
+impl std::fmt::Debug for PriceLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Serialize for PriceLevel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for order_id in self.iter() {
+            seq.serialize_element(order_id)?;
+        }
+        seq.end()
+    }
+}
+
+impl BorshSerialize for PriceLevel {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        BorshSerialize::serialize(&(self.len() as u32), writer)?;
+        for order_id in self.iter() {
+            BorshSerialize::serialize(order_id, writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for PriceLevel {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        Ok(Vec::<OrderId>::deserialize_reader(reader)?.into())
+    }
+}
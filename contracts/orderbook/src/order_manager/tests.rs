@@ -5,20 +5,26 @@ use std::collections::BTreeMap;
 use borsh::BorshSerialize;
 use k256::ecdsa::signature::DigestSigner;
 use k256::ecdsa::{Signature, SigningKey};
-use sha3::{Digest, Sha3_256};
+use sha3::{Digest, Keccak256, Sha3_256};
 
 use crate::model::WithdrawDestination;
 use crate::zk::smt::GetKey;
 use crate::{
     model::{
-        AssetInfo, Balance, ExecuteState, Order, OrderSide, OrderType, OrderbookEvent, Pair,
-        PairInfo, UserInfo,
+        AssetInfo, Balance, CircuitBreaker, CollateralConfig, Debt, DustSweep, ExecuteState,
+        FeeTier, MarginAccount, MarketStatus, Order, OrderSide, OrderType, OrderbookEvent, Pair,
+        PairInfo, PendingWithdrawal, PerpConfig, Position, SessionKeyPermissions, UserInfo,
+        WithdrawDelay, WithdrawLimit,
     },
     transaction::{
-        AddSessionKeyPrivateInput, CreateOrderPrivateInput, PermissionedOrderbookAction,
+        AddSessionKeyPrivateInput, CancelOnDisconnectPrivateInput, CancelOrderPrivateInput,
+        CreateOrderPrivateInput, OnboardUsersPrivateInput, OnboardedUser,
+        PermissionedOrderbookAction, TransferPrivateInput, UpdateFeeTiersPrivateInput,
         WithdrawPrivateInput,
     },
+    utils::SignedAction,
     zk::FullState,
+    INSURANCE_FUND_IDENTITY, ORDERBOOK_ACCOUNT_IDENTITY,
 };
 use sdk::{BlockHeight, ContractName, LaneId};
 
@@ -110,6 +116,20 @@ impl TestSigner {
         let signature: Signature = self.signing_key.sign_digest(hasher);
         signature.to_vec()
     }
+
+    fn eth_address(&self) -> Vec<u8> {
+        Keccak256::digest(&self.public_key[1..])[12..].to_vec()
+    }
+
+    fn sign_typed(&self, action: &SignedAction) -> Vec<u8> {
+        let (signature, recovery_id) = self
+            .signing_key
+            .sign_prehash_recoverable(&crate::eip712::signing_hash(action))
+            .expect("sign typed data");
+        let mut signature = signature.to_vec();
+        signature.push(recovery_id.to_byte() + 27);
+        signature
+    }
 }
 
 fn serialize<T: BorshSerialize>(value: &T) -> Vec<u8> {
@@ -119,12 +139,23 @@ fn serialize<T: BorshSerialize>(value: &T) -> Vec<u8> {
 fn apply_user_updates(user: &mut UserInfo, events: &[OrderbookEvent]) {
     for event in events {
         match event {
-            OrderbookEvent::SessionKeyAdded { session_keys, .. } => {
+            OrderbookEvent::SessionKeyAdded {
+                session_keys,
+                session_key_scopes,
+                ..
+            } => {
                 user.session_keys = session_keys.clone();
+                user.session_key_scopes = session_key_scopes.clone();
             }
             OrderbookEvent::NonceIncremented { nonce, .. } => {
                 user.nonce = *nonce;
             }
+            OrderbookEvent::WithdrawalRecorded { window, .. } => {
+                user.set_withdrawal_window(window.clone());
+            }
+            OrderbookEvent::WithdrawalQueued { withdrawal, .. } => {
+                user.set_pending_withdrawal(withdrawal.clone());
+            }
             _ => {}
         }
     }
@@ -169,6 +200,8 @@ fn add_session_key_registers_new_key() {
 
     let private_input = serialize(&AddSessionKeyPrivateInput {
         new_public_key: key.clone(),
+        permissions: SessionKeyPermissions::ALL,
+        pair: None,
     });
     let events = execute_action_ok(
         &mut orderbook,
@@ -195,6 +228,8 @@ fn add_session_key_registers_new_key() {
             PermissionedOrderbookAction::AddSessionKey,
             &serialize(&AddSessionKeyPrivateInput {
                 new_public_key: key,
+                permissions: SessionKeyPermissions::ALL,
+                pair: None,
             }),
         )
         .expect_err("duplicate keys must fail");
@@ -320,187 +355,3272 @@ fn deposit_updates_balance_and_event() {
 
     execute_action_ok(
         &mut orderbook,
-        &mut user,
-        PermissionedOrderbookAction::CreatePair {
-            pair: pair.clone(),
-            info: make_pair_info(&pair, 3, 2),
-        },
+        &mut user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey {},
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Deposit {
+            symbol: pair.1.clone(),
+            amount: 500,
+        },
+        Vec::new(),
+    );
+
+    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 500);
+    assert_eq!(events.len(), 1);
+    assert!(matches!(
+        events[0],
+        OrderbookEvent::BalanceUpdated { ref user, ref symbol, amount }
+            if user == "bob" && symbol == &pair.1 && amount == 500
+    ));
+}
+
+#[test]
+fn withdraw_deducts_balance() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("carol");
+    let signer = TestSigner::new(2);
+    let session_key = signer.public_key.clone();
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Deposit {
+            symbol: pair.1.clone(),
+            amount: 1_000,
+        },
+        Vec::new(),
+    );
+
+    let destination = WithdrawDestination {
+        network: "hyli".to_string(),
+        address: "dest-address".to_string(),
+    };
+    let withdraw_message = format!("{}:{}:withdraw:{}:{}", user.user, user.nonce, pair.1, 400);
+    let withdraw_events = execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Withdraw {
+            symbol: pair.1.clone(),
+            amount: 400,
+            destination: destination.clone(),
+            block_height: 0,
+        },
+        serialize(&WithdrawPrivateInput {
+            signature: signer.sign(&withdraw_message),
+            public_key: session_key.clone(),
+        }),
+    );
+
+    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 600);
+    assert_eq!(withdraw_events.len(), 2);
+    assert!(matches!(
+        withdraw_events[0],
+        OrderbookEvent::BalanceUpdated { ref user, ref symbol, amount }
+            if user == "carol" && symbol == &pair.1 && amount == 600
+    ));
+
+    let overdraft_message = format!("{}:{}:withdraw:{}:{}", user.user, user.nonce, pair.1, 700);
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::Withdraw {
+            symbol: pair.1.clone(),
+            amount: 700,
+            destination,
+            block_height: 0,
+        },
+        serialize(&WithdrawPrivateInput {
+            signature: signer.sign(&overdraft_message),
+            public_key: session_key,
+        }),
+    );
+    assert!(err.contains("Insufficient balance"));
+}
+
+#[test]
+fn transfer_moves_balances_between_accounts() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut sender = test_user("ivy");
+    let mut recipient = test_user("jon");
+    let signer = TestSigner::new(33);
+    let trade_only_signer = TestSigner::new(34);
+
+    let mut add_session_key =
+        |user: &mut UserInfo, signer: &TestSigner, permissions: SessionKeyPermissions| {
+            execute_action_ok(
+                &mut orderbook,
+                user,
+                PermissionedOrderbookAction::AddSessionKey,
+                serialize(&AddSessionKeyPrivateInput {
+                    new_public_key: signer.public_key.clone(),
+                    permissions,
+                    pair: None,
+                }),
+            );
+        };
+    add_session_key(&mut sender, &signer, SessionKeyPermissions::ALL);
+    add_session_key(
+        &mut sender,
+        &trade_only_signer,
+        SessionKeyPermissions::TRADE_ONLY,
+    );
+    add_session_key(
+        &mut recipient,
+        &TestSigner::new(35),
+        SessionKeyPermissions::ALL,
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut sender,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut sender,
+        PermissionedOrderbookAction::Deposit {
+            symbol: pair.1.clone(),
+            amount: 1_000,
+        },
+        Vec::new(),
+    );
+
+    let transfer = |to: &str, amount: u64| PermissionedOrderbookAction::Transfer {
+        to: to.to_string(),
+        symbol: pair.1.clone(),
+        amount,
+    };
+    let signed = |user: &UserInfo, signer: &TestSigner, to: &str, amount: u64| {
+        serialize(&TransferPrivateInput {
+            signature: signer.sign(&format!(
+                "{}:{}:transfer:{to}:{}:{amount}",
+                user.user, user.nonce, pair.1
+            )),
+            public_key: signer.public_key.clone(),
+        })
+    };
+
+    let nonce = sender.nonce;
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut sender,
+        transfer("jon", 300),
+        signed(&sender, &signer, "jon", 300),
+    );
+    assert_eq!(sender.nonce, nonce + 1);
+    assert_eq!(orderbook.state.get_balance(&sender, &pair.1).0, 700);
+    assert_eq!(orderbook.state.get_balance(&recipient, &pair.1).0, 300);
+    assert!(events.iter().any(|event| matches!(
+        event,
+        OrderbookEvent::Transferred { from, to, symbol, amount }
+            if from == "ivy" && to == "jon" && symbol == &pair.1 && *amount == 300
+    )));
+
+    // Keys that cannot withdraw cannot move funds out either
+    let err = execute_action_err(
+        &mut orderbook,
+        &sender,
+        transfer("jon", 100),
+        signed(&sender, &trade_only_signer, "jon", 100),
+    );
+    assert!(err.contains("not allowed"), "{err}");
+
+    for (to, amount, expected) in [
+        ("jon", 800, "Insufficient balance"),
+        ("ivy", 100, "itself"),
+        ("kim", 100, "not registered"),
+        (INSURANCE_FUND_IDENTITY, 100, "reserved"),
+    ] {
+        let err = execute_action_err(
+            &mut orderbook,
+            &sender,
+            transfer(to, amount),
+            signed(&sender, &signer, to, amount),
+        );
+        assert!(err.contains(expected), "{to}: {err}");
+    }
+}
+
+#[test]
+fn cancel_order_refunds_and_removes() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("dan");
+    let signer = TestSigner::new(3);
+    let session_key = signer.public_key.clone();
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+
+    orderbook
+        .state
+        .users_info
+        .insert(user.user.clone(), user.clone());
+    let order = make_limit_order("order-1", OrderSide::Bid, 100, 10);
+
+    orderbook
+        .state
+        .order_manager
+        .insert_order(&order, &user.get_key())
+        .expect("order insertion should succeed");
+
+    let mut balances = BTreeMap::new();
+    balances.insert(user.clone(), Balance(0));
+
+    let cancel_message = format!("{}:{}:cancel:{}", user.user, user.nonce, order.order_id);
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Cancel {
+            order_id: order.order_id.clone(),
+        },
+        serialize(&CancelOrderPrivateInput {
+            signature: signer.sign(&cancel_message),
+            public_key: session_key,
+        }),
+    );
+
+    assert!(orderbook.state.order_manager.orders.is_empty());
+    assert_eq!(orderbook.state.order_manager.count_buy_orders(&pair), 0);
+    assert_eq!(orderbook.state.order_manager.count_sell_orders(&pair), 0);
+    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 10);
+
+    assert!(events.iter().any(|event| matches!(
+        event,
+        OrderbookEvent::OrderCancelled { order_id, .. } if order_id == "order-1"
+    )));
+}
+
+#[test]
+fn cancel_on_disconnect_cancels_all_orders() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("jack");
+    let signer = TestSigner::new(9);
+    let session_key = signer.public_key.clone();
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+
+    orderbook
+        .state
+        .users_info
+        .insert(user.user.clone(), user.clone());
+    for order_id in ["order-1", "order-2"] {
+        orderbook
+            .state
+            .order_manager
+            .insert_order(
+                &make_limit_order(order_id, OrderSide::Bid, 100, 10),
+                &user.get_key(),
+            )
+            .expect("order insertion should succeed");
+    }
+
+    // The session was armed before order-3 was placed, and order-3 is already gone
+    let order_ids = vec![
+        "order-1".to_string(),
+        "order-2".to_string(),
+        "order-3".to_string(),
+    ];
+    let private_input = serialize(&CancelOnDisconnectPrivateInput {
+        signature: signer.sign(&crate::utils::cancel_on_disconnect_message(
+            &user.user, user.nonce, 30, &order_ids,
+        )),
+        public_key: session_key,
+        timeout_secs: 30,
+    });
+
+    // The signature only covers the orders it was given for
+    execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::CancelOnDisconnect {
+            order_ids: order_ids[..2].to_vec(),
+        },
+        private_input.clone(),
+    );
+
+    // The signature is bound to the timeout it was given for
+    let mut wrong_timeout = borsh::from_slice::<CancelOnDisconnectPrivateInput>(&private_input)
+        .expect("deserialize private input");
+    wrong_timeout.timeout_secs = 60;
+    execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::CancelOnDisconnect {
+            order_ids: order_ids.clone(),
+        },
+        serialize(&wrong_timeout),
+    );
+
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CancelOnDisconnect { order_ids },
+        private_input,
+    );
+
+    assert!(orderbook.state.order_manager.orders.is_empty());
+    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 20);
+    // Both refunds are merged in a single balance update
+    assert_eq!(
+        events
+            .iter()
+            .filter(|event| matches!(event, OrderbookEvent::BalanceUpdated { .. }))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn cancel_on_disconnect_cannot_be_replayed() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("jill");
+    let signer = TestSigner::new(19);
+    let session_key = signer.public_key.clone();
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+    orderbook
+        .state
+        .users_info
+        .insert(user.user.clone(), user.clone());
+    let insert_order = |orderbook: &mut FullState, user: &UserInfo| {
+        orderbook
+            .state
+            .order_manager
+            .insert_order(
+                &make_limit_order("order-1", OrderSide::Bid, 100, 10),
+                &user.get_key(),
+            )
+            .expect("order insertion should succeed");
+    };
+    insert_order(&mut orderbook, &user);
+
+    let order_ids = vec!["order-1".to_string()];
+    let private_input = serialize(&CancelOnDisconnectPrivateInput {
+        signature: signer.sign(&crate::utils::cancel_on_disconnect_message(
+            &user.user, user.nonce, 30, &order_ids,
+        )),
+        public_key: session_key,
+        timeout_secs: 30,
+    });
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CancelOnDisconnect {
+            order_ids: order_ids.clone(),
+        },
+        private_input.clone(),
+    );
+
+    // An order with the same id placed again is not cancelled by the consumed signature
+    insert_order(&mut orderbook, &user);
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::CancelOnDisconnect { order_ids },
+        private_input,
+    );
+    assert!(err.contains("signature"), "{err}");
+    assert!(orderbook.state.order_manager.orders.contains_key("order-1"));
+}
+
+#[test]
+fn eip712_signed_order_is_accepted() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("kate");
+    let signer = TestSigner::new(10);
+    let address = signer.eth_address();
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: address.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Deposit {
+            symbol: pair.1.clone(),
+            amount: 1_000,
+        },
+        Vec::new(),
+    );
+
+    let order = make_limit_order("order-1", OrderSide::Bid, 100, 10);
+    let signature = signer.sign_typed(&SignedAction::CreateOrder {
+        user: &user.user,
+        nonce: user.nonce,
+        order: &order,
+    });
+
+    // The typed data covers the whole order, not only its id
+    let mut tampered_order = order.clone();
+    tampered_order.quantity = 20;
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::CreateOrder(tampered_order),
+        serialize(&CreateOrderPrivateInput {
+            signature: signature.clone(),
+            public_key: address.clone(),
+            block_height: 0,
+        }),
+    );
+    assert!(err.contains("Invalid EIP-712 signature"));
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreateOrder(order),
+        serialize(&CreateOrderPrivateInput {
+            signature,
+            public_key: address,
+            block_height: 0,
+        }),
+    );
+    assert!(orderbook.state.order_manager.orders.contains_key("order-1"));
+}
+
+#[cfg(feature = "ed25519")]
+#[test]
+fn ed25519_session_key_signs_orders() {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("liam");
+    let signing_key = SigningKey::from_bytes(&[11; 32]);
+    let session_key = signing_key.verifying_key().to_bytes().to_vec();
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Deposit {
+            symbol: pair.1.clone(),
+            amount: 1_000,
+        },
+        Vec::new(),
+    );
+
+    let order = make_limit_order("order-1", OrderSide::Bid, 100, 10);
+    let message = format!(
+        "{}:{}:create_order:{}",
+        user.user, user.nonce, order.order_id
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreateOrder(order),
+        serialize(&CreateOrderPrivateInput {
+            signature: signing_key.sign(message.as_bytes()).to_bytes().to_vec(),
+            public_key: session_key,
+            block_height: 0,
+        }),
+    );
+    assert!(orderbook.state.order_manager.orders.contains_key("order-1"));
+}
+
+#[cfg(not(feature = "ed25519"))]
+#[test]
+fn ed25519_session_key_requires_feature() {
+    let mut orderbook = build_orderbook();
+    let user = test_user("liam");
+
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: vec![11; 32],
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+    assert!(err.contains("not supported"));
+}
+
+#[cfg(feature = "passkey")]
+#[test]
+fn passkey_session_key_signs_orders() {
+    use crate::webauthn::{self, WebAuthnAssertion};
+    use p256::ecdsa::{signature::Signer, Signature as P256Signature, SigningKey as P256Key};
+    use sha2::Sha256;
+
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("nora");
+    let signing_key = P256Key::from_bytes(&p256::FieldBytes::from([13; 32])).expect("signing key");
+    let session_key = webauthn::passkey_session_key(
+        signing_key
+            .verifying_key()
+            .to_encoded_point(true)
+            .as_bytes(),
+    );
+    let assert_message = |message: &str| {
+        // User present and verified, signature counter at 1
+        let mut authenticator_data = vec![0; 32];
+        authenticator_data.extend_from_slice(&[0x05, 0, 0, 0, 1]);
+        let client_data_json = format!(
+            r#"{{"type":"webauthn.get","challenge":"{}","origin":"https://hyliquid.example","crossOrigin":false}}"#,
+            webauthn::base64url(&webauthn::challenge(message))
+        )
+        .into_bytes();
+        let mut signed_data = authenticator_data.clone();
+        signed_data.extend_from_slice(&Sha256::digest(&client_data_json));
+        let signature: P256Signature = signing_key.sign(&signed_data);
+        borsh::to_vec(&WebAuthnAssertion {
+            authenticator_data,
+            client_data_json,
+            signature: signature.to_der().as_bytes().to_vec(),
+        })
+        .expect("serialize assertion")
+    };
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Deposit {
+            symbol: pair.1.clone(),
+            amount: 1_000,
+        },
+        Vec::new(),
+    );
+
+    let order = make_limit_order("order-1", OrderSide::Bid, 100, 10);
+    let message = format!(
+        "{}:{}:create_order:{}",
+        user.user, user.nonce, order.order_id
+    );
+
+    // The challenge binds the assertion to the action message
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::CreateOrder(make_limit_order(
+            "order-2",
+            OrderSide::Bid,
+            100,
+            10,
+        )),
+        serialize(&CreateOrderPrivateInput {
+            signature: assert_message(&message),
+            public_key: session_key.clone(),
+            block_height: 0,
+        }),
+    );
+    assert!(err.contains("Invalid signature"));
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreateOrder(order),
+        serialize(&CreateOrderPrivateInput {
+            signature: assert_message(&message),
+            public_key: session_key,
+            block_height: 0,
+        }),
+    );
+    assert!(orderbook.state.order_manager.orders.contains_key("order-1"));
+}
+
+#[cfg(not(feature = "passkey"))]
+#[test]
+fn passkey_session_key_requires_feature() {
+    let mut orderbook = build_orderbook();
+    let user = test_user("nora");
+
+    let mut session_key = vec![crate::webauthn::PASSKEY_KEY_TAG, 0x02];
+    session_key.extend_from_slice(&[13; 32]);
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key,
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+    assert!(err.contains("not supported"));
+}
+
+#[test]
+fn webauthn_challenge_is_unpadded_base64url() {
+    use crate::webauthn::base64url;
+
+    assert_eq!(base64url(b""), "");
+    assert_eq!(base64url(b"f"), "Zg");
+    assert_eq!(base64url(b"fo"), "Zm8");
+    assert_eq!(base64url(b"foo"), "Zm9v");
+    assert_eq!(base64url(&[0xfb, 0xff, 0xbf]), "-_-_");
+}
+
+#[test]
+fn create_orders_is_all_or_nothing() {
+    let mut orderbook = build_orderbook();
+    let eth_pair = sample_pair();
+    let btc_pair = ("BTC".to_string(), "USDC".to_string());
+    let mut user = test_user("mona");
+    let signer = TestSigner::new(12);
+    let session_key = signer.public_key.clone();
+
+    for pair in [&eth_pair, &btc_pair] {
+        execute_action_ok(
+            &mut orderbook,
+            &mut user,
+            PermissionedOrderbookAction::CreatePair {
+                pair: pair.clone(),
+                info: make_pair_info(pair, 0, 0),
+            },
+            Vec::new(),
+        );
+    }
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Deposit {
+            symbol: "USDC".to_string(),
+            amount: 1_000,
+        },
+        Vec::new(),
+    );
+
+    let eth_order = make_limit_order("eth-bid", OrderSide::Bid, 50, 10);
+    let mut btc_order = make_limit_order("btc-bid", OrderSide::Bid, 60, 10);
+    btc_order.pair = btc_pair.clone();
+
+    // The second order cannot be funded, so the first one must not be placed either
+    let orders = vec![eth_order.clone(), btc_order.clone()];
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::CreateOrders(orders.clone()),
+        serialize(&CreateOrderPrivateInput {
+            signature: signer.sign(
+                &SignedAction::CreateOrders {
+                    user: &user.user,
+                    nonce: user.nonce,
+                    orders: &orders,
+                }
+                .message(),
+            ),
+            public_key: session_key.clone(),
+            block_height: 0,
+        }),
+    );
+    assert!(err.contains("Order btc-bid failed"));
+
+    btc_order.quantity = 5;
+    let orders = vec![eth_order, btc_order];
+    let nonce = user.nonce;
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreateOrders(orders.clone()),
+        serialize(&CreateOrderPrivateInput {
+            signature: signer.sign(
+                &SignedAction::CreateOrders {
+                    user: &user.user,
+                    nonce: user.nonce,
+                    orders: &orders,
+                }
+                .message(),
+            ),
+            public_key: session_key,
+            block_height: 0,
+        }),
+    );
+
+    assert!(orderbook.state.order_manager.orders.contains_key("eth-bid"));
+    assert!(orderbook.state.order_manager.orders.contains_key("btc-bid"));
+    assert_eq!(orderbook.state.get_balance(&user, "USDC").0, 200);
+    // The batch consumes a single nonce
+    assert_eq!(user.nonce, nonce + 1);
+    assert_eq!(
+        events
+            .iter()
+            .filter(|event| matches!(event, OrderbookEvent::NonceIncremented { .. }))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn sweep_dust_sells_small_balances_for_the_quote() {
+    let mut orderbook = build_orderbook();
+    let quote = "USDC".to_string();
+    let mut owner = test_user("kit");
+    let mut maker = test_user("lou");
+    let owner_signer = TestSigner::new(36);
+    let maker_signer = TestSigner::new(37);
+
+    for symbol in ["ETH", "BTC", "SOL"] {
+        let pair = (symbol.to_string(), quote.clone());
+        execute_action_ok(
+            &mut orderbook,
+            &mut maker,
+            PermissionedOrderbookAction::CreatePair {
+                pair: pair.clone(),
+                info: make_pair_info(&pair, 2, 2),
+            },
+            Vec::new(),
+        );
+    }
+    for (user, signer, deposits) in [
+        (
+            &mut owner,
+            &owner_signer,
+            vec![("ETH", 50), ("BTC", 20), ("SOL", 80)],
+        ),
+        (&mut maker, &maker_signer, vec![("USDC", 1_000)]),
+    ] {
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::AddSessionKey,
+            serialize(&AddSessionKeyPrivateInput {
+                new_public_key: signer.public_key.clone(),
+                permissions: SessionKeyPermissions::ALL,
+                pair: None,
+            }),
+        );
+        for (symbol, amount) in deposits {
+            execute_action_ok(
+                &mut orderbook,
+                user,
+                PermissionedOrderbookAction::Deposit {
+                    symbol: symbol.to_string(),
+                    amount,
+                },
+                Vec::new(),
+            );
+        }
+    }
+    for (symbol, price) in [("ETH", 150), ("BTC", 300), ("SOL", 150)] {
+        let mut order = make_limit_order(&format!("{symbol}-bid"), OrderSide::Bid, price, 100);
+        order.pair = (symbol.to_string(), quote.clone());
+        let message = format!(
+            "{}:{}:create_order:{}",
+            maker.user, maker.nonce, order.order_id
+        );
+        execute_action_ok(
+            &mut orderbook,
+            &mut maker,
+            PermissionedOrderbookAction::CreateOrder(order),
+            serialize(&CreateOrderPrivateInput {
+                signature: maker_signer.sign(&message),
+                public_key: maker_signer.public_key.clone(),
+                block_height: 0,
+            }),
+        );
+    }
+
+    let sweeps = |symbols: &[&str]| -> Vec<DustSweep> {
+        symbols
+            .iter()
+            .map(|symbol| DustSweep {
+                symbol: symbol.to_string(),
+                order_id: format!("{symbol}-sweep"),
+            })
+            .collect()
+    };
+    let sweep_dust = |user: &UserInfo, sweeps: Vec<DustSweep>| {
+        let signature = owner_signer.sign(
+            &SignedAction::SweepDust {
+                user: &user.user,
+                nonce: user.nonce,
+                quote: &quote,
+                sweeps: &sweeps,
+            }
+            .message(),
+        );
+        (
+            PermissionedOrderbookAction::SweepDust {
+                quote: quote.clone(),
+                sweeps,
+            },
+            serialize(&CreateOrderPrivateInput {
+                signature,
+                public_key: owner_signer.public_key.clone(),
+                block_height: 0,
+            }),
+        )
+    };
+
+    // 80 SOL are worth 120 at the best bid, more than one unit of USDC
+    for (symbols, expected) in [
+        (vec!["ETH", "SOL"], "it is not dust"),
+        (vec!["XRP"], "is not a spot pair"),
+    ] {
+        let (action, private_input) = sweep_dust(&owner, sweeps(&symbols));
+        let err = execute_action_err(&mut orderbook, &owner, action, private_input);
+        assert!(err.contains(expected), "{symbols:?}: {err}");
+    }
+
+    // 50 ETH worth 75 and 20 BTC worth 60 are sold in the same action
+    let (action, private_input) = sweep_dust(&owner, sweeps(&["ETH", "BTC"]));
+    let nonce = owner.nonce;
+    let events = execute_action_ok(&mut orderbook, &mut owner, action, private_input);
+    assert_eq!(owner.nonce, nonce + 1);
+    assert_eq!(orderbook.state.get_balance(&owner, "ETH").0, 0);
+    assert_eq!(orderbook.state.get_balance(&owner, "BTC").0, 0);
+    assert_eq!(orderbook.state.get_balance(&owner, "SOL").0, 80);
+    assert_eq!(orderbook.state.get_balance(&owner, &quote).0, 135);
+    assert!(events.iter().any(|event| matches!(
+        event,
+        OrderbookEvent::DustSwept { user, symbols, proceeds, .. }
+            if user == "kit" && symbols == &["ETH", "BTC"] && *proceeds == 135
+    )));
+    assert!(matches!(
+        events.last(),
+        Some(OrderbookEvent::NonceIncremented { .. })
+    ));
+}
+
+#[test]
+fn trade_only_session_key_cannot_withdraw() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("erin");
+    let signer = TestSigner::new(4);
+    let session_key = signer.public_key.clone();
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::TRADE_ONLY,
+            pair: None,
+        }),
+    );
+    assert_eq!(user.session_key_scopes.len(), 1);
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Deposit {
+            symbol: pair.1.clone(),
+            amount: 1_000,
+        },
+        Vec::new(),
+    );
+
+    let withdraw_message = format!("{}:{}:withdraw:{}:{}", user.user, user.nonce, pair.1, 400);
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::Withdraw {
+            symbol: pair.1.clone(),
+            amount: 400,
+            destination: WithdrawDestination {
+                network: "hyli".to_string(),
+                address: "dest-address".to_string(),
+            },
+            block_height: 0,
+        },
+        serialize(&WithdrawPrivateInput {
+            signature: signer.sign(&withdraw_message),
+            public_key: session_key.clone(),
+        }),
+    );
+    assert!(err.contains("not allowed"));
+
+    let order = make_limit_order("order-1", OrderSide::Bid, 100, 10);
+    let order_message = format!(
+        "{}:{}:create_order:{}",
+        user.user, user.nonce, order.order_id
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreateOrder(order),
+        serialize(&CreateOrderPrivateInput {
+            signature: signer.sign(&order_message),
+            public_key: session_key,
+            block_height: 0,
+        }),
+    );
+    assert!(orderbook.state.order_manager.orders.contains_key("order-1"));
+}
+
+#[test]
+fn pair_restricted_session_key_rejects_other_pairs() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let other_pair = ("BTC".to_string(), "USDC".to_string());
+    let mut user = test_user("frank");
+    let signer = TestSigner::new(5);
+    let session_key = signer.public_key.clone();
+
+    for p in [&pair, &other_pair] {
+        execute_action_ok(
+            &mut orderbook,
+            &mut user,
+            PermissionedOrderbookAction::CreatePair {
+                pair: p.clone(),
+                info: make_pair_info(p, 3, 2),
+            },
+            Vec::new(),
+        );
+    }
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: Some(pair.clone()),
+        }),
+    );
+
+    let mut order = make_limit_order("order-1", OrderSide::Ask, 100, 10);
+    order.pair = other_pair;
+    let order_message = format!(
+        "{}:{}:create_order:{}",
+        user.user, user.nonce, order.order_id
+    );
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::CreateOrder(order),
+        serialize(&CreateOrderPrivateInput {
+            signature: signer.sign(&order_message),
+            public_key: session_key.clone(),
+            block_height: 0,
+        }),
+    );
+    assert!(err.contains("restricted to pair"));
+
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: TestSigner::new(6).public_key,
+            permissions: SessionKeyPermissions::NONE,
+            pair: None,
+        }),
+    );
+    assert!(err.contains("at least one permission"));
+}
+
+#[test]
+fn update_fee_tiers_requires_orderbook_account() {
+    let mut orderbook = build_orderbook();
+    let mut user = test_user("gina");
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: TestSigner::new(6).public_key,
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::UpdateFeeTiers,
+        serialize(&UpdateFeeTiersPrivateInput {
+            updates: vec![(user.user.clone(), FeeTier::default())],
+        }),
+    );
+    assert!(err.contains("can update fee tiers"));
+}
+
+#[test]
+fn taker_fee_is_credited_to_orderbook_account() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut maker = test_user("hank");
+    let mut taker = test_user("iris");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let maker_signer = TestSigner::new(7);
+    let taker_signer = TestSigner::new(8);
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut maker,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 0, 0),
+        },
+        Vec::new(),
+    );
+
+    for (user, signer, symbol) in [
+        (&mut maker, &maker_signer, &pair.0),
+        (&mut taker, &taker_signer, &pair.1),
+    ] {
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::AddSessionKey,
+            serialize(&AddSessionKeyPrivateInput {
+                new_public_key: signer.public_key.clone(),
+                permissions: SessionKeyPermissions::ALL,
+                pair: None,
+            }),
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::Deposit {
+                symbol: symbol.clone(),
+                amount: 10_000,
+            },
+            Vec::new(),
+        );
+    }
+
+    let fee_tier = FeeTier {
+        tier: 1,
+        maker_fee_bps: 0,
+        taker_fee_bps: 100,
+    };
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::UpdateFeeTiers,
+        serialize(&UpdateFeeTiersPrivateInput {
+            updates: vec![(taker.user.clone(), fee_tier.clone())],
+        }),
+    );
+    let mut taker = orderbook
+        .state
+        .get_user_info(&taker.user)
+        .expect("taker should exist");
+    assert_eq!(taker.fee_tier, fee_tier);
+
+    for (user, signer, order) in [
+        (
+            &mut maker,
+            &maker_signer,
+            make_limit_order("ask-1", OrderSide::Ask, 10, 100),
+        ),
+        (
+            &mut taker,
+            &taker_signer,
+            make_limit_order("bid-1", OrderSide::Bid, 10, 100),
+        ),
+    ] {
+        let message = format!(
+            "{}:{}:create_order:{}",
+            user.user, user.nonce, order.order_id
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::CreateOrder(order),
+            serialize(&CreateOrderPrivateInput {
+                signature: signer.sign(&message),
+                public_key: signer.public_key.clone(),
+                block_height: 0,
+            }),
+        );
+    }
+
+    // The taker received 100 ETH and paid 1% of it, the maker paid no fee
+    assert_eq!(orderbook.state.get_balance(&taker, &pair.0).0, 99);
+    assert_eq!(orderbook.state.get_balance(&operator, &pair.0).0, 1);
+    assert_eq!(orderbook.state.get_balance(&maker, &pair.1).0, 1_000);
+}
+
+#[test]
+fn maker_fee_is_charged_from_the_maker_tier() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut maker = test_user("hugo");
+    let mut taker = test_user("ines");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let maker_signer = TestSigner::new(27);
+    let taker_signer = TestSigner::new(28);
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut maker,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 0, 0),
+        },
+        Vec::new(),
+    );
+
+    for (user, signer, symbol) in [
+        (&mut maker, &maker_signer, &pair.0),
+        (&mut taker, &taker_signer, &pair.1),
+    ] {
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::AddSessionKey,
+            serialize(&AddSessionKeyPrivateInput {
+                new_public_key: signer.public_key.clone(),
+                permissions: SessionKeyPermissions::ALL,
+                pair: None,
+            }),
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::Deposit {
+                symbol: symbol.clone(),
+                amount: 10_000,
+            },
+            Vec::new(),
+        );
+    }
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::UpdateFeeTiers,
+        serialize(&UpdateFeeTiersPrivateInput {
+            updates: vec![(
+                maker.user.clone(),
+                FeeTier {
+                    tier: 1,
+                    maker_fee_bps: 100,
+                    taker_fee_bps: 0,
+                },
+            )],
+        }),
+    );
+    let mut maker = orderbook
+        .state
+        .get_user_info(&maker.user)
+        .expect("maker should exist");
+
+    for (user, signer, order) in [
+        (
+            &mut maker,
+            &maker_signer,
+            make_limit_order("ask-1", OrderSide::Ask, 10, 100),
+        ),
+        (
+            &mut taker,
+            &taker_signer,
+            make_limit_order("bid-1", OrderSide::Bid, 10, 100),
+        ),
+    ] {
+        let message = format!(
+            "{}:{}:create_order:{}",
+            user.user, user.nonce, order.order_id
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::CreateOrder(order),
+            serialize(&CreateOrderPrivateInput {
+                signature: signer.sign(&message),
+                public_key: signer.public_key.clone(),
+                block_height: 0,
+            }),
+        );
+    }
+
+    // The maker received 1000 USDC and paid 1% of it, the taker paid no fee
+    assert_eq!(orderbook.state.get_balance(&maker, &pair.1).0, 990);
+    assert_eq!(orderbook.state.get_balance(&operator, &pair.1).0, 10);
+    assert_eq!(orderbook.state.get_balance(&taker, &pair.0).0, 100);
+}
+
+#[test]
+fn withdraw_limit_caps_each_window() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("nora");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let signer = TestSigner::new(13);
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 0, 0),
+        },
+        Vec::new(),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: signer.public_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Deposit {
+            symbol: pair.1.clone(),
+            amount: 1_000,
+        },
+        Vec::new(),
+    );
+
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::UpdateWithdrawLimits {
+            updates: vec![(pair.1.clone(), None)],
+        },
+        Vec::new(),
+    );
+    assert!(err.contains("can update withdraw limits"));
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::UpdateWithdrawLimits {
+            updates: vec![(
+                pair.1.clone(),
+                Some(WithdrawLimit {
+                    max_amount: 500,
+                    window_blocks: 100,
+                }),
+            )],
+        },
+        Vec::new(),
+    );
+
+    let withdraw = |user: &UserInfo, amount: u64, block_height: u64| {
+        let message = format!(
+            "{}:{}:withdraw:{}:{}",
+            user.user, user.nonce, pair.1, amount
+        );
+        (
+            PermissionedOrderbookAction::Withdraw {
+                symbol: pair.1.clone(),
+                amount,
+                destination: WithdrawDestination {
+                    network: "hyli".to_string(),
+                    address: "dest-address".to_string(),
+                },
+                block_height,
+            },
+            serialize(&WithdrawPrivateInput {
+                signature: signer.sign(&message),
+                public_key: signer.public_key.clone(),
+            }),
+        )
+    };
+
+    let (action, private_input) = withdraw(&user, 300, 150);
+    execute_action_ok(&mut orderbook, &mut user, action, private_input);
+    let window = user
+        .get_withdrawal_window(&pair.1)
+        .expect("withdrawal should be recorded");
+    assert_eq!((window.window_start, window.withdrawn), (100, 300));
+
+    // The window starting at block 100 only has 200 left
+    let (action, private_input) = withdraw(&user, 300, 199);
+    let err = execute_action_err(&mut orderbook, &user, action, private_input);
+    assert!(err.contains("while the limit is 500"));
+
+    // Withdrawals cannot be accounted in a window prior to the recorded one
+    let (action, private_input) = withdraw(&user, 100, 99);
+    let err = execute_action_err(&mut orderbook, &user, action, private_input);
+    assert!(err.contains("before the current withdrawal window"));
+
+    let (action, private_input) = withdraw(&user, 300, 200);
+    execute_action_ok(&mut orderbook, &mut user, action, private_input);
+    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 400);
+    assert_eq!(
+        orderbook.state.get_user_info(&user.user).unwrap(),
+        user,
+        "committed user info should track the new window"
+    );
+}
+
+#[test]
+fn large_withdrawals_wait_in_the_queue_until_released() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("ned");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let signer = TestSigner::new(38);
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 0, 0),
+        },
+        Vec::new(),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: signer.public_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Deposit {
+            symbol: pair.1.clone(),
+            amount: 1_500,
+        },
+        Vec::new(),
+    );
+
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::UpdateWithdrawDelays {
+            updates: vec![(pair.1.clone(), None)],
+        },
+        Vec::new(),
+    );
+    assert!(err.contains("can update withdraw delays"));
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::UpdateWithdrawDelays {
+            updates: vec![(
+                pair.1.clone(),
+                Some(WithdrawDelay {
+                    threshold: 500,
+                    delay_blocks: 100,
+                    requires_approval: true,
+                }),
+            )],
+        },
+        Vec::new(),
+    );
+
+    let destination = WithdrawDestination {
+        network: "hyli".to_string(),
+        address: "dest-address".to_string(),
+    };
+    let private_input = |user: &UserInfo, amount: u64| {
+        let message = format!(
+            "{}:{}:withdraw:{}:{}",
+            user.user, user.nonce, pair.1, amount
+        );
+        serialize(&WithdrawPrivateInput {
+            signature: signer.sign(&message),
+            public_key: signer.public_key.clone(),
+        })
+    };
+    let request = |amount: u64, block_height: u64| PermissionedOrderbookAction::RequestWithdrawal {
+        symbol: pair.1.clone(),
+        amount,
+        destination: destination.clone(),
+        block_height,
+    };
+
+    // Large withdrawals cannot be paid out directly, small ones cannot be queued
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::Withdraw {
+            symbol: pair.1.clone(),
+            amount: 600,
+            destination: destination.clone(),
+            block_height: 50,
+        },
+        private_input(&user, 600),
+    );
+    assert!(err.contains("must be requested"));
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        request(300, 50),
+        private_input(&user, 300),
+    );
+    assert!(err.contains("can be withdrawn directly"));
+
+    let withdrawal = PendingWithdrawal {
+        id: user.nonce,
+        symbol: pair.1.clone(),
+        amount: 600,
+        destination: destination.clone(),
+        available_at: 150,
+        approved: false,
+    };
+    let input = private_input(&user, 600);
+    execute_action_ok(&mut orderbook, &mut user, request(600, 50), input);
+    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 900);
+    assert_eq!(user.pending_withdrawals, vec![withdrawal.clone()]);
+    assert_eq!(orderbook.state.get_user_info(&user.user).unwrap(), user);
+
+    let release = |withdrawal: &PendingWithdrawal, block_height: u64| {
+        PermissionedOrderbookAction::ReleaseWithdrawal {
+            user: "ned".to_string(),
+            withdrawal: withdrawal.clone(),
+            block_height,
+        }
+    };
+    let err = execute_action_err(
+        &mut orderbook,
+        &operator,
+        release(&withdrawal, 150),
+        Vec::new(),
+    );
+    assert!(err.contains("waiting for the approval"));
+
+    let review =
+        |withdrawal_id: u32, approve: bool| PermissionedOrderbookAction::ReviewWithdrawal {
+            user: "ned".to_string(),
+            withdrawal_id,
+            approve,
+        };
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        review(withdrawal.id, true),
+        Vec::new(),
+    );
+    assert!(err.contains("can review withdrawals"));
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        review(withdrawal.id, true),
+        Vec::new(),
+    );
+    let approved = PendingWithdrawal {
+        approved: true,
+        ..withdrawal
+    };
+
+    let err = execute_action_err(
+        &mut orderbook,
+        &operator,
+        release(&approved, 149),
+        Vec::new(),
+    );
+    assert!(err.contains("delayed until block 150"));
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        release(&approved, 150),
+        Vec::new(),
+    );
+    assert!(events.contains(&OrderbookEvent::WithdrawalReleased {
+        user: "ned".to_string(),
+        withdrawal: approved,
+    }));
+    let committed = orderbook.state.get_user_info("ned").unwrap();
+    assert!(committed.pending_withdrawals.is_empty());
+    assert_eq!(orderbook.state.get_balance(&committed, &pair.1).0, 900);
+
+    // Rejected withdrawals are refunded
+    let rejected_id = user.nonce;
+    let input = private_input(&user, 501);
+    execute_action_ok(&mut orderbook, &mut user, request(501, 200), input);
+    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 399);
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        review(rejected_id, false),
+        Vec::new(),
+    );
+    let committed = orderbook.state.get_user_info("ned").unwrap();
+    assert!(committed.pending_withdrawals.is_empty());
+    assert_eq!(orderbook.state.get_balance(&committed, &pair.1).0, 900);
+}
+
+#[test]
+fn bridge_pause_only_halts_withdrawals_to_other_networks() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("olga");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let signer = TestSigner::new(39);
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 0, 0),
+        },
+        Vec::new(),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: signer.public_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Deposit {
+            symbol: pair.1.clone(),
+            amount: 1_000,
+        },
+        Vec::new(),
+    );
+
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::UpdateBridgePause { paused: true },
+        Vec::new(),
+    );
+    assert!(err.contains("can pause the bridge"));
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::UpdateBridgePause { paused: true },
+        Vec::new(),
+    );
+    assert!(events.contains(&OrderbookEvent::BridgePauseUpdated { paused: true }));
+    assert!(orderbook.state.bridge_paused);
+
+    let withdraw = |user: &UserInfo, network: &str| {
+        let message = format!("{}:{}:withdraw:{}:100", user.user, user.nonce, pair.1);
+        let action = PermissionedOrderbookAction::Withdraw {
+            symbol: pair.1.clone(),
+            amount: 100,
+            destination: WithdrawDestination {
+                network: network.to_string(),
+                address: "dest-address".to_string(),
+            },
+            block_height: 10,
+        };
+        let private_input = serialize(&WithdrawPrivateInput {
+            signature: signer.sign(&message),
+            public_key: signer.public_key.clone(),
+        });
+        (action, private_input)
+    };
+
+    let (action, input) = withdraw(&user, "ethereum-sepolia");
+    let err = execute_action_err(&mut orderbook, &user, action, input);
+    assert!(err.contains("withdrawals to ethereum-sepolia are paused"));
+
+    // Withdrawals to Hyli go on
+    let (action, input) = withdraw(&user, "hyli");
+    execute_action_ok(&mut orderbook, &mut user, action, input);
+    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 900);
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::UpdateBridgePause { paused: false },
+        Vec::new(),
+    );
+    let (action, input) = withdraw(&user, "ethereum-sepolia");
+    execute_action_ok(&mut orderbook, &mut user, action, input);
+    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 800);
+}
+
+#[test]
+fn pair_status_follows_lifecycle() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut user = test_user("paula");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let signer = TestSigner::new(15);
+    let session_key = signer.public_key.clone();
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 3, 2),
+        },
+        Vec::new(),
+    );
+    assert_eq!(
+        orderbook.state.pairs_status.get(&pair),
+        Some(&MarketStatus::Active)
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::AddSessionKey,
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: session_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        }),
+    );
+
+    let update_status = |status| PermissionedOrderbookAction::UpdatePairStatus {
+        pair: pair.clone(),
+        status,
+    };
+
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        update_status(MarketStatus::Halted),
+        Vec::new(),
+    );
+    assert!(err.contains("can update pair status"));
+
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        update_status(MarketStatus::Halted),
+        Vec::new(),
+    );
+    assert!(events.contains(&OrderbookEvent::PairStatusUpdated {
+        pair: pair.clone(),
+        status: MarketStatus::Halted,
+    }));
+
+    // Orders are refused on a halted pair
+    let order = make_limit_order("order-1", OrderSide::Ask, 100, 10);
+    let order_message = format!(
+        "{}:{}:create_order:{}",
+        user.user, user.nonce, order.order_id
+    );
+    let err = execute_action_err(
+        &mut orderbook,
+        &user,
+        PermissionedOrderbookAction::CreateOrder(order.clone()),
+        serialize(&CreateOrderPrivateInput {
+            signature: signer.sign(&order_message),
+            public_key: session_key.clone(),
+            block_height: 0,
+        }),
+    );
+    assert!(err.contains("orders are not accepted"));
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        update_status(MarketStatus::Active),
+        Vec::new(),
+    );
+
+    // Resting orders must be cancelled before the pair is delisted
+    orderbook
+        .state
+        .users_info
+        .insert(user.user.clone(), user.clone());
+    orderbook
+        .state
+        .order_manager
+        .insert_order(&order, &user.get_key())
+        .expect("order insertion should succeed");
+    let err = execute_action_err(
+        &mut orderbook,
+        &operator,
+        update_status(MarketStatus::Delisted),
+        Vec::new(),
+    );
+    assert!(err.contains("still has 1 resting orders"));
+
+    let cancel_message = format!("{}:{}:cancel:{}", user.user, user.nonce, order.order_id);
+    execute_action_ok(
+        &mut orderbook,
+        &mut user,
+        PermissionedOrderbookAction::Cancel {
+            order_id: order.order_id.clone(),
+        },
+        serialize(&CancelOrderPrivateInput {
+            signature: signer.sign(&cancel_message),
+            public_key: session_key,
+        }),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        update_status(MarketStatus::Delisted),
+        Vec::new(),
+    );
+    assert_eq!(
+        orderbook.state.pairs_status.get(&pair),
+        Some(&MarketStatus::Delisted)
+    );
+
+    // A delisted pair cannot be resumed
+    let err = execute_action_err(
+        &mut orderbook,
+        &operator,
+        update_status(MarketStatus::Active),
+        Vec::new(),
+    );
+    assert!(err.contains("cannot go from Delisted to Active"));
+
+    let err = execute_action_err(
+        &mut orderbook,
+        &operator,
+        PermissionedOrderbookAction::UpdatePairStatus {
+            pair: ("BTC".to_string(), "USDC".to_string()),
+            status: MarketStatus::Halted,
+        },
+        Vec::new(),
+    );
+    assert!(err.contains("does not exist"));
+}
+
+#[test]
+fn circuit_breaker_halts_orders_that_trade() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut maker = test_user("quinn");
+    let mut taker = test_user("rosa");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let maker_signer = TestSigner::new(16);
+    let taker_signer = TestSigner::new(17);
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 0, 0),
+        },
+        Vec::new(),
+    );
+    for (user, signer, symbol, amount) in [
+        (&mut maker, &maker_signer, &pair.0, 100),
+        (&mut taker, &taker_signer, &pair.1, 10_000),
+    ] {
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::AddSessionKey,
+            serialize(&AddSessionKeyPrivateInput {
+                new_public_key: signer.public_key.clone(),
+                permissions: SessionKeyPermissions::ALL,
+                pair: None,
+            }),
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::Deposit {
+                symbol: symbol.clone(),
+                amount,
+            },
+            Vec::new(),
+        );
+    }
+
+    let update_breaker = PermissionedOrderbookAction::UpdateCircuitBreakers {
+        updates: vec![(
+            pair.clone(),
+            Some(CircuitBreaker {
+                max_move_bps: 1_000,
+                window_blocks: 10,
+                halt_blocks: 5,
+            }),
+        )],
+    };
+    let err = execute_action_err(&mut orderbook, &maker, update_breaker.clone(), Vec::new());
+    assert!(err.contains("can update circuit breakers"));
+    execute_action_ok(&mut orderbook, &mut operator, update_breaker, Vec::new());
+
+    let create_order = |user: &UserInfo, signer: &TestSigner, order: &Order, block_height| {
+        let message = format!(
+            "{}:{}:create_order:{}",
+            user.user, user.nonce, order.order_id
+        );
+        (
+            PermissionedOrderbookAction::CreateOrder(order.clone()),
+            serialize(&CreateOrderPrivateInput {
+                signature: signer.sign(&message),
+                public_key: signer.public_key.clone(),
+                block_height,
+            }),
+        )
+    };
+
+    for order in [
+        make_limit_order("ask-1", OrderSide::Ask, 100, 10),
+        make_limit_order("ask-2", OrderSide::Ask, 115, 10),
+    ] {
+        let (action, private_input) = create_order(&maker, &maker_signer, &order, 0);
+        let events = execute_action_ok(&mut orderbook, &mut maker, action, private_input);
+        // Resting orders do not move the price
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, OrderbookEvent::PriceWindowStarted { .. })));
+    }
+
+    // The first trade starts a window
+    let order = make_limit_order("bid-1", OrderSide::Bid, 100, 5);
+    let (action, private_input) = create_order(&taker, &taker_signer, &order, 1);
+    let events = execute_action_ok(&mut orderbook, &mut taker, action, private_input);
+    assert!(events.contains(&OrderbookEvent::PriceWindowStarted {
+        pair: pair.clone(),
+        reference_price: 100,
+        block_height: 1,
+    }));
+
+    // Sweeping the book up to 115 moves the price by 15% within the window
+    let order = make_limit_order("bid-2", OrderSide::Bid, 115, 10);
+    let (action, private_input) = create_order(&taker, &taker_signer, &order, 2);
+    let events = execute_action_ok(&mut orderbook, &mut taker, action, private_input);
+    assert!(events.contains(&OrderbookEvent::CircuitBreakerTripped {
+        pair: pair.clone(),
+        reference_price: 100,
+        price: 115,
+        block_height: 2,
+        halted_until: 7,
+    }));
+
+    // Orders that would trade are refused until the halt ends, resting ones are accepted
+    let order = make_limit_order("bid-3", OrderSide::Bid, 115, 1);
+    let (action, private_input) = create_order(&taker, &taker_signer, &order, 6);
+    let err = execute_action_err(&mut orderbook, &taker, action, private_input);
+    assert!(err.contains("halted by its circuit breaker until block 7"));
+
+    let (action, private_input) = create_order(
+        &maker,
+        &maker_signer,
+        &make_limit_order("ask-3", OrderSide::Ask, 130, 1),
+        6,
+    );
+    execute_action_ok(&mut orderbook, &mut maker, action, private_input);
+
+    // The price the breaker tripped at is the reference once the halt ends
+    let (action, private_input) = create_order(&taker, &taker_signer, &order, 7);
+    execute_action_ok(&mut orderbook, &mut taker, action, private_input);
+    let breaker = orderbook
+        .state
+        .circuit_breakers
+        .get(&pair)
+        .expect("pair should have a circuit breaker");
+    assert_eq!(
+        (
+            breaker.reference_price,
+            breaker.window_start,
+            breaker.halted_until
+        ),
+        (Some(115), 2, 7)
+    );
+}
+
+#[test]
+fn index_prices_feed_circuit_breakers() {
+    let mut orderbook = build_orderbook();
+    let pair = sample_pair();
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let user = test_user("sol");
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: make_pair_info(&pair, 0, 0),
+        },
+        Vec::new(),
+    );
+
+    let update_prices = |price, block_height| PermissionedOrderbookAction::UpdateIndexPrices {
+        prices: vec![(pair.clone(), price)],
+        block_height,
+    };
+
+    // Without a breaker, index prices are accepted but not tracked
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        update_prices(100, 1),
+        Vec::new(),
+    );
+    assert_eq!(events.len(), 1);
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::UpdateCircuitBreakers {
+            updates: vec![(
+                pair.clone(),
+                Some(CircuitBreaker {
+                    max_move_bps: 1_000,
+                    window_blocks: 10,
+                    halt_blocks: 5,
+                }),
+            )],
+        },
+        Vec::new(),
+    );
+
+    let err = execute_action_err(&mut orderbook, &user, update_prices(100, 1), Vec::new());
+    assert!(err.contains("can update index prices"));
+    let err = execute_action_err(&mut orderbook, &operator, update_prices(0, 1), Vec::new());
+    assert!(err.contains("cannot be zero"));
+
+    // Each index price is the reference of a new window
+    for (price, block_height) in [(100, 1), (105, 2)] {
+        let events = execute_action_ok(
+            &mut orderbook,
+            &mut operator,
+            update_prices(price, block_height),
+            Vec::new(),
+        );
+        assert!(events.contains(&OrderbookEvent::PriceWindowStarted {
+            pair: pair.clone(),
+            reference_price: price,
+            block_height,
+        }));
+    }
+
+    // Unless it moved too much from the current reference
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        update_prices(120, 3),
+        Vec::new(),
+    );
+    assert!(events.contains(&OrderbookEvent::CircuitBreakerTripped {
+        pair: pair.clone(),
+        reference_price: 105,
+        price: 120,
+        block_height: 3,
+        halted_until: 8,
+    }));
+
+    // Halted pairs are not tracked until the halt ends
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        update_prices(150, 4),
+        Vec::new(),
+    );
+    assert_eq!(events.len(), 1);
+    let breaker = orderbook
+        .state
+        .circuit_breakers
+        .get(&pair)
+        .expect("pair should have a circuit breaker");
+    assert_eq!(breaker.reference_price, Some(120));
+}
+
+#[test]
+fn perp_fills_open_and_close_margined_positions() {
+    let mut orderbook = build_orderbook();
+    let perp: Pair = ("ETH-PERP".to_string(), "USDC".to_string());
+    let mut maker = test_user("uma");
+    let mut taker = test_user("vic");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let maker_signer = TestSigner::new(18);
+    let taker_signer = TestSigner::new(19);
+    let config = PerpConfig {
+        initial_margin_bps: 1_000,
+        maintenance_margin_bps: 500,
+        liquidation_penalty_bps: 0,
+    };
+    let create_market = PermissionedOrderbookAction::CreatePerpMarket {
+        pair: perp.clone(),
+        info: make_pair_info(&perp, 0, 0),
+        config: config.clone(),
+    };
+
+    let err = execute_action_err(&mut orderbook, &maker, create_market.clone(), Vec::new());
+    assert!(err.contains("can create perp markets"));
+    let events = execute_action_ok(&mut orderbook, &mut operator, create_market, Vec::new());
+    assert!(events.contains(&OrderbookEvent::PerpMarketCreated {
+        pair: perp.clone(),
+        config,
+    }));
+
+    for (user, signer) in [(&mut maker, &maker_signer), (&mut taker, &taker_signer)] {
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::AddSessionKey,
+            serialize(&AddSessionKeyPrivateInput {
+                new_public_key: signer.public_key.clone(),
+                permissions: SessionKeyPermissions::ALL,
+                pair: None,
+            }),
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::Deposit {
+                symbol: perp.1.clone(),
+                amount: 1_000,
+            },
+            Vec::new(),
+        );
+    }
+
+    let perp_order = |id: &str, side, price, quantity| Order {
+        pair: perp.clone(),
+        ..make_limit_order(id, side, price, quantity)
+    };
+    let create_order = |user: &UserInfo, signer: &TestSigner, order: &Order| {
+        let message = format!(
+            "{}:{}:create_order:{}",
+            user.user, user.nonce, order.order_id
+        );
+        (
+            PermissionedOrderbookAction::CreateOrder(order.clone()),
+            serialize(&CreateOrderPrivateInput {
+                signature: signer.sign(&message),
+                public_key: signer.public_key.clone(),
+                block_height: 0,
+            }),
+        )
+    };
+    let position = |orderbook: &FullState, user: &UserInfo| {
+        orderbook
+            .state
+            .get_user_info(&user.user)
+            .expect("user should exist")
+            .get_position(&perp)
+            .cloned()
+    };
+
+    // A resting order locks its initial margin rather than the base asset
+    let ask = perp_order("ask-1", OrderSide::Ask, 100, 10);
+    let (action, private_input) = create_order(&maker, &maker_signer, &ask);
+    execute_action_ok(&mut orderbook, &mut maker, action, private_input);
+    assert_eq!(orderbook.state.get_balance(&maker, &perp.1).0, 900);
+    assert_eq!(
+        orderbook.state.get_locked_balances(&maker).get(&perp.1),
+        Some(&100)
+    );
+
+    // Fills open positions, moving the margin of the filled size into them
+    let (action, private_input) = create_order(
+        &taker,
+        &taker_signer,
+        &perp_order("bid-1", OrderSide::Bid, 100, 4),
+    );
+    execute_action_ok(&mut orderbook, &mut taker, action, private_input);
+    assert_eq!(
+        position(&orderbook, &taker),
+        Some(Position {
+            pair: perp.clone(),
+            size: 4,
+            entry_price: 100,
+            margin: 40,
+            funding_index: 0,
+        })
+    );
+    assert_eq!(
+        position(&orderbook, &maker).map(|position| position.size),
+        Some(-4)
+    );
+    assert_eq!(orderbook.state.get_balance(&taker, &perp.1).0, 960);
+    assert_eq!(orderbook.state.get_balance(&maker, &perp.1).0, 900);
+
+    // Cancelling refunds the margin still locked by the order
+    let cancel_message = format!("{}:{}:cancel:{}", maker.user, maker.nonce, ask.order_id);
+    execute_action_ok(
+        &mut orderbook,
+        &mut maker,
+        PermissionedOrderbookAction::Cancel {
+            order_id: ask.order_id.clone(),
+        },
+        serialize(&CancelOrderPrivateInput {
+            signature: maker_signer.sign(&cancel_message),
+            public_key: maker_signer.public_key.clone(),
+        }),
+    );
+    assert_eq!(orderbook.state.get_balance(&maker, &perp.1).0, 960);
+
+    // Closing realizes the pnl of the price move, and removes the positions
+    let (action, private_input) = create_order(
+        &maker,
+        &maker_signer,
+        &perp_order("bid-2", OrderSide::Bid, 120, 4),
+    );
+    execute_action_ok(&mut orderbook, &mut maker, action, private_input);
+    let (action, private_input) = create_order(
+        &taker,
+        &taker_signer,
+        &perp_order("ask-2", OrderSide::Ask, 120, 4),
+    );
+    let events = execute_action_ok(&mut orderbook, &mut taker, action, private_input);
+    assert!(events.contains(&OrderbookEvent::PositionUpdated {
+        user: taker.user.clone(),
+        position: Position::new(perp.clone(), 0),
+    }));
+    assert_eq!(position(&orderbook, &taker), None);
+    assert_eq!(position(&orderbook, &maker), None);
+    assert_eq!(orderbook.state.get_balance(&taker, &perp.1).0, 1_080);
+    assert_eq!(orderbook.state.get_balance(&maker, &perp.1).0, 920);
+}
+
+#[test]
+fn funding_settlement_moves_payments_between_positions() {
+    let mut orderbook = build_orderbook();
+    let perp: Pair = ("SOL-PERP".to_string(), "USDC".to_string());
+    let mut maker = test_user("wes");
+    let mut taker = test_user("xia");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let maker_signer = TestSigner::new(20);
+    let taker_signer = TestSigner::new(21);
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::CreatePerpMarket {
+            pair: perp.clone(),
+            info: make_pair_info(&perp, 0, 0),
+            config: PerpConfig {
+                initial_margin_bps: 1_000,
+                maintenance_margin_bps: 500,
+                liquidation_penalty_bps: 0,
+            },
+        },
+        Vec::new(),
+    );
+
+    for (user, signer, order) in [
+        (
+            &mut maker,
+            &maker_signer,
+            make_limit_order("ask-1", OrderSide::Ask, 100, 10),
+        ),
+        (
+            &mut taker,
+            &taker_signer,
+            make_limit_order("bid-1", OrderSide::Bid, 100, 4),
+        ),
+    ] {
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::AddSessionKey,
+            serialize(&AddSessionKeyPrivateInput {
+                new_public_key: signer.public_key.clone(),
+                permissions: SessionKeyPermissions::ALL,
+                pair: None,
+            }),
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::Deposit {
+                symbol: perp.1.clone(),
+                amount: 1_000,
+            },
+            Vec::new(),
+        );
+        let order = Order {
+            pair: perp.clone(),
+            ..order
+        };
+        let message = format!(
+            "{}:{}:create_order:{}",
+            user.user, user.nonce, order.order_id
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::CreateOrder(order),
+            serialize(&CreateOrderPrivateInput {
+                signature: signer.sign(&message),
+                public_key: signer.public_key.clone(),
+                block_height: 0,
+            }),
+        );
+    }
+    assert_eq!(orderbook.state.get_balance(&taker, &perp.1).0, 960);
+    assert_eq!(orderbook.state.get_balance(&maker, &perp.1).0, 900);
+
+    let settle = |payment| PermissionedOrderbookAction::SettleFunding {
+        pair: perp.clone(),
+        payment,
+    };
+    let err = execute_action_err(&mut orderbook, &taker, settle(5), Vec::new());
+    assert!(err.contains("can settle funding"));
+    let err = execute_action_err(
+        &mut orderbook,
+        &operator,
+        PermissionedOrderbookAction::SettleFunding {
+            pair: ("ETH".to_string(), "USDC".to_string()),
+            payment: 5,
+        },
+        Vec::new(),
+    );
+    assert!(err.contains("is not a perp market"));
+
+    // Longs pay shorts when the funding is positive
+    let events = execute_action_ok(&mut orderbook, &mut operator, settle(5), Vec::new());
+    assert!(events.contains(&OrderbookEvent::FundingRateAccrued {
+        pair: perp.clone(),
+        funding_index: 5,
+    }));
+    assert!(events.contains(&OrderbookEvent::FundingPaid {
+        user: taker.user.clone(),
+        pair: perp.clone(),
+        amount: 20,
+    }));
+    assert!(events.contains(&OrderbookEvent::FundingPaid {
+        user: maker.user.clone(),
+        pair: perp.clone(),
+        amount: -20,
+    }));
+    assert_eq!(orderbook.state.get_balance(&taker, &perp.1).0, 940);
+    assert_eq!(orderbook.state.get_balance(&maker, &perp.1).0, 920);
+
+    // A payer short of free balance pays the rest from its margin
+    let events = execute_action_ok(&mut orderbook, &mut operator, settle(300), Vec::new());
+    assert!(events.contains(&OrderbookEvent::FundingPaid {
+        user: taker.user.clone(),
+        pair: perp.clone(),
+        amount: 980,
+    }));
+    assert_eq!(orderbook.state.get_balance(&taker, &perp.1).0, 0);
+    assert_eq!(orderbook.state.get_balance(&maker, &perp.1).0, 2_120);
+    let taker_position = orderbook
+        .state
+        .get_user_info(&taker.user)
+        .expect("taker should exist")
+        .get_position(&perp)
+        .cloned();
+    assert_eq!(
+        taker_position,
+        Some(Position {
+            pair: perp.clone(),
+            size: 4,
+            entry_price: 100,
+            margin: 0,
+            funding_index: 305,
+        })
+    );
+}
+
+#[test]
+fn cross_margin_borrows_against_collateral_and_calls_accounts() {
+    let mut orderbook = build_orderbook();
+    let spot = sample_pair();
+    let perp: Pair = ("SOL-PERP".to_string(), "USDC".to_string());
+    let mut maker = test_user("yan");
+    let mut taker = test_user("zoe");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let maker_signer = TestSigner::new(22);
+    let taker_signer = TestSigner::new(23);
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::CreatePair {
+            pair: spot.clone(),
+            info: make_pair_info(&spot, 0, 0),
+        },
+        Vec::new(),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::CreatePerpMarket {
+            pair: perp.clone(),
+            info: make_pair_info(&perp, 0, 0),
+            config: PerpConfig {
+                initial_margin_bps: 1_000,
+                maintenance_margin_bps: 500,
+                liquidation_penalty_bps: 0,
+            },
+        },
+        Vec::new(),
+    );
+
+    let collateral = |symbol: &str| PermissionedOrderbookAction::UpdateCollaterals {
+        updates: vec![(
+            symbol.to_string(),
+            Some(CollateralConfig {
+                quote: "USDC".to_string(),
+                weight_bps: 5_000,
+            }),
+        )],
+    };
+    let err = execute_action_err(&mut orderbook, &taker, collateral("ETH"), Vec::new());
+    assert!(err.contains("can update collaterals"));
+    let err = execute_action_err(
+        &mut orderbook,
+        &operator,
+        collateral("SOL-PERP"),
+        Vec::new(),
+    );
+    assert!(err.contains("cannot be a collateral"));
+    execute_action_ok(&mut orderbook, &mut operator, collateral("ETH"), Vec::new());
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::UpdateIndexPrices {
+            prices: vec![(spot.clone(), 100)],
+            block_height: 0,
+        },
+        Vec::new(),
+    );
+
+    for (user, signer, symbol, amount) in [
+        (&mut maker, &maker_signer, &perp.1, 1_000),
+        (&mut taker, &taker_signer, &spot.0, 1),
+    ] {
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::AddSessionKey,
+            serialize(&AddSessionKeyPrivateInput {
+                new_public_key: signer.public_key.clone(),
+                permissions: SessionKeyPermissions::ALL,
+                pair: None,
+            }),
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::Deposit {
+                symbol: symbol.clone(),
+                amount,
+            },
+            Vec::new(),
+        );
+    }
+
+    let create_order = |user: &UserInfo, signer: &TestSigner, order: Order| {
+        let order = Order {
+            pair: perp.clone(),
+            ..order
+        };
+        let message = format!(
+            "{}:{}:create_order:{}",
+            user.user, user.nonce, order.order_id
+        );
+        (
+            PermissionedOrderbookAction::CreateOrder(order),
+            serialize(&CreateOrderPrivateInput {
+                signature: signer.sign(&message),
+                public_key: signer.public_key.clone(),
+                block_height: 0,
+            }),
+        )
+    };
+    let (action, private_input) = create_order(
+        &maker,
+        &maker_signer,
+        make_limit_order("ask-1", OrderSide::Ask, 100, 10),
+    );
+    execute_action_ok(&mut orderbook, &mut maker, action, private_input);
+
+    // 1 ETH weighted at 50% backs 50 USDC of equity, under the 100 USDC of initial margin
+    let (action, private_input) = create_order(
+        &taker,
+        &taker_signer,
+        make_limit_order("bid-1", OrderSide::Bid, 100, 10),
+    );
+    let err = execute_action_err(&mut orderbook, &taker, action, private_input);
+    assert!(err.contains("has not enough USDC margin"));
+
+    execute_action_ok(
+        &mut orderbook,
+        &mut taker,
+        PermissionedOrderbookAction::Deposit {
+            symbol: spot.0.clone(),
+            amount: 3,
+        },
+        Vec::new(),
+    );
+    let (action, private_input) = create_order(
+        &taker,
+        &taker_signer,
+        make_limit_order("bid-1", OrderSide::Bid, 100, 10),
+    );
+    let events = execute_action_ok(&mut orderbook, &mut taker, action, private_input);
+    assert!(events.contains(&OrderbookEvent::DebtUpdated {
+        user: taker.user.clone(),
+        debt: Debt {
+            symbol: perp.1.clone(),
+            amount: 100,
+        },
+    }));
+    let taker_info = orderbook
+        .state
+        .get_user_info(&taker.user)
+        .expect("taker should exist");
+    assert_eq!(
+        orderbook.state.get_margin_account(&taker_info, &perp.1),
+        Ok(MarginAccount {
+            quote: perp.1.clone(),
+            equity: 200,
+            initial_margin: 100,
+            maintenance_margin: 50,
+        })
+    );
+
+    let withdraw_message = format!("{}:{}:withdraw:{}:{}", taker.user, taker.nonce, spot.0, 3);
+    let err = execute_action_err(
+        &mut orderbook,
+        &taker,
+        PermissionedOrderbookAction::Withdraw {
+            symbol: spot.0.clone(),
+            amount: 3,
+            destination: WithdrawDestination {
+                network: "hyli".to_string(),
+                address: "dest-address".to_string(),
+            },
+            block_height: 0,
+        },
+        serialize(&WithdrawPrivateInput {
+            signature: taker_signer.sign(&withdraw_message),
+            public_key: taker_signer.public_key.clone(),
+        }),
+    );
+    assert!(err.contains("margin account"));
+
+    // Collateral and position losses take the equity under the maintenance margin
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::UpdateIndexPrices {
+            prices: vec![(spot.clone(), 40), (perp.clone(), 95)],
+            block_height: 0,
+        },
+        Vec::new(),
+    );
+    assert!(events.contains(&OrderbookEvent::IndexPriceUpdated {
+        pair: perp.clone(),
+        price: 95,
+    }));
+    assert!(events.contains(&OrderbookEvent::MarginCalled {
+        user: taker.user.clone(),
+        account: MarginAccount {
+            quote: perp.1.clone(),
+            equity: 30,
+            initial_margin: 95,
+            maintenance_margin: 48,
+        },
+    }));
+    assert!(!events.iter().any(|event| matches!(
+        event,
+        OrderbookEvent::MarginCalled { user, .. } if user == &maker.user
+    )));
+
+    // Deposits of the borrowed asset repay the debt first
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut taker,
+        PermissionedOrderbookAction::Deposit {
+            symbol: perp.1.clone(),
+            amount: 150,
+        },
+        Vec::new(),
+    );
+    assert!(events.contains(&OrderbookEvent::DebtUpdated {
+        user: taker.user.clone(),
+        debt: Debt {
+            symbol: perp.1.clone(),
+            amount: 0,
+        },
+    }));
+    assert_eq!(orderbook.state.get_balance(&taker, &perp.1).0, 50);
+}
+
+#[test]
+fn liquidation_force_closes_under_margined_positions() {
+    let mut orderbook = build_orderbook();
+    let perp: Pair = ("SOL-PERP".to_string(), "USDC".to_string());
+    let mut maker = test_user("abe");
+    let mut trader = test_user("bea");
+    let mut bidder = test_user("cid");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let maker_signer = TestSigner::new(24);
+    let trader_signer = TestSigner::new(25);
+    let bidder_signer = TestSigner::new(26);
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        PermissionedOrderbookAction::CreatePerpMarket {
+            pair: perp.clone(),
+            info: make_pair_info(&perp, 0, 0),
+            config: PerpConfig {
+                initial_margin_bps: 1_000,
+                maintenance_margin_bps: 500,
+                liquidation_penalty_bps: 0,
+            },
+        },
+        Vec::new(),
+    );
+
+    for (user, signer, amount) in [
+        (&mut maker, &maker_signer, 1_000),
+        (&mut trader, &trader_signer, 120),
+        (&mut bidder, &bidder_signer, 1_000),
+    ] {
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::AddSessionKey,
+            serialize(&AddSessionKeyPrivateInput {
+                new_public_key: signer.public_key.clone(),
+                permissions: SessionKeyPermissions::ALL,
+                pair: None,
+            }),
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::Deposit {
+                symbol: perp.1.clone(),
+                amount,
+            },
+            Vec::new(),
+        );
+    }
+
+    let create_order = |user: &UserInfo, signer: &TestSigner, order: Order| {
+        let order = Order {
+            pair: perp.clone(),
+            ..order
+        };
+        let message = format!(
+            "{}:{}:create_order:{}",
+            user.user, user.nonce, order.order_id
+        );
+        (
+            PermissionedOrderbookAction::CreateOrder(order),
+            serialize(&CreateOrderPrivateInput {
+                signature: signer.sign(&message),
+                public_key: signer.public_key.clone(),
+                block_height: 0,
+            }),
+        )
+    };
+    for (user, signer, order) in [
+        (
+            &mut maker,
+            &maker_signer,
+            make_limit_order("ask-1", OrderSide::Ask, 100, 10),
+        ),
+        (
+            &mut bidder,
+            &bidder_signer,
+            make_limit_order("bid-3", OrderSide::Bid, 85, 10),
+        ),
+    ] {
+        let (action, private_input) = create_order(user, signer, order);
+        execute_action_ok(&mut orderbook, user, action, private_input);
+    }
+    // The trader goes long 10 at 100, with a resting bid under the book
+    for order in [
+        make_limit_order("bid-1", OrderSide::Bid, 100, 10),
+        make_limit_order("bid-2", OrderSide::Bid, 50, 1),
+    ] {
+        let (action, private_input) = create_order(&trader, &trader_signer, order);
+        execute_action_ok(&mut orderbook, &mut trader, action, private_input);
+    }
+    assert_eq!(orderbook.state.get_balance(&trader, &perp.1).0, 15);
+
+    let index_price = |price| PermissionedOrderbookAction::UpdateIndexPrices {
+        prices: vec![(perp.clone(), price)],
+        block_height: 0,
+    };
+    let liquidate = |user: &UserInfo| PermissionedOrderbookAction::Liquidate {
+        user: user.user.clone(),
+        pair: perp.clone(),
+        order_id: "liquidation-1".to_string(),
+        block_height: 0,
+    };
+    let err = execute_action_err(&mut orderbook, &bidder, liquidate(&trader), Vec::new());
+    assert!(err.contains("can liquidate positions"));
+    let err = execute_action_err(&mut orderbook, &operator, liquidate(&bidder), Vec::new());
+    assert!(err.contains("has no position"));
+
+    // Equity of 15 + 100 - 60 stays above the 47 of maintenance margin at 94
+    execute_action_ok(&mut orderbook, &mut operator, index_price(94), Vec::new());
+    let err = execute_action_err(&mut orderbook, &operator, liquidate(&trader), Vec::new());
+    assert!(err.contains("cannot be liquidated"));
+
+    // At 85 the equity is negative: the position is sold to the book and the loss borrowed
+    execute_action_ok(&mut orderbook, &mut operator, index_price(85), Vec::new());
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        liquidate(&trader),
+        Vec::new(),
+    );
+    assert_eq!(
+        events.first(),
+        Some(&OrderbookEvent::Liquidation {
+            user: trader.user.clone(),
+            pair: perp.clone(),
+            size: 10,
+            account: MarginAccount {
+                quote: perp.1.clone(),
+                equity: -35,
+                initial_margin: 85,
+                maintenance_margin: 43,
+            },
+        })
+    );
+    assert!(events.contains(&OrderbookEvent::OrderCancelled {
+        order_id: "bid-2".to_string(),
+        pair: perp.clone(),
+    }));
+    assert!(events.contains(&OrderbookEvent::DebtUpdated {
+        user: trader.user.clone(),
+        debt: Debt {
+            symbol: perp.1.clone(),
+            amount: 30,
+        },
+    }));
+    let trader_info = orderbook
+        .state
+        .get_user_info(&trader.user)
+        .expect("trader should exist");
+    assert_eq!(trader_info.get_position(&perp), None);
+    assert_eq!(orderbook.state.get_balance(&trader, &perp.1).0, 0);
+    let bidder_position = orderbook
+        .state
+        .get_user_info(&bidder.user)
+        .expect("bidder should exist")
+        .get_position(&perp)
+        .cloned();
+    assert_eq!(
+        bidder_position.map(|position| (position.size, position.entry_price)),
+        Some((10, 85))
+    );
+}
+
+#[test]
+fn insurance_fund_collects_penalties_and_covers_bankrupt_accounts() {
+    let mut orderbook = build_orderbook();
+    let perp: Pair = ("SOL-PERP".to_string(), "USDC".to_string());
+    let mut maker = test_user("dee");
+    let mut solvent = test_user("eli");
+    let mut bankrupt = test_user("fay");
+    let mut first_bidder = test_user("gus");
+    let mut second_bidder = test_user("hal");
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let create_perp_market =
+        |liquidation_penalty_bps| PermissionedOrderbookAction::CreatePerpMarket {
+            pair: perp.clone(),
+            info: make_pair_info(&perp, 0, 0),
+            config: PerpConfig {
+                initial_margin_bps: 1_000,
+                maintenance_margin_bps: 500,
+                liquidation_penalty_bps,
+            },
+        };
+    let err = execute_action_err(
+        &mut orderbook,
+        &operator,
+        create_perp_market(600),
+        Vec::new(),
+    );
+    assert!(err.contains("cannot exceed its maintenance margin"));
+    execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        create_perp_market(200),
         Vec::new(),
     );
 
+    let create_order = |user: &UserInfo, signer: &TestSigner, order: Order| {
+        let order = Order {
+            pair: perp.clone(),
+            ..order
+        };
+        let message = format!(
+            "{}:{}:create_order:{}",
+            user.user, user.nonce, order.order_id
+        );
+        (
+            PermissionedOrderbookAction::CreateOrder(order),
+            serialize(&CreateOrderPrivateInput {
+                signature: signer.sign(&message),
+                public_key: signer.public_key.clone(),
+                block_height: 0,
+            }),
+        )
+    };
+    for (user, seed, amount, order) in [
+        (
+            &mut maker,
+            27,
+            2_000,
+            make_limit_order("ask-1", OrderSide::Ask, 100, 20),
+        ),
+        (
+            &mut solvent,
+            28,
+            130,
+            make_limit_order("bid-1", OrderSide::Bid, 100, 10),
+        ),
+        (
+            &mut bankrupt,
+            29,
+            100,
+            make_limit_order("bid-2", OrderSide::Bid, 100, 10),
+        ),
+        (
+            &mut first_bidder,
+            30,
+            1_000,
+            make_limit_order("bid-3", OrderSide::Bid, 90, 10),
+        ),
+        (
+            &mut second_bidder,
+            31,
+            1_000,
+            make_limit_order("bid-4", OrderSide::Bid, 80, 10),
+        ),
+    ] {
+        let signer = TestSigner::new(seed);
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::AddSessionKey,
+            serialize(&AddSessionKeyPrivateInput {
+                new_public_key: signer.public_key.clone(),
+                permissions: SessionKeyPermissions::ALL,
+                pair: None,
+            }),
+        );
+        execute_action_ok(
+            &mut orderbook,
+            user,
+            PermissionedOrderbookAction::Deposit {
+                symbol: perp.1.clone(),
+                amount,
+            },
+            Vec::new(),
+        );
+        let (action, private_input) = create_order(user, &signer, order);
+        execute_action_ok(&mut orderbook, user, action, private_input);
+    }
     execute_action_ok(
         &mut orderbook,
-        &mut user,
-        PermissionedOrderbookAction::AddSessionKey {},
-        serialize(&AddSessionKeyPrivateInput {
-            new_public_key: session_key.clone(),
-        }),
+        &mut operator,
+        PermissionedOrderbookAction::UpdateIndexPrices {
+            prices: vec![(perp.clone(), 90)],
+            block_height: 0,
+        },
+        Vec::new(),
     );
 
+    let liquidate = |user: &UserInfo, order_id: &str| PermissionedOrderbookAction::Liquidate {
+        user: user.user.clone(),
+        pair: perp.clone(),
+        order_id: order_id.to_string(),
+        block_height: 0,
+    };
+    let fund_balance = |orderbook: &FullState| {
+        let fund = orderbook
+            .state
+            .get_user_info(INSURANCE_FUND_IDENTITY)
+            .expect("insurance fund should be registered");
+        orderbook.state.get_balance(&fund, &perp.1).0
+    };
+
+    // Closed at 90 without loss, the account pays 2% of the 900 closed to the fund
     let events = execute_action_ok(
         &mut orderbook,
-        &mut user,
-        PermissionedOrderbookAction::Deposit {
-            symbol: pair.1.clone(),
-            amount: 500,
-        },
+        &mut operator,
+        liquidate(&solvent, "liquidation-1"),
         Vec::new(),
     );
+    assert!(events.iter().any(|event| matches!(
+        event,
+        OrderbookEvent::SessionKeyAdded { user, session_keys, .. }
+            if user == INSURANCE_FUND_IDENTITY && session_keys.is_empty()
+    )));
+    assert!(events.contains(&OrderbookEvent::InsuranceFundUpdated {
+        user: solvent.user.clone(),
+        symbol: perp.1.clone(),
+        change: 18,
+    }));
+    assert_eq!(orderbook.state.get_balance(&solvent, &perp.1).0, 12);
+    assert_eq!(fund_balance(&orderbook), 18);
 
-    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 500);
-    assert_eq!(events.len(), 1);
-    assert!(matches!(
-        events[0],
-        OrderbookEvent::BalanceUpdated { ref user, ref symbol, amount }
-            if user == "bob" && symbol == &pair.1 && amount == 500
-    ));
+    // Closed at 80, the account is left 100 in debt, of which the fund repays what it holds
+    let events = execute_action_ok(
+        &mut orderbook,
+        &mut operator,
+        liquidate(&bankrupt, "liquidation-2"),
+        Vec::new(),
+    );
+    assert!(events.contains(&OrderbookEvent::InsuranceFundUpdated {
+        user: bankrupt.user.clone(),
+        symbol: perp.1.clone(),
+        change: -18,
+    }));
+    assert_eq!(
+        events
+            .iter()
+            .rev()
+            .find(|event| matches!(event, OrderbookEvent::DebtUpdated { .. })),
+        Some(&OrderbookEvent::DebtUpdated {
+            user: bankrupt.user.clone(),
+            debt: Debt {
+                symbol: perp.1.clone(),
+                amount: 82,
+            },
+        })
+    );
+    assert_eq!(fund_balance(&orderbook), 0);
 }
 
 #[test]
-fn withdraw_deducts_balance() {
+fn registration_requires_canonical_identity() {
     let mut orderbook = build_orderbook();
-    let pair = sample_pair();
-    let mut user = test_user("carol");
-    let signer = TestSigner::new(2);
-    let session_key = signer.public_key.clone();
+    let signer = TestSigner::new(14);
+    let payload = || {
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: signer.public_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        })
+    };
+
+    for (identity, expected) in [
+        ("Olga@wallet", "canonical form \"olga@wallet\""),
+        (" olga@wallet", "canonical form"),
+        ("olga wallet", "not allowed"),
+        ("olga@wallet@evm", "at most one '@'"),
+        (ORDERBOOK_ACCOUNT_IDENTITY, "reserved"),
+        ("olga@orderbook", "reserved"),
+        ("", "length"),
+    ] {
+        let err = execute_action_err(
+            &mut orderbook,
+            &test_user(identity),
+            PermissionedOrderbookAction::AddSessionKey,
+            payload(),
+        );
+        assert!(err.contains(expected), "{identity:?}: {err}");
+    }
+
+    let long_identity = "o".repeat(crate::utils::MAX_IDENTITY_LEN + 1);
+    let err = execute_action_err(
+        &mut orderbook,
+        &test_user(&long_identity),
+        PermissionedOrderbookAction::AddSessionKey,
+        payload(),
+    );
+    assert!(err.contains("length"));
 
+    let mut user = test_user("olga@wallet");
     execute_action_ok(
         &mut orderbook,
         &mut user,
         PermissionedOrderbookAction::AddSessionKey,
-        serialize(&AddSessionKeyPrivateInput {
-            new_public_key: session_key.clone(),
-        }),
+        payload(),
     );
+    assert_eq!(user.nonce, 1);
+}
 
+#[test]
+fn sub_accounts_have_their_own_keys_and_balances() {
+    let mut orderbook = build_orderbook();
+    let signer = TestSigner::new(32);
+    let payload = || {
+        serialize(&AddSessionKeyPrivateInput {
+            new_public_key: signer.public_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        })
+    };
+    let mut parent = test_user("rhea@wallet");
     execute_action_ok(
         &mut orderbook,
-        &mut user,
+        &mut parent,
+        PermissionedOrderbookAction::AddSessionKey,
+        payload(),
+    );
+    execute_action_ok(
+        &mut orderbook,
+        &mut parent,
         PermissionedOrderbookAction::CreatePair {
-            pair: pair.clone(),
-            info: make_pair_info(&pair, 3, 2),
+            pair: sample_pair(),
+            info: make_pair_info(&sample_pair(), 0, 0),
         },
         Vec::new(),
     );
-
     execute_action_ok(
         &mut orderbook,
-        &mut user,
+        &mut parent,
         PermissionedOrderbookAction::Deposit {
-            symbol: pair.1.clone(),
-            amount: 1_000,
+            symbol: "USDC".to_string(),
+            amount: 100,
         },
         Vec::new(),
     );
 
-    let destination = WithdrawDestination {
-        network: "hyli".to_string(),
-        address: "dest-address".to_string(),
-    };
-    let withdraw_message = format!("{}:{}:withdraw:{}:{}", user.user, user.nonce, pair.1, 400);
-    let withdraw_events = execute_action_ok(
+    for (name, expected) in [
+        ("", "length"),
+        ("Strategy1", "not allowed in a sub-account name"),
+        ("strategy/1", "not allowed in a sub-account name"),
+    ] {
+        let err = execute_action_err(
+            &mut orderbook,
+            &UserInfo::new_sub_account(&parent, name),
+            PermissionedOrderbookAction::AddSessionKey,
+            payload(),
+        );
+        assert!(err.contains(expected), "{name:?}: {err}");
+    }
+    let err = execute_action_err(
         &mut orderbook,
-        &mut user,
-        PermissionedOrderbookAction::Withdraw {
-            symbol: pair.1.clone(),
-            amount: 400,
-            destination: destination.clone(),
-        },
-        serialize(&WithdrawPrivateInput {
-            signature: signer.sign(&withdraw_message),
-            public_key: session_key.clone(),
-        }),
+        &UserInfo::new_sub_account(&test_user("rhea@orderbook"), "strategy1"),
+        PermissionedOrderbookAction::AddSessionKey,
+        payload(),
     );
+    assert!(err.contains("reserved"));
 
-    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 600);
-    assert_eq!(withdraw_events.len(), 2);
-    assert!(matches!(
-        withdraw_events[0],
-        OrderbookEvent::BalanceUpdated { ref user, ref symbol, amount }
-            if user == "carol" && symbol == &pair.1 && amount == 600
-    ));
+    // The sub-account key is derived from the key of its parent
+    let mut sub_account = UserInfo::new_sub_account(&parent, "strategy1");
+    let expected_key: [u8; 32] = Sha3_256::new()
+        .chain_update(<[u8; 32]>::from(parent.get_key()))
+        .chain_update(b"/strategy1")
+        .finalize()
+        .into();
+    assert_eq!(<[u8; 32]>::from(sub_account.get_key()), expected_key);
 
-    let overdraft_message = format!("{}:{}:withdraw:{}:{}", user.user, user.nonce, pair.1, 700);
-    let err = execute_action_err(
+    execute_action_ok(
         &mut orderbook,
-        &user,
-        PermissionedOrderbookAction::Withdraw {
-            symbol: pair.1.clone(),
-            amount: 700,
-            destination,
-        },
-        serialize(&WithdrawPrivateInput {
-            signature: signer.sign(&overdraft_message),
-            public_key: session_key,
-        }),
+        &mut sub_account,
+        PermissionedOrderbookAction::AddSessionKey,
+        payload(),
+    );
+    assert_eq!(sub_account.nonce, 1);
+    assert_eq!(orderbook.state.get_balance(&sub_account, "USDC").0, 0);
+    assert_eq!(orderbook.state.get_balance(&parent, "USDC").0, 100);
+    assert_eq!(
+        orderbook
+            .state
+            .get_user_info(&sub_account.user)
+            .expect("sub-account should exist")
+            .session_keys,
+        vec![signer.public_key.clone()]
+    );
+    assert!(
+        orderbook
+            .state
+            .get_user_info(&sub_account.user)
+            .expect("sub-account should exist")
+            .sub_account
     );
-    assert!(err.contains("Insufficient balance"));
 }
 
 #[test]
-fn cancel_order_refunds_and_removes() {
-    let mut orderbook = build_orderbook();
-    let pair = sample_pair();
-    let mut user = test_user("dan");
-    let signer = TestSigner::new(3);
-    let session_key = signer.public_key.clone();
+fn identities_registered_before_sub_accounts_keep_their_key() {
+    // Identities were not validated before sub-accounts existed, so some contain the separator
+    let mut legacy = test_user("old@wallet/desk");
+    legacy.nonce = 1;
+    let legacy_key: [u8; 32] = Sha3_256::new()
+        .chain_update(b"old@wallet/desk")
+        .chain_update(b"old@wallet/desk")
+        .finalize()
+        .into();
+    assert_eq!(<[u8; 32]>::from(legacy.get_key()), legacy_key);
+
+    let light = ExecuteState::from_data(
+        HashMap::from([(sample_pair(), make_pair_info(&sample_pair(), 0, 0))]),
+        OrderManager::default(),
+        HashMap::from([(legacy.user.clone(), legacy.clone())]),
+        HashMap::from([(
+            "USDC".to_string(),
+            HashMap::from([(legacy.get_key(), Balance(40))]),
+        )]),
+    )
+    .expect("light state");
+    let mut orderbook = FullState::from_data(
+        &light,
+        b"secret".to_vec(),
+        LaneId::default(),
+        BlockHeight(0),
+    )
+    .expect("full state");
+    assert_eq!(orderbook.state.get_balance(&legacy, "USDC").0, 40);
 
     execute_action_ok(
         &mut orderbook,
-        &mut user,
+        &mut legacy,
+        PermissionedOrderbookAction::Deposit {
+            symbol: "USDC".to_string(),
+            amount: 10,
+        },
+        Vec::new(),
+    );
+    assert_eq!(<[u8; 32]>::from(legacy.get_key()), legacy_key);
+    assert_eq!(orderbook.state.get_balance(&legacy, "USDC").0, 50);
+
+    // New identities containing the separator can only be registered as sub-accounts
+    let signer = TestSigner::new(33);
+    let err = execute_action_err(
+        &mut orderbook,
+        &test_user("new@wallet/desk"),
         PermissionedOrderbookAction::AddSessionKey,
         serialize(&AddSessionKeyPrivateInput {
-            new_public_key: session_key.clone(),
+            new_public_key: signer.public_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
         }),
     );
+    assert!(err.contains("is not allowed"), "{err}");
+}
 
-    execute_action_ok(
+#[test]
+fn onboard_users_registers_identities_in_one_action() {
+    let mut orderbook = build_orderbook();
+    let mut operator = UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new());
+    let onboarded = |user: &str, seed: u8| OnboardedUser {
+        user: user.to_string(),
+        salt: user.as_bytes().to_vec(),
+        public_key: TestSigner::new(seed).public_key,
+        permissions: SessionKeyPermissions::TRADE_ONLY,
+        pair: None,
+    };
+    let payload = |users: Vec<OnboardedUser>| serialize(&OnboardUsersPrivateInput { users });
+
+    let err = execute_action_err(
         &mut orderbook,
-        &mut user,
-        PermissionedOrderbookAction::CreatePair {
-            pair: pair.clone(),
-            info: make_pair_info(&pair, 3, 2),
-        },
-        Vec::new(),
+        &test_user("paul@desk"),
+        PermissionedOrderbookAction::OnboardUsers,
+        payload(vec![onboarded("paul@desk", 15)]),
     );
+    assert!(err.contains("can onboard users"));
 
-    orderbook
-        .state
-        .users_info
-        .insert(user.user.clone(), user.clone());
-    let order = make_limit_order("order-1", OrderSide::Bid, 100, 10);
-
-    orderbook
-        .state
-        .order_manager
-        .insert_order(&order, &user.get_key())
-        .expect("order insertion should succeed");
-
-    let mut balances = BTreeMap::new();
-    balances.insert(user.clone(), Balance(0));
+    for (users, expected) in [
+        (vec![], "No users"),
+        (
+            vec![onboarded("paul@desk", 15), onboarded("paul@desk", 16)],
+            "onboarded twice",
+        ),
+        (vec![onboarded("Paul@desk", 15)], "Invalid identity"),
+    ] {
+        let err = execute_action_err(
+            &mut orderbook,
+            &operator,
+            PermissionedOrderbookAction::OnboardUsers,
+            payload(users),
+        );
+        assert!(err.contains(expected), "{err}");
+    }
 
-    let cancel_message = format!("{}:{}:cancel:{}", user.user, user.nonce, order.order_id);
     let events = execute_action_ok(
         &mut orderbook,
-        &mut user,
-        PermissionedOrderbookAction::Cancel {
-            order_id: order.order_id.clone(),
-        },
-        serialize(&CreateOrderPrivateInput {
-            signature: signer.sign(&cancel_message),
-            public_key: session_key,
-        }),
+        &mut operator,
+        PermissionedOrderbookAction::OnboardUsers,
+        payload(vec![
+            onboarded("paul@desk", 15),
+            onboarded("quinn@desk", 16),
+        ]),
     );
+    assert_eq!(events.len(), 5);
+    let operator = orderbook
+        .state
+        .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+        .expect("operator should exist");
+    assert_eq!(operator.nonce, 1);
+    for (user, seed) in [("paul@desk", 15), ("quinn@desk", 16)] {
+        let user_info = orderbook
+            .state
+            .get_user_info(user)
+            .expect("onboarded user should exist");
+        assert_eq!(user_info.nonce, 1);
+        assert_eq!(
+            user_info.session_keys,
+            vec![TestSigner::new(seed).public_key]
+        );
+        assert_eq!(
+            user_info.session_key_scopes[0].permissions,
+            SessionKeyPermissions::TRADE_ONLY
+        );
+    }
 
-    assert!(orderbook.state.order_manager.orders.is_empty());
-    assert_eq!(orderbook.state.order_manager.count_buy_orders(&pair), 0);
-    assert_eq!(orderbook.state.order_manager.count_sell_orders(&pair), 0);
-    assert_eq!(orderbook.state.get_balance(&user, &pair.1).0, 10);
-
-    assert!(events.iter().any(|event| matches!(
-        event,
-        OrderbookEvent::OrderCancelled { order_id, .. } if order_id == "order-1"
-    )));
+    let err = execute_action_err(
+        &mut orderbook,
+        &operator,
+        PermissionedOrderbookAction::OnboardUsers,
+        payload(vec![onboarded("paul@desk", 17)]),
+    );
+    assert!(err.contains("already registered"));
 }
 
 #[test]
@@ -958,3 +4078,119 @@ fn perf_insert_order_mixed_sides() {
         num_orders_per_side as usize
     );
 }
+
+#[test]
+fn price_level_keeps_time_priority_across_removals() {
+    let mut level = PriceLevel::from(["a", "b", "c", "d"].map(String::from).to_vec());
+
+    assert!(level.remove(&"b".to_string()));
+    assert!(!level.remove(&"b".to_string()));
+    assert!(level.remove(&"d".to_string()));
+    level.push_back("e".to_string());
+    level.push_front("f".to_string());
+
+    let order_ids: Vec<_> = level.iter().cloned().collect();
+    assert_eq!(order_ids, ["f", "a", "c", "e"].map(String::from).to_vec());
+    assert_eq!(level.len(), 4);
+    assert_eq!(level.front(), Some(&"f".to_string()));
+    assert_eq!(level.back(), Some(&"e".to_string()));
+    assert!(level.contains(&"c".to_string()));
+    assert!(!level.contains(&"d".to_string()));
+
+    assert_eq!(level.pop_front(), Some("f".to_string()));
+    for order_id in ["a", "c", "e"] {
+        assert!(level.remove(&order_id.to_string()));
+    }
+    assert!(level.is_empty());
+    assert_eq!(level.front(), None);
+    assert_eq!(level.iter().count(), 0);
+}
+
+#[test]
+fn price_level_encodes_as_its_queue() {
+    let mut level = PriceLevel::from(["a", "b", "c"].map(String::from).to_vec());
+    level.remove(&"a".to_string());
+    // Reuses the slot freed by "a"
+    level.push_back("d".to_string());
+
+    let order_ids = ["b", "c", "d"].map(String::from).to_vec();
+    let encoded = borsh::to_vec(&level).expect("encoding price level");
+    assert_eq!(
+        encoded,
+        borsh::to_vec(&std::collections::VecDeque::from(order_ids.clone()))
+            .expect("encoding queue")
+    );
+    assert_eq!(
+        borsh::from_slice::<PriceLevel>(&encoded).expect("decoding price level"),
+        PriceLevel::from(order_ids)
+    );
+}
+
+#[test]
+fn clean_only_drops_the_emptied_price_level() {
+    let mut manager = OrderManager::new();
+    let user = test_user("maker");
+    let first = make_limit_order("ask-1", OrderSide::Ask, 100, 5);
+    let second = make_limit_order("ask-2", OrderSide::Ask, 101, 5);
+    for order in [&first, &second] {
+        execute_order(&mut manager, &user.get_key(), order).expect("order should rest");
+    }
+
+    let events = manager
+        .cancel_order_dry_run(&first.order_id)
+        .expect("cancel should be valid");
+    for event in &events {
+        manager
+            .apply_event(user.get_key(), event)
+            .expect("cancel should apply");
+    }
+    manager.clean(&events);
+
+    let levels = manager.ask_orders.get(&first.pair).expect("ask levels");
+    assert_eq!(levels.keys().copied().collect::<Vec<_>>(), vec![101]);
+    assert!(!manager.orders.contains_key(&first.order_id));
+}
+
+#[test]
+fn perf_cancel_orders_deep_level() {
+    use std::time::Instant;
+
+    let mut manager = OrderManager::new();
+    let user = test_user("perf_user");
+    let num_orders = 10_000;
+
+    println!("\n=== Performance Test: cancel (single price level) ===");
+    println!("Cancelling {num_orders} ask orders resting at the same price, newest first");
+
+    for i in 0..num_orders {
+        let order = make_limit_order(&format!("ask-{i}"), OrderSide::Ask, 100, 10);
+        manager
+            .insert_order(&order, &user.get_key())
+            .expect("insertion should succeed");
+    }
+
+    let start = Instant::now();
+    for i in (0..num_orders).rev() {
+        let order_id = format!("ask-{i}");
+        let events = manager
+            .cancel_order_dry_run(&order_id)
+            .expect("cancel should be valid");
+        for event in &events {
+            manager
+                .apply_event(user.get_key(), event)
+                .expect("cancel should apply");
+        }
+        manager.clean(&events);
+    }
+    let duration = start.elapsed();
+
+    println!("Total time: {duration:?}");
+    println!("Average time per cancel: {:?}", duration / num_orders);
+    println!(
+        "Cancels per second: {:.0}",
+        num_orders as f64 / duration.as_secs_f64()
+    );
+
+    assert!(manager.orders.is_empty());
+    assert!(!manager.ask_orders.contains_key(&sample_pair()));
+}
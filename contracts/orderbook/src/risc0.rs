@@ -0,0 +1,17 @@
+#![no_main]
+
+use orderbook::zk::ZkVmState;
+use sdk::{
+    guest::{execute, GuestEnv, Risc0Env},
+    Calldata,
+};
+
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    let env = Risc0Env {};
+    let (commitment_metadata, calldata): (Vec<u8>, Vec<Calldata>) = env.read();
+
+    let output = execute::<ZkVmState>(&commitment_metadata, &calldata);
+    env.commit(output);
+}
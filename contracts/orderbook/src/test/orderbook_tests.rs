@@ -11,15 +11,18 @@ use sdk::{BlobIndex, Calldata, ContractName, Identity, TxContext, TxHash};
 use sha3::{Digest, Sha3_256};
 
 use crate::model::{
-    AssetInfo, ExecuteState, Order, OrderSide, OrderType, OrderbookEvent, Pair, PairInfo, UserInfo,
+    AssetInfo, CircuitBreaker, CircuitBreakerState, ExecuteState, MarketStatus, Order, OrderSide,
+    OrderType, OrderbookEvent, Pair, PairInfo, PerpMarket, SessionKeyPermissions, UserInfo,
     WithdrawDestination,
 };
 use crate::transaction::{
-    AddSessionKeyPrivateInput, CancelOrderPrivateInput, CreateOrderPrivateInput, OrderbookAction,
-    PermissionedOrderbookAction, PermissionedPrivateInput, WithdrawPrivateInput,
+    AddSessionKeyPrivateInput, CancelOrderPrivateInput, CreateOrderPrivateInput,
+    OnboardUsersPrivateInput, OnboardedUser, OrderbookAction, PermissionedOrderbookAction,
+    PermissionedPrivateInput, WithdrawPrivateInput,
 };
-use crate::zk::OrderManagerRoots;
+use crate::zk::smt::GetKey;
 use crate::zk::{FullState, ZkVmState, H256};
+use crate::zk::{OrderManagerMerkles, OrderManagerRoots};
 use crate::ORDERBOOK_ACCOUNT_IDENTITY;
 
 struct TestSigner {
@@ -72,6 +75,10 @@ struct OwnedCommitment {
     users_info_root: H256,
     balances_roots: BTreeMap<String, H256>,
     assets: BTreeMap<String, AssetInfo>,
+    pairs_status: BTreeMap<Pair, MarketStatus>,
+    circuit_breakers: BTreeMap<Pair, CircuitBreakerState>,
+    perp_markets: BTreeMap<Pair, PerpMarket>,
+    bridge_paused: bool,
     order_commitment: OrderManagerRoots,
     hashed_secret: [u8; 32],
     lane_id: LaneId,
@@ -162,6 +169,86 @@ fn run_action(
     events
 }
 
+/// Executes the actions in order, and proves them all in a single guest execution
+/// from the merged commitment metadata
+fn run_batch(
+    light: &mut ExecuteState,
+    full: &mut FullState,
+    actions: Vec<(&str, PermissionedOrderbookAction, Vec<u8>)>,
+) {
+    let (cn, id, tx_ctx, _, secret) = get_ctx();
+
+    let batch_initial_state = full.clone();
+    let mut metadata = Vec::new();
+    let mut calldata = Vec::new();
+
+    for (index, (user, action, private_payload)) in actions.into_iter().enumerate() {
+        let user_info = light
+            .get_user_info(user)
+            .unwrap_or_else(|_| test_user(user));
+
+        let events = light
+            .execute_permissioned_action(user_info.clone(), action.clone(), &private_payload)
+            .expect("light execution");
+        light.order_manager.clean(&events);
+
+        metadata.push(
+            full.derive_zkvm_commitment_metadata_from_events(&user_info, &events, &action)
+                .expect("derive metadata"),
+        );
+        full.apply_events_and_update_roots(&user_info, events)
+            .expect("full execution");
+
+        let permissioned_private_input = PermissionedPrivateInput {
+            secret: secret.clone(),
+            user_info,
+            private_input: private_payload,
+        };
+        calldata.push(Calldata {
+            identity: id.clone(),
+            blobs:
+                vec![OrderbookAction::PermissionedOrderbookAction(action, 0).as_blob(cn.clone())]
+                    .into(),
+            tx_blob_count: 1,
+            index: BlobIndex(0),
+            tx_hash: TxHash::from(format!("batch-tx-hash-{index}").as_bytes()),
+            tx_ctx: Some(tx_ctx.clone()),
+            private_input: borsh::to_vec(&permissioned_private_input)
+                .expect("serialize private input"),
+        });
+    }
+
+    let commitment_metadata = batch_initial_state
+        .merge_zkvm_commitment_metadata(&metadata)
+        .expect("merge metadata");
+    let res = guest::execute::<ZkVmState>(&commitment_metadata, &calldata);
+
+    assert_eq!(res.len(), calldata.len(), "expected one output per tx");
+    for (index, hyli_output) in res.iter().enumerate() {
+        assert!(
+            hyli_output.success,
+            "batched tx {index} failed: {}",
+            String::from_utf8_lossy(&hyli_output.program_outputs)
+        );
+        if let Some(next_output) = res.get(index + 1) {
+            assert_eq!(
+                hyli_output.next_state, next_output.initial_state,
+                "batched tx {index} does not chain with the next one"
+            );
+        }
+    }
+    assert_eq!(
+        res[0].initial_state,
+        batch_initial_state.commit(),
+        "Full initial state mismatch for batch"
+    );
+    assert_eq!(
+        res[res.len() - 1].next_state,
+        full.commit(),
+        "Full next state mismatch for batch"
+    );
+}
+
 #[derive(Default, Clone, Copy)]
 struct BalanceExpectation {
     base: i128,
@@ -263,27 +350,40 @@ fn submit_signed_order<'a>(
     user: &str,
     order: Order,
 ) {
+    let private_payload = create_order_payload(full, users, signers, user, &order);
+
+    let _ = run_action(
+        light,
+        full,
+        user,
+        PermissionedOrderbookAction::CreateOrder(order),
+        private_payload,
+    );
+}
+
+fn create_order_payload<'a>(
+    full: &FullState,
+    users: &[&'a str],
+    signers: &'a [TestSigner],
+    user: &str,
+    order: &Order,
+) -> Vec<u8> {
     let signer = signer_for(users, signers, user);
     let user_info = full
         .state
         .get_user_info(user)
         .expect("user info for signature");
-    let order_id = order.order_id.clone();
-    let msg = format!("{}:{}:create_order:{}", user, user_info.nonce, order_id);
+    let msg = format!(
+        "{}:{}:create_order:{}",
+        user, user_info.nonce, order.order_id
+    );
     let signature = signer.sign(&msg);
     let private_input = CreateOrderPrivateInput {
         signature,
         public_key: signer.public_key.clone(),
+        block_height: 0,
     };
-    let private_payload = borsh::to_vec(&private_input).expect("serialize create order input");
-
-    let _ = run_action(
-        light,
-        full,
-        user,
-        PermissionedOrderbookAction::CreateOrder(order),
-        private_payload,
-    );
+    borsh::to_vec(&private_input).expect("serialize create order input")
 }
 
 fn cancel_signed_order<'a>(
@@ -328,6 +428,8 @@ fn add_session_key<'a>(
     let signer = signer_for(users, signers, user);
     let payload = borsh::to_vec(&AddSessionKeyPrivateInput {
         new_public_key: signer.public_key.clone(),
+        permissions: SessionKeyPermissions::ALL,
+        pair: None,
     })
     .expect("serialize add session key input");
 
@@ -397,6 +499,7 @@ fn withdraw_with_signature<'a>(
             symbol: symbol.to_string(),
             amount,
             destination,
+            block_height: 0,
         },
         private_payload,
     );
@@ -787,6 +890,8 @@ fn test_add_session_key_state_commitment() {
 
     let private_payload = borsh::to_vec(&AddSessionKeyPrivateInput {
         new_public_key: signer.public_key.clone(),
+        permissions: SessionKeyPermissions::ALL,
+        pair: None,
     })
     .expect("serialize add session key input");
 
@@ -824,6 +929,184 @@ fn test_add_session_key_state_commitment() {
     );
 }
 
+#[test_log::test]
+fn test_onboard_users_state_commitment() {
+    let (_, _, _, lane_id, secret) = get_ctx();
+
+    let mut light = ExecuteState::default();
+    let mut full = FullState::from_data(
+        &light,
+        secret.clone(),
+        lane_id.clone(),
+        BlockHeight::default(),
+    )
+    .expect("building full state");
+
+    let pair: Pair = ("ETH".to_string(), "USDC".to_string());
+    let _ = run_action(
+        &mut light,
+        &mut full,
+        "alice",
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: PairInfo {
+                base: AssetInfo::new(0, ContractName("ETH".to_string())),
+                quote: AssetInfo::new(0, ContractName("USDC".to_string())),
+            },
+        },
+        Vec::new(),
+    );
+
+    let signers = [TestSigner::new(1), TestSigner::new(2)];
+    let users = vec![
+        OnboardedUser {
+            user: "bob@desk".to_string(),
+            salt: b"bob-salt".to_vec(),
+            public_key: signers[0].public_key.clone(),
+            permissions: SessionKeyPermissions::ALL,
+            pair: None,
+        },
+        OnboardedUser {
+            user: "carol@desk".to_string(),
+            salt: b"carol-salt".to_vec(),
+            public_key: signers[1].public_key.clone(),
+            permissions: SessionKeyPermissions::CREATE_ORDER,
+            pair: Some(pair.clone()),
+        },
+    ];
+    let events = run_action(
+        &mut light,
+        &mut full,
+        ORDERBOOK_ACCOUNT_IDENTITY,
+        PermissionedOrderbookAction::OnboardUsers,
+        borsh::to_vec(&OnboardUsersPrivateInput {
+            users: users.clone(),
+        })
+        .expect("serialize onboard users input"),
+    );
+    assert_eq!(events.len(), 2 * users.len() + 1);
+
+    for (onboarded, signer) in users.iter().zip(&signers) {
+        let user_info = full
+            .state
+            .get_user_info(&onboarded.user)
+            .expect("onboarded user info");
+        assert_eq!(user_info.salt, onboarded.salt);
+        assert_eq!(user_info.nonce, 1);
+        assert_eq!(user_info.session_keys, vec![signer.public_key.clone()]);
+    }
+
+    // Onboarded users are committed like users who registered themselves
+    let _ = deposit(&mut light, &mut full, "bob@desk", "USDC", 1_000);
+    let rebuilt = FullState::from_data(&light, secret, lane_id, BlockHeight::default())
+        .expect("rebuilding full state");
+    assert_eq!(rebuilt.commit(), full.commit());
+}
+
+#[test_log::test]
+fn test_pair_status_state_commitment() {
+    let (_, _, _, lane_id, secret) = get_ctx();
+
+    let mut light = ExecuteState::default();
+    let mut full = FullState::from_data(
+        &light,
+        secret.clone(),
+        lane_id.clone(),
+        BlockHeight::default(),
+    )
+    .expect("building full state");
+
+    let pair: Pair = ("ETH".to_string(), "USDC".to_string());
+    let _ = run_action(
+        &mut light,
+        &mut full,
+        ORDERBOOK_ACCOUNT_IDENTITY,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: PairInfo {
+                base: AssetInfo::new(0, ContractName("ETH".to_string())),
+                quote: AssetInfo::new(0, ContractName("USDC".to_string())),
+            },
+        },
+        Vec::new(),
+    );
+    let parsed = decode_commitment(&full.commit());
+    assert_eq!(parsed.pairs_status.get(&pair), Some(&MarketStatus::Active));
+
+    let _ = run_action(
+        &mut light,
+        &mut full,
+        ORDERBOOK_ACCOUNT_IDENTITY,
+        PermissionedOrderbookAction::UpdatePairStatus {
+            pair: pair.clone(),
+            status: MarketStatus::Halted,
+        },
+        Vec::new(),
+    );
+    let parsed = decode_commitment(&full.commit());
+    assert_eq!(parsed.pairs_status.get(&pair), Some(&MarketStatus::Halted));
+
+    let rebuilt = FullState::from_data(&light, secret, lane_id, BlockHeight::default())
+        .expect("rebuilding full state");
+    assert_eq!(rebuilt.commit(), full.commit());
+}
+
+#[test_log::test]
+fn test_circuit_breaker_state_commitment() {
+    let (_, _, _, lane_id, secret) = get_ctx();
+
+    let mut light = ExecuteState::default();
+    let mut full = FullState::from_data(
+        &light,
+        secret.clone(),
+        lane_id.clone(),
+        BlockHeight::default(),
+    )
+    .expect("building full state");
+
+    let pair: Pair = ("ETH".to_string(), "USDC".to_string());
+    let _ = run_action(
+        &mut light,
+        &mut full,
+        ORDERBOOK_ACCOUNT_IDENTITY,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: PairInfo {
+                base: AssetInfo::new(0, ContractName("ETH".to_string())),
+                quote: AssetInfo::new(0, ContractName("USDC".to_string())),
+            },
+        },
+        Vec::new(),
+    );
+    assert!(decode_commitment(&full.commit())
+        .circuit_breakers
+        .is_empty());
+
+    let config = CircuitBreaker {
+        max_move_bps: 500,
+        window_blocks: 20,
+        halt_blocks: 10,
+    };
+    let _ = run_action(
+        &mut light,
+        &mut full,
+        ORDERBOOK_ACCOUNT_IDENTITY,
+        PermissionedOrderbookAction::UpdateCircuitBreakers {
+            updates: vec![(pair.clone(), Some(config.clone()))],
+        },
+        Vec::new(),
+    );
+    let parsed = decode_commitment(&full.commit());
+    assert_eq!(
+        parsed.circuit_breakers.get(&pair),
+        Some(&CircuitBreakerState::new(config))
+    );
+
+    let rebuilt = FullState::from_data(&light, secret, lane_id, BlockHeight::default())
+        .expect("rebuilding full state");
+    assert_eq!(rebuilt.commit(), full.commit());
+}
+
 #[test_log::test]
 fn test_equal_price_limit_orders_fill_in_fifo_order() {
     let (_, _, _, lane_id, secret) = get_ctx();
@@ -1136,6 +1419,9 @@ fn test_cancel_order_restores_balance_and_removes_state() {
         full.state.get_balance(&full_user_info, &quote_symbol).0,
         expected_quote_after_bid
     );
+    let locked = light.get_locked_balances(&light_user_info);
+    assert_eq!(locked.get(&base_symbol), Some(&ask_quantity));
+    assert_eq!(locked.get(&quote_symbol), Some(&(bid_quantity * bid_price)));
 
     assert!(
         light.order_manager.orders.contains_key(ask_order_id),
@@ -1203,6 +1489,9 @@ fn test_cancel_order_restores_balance_and_removes_state() {
         full.state.get_balance(&full_user_info, &quote_symbol).0,
         expected_quote_after_bid
     );
+    let locked = light.get_locked_balances(&light_user_info);
+    assert_eq!(locked.get(&base_symbol), None);
+    assert_eq!(locked.get(&quote_symbol), Some(&(bid_quantity * bid_price)));
 
     assert!(
         !light.order_manager.orders.contains_key(ask_order_id),
@@ -1258,6 +1547,87 @@ fn test_cancel_order_restores_balance_and_removes_state() {
     );
 }
 
+#[test_log::test]
+fn test_cloned_full_state_keeps_its_trees() {
+    let (_, _, _, lane_id, secret) = get_ctx();
+
+    let mut light = ExecuteState::default();
+    let mut full = FullState::from_data(
+        &light,
+        secret.clone(),
+        lane_id.clone(),
+        BlockHeight::default(),
+    )
+    .expect("building full state");
+
+    let pair: Pair = ("HYLLAR".to_string(), "ORANJ".to_string());
+    let pair_info = PairInfo {
+        base: AssetInfo::new(0, ContractName(pair.0.clone())),
+        quote: AssetInfo::new(0, ContractName(pair.1.clone())),
+    };
+
+    let users = ["alice"];
+    let signers = vec![TestSigner::new(1)];
+    let user = users[0];
+
+    add_session_key(&mut light, &mut full, &users, &signers, user);
+    let _ = run_action(
+        &mut light,
+        &mut full,
+        user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: pair_info,
+        },
+        Vec::new(),
+    );
+    let _ = deposit(&mut light, &mut full, user, &pair.0, 100);
+    for (order_id, price) in [("ask-1", 12), ("ask-2", 12), ("ask-3", 13)] {
+        submit_signed_order(
+            &mut light,
+            &mut full,
+            &users,
+            &signers,
+            user,
+            Order {
+                order_id: order_id.to_string(),
+                order_type: OrderType::Limit,
+                order_side: OrderSide::Ask,
+                price: Some(price),
+                pair: pair.clone(),
+                quantity: 10,
+            },
+        );
+    }
+
+    let mut cloned = full.clone();
+    assert_eq!(cloned.commit(), full.commit());
+    assert_eq!(
+        cloned.order_manager_mt.commitment(),
+        OrderManagerMerkles::from_order_manager(&full.state.order_manager)
+            .expect("rebuilding order manager trees")
+            .commitment()
+    );
+
+    // The clone updates its own trees
+    let initial_commitment = full.commit();
+    let mut cloned_light = light.clone();
+    let _ = cancel_signed_order(
+        &mut cloned_light,
+        &mut cloned,
+        &users,
+        &signers,
+        user,
+        "ask-2",
+    );
+    assert_eq!(full.commit(), initial_commitment);
+    assert_ne!(cloned.commit(), initial_commitment);
+
+    let rebuilt = FullState::from_data(&cloned_light, secret, lane_id, BlockHeight::default())
+        .expect("rebuilding full state");
+    assert_eq!(rebuilt.commit(), cloned.commit());
+}
+
 #[test_log::test]
 fn test_complex_multi_user_orderbook() {
     let (_, _, _, lane_id, secret) = get_ctx();
@@ -1905,3 +2275,232 @@ fn test_escape_cancels_orders_and_resets_balances() {
     assert_eq!(full.state.get_balance(&full_user_info, &pair.0).0, 0);
     assert_eq!(full.state.get_balance(&full_user_info, &pair.1).0, 0);
 }
+
+#[test_log::test]
+fn test_escape_transaction_pays_out_escape_balances() {
+    let (cn, _, _, lane_id, secret) = get_ctx();
+
+    let mut light = ExecuteState::default();
+    let mut full = FullState::from_data(
+        &light,
+        secret.clone(),
+        lane_id.clone(),
+        BlockHeight::default(),
+    )
+    .expect("building full state");
+
+    let pair: Pair = ("HYLLAR".to_string(), "ORANJ".to_string());
+    let pair_info = PairInfo {
+        base: AssetInfo::new(0, ContractName(pair.0.clone())),
+        quote: AssetInfo::new(0, ContractName(pair.1.clone())),
+    };
+
+    let users = ["pia"];
+    let signers = vec![TestSigner::new(40)];
+    let user = users[0];
+
+    add_session_key(&mut light, &mut full, &users, &signers, user);
+    let _ = run_action(
+        &mut light,
+        &mut full,
+        user,
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: pair_info,
+        },
+        Vec::new(),
+    );
+    let _ = deposit(&mut light, &mut full, user, &pair.0, 150);
+    let _ = deposit(&mut light, &mut full, user, &pair.1, 200);
+    submit_signed_order(
+        &mut light,
+        &mut full,
+        &users,
+        &signers,
+        user,
+        Order {
+            order_id: "escape-tx-ask".to_string(),
+            order_type: OrderType::Limit,
+            order_side: OrderSide::Ask,
+            price: Some(10),
+            pair: pair.clone(),
+            quantity: 30,
+        },
+    );
+
+    let user_info = light.get_user_info(user).expect("user info");
+    assert_eq!(light.get_balance(&user_info, &pair.0).0, 120);
+
+    // The open order is escaped with
+    let escape_balances = light.escape_balances(&user_info.get_key());
+    assert_eq!(escape_balances.get(&pair.0).map(|b| b.0), Some(150));
+    assert_eq!(escape_balances.get(&pair.1).map(|b| b.0), Some(200));
+
+    let transfers: Vec<(ContractName, u64)> = escape_balances
+        .iter()
+        .map(|(symbol, balance)| (light.assets_info[symbol].contract_name.clone(), balance.0))
+        .collect();
+    let tx = crate::utils::escape_transaction(cn, &user_info, &transfers, 0);
+    assert_eq!(tx.blobs.len(), 3);
+
+    let calldata = sdk::Calldata {
+        identity: tx.identity.clone(),
+        tx_blob_count: tx.blobs.len(),
+        blobs: tx.blobs.clone().into(),
+        index: BlobIndex(0),
+        tx_hash: TxHash::from("escape-tx-test".as_bytes()),
+        tx_ctx: Some(TxContext {
+            lane_id,
+            block_height: BlockHeight(full.last_block_number.0 + 5_001),
+            ..Default::default()
+        }),
+        private_input: Vec::new(),
+    };
+    let events = light
+        .escape(&full.last_block_number, &calldata, &user_info)
+        .expect("escape with the built transaction should succeed");
+    light
+        .apply_events(&user_info, &events)
+        .expect("Could not apply escape events");
+
+    assert!(light.order_manager.orders.is_empty());
+    assert_eq!(light.get_balance(&user_info, &pair.0).0, 0);
+    assert_eq!(light.get_balance(&user_info, &pair.1).0, 0);
+}
+
+#[test_log::test]
+fn test_batched_actions_are_proven_in_one_execution() {
+    let (_, _, _, lane_id, secret) = get_ctx();
+
+    let mut light = ExecuteState::default();
+    let mut full = FullState::from_data(
+        &light,
+        secret.clone(),
+        lane_id.clone(),
+        BlockHeight::default(),
+    )
+    .expect("building full state");
+
+    let pair: Pair = ("HYLLAR".to_string(), "ORANJ".to_string());
+    let pair_info = PairInfo {
+        base: AssetInfo::new(0, ContractName(pair.0.clone())),
+        quote: AssetInfo::new(0, ContractName(pair.1.clone())),
+    };
+
+    let users = ["alice", "bob"];
+    let signers: Vec<TestSigner> = (0..users.len())
+        .map(|idx| TestSigner::new((idx + 1) as u8))
+        .collect();
+
+    for user in &users {
+        add_session_key(&mut light, &mut full, &users, &signers, user);
+    }
+
+    let _ = run_action(
+        &mut light,
+        &mut full,
+        users[0],
+        PermissionedOrderbookAction::CreatePair {
+            pair: pair.clone(),
+            info: pair_info,
+        },
+        Vec::new(),
+    );
+
+    // The ask is created, then partially filled, within the same batch
+    let ask = Order {
+        order_id: "batch-ask".to_string(),
+        order_type: OrderType::Limit,
+        order_side: OrderSide::Ask,
+        price: Some(10),
+        pair: pair.clone(),
+        quantity: 50,
+    };
+    let bid = Order {
+        order_id: "batch-bid".to_string(),
+        order_type: OrderType::Limit,
+        order_side: OrderSide::Bid,
+        price: Some(10),
+        pair: pair.clone(),
+        quantity: 20,
+    };
+    // Deposits do not change nonces, so orders can be signed ahead of the batch
+    let ask_payload = create_order_payload(&full, &users, &signers, users[0], &ask);
+    let bid_payload = create_order_payload(&full, &users, &signers, users[1], &bid);
+
+    run_batch(
+        &mut light,
+        &mut full,
+        vec![
+            (
+                users[0],
+                PermissionedOrderbookAction::Deposit {
+                    symbol: pair.0.clone(),
+                    amount: 100,
+                },
+                Vec::new(),
+            ),
+            (
+                users[1],
+                PermissionedOrderbookAction::Deposit {
+                    symbol: pair.1.clone(),
+                    amount: 1_000,
+                },
+                Vec::new(),
+            ),
+            (
+                users[0],
+                PermissionedOrderbookAction::CreateOrder(ask),
+                ask_payload,
+            ),
+            (
+                users[1],
+                PermissionedOrderbookAction::CreateOrder(bid),
+                bid_payload,
+            ),
+        ],
+    );
+
+    let remaining_ask = light
+        .order_manager
+        .orders
+        .get("batch-ask")
+        .expect("ask should remain in the book");
+    assert_eq!(remaining_ask.quantity, 30);
+    assert!(
+        !light.order_manager.orders.contains_key("batch-bid"),
+        "bid should be fully filled"
+    );
+}
+
+#[test_log::test]
+fn test_signing_test_vectors_match_contract_verification() {
+    let vectors = crate::test_vectors::signing_test_vectors();
+
+    let names: HashSet<&str> = vectors.iter().map(|vector| vector.name).collect();
+    assert_eq!(names.len(), vectors.len(), "vector names should be unique");
+    assert!(vectors.iter().any(|vector| vector.accepted));
+    assert!(vectors.iter().any(|vector| !vector.accepted));
+
+    for vector in &vectors {
+        assert_eq!(
+            vector.message,
+            vector.action.message(),
+            "{}: message should be built from the action",
+            vector.name
+        );
+        let result = vector.verify();
+        assert_eq!(
+            result.is_ok(),
+            vector.accepted,
+            "{}: unexpected verification result {result:?}",
+            vector.name
+        );
+    }
+
+    // Signatures are deterministic, so the exported vectors never change between runs
+    let exported = serde_json::to_string(&vectors).expect("serialize vectors");
+    let exported_again = serde_json::to_string(&crate::test_vectors::signing_test_vectors())
+        .expect("serialize vectors");
+    assert_eq!(exported, exported_again);
+}
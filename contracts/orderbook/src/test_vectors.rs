@@ -0,0 +1,447 @@
+//! Canonical signing test vectors, so that wallets and SDKs written in other languages can check
+//! that they build and sign action messages the way the contract verifies them.
+//! Export them as JSON with `cargo run -p orderbook --example signing_test_vectors`.
+//!
+//! Ed25519 session keys are not covered, as the contract only accepts them when built with the
+//! `ed25519` feature.
+
+use k256::ecdsa::{signature::DigestSigner, Signature, SigningKey};
+use serde::Serialize;
+use sha3::{Digest, Keccak256, Sha3_256};
+
+use crate::{
+    eip712,
+    model::{Order, OrderSide, OrderType, UserInfo},
+    utils::{self, SignedAction},
+};
+
+/// ECDSA secp256k1 signature of the SHA3-256 of the message, with a SEC1 encoded public key
+pub const SECP256K1_SHA3_SCHEME: &str = "secp256k1_sha3";
+/// Recoverable ECDSA secp256k1 signature of the EIP-712 digest, with the wallet address as key
+pub const EIP712_SCHEME: &str = "eip712";
+
+/// Action signed by a test vector
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VectorAction {
+    CreateOrder {
+        user: String,
+        nonce: u32,
+        order: Order,
+    },
+    CreateOrders {
+        user: String,
+        nonce: u32,
+        orders: Vec<Order>,
+    },
+    CancelOrder {
+        user: String,
+        nonce: u32,
+        order_id: String,
+    },
+    Withdraw {
+        user: String,
+        nonce: u32,
+        symbol: String,
+        amount: u64,
+    },
+    CancelOnDisconnect {
+        user: String,
+        nonce: u32,
+        timeout_secs: u64,
+        order_ids: Vec<String>,
+    },
+}
+
+impl VectorAction {
+    pub fn user(&self) -> &str {
+        match self {
+            VectorAction::CreateOrder { user, .. }
+            | VectorAction::CreateOrders { user, .. }
+            | VectorAction::CancelOrder { user, .. }
+            | VectorAction::Withdraw { user, .. }
+            | VectorAction::CancelOnDisconnect { user, .. } => user,
+        }
+    }
+
+    /// Action as verified by the contract. Cancel-on-disconnect is only signed as a message.
+    pub fn signed_action(&self) -> Option<SignedAction<'_>> {
+        match self {
+            VectorAction::CreateOrder { user, nonce, order } => Some(SignedAction::CreateOrder {
+                user,
+                nonce: *nonce,
+                order,
+            }),
+            VectorAction::CreateOrders {
+                user,
+                nonce,
+                orders,
+            } => Some(SignedAction::CreateOrders {
+                user,
+                nonce: *nonce,
+                orders,
+            }),
+            VectorAction::CancelOrder {
+                user,
+                nonce,
+                order_id,
+            } => Some(SignedAction::CancelOrder {
+                user,
+                nonce: *nonce,
+                order_id,
+            }),
+            VectorAction::Withdraw {
+                user,
+                nonce,
+                symbol,
+                amount,
+            } => Some(SignedAction::Withdraw {
+                user,
+                nonce: *nonce,
+                symbol,
+                amount: *amount,
+            }),
+            VectorAction::CancelOnDisconnect { .. } => None,
+        }
+    }
+
+    /// Message signed with the `secp256k1_sha3` scheme
+    pub fn message(&self) -> String {
+        if let VectorAction::CancelOnDisconnect {
+            user,
+            nonce,
+            timeout_secs,
+            order_ids,
+        } = self
+        {
+            return utils::cancel_on_disconnect_message(user, *nonce, *timeout_secs, order_ids);
+        }
+        self.signed_action()
+            .expect("every other action is signed")
+            .message()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SigningTestVector {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// `secp256k1_sha3` or `eip712`
+    pub scheme: &'static str,
+    pub action: VectorAction,
+    /// Message signed with the `secp256k1_sha3` scheme
+    pub message: String,
+    /// Hex encoded EIP-712 digest signed with the `eip712` scheme
+    pub signing_hash: Option<String>,
+    /// Hex encoded secp256k1 private key of the signer
+    pub private_key: String,
+    /// Hex encoded key sent along the signature: SEC1 public key or Ethereum address
+    pub public_key: String,
+    /// Hex encoded session keys registered for the user
+    pub registered_keys: Vec<String>,
+    /// Hex encoded signature: `r ‖ s` for `secp256k1_sha3`, `r ‖ s ‖ v` for `eip712`
+    pub signature: String,
+    /// Whether the contract accepts the signature
+    pub accepted: bool,
+}
+
+impl SigningTestVector {
+    /// Valid vector of the `secp256k1_sha3` scheme, signed with the uncompressed key of `signer`
+    fn sha3(
+        name: &'static str,
+        description: &'static str,
+        action: VectorAction,
+        signer: &Signer,
+    ) -> Self {
+        let public_key = hex::encode(signer.public_key(false));
+        SigningTestVector {
+            name,
+            description,
+            scheme: SECP256K1_SHA3_SCHEME,
+            message: action.message(),
+            signing_hash: None,
+            private_key: signer.private_key(),
+            registered_keys: vec![public_key.clone()],
+            public_key,
+            signature: hex::encode(signer.sign(&action.message())),
+            accepted: true,
+            action,
+        }
+    }
+
+    /// Valid vector of the `eip712` scheme, signed by the wallet of `signer`
+    fn eip712(
+        name: &'static str,
+        description: &'static str,
+        action: VectorAction,
+        signer: &Signer,
+    ) -> Self {
+        let signed_action = action
+            .signed_action()
+            .expect("EIP-712 vectors sign an action");
+        let signing_hash = hex::encode(eip712::signing_hash(&signed_action));
+        let signature = hex::encode(signer.sign_typed(&signed_action));
+        let address = hex::encode(signer.eth_address());
+        SigningTestVector {
+            name,
+            description,
+            scheme: EIP712_SCHEME,
+            message: action.message(),
+            signing_hash: Some(signing_hash),
+            private_key: signer.private_key(),
+            registered_keys: vec![address.clone()],
+            public_key: address,
+            signature,
+            accepted: true,
+            action,
+        }
+    }
+
+    /// Verifies the signature of the vector the way the contract does
+    pub fn verify(&self) -> Result<(), String> {
+        let decode = |value: &str| hex::decode(value).map_err(|e| format!("Invalid hex: {e}"));
+        let mut user_info = UserInfo::new(self.action.user().to_string(), Vec::new());
+        user_info.session_keys = self
+            .registered_keys
+            .iter()
+            .map(|key| decode(key))
+            .collect::<Result<_, _>>()?;
+        let public_key = decode(&self.public_key)?;
+        let signature = decode(&self.signature)?;
+
+        match self.action.signed_action() {
+            Some(action) => utils::verify_user_action_authorization(
+                &user_info,
+                &public_key,
+                &action,
+                &signature,
+            ),
+            None => utils::verify_user_signature_authorization(
+                &user_info,
+                &public_key,
+                &self.action.message(),
+                &signature,
+            ),
+        }
+    }
+}
+
+/// Deterministic secp256k1 signer: signatures follow RFC 6979 and are low-S normalized
+struct Signer {
+    signing_key: SigningKey,
+}
+
+impl Signer {
+    fn new(seed: u8) -> Self {
+        let field_bytes = k256::FieldBytes::from([seed; 32]);
+        Signer {
+            signing_key: SigningKey::from_bytes(&field_bytes).expect("valid signing key"),
+        }
+    }
+
+    fn private_key(&self) -> String {
+        hex::encode(self.signing_key.to_bytes())
+    }
+
+    fn public_key(&self, compressed: bool) -> Vec<u8> {
+        self.signing_key
+            .verifying_key()
+            .to_encoded_point(compressed)
+            .as_bytes()
+            .to_vec()
+    }
+
+    fn eth_address(&self) -> Vec<u8> {
+        Keccak256::digest(&self.public_key(false)[1..])[12..].to_vec()
+    }
+
+    fn sign(&self, message: &str) -> Vec<u8> {
+        let signature: Signature = self
+            .signing_key
+            .sign_digest(Sha3_256::new_with_prefix(message.as_bytes()));
+        signature.to_vec()
+    }
+
+    fn sign_typed(&self, action: &SignedAction) -> Vec<u8> {
+        let (signature, recovery_id) = self
+            .signing_key
+            .sign_prehash_recoverable(&eip712::signing_hash(action))
+            .expect("sign typed data");
+        let mut signature = signature.to_vec();
+        signature.push(recovery_id.to_byte() + 27);
+        signature
+    }
+}
+
+fn sample_order(order_id: &str, order_side: OrderSide, price: u64, quantity: u64) -> Order {
+    Order {
+        order_id: order_id.to_string(),
+        order_type: OrderType::Limit,
+        order_side,
+        price: Some(price),
+        pair: ("HYLLAR".to_string(), "ORANJ".to_string()),
+        quantity,
+    }
+}
+
+/// Canonical signing test vectors, accepted and rejected by the contract
+pub fn signing_test_vectors() -> Vec<SigningTestVector> {
+    let alice = Signer::new(1);
+    let mallory = Signer::new(2);
+    let user = "alice".to_string();
+
+    let create_order = VectorAction::CreateOrder {
+        user: user.clone(),
+        nonce: 7,
+        order: sample_order("order-1", OrderSide::Bid, 1_000, 25),
+    };
+    let create_orders = VectorAction::CreateOrders {
+        user: user.clone(),
+        nonce: 8,
+        orders: vec![
+            sample_order("order-2", OrderSide::Bid, 990, 10),
+            sample_order("order-3", OrderSide::Ask, 1_010, 10),
+        ],
+    };
+    let cancel_order = VectorAction::CancelOrder {
+        user: user.clone(),
+        nonce: 9,
+        order_id: "order-1".to_string(),
+    };
+    let withdraw = VectorAction::Withdraw {
+        user: user.clone(),
+        nonce: 10,
+        symbol: "ORANJ".to_string(),
+        amount: 500,
+    };
+    let cancel_on_disconnect = VectorAction::CancelOnDisconnect {
+        user: user.clone(),
+        nonce: 11,
+        timeout_secs: 30,
+        order_ids: vec!["order-2".to_string(), "order-3".to_string()],
+    };
+
+    let mut vectors = vec![
+        SigningTestVector::sha3(
+            "create_order",
+            "Limit order signed with an uncompressed session key",
+            create_order.clone(),
+            &alice,
+        ),
+        SigningTestVector::sha3(
+            "create_orders",
+            "Atomic batch of orders, whose ids are joined with commas",
+            create_orders.clone(),
+            &alice,
+        ),
+        SigningTestVector::sha3(
+            "cancel_order",
+            "Cancellation of an order",
+            cancel_order.clone(),
+            &alice,
+        ),
+        SigningTestVector::sha3(
+            "withdraw",
+            "Withdrawal of an amount of an asset",
+            withdraw.clone(),
+            &alice,
+        ),
+        SigningTestVector::sha3(
+            "cancel_on_disconnect",
+            "Arming of cancel-on-disconnect on a set of orders, consumed by their cancellation",
+            cancel_on_disconnect,
+            &alice,
+        ),
+        SigningTestVector::eip712(
+            "create_order_eip712",
+            "Limit order signed as EIP-712 typed data by a wallet registered with its address",
+            create_order.clone(),
+            &alice,
+        ),
+        SigningTestVector::eip712(
+            "create_orders_eip712",
+            "Atomic batch of orders signed as EIP-712 typed data",
+            create_orders,
+            &alice,
+        ),
+        SigningTestVector::eip712(
+            "withdraw_eip712",
+            "Withdrawal signed as EIP-712 typed data",
+            withdraw.clone(),
+            &alice,
+        ),
+    ];
+
+    // Compressed keys are accepted, as long as they are registered in that form
+    let compressed_key = hex::encode(alice.public_key(true));
+    vectors.push(SigningTestVector {
+        name: "create_order_compressed_key",
+        description: "Limit order signed with a session key registered in compressed form",
+        public_key: compressed_key.clone(),
+        registered_keys: vec![compressed_key],
+        ..SigningTestVector::sha3("", "", create_order.clone(), &alice)
+    });
+
+    // Wallets may use the raw recovery id (0 or 1) instead of 27 or 28 for v
+    let mut raw_recovery_id = SigningTestVector::eip712(
+        "cancel_order_eip712_raw_recovery_id",
+        "Cancellation signed as EIP-712 typed data, with v as the raw recovery id",
+        cancel_order.clone(),
+        &alice,
+    );
+    let mut signature = hex::decode(&raw_recovery_id.signature).expect("hex signature");
+    signature[64] -= 27;
+    raw_recovery_id.signature = hex::encode(signature);
+    vectors.push(raw_recovery_id);
+
+    let previous_nonce = VectorAction::CreateOrder {
+        user: user.clone(),
+        nonce: 6,
+        order: sample_order("order-1", OrderSide::Bid, 1_000, 25),
+    };
+    vectors.push(SigningTestVector {
+        name: "wrong_nonce",
+        description: "Signature of the same order with a previous nonce",
+        signature: hex::encode(alice.sign(&previous_nonce.message())),
+        accepted: false,
+        ..SigningTestVector::sha3("", "", create_order.clone(), &alice)
+    });
+    vectors.push(SigningTestVector {
+        name: "wrong_signer",
+        description: "Signature made by another key than the one sent along",
+        signature: hex::encode(mallory.sign(&cancel_order.message())),
+        accepted: false,
+        ..SigningTestVector::sha3("", "", cancel_order.clone(), &alice)
+    });
+    vectors.push(SigningTestVector {
+        name: "unregistered_key",
+        description: "Valid signature made by a key that is not registered for the user",
+        registered_keys: vec![hex::encode(alice.public_key(false))],
+        accepted: false,
+        ..SigningTestVector::sha3("", "", withdraw.clone(), &mallory)
+    });
+    vectors.push(SigningTestVector {
+        name: "sha3_signature_from_wallet",
+        description: "Message signed with the sha3 scheme by a key registered as a wallet address",
+        signature: hex::encode(alice.sign(&cancel_order.message())),
+        accepted: false,
+        ..SigningTestVector::eip712("", "", cancel_order.clone(), &alice)
+    });
+
+    let tampered_withdraw = VectorAction::Withdraw {
+        user,
+        nonce: 10,
+        symbol: "ORANJ".to_string(),
+        amount: 5_000,
+    };
+    let tampered_signature = SigningTestVector::eip712("", "", withdraw, &alice).signature;
+    vectors.push(SigningTestVector {
+        name: "tampered_amount_eip712",
+        description: "EIP-712 signature of a withdrawal whose amount was changed afterwards",
+        signature: tampered_signature,
+        accepted: false,
+        ..SigningTestVector::eip712("", "", tampered_withdraw, &alice)
+    });
+
+    vectors
+}
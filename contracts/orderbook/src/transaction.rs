@@ -4,10 +4,12 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     model::{
-        ExecuteState, Order, OrderType, OrderbookEvent, Pair, PairInfo, UserInfo,
-        WithdrawDestination,
+        CircuitBreaker, CollateralConfig, DustSweep, ExecuteState, FeeTier, MarketStatus, Order,
+        OrderId, OrderType, OrderbookEvent, Pair, PairInfo, PendingWithdrawal, PerpConfig,
+        SessionKeyPermissions, Symbol, UserInfo, WithdrawDelay, WithdrawDestination, WithdrawLimit,
     },
-    utils,
+    utils::{self, SignedAction},
+    zk::smt::GetKey,
 };
 
 /// Structure to deserialize permissioned private data
@@ -22,18 +24,24 @@ pub struct PermissionedPrivateInput {
     pub private_input: Vec<u8>,
 }
 
-/// Structure to deserialize private data during order creation
+/// Structure to deserialize private data during session key registration
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct AddSessionKeyPrivateInput {
     pub new_public_key: Vec<u8>,
+    // Actions the new key is allowed to sign
+    pub permissions: SessionKeyPermissions,
+    // If set, the key can only trade on this pair
+    pub pair: Option<Pair>,
 }
 
-/// Structure to deserialize private data during order creation
+/// Structure to deserialize private data during order creation, and dust sweeps
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct CreateOrderPrivateInput {
     // Used to assert user approval of that action
     pub signature: Vec<u8>,
     pub public_key: Vec<u8>,
+    // Block the circuit breakers are checked at. It cannot be after the block of the tx.
+    pub block_height: u64,
 }
 
 /// Structure to deserialize private data during order cancellation
@@ -43,6 +51,16 @@ pub struct CancelOrderPrivateInput {
     pub public_key: Vec<u8>,
 }
 
+/// Structure to deserialize private data during cancel-on-disconnect
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct CancelOnDisconnectPrivateInput {
+    // Signature given by the session key when arming cancel-on-disconnect, over the nonce of the
+    // user and the ids of the orders, submitted by the server once the heartbeat lapses
+    pub signature: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub timeout_secs: u64,
+}
+
 /// Structure to deserialize private data during withdraw
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct WithdrawPrivateInput {
@@ -50,6 +68,37 @@ pub struct WithdrawPrivateInput {
     pub public_key: Vec<u8>,
 }
 
+/// Structure to deserialize private data during internal transfer
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct TransferPrivateInput {
+    pub signature: Vec<u8>,
+    pub public_key: Vec<u8>,
+}
+
+/// Structure to deserialize private data during fee tiers update
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct UpdateFeeTiersPrivateInput {
+    // Kept private so that users' volume tiers are not published onchain
+    pub updates: Vec<(String, FeeTier)>,
+}
+
+/// Identity registered by the operator during bulk onboarding, with its first session key
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
+pub struct OnboardedUser {
+    pub user: String,
+    pub salt: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub permissions: SessionKeyPermissions,
+    pub pair: Option<Pair>,
+}
+
+/// Structure to deserialize private data during bulk onboarding
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct OnboardUsersPrivateInput {
+    // Kept private like the session keys users register themselves
+    pub users: Vec<OnboardedUser>,
+}
+
 /// Structure to deserialize private data during escape
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct EscapePrivateInput {
@@ -78,6 +127,8 @@ pub enum PermissionedOrderbookAction {
         amount: u64,
     },
     CreateOrder(Order),
+    /// Places orders on several pairs atomically: all of them are executed, or none
+    CreateOrders(Vec<Order>),
     Cancel {
         order_id: String,
     },
@@ -85,8 +136,103 @@ pub enum PermissionedOrderbookAction {
         symbol: String,
         amount: u64,
         destination: WithdrawDestination,
+        /// Block the withdraw limits are accounted at. It cannot be after the block of the tx.
+        block_height: u64,
+    },
+    /// Moves a balance to another account of the orderbook, without any onchain token transfer
+    Transfer {
+        to: String,
+        symbol: String,
+        amount: u64,
+    },
+    /// Sells dust balances of several assets for `quote` at market, atomically
+    SweepDust {
+        quote: Symbol,
+        sweeps: Vec<DustSweep>,
     },
     UpgradeContract(ProgramId),
+    UpdateFeeTiers,
+    CancelOnDisconnect {
+        order_ids: Vec<OrderId>,
+    },
+    UpdateWithdrawLimits {
+        updates: Vec<(Symbol, Option<WithdrawLimit>)>,
+    },
+    /// Registers identities in bulk, on behalf of the operator
+    OnboardUsers,
+    /// Halts, resumes or delists a pair, on behalf of the operator
+    UpdatePairStatus {
+        pair: Pair,
+        status: MarketStatus,
+    },
+    /// Sets or removes the circuit breakers of pairs, on behalf of the operator
+    UpdateCircuitBreakers {
+        updates: Vec<(Pair, Option<CircuitBreaker>)>,
+    },
+    /// Feeds the index prices of pairs to their circuit breakers, on behalf of the operator
+    UpdateIndexPrices {
+        prices: Vec<(Pair, u64)>,
+        /// Block the prices are tracked at. It cannot be after the block of the tx.
+        block_height: u64,
+    },
+    /// Creates a pair traded as perpetual futures, on behalf of the operator
+    CreatePerpMarket {
+        pair: Pair,
+        info: PairInfo,
+        config: PerpConfig,
+    },
+    /// Settles the funding of a perp market on its positions, on behalf of the operator
+    SettleFunding {
+        pair: Pair,
+        /// Funding paid by a long position of one base unit, received by shorts when positive
+        payment: i64,
+    },
+    /// Accepts assets as collateral of the margin accounts, on behalf of the operator
+    UpdateCollaterals {
+        updates: Vec<(Symbol, Option<CollateralConfig>)>,
+    },
+    /// Force-closes the position of an under-margined account on the book, on behalf of the
+    /// operator
+    Liquidate {
+        user: String,
+        pair: Pair,
+        /// Id of the market order closing the position
+        order_id: OrderId,
+        /// Block the circuit breaker of the pair is checked at. It cannot be after the block of
+        /// the tx.
+        block_height: u64,
+    },
+    /// Sets or removes the delay of the large withdrawals of assets, on behalf of the operator
+    UpdateWithdrawDelays {
+        updates: Vec<(Symbol, Option<WithdrawDelay>)>,
+    },
+    /// Queues a withdrawal above the threshold of the withdraw delay of the asset. It is signed
+    /// like a direct withdrawal.
+    RequestWithdrawal {
+        symbol: String,
+        amount: u64,
+        destination: WithdrawDestination,
+        /// Block the delay and the withdraw limits start at. It cannot be after the block of the
+        /// tx.
+        block_height: u64,
+    },
+    /// Approves or rejects a queued withdrawal, on behalf of the operator
+    ReviewWithdrawal {
+        user: String,
+        withdrawal_id: u32,
+        approve: bool,
+    },
+    /// Pays out a queued withdrawal once its delay is over, on behalf of the operator
+    ReleaseWithdrawal {
+        user: String,
+        withdrawal: PendingWithdrawal,
+        /// Block the delay is checked at. It cannot be after the block of the tx.
+        block_height: u64,
+    },
+    /// Pauses or resumes the withdrawals to other networks than Hyli, on behalf of the operator
+    UpdateBridgePause {
+        paused: bool,
+    },
 }
 
 #[derive(Serialize, Deserialize, BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
@@ -94,6 +240,26 @@ pub enum PermissionlessOrderbookAction {
     Escape { user_key: [u8; 32] },
 }
 
+impl PermissionedOrderbookAction {
+    /// Whether the given order is created by this action
+    pub fn creates_order(&self, order_id: &OrderId) -> bool {
+        match self {
+            PermissionedOrderbookAction::CreateOrder(order) => &order.order_id == order_id,
+            PermissionedOrderbookAction::CreateOrders(orders) => {
+                orders.iter().any(|order| &order.order_id == order_id)
+            }
+            PermissionedOrderbookAction::SweepDust { sweeps, .. } => {
+                sweeps.iter().any(|sweep| &sweep.order_id == order_id)
+            }
+            PermissionedOrderbookAction::Liquidate {
+                order_id: liquidation_order_id,
+                ..
+            } => liquidation_order_id == order_id,
+            _ => false,
+        }
+    }
+}
+
 impl OrderbookAction {
     pub fn as_blob(&self, contract_name: sdk::ContractName) -> sdk::Blob {
         sdk::Blob {
@@ -143,17 +309,79 @@ impl ExecuteState {
                 // The orderbook server knows the public key as user informed it offchain.
                 let add_session_key_private_input =
                     borsh::from_slice::<AddSessionKeyPrivateInput>(private_input).map_err(|e| {
-                        format!("Failed to deserialize CreateOrderPrivateInput: {e}")
+                        format!("Failed to deserialize AddSessionKeyPrivateInput: {e}")
                     })?;
 
                 self.add_session_key(
                     user_info.clone(),
                     &add_session_key_private_input.new_public_key,
+                    add_session_key_private_input.permissions,
+                    add_session_key_private_input.pair,
                 )
             }
             PermissionedOrderbookAction::Deposit { symbol, amount } => {
                 self.deposit(&symbol, amount, user_info)
             }
+            PermissionedOrderbookAction::UpdateFeeTiers => {
+                let update_fee_tiers_private_input =
+                    borsh::from_slice::<UpdateFeeTiersPrivateInput>(private_input).map_err(
+                        |e| format!("Failed to deserialize UpdateFeeTiersPrivateInput: {e}"),
+                    )?;
+
+                self.update_fee_tiers(user_info, &update_fee_tiers_private_input.updates)
+            }
+            PermissionedOrderbookAction::UpdateWithdrawLimits { updates } => {
+                self.update_withdraw_limits(user_info, &updates)
+            }
+            PermissionedOrderbookAction::UpdatePairStatus { pair, status } => {
+                self.update_pair_status(user_info, &pair, status)
+            }
+            PermissionedOrderbookAction::UpdateCircuitBreakers { updates } => {
+                self.update_circuit_breakers(user_info, &updates)
+            }
+            PermissionedOrderbookAction::UpdateIndexPrices {
+                prices,
+                block_height,
+            } => self.update_index_prices(user_info, &prices, block_height),
+            PermissionedOrderbookAction::CreatePerpMarket { pair, info, config } => {
+                self.create_perp_market(user_info, &pair, &info, &config)
+            }
+            PermissionedOrderbookAction::SettleFunding { pair, payment } => {
+                self.settle_funding(user_info, &pair, payment)
+            }
+            PermissionedOrderbookAction::UpdateCollaterals { updates } => {
+                self.update_collaterals(user_info, &updates)
+            }
+            PermissionedOrderbookAction::Liquidate {
+                user,
+                pair,
+                order_id,
+                block_height,
+            } => self.liquidate(user_info, &user, &pair, &order_id, block_height),
+            PermissionedOrderbookAction::UpdateWithdrawDelays { updates } => {
+                self.update_withdraw_delays(user_info, &updates)
+            }
+            PermissionedOrderbookAction::ReviewWithdrawal {
+                user,
+                withdrawal_id,
+                approve,
+            } => self.review_withdrawal(user_info, &user, withdrawal_id, approve),
+            PermissionedOrderbookAction::ReleaseWithdrawal {
+                user,
+                withdrawal,
+                block_height,
+            } => self.release_withdrawal(user_info, &user, &withdrawal, block_height),
+            PermissionedOrderbookAction::UpdateBridgePause { paused } => {
+                self.update_bridge_pause(user_info, paused)
+            }
+            PermissionedOrderbookAction::OnboardUsers => {
+                let onboard_users_private_input =
+                    borsh::from_slice::<OnboardUsersPrivateInput>(private_input).map_err(|e| {
+                        format!("Failed to deserialize OnboardUsersPrivateInput: {e}")
+                    })?;
+
+                self.onboard_users(user_info, &onboard_users_private_input.users)
+            }
             PermissionedOrderbookAction::CreateOrder(Order {
                 order_id,
                 order_side,
@@ -163,61 +391,192 @@ impl ExecuteState {
                 quantity,
             }) => {
                 // Assert that the order is correctly created
-                if order_type == OrderType::Limit && price.is_none() {
-                    return Err("Limit orders must have a price".to_string());
-                }
-                if order_type == OrderType::Market && price.is_some() {
-                    return Err("Market orders cannot have a price".to_string());
-                }
+                check_order_price(&order_type, &price)?;
 
                 let create_order_private_input =
                     borsh::from_slice::<CreateOrderPrivateInput>(private_input).map_err(|e| {
                         format!("Failed to deserialize CreateOrderPrivateInput: {e}")
                     })?;
 
+                let order = Order {
+                    order_id,
+                    order_type,
+                    order_side,
+                    price,
+                    pair,
+                    quantity,
+                };
+
                 // Verify user signature authorization
                 // On this step, signature is provided in private_input and hence is never public.
                 // The orderbook server knows the signature as user informed it offchain.
                 // As the public key has been registered, only the user can create that signature and hence allow this order creation
-                utils::verify_user_signature_authorization(
+                utils::verify_user_action_authorization(
                     user_info,
                     &create_order_private_input.public_key,
-                    &format!(
-                        "{}:{}:create_order:{order_id}",
-                        user_info.user, user_info.nonce
-                    ),
+                    &SignedAction::CreateOrder {
+                        user: &user_info.user,
+                        nonce: user_info.nonce,
+                        order: &order,
+                    },
                     &create_order_private_input.signature,
                 )
                 .map_err(|err| format!("Failed to verify user signature authorization: {err}"))?;
+                utils::verify_session_key_scope(
+                    user_info,
+                    &create_order_private_input.public_key,
+                    SessionKeyPermissions::CREATE_ORDER,
+                    Some(&order.pair),
+                )?;
 
-                let order = Order {
-                    order_id,
-                    order_type,
-                    order_side,
-                    price,
-                    pair,
-                    quantity,
-                };
+                self.execute_order(user_info, order, create_order_private_input.block_height)
+            }
+            PermissionedOrderbookAction::CreateOrders(orders) => {
+                for order in &orders {
+                    check_order_price(&order.order_type, &order.price)?;
+                }
+
+                let create_orders_private_input =
+                    borsh::from_slice::<CreateOrderPrivateInput>(private_input).map_err(|e| {
+                        format!("Failed to deserialize CreateOrderPrivateInput: {e}")
+                    })?;
 
-                self.execute_order(user_info, order)
+                // A single signature covers all the orders, so that none of them can be executed alone
+                utils::verify_user_action_authorization(
+                    user_info,
+                    &create_orders_private_input.public_key,
+                    &SignedAction::CreateOrders {
+                        user: &user_info.user,
+                        nonce: user_info.nonce,
+                        orders: &orders,
+                    },
+                    &create_orders_private_input.signature,
+                )
+                .map_err(|err| format!("Failed to verify user signature authorization: {err}"))?;
+                for order in &orders {
+                    utils::verify_session_key_scope(
+                        user_info,
+                        &create_orders_private_input.public_key,
+                        SessionKeyPermissions::CREATE_ORDER,
+                        Some(&order.pair),
+                    )?;
+                }
+
+                self.execute_orders(user_info, &orders, create_orders_private_input.block_height)
+            }
+            PermissionedOrderbookAction::SweepDust { quote, sweeps } => {
+                let sweep_dust_private_input =
+                    borsh::from_slice::<CreateOrderPrivateInput>(private_input).map_err(|e| {
+                        format!("Failed to deserialize CreateOrderPrivateInput: {e}")
+                    })?;
+
+                utils::verify_user_action_authorization(
+                    user_info,
+                    &sweep_dust_private_input.public_key,
+                    &SignedAction::SweepDust {
+                        user: &user_info.user,
+                        nonce: user_info.nonce,
+                        quote: &quote,
+                        sweeps: &sweeps,
+                    },
+                    &sweep_dust_private_input.signature,
+                )
+                .map_err(|err| format!("Failed to verify user signature authorization: {err}"))?;
+                for sweep in &sweeps {
+                    utils::verify_session_key_scope(
+                        user_info,
+                        &sweep_dust_private_input.public_key,
+                        SessionKeyPermissions::CREATE_ORDER,
+                        Some(&(sweep.symbol.clone(), quote.clone())),
+                    )?;
+                }
+
+                self.sweep_dust(
+                    user_info,
+                    &quote,
+                    &sweeps,
+                    sweep_dust_private_input.block_height,
+                )
             }
             PermissionedOrderbookAction::Cancel { order_id } => {
                 let cancel_order_private_data =
-                    borsh::from_slice::<CreateOrderPrivateInput>(private_input).map_err(|e| {
+                    borsh::from_slice::<CancelOrderPrivateInput>(private_input).map_err(|e| {
                         format!("Failed to deserialize CancelOrderPrivateInput: {e}")
                     })?;
                 // Verify user signature authorization
-                utils::verify_user_signature_authorization(
+                utils::verify_user_action_authorization(
                     user_info,
                     &cancel_order_private_data.public_key,
-                    &format!("{}:{}:cancel:{order_id}", user_info.user, user_info.nonce),
+                    &SignedAction::CancelOrder {
+                        user: &user_info.user,
+                        nonce: user_info.nonce,
+                        order_id: &order_id,
+                    },
                     &cancel_order_private_data.signature,
                 )
                 .map_err(|err| format!("Failed to verify user signature authorization: {err}"))?;
+                let order_pair = self
+                    .order_manager
+                    .orders
+                    .get(&order_id)
+                    .map(|order| order.pair.clone());
+                utils::verify_session_key_scope(
+                    user_info,
+                    &cancel_order_private_data.public_key,
+                    SessionKeyPermissions::CANCEL,
+                    order_pair.as_ref(),
+                )?;
 
                 self.cancel_order(order_id, user_info)
             }
-            PermissionedOrderbookAction::Withdraw { symbol, amount, .. } => {
+            PermissionedOrderbookAction::CancelOnDisconnect { order_ids } => {
+                let cancel_on_disconnect_private_data =
+                    borsh::from_slice::<CancelOnDisconnectPrivateInput>(private_input).map_err(
+                        |e| format!("Failed to deserialize CancelOnDisconnectPrivateInput: {e}"),
+                    )?;
+                utils::verify_user_signature_authorization(
+                    user_info,
+                    &cancel_on_disconnect_private_data.public_key,
+                    &utils::cancel_on_disconnect_message(
+                        &user_info.user,
+                        user_info.nonce,
+                        cancel_on_disconnect_private_data.timeout_secs,
+                        &order_ids,
+                    ),
+                    &cancel_on_disconnect_private_data.signature,
+                )
+                .map_err(|err| format!("Failed to verify user signature authorization: {err}"))?;
+                // Orders filled or cancelled since the session was armed are skipped
+                let user_key = user_info.get_key();
+                let open_order_ids: Vec<OrderId> = order_ids
+                    .iter()
+                    .filter(|order_id| {
+                        self.order_manager.orders_owner.get(*order_id) == Some(&user_key)
+                    })
+                    .cloned()
+                    .collect();
+                for order_id in &open_order_ids {
+                    let order_pair = self
+                        .order_manager
+                        .orders
+                        .get(order_id)
+                        .map(|order| order.pair.clone());
+                    utils::verify_session_key_scope(
+                        user_info,
+                        &cancel_on_disconnect_private_data.public_key,
+                        SessionKeyPermissions::CANCEL,
+                        order_pair.as_ref(),
+                    )?;
+                }
+
+                self.cancel_orders(&open_order_ids, user_info)
+            }
+            PermissionedOrderbookAction::Withdraw {
+                symbol,
+                amount,
+                destination,
+                block_height,
+            } => {
                 // TODO: assert there is a transfer blob for that symbol
 
                 let withdraw_private_data =
@@ -225,19 +584,99 @@ impl ExecuteState {
                         .map_err(|e| format!("Failed to deserialize WithdrawPrivateInput: {e}"))?;
 
                 // Verify user signature authorization
-                utils::verify_user_signature_authorization(
+                utils::verify_user_action_authorization(
                     user_info,
                     &withdraw_private_data.public_key,
-                    &format!(
-                        "{}:{}:withdraw:{symbol}:{amount}",
-                        user_info.user, user_info.nonce
-                    ),
+                    &SignedAction::Withdraw {
+                        user: &user_info.user,
+                        nonce: user_info.nonce,
+                        symbol: &symbol,
+                        amount,
+                    },
+                    &withdraw_private_data.signature,
+                )
+                .map_err(|err| format!("Failed to verify user signature authorization: {err}"))?;
+                utils::verify_session_key_scope(
+                    user_info,
+                    &withdraw_private_data.public_key,
+                    SessionKeyPermissions::WITHDRAW,
+                    None,
+                )?;
+
+                self.ensure_bridge_open(&destination)?;
+                self.withdraw(&symbol, &amount, block_height, user_info)
+            }
+            PermissionedOrderbookAction::RequestWithdrawal {
+                symbol,
+                amount,
+                destination,
+                block_height,
+            } => {
+                let withdraw_private_data =
+                    borsh::from_slice::<WithdrawPrivateInput>(private_input)
+                        .map_err(|e| format!("Failed to deserialize WithdrawPrivateInput: {e}"))?;
+
+                // The user signs the same message as for a direct withdrawal
+                utils::verify_user_action_authorization(
+                    user_info,
+                    &withdraw_private_data.public_key,
+                    &SignedAction::Withdraw {
+                        user: &user_info.user,
+                        nonce: user_info.nonce,
+                        symbol: &symbol,
+                        amount,
+                    },
                     &withdraw_private_data.signature,
                 )
                 .map_err(|err| format!("Failed to verify user signature authorization: {err}"))?;
+                utils::verify_session_key_scope(
+                    user_info,
+                    &withdraw_private_data.public_key,
+                    SessionKeyPermissions::WITHDRAW,
+                    None,
+                )?;
 
-                self.withdraw(&symbol, &amount, user_info)
+                self.ensure_bridge_open(&destination)?;
+                self.request_withdrawal(&symbol, amount, &destination, block_height, user_info)
+            }
+            PermissionedOrderbookAction::Transfer { to, symbol, amount } => {
+                let transfer_private_data =
+                    borsh::from_slice::<TransferPrivateInput>(private_input)
+                        .map_err(|e| format!("Failed to deserialize TransferPrivateInput: {e}"))?;
+
+                utils::verify_user_action_authorization(
+                    user_info,
+                    &transfer_private_data.public_key,
+                    &SignedAction::Transfer {
+                        user: &user_info.user,
+                        nonce: user_info.nonce,
+                        to: &to,
+                        symbol: &symbol,
+                        amount,
+                    },
+                    &transfer_private_data.signature,
+                )
+                .map_err(|err| format!("Failed to verify user signature authorization: {err}"))?;
+                // Moving funds out of the account is allowed to the same keys as withdrawals
+                utils::verify_session_key_scope(
+                    user_info,
+                    &transfer_private_data.public_key,
+                    SessionKeyPermissions::WITHDRAW,
+                    None,
+                )?;
+
+                self.transfer(&to, &symbol, amount, user_info)
             }
         }
     }
 }
+
+fn check_order_price(order_type: &OrderType, price: &Option<u64>) -> Result<(), String> {
+    if *order_type == OrderType::Limit && price.is_none() {
+        return Err("Limit orders must have a price".to_string());
+    }
+    if *order_type == OrderType::Market && price.is_some() {
+        return Err("Market orders cannot have a price".to_string());
+    }
+    Ok(())
+}
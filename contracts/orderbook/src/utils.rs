@@ -1,10 +1,268 @@
+use hyli_smt_token::SmtTokenAction;
 use k256::{
     ecdsa::{Signature, VerifyingKey},
     EncodedPoint,
 };
+use sdk::{BlobTransaction, ContractAction, ContractName, Identity};
 use sha3::{Digest, Sha3_256};
 
-use crate::model::UserInfo;
+use crate::{
+    eip712,
+    model::{
+        DustSweep, Order, OrderId, Pair, SessionKeyPermissions, UserInfo, WithdrawDestination,
+    },
+    transaction::{OrderbookAction, PermissionlessOrderbookAction},
+    webauthn,
+    zk::smt::GetKey,
+    ORDERBOOK_ACCOUNT_IDENTITY,
+};
+
+/// Length of an Ed25519 public key registered as a session key
+pub const ED25519_PUBLIC_KEY_LEN: usize = 32;
+
+/// Signature scheme of a session key, told apart by the encoding of the registered key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionKeyScheme {
+    /// SEC1 encoded secp256k1 public key, signing the SHA3-256 of the message
+    Secp256k1,
+    /// 32 bytes Ed25519 public key, signing the raw message
+    Ed25519,
+    /// 20 bytes Ethereum address, signing the EIP-712 typed data of the action
+    EthAddress,
+    /// Tagged P-256 public key, signing WebAuthn assertions
+    Passkey,
+}
+
+impl SessionKeyScheme {
+    pub fn of(public_key: &[u8]) -> Self {
+        if public_key.len() == ED25519_PUBLIC_KEY_LEN {
+            SessionKeyScheme::Ed25519
+        } else if public_key.len() == eip712::ETH_ADDRESS_LEN {
+            SessionKeyScheme::EthAddress
+        } else if webauthn::is_passkey(public_key) {
+            SessionKeyScheme::Passkey
+        } else {
+            SessionKeyScheme::Secp256k1
+        }
+    }
+}
+
+/// Maximum length of a user identity, in bytes
+pub const MAX_IDENTITY_LEN: usize = 128;
+
+/// Contract part of the identities reserved to the orderbook itself
+const RESERVED_IDENTITY_SUFFIX: &str = "@orderbook";
+
+/// Whether the identity belongs to the orderbook itself, like the insurance fund
+pub fn is_reserved_identity(identity: &str) -> bool {
+    identity == ORDERBOOK_ACCOUNT_IDENTITY || identity.ends_with(RESERVED_IDENTITY_SUFFIX)
+}
+
+/// Separates the parent identity from the name of a sub-account, as in `alice@wallet/strategy1`
+pub const SUB_ACCOUNT_SEPARATOR: char = '/';
+
+/// Maximum length of the name of a sub-account, in bytes
+pub const MAX_SUB_ACCOUNT_NAME_LEN: usize = 32;
+
+/// Identity of the sub-account `name` of `parent`
+pub fn sub_account_identity(parent: &str, name: &str) -> String {
+    format!("{parent}{SUB_ACCOUNT_SEPARATOR}{name}")
+}
+
+/// Parent identity and name of a sub-account identity, `None` for a top-level identity
+pub fn split_sub_account(identity: &str) -> Option<(&str, &str)> {
+    identity.split_once(SUB_ACCOUNT_SEPARATOR)
+}
+
+/// Canonical form of an identity: surrounding whitespace trimmed and ASCII letters lowercased
+pub fn canonical_identity(identity: &str) -> String {
+    identity.trim().to_ascii_lowercase()
+}
+
+/// Checks that a new identity can be registered. Identities are SMT keys, so they are only
+/// registered in their canonical form, with a restricted charset, so that identities that look
+/// the same cannot belong to different users. Only registrations are checked: identities
+/// registered before these rules existed keep working.
+pub fn validate_new_identity(identity: &str) -> Result<(), String> {
+    if identity.is_empty() || identity.len() > MAX_IDENTITY_LEN {
+        return Err(format!(
+            "Invalid identity: length must be between 1 and {MAX_IDENTITY_LEN}, got {}",
+            identity.len()
+        ));
+    }
+    let canonical = canonical_identity(identity);
+    if canonical != identity {
+        return Err(format!(
+            "Invalid identity {identity:?}: identities must be registered in their canonical form {canonical:?}"
+        ));
+    }
+    if let Some(c) = identity.chars().find(|c| {
+        !(c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-' | '@'))
+    }) {
+        return Err(format!(
+            "Invalid identity {identity:?}: character {c:?} is not allowed"
+        ));
+    }
+    if identity.matches('@').count() > 1 {
+        return Err(format!(
+            "Invalid identity {identity:?}: at most one '@' is allowed"
+        ));
+    }
+    if is_reserved_identity(identity) {
+        return Err(format!(
+            "Invalid identity {identity:?}: identity is reserved"
+        ));
+    }
+    Ok(())
+}
+
+/// Checks the identity of a new sub-account: its parent follows the rules of the identities,
+/// and its name is a short lowercase label.
+pub fn validate_new_sub_account(identity: &str) -> Result<(), String> {
+    let Some((parent, name)) = split_sub_account(identity) else {
+        return Err(format!(
+            "Invalid identity {identity:?}: sub-accounts are named <identity>{SUB_ACCOUNT_SEPARATOR}<name>"
+        ));
+    };
+    validate_new_identity(parent)?;
+    if name.is_empty() || name.len() > MAX_SUB_ACCOUNT_NAME_LEN {
+        return Err(format!(
+            "Invalid identity: sub-account name length must be between 1 and {MAX_SUB_ACCOUNT_NAME_LEN}, got {}",
+            name.len()
+        ));
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '.' | '_' | '-')))
+    {
+        return Err(format!(
+            "Invalid identity {parent}{SUB_ACCOUNT_SEPARATOR}{name}: character {c:?} is not allowed in a sub-account name"
+        ));
+    }
+    Ok(())
+}
+
+/// Action a user authorizes by signing it with one of their session keys
+#[derive(Debug, Clone, Copy)]
+pub enum SignedAction<'a> {
+    CreateOrder {
+        user: &'a str,
+        nonce: u32,
+        order: &'a Order,
+    },
+    CreateOrders {
+        user: &'a str,
+        nonce: u32,
+        orders: &'a [Order],
+    },
+    CancelOrder {
+        user: &'a str,
+        nonce: u32,
+        order_id: &'a str,
+    },
+    Withdraw {
+        user: &'a str,
+        nonce: u32,
+        symbol: &'a str,
+        amount: u64,
+    },
+    Transfer {
+        user: &'a str,
+        nonce: u32,
+        to: &'a str,
+        symbol: &'a str,
+        amount: u64,
+    },
+    SweepDust {
+        user: &'a str,
+        nonce: u32,
+        quote: &'a str,
+        sweeps: &'a [DustSweep],
+    },
+}
+
+impl SignedAction<'_> {
+    /// Message signed by secp256k1 session keys
+    pub fn message(&self) -> String {
+        match self {
+            SignedAction::CreateOrder { user, nonce, order } => {
+                format!("{user}:{nonce}:create_order:{}", order.order_id)
+            }
+            SignedAction::CreateOrders {
+                user,
+                nonce,
+                orders,
+            } => {
+                let order_ids: Vec<&str> =
+                    orders.iter().map(|order| order.order_id.as_str()).collect();
+                format!("{user}:{nonce}:create_orders:{}", order_ids.join(","))
+            }
+            SignedAction::CancelOrder {
+                user,
+                nonce,
+                order_id,
+            } => format!("{user}:{nonce}:cancel:{order_id}"),
+            SignedAction::Withdraw {
+                user,
+                nonce,
+                symbol,
+                amount,
+            } => format!("{user}:{nonce}:withdraw:{symbol}:{amount}"),
+            SignedAction::Transfer {
+                user,
+                nonce,
+                to,
+                symbol,
+                amount,
+            } => format!("{user}:{nonce}:transfer:{to}:{symbol}:{amount}"),
+            SignedAction::SweepDust {
+                user,
+                nonce,
+                quote,
+                sweeps,
+            } => {
+                let sweeps: Vec<String> = sweeps
+                    .iter()
+                    .map(|sweep| format!("{}:{}", sweep.symbol, sweep.order_id))
+                    .collect();
+                format!("{user}:{nonce}:sweep_dust:{quote}:{}", sweeps.join(","))
+            }
+        }
+    }
+}
+
+/// Escape transaction of a user, sent once the orderbook has stalled: the escape action, then a
+/// transfer from the orderbook account of each escaped balance. `transfers` are the token
+/// contract and amount of each balance, as served by `GET /escape_proof/{identity}` with the
+/// private input the transaction is proven with.
+pub fn escape_transaction(
+    orderbook_cn: ContractName,
+    user_info: &UserInfo,
+    transfers: &[(ContractName, u64)],
+    action_id: u32,
+) -> BlobTransaction {
+    let mut blobs = vec![OrderbookAction::PermissionlessOrderbookAction(
+        PermissionlessOrderbookAction::Escape {
+            user_key: user_info.get_key().into(),
+        },
+        action_id,
+    )
+    .as_blob(orderbook_cn)];
+    for (contract_name, amount) in transfers {
+        if *amount == 0 {
+            continue;
+        }
+        blobs.push(
+            SmtTokenAction::Transfer {
+                sender: Identity(ORDERBOOK_ACCOUNT_IDENTITY.to_string()),
+                recipient: Identity(user_info.user.clone()),
+                amount: *amount as u128,
+            }
+            .as_blob(contract_name.clone(), None, None),
+        );
+    }
+    BlobTransaction::new(ORDERBOOK_ACCOUNT_IDENTITY, blobs)
+}
 
 /// Verifies that the signature provided in private_input was made with the private key
 /// of the specified user by validating:
@@ -22,13 +280,126 @@ pub fn verify_user_signature_authorization(
     }
 
     // Verify the signature of the order_id with the public key
-    if !verify_signature(signature, msg, pubkey) {
+    let is_valid = match SessionKeyScheme::of(pubkey) {
+        SessionKeyScheme::Ed25519 => verify_ed25519_signature(signature, msg, pubkey)?,
+        SessionKeyScheme::Passkey => webauthn::verify_assertion(signature, msg, pubkey)?,
+        SessionKeyScheme::Secp256k1 | SessionKeyScheme::EthAddress => {
+            verify_signature(signature, msg, pubkey)
+        }
+    };
+    if !is_valid {
         return Err("Invalid signature for order_id".to_string());
     }
 
     Ok(())
 }
 
+/// Verifies that the action was signed by one of the user's session keys.
+/// Keys registered as an Ethereum address must sign the EIP-712 typed data of the action,
+/// other keys sign its message with the sha3 scheme.
+pub fn verify_user_action_authorization(
+    user_info: &UserInfo,
+    pubkey: &Vec<u8>,
+    action: &SignedAction,
+    signature: &Vec<u8>,
+) -> Result<(), String> {
+    if SessionKeyScheme::of(pubkey) != SessionKeyScheme::EthAddress {
+        return verify_user_signature_authorization(
+            user_info,
+            pubkey,
+            &action.message(),
+            signature,
+        );
+    }
+
+    if !user_info.session_keys.contains(pubkey) {
+        return Err(format!("Public key not found for user {}", user_info.user));
+    }
+
+    let signer = eip712::recover_address(&eip712::signing_hash(action), signature)?;
+    if signer.as_slice() != pubkey.as_slice() {
+        return Err("Invalid EIP-712 signature".to_string());
+    }
+
+    Ok(())
+}
+
+/// Verifies that the session key is allowed to sign the requested action:
+/// 1. That the key scope grants the required permissions
+/// 2. That the action targets the pair the key is restricted to, if any
+///
+/// Keys registered without a scope are unrestricted. Actions that are not tied to a pair
+/// (e.g. withdrawals) only check permissions.
+pub fn verify_session_key_scope(
+    user_info: &UserInfo,
+    pubkey: &[u8],
+    required: SessionKeyPermissions,
+    pair: Option<&Pair>,
+) -> Result<(), String> {
+    let Some(scope) = user_info.get_session_key_scope(pubkey) else {
+        return Ok(());
+    };
+
+    if !scope.permissions.contains(required) {
+        return Err(format!(
+            "Session key of user {} is not allowed to perform this action",
+            user_info.user
+        ));
+    }
+
+    if let (Some(allowed_pair), Some(pair)) = (&scope.pair, pair) {
+        if pair != allowed_pair {
+            return Err(format!(
+                "Session key of user {} is restricted to pair {}/{}, got {}/{}",
+                user_info.user, allowed_pair.0, allowed_pair.1, pair.0, pair.1
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Message signed by a session key to arm cancel-on-disconnect on a set of orders. It only
+/// authorizes their cancellation as long as the user does not act in the meantime, and the
+/// cancellation consumes the nonce, so it cannot be replayed.
+pub fn cancel_on_disconnect_message(
+    user: &str,
+    nonce: u32,
+    timeout_secs: u64,
+    order_ids: &[OrderId],
+) -> String {
+    format!(
+        "{user}:{nonce}:cancel_on_disconnect:{timeout_secs}:{}",
+        order_ids.join(",")
+    )
+}
+
+/// Message signed by a session key to save a withdrawal address
+pub fn save_address_message(user: &str, destination: &WithdrawDestination, label: &str) -> String {
+    format!(
+        "{user}:save_address:{}:{}:{label}",
+        destination.network, destination.address
+    )
+}
+
+/// Message signed by a session key to delete a saved withdrawal address
+pub fn delete_address_message(user: &str, destination: &WithdrawDestination) -> String {
+    format!(
+        "{user}:delete_address:{}:{}",
+        destination.network, destination.address
+    )
+}
+
+/// Message signed by a session key to create an API key
+pub fn create_api_key_message(user: &str, label: &str) -> String {
+    format!("{user}:create_api_key:{label}")
+}
+
+/// Message signed by a session key to revoke an API key
+pub fn revoke_api_key_message(user: &str, key_id: i64) -> String {
+    format!("{user}:revoke_api_key:{key_id}")
+}
+
 /// Verifies a signature for a given message with a public key
 /// Uses ECDSA with secp256k1 curve and SHA3_256 hashing
 pub fn verify_signature(signature: &Vec<u8>, msg: &str, public_key: &Vec<u8>) -> bool {
@@ -57,3 +428,36 @@ pub fn verify_signature(signature: &Vec<u8>, msg: &str, public_key: &Vec<u8>) ->
     use k256::ecdsa::signature::DigestVerifier;
     verifying_key.verify_digest(hasher, &signature).is_ok()
 }
+
+/// Verifies an Ed25519 signature of the raw message bytes with a 32 bytes public key
+#[cfg(feature = "ed25519")]
+pub fn verify_ed25519_signature(
+    signature: &[u8],
+    msg: &str,
+    public_key: &[u8],
+) -> Result<bool, String> {
+    use ed25519_dalek::{Signature, VerifyingKey};
+
+    let Ok(public_key) = <&[u8; ED25519_PUBLIC_KEY_LEN]>::try_from(public_key) else {
+        return Ok(false);
+    };
+    let Ok(verifying_key) = VerifyingKey::from_bytes(public_key) else {
+        return Ok(false);
+    };
+    let Ok(signature) = Signature::from_slice(signature) else {
+        return Ok(false);
+    };
+
+    Ok(verifying_key
+        .verify_strict(msg.as_bytes(), &signature)
+        .is_ok())
+}
+
+#[cfg(not(feature = "ed25519"))]
+pub fn verify_ed25519_signature(
+    _signature: &[u8],
+    _msg: &str,
+    _public_key: &[u8],
+) -> Result<bool, String> {
+    Err("Ed25519 session keys are not supported: the orderbook was built without the ed25519 feature".to_string())
+}
@@ -0,0 +1,138 @@
+//! WebAuthn passkeys registered as session keys, so that users can sign actions with the
+//! authenticator of their device (Touch ID, Windows Hello, security keys...).
+//!
+//! A passkey is registered as [`PASSKEY_KEY_TAG`] followed by its SEC1 encoded P-256 public key,
+//! which keeps it apart from secp256k1 keys of the same length. An action is signed by a WebAuthn
+//! assertion whose challenge is the SHA3-256 digest of the action message, sent as the
+//! borsh-encoded [`WebAuthnAssertion`] in place of the signature.
+//!
+//! The relying party id and the origin are not checked by the contract: any authenticator holding
+//! the registered key can sign.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use sha3::{Digest, Sha3_256};
+
+/// First byte of the passkeys registered as session keys
+pub const PASSKEY_KEY_TAG: u8 = 0x50;
+
+/// Lengths of a tagged compressed or uncompressed P-256 public key
+const PASSKEY_KEY_LENS: [usize; 2] = [34, 66];
+
+/// RP id hash (32 bytes), flags (1 byte) and signature counter (4 bytes)
+const MIN_AUTHENTICATOR_DATA_LEN: usize = 37;
+const FLAGS_INDEX: usize = 32;
+const FLAG_USER_PRESENT: u8 = 0x01;
+
+const BASE64URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Assertion returned by `navigator.credentials.get`
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct WebAuthnAssertion {
+    pub authenticator_data: Vec<u8>,
+    pub client_data_json: Vec<u8>,
+    /// ECDSA P-256 signature, DER encoded as returned by authenticators, or as raw `r ‖ s`
+    pub signature: Vec<u8>,
+}
+
+pub fn is_passkey(public_key: &[u8]) -> bool {
+    public_key.first() == Some(&PASSKEY_KEY_TAG) && PASSKEY_KEY_LENS.contains(&public_key.len())
+}
+
+/// Session key of a passkey, from its SEC1 encoded P-256 public key
+pub fn passkey_session_key(sec1_public_key: &[u8]) -> Vec<u8> {
+    let mut session_key = Vec::with_capacity(sec1_public_key.len() + 1);
+    session_key.push(PASSKEY_KEY_TAG);
+    session_key.extend_from_slice(sec1_public_key);
+    session_key
+}
+
+/// Challenge of the assertion signing `msg`
+pub fn challenge(msg: &str) -> [u8; 32] {
+    Sha3_256::digest(msg.as_bytes()).into()
+}
+
+/// Unpadded base64url encoding, as the challenge appears in the client data
+pub fn base64url(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (u32::from(chunk[0]) << 16)
+            | (u32::from(chunk.get(1).copied().unwrap_or_default()) << 8)
+            | u32::from(chunk.get(2).copied().unwrap_or_default());
+        for i in 0..=chunk.len() {
+            let index = (group >> (18 - 6 * i)) & 0x3f;
+            encoded.push(BASE64URL_ALPHABET[index as usize] as char);
+        }
+    }
+    encoded
+}
+
+/// Browsers serialize the client data without whitespace, so the fields are matched as is
+fn client_data_matches(client_data_json: &[u8], msg: &str) -> bool {
+    let Ok(client_data) = core::str::from_utf8(client_data_json) else {
+        return false;
+    };
+    let expected_challenge = format!("\"challenge\":\"{}\"", base64url(&challenge(msg)));
+    client_data.contains("\"type\":\"webauthn.get\"") && client_data.contains(&expected_challenge)
+}
+
+/// Checks that a passkey to register holds a valid P-256 public key
+#[cfg(feature = "passkey")]
+pub fn validate_passkey(public_key: &[u8]) -> Result<(), String> {
+    if !is_passkey(public_key) {
+        return Err("Invalid session key: not a tagged P-256 public key".to_string());
+    }
+    p256::ecdsa::VerifyingKey::from_sec1_bytes(&public_key[1..])
+        .map(|_| ())
+        .map_err(|e| format!("Invalid session key: {e}"))
+}
+
+#[cfg(not(feature = "passkey"))]
+pub fn validate_passkey(_public_key: &[u8]) -> Result<(), String> {
+    Err("Passkey session keys are not supported by this orderbook".to_string())
+}
+
+/// Verifies a borsh-encoded [`WebAuthnAssertion`] of `msg` with a passkey session key
+#[cfg(feature = "passkey")]
+pub fn verify_assertion(signature: &[u8], msg: &str, public_key: &[u8]) -> Result<bool, String> {
+    use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+    use sha2::Sha256;
+
+    if !is_passkey(public_key) {
+        return Ok(false);
+    }
+    let Ok(verifying_key) = VerifyingKey::from_sec1_bytes(&public_key[1..]) else {
+        return Ok(false);
+    };
+    let Ok(assertion) = borsh::from_slice::<WebAuthnAssertion>(signature) else {
+        return Ok(false);
+    };
+
+    let authenticator_data = &assertion.authenticator_data;
+    if authenticator_data.len() < MIN_AUTHENTICATOR_DATA_LEN
+        || authenticator_data[FLAGS_INDEX] & FLAG_USER_PRESENT == 0
+    {
+        return Ok(false);
+    }
+    if !client_data_matches(&assertion.client_data_json, msg) {
+        return Ok(false);
+    }
+
+    let Ok(signature) = Signature::from_der(&assertion.signature)
+        .or_else(|_| Signature::from_slice(&assertion.signature))
+    else {
+        return Ok(false);
+    };
+    // Authenticators do not normalize s, and the nonce already prevents replays
+    let signature = signature.normalize_s().unwrap_or(signature);
+
+    // The authenticator signs its data followed by the SHA-256 of the client data
+    let mut signed_data = authenticator_data.clone();
+    signed_data.extend_from_slice(&Sha256::digest(&assertion.client_data_json));
+    Ok(verifying_key.verify(&signed_data, &signature).is_ok())
+}
+
+#[cfg(not(feature = "passkey"))]
+pub fn verify_assertion(_signature: &[u8], _msg: &str, _public_key: &[u8]) -> Result<bool, String> {
+    Err("Passkey session keys are not supported: the orderbook was built without the passkey feature".to_string())
+}
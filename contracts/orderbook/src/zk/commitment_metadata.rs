@@ -7,9 +7,9 @@ use crate::{
     },
     transaction::PermissionedOrderbookAction,
     zk::{
-        order_merkle::OrderPriceLevel,
+        order_merkle::{build_witness, OrderPriceLevel},
         smt::{GetKey, UserBalance},
-        FullState, OrderManagerWitnesses, Proof, ZkVmState, ZkWitnessSet, SMT,
+        FullState, OrderManagerWitnesses, Proof, ZkVmState, ZkWitnessSet, H256, SMT,
     },
 };
 
@@ -40,6 +40,9 @@ impl FullState {
         let base = self.resolve_user_from_state(base_user, &base_user.user)?;
         users_info_needed.insert(base);
         let mut balances_needed: HashMap<Symbol, Vec<UserBalance>> = HashMap::new();
+        // Users registered within these events, before they are in the state: onboarded by the
+        // operator, or the insurance fund on its first penalty
+        let mut onboarded: HashMap<&str, UserInfo> = HashMap::new();
 
         for event in events {
             match event {
@@ -48,7 +51,10 @@ impl FullState {
                     symbol,
                     amount,
                 } => {
-                    let ui = self.resolve_user_from_state(base_user, user)?;
+                    let ui = match onboarded.get(user.as_str()) {
+                        Some(ui) => ui.clone(),
+                        None => self.resolve_user_from_state(base_user, user)?,
+                    };
                     users_info_needed.insert(ui.clone());
                     let user_key = ui.get_key();
                     users_info_needed.insert(ui);
@@ -60,9 +66,28 @@ impl FullState {
                             balance: Balance(*amount),
                         });
                 }
+                OrderbookEvent::SessionKeyAdded { user, salt, .. }
+                    if user != &base_user.user && self.state.get_user_info(user).is_err() =>
+                {
+                    let ui = UserInfo::new(user.clone(), salt.clone());
+                    onboarded.insert(user, ui.clone());
+                    users_info_needed.insert(ui);
+                }
                 OrderbookEvent::SessionKeyAdded { user, .. }
-                | OrderbookEvent::NonceIncremented { user, .. } => {
-                    let ui = self.resolve_user_from_state(base_user, user)?;
+                | OrderbookEvent::NonceIncremented { user, .. }
+                | OrderbookEvent::FeeTierUpdated { user, .. }
+                | OrderbookEvent::WithdrawalRecorded { user, .. }
+                | OrderbookEvent::WithdrawalQueued { user, .. }
+                | OrderbookEvent::WithdrawalReviewed { user, .. }
+                | OrderbookEvent::WithdrawalReleased { user, .. }
+                | OrderbookEvent::PositionUpdated { user, .. }
+                | OrderbookEvent::DebtUpdated { user, .. }
+                | OrderbookEvent::MarginCalled { user, .. }
+                | OrderbookEvent::Liquidation { user, .. } => {
+                    let ui = match onboarded.get(user.as_str()) {
+                        Some(ui) => ui.clone(),
+                        None => self.resolve_user_from_state(base_user, user)?,
+                    };
                     users_info_needed.insert(ui);
                 }
                 OrderbookEvent::PairCreated { pair, .. } => {
@@ -73,6 +98,35 @@ impl FullState {
             }
         }
 
+        // Margin accounts are valued from every balance of their quote and collaterals
+        for ui in &users_info_needed {
+            let mut quotes = ui.margin_quotes();
+            for event in events {
+                match event {
+                    OrderbookEvent::PositionUpdated { user, position } if user == &ui.user => {
+                        quotes.insert(position.pair.1.clone());
+                    }
+                    OrderbookEvent::DebtUpdated { user, debt } if user == &ui.user => {
+                        quotes.insert(debt.symbol.clone());
+                    }
+                    OrderbookEvent::Liquidation { user, pair, .. } if user == &ui.user => {
+                        quotes.insert(pair.1.clone());
+                    }
+                    _ => {}
+                }
+            }
+            for symbol in self.state.margin_symbols(&quotes) {
+                let user_key = ui.get_key();
+                let balances = balances_needed.entry(symbol.clone()).or_default();
+                if !balances.iter().any(|balance| balance.user_key == user_key) {
+                    balances.push(UserBalance {
+                        user_key,
+                        balance: self.state.get_balance(ui, &symbol),
+                    });
+                }
+            }
+        }
+
         Ok((users_info_needed, balances_needed))
     }
 
@@ -253,14 +307,16 @@ impl FullState {
                         OrderSide::Ask => &self.state.order_manager.ask_orders,
                     };
 
-                    let price_map = side_map.get(&order.pair).cloned().unwrap_or_default();
-
-                    let order_queue = price_map.get(&price).cloned().unwrap_or_default();
+                    let order_ids = side_map
+                        .get(&order.pair)
+                        .and_then(|price_map| price_map.get(&price))
+                        .map(|order_queue| order_queue.iter().cloned().collect())
+                        .unwrap_or_default();
 
                     let price_level = OrderPriceLevel {
                         pair: order.pair.clone(),
                         price,
-                        order_ids: order_queue.iter().cloned().collect(),
+                        order_ids,
                     };
 
                     match order.order_side {
@@ -291,7 +347,11 @@ impl FullState {
         for user in users {
             values.insert(user.clone());
         }
-        Ok(ZkWitnessSet { values, proof })
+        Ok(ZkWitnessSet {
+            values,
+            deleted: HashSet::new(),
+            proof,
+        })
     }
 
     fn create_balances_witness(
@@ -307,7 +367,11 @@ impl FullState {
                 balance,
             });
         }
-        Ok(ZkWitnessSet { values, proof })
+        Ok(ZkWitnessSet {
+            values,
+            deleted: HashSet::new(),
+            proof,
+        })
     }
 
     fn get_users_info_proofs(&self, users_info: &HashSet<UserInfo>) -> Result<Proof, String> {
@@ -374,15 +438,9 @@ impl FullState {
                 | OrderbookEvent::OrderCancelled { order_id, .. } => {
                     if let Some(order_owner) = self.state.order_manager.orders_owner.get(order_id) {
                         orders_owner.insert(order_id.clone(), *order_owner);
-                    } else if let PermissionedOrderbookAction::CreateOrder(Order {
-                        order_id: create_order_id,
-                        ..
-                    }) = action
-                    {
-                        if create_order_id == order_id {
-                            // Special case: the order was created in the same tx, we can use the user_info
-                            orders_owner.insert(order_id.clone(), user_info.get_key());
-                        }
+                    } else if action.creates_order(order_id) {
+                        // Special case: the order was created in the same tx, we can use the user_info
+                        orders_owner.insert(order_id.clone(), user_info.get_key());
                     } else {
                         return Err(format!(
                             "Order with id {order_id} does not have an owner in orders_owner mapping"
@@ -462,6 +520,99 @@ impl FullState {
             hashed_secret: self.hashed_secret,
             last_block_number: self.last_block_number,
             assets: self.state.assets_info.clone(),
+            pairs_status: self.state.pairs_status.clone(),
+            circuit_breakers: self.state.circuit_breakers.clone(),
+            perp_markets: self.state.perp_markets.clone(),
+            bridge_paused: self.state.bridge_paused,
+        };
+
+        borsh::to_vec(&zkvm_state)
+            .map_err(|e| format!("Failed to serialize ZkVm orderbook metadata: {e}"))
+    }
+
+    /// Merges the commitment metadata derived for consecutive txs into the metadata of a single
+    /// zkvm execution proving all of them, `self` being the state before the first tx.
+    /// A value that is updated by a tx is witnessed by it, so the initial value of each key is
+    /// taken from the first metadata witnessing it, and proven against the roots of `self`.
+    pub fn merge_zkvm_commitment_metadata(&self, metadata: &[Vec<u8>]) -> Result<Vec<u8>, String> {
+        let mut users_info: BTreeMap<H256, UserInfo> = BTreeMap::new();
+        let mut balances: BTreeMap<Symbol, BTreeMap<H256, UserBalance>> = BTreeMap::new();
+        let mut orders: BTreeMap<H256, Order> = BTreeMap::new();
+        let mut bid_levels: BTreeMap<H256, OrderPriceLevel> = BTreeMap::new();
+        let mut ask_levels: BTreeMap<H256, OrderPriceLevel> = BTreeMap::new();
+        let mut orders_owner = HashMap::new();
+
+        for (index, tx_metadata) in metadata.iter().enumerate() {
+            let zkvm_state: ZkVmState = borsh::from_slice(tx_metadata)
+                .map_err(|e| format!("Failed to decode commitment metadata {index}: {e}"))?;
+
+            for user_info in zkvm_state.users_info.values {
+                users_info.entry(user_info.get_key()).or_insert(user_info);
+            }
+            for (symbol, witness) in zkvm_state.balances {
+                let symbol_balances = balances.entry(symbol).or_default();
+                for balance in witness.values {
+                    symbol_balances.entry(balance.get_key()).or_insert(balance);
+                }
+            }
+            let order_manager = zkvm_state.order_manager;
+            for order in order_manager.orders.values {
+                orders.entry(order.get_key()).or_insert(order);
+            }
+            for level in order_manager.bid_orders.values {
+                bid_levels.entry(level.get_key()).or_insert(level);
+            }
+            for level in order_manager.ask_orders.values {
+                ask_levels.entry(level.get_key()).or_insert(level);
+            }
+            for (order_id, owner) in order_manager.orders_owner {
+                orders_owner.entry(order_id).or_insert(owner);
+            }
+        }
+
+        let users_info = build_witness(
+            &self.users_info_mt,
+            users_info.into_values().collect(),
+            "users info merkle proof for batch",
+        )?;
+
+        let zero_tree = SMT::<UserBalance>::zero();
+        let balances = balances
+            .into_iter()
+            .map(|(symbol, symbol_balances)| {
+                // Pairs created in the batch have no balances tree yet
+                let tree = self.balances_mt.get(&symbol).unwrap_or(&zero_tree);
+                let witness = build_witness(
+                    tree,
+                    symbol_balances.into_values().collect(),
+                    &format!("{symbol} balances merkle proof for batch"),
+                )?;
+                Ok((symbol, witness))
+            })
+            .collect::<Result<HashMap<_, _>, String>>()?;
+
+        let order_manager = self
+            .order_manager_mt
+            .create_orders_witnesses(
+                orders.into_values().collect(),
+                bid_levels.into_values().collect(),
+                ask_levels.into_values().collect(),
+                orders_owner,
+            )
+            .map_err(|e| format!("Failed to build order manager witness for batch: {e}"))?;
+
+        let zkvm_state = ZkVmState {
+            users_info,
+            balances,
+            order_manager,
+            lane_id: self.lane_id.clone(),
+            hashed_secret: self.hashed_secret,
+            last_block_number: self.last_block_number,
+            assets: self.state.assets_info.clone(),
+            pairs_status: self.state.pairs_status.clone(),
+            circuit_breakers: self.state.circuit_breakers.clone(),
+            perp_markets: self.state.perp_markets.clone(),
+            bridge_paused: self.state.bridge_paused,
         };
 
         borsh::to_vec(&zkvm_state)
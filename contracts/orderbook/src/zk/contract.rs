@@ -5,10 +5,10 @@ use sha3::{Digest, Sha3_256};
 use sparse_merkle_tree::traits::Value;
 
 use crate::{
-    model::{Balance, ExecuteState, OrderbookEvent},
+    model::{Balance, ExecuteState},
     transaction::{
-        EscapePrivateInput, OrderbookAction, PermissionedOrderbookAction, PermissionedPrivateInput,
-        PermissionlessOrderbookAction,
+        CreateOrderPrivateInput, EscapePrivateInput, OrderbookAction, PermissionedOrderbookAction,
+        PermissionedPrivateInput, PermissionlessOrderbookAction,
     },
     zk::{
         order_merkle::collect_price_levels,
@@ -91,6 +91,54 @@ impl sdk::ZkContract for ZkVmState {
                     ));
                 }
 
+                // Withdraw limits cannot be accounted in a window that has not started yet, and
+                // withdrawal delays cannot start or end at a block that has not been reached yet
+                if let PermissionedOrderbookAction::Withdraw { block_height, .. }
+                | PermissionedOrderbookAction::RequestWithdrawal { block_height, .. }
+                | PermissionedOrderbookAction::ReleaseWithdrawal { block_height, .. } = &action
+                {
+                    if *block_height > tx_ctx.block_height.0 {
+                        return Err(format!(
+                            "Withdraw accounted at block {block_height}, after the block of the tx {}",
+                            tx_ctx.block_height.0
+                        ));
+                    }
+                }
+                // Index prices cannot be tracked at a block that has not been reached yet
+                if let PermissionedOrderbookAction::UpdateIndexPrices { block_height, .. } = &action
+                {
+                    if *block_height > tx_ctx.block_height.0 {
+                        return Err(format!(
+                            "Index prices tracked at block {block_height}, after the block of the tx {}",
+                            tx_ctx.block_height.0
+                        ));
+                    }
+                }
+                // Circuit breakers cannot be checked at a block that has not been reached yet
+                if let PermissionedOrderbookAction::Liquidate { block_height, .. } = &action {
+                    if *block_height > tx_ctx.block_height.0 {
+                        return Err(format!(
+                            "Liquidation checked at block {block_height}, after the block of the tx {}",
+                            tx_ctx.block_height.0
+                        ));
+                    }
+                }
+                if let PermissionedOrderbookAction::CreateOrder(_)
+                | PermissionedOrderbookAction::CreateOrders(_)
+                | PermissionedOrderbookAction::SweepDust { .. } = &action
+                {
+                    let create_order_private_input: CreateOrderPrivateInput = borsh::from_slice(
+                        &permissioned_private_input.private_input,
+                    )
+                    .map_err(|e| format!("Failed to deserialize CreateOrderPrivateInput: {e}"))?;
+                    if create_order_private_input.block_height > tx_ctx.block_height.0 {
+                        return Err(format!(
+                            "Orders checked at block {}, after the block of the tx {}",
+                            create_order_private_input.block_height, tx_ctx.block_height.0
+                        ));
+                    }
+                }
+
                 let user_info = permissioned_private_input.user_info.clone();
 
                 // Assert that used user_info is correct
@@ -138,15 +186,11 @@ impl sdk::ZkContract for ZkVmState {
             }
         };
 
+        // Orders and price levels emptied by the events are deleted from their witnesses
+        state.order_manager.clean(&events);
+
         // Filter out unwanted events for privacy
-        events.retain(|evt| {
-            !matches!(
-                evt,
-                OrderbookEvent::BalanceUpdated { .. }
-                    | OrderbookEvent::SessionKeyAdded { .. }
-                    | OrderbookEvent::NonceIncremented { .. }
-            )
-        });
+        events.retain(|evt| !evt.is_private());
 
         let res =
             borsh::to_vec(&events).map_err(|e| format!("Failed to encode OrderbookEvents: {e}"))?;
@@ -158,32 +202,34 @@ impl sdk::ZkContract for ZkVmState {
 
     fn commit(&self) -> StateCommitment {
         let order_manager_roots = self.order_manager.commitment();
-        StateCommitment(
-            borsh::to_vec(&ParsedStateCommitment {
-                users_info_root: self
-                    .users_info
-                    .compute_root()
-                    .expect("compute user info root"),
-                balances_roots: self
-                    .balances
-                    .iter()
-                    .filter_map(|(symbol, witness)| {
-                        let root = witness.compute_root().expect("compute user balance root");
-                        if root == H256::zero() {
-                            None
-                        } else {
-                            Some((symbol.clone(), root))
-                        }
-                    })
-                    .collect(),
-                assets: self.assets.iter().collect(),
-                order_manager_roots,
-                hashed_secret: self.hashed_secret,
-                lane_id: &self.lane_id,
-                last_block_number: &self.last_block_number,
-            })
-            .expect("Could not encode onchain state into state commitment"),
-        )
+        ParsedStateCommitment {
+            users_info_root: self
+                .users_info
+                .compute_root()
+                .expect("compute user info root"),
+            balances_roots: self
+                .balances
+                .iter()
+                .filter_map(|(symbol, witness)| {
+                    let root = witness.compute_root().expect("compute user balance root");
+                    if root == H256::zero() {
+                        None
+                    } else {
+                        Some((symbol.clone(), root))
+                    }
+                })
+                .collect(),
+            assets: self.assets.iter().collect(),
+            pairs_status: self.pairs_status.iter().collect(),
+            circuit_breakers: self.circuit_breakers.iter().collect(),
+            perp_markets: self.perp_markets.iter().collect(),
+            bridge_paused: self.bridge_paused,
+            order_manager_roots,
+            hashed_secret: self.hashed_secret,
+            lane_id: &self.lane_id,
+            last_block_number: &self.last_block_number,
+        }
+        .encode()
     }
 }
 
@@ -200,8 +246,8 @@ impl ZkVmState {
             assets_info: std::mem::take(&mut self.assets), // Assets info is not part of zkvm state
             users_info: self
                 .users_info
-                .values
-                .drain()
+                .take_values()
+                .into_iter()
                 .map(|u| (u.user.clone(), u))
                 .collect(),
             balances: self
@@ -211,14 +257,18 @@ impl ZkVmState {
                     (
                         symbol.clone(),
                         witness
-                            .values
-                            .drain()
+                            .take_values()
+                            .into_iter()
                             .map(|ub| (ub.user_key, ub.balance))
                             .collect::<HashMap<H256, Balance>>(),
                     )
                 })
                 .collect::<HashMap<String, HashMap<H256, Balance>>>(),
             order_manager,
+            pairs_status: std::mem::take(&mut self.pairs_status),
+            circuit_breakers: std::mem::take(&mut self.circuit_breakers),
+            perp_markets: std::mem::take(&mut self.perp_markets),
+            bridge_paused: self.bridge_paused,
         }
     }
 
@@ -245,12 +295,11 @@ impl ZkVmState {
     /// This function applies to self all the changes that happened in the execution state
     pub fn take_changes_back(&mut self, state: &mut ExecuteState) -> Result<(), String> {
         self.users_info
-            .values
-            .extend(std::mem::take(&mut state.users_info).into_values());
+            .put_values(std::mem::take(&mut state.users_info).into_values());
 
         for (symbol, witness) in self.balances.iter_mut() {
             if let Some(state_balances) = state.balances.remove(symbol) {
-                witness.values.extend(
+                witness.put_values(
                     state_balances
                         .into_iter()
                         .map(|(user_key, balance)| UserBalance { user_key, balance }),
@@ -259,18 +308,26 @@ impl ZkVmState {
         }
 
         std::mem::swap(&mut self.assets, &mut state.assets_info);
+        std::mem::swap(&mut self.pairs_status, &mut state.pairs_status);
+        std::mem::swap(&mut self.circuit_breakers, &mut state.circuit_breakers);
+        std::mem::swap(&mut self.perp_markets, &mut state.perp_markets);
+        self.bridge_paused = state.bridge_paused;
 
-        // Update orders
-        self.order_manager.orders.values = std::mem::take(&mut state.order_manager.orders)
-            .into_values()
-            .collect();
+        // Update orders, the ones removed from the order manager are deleted
+        self.order_manager
+            .orders
+            .replace_values(std::mem::take(&mut state.order_manager.orders).into_values());
         self.order_manager.orders_owner = std::mem::take(&mut state.order_manager.orders_owner);
 
         // Update bid orders
         let bid_orders = std::mem::take(&mut state.order_manager.bid_orders);
-        self.order_manager.bid_orders.values = collect_price_levels(&bid_orders);
+        self.order_manager
+            .bid_orders
+            .replace_values(collect_price_levels(&bid_orders));
         let ask_orders = std::mem::take(&mut state.order_manager.ask_orders);
-        self.order_manager.ask_orders.values = collect_price_levels(&ask_orders);
+        self.order_manager
+            .ask_orders
+            .replace_values(collect_price_levels(&ask_orders));
 
         Ok(())
     }
@@ -279,11 +336,14 @@ impl ZkVmState {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::{AssetInfo, Balance, Order, OrderSide, OrderType, UserInfo};
+    use crate::model::{
+        AssetInfo, Balance, CircuitBreaker, CircuitBreakerState, Collateral, CollateralConfig,
+        FeeTier, MarketStatus, Order, OrderSide, OrderType, PerpConfig, PerpMarket, UserInfo,
+    };
     use crate::order_manager::OrderManager;
     use crate::zk::{
         order_merkle::{collect_price_levels, OrderManagerWitnesses},
-        OrderManagerMerkles, ZkWitnessSet, H256, SMT,
+        LegacyStateCommitment, OrderManagerMerkles, ZkWitnessSet, H256, SMT,
     };
     use borsh::{BorshDeserialize, BorshSerialize};
     use sdk::merkle_utils::BorshableMerkleProof;
@@ -316,14 +376,17 @@ mod tests {
         OrderManagerWitnesses {
             orders: ZkWitnessSet {
                 values: orders_values,
+                deleted: HashSet::new(),
                 proof: Proof::CurrentRootHash(H256::default()),
             },
             bid_orders: ZkWitnessSet {
                 values: bid_levels,
+                deleted: HashSet::new(),
                 proof: Proof::CurrentRootHash(H256::default()),
             },
             ask_orders: ZkWitnessSet {
                 values: ask_levels,
+                deleted: HashSet::new(),
                 proof: Proof::CurrentRootHash(H256::default()),
             },
             orders_owner: order_manager.orders_owner.clone(),
@@ -340,6 +403,7 @@ mod tests {
 
         let users_info = ZkWitnessSet {
             values: users_values,
+            deleted: HashSet::new(),
             proof: Proof::CurrentRootHash(H256::default()),
         };
 
@@ -367,6 +431,7 @@ mod tests {
             "ETH".to_string(),
             ZkWitnessSet {
                 values: eth_balances,
+                deleted: HashSet::new(),
                 proof: Proof::CurrentRootHash(H256::default()),
             },
         );
@@ -374,6 +439,7 @@ mod tests {
             "USDC".to_string(),
             ZkWitnessSet {
                 values: usdc_balances,
+                deleted: HashSet::new(),
                 proof: Proof::CurrentRootHash(H256::default()),
             },
         );
@@ -381,7 +447,16 @@ mod tests {
         let mut assets = HashMap::new();
         assets.insert(
             "ETH".to_string(),
-            AssetInfo::new(18, ContractName("eth".to_string())),
+            AssetInfo {
+                collateral: Some(Collateral {
+                    config: CollateralConfig {
+                        quote: "USDC".to_string(),
+                        weight_bps: 8_000,
+                    },
+                    price: 1_500,
+                }),
+                ..AssetInfo::new(18, ContractName("eth".to_string()))
+            },
         );
         assets.insert(
             "USDC".to_string(),
@@ -423,6 +498,33 @@ mod tests {
             last_block_number: BlockHeight::default(),
             order_manager: order_manager_witness,
             assets,
+            pairs_status: HashMap::from([(pair.clone(), MarketStatus::Halted)]),
+            circuit_breakers: HashMap::from([(
+                pair.clone(),
+                CircuitBreakerState {
+                    config: CircuitBreaker {
+                        max_move_bps: 500,
+                        window_blocks: 10,
+                        halt_blocks: 5,
+                    },
+                    reference_price: Some(price),
+                    window_start: 7,
+                    halted_until: 12,
+                },
+            )]),
+            perp_markets: HashMap::from([(
+                pair,
+                PerpMarket {
+                    config: PerpConfig {
+                        initial_margin_bps: 1_000,
+                        maintenance_margin_bps: 500,
+                        liquidation_penalty_bps: 0,
+                    },
+                    funding_index: -3,
+                    index_price: price,
+                },
+            )]),
+            bridge_paused: true,
         }
     }
 
@@ -506,6 +608,10 @@ mod tests {
             actual.values, expected.values,
             "{label} witness values differ"
         );
+        assert_eq!(
+            actual.deleted, expected.deleted,
+            "{label} witness tombstones differ"
+        );
         assert_eq!(
             discriminant(&actual.proof),
             discriminant(&expected.proof),
@@ -534,6 +640,22 @@ mod tests {
             execution_state.assets_info, expected_state.assets,
             "asset info mismatch after into_orderbook_state"
         );
+        assert_eq!(
+            execution_state.pairs_status, expected_state.pairs_status,
+            "pairs status mismatch after into_orderbook_state"
+        );
+        assert_eq!(
+            execution_state.circuit_breakers, expected_state.circuit_breakers,
+            "circuit breakers mismatch after into_orderbook_state"
+        );
+        assert_eq!(
+            execution_state.perp_markets, expected_state.perp_markets,
+            "perp markets mismatch after into_orderbook_state"
+        );
+        assert_eq!(
+            execution_state.bridge_paused, expected_state.bridge_paused,
+            "bridge pause mismatch after into_orderbook_state"
+        );
         assert_eq!(
             execution_state.order_manager, expected_order_manager,
             "order manager mismatch after into_orderbook_state"
@@ -546,6 +668,22 @@ mod tests {
             .expect("take_changes_back should succeed");
 
         assert_eq!(zk_state.assets, expected_state.assets, "assets mismatch");
+        assert_eq!(
+            zk_state.pairs_status, expected_state.pairs_status,
+            "pairs status mismatch"
+        );
+        assert_eq!(
+            zk_state.circuit_breakers, expected_state.circuit_breakers,
+            "circuit breakers mismatch"
+        );
+        assert_eq!(
+            zk_state.perp_markets, expected_state.perp_markets,
+            "perp markets mismatch"
+        );
+        assert_eq!(
+            zk_state.bridge_paused, expected_state.bridge_paused,
+            "bridge pause mismatch"
+        );
         assert_order_manager_witness_equal(&zk_state.order_manager, &expected_state.order_manager);
         assert_eq!(zk_state.lane_id, expected_state.lane_id, "lane id mismatch");
         assert_eq!(
@@ -599,11 +737,13 @@ mod tests {
     fn commit_skips_zero_root_balance_witnesses() {
         let users_witness = ZkWitnessSet {
             values: HashSet::new(),
+            deleted: HashSet::new(),
             proof: Proof::CurrentRootHash(H256::default()),
         };
 
         let zero_balance_witness = ZkWitnessSet {
             values: HashSet::new(),
+            deleted: HashSet::new(),
             proof: Proof::CurrentRootHash(H256::default()),
         };
 
@@ -612,6 +752,7 @@ mod tests {
         let non_zero_root = H256::from(non_zero_bytes);
         let non_zero_witness = ZkWitnessSet {
             values: HashSet::new(),
+            deleted: HashSet::new(),
             proof: Proof::CurrentRootHash(non_zero_root),
         };
 
@@ -634,6 +775,10 @@ mod tests {
             last_block_number,
             order_manager: zk_order_manager,
             assets: assets.clone(),
+            pairs_status: HashMap::new(),
+            circuit_breakers: HashMap::new(),
+            perp_markets: HashMap::new(),
+            bridge_paused: false,
         };
 
         let commit = zk_state.commit();
@@ -645,18 +790,20 @@ mod tests {
             .expect("expected order manager commitment")
             .commitment();
 
-        let expected_commitment = StateCommitment(
-            borsh::to_vec(&ParsedStateCommitment {
-                users_info_root: users_witness.clone().compute_root().expect("users root"),
-                balances_roots: expected_balances,
-                assets: assets.iter().collect::<BTreeMap<_, _>>(),
-                order_manager_roots: expected_orders_commitment,
-                hashed_secret,
-                lane_id: &lane_id,
-                last_block_number: &last_block_number,
-            })
-            .expect("encode expected commitment"),
-        );
+        let expected_commitment = ParsedStateCommitment {
+            users_info_root: users_witness.clone().compute_root().expect("users root"),
+            balances_roots: expected_balances,
+            assets: assets.iter().collect::<BTreeMap<_, _>>(),
+            pairs_status: BTreeMap::new(),
+            circuit_breakers: BTreeMap::new(),
+            perp_markets: BTreeMap::new(),
+            bridge_paused: false,
+            order_manager_roots: expected_orders_commitment,
+            hashed_secret,
+            lane_id: &lane_id,
+            last_block_number: &last_block_number,
+        }
+        .encode();
 
         assert_eq!(
             commit.0, expected_commitment.0,
@@ -683,11 +830,13 @@ mod tests {
 
         let balance_witness = ZkWitnessSet {
             values: HashSet::from([user_balance.clone()]),
+            deleted: HashSet::new(),
             proof: Proof::Some(BorshableMerkleProof(balance_proof)),
         };
 
         let users_witness = ZkWitnessSet {
             values: HashSet::from([alice]),
+            deleted: HashSet::new(),
             proof: Proof::CurrentRootHash(H256::default()),
         };
 
@@ -709,6 +858,10 @@ mod tests {
             last_block_number,
             order_manager: zk_order_manager,
             assets: assets.clone(),
+            pairs_status: HashMap::new(),
+            circuit_breakers: HashMap::new(),
+            perp_markets: HashMap::new(),
+            bridge_paused: false,
         };
 
         let commit = zk_state.commit();
@@ -717,22 +870,305 @@ mod tests {
             .expect("expected order manager commitment")
             .commitment();
 
-        let expected_commitment = StateCommitment(
+        let expected_commitment = ParsedStateCommitment {
+            users_info_root: users_witness.compute_root().expect("users root"),
+            balances_roots: BTreeMap::from([("TOKEN".to_string(), balance_root)]),
+            assets: assets.iter().collect::<BTreeMap<_, _>>(),
+            pairs_status: BTreeMap::new(),
+            circuit_breakers: BTreeMap::new(),
+            perp_markets: BTreeMap::new(),
+            bridge_paused: false,
+            order_manager_roots: expected_orders_commitment,
+            hashed_secret,
+            lane_id: &lane_id,
+            last_block_number: &last_block_number,
+        }
+        .encode();
+
+        assert_eq!(
+            commit.0, expected_commitment.0,
+            "commit should honor roots derived from balance proofs"
+        );
+    }
+
+    #[test]
+    fn user_leaf_keeps_first_version_encoding_until_a_later_field_is_used() {
+        let leaf = |bytes: &[u8]| {
+            let mut h = [0u8; 32];
+            h.copy_from_slice(&Sha3_256::digest(bytes));
+            H256::from(h)
+        };
+        let mut alice = sample_user("alice", 0xAB, 3, Some(vec![1, 2, 3]));
+        let first_version =
+            borsh::to_vec(&(&alice.user, &alice.salt, &alice.nonce, &alice.session_keys))
+                .expect("encode first version user info");
+        assert_eq!(alice.to_h256(), leaf(&first_version));
+
+        alice.fee_tier = FeeTier {
+            tier: 1,
+            maker_fee_bps: 5,
+            taker_fee_bps: 10,
+        };
+        let full = borsh::to_vec(&alice).expect("encode user info");
+        assert_eq!(alice.to_h256(), leaf(&full));
+        assert_ne!(leaf(&full), leaf(&first_version));
+    }
+
+    #[test]
+    fn commit_keeps_first_version_layout_until_a_later_field_is_used() {
+        let pair = ("BTC".to_string(), "USDC".to_string());
+        let order_manager = order_manager_witness_from_manager(&OrderManager::default());
+        let order_manager_roots = order_manager.commitment();
+        let mut zk_state = ZkVmState {
+            users_info: ZkWitnessSet {
+                values: HashSet::new(),
+                deleted: HashSet::new(),
+                proof: Proof::CurrentRootHash(H256::default()),
+            },
+            balances: HashMap::new(),
+            lane_id: LaneId::default(),
+            hashed_secret: [3u8; 32],
+            last_block_number: BlockHeight::default(),
+            order_manager,
+            assets: HashMap::from([(
+                "BTC".to_string(),
+                AssetInfo::new(8, ContractName::from("btc")),
+            )]),
+            pairs_status: HashMap::from([(pair.clone(), MarketStatus::Active)]),
+            circuit_breakers: HashMap::new(),
+            perp_markets: HashMap::new(),
+            bridge_paused: false,
+        };
+
+        let first_version = borsh::to_vec(&LegacyStateCommitment {
+            users_info_root: zk_state.users_info.compute_root().expect("users root"),
+            balances_roots: &BTreeMap::new(),
+            assets: BTreeMap::from([(&"BTC".to_string(), (8, &ContractName::from("btc")))]),
+            order_manager_roots: &order_manager_roots,
+            hashed_secret: [3u8; 32],
+            lane_id: &LaneId::default(),
+            last_block_number: &BlockHeight::default(),
+        })
+        .expect("encode first version commitment");
+        assert_eq!(zk_state.commit().0, first_version);
+
+        zk_state.pairs_status.insert(pair, MarketStatus::Halted);
+        let commit = zk_state.commit();
+        assert_ne!(commit.0, first_version);
+        assert_eq!(
+            commit.0,
             borsh::to_vec(&ParsedStateCommitment {
-                users_info_root: users_witness.compute_root().expect("users root"),
-                balances_roots: BTreeMap::from([("TOKEN".to_string(), balance_root)]),
-                assets: assets.iter().collect::<BTreeMap<_, _>>(),
-                order_manager_roots: expected_orders_commitment,
-                hashed_secret,
-                lane_id: &lane_id,
-                last_block_number: &last_block_number,
+                users_info_root: zk_state.users_info.compute_root().expect("users root"),
+                balances_roots: BTreeMap::new(),
+                assets: zk_state.assets.iter().collect(),
+                pairs_status: zk_state.pairs_status.iter().collect(),
+                circuit_breakers: BTreeMap::new(),
+                perp_markets: BTreeMap::new(),
+                bridge_paused: false,
+                order_manager_roots,
+                hashed_secret: [3u8; 32],
+                lane_id: &LaneId::default(),
+                last_block_number: &BlockHeight::default(),
             })
-            .expect("encode expected commitment"),
+            .expect("encode commitment")
         );
+    }
+
+    fn sample_balances() -> Vec<UserBalance> {
+        ["alice", "bob", "carol"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| UserBalance {
+                user_key: sample_user(name, i as u8, 0, None).get_key(),
+                balance: Balance(100 * (i as u64 + 1)),
+            })
+            .collect()
+    }
 
+    fn balance_witness(
+        tree: &SMT<UserBalance>,
+        values: &[UserBalance],
+    ) -> ZkWitnessSet<UserBalance> {
+        let proof = tree.merkle_proof(values.iter()).expect("balance proof");
+        ZkWitnessSet {
+            values: values.iter().cloned().collect(),
+            deleted: HashSet::new(),
+            proof: Proof::Some(BorshableMerkleProof(proof)),
+        }
+    }
+
+    #[test]
+    fn witness_proof_survives_borsh_roundtrip() {
+        let balances = sample_balances();
+        let mut tree = SMT::zero();
+        tree.update_all(balances.iter().cloned())
+            .expect("update balance tree");
+
+        let witness = balance_witness(&tree, &balances[..2]);
+        let encoded = borsh::to_vec(&witness).expect("serialize witness");
+        let decoded =
+            ZkWitnessSet::<UserBalance>::try_from_slice(&encoded).expect("deserialize witness");
+
+        assert_eq!(witness.compute_root(), Ok(tree.root()));
         assert_eq!(
-            commit.0, expected_commitment.0,
-            "commit should honor roots derived from balance proofs"
+            decoded.compute_root(),
+            Ok(tree.root()),
+            "decoded proof should verify against the tree root"
+        );
+    }
+
+    #[test]
+    fn witness_with_wrong_leaf_set_does_not_match_root() {
+        let balances = sample_balances();
+        let mut tree = SMT::zero();
+        tree.update_all(balances.iter().cloned())
+            .expect("update balance tree");
+        let root = tree.root();
+
+        let mut tampered = balance_witness(&tree, &balances[..1]);
+        tampered.values = HashSet::from([UserBalance {
+            balance: Balance(1_000_000),
+            ..balances[0].clone()
+        }]);
+        assert_ne!(tampered.compute_root().ok(), Some(root), "tampered value");
+
+        let mut extra = balance_witness(&tree, &balances[..1]);
+        extra.values.insert(balances[1].clone());
+        assert_ne!(
+            extra.compute_root().ok(),
+            Some(root),
+            "value outside the proof"
+        );
+
+        let mut missing = balance_witness(&tree, &balances[..2]);
+        missing.values.remove(&balances[1]);
+        assert_ne!(
+            missing.compute_root().ok(),
+            Some(root),
+            "value missing from the set"
         );
+
+        let mut empty = balance_witness(&tree, &balances[..1]);
+        empty.values.clear();
+        assert!(empty.compute_root().is_err(), "proof without values");
+    }
+
+    #[test]
+    fn witness_proven_against_stale_root_does_not_match() {
+        let balances = sample_balances();
+        let mut tree = SMT::zero();
+        tree.update_all(balances.iter().cloned())
+            .expect("update balance tree");
+        let stale_root = tree.root();
+        let stale_witness = balance_witness(&tree, &balances[..1]);
+
+        // Another leaf changes after the proof was generated
+        tree.update_all(std::iter::once(UserBalance {
+            balance: Balance(1),
+            ..balances[2].clone()
+        }))
+        .expect("update balance tree");
+
+        assert_ne!(stale_root, tree.root());
+        assert_eq!(stale_witness.compute_root(), Ok(stale_root));
+        assert_eq!(
+            balance_witness(&tree, &balances[..1]).compute_root(),
+            Ok(tree.root()),
+            "a fresh proof of the same values should verify against the new root"
+        );
+    }
+
+    #[test]
+    fn witness_proves_deleted_keys_removed() {
+        let balances = sample_balances();
+        let mut tree = SMT::zero();
+        tree.update_all(balances.iter().cloned())
+            .expect("update balance tree");
+
+        let mut witness = balance_witness(&tree, &balances[..2]);
+        let taken = witness.take_values();
+        assert_eq!(taken.len(), 2);
+        witness.put_values([balances[0].clone()]);
+
+        tree.update_all(std::iter::once(UserBalance {
+            balance: Balance(0),
+            ..balances[1].clone()
+        }))
+        .expect("remove balance from tree");
+        assert_eq!(witness.compute_root(), Ok(tree.root()));
+
+        // A value put back lifts its tombstone
+        let mut restored = balance_witness(&tree, &balances[..2]);
+        restored.replace_values(balances[..2].iter().cloned());
+        assert!(restored.deleted.is_empty());
+
+        // Deleting every key still proves the removals
+        let mut emptied = balance_witness(&tree, &balances[..1]);
+        emptied.replace_values([]);
+        tree.update_all(std::iter::once(UserBalance {
+            balance: Balance(0),
+            ..balances[0].clone()
+        }))
+        .expect("remove balance from tree");
+        assert_eq!(emptied.compute_root(), Ok(tree.root()));
+    }
+
+    #[test]
+    fn witness_with_key_set_and_deleted_is_rejected() {
+        let balances = sample_balances();
+        let mut tree = SMT::zero();
+        tree.update_all(balances.iter().cloned())
+            .expect("update balance tree");
+
+        let mut witness = balance_witness(&tree, &balances[..2]);
+        witness.deleted.insert(balances[1].get_key());
+        assert!(witness.compute_root().is_err());
+    }
+
+    #[test]
+    fn full_state_borsh_roundtrip_keeps_trees() {
+        let state = sample_zk_state().into_orderbook_state();
+        let full_state = crate::zk::FullState::from_data(
+            &state,
+            vec![1, 2, 3],
+            LaneId::default(),
+            BlockHeight(3),
+        )
+        .expect("build full state");
+
+        let bytes = borsh::to_vec(&full_state).expect("encode full state");
+        let decoded: crate::zk::FullState = borsh::from_slice(&bytes).expect("decode full state");
+
+        assert_eq!(
+            decoded.commit().0,
+            full_state.commit().0,
+            "commitment mismatch"
+        );
+        assert_eq!(
+            borsh::to_vec(&decoded).expect("encode decoded full state"),
+            bytes,
+            "encoding is not deterministic"
+        );
+        for (symbol, tree) in &full_state.balances_mt {
+            let decoded_tree = decoded
+                .balances_mt
+                .get(symbol)
+                .unwrap_or_else(|| panic!("missing balances tree for {symbol}"));
+            let user_keys = state.balances[symbol]
+                .keys()
+                .map(|user_key| UserBalance {
+                    user_key: *user_key,
+                    balance: Balance(0),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                decoded_tree
+                    .merkle_proof(user_keys.iter())
+                    .expect("proof from decoded tree"),
+                tree.merkle_proof(user_keys.iter())
+                    .expect("proof from original tree"),
+                "balances proofs differ for {symbol}"
+            );
+        }
     }
 }
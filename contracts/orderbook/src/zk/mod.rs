@@ -2,11 +2,13 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use sdk::merkle_utils::BorshableMerkleProof;
-use sdk::{BlockHeight, LaneId, StateCommitment};
+use sdk::{BlockHeight, ContractName, LaneId, StateCommitment};
 use sha3::{Digest, Sha3_256};
 use sparse_merkle_tree::traits::Value;
 
-use crate::model::{AssetInfo, ExecuteState, Symbol, UserInfo};
+use crate::model::{
+    AssetInfo, CircuitBreakerState, ExecuteState, MarketStatus, Pair, PerpMarket, Symbol, UserInfo,
+};
 use crate::zk::order_merkle::OrderManagerWitnesses;
 use crate::zk::smt::{GetKey, SHA3_256Hasher, UserBalance};
 
@@ -37,9 +39,9 @@ pub struct ZkWitnessSet<
         + std::hash::Hash
         + Clone,
 > {
-    // TODO: we might want to use initial_values and updated_values
-    // Could we then say that all values that have not been updated will be reset to 0 (and hence removed from the tree)?
     values: HashSet<T>,
+    /// Tombstones: keys of the proof whose value was removed, committed as zero leaves
+    deleted: HashSet<H256>,
     proof: Proof,
 }
 
@@ -59,11 +61,21 @@ impl<
         match &self.proof {
             Proof::CurrentRootHash(root_hash) => Ok(*root_hash),
             Proof::Some(proof) => {
-                let leaves: Vec<(_, _)> = self
-                    .values
-                    .iter()
-                    .map(|v| (v.get_key().into(), v.to_h256()))
-                    .collect();
+                let mut leaves: Vec<(_, _)> = Vec::with_capacity(self.values.len());
+                for value in self.values.iter() {
+                    let key = value.get_key();
+                    if self.deleted.contains(&key) {
+                        return Err(format!(
+                            "Key {key:?} is both set and deleted in the witness"
+                        ));
+                    }
+                    leaves.push((key.into(), value.to_h256()));
+                }
+                leaves.extend(
+                    self.deleted
+                        .iter()
+                        .map(|key| ((*key).into(), sparse_merkle_tree::H256::zero())),
+                );
 
                 if leaves.is_empty() {
                     return Err("No leaves in merkle proof, proof should be empty".to_string());
@@ -79,6 +91,27 @@ impl<
             }
         }
     }
+
+    /// Moves the values out of the set. Their keys are deleted until the values are put back.
+    fn take_values(&mut self) -> HashSet<T> {
+        self.deleted
+            .extend(self.values.iter().map(|value| value.get_key()));
+        std::mem::take(&mut self.values)
+    }
+
+    /// Sets values, lifting the tombstones of their keys
+    fn put_values(&mut self, values: impl IntoIterator<Item = T>) {
+        for value in values {
+            self.deleted.remove(&value.get_key());
+            self.values.insert(value);
+        }
+    }
+
+    /// Replaces the values of the set: the keys missing from `values` are deleted
+    fn replace_values(&mut self, values: impl IntoIterator<Item = T>) {
+        self.take_values();
+        self.put_values(values);
+    }
 }
 
 impl<
@@ -95,13 +128,14 @@ impl<
     fn default() -> Self {
         ZkWitnessSet {
             values: HashSet::new(),
+            deleted: HashSet::new(),
             proof: Proof::CurrentRootHash(H256::zero()),
         }
     }
 }
 
 // Full state with commitment structures
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, BorshSerialize, BorshDeserialize)]
 pub struct FullState {
     pub users_info_mt: SMT<UserInfo>,
     pub balances_mt: HashMap<String, SMT<UserBalance>>,
@@ -179,18 +213,24 @@ impl FullState {
 
     pub fn commit(&self) -> StateCommitment {
         let order_manager_roots = self.order_manager_mt.commitment();
-        StateCommitment(
-            borsh::to_vec(&ParsedStateCommitment {
-                users_info_root: self.users_info_mt.root(),
-                balances_roots: self.balance_roots(),
-                assets: self.state.assets_info.iter().collect::<BTreeMap<_, _>>(),
-                order_manager_roots,
-                hashed_secret: self.hashed_secret,
-                lane_id: &self.lane_id,
-                last_block_number: &self.last_block_number,
-            })
-            .expect("Could not encode onchain state into state commitment"),
-        )
+        ParsedStateCommitment {
+            users_info_root: self.users_info_mt.root(),
+            balances_roots: self.balance_roots(),
+            assets: self.state.assets_info.iter().collect::<BTreeMap<_, _>>(),
+            pairs_status: self.state.pairs_status.iter().collect::<BTreeMap<_, _>>(),
+            circuit_breakers: self
+                .state
+                .circuit_breakers
+                .iter()
+                .collect::<BTreeMap<_, _>>(),
+            perp_markets: self.state.perp_markets.iter().collect::<BTreeMap<_, _>>(),
+            bridge_paused: self.state.bridge_paused,
+            order_manager_roots,
+            hashed_secret: self.hashed_secret,
+            lane_id: &self.lane_id,
+            last_block_number: &self.last_block_number,
+        }
+        .encode()
     }
 }
 
@@ -200,12 +240,73 @@ pub struct ParsedStateCommitment<'a> {
     pub users_info_root: H256,
     pub balances_roots: BTreeMap<Symbol, H256>,
     pub assets: BTreeMap<&'a Symbol, &'a AssetInfo>,
+    pub pairs_status: BTreeMap<&'a Pair, &'a MarketStatus>,
+    pub circuit_breakers: BTreeMap<&'a Pair, &'a CircuitBreakerState>,
+    pub perp_markets: BTreeMap<&'a Pair, &'a PerpMarket>,
+    pub bridge_paused: bool,
     pub order_manager_roots: OrderManagerRoots,
     pub hashed_secret: [u8; 32],
     pub lane_id: &'a LaneId,
     pub last_block_number: &'a BlockHeight,
 }
 
+impl ParsedStateCommitment<'_> {
+    /// Encodes the commitment. A state that uses none of the fields committed since the first
+    /// version of the contract, every pair being active, keeps the layout of that version: a
+    /// deployment upgraded with `UpgradeContract` goes on proving against the commitment it
+    /// settled before the upgrade, until a transaction uses one of these fields.
+    pub fn encode(&self) -> StateCommitment {
+        let legacy = self
+            .pairs_status
+            .values()
+            .all(|status| **status == MarketStatus::Active)
+            && self.circuit_breakers.is_empty()
+            && self.perp_markets.is_empty()
+            && !self.bridge_paused
+            && self.assets.values().all(|info| {
+                let AssetInfo {
+                    scale: _,
+                    contract_name: _,
+                    withdraw_limit,
+                    collateral,
+                    withdraw_delay,
+                } = info;
+                withdraw_limit.is_none() && collateral.is_none() && withdraw_delay.is_none()
+            });
+        let encoded = if legacy {
+            borsh::to_vec(&LegacyStateCommitment {
+                users_info_root: self.users_info_root,
+                balances_roots: &self.balances_roots,
+                assets: self
+                    .assets
+                    .iter()
+                    .map(|(symbol, info)| (*symbol, (info.scale, &info.contract_name)))
+                    .collect(),
+                order_manager_roots: &self.order_manager_roots,
+                hashed_secret: self.hashed_secret,
+                lane_id: self.lane_id,
+                last_block_number: self.last_block_number,
+            })
+        } else {
+            borsh::to_vec(self)
+        };
+        StateCommitment(encoded.expect("Could not encode onchain state into state commitment"))
+    }
+}
+
+/// Layout of the commitment in the first version of the contract, assets being committed as
+/// their `(scale, contract_name)`
+#[derive(BorshSerialize)]
+struct LegacyStateCommitment<'a> {
+    users_info_root: H256,
+    balances_roots: &'a BTreeMap<Symbol, H256>,
+    assets: BTreeMap<&'a Symbol, (u64, &'a ContractName)>,
+    order_manager_roots: &'a OrderManagerRoots,
+    hashed_secret: [u8; 32],
+    lane_id: &'a LaneId,
+    last_block_number: &'a BlockHeight,
+}
+
 #[derive(Debug, Clone, BorshDeserialize, BorshSerialize)]
 pub struct ZkVmState {
     pub users_info: ZkWitnessSet<UserInfo>,
@@ -215,51 +316,8 @@ pub struct ZkVmState {
     pub last_block_number: BlockHeight,
     pub order_manager: OrderManagerWitnesses,
     pub assets: HashMap<Symbol, AssetInfo>,
-}
-
-/// impl of functions for state management
-impl borsh::BorshSerialize for FullState {
-    fn serialize<W: std::io::Write>(
-        &self,
-        _writer: &mut W,
-    ) -> std::result::Result<(), std::io::Error> {
-        panic!("FullState::serialize: todo!()")
-    }
-}
-
-impl borsh::BorshDeserialize for FullState {
-    fn deserialize_reader<R: std::io::Read>(
-        _reader: &mut R,
-    ) -> std::result::Result<Self, std::io::Error> {
-        panic!("FullState::deserialize: todo!()")
-    }
-}
-
-impl Clone for FullState {
-    fn clone(&self) -> Self {
-        let user_info_root = *self.users_info_mt.root();
-        let user_info_store = self.users_info_mt.store().clone();
-        let users_info_mt = SMT::from_store(user_info_root.into(), user_info_store);
-
-        let mut balances_mt = HashMap::new();
-        for (symbol, tree) in &self.balances_mt {
-            let root = *tree.root();
-            let store = tree.store().clone();
-            let new_tree = SMT::from_store(root.into(), store);
-            balances_mt.insert(symbol.clone(), new_tree);
-        }
-
-        let order_manager_mt = OrderManagerMerkles::from_order_manager(&self.state.order_manager)
-            .expect("clone order manager merkle trees");
-
-        Self {
-            users_info_mt,
-            balances_mt,
-            order_manager_mt,
-            state: self.state.clone(),
-            hashed_secret: self.hashed_secret,
-            lane_id: self.lane_id.clone(),
-            last_block_number: self.last_block_number,
-        }
-    }
+    pub pairs_status: HashMap<Pair, MarketStatus>,
+    pub circuit_breakers: HashMap<Pair, CircuitBreakerState>,
+    pub perp_markets: HashMap<Pair, PerpMarket>,
+    pub bridge_paused: bool,
 }
@@ -1,4 +1,4 @@
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use sdk::merkle_utils::BorshableMerkleProof;
@@ -6,7 +6,7 @@ use sparse_merkle_tree::traits::Value;
 
 use crate::{
     model::{Order, OrderId, Pair},
-    order_manager::OrderManager,
+    order_manager::{OrderManager, PriceLevel},
     zk::{Proof, ZkWitnessSet},
 };
 
@@ -25,7 +25,7 @@ pub struct OrderPriceLevel {
 }
 
 impl OrderPriceLevel {
-    pub fn from_queue(pair: &Pair, price: u64, queue: &VecDeque<OrderId>) -> Self {
+    pub fn from_queue(pair: &Pair, price: u64, queue: &PriceLevel) -> Self {
         OrderPriceLevel {
             pair: pair.clone(),
             price,
@@ -49,7 +49,7 @@ pub struct OrderManagerWitnesses {
     pub orders_owner: HashMap<OrderId, H256>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
 pub struct OrderManagerMerkles {
     pub orders: SMT<Order>,
     pub bid_orders: SMT<OrderPriceLevel>,
@@ -161,12 +161,12 @@ impl OrderManagerWitnesses {
 
         for level in &self.bid_orders.values {
             let entry = manager.bid_orders.entry(level.pair.clone()).or_default();
-            entry.insert(level.price, VecDeque::from(level.order_ids.clone()));
+            entry.insert(level.price, PriceLevel::from(level.order_ids.clone()));
         }
 
         for level in &self.ask_orders.values {
             let entry = manager.ask_orders.entry(level.pair.clone()).or_default();
-            entry.insert(level.price, VecDeque::from(level.order_ids.clone()));
+            entry.insert(level.price, PriceLevel::from(level.order_ids.clone()));
         }
 
         manager.orders_owner = self.orders_owner.clone();
@@ -176,7 +176,7 @@ impl OrderManagerWitnesses {
 }
 
 pub fn collect_price_levels(
-    side_map: &HashMap<Pair, BTreeMap<u64, VecDeque<OrderId>>>,
+    side_map: &HashMap<Pair, BTreeMap<u64, PriceLevel>>,
 ) -> HashSet<OrderPriceLevel> {
     let mut levels = HashSet::new();
     for (pair, price_map) in side_map {
@@ -187,7 +187,7 @@ pub fn collect_price_levels(
     levels
 }
 
-fn build_witness<T>(
+pub(super) fn build_witness<T>(
     tree: &SMT<T>,
     values: HashSet<T>,
     err_context: &str,
@@ -206,6 +206,7 @@ where
     if values.is_empty() {
         return Ok(ZkWitnessSet {
             values: HashSet::new(),
+            deleted: HashSet::new(),
             proof: Proof::CurrentRootHash(tree.root()),
         });
     }
@@ -221,6 +222,7 @@ where
 
     Ok(ZkWitnessSet {
         values: set,
+        deleted: HashSet::new(),
         proof: Proof::Some(BorshableMerkleProof(proof)),
     })
 }
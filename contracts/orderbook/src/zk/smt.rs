@@ -4,12 +4,15 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use sha3::{Digest, Sha3_256};
 use sparse_merkle_tree::{
     default_store::DefaultStore,
-    traits::{Hasher, Value},
+    merge::MergeValue,
+    traits::{Hasher, StoreWriteOps, Value},
+    tree::{BranchKey, BranchNode},
     SparseMerkleTree, H256,
 };
 
 use crate::{
-    model::{Balance, Order, OrderSide, OrderType, UserInfo},
+    model::{Balance, FeeTier, Order, OrderSide, OrderType, UserInfo},
+    utils::{split_sub_account, sub_account_identity, SUB_ACCOUNT_SEPARATOR},
     zk::order_merkle::OrderPriceLevel,
 };
 
@@ -67,6 +70,57 @@ impl UserInfo {
             salt,
             nonce: 0,
             session_keys: Vec::new(),
+            session_key_scopes: Vec::new(),
+            fee_tier: FeeTier::default(),
+            withdrawal_windows: Vec::new(),
+            pending_withdrawals: Vec::new(),
+            positions: Vec::new(),
+            debts: Vec::new(),
+            sub_account: false,
+        }
+    }
+
+    /// Sub-account `name` of `parent`, sharing its salt
+    pub fn new_sub_account(parent: &UserInfo, name: &str) -> Self {
+        UserInfo {
+            sub_account: true,
+            ..UserInfo::new(
+                sub_account_identity(&parent.user, name),
+                parent.salt.clone(),
+            )
+        }
+    }
+
+    /// Encoding hashed in the users tree. A user that has none of the fields added after
+    /// `session_keys` is encoded as before they existed, so that the leaves of the users of a
+    /// deployment, and its commitment, do not change when it is upgraded. Borsh encodings are
+    /// self-delimiting: the full encoding of a user extends its first four fields, so it never
+    /// equals the shorter encoding of another user.
+    pub fn leaf_encoding(&self) -> Vec<u8> {
+        let UserInfo {
+            user,
+            salt,
+            nonce,
+            session_keys,
+            session_key_scopes,
+            fee_tier,
+            withdrawal_windows,
+            positions,
+            debts,
+            pending_withdrawals,
+            sub_account,
+        } = self;
+        let legacy = !sub_account
+            && session_key_scopes.is_empty()
+            && *fee_tier == FeeTier::default()
+            && withdrawal_windows.is_empty()
+            && positions.is_empty()
+            && debts.is_empty()
+            && pending_withdrawals.is_empty();
+        if legacy {
+            borsh::to_vec(&(user, salt, nonce, session_keys)).unwrap()
+        } else {
+            borsh::to_vec(self).unwrap()
         }
     }
 }
@@ -78,8 +132,23 @@ pub trait GetKey {
 impl GetKey for UserInfo {
     fn get_key(&self) -> BorshableH256 {
         let mut hasher = Sha3_256::new();
-        hasher.update(self.user.as_bytes());
-        hasher.update(&self.salt);
+        match split_sub_account(&self.user).filter(|_| self.sub_account) {
+            // Sub-accounts share the salt of their parent, and are derived from its key
+            Some((parent, name)) => {
+                let parent_key: [u8; 32] = Sha3_256::new()
+                    .chain_update(parent.as_bytes())
+                    .chain_update(&self.salt)
+                    .finalize()
+                    .into();
+                hasher.update(parent_key);
+                hasher.update([SUB_ACCOUNT_SEPARATOR as u8]);
+                hasher.update(name.as_bytes());
+            }
+            None => {
+                hasher.update(self.user.as_bytes());
+                hasher.update(&self.salt);
+            }
+        }
         let result = hasher.finalize();
         let mut h = [0u8; 32];
         h.copy_from_slice(&result);
@@ -99,7 +168,7 @@ impl Value for UserInfo {
             return H256::zero();
         }
 
-        let serialized = borsh::to_vec(self).unwrap();
+        let serialized = self.leaf_encoding();
         let mut hasher = Sha3_256::new();
         hasher.update(&serialized);
         let result = hasher.finalize();
@@ -114,6 +183,13 @@ impl Value for UserInfo {
             salt: Vec::new(),
             nonce: 0,
             session_keys: Vec::new(),
+            session_key_scopes: Vec::new(),
+            fee_tier: FeeTier::default(),
+            withdrawal_windows: Vec::new(),
+            pending_withdrawals: Vec::new(),
+            positions: Vec::new(),
+            debts: Vec::new(),
+            sub_account: false,
         }
     }
 }
@@ -367,6 +443,122 @@ where
     }
 }
 
+/// Copies the nodes of the tree instead of rebuilding them from its leaves
+impl<T> Clone for SMT<T>
+where
+    T: Value + Clone,
+{
+    fn clone(&self) -> Self {
+        SMT::from_store(self.root(), self.store().clone())
+    }
+}
+
+/// Encodes the root and the whole store, so that a tree is restored without rebuilding it from
+/// its leaves. Nodes are written in key order, the encoding of a tree is deterministic.
+impl<T: Value + Clone> BorshSerialize for SMT<T> {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.root().serialize(writer)?;
+
+        let mut branches = self.store().branches_map().iter().collect::<Vec<_>>();
+        branches.sort_by(|(a, _), (b, _)| a.cmp(b));
+        (branches.len() as u64).serialize(writer)?;
+        for (key, node) in branches {
+            key.height.serialize(writer)?;
+            BorshableH256(key.node_key).serialize(writer)?;
+            serialize_merge_value(&node.left, writer)?;
+            serialize_merge_value(&node.right, writer)?;
+        }
+
+        let mut leaves = self.store().leaves_map().iter().collect::<Vec<_>>();
+        leaves.sort_by(|(a, _), (b, _)| a.cmp(b));
+        (leaves.len() as u64).serialize(writer)?;
+        for (key, leaf) in leaves {
+            BorshableH256(*key).serialize(writer)?;
+            BorshableH256(*leaf).serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Value + Clone> BorshDeserialize for SMT<T> {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        let root = BorshableH256::deserialize_reader(reader)?;
+        let mut store = DefaultStore::<H256>::default();
+
+        for _ in 0..u64::deserialize_reader(reader)? {
+            let height = u8::deserialize_reader(reader)?;
+            let node_key = BorshableH256::deserialize_reader(reader)?.0;
+            let node = BranchNode {
+                left: deserialize_merge_value(reader)?,
+                right: deserialize_merge_value(reader)?,
+            };
+            store
+                .insert_branch(BranchKey::new(height, node_key), node)
+                .map_err(|e| std::io::Error::other(format!("Inserting SMT branch: {e}")))?;
+        }
+
+        for _ in 0..u64::deserialize_reader(reader)? {
+            let key = BorshableH256::deserialize_reader(reader)?.0;
+            let leaf = BorshableH256::deserialize_reader(reader)?.0;
+            store
+                .insert_leaf(key, leaf)
+                .map_err(|e| std::io::Error::other(format!("Inserting SMT leaf: {e}")))?;
+        }
+
+        Ok(SMT::from_store(root, store))
+    }
+}
+
+fn serialize_merge_value<W: std::io::Write>(
+    value: &MergeValue,
+    writer: &mut W,
+) -> std::io::Result<()> {
+    match value {
+        MergeValue::Value(value) => {
+            0u8.serialize(writer)?;
+            BorshableH256(*value).serialize(writer)
+        }
+        MergeValue::MergeWithZero {
+            base_node,
+            zero_bits,
+            zero_count,
+        } => {
+            1u8.serialize(writer)?;
+            BorshableH256(*base_node).serialize(writer)?;
+            BorshableH256(*zero_bits).serialize(writer)?;
+            zero_count.serialize(writer)
+        }
+        MergeValue::ShortCut { key, value, height } => {
+            2u8.serialize(writer)?;
+            BorshableH256(*key).serialize(writer)?;
+            BorshableH256(*value).serialize(writer)?;
+            height.serialize(writer)
+        }
+    }
+}
+
+fn deserialize_merge_value<R: std::io::Read>(reader: &mut R) -> std::io::Result<MergeValue> {
+    match u8::deserialize_reader(reader)? {
+        0 => Ok(MergeValue::Value(
+            BorshableH256::deserialize_reader(reader)?.0,
+        )),
+        1 => Ok(MergeValue::MergeWithZero {
+            base_node: BorshableH256::deserialize_reader(reader)?.0,
+            zero_bits: BorshableH256::deserialize_reader(reader)?.0,
+            zero_count: u8::deserialize_reader(reader)?,
+        }),
+        2 => Ok(MergeValue::ShortCut {
+            key: BorshableH256::deserialize_reader(reader)?.0,
+            value: BorshableH256::deserialize_reader(reader)?.0,
+            height: u8::deserialize_reader(reader)?,
+        }),
+        tag => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Invalid SMT merge value tag {tag}"),
+        )),
+    }
+}
+
 // Custom SHA3_256Hasher implementation
 #[derive(Default, Debug)]
 pub struct SHA3_256Hasher(Sha3_256);
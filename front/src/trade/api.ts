@@ -54,12 +54,10 @@ export interface ApiOrder {
 }
 
 export interface PaginationInfo {
-    page: number;
     limit: number;
-    total: number;
-    total_pages: number;
-    has_next: boolean;
-    has_prev: boolean;
+    // Cursor of the next page, null on the last one
+    next_cursor: string | null;
+    has_more: boolean;
 }
 
 interface PaginatedApiOrdersResponse {
@@ -68,9 +66,9 @@ interface PaginatedApiOrdersResponse {
 }
 
 export interface PaginationParams {
-    page?: number;
+    cursor?: string;
     limit?: number;
-    sort_by?: string;
+    // Order of the commits that created the orders, latest first by default
     sort_order?: "asc" | "desc";
 }
 
@@ -208,9 +206,8 @@ export async function fetchOrdersForInstrument(
 
     // Add pagination parameters to URL if provided
     if (pagination) {
-        if (pagination.page) url.searchParams.set("page", pagination.page.toString());
+        if (pagination.cursor) url.searchParams.set("cursor", pagination.cursor);
         if (pagination.limit) url.searchParams.set("limit", pagination.limit.toString());
-        if (pagination.sort_by) url.searchParams.set("sort_by", pagination.sort_by);
         if (pagination.sort_order) url.searchParams.set("sort_order", pagination.sort_order);
     }
 
@@ -247,7 +244,7 @@ export async function fetchOrdersForInstrument(
 }
 
 export async function fetchFills(): Promise<Fill[]> {
-    const response = await fetch(`${API_BASE_URL}/api/user/trades`, {
+    const response = await fetch(`${API_BASE_URL}/api/user/fills`, {
         headers: {
             "Content-Type": "application/json",
             ...getAuthHeaders(),
@@ -258,9 +255,10 @@ export async function fetchFills(): Promise<Fill[]> {
         throw new Error(`Failed to fetch fills: ${response.status} ${response.statusText}`);
     }
 
-    const data: { trades: ApiTrade[] } = await response.json();
+    // Fills carry the side of the order of the user
+    const data: { data: ApiTrade[] } = await response.json();
 
-    return data.trades.map((trade) => transformTrade(trade));
+    return data.data.map((trade) => transformTrade(trade));
 }
 
 export async function fetchBalances(): Promise<Balance[]> {
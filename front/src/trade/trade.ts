@@ -265,10 +265,12 @@ export const activityState = reactive({
     ordersPagination: null as PaginationInfo | null,
     ordersCurrentPage: 1,
     ordersPageSize: 20,
-    ordersSortBy: "created_at",
     ordersSortOrder: "desc" as "asc" | "desc",
 });
 
+// Cursors the pages of orders up to the current one were fetched with, undefined for the first
+const ordersPageCursors: (string | undefined)[] = [undefined];
+
 const applyOrderbookTicksPreference = () => {
     const selectedInstrument = instrumentsState.selected;
     if (!selectedInstrument) {
@@ -291,6 +293,7 @@ watch(
         activityState.orders = [];
         activityState.ordersPagination = null;
         activityState.ordersCurrentPage = 1;
+        ordersPageCursors.splice(0, ordersPageCursors.length, undefined);
 
         if (!symbol) {
             return;
@@ -396,7 +399,7 @@ export function useOrderFormState() {
 }
 
 // Functions to handle orders pagination
-export async function loadOrdersPage(page: number, pageSize?: number, sortBy?: string, sortOrder?: "asc" | "desc") {
+async function loadOrdersPage(cursor: string | undefined) {
     if (!instrumentsAndAssets.isLoaded.value) {
         throw new Error("Instruments not loaded yet");
     }
@@ -405,18 +408,11 @@ export async function loadOrdersPage(page: number, pageSize?: number, sortBy?: s
     }
 
     const pagination: PaginationParams = {
-        page,
-        limit: pageSize || activityState.ordersPageSize,
-        sort_by: sortBy || activityState.ordersSortBy,
-        sort_order: sortOrder || activityState.ordersSortOrder,
+        cursor,
+        limit: activityState.ordersPageSize,
+        sort_order: activityState.ordersSortOrder,
     };
 
-    // Update state
-    activityState.ordersCurrentPage = page;
-    if (pageSize) activityState.ordersPageSize = pageSize;
-    if (sortBy) activityState.ordersSortBy = sortBy;
-    if (sortOrder) activityState.ordersSortOrder = sortOrder;
-
     // Fetch new data for the selected instrument
     const parts = instrumentsState.selected.symbol.split("/");
     if (parts.length !== 2) throw new Error("Invalid instrument symbol format");
@@ -430,19 +426,29 @@ export async function loadOrdersPage(page: number, pageSize?: number, sortBy?: s
 }
 
 export async function nextOrdersPage() {
-    if (activityState.ordersPagination?.has_next) {
-        await loadOrdersPage(activityState.ordersCurrentPage + 1);
+    const cursor = activityState.ordersPagination?.next_cursor;
+    if (cursor) {
+        ordersPageCursors.push(cursor);
+        activityState.ordersCurrentPage = ordersPageCursors.length;
+        await loadOrdersPage(cursor);
     }
 }
 
 export async function prevOrdersPage() {
-    if (activityState.ordersPagination?.has_prev) {
-        await loadOrdersPage(activityState.ordersCurrentPage - 1);
+    if (ordersPageCursors.length > 1) {
+        ordersPageCursors.pop();
+        activityState.ordersCurrentPage = ordersPageCursors.length;
+        await loadOrdersPage(ordersPageCursors[ordersPageCursors.length - 1]);
     }
 }
 
-export async function changeOrdersSorting(sortBy: string, sortOrder: "asc" | "desc") {
-    await loadOrdersPage(1, activityState.ordersPageSize, sortBy, sortOrder);
+// Restarts from the first page, as the cursors of the previous pages only hold for their order
+export async function changeOrdersPaging(sortOrder: "asc" | "desc", pageSize?: number) {
+    activityState.ordersSortOrder = sortOrder;
+    if (pageSize) activityState.ordersPageSize = pageSize;
+    ordersPageCursors.splice(0, ordersPageCursors.length, undefined);
+    activityState.ordersCurrentPage = 1;
+    await loadOrdersPage(undefined);
 }
 
 export async function submitOrder() {
@@ -1,39 +1,25 @@
-use anyhow::{Context, Result};
-use k256::{
-    ecdsa::{signature::DigestSigner, Signature, SigningKey},
-    SecretKey,
-};
-use sha3::{Digest, Sha3_256};
+use anyhow::Result;
+use hyliquid_client::SessionKey;
+use orderbook::model::Order;
 
 /// User authentication context containing identity and cryptographic keys
 #[derive(Clone)]
 pub struct UserAuth {
     pub identity: String,
-    pub signing_key: SigningKey,
+    pub session_key: SessionKey,
     pub public_key_hex: String,
 }
 
 impl UserAuth {
     /// Create a new UserAuth from an identity string
-    /// This replicates the logic from tx_sender.rs
+    /// The session key is derived from the identity, as tx_sender does
     pub fn new(identity: &str) -> Result<Self> {
-        // Generate keypair from identity (deterministic)
-        let mut hasher = Sha3_256::new();
-        hasher.update(identity.as_bytes());
-        let derived_key = hasher.finalize();
-        let private_key_bytes = derived_key.to_vec();
-
-        let secret_key = SecretKey::from_slice(&private_key_bytes)
-            .context("Invalid private key derived from identity")?;
-        let signing_key = SigningKey::from(secret_key);
-
-        let public_key = signing_key.verifying_key();
-        let public_key_bytes = public_key.to_encoded_point(false).as_bytes().to_vec();
-        let public_key_hex = hex::encode(public_key_bytes);
+        let session_key = SessionKey::from_seed(identity)?;
+        let public_key_hex = session_key.public_key_hex();
 
         Ok(UserAuth {
             identity: identity.to_string(),
-            signing_key,
+            session_key,
             public_key_hex,
         })
     }
@@ -41,33 +27,32 @@ impl UserAuth {
     /// Create a signature for the given data
     /// Format matches tx_sender: SHA3-256 hash of the data, then ECDSA sign
     pub fn sign(&self, data: &str) -> Result<String> {
-        let mut hasher = Sha3_256::new();
-        hasher.update(data.as_bytes());
-
-        let signature: Signature = self.signing_key.sign_digest(hasher);
-        Ok(hex::encode(signature.to_bytes()))
+        Ok(self.session_key.sign(data))
     }
 
     /// Create signature for create_order action
     /// Format: {identity}:{nonce}:create_order:{order_id}
-    pub fn sign_create_order(&self, nonce: u32, order_id: &str) -> Result<String> {
-        let data = format!("{}:{}:create_order:{}", self.identity, nonce, order_id);
-        self.sign(&data)
+    pub fn sign_create_order(&self, nonce: u32, order: &Order) -> Result<String> {
+        Ok(self
+            .session_key
+            .sign_create_order(&self.identity, nonce, order))
     }
 
     /// Create signature for cancel action
     /// Format: {identity}:{nonce}:cancel:{order_id}
     pub fn sign_cancel(&self, nonce: u32, order_id: &str) -> Result<String> {
-        let data = format!("{}:{}:cancel:{}", self.identity, nonce, order_id);
-        self.sign(&data)
+        Ok(self
+            .session_key
+            .sign_cancel(&self.identity, nonce, order_id))
     }
 
     #[allow(dead_code)]
     /// Create signature for withdraw action
     /// Format: {identity}:{nonce}:withdraw:{symbol}:{amount}
     pub fn sign_withdraw(&self, nonce: u32, symbol: &str, amount: u64) -> Result<String> {
-        let data = format!("{}:{}:withdraw:{}:{}", self.identity, nonce, symbol, amount);
-        self.sign(&data)
+        Ok(self
+            .session_key
+            .sign_withdraw(&self.identity, nonce, symbol, amount))
     }
 }
 
@@ -100,7 +85,15 @@ mod tests {
     #[test]
     fn test_create_order_signature() {
         let auth = UserAuth::new("test_user").unwrap();
-        let sig = auth.sign_create_order(0, "order_123").unwrap();
+        let order = crate::http_client::build_order(
+            "order_123".to_string(),
+            orderbook::model::OrderSide::Bid,
+            orderbook::model::OrderType::Limit,
+            Some(100),
+            ("BTC".to_string(), "USDC".to_string()),
+            1,
+        );
+        let sig = auth.sign_create_order(0, &order).unwrap();
         assert!(!sig.is_empty());
     }
 }
@@ -21,6 +21,13 @@ pub struct Config {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub base_url: String,
+    /// Admin secret of the server, used to create the pair
+    #[serde(default = "default_admin_secret")]
+    pub admin_secret: String,
+}
+
+fn default_admin_secret() -> String {
+    "admin_secret".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -207,6 +214,9 @@ impl Config {
         if let Ok(val) = std::env::var("BASE_URL") {
             config.server.base_url = val;
         }
+        if let Ok(val) = std::env::var("ADMIN_SECRET") {
+            config.server.admin_secret = val;
+        }
         if let Ok(val) = std::env::var("BASE_ASSET") {
             config.instrument.base_asset = val;
         }
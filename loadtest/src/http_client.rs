@@ -65,24 +65,16 @@ pub enum OrderStatus {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct PaginatedUserOrders {
-    pub data: Vec<ApiOrder>,
+pub struct Paginated<T> {
+    pub data: Vec<T>,
     pub pagination: Pagination,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Pagination {
-    pub page: u32,
     pub limit: u32,
-    pub total: u32,
-    pub total_pages: u32,
-    pub has_next: bool,
-    pub has_prev: bool,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserTrades {
-    pub trades: Vec<ApiTrade>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -136,7 +128,7 @@ impl OrderbookClient {
         &self,
         user: &mut GooseUser,
         auth: &UserAuth,
-    ) -> Result<PaginatedUserOrders, Box<TransactionError>> {
+    ) -> Result<Paginated<ApiOrder>, Box<TransactionError>> {
         let path = "/api/user/orders".to_string();
 
         let builder = user
@@ -158,7 +150,7 @@ impl OrderbookClient {
             }));
         }
 
-        let orders = response.json::<PaginatedUserOrders>().await?;
+        let orders = response.json::<Paginated<ApiOrder>>().await?;
 
         Ok(orders)
     }
@@ -168,7 +160,7 @@ impl OrderbookClient {
         &self,
         user: &mut GooseUser,
         auth: &UserAuth,
-    ) -> Result<UserTrades, Box<TransactionError>> {
+    ) -> Result<Paginated<ApiTrade>, Box<TransactionError>> {
         let path = "/api/user/trades".to_string();
 
         let builder = user
@@ -190,7 +182,7 @@ impl OrderbookClient {
             }));
         }
 
-        let trades = response.json::<UserTrades>().await?;
+        let trades = response.json::<Paginated<ApiTrade>>().await?;
 
         Ok(trades)
     }
@@ -383,15 +375,16 @@ impl OrderbookClient {
     pub async fn create_pair(
         &self,
         user: &mut GooseUser,
-        auth: &UserAuth,
+        admin_secret: &str,
         pair: (String, String),
     ) -> TransactionResult {
-        let path = "/create_pair";
+        let path = "/admin/create_pair";
 
         let request_body = {
             let base_symbol = pair.0.clone();
             let quote_symbol = pair.1.clone();
             CreatePairRequest {
+                secret: admin_secret.to_string(),
                 base_contract: base_symbol.to_lowercase(),
                 quote_contract: quote_symbol.to_lowercase(),
             }
@@ -402,7 +395,6 @@ impl OrderbookClient {
         // Build custom request with headers
         let builder = user
             .get_request_builder(&GooseMethod::Post, path)?
-            .header("x-identity", &auth.identity)
             .header("Content-Type", "application/json")
             .body(body);
 
@@ -83,7 +83,7 @@ async fn place_bid_orders_transaction(user: &mut GooseUser) -> TransactionResult
         );
 
         // Sign the order
-        let signature = user_state.auth.sign_create_order(nonce, &order_id).unwrap();
+        let signature = user_state.auth.sign_create_order(nonce, &order).unwrap();
 
         // Send order
         let mut err = false;
@@ -175,7 +175,7 @@ async fn place_ask_orders_transaction(user: &mut GooseUser) -> TransactionResult
         );
 
         // Sign the order
-        let signature = user_state.auth.sign_create_order(nonce, &order_id).unwrap();
+        let signature = user_state.auth.sign_create_order(nonce, &order).unwrap();
 
         // Send order
         match client
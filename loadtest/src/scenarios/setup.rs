@@ -68,13 +68,12 @@ async fn create_pair_transaction(user: &mut GooseUser) -> TransactionResult {
     };
 
     if user.weighted_users_index == 0 {
-        let user_state = user.get_session_data::<UserState>().unwrap();
-        let user_auth = user_state.auth.clone();
-
         info!("Creating trading pair: {}", config.instrument_symbol());
 
         let client = OrderbookClient::new(&config).unwrap();
-        let _ = client.create_pair(user, &user_auth, config.pair()).await; // Ignore errors (pair might exist)
+        let _ = client
+            .create_pair(user, &config.server.admin_secret, config.pair())
+            .await; // Ignore errors (pair might exist)
     }
 
     Ok(())
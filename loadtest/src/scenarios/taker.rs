@@ -148,7 +148,7 @@ async fn place_crossing_order(
             config.pair(),
             quantity,
         );
-        let signature = auth.sign_create_order(nonce, &order_id).unwrap();
+        let signature = auth.sign_create_order(nonce, &order).unwrap();
         (order_id, signature, auth, order)
     };
 
@@ -224,7 +224,7 @@ async fn place_self_cross_pair(
             config.pair(),
             quantity,
         );
-        let signature = auth.sign_create_order(nonce, &order_id).unwrap();
+        let signature = auth.sign_create_order(nonce, &order).unwrap();
         (order_id, signature, auth, order)
     };
 
@@ -261,7 +261,7 @@ async fn place_self_cross_pair(
             config.pair(),
             quantity,
         );
-        let signature = auth.sign_create_order(nonce, &order_id).unwrap();
+        let signature = auth.sign_create_order(nonce, &order).unwrap();
         (order_id, signature, auth, order)
     };
 
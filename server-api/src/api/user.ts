@@ -1,12 +1,13 @@
 /**
- * Balance endpoints
+ * User endpoints: balances, and paginated orders, trades, fills and balance history
  */
 
 import { Elysia } from "elysia";
 import { AssetService, UserService } from "../services";
 import { authMiddleware, AuthHeaders } from "../middleware/auth";
 import { CustomError } from "../middleware/error-handler";
-import { PaginationQuery } from "../types";
+import { CursorQuery, OrderSide, OrderStatus } from "../types";
+import { decodeCursor } from "../database/pagination";
 
 export const userRoutes = (
   userService: UserService,
@@ -32,17 +33,11 @@ export const userRoutes = (
     .get(
       "/api/user/orders",
       async ({ auth, query }: { auth: AuthHeaders; query: any }) => {
-        const pagination: PaginationQuery = {
-          page: query.page ? parseInt(query.page.toString()) : undefined,
-          limit: query.limit ? parseInt(query.limit.toString()) : undefined,
-          sort_by: query.sort_by?.toString(),
-          sort_order: query.sort_order?.toString() as "asc" | "desc",
-        };
-        const orders = await userService.getOrdersPaginated(
+        return userService.getOrders(
           auth.user,
-          pagination
+          { status: orderStatus(query), side: orderSide(query) },
+          cursorQuery(query)
         );
-        return orders;
       }
     )
     .get(
@@ -53,58 +48,165 @@ export const userRoutes = (
         query,
       }: {
         auth: AuthHeaders;
-        params: { baseAssetSymbol: string; quoteAssetSymbol: string };
+        params: PairParams;
         query: any;
       }) => {
-        const symbol = assetService.getInstrumentSymbol(
-          params.baseAssetSymbol,
-          params.quoteAssetSymbol
+        return userService.getOrders(
+          auth.user,
+          {
+            instrument_id: await instrumentId(assetService, params),
+            status: orderStatus(query),
+            side: orderSide(query),
+          },
+          cursorQuery(query)
         );
-        const instrumentId = await assetService.getInstrumentId(symbol);
-        if (!instrumentId) {
-          throw new CustomError(`Instrument not found: ${symbol}`, 404);
-        }
-
-        const pagination: PaginationQuery = {
-          page: query.page ? parseInt(query.page.toString()) : undefined,
-          limit: query.limit ? parseInt(query.limit.toString()) : undefined,
-          sort_by: query.sort_by?.toString(),
-          sort_order: query.sort_order?.toString() as "asc" | "desc",
-        };
-        const orders = await userService.getOrdersByPairPaginated(
+      }
+    )
+    .get(
+      "/api/user/trades",
+      async ({ auth, query }: { auth: AuthHeaders; query: any }) => {
+        return userService.getTrades(
           auth.user,
-          instrumentId,
-          pagination
+          { side: orderSide(query) },
+          cursorQuery(query)
         );
-        return orders;
       }
     )
-    .get("/api/user/trades", async ({ auth }: { auth: AuthHeaders }) => {
-      const trades = await userService.getTrades(auth.user);
-      return trades;
-    })
     .get(
       "/api/user/trades/:baseAssetSymbol/:quoteAssetSymbol",
       async ({
         auth,
         params,
+        query,
       }: {
         auth: AuthHeaders;
-        params: { baseAssetSymbol: string; quoteAssetSymbol: string };
+        params: PairParams;
+        query: any;
       }) => {
-        const symbol = assetService.getInstrumentSymbol(
-          params.baseAssetSymbol,
-          params.quoteAssetSymbol
+        return userService.getTrades(
+          auth.user,
+          {
+            instrument_id: await instrumentId(assetService, params),
+            side: orderSide(query),
+          },
+          cursorQuery(query)
         );
-        const instrumentId = await assetService.getInstrumentId(symbol);
-        if (!instrumentId) {
-          throw new CustomError(`Instrument not found: ${symbol}`, 404);
-        }
-        const trades = await userService.getTradesByPair(
+      }
+    )
+    .get(
+      "/api/user/fills",
+      async ({ auth, query }: { auth: AuthHeaders; query: any }) => {
+        return userService.getFills(
           auth.user,
-          instrumentId
+          { side: orderSide(query) },
+          cursorQuery(query)
+        );
+      }
+    )
+    .get(
+      "/api/user/fills/:baseAssetSymbol/:quoteAssetSymbol",
+      async ({
+        auth,
+        params,
+        query,
+      }: {
+        auth: AuthHeaders;
+        params: PairParams;
+        query: any;
+      }) => {
+        return userService.getFills(
+          auth.user,
+          {
+            instrument_id: await instrumentId(assetService, params),
+            side: orderSide(query),
+          },
+          cursorQuery(query)
+        );
+      }
+    )
+    .get(
+      "/api/user/balance_history",
+      async ({ auth, query }: { auth: AuthHeaders; query: any }) => {
+        return userService.getBalanceHistory(
+          auth.user,
+          { symbol: query.symbol?.toString() },
+          cursorQuery(query)
         );
-        return trades;
       }
     );
 };
+
+interface PairParams {
+  baseAssetSymbol: string;
+  quoteAssetSymbol: string;
+}
+
+async function instrumentId(
+  assetService: AssetService,
+  params: PairParams
+): Promise<number> {
+  const symbol = assetService.getInstrumentSymbol(
+    params.baseAssetSymbol,
+    params.quoteAssetSymbol
+  );
+  const instrumentId = await assetService.getInstrumentId(symbol);
+  if (!instrumentId) {
+    throw new CustomError(`Instrument not found: ${symbol}`, 404);
+  }
+  return instrumentId;
+}
+
+/**
+ * Cursor, limit, order and commit range of a paginated query
+ */
+function cursorQuery(query: any): CursorQuery {
+  const cursor = query.cursor?.toString();
+  if (cursor && !decodeCursor(cursor)) {
+    throw new CustomError(`Invalid cursor: ${cursor}`, 400);
+  }
+  const sortOrder = query.sort_order?.toString();
+  if (sortOrder && sortOrder !== "asc" && sortOrder !== "desc") {
+    throw new CustomError(`Invalid sort order: ${sortOrder}`, 400);
+  }
+  return {
+    cursor,
+    limit: integerParam(query, "limit"),
+    sort_order: sortOrder,
+    from_commit: integerParam(query, "from_commit"),
+    to_commit: integerParam(query, "to_commit"),
+  };
+}
+
+function integerParam(query: any, name: string): number | undefined {
+  if (query[name] === undefined) {
+    return undefined;
+  }
+  const value = parseInt(query[name].toString(), 10);
+  if (!Number.isSafeInteger(value) || value < 0) {
+    throw new CustomError(`Invalid ${name}: ${query[name]}`, 400);
+  }
+  return value;
+}
+
+function orderSide(query: any): OrderSide | undefined {
+  return enumParam(query, "side", Object.values(OrderSide));
+}
+
+function orderStatus(query: any): OrderStatus | undefined {
+  return enumParam(query, "status", Object.values(OrderStatus));
+}
+
+function enumParam<T extends string>(
+  query: any,
+  name: string,
+  values: T[]
+): T | undefined {
+  const value = query[name]?.toString() as T | undefined;
+  if (value !== undefined && !values.includes(value)) {
+    throw new CustomError(
+      `Invalid ${name}: ${value}, expected one of ${values.join(", ")}`,
+      400
+    );
+  }
+  return value;
+}
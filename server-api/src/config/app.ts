@@ -8,6 +8,8 @@ export interface AppConfig {
   serverBaseUrl: string;
   contractName: string;
   wsPollingIntervalMs: number;
+  wsHeartbeatIntervalMs: number;
+  wsReplayBufferSize: number;
   nodeEnv: string;
 }
 
@@ -21,6 +23,14 @@ export function getAppConfig(): AppConfig {
       process.env.WS_POLLING_INTERVAL_MS || "1000",
       10
     ),
+    wsHeartbeatIntervalMs: parseInt(
+      process.env.WS_HEARTBEAT_INTERVAL_MS || "15000",
+      10
+    ),
+    wsReplayBufferSize: parseInt(
+      process.env.WS_REPLAY_BUFFER_SIZE || "256",
+      10
+    ),
     nodeEnv: process.env.NODE_ENV || "development",
   };
 }
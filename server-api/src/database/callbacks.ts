@@ -4,6 +4,8 @@ import {
   InstrumentsSubscription,
   L2BookData,
   L2BookSubscription,
+  Liquidation,
+  LiquidationsSubscription,
   Order,
   OrdersSubscription,
   Trade,
@@ -25,12 +27,18 @@ export class DatabaseCallbacks {
   private static instance: DatabaseCallbacks;
   private pool: Pool;
   private notificationClient: any = null;
-  private notificationChannels = ["orders", "trades", "instruments"];
+  private notificationChannels = [
+    "orders",
+    "trades",
+    "instruments",
+    "liquidations",
+  ];
 
   // TODO: store this in db to be retrieved when restarting the server
   private last_seen_trade_id: number = 0;
   private last_seen_order_id: number = 0;
   private last_seen_instrument_id: number = 0;
+  private last_seen_liquidation_id: number = 0;
 
   private userService: UserService;
   private queries: DatabaseQueries;
@@ -42,6 +50,10 @@ export class DatabaseCallbacks {
   private tradeManager: SubscriptionManager<Trade[], TradesSubscription>;
   private orderManager: SubscriptionManager<Order[], OrdersSubscription>;
   private instrumentManager: SubscriptionManager<void, InstrumentsSubscription>;
+  private liquidationManager: SubscriptionManager<
+    Liquidation[],
+    LiquidationsSubscription
+  >;
   private bookHandler: PolledSubscriptionHandler<
     L2BookData,
     L2BookSubscription
@@ -64,6 +76,10 @@ export class DatabaseCallbacks {
       void,
       InstrumentsSubscription
     >();
+    this.liquidationManager = new SubscriptionManager<
+      Liquidation[],
+      LiquidationsSubscription
+    >();
 
     // Initialize polled subscription handlers
     this.bookHandler = new BookSubscriptionHandler(this.queries);
@@ -113,6 +129,9 @@ export class DatabaseCallbacks {
         if (message.channel === "instruments") {
           this.handleInstrumentsUpdate();
         }
+        if (message.channel === "liquidations") {
+          this.handleNewLiquidations();
+        }
       });
 
       // Start listening on all channels with the dedicated connection
@@ -167,6 +186,11 @@ export class DatabaseCallbacks {
     this.pool.query("SELECT MAX(event_id) FROM order_events").then((result) => {
       this.last_seen_order_id = result.rows[0].max || 0;
     });
+    this.pool
+      .query("SELECT MAX(liquidation_id) FROM liquidations")
+      .then((result) => {
+        this.last_seen_liquidation_id = result.rows[0].max || 0;
+      });
   }
 
   private handleNewTrades() {
@@ -236,7 +260,7 @@ export class DatabaseCallbacks {
   private handleNewOrders() {
     this.pool
       .query(
-        "SELECT event_id, order_id, instrument_id, identity, side, type, price, qty, qty_filled, status, event_time FROM order_events WHERE event_id > $1",
+        "SELECT event_id, order_id, instrument_id, identity, side, type, price, qty, qty_filled, status, client_order_id, tag, event_time FROM order_events WHERE event_id > $1",
         [this.last_seen_order_id]
       )
       .then(async (result) => {
@@ -265,6 +289,8 @@ export class DatabaseCallbacks {
               qty_remaining:
                 parseInt(row.qty, 10) - parseInt(row.qty_filled, 10),
               status: row.status,
+              client_order_id: row.client_order_id,
+              tag: row.tag,
               created_at: new Date(row.event_time), // TODO: fix this
               updated_at: new Date(row.event_time),
             };
@@ -286,6 +312,52 @@ export class DatabaseCallbacks {
       });
   }
 
+  private handleNewLiquidations() {
+    this.pool
+      .query(
+        "SELECT liquidation_id, identity, symbol, size, equity, maintenance_margin, created_at FROM liquidations WHERE liquidation_id > $1 ORDER BY liquidation_id",
+        [this.last_seen_liquidation_id]
+      )
+      .then((result) => {
+        if (result.rows.length === 0) {
+          return;
+        }
+        this.last_seen_liquidation_id =
+          result.rows[result.rows.length - 1].liquidation_id;
+
+        // liquidations sorted by instrument
+        const payloads: Map<string, Liquidation[]> = new Map();
+        for (const row of result.rows) {
+          if (!payloads.has(row.symbol)) {
+            payloads.set(row.symbol, []);
+          }
+          payloads.get(row.symbol)!.push({
+            liquidation_id: parseInt(row.liquidation_id, 10),
+            identity: row.identity,
+            symbol: row.symbol,
+            size: parseInt(row.size, 10),
+            equity: parseInt(row.equity, 10),
+            maintenance_margin: parseInt(row.maintenance_margin, 10),
+            created_at: new Date(row.created_at),
+          });
+        }
+
+        for (const [symbol, payload] of payloads) {
+          const callbacks =
+            this.liquidationManager.getCallbacksForSubscription({
+              type: "liquidations",
+              instrument: symbol,
+            });
+          for (const callback of callbacks) {
+            callback?.(payload);
+          }
+        }
+      })
+      .catch((error: Error) => {
+        console.error(`Failed to get new liquidations`, error);
+      });
+  }
+
   private startPolling() {
     this.pollingInterval = setInterval(() => {
       this.pollUpdates();
@@ -321,6 +393,14 @@ export class DatabaseCallbacks {
     this.orderManager.addCallback(client_id, subscription, callback);
   }
 
+  addLiquidationNotificationCallback(
+    client_id: string,
+    subscription: LiquidationsSubscription,
+    callback: (payload: Liquidation[]) => void
+  ) {
+    this.liquidationManager.addCallback(client_id, subscription, callback);
+  }
+
   addBookNotificationCallback(
     client_id: string,
     subscription: L2BookSubscription,
@@ -373,6 +453,13 @@ export class DatabaseCallbacks {
     this.orderManager.removeCallback(client_id, subscription);
   }
 
+  removeLiquidationNotificationCallback(
+    client_id: string,
+    subscription: LiquidationsSubscription
+  ) {
+    this.liquidationManager.removeCallback(client_id, subscription);
+  }
+
   async close() {
     this.isShuttingDown = true;
 
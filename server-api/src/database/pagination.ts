@@ -0,0 +1,141 @@
+import { Pool } from "pg";
+import { CursorPage, CursorQuery } from "@/types";
+
+/**
+ * Cursor pagination shared by the query endpoints
+ *
+ * Rows are ordered by the commit that wrote them, then by their own id, so that pages stay
+ * stable while rows are appended. The cursor of a page is the position of its last row, and
+ * the next page starts right after it.
+ */
+
+export const DEFAULT_PAGE_LIMIT = 20;
+export const MAX_PAGE_LIMIT = 100;
+
+interface Cursor {
+  commit_id: number;
+  id: string;
+}
+
+export function encodeCursor(cursor: Cursor): string {
+  return Buffer.from(JSON.stringify([cursor.commit_id, cursor.id])).toString(
+    "base64url"
+  );
+}
+
+/**
+ * Position of a cursor, null when it was not issued by `encodeCursor`
+ */
+export function decodeCursor(cursor: string): Cursor | null {
+  try {
+    const [commit_id, id] = JSON.parse(
+      Buffer.from(cursor, "base64url").toString()
+    );
+    if (!Number.isSafeInteger(commit_id) || typeof id !== "string") {
+      return null;
+    }
+    return { commit_id, id };
+  } catch {
+    return null;
+  }
+}
+
+/**
+ * Conditions of a query, numbering their parameters in order
+ */
+export class Filters {
+  private clauses: string[] = [];
+  readonly params: unknown[] = [];
+
+  /**
+   * Adds `clause`, given the placeholders of `values`
+   */
+  add(clause: (...params: string[]) => string, ...values: unknown[]): this {
+    const params = values.map((value) => {
+      this.params.push(value);
+      return `$${this.params.length}`;
+    });
+    this.clauses.push(clause(...params));
+    return this;
+  }
+
+  /**
+   * Adds `clause` unless `value` is unset
+   */
+  addIf(clause: (param: string) => string, value: unknown): this {
+    return value === undefined || value === null ? this : this.add(clause, value);
+  }
+
+  where(): string {
+    return this.clauses.length ? `WHERE ${this.clauses.join(" AND ")}` : "";
+  }
+}
+
+export interface PageSource<T> {
+  /** Selected columns, which must include `commit_id` and the id column */
+  select: string;
+  from: string;
+  /** Commit column, e.g. `o.commit_id` */
+  commitColumn: string;
+  /** Column breaking ties between the rows of a commit, e.g. `o.order_id` */
+  idColumn: string;
+  /** SQL type of the id column, to compare it with the cursor */
+  idType: "bigint" | "text";
+  /** Name of the id column in the selected rows */
+  idField: string;
+  filters: Filters;
+  map: (row: any) => T;
+}
+
+/**
+ * Page of rows of `source` after the cursor of `query`
+ */
+export async function fetchPage<T>(
+  pool: Pool,
+  source: PageSource<T>,
+  query: CursorQuery
+): Promise<CursorPage<T>> {
+  const limit = Math.min(query.limit || DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT);
+  const ascending = query.sort_order === "asc";
+  const { filters, commitColumn, idColumn, idType } = source;
+
+  filters
+    .addIf((p) => `${commitColumn} >= ${p}`, query.from_commit)
+    .addIf((p) => `${commitColumn} <= ${p}`, query.to_commit);
+  const cursor = query.cursor ? decodeCursor(query.cursor) : null;
+  if (cursor) {
+    filters.add(
+      (commit, id) =>
+        `(${commitColumn}, ${idColumn}) ${ascending ? ">" : "<"} (${commit}::bigint, ${id}::${idType})`,
+      cursor.commit_id,
+      cursor.id
+    );
+  }
+
+  const direction = ascending ? "ASC" : "DESC";
+  // One more row than the page tells whether another page follows
+  const result = await pool.query(
+    `SELECT ${source.select} FROM ${source.from} ${filters.where()}
+     ORDER BY ${commitColumn} ${direction}, ${idColumn} ${direction}
+     LIMIT ${limit + 1}`,
+    filters.params
+  );
+
+  const rows = result.rows.slice(0, limit);
+  const last = rows[rows.length - 1];
+  const has_more = result.rows.length > limit;
+  return {
+    data: rows.map(source.map),
+    pagination: {
+      limit,
+      has_more,
+      next_cursor:
+        has_more && last
+          ? encodeCursor({
+              commit_id: parseInt(last.commit_id, 10),
+              id: String(last[source.idField]),
+            })
+          : null,
+    },
+  };
+}
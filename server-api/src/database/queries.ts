@@ -1,5 +1,19 @@
 import { Pool } from "pg";
-import { Asset, Instrument, Order, Trade, User } from "@/types";
+import {
+  Asset,
+  BalanceHistoryEntry,
+  BalanceHistoryFilter,
+  CursorPage,
+  CursorQuery,
+  Fill,
+  Instrument,
+  Order,
+  OrdersFilter,
+  Trade,
+  TradesFilter,
+  User,
+} from "@/types";
+import { fetchPage, Filters } from "./pagination";
 
 /**
  * Database query helpers
@@ -35,6 +49,7 @@ export class DatabaseQueries {
       tick_size: parseInt(row.tick_size, 10),
       qty_step: parseInt(row.qty_step, 10),
       commit_id: parseInt(row.commit_id, 10),
+      halted_until_block: parseInt(row.halted_until_block, 10),
     }));
   }
 
@@ -98,109 +113,45 @@ export class DatabaseQueries {
 
   async getUserOrders(
     identity: string,
-    page: number = 1,
-    limit: number = 20,
-    sortBy: string = "created_at",
-    sortOrder: "asc" | "desc" = "desc"
-  ): Promise<{ orders: Array<Order>; total: number }> {
-    const offset = (page - 1) * limit;
-
-    // Validate sort column to prevent SQL injection
-    const allowedSortColumns = [
-      "created_at",
-      "updated_at",
-      "price",
-      "qty",
-      "status",
-    ];
-    const safeSortBy = allowedSortColumns.includes(sortBy)
-      ? sortBy
-      : "created_at";
-    const safeSortOrder = sortOrder === "asc" ? "ASC" : "DESC";
-
-    // Get total count
-    const countResult = await this.pool.query(
-      "SELECT COUNT(*) FROM orders WHERE identity = $1",
-      [identity]
-    );
-    const total = parseInt(countResult.rows[0].count, 10);
-
-    // Get paginated results
-    const result = await this.pool.query(
-      `SELECT * FROM orders WHERE identity = $1 ORDER BY ${safeSortBy} ${safeSortOrder} LIMIT $2 OFFSET $3`,
-      [identity, limit, offset]
+    filter: OrdersFilter,
+    query: CursorQuery
+  ): Promise<CursorPage<Order>> {
+    const filters = new Filters()
+      .add((p) => `identity = ${p}`, identity)
+      .addIf((p) => `instrument_id = ${p}`, filter.instrument_id)
+      .addIf((p) => `status = ${p}`, filter.status)
+      .addIf((p) => `side = ${p}`, filter.side);
+
+    return fetchPage(
+      this.pool,
+      {
+        select: "*",
+        from: "orders",
+        commitColumn: "commit_id",
+        idColumn: "order_id",
+        idType: "text",
+        idField: "order_id",
+        filters,
+        map: (row) => ({
+          order_id: row.order_id,
+          commit_id: parseInt(row.commit_id, 10),
+          instrument_id: parseInt(row.instrument_id, 10),
+          identity: row.identity,
+          side: row.side,
+          type: row.type,
+          price: row.price === null ? null : parseInt(row.price, 10),
+          qty: parseInt(row.qty, 10),
+          qty_filled: parseInt(row.qty_filled, 10),
+          qty_remaining: parseInt(row.qty_remaining, 10),
+          status: row.status,
+          client_order_id: row.client_order_id,
+          tag: row.tag,
+          created_at: row.created_at,
+          updated_at: row.updated_at,
+        }),
+      },
+      query
     );
-
-    const orders = result.rows.map((row) => ({
-      order_id: row.order_id,
-      instrument_id: parseInt(row.instrument_id, 10),
-      identity: row.identity,
-      side: row.side,
-      type: row.type,
-      price: parseInt(row.price, 10),
-      qty: parseInt(row.qty, 10),
-      qty_filled: parseInt(row.qty_filled, 10),
-      qty_remaining: parseInt(row.qty_remaining, 10),
-      status: row.status,
-      created_at: row.created_at,
-      updated_at: row.updated_at,
-    }));
-
-    return { orders, total };
-  }
-
-  async getUserOrdersByPair(
-    identity: string,
-    instrumentId: number,
-    page: number = 1,
-    limit: number = 20,
-    sortBy: string = "created_at",
-    sortOrder: "asc" | "desc" = "desc"
-  ): Promise<{ orders: Array<Order>; total: number }> {
-    const offset = (page - 1) * limit;
-
-    // Validate sort column to prevent SQL injection
-    const allowedSortColumns = [
-      "created_at",
-      "updated_at",
-      "price",
-      "qty",
-      "status",
-    ];
-    const safeSortBy = allowedSortColumns.includes(sortBy)
-      ? sortBy
-      : "created_at";
-    const safeSortOrder = sortOrder === "asc" ? "ASC" : "DESC";
-
-    // Get total count
-    const countResult = await this.pool.query(
-      "SELECT COUNT(*) FROM orders WHERE identity = $1 AND instrument_id = $2",
-      [identity, instrumentId]
-    );
-    const total = parseInt(countResult.rows[0].count, 10);
-
-    // Get paginated results
-    const result = await this.pool.query(
-      `SELECT * FROM orders WHERE identity = $1 AND instrument_id = $2 ORDER BY ${safeSortBy} ${safeSortOrder} LIMIT $3 OFFSET $4`,
-      [identity, instrumentId, limit, offset]
-    );
-
-    const orders = result.rows.map((row) => ({
-      order_id: row.order_id,
-      instrument_id: parseInt(row.instrument_id, 10),
-      identity: row.identity,
-      side: row.side,
-      type: row.type,
-      price: parseInt(row.price, 10),
-      qty: parseInt(row.qty, 10),
-      qty_filled: parseInt(row.qty_filled, 10),
-      qty_remaining: parseInt(row.qty_remaining, 10),
-      status: row.status,
-      created_at: row.created_at,
-      updated_at: row.updated_at,
-    }));
-
-    return { orders, total };
   }
 
   async getOrderbook(
@@ -264,46 +215,121 @@ export class DatabaseQueries {
     return parseInt(result.rows?.[0]?.sum, 10) || 0;
   }
 
-  async getUserTrades(identity: string): Promise<Array<Trade>> {
-    const result = await this.pool.query(
-      `
-      SELECT trade_id, instrument_id, price, qty, trade_time, side FROM trade_events WHERE 
-      taker_identity = $1
-      OR maker_identity = $1;
-    `,
-      [identity]
+  async getUserTrades(
+    identity: string,
+    filter: TradesFilter,
+    query: CursorQuery
+  ): Promise<CursorPage<Trade>> {
+    const filters = new Filters()
+      .add((p) => `(taker_identity = ${p} OR maker_identity = ${p})`, identity)
+      .addIf((p) => `instrument_id = ${p}`, filter.instrument_id)
+      .addIf((p) => `side = ${p}`, filter.side);
+
+    return fetchPage(
+      this.pool,
+      {
+        select: "trade_id, commit_id, instrument_id, price, qty, trade_time, side",
+        from: "trade_events",
+        commitColumn: "commit_id",
+        idColumn: "trade_id",
+        idType: "bigint",
+        idField: "trade_id",
+        filters,
+        map: (row) => ({
+          trade_id: parseInt(row.trade_id, 10),
+          commit_id: parseInt(row.commit_id, 10),
+          instrument_id: parseInt(row.instrument_id, 10),
+          price: parseInt(row.price, 10),
+          qty: parseInt(row.qty, 10),
+          trade_time: row.trade_time,
+          side: row.side,
+        }),
+      },
+      query
     );
-    return result.rows.map((row) => ({
-      trade_id: parseInt(row.trade_id, 10),
-      instrument_id: parseInt(row.instrument_id, 10),
-      price: parseInt(row.price, 10),
-      qty: parseInt(row.qty, 10),
-      trade_time: row.trade_time,
-      side: row.side,
-    }));
   }
 
-  async getUserTradesByPair(
+  /**
+   * Trades of a user from their side: `filter.side` is the side of their order
+   */
+  async getUserFills(
     identity: string,
-    instrumentId: number
-  ): Promise<Array<Trade>> {
-    const result = await this.pool.query(
-      `
-      SELECT trade_id, instrument_id, price, qty, trade_time, side FROM trade_events WHERE 
-      taker_identity = $1
-      OR maker_identity = $1
-      AND instrument_id = $2;
-    `,
-      [identity, instrumentId]
+    filter: TradesFilter,
+    query: CursorQuery
+  ): Promise<CursorPage<Fill>> {
+    // The identity is the first parameter, referred to by the selected columns
+    const filters = new Filters()
+      .add((p) => `(t.taker_identity = ${p} OR t.maker_identity = ${p})`, identity)
+      .addIf((p) => `t.instrument_id = ${p}`, filter.instrument_id)
+      .addIf(
+        (p) =>
+          `(CASE WHEN t.taker_identity = $1 THEN t.side ELSE get_other_side(t.side) END) = ${p}`,
+        filter.side
+      );
+
+    return fetchPage(
+      this.pool,
+      {
+        select: `t.trade_id, t.commit_id, t.instrument_id, t.price, t.qty, t.trade_time,
+          CASE WHEN t.taker_identity = $1 THEN t.taker_order_id ELSE t.maker_order_id END AS order_id,
+          CASE WHEN t.taker_identity = $1 THEN t.side ELSE get_other_side(t.side) END AS side,
+          CASE WHEN t.taker_identity = $1 THEN 'taker' ELSE 'maker' END AS liquidity`,
+        from: "trade_events t",
+        commitColumn: "t.commit_id",
+        idColumn: "t.trade_id",
+        idType: "bigint",
+        idField: "trade_id",
+        filters,
+        map: (row) => ({
+          trade_id: parseInt(row.trade_id, 10),
+          commit_id: parseInt(row.commit_id, 10),
+          instrument_id: parseInt(row.instrument_id, 10),
+          order_id: row.order_id,
+          side: row.side,
+          liquidity: row.liquidity,
+          price: parseInt(row.price, 10),
+          qty: parseInt(row.qty, 10),
+          trade_time: row.trade_time,
+        }),
+      },
+      query
+    );
+  }
+
+  /**
+   * Balances of a user after each commit that changed them
+   */
+  async getUserBalanceHistory(
+    identity: string,
+    filter: BalanceHistoryFilter,
+    query: CursorQuery
+  ): Promise<CursorPage<BalanceHistoryEntry>> {
+    const filters = new Filters()
+      .add((p) => `b.identity = ${p}`, identity)
+      .addIf((p) => `a.symbol = ${p}`, filter.symbol);
+
+    return fetchPage(
+      this.pool,
+      {
+        select:
+          "b.event_id, b.commit_id, a.symbol, b.total, b.reserved, b.event_time",
+        from: "balance_events b JOIN assets a ON a.asset_id = b.asset_id",
+        commitColumn: "b.commit_id",
+        idColumn: "b.event_id",
+        idType: "bigint",
+        idField: "event_id",
+        filters,
+        map: (row) => ({
+          event_id: parseInt(row.event_id, 10),
+          commit_id: parseInt(row.commit_id, 10),
+          symbol: row.symbol,
+          total: parseInt(row.total, 10),
+          reserved: parseInt(row.reserved, 10),
+          event_time: row.event_time,
+        }),
+      },
+      query
     );
-    return result.rows.map((row) => ({
-      trade_id: parseInt(row.trade_id, 10),
-      instrument_id: parseInt(row.instrument_id, 10),
-      price: parseInt(row.price, 10),
-      qty: parseInt(row.qty, 10),
-      trade_time: row.trade_time,
-      side: row.side,
-    }));
   }
 
   async getCandlestickData(
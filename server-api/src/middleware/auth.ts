@@ -9,6 +9,15 @@ export interface AuthHeaders {
   user: string;
 }
 
+/**
+ * Identity of the sub-account selected by the x-sub-account header, named
+ * `<identity>/<name>` by the orderbook, or the identity itself
+ */
+const selectSubAccount = (user: string, subAccount: unknown): string =>
+  typeof subAccount === "string" && subAccount !== ""
+    ? `${user}/${subAccount}`
+    : user;
+
 export const authMiddleware = () => {
   return new Elysia({ name: "auth" }).derive(({ headers }) => {
     const user = headers["x-identity"] || headers["x-identity"];
@@ -19,7 +28,7 @@ export const authMiddleware = () => {
 
     return {
       auth: {
-        user,
+        user: selectSubAccount(user, headers["x-sub-account"]),
       } as AuthHeaders,
     };
   });
@@ -30,7 +39,12 @@ export const optionalAuthMiddleware = () => {
     const user = headers["x-identity"] || headers["x-identity"];
 
     return {
-      auth: user && typeof user === "string" ? ({ user } as AuthHeaders) : null,
+      auth:
+        user && typeof user === "string"
+          ? ({
+              user: selectSubAccount(user, headers["x-sub-account"]),
+            } as AuthHeaders)
+          : null,
     };
   });
 };
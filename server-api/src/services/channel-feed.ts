@@ -0,0 +1,66 @@
+/**
+ * Per-channel sequencing and replay buffer for the WebSocket feed
+ */
+
+export interface SequencedUpdate {
+  seq: number;
+  data: any;
+  timestamp: number;
+}
+
+export class ChannelFeed {
+  // Identifies this instance of the feed: sequence numbers are only comparable within an epoch
+  readonly epoch: string;
+  private seq = 0;
+  private lastData: unknown = undefined;
+  private buffer: SequencedUpdate[] = [];
+
+  constructor(private readonly capacity: number) {
+    this.epoch = `${Date.now().toString(36)}${Math.random()
+      .toString(36)
+      .slice(2, 8)}`;
+  }
+
+  get currentSeq(): number {
+    return this.seq;
+  }
+
+  /**
+   * Assign the next sequence number to an update and keep it for replay.
+   * Database callbacks fan the same payload out to every subscriber of a channel,
+   * so a payload that was just published keeps its sequence number.
+   */
+  publish(data: any): SequencedUpdate {
+    const last = this.buffer[this.buffer.length - 1];
+    if (last && this.lastData === data) {
+      return last;
+    }
+
+    this.seq += 1;
+    this.lastData = data;
+    const update = { seq: this.seq, data, timestamp: Date.now() };
+    this.buffer.push(update);
+    if (this.buffer.length > this.capacity) {
+      this.buffer.shift();
+    }
+    return update;
+  }
+
+  /**
+   * Updates published after `lastSeq`, or null if the gap cannot be filled from the buffer
+   */
+  since(lastSeq: number): SequencedUpdate[] | null {
+    if (lastSeq > this.seq) {
+      return null;
+    }
+    if (lastSeq === this.seq) {
+      return [];
+    }
+
+    const oldest = this.buffer[0];
+    if (!oldest || oldest.seq > lastSeq + 1) {
+      return null;
+    }
+    return this.buffer.filter((update) => update.seq > lastSeq);
+  }
+}
@@ -5,9 +5,15 @@
 import {
   UserBalances,
   BalanceResponse,
-  UserTrades,
-  PaginatedUserOrders,
-  PaginationQuery,
+  BalanceHistoryEntry,
+  BalanceHistoryFilter,
+  CursorPage,
+  CursorQuery,
+  Fill,
+  Order,
+  OrdersFilter,
+  Trade,
+  TradesFilter,
   User,
 } from "../types";
 import { DatabaseQueries } from "../database/queries";
@@ -57,97 +63,47 @@ export class UserService {
   }
 
   /**
-   * Get user orders with pagination
+   * Get a page of the orders of a user
    */
-  async getOrdersPaginated(
+  async getOrders(
     user: string,
-    pagination: PaginationQuery = {}
-  ): Promise<PaginatedUserOrders> {
-    const page = pagination.page || 1;
-    const limit = Math.min(pagination.limit || 20, 100); // Cap at 100 items per page
-    const sortBy = pagination.sort_by || "created_at";
-    const sortOrder = pagination.sort_order || "desc";
-
-    const { orders, total } = await this.queries.getUserOrders(
-      user,
-      page,
-      limit,
-      sortBy,
-      sortOrder
-    );
-
-    const totalPages = Math.ceil(total / limit);
-
-    return {
-      data: orders,
-      pagination: {
-        page,
-        limit,
-        total,
-        total_pages: totalPages,
-        has_next: page < totalPages,
-        has_prev: page > 1,
-      },
-    };
+    filter: OrdersFilter,
+    query: CursorQuery
+  ): Promise<CursorPage<Order>> {
+    return this.queries.getUserOrders(user, filter, query);
   }
 
   /**
-   * Get user orders by pair with pagination
+   * Get a page of the trades of a user
    */
-  async getOrdersByPairPaginated(
+  async getTrades(
     user: string,
-    instrumentId: number,
-    pagination: PaginationQuery = {}
-  ): Promise<PaginatedUserOrders> {
-    const page = pagination.page || 1;
-    const limit = Math.min(pagination.limit || 20, 100); // Cap at 100 items per page
-    const sortBy = pagination.sort_by || "created_at";
-    const sortOrder = pagination.sort_order || "desc";
-
-    const { orders, total } = await this.queries.getUserOrdersByPair(
-      user,
-      instrumentId,
-      page,
-      limit,
-      sortBy,
-      sortOrder
-    );
-
-    const totalPages = Math.ceil(total / limit);
-
-    return {
-      data: orders,
-      pagination: {
-        page,
-        limit,
-        total,
-        total_pages: totalPages,
-        has_next: page < totalPages,
-        has_prev: page > 1,
-      },
-    };
+    filter: TradesFilter,
+    query: CursorQuery
+  ): Promise<CursorPage<Trade>> {
+    return this.queries.getUserTrades(user, filter, query);
   }
 
   /**
-   * Get user trades
+   * Get a page of the fills of a user
    */
-  async getTrades(user: string): Promise<UserTrades> {
-    const trades = await this.queries.getUserTrades(user);
-    return { trades };
+  async getFills(
+    user: string,
+    filter: TradesFilter,
+    query: CursorQuery
+  ): Promise<CursorPage<Fill>> {
+    return this.queries.getUserFills(user, filter, query);
   }
 
   /**
-   * Get user trades by pair
+   * Get a page of the balance history of a user
    */
-  async getTradesByPair(
-    identity: string,
-    instrumentId: number
-  ): Promise<UserTrades> {
-    const trades = await this.queries.getUserTradesByPair(
-      identity,
-      instrumentId
-    );
-    return { trades };
+  async getBalanceHistory(
+    user: string,
+    filter: BalanceHistoryFilter,
+    query: CursorQuery
+  ): Promise<CursorPage<BalanceHistoryEntry>> {
+    return this.queries.getUserBalanceHistory(user, filter, query);
   }
 
   /**
@@ -10,10 +10,15 @@ import {
   TradesSubscription,
   OrdersSubscription,
   CandlestickSubscription,
+  LiquidationsSubscription,
   WebSocketSubscription,
+  HeartbeatResponse,
+  ResyncResponse,
   getSubscriptionKey,
 } from "../types/websocket";
 import { BookService } from "./book-service";
+import { ChannelFeed } from "./channel-feed";
+import { getAppConfig } from "@/config/app";
 import { DatabaseCallbacks } from "@/database/callbacks";
 import { CustomError } from "@/middleware";
 
@@ -37,6 +42,10 @@ export class WebSocketService {
   private bookService: BookService;
   private databaseCallbacks: DatabaseCallbacks;
 
+  // Sequencing of each channel with at least one subscriber, by subscription key
+  private feeds: Map<string, ChannelFeed> = new Map();
+  private readonly replayBufferSize: number;
+
   // Subscription configuration
   private readonly subscriptionConfigs: SubscriptionHandlers;
 
@@ -62,6 +71,11 @@ export class WebSocketService {
         id,
         sub as OrdersSubscription
       ),
+    liquidations: (id: string, sub: WebSocketSubscription) =>
+      this.databaseCallbacks.removeLiquidationNotificationCallback(
+        id,
+        sub as LiquidationsSubscription
+      ),
   };
 
   constructor(bookService: BookService) {
@@ -72,6 +86,13 @@ export class WebSocketService {
     };
     this.databaseCallbacks = DatabaseCallbacks.getInstance();
 
+    const config = getAppConfig();
+    this.replayBufferSize = config.wsReplayBufferSize;
+    this.channelManager.intervals.set(
+      "heartbeat",
+      setInterval(() => this.sendHeartbeats(), config.wsHeartbeatIntervalMs)
+    );
+
     // Initialize subscription configurations
     this.subscriptionConfigs = {
       l2Book: {
@@ -115,6 +136,15 @@ export class WebSocketService {
           );
         },
       },
+      liquidations: {
+        subscribe: (clientId, subscription, callback) => {
+          this.databaseCallbacks.addLiquidationNotificationCallback(
+            clientId,
+            subscription as LiquidationsSubscription,
+            callback
+          );
+        },
+      },
       candlestick: {
         subscribe: (clientId, subscription, callback) => {
           this.databaseCallbacks.addCandlestickNotificationCallback(
@@ -143,13 +173,22 @@ export class WebSocketService {
         .ws("/ws", {
           // Validate incoming messages
           body: t.Object({
-            method: t.Union([t.Literal("subscribe"), t.Literal("unsubscribe")]),
-            subscription: t.Object({
-              type: t.String(),
-              instrument: t.String(),
-              groupTicks: t.Optional(t.Number()),
-              stepSec: t.Optional(t.Number()),
-            }),
+            method: t.Union([
+              t.Literal("subscribe"),
+              t.Literal("unsubscribe"),
+              t.Literal("resync"),
+              t.Literal("ping"),
+            ]),
+            subscription: t.Optional(
+              t.Object({
+                type: t.String(),
+                instrument: t.String(),
+                groupTicks: t.Optional(t.Number()),
+                stepSec: t.Optional(t.Number()),
+              })
+            ),
+            lastSeq: t.Optional(t.Number()),
+            epoch: t.Optional(t.String()),
           }),
 
           open: (ws: any) => {
@@ -288,6 +327,17 @@ export class WebSocketService {
         case "unsubscribe":
           this.handleUnsubscribe(clientId, message.subscription);
           break;
+        case "resync":
+          await this.handleResync(
+            clientId,
+            message.subscription,
+            message.lastSeq,
+            message.epoch
+          );
+          break;
+        case "ping":
+          this.safeJsonSend(client, { type: "pong", timestamp: Date.now() });
+          break;
         default:
           this.sendError(clientId, `Unknown method: ${message.method}`);
       }
@@ -304,6 +354,11 @@ export class WebSocketService {
     const client = this.getClient(clientId);
     if (!client) return;
 
+    if (!subscription) {
+      this.sendError(clientId, "Missing subscription");
+      return;
+    }
+
     if (subscription.instrument) {
       subscription.instrument = subscription.instrument.toUpperCase();
     }
@@ -321,13 +376,14 @@ export class WebSocketService {
     }
 
     try {
-      await this.subscribeToChannel(clientId, subscription, config);
       client.subscriptions.set(subscriptionKey, subscription);
+      await this.subscribeToChannel(clientId, subscription, config);
       console.log(
         `Client ${clientId} subscribed to ${subscription.type}: ${subscription.instrument}. Key: ${subscriptionKey}`
       );
     } catch (error) {
       console.error(`Subscription error for client ${clientId}:`, error);
+      this.handleUnsubscribe(clientId, subscription);
       this.sendError(
         clientId,
         `Failed to subscribe: ${
@@ -344,6 +400,11 @@ export class WebSocketService {
     const client = this.getClient(clientId);
     if (!client) return;
 
+    if (!subscription) {
+      this.sendError(clientId, "Missing subscription");
+      return;
+    }
+
     const subscriptionKey = getSubscriptionKey(subscription);
     const existingSubscription = client.subscriptions.get(subscriptionKey);
 
@@ -358,6 +419,7 @@ export class WebSocketService {
       if (handler) {
         handler(clientId, existingSubscription);
       }
+      this.releaseFeed(subscriptionKey);
 
       console.log(
         `Client ${clientId} unsubscribed from ${subscription.type}: ${subscription.instrument}. Key: ${subscriptionKey}`
@@ -380,13 +442,22 @@ export class WebSocketService {
     const client = this.getClient(clientId);
     if (!client) return;
 
-    // Create callback that sends data to client
+    const subscriptionKey = getSubscriptionKey(subscription);
+    if (!this.feeds.has(subscriptionKey)) {
+      this.feeds.set(subscriptionKey, new ChannelFeed(this.replayBufferSize));
+    }
+
+    // Create callback that sequences data and sends it to client
     const callback = (data: T) => {
+      const feed = this.feeds.get(subscriptionKey);
+      if (!feed) return;
+      const update = feed.publish(data);
       this.sendUpdate(
         clientId,
         subscription.type,
         subscription.instrument,
-        data
+        data,
+        { seq: update.seq, epoch: feed.epoch }
       );
     };
 
@@ -396,13 +467,7 @@ export class WebSocketService {
     // Send initial data if available
     if (config.getInitialData) {
       try {
-        const initialData = await config.getInitialData(subscription);
-        this.sendUpdate(
-          clientId,
-          subscription.type,
-          subscription.instrument,
-          initialData
-        );
+        await this.sendSnapshot(clientId, subscription, config);
       } catch (error) {
         throw new CustomError(
           `Failed to get initial data: ${
@@ -414,6 +479,153 @@ export class WebSocketService {
     }
   }
 
+  /**
+   * Send the current state of a channel, tagged with the sequence number it reflects
+   */
+  private async sendSnapshot<T>(
+    clientId: string,
+    subscription: WebSocketSubscription,
+    config: SubscriptionConfig<T>
+  ) {
+    if (!config.getInitialData) return;
+
+    const feed = this.feeds.get(getSubscriptionKey(subscription));
+    const initialData = await config.getInitialData(subscription);
+    this.sendUpdate(
+      clientId,
+      subscription.type,
+      subscription.instrument,
+      initialData,
+      feed && { seq: feed.currentSeq, epoch: feed.epoch, snapshot: true }
+    );
+  }
+
+  /**
+   * Handle resync requests: replay the updates missed since `lastSeq` if they are still
+   * buffered, otherwise send a fresh snapshot of the channel
+   */
+  private async handleResync(
+    clientId: string,
+    subscription: any,
+    lastSeq?: number,
+    epoch?: string
+  ) {
+    const client = this.getClient(clientId);
+    if (!client) return;
+
+    if (!subscription) {
+      this.sendError(clientId, "Missing subscription");
+      return;
+    }
+    if (subscription.instrument) {
+      subscription.instrument = subscription.instrument.toUpperCase();
+    }
+
+    const subscriptionKey = getSubscriptionKey(subscription);
+    const feed = this.feeds.get(subscriptionKey);
+    const existingSubscription = client.subscriptions.get(subscriptionKey);
+    if (!feed || !existingSubscription) {
+      this.sendError(
+        clientId,
+        `Cannot resync ${subscriptionKey}: not subscribed to this channel`
+      );
+      return;
+    }
+
+    const missed =
+      lastSeq !== undefined && epoch === feed.epoch
+        ? feed.since(lastSeq)
+        : null;
+
+    let status: ResyncResponse["status"];
+    if (missed) {
+      for (const update of missed) {
+        this.sendUpdate(
+          clientId,
+          existingSubscription.type,
+          existingSubscription.instrument,
+          update.data,
+          { seq: update.seq, epoch: feed.epoch, replay: true }
+        );
+      }
+      status = "replayed";
+    } else {
+      const config =
+        this.subscriptionConfigs[
+          existingSubscription.type as keyof SubscriptionHandlers
+        ];
+      if (config.getInitialData) {
+        try {
+          await this.sendSnapshot(clientId, existingSubscription, config);
+        } catch (error) {
+          this.sendError(
+            clientId,
+            `Failed to resync: ${
+              error instanceof Error ? error.message : "Unknown error"
+            }`
+          );
+          return;
+        }
+        status = "snapshot";
+      } else {
+        status = "reset";
+      }
+    }
+
+    const response: ResyncResponse = {
+      type: "resync",
+      instrument: existingSubscription.instrument,
+      subscription: existingSubscription,
+      status,
+      seq: feed.currentSeq,
+      epoch: feed.epoch,
+      timestamp: Date.now(),
+    };
+    this.safeJsonSend(client, response);
+  }
+
+  /**
+   * Drop the feed of a channel once its last subscriber is gone.
+   * Nothing is published on a channel without subscribers, so its buffer could not be trusted anymore.
+   */
+  private releaseFeed(subscriptionKey: string) {
+    for (const client of this.channelManager.clients.values()) {
+      if (client.subscriptions.has(subscriptionKey)) {
+        return;
+      }
+    }
+    this.feeds.delete(subscriptionKey);
+  }
+
+  /**
+   * Send a heartbeat to every client, with the latest sequence number of its channels
+   * so that idle clients can detect missed updates
+   */
+  private sendHeartbeats() {
+    const timestamp = Date.now();
+    for (const client of this.channelManager.clients.values()) {
+      if (!this.isWebSocketOpen(client)) continue;
+
+      const channels: HeartbeatResponse["channels"] = {};
+      for (const subscriptionKey of client.subscriptions.keys()) {
+        const feed = this.feeds.get(subscriptionKey);
+        if (feed) {
+          channels[subscriptionKey] = {
+            seq: feed.currentSeq,
+            epoch: feed.epoch,
+          };
+        }
+      }
+
+      const heartbeat: HeartbeatResponse = {
+        type: "heartbeat",
+        channels,
+        timestamp,
+      };
+      this.safeJsonSend(client, heartbeat);
+    }
+  }
+
   /**
    * Generic method to send updates to clients
    */
@@ -421,7 +633,8 @@ export class WebSocketService {
     clientId: string,
     type: string,
     instrument: string,
-    data: T
+    data: T,
+    sequence?: Pick<WebSocketResponse, "seq" | "epoch" | "snapshot" | "replay">
   ) {
     const client = this.getClient(clientId);
     if (!client || !this.isWebSocketOpen(client)) return;
@@ -431,6 +644,7 @@ export class WebSocketService {
       instrument,
       data: this.formatDataForType(type, data),
       timestamp: Date.now(),
+      ...sequence,
     };
 
     this.safeJsonSend(client, response);
@@ -445,6 +659,8 @@ export class WebSocketService {
         return { orders: data };
       case "trades":
         return { trades: data };
+      case "liquidations":
+        return { liquidations: data };
       case "candlestick":
         return { candlesticks: data };
       case "l2Book":
@@ -539,6 +755,12 @@ export class WebSocketService {
    * Close all connections and clean up
    */
   close() {
+    for (const interval of this.channelManager.intervals.values()) {
+      clearInterval(interval);
+    }
+    this.channelManager.intervals.clear();
+    this.feeds.clear();
+
     // Close all client connections
     for (const client of this.channelManager.clients.values()) {
       if (client.ws.raw.readyState === 1) {
@@ -2,7 +2,7 @@
  * API request and response types
  */
 
-import { Order, Trade } from "./orderbook";
+import { Order, OrderSide, OrderStatus } from "./orderbook";
 
 export interface ConfigResponse {
   contract_name: string;
@@ -23,36 +23,51 @@ export interface UserOrders {
   orders: Order[];
 }
 
-export interface PaginatedUserOrders extends PaginatedResponse<Order> {}
-
-export interface UserTrades {
-  trades: Trade[];
-}
 
 export interface GetBookQuery {
   levels?: number;
   group_ticks?: number;
 }
 
-export interface PaginationQuery {
-  page?: number;
+/**
+ * Page of a query endpoint, in the order of the commits of its rows
+ */
+export interface CursorQuery {
+  /** `next_cursor` of the previous page, unset for the first one */
+  cursor?: string;
   limit?: number;
-  sort_by?: string;
+  /** Oldest commits first when "asc", latest first by default */
   sort_order?: "asc" | "desc";
+  /** Range of commits of the rows, both ends included */
+  from_commit?: number;
+  to_commit?: number;
 }
 
-export interface PaginatedResponse<T> {
+export interface CursorPage<T> {
   data: T[];
   pagination: {
-    page: number;
     limit: number;
-    total: number;
-    total_pages: number;
-    has_next: boolean;
-    has_prev: boolean;
+    /** Cursor of the next page, null on the last one */
+    next_cursor: string | null;
+    has_more: boolean;
   };
 }
 
+export interface OrdersFilter {
+  instrument_id?: number;
+  status?: OrderStatus;
+  side?: OrderSide;
+}
+
+export interface TradesFilter {
+  instrument_id?: number;
+  side?: OrderSide;
+}
+
+export interface BalanceHistoryFilter {
+  symbol?: string;
+}
+
 export interface AuthHeaders {
   user: string;
 }
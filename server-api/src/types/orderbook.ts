@@ -9,6 +9,8 @@ export interface Asset {
   step: number;
   status: string;
   created_at: Date;
+  /** Where the asset is deposited from and withdrawn to, null when it has no backing */
+  backing: "hyli_token" | "bridge" | null;
 }
 
 export interface Instrument {
@@ -19,6 +21,10 @@ export interface Instrument {
   base_asset_id: number;
   quote_asset_id: number;
   status: MarketStatus;
+  /** Block until which the orders that would trade are refused by the circuit breaker */
+  halted_until_block: number;
+  /** "perp" for perpetual futures, whose base asset is never delivered */
+  kind: "spot" | "perp";
   created_at: Date;
 }
 
@@ -39,6 +45,8 @@ export interface Balance {
 
 export interface Order {
   order_id: string;
+  /** Commit that created the order */
+  commit_id: number;
   instrument_id: number;
   identity: string;
   side: OrderSide;
@@ -48,12 +56,16 @@ export interface Order {
   qty_filled: number;
   qty_remaining: number;
   status: OrderStatus;
+  // Identifiers given by the client when placing the order
+  client_order_id: string | null;
+  tag: string | null;
   created_at: Date;
   updated_at: Date;
 }
 
 export interface Trade {
   trade_id: number;
+  commit_id: number;
   instrument_id: number;
   price: number;
   qty: number;
@@ -61,11 +73,51 @@ export interface Trade {
   side: OrderSide;
 }
 
+/**
+ * Trade seen from one of its users: the side and order are theirs
+ */
+export interface Fill {
+  trade_id: number;
+  commit_id: number;
+  instrument_id: number;
+  order_id: string;
+  side: OrderSide;
+  liquidity: "maker" | "taker";
+  price: number;
+  qty: number;
+  trade_time: Date;
+}
+
+/**
+ * Balance of an asset after a commit changed it
+ */
+export interface BalanceHistoryEntry {
+  event_id: number;
+  commit_id: number;
+  symbol: string;
+  total: number;
+  reserved: number;
+  event_time: Date;
+}
+
+export interface Liquidation {
+  liquidation_id: number;
+  identity: string;
+  symbol: string;
+  /** Signed size of the force-closed position, negative for a short */
+  size: number;
+  /** Equity and maintenance margin of the account before the liquidation */
+  equity: number;
+  maintenance_margin: number;
+  created_at: Date;
+}
+
 // Enums
 export enum MarketStatus {
   ACTIVE = "active",
   HALTED = "halted",
   CLOSED = "closed",
+  DELISTED = "delisted",
 }
 
 export enum UserStatus {
@@ -8,8 +8,11 @@ export interface WebSocketSubscription {
 }
 
 export interface WebSocketMessage {
-  method: "subscribe" | "unsubscribe";
-  subscription: WebSocketSubscription;
+  method: "subscribe" | "unsubscribe" | "resync" | "ping";
+  subscription?: WebSocketSubscription;
+  // Last sequence number and epoch the client received on the channel, for resync
+  lastSeq?: number;
+  epoch?: string;
 }
 
 export interface WebSocketResponse {
@@ -17,6 +20,36 @@ export interface WebSocketResponse {
   instrument: string;
   data: any;
   timestamp: number;
+  seq?: number;
+  epoch?: string;
+  // Full state of the channel as of `seq`, rather than a single update
+  snapshot?: boolean;
+  // Update sent again in response to a resync request
+  replay?: boolean;
+}
+
+export interface ChannelSequence {
+  seq: number;
+  epoch: string;
+}
+
+export interface HeartbeatResponse {
+  type: "heartbeat";
+  // Latest sequence number of each channel the client is subscribed to, by subscription key
+  channels: Record<string, ChannelSequence>;
+  timestamp: number;
+}
+
+export interface ResyncResponse {
+  type: "resync";
+  instrument: string;
+  subscription: WebSocketSubscription;
+  // "replayed": missed updates were sent again, "snapshot": a fresh snapshot was sent,
+  // "reset": the channel has no snapshot and the client must refetch its state over REST
+  status: "replayed" | "snapshot" | "reset";
+  seq: number;
+  epoch: string;
+  timestamp: number;
 }
 
 export interface L2BookSubscription extends WebSocketSubscription {
@@ -38,6 +71,10 @@ export interface InstrumentsSubscription extends WebSocketSubscription {
   type: "instruments";
 }
 
+export interface LiquidationsSubscription extends WebSocketSubscription {
+  type: "liquidations";
+}
+
 export interface CandlestickSubscription extends WebSocketSubscription {
   type: "candlestick";
   stepSec: number;
@@ -1,23 +1,26 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc,
     },
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
     vec,
 };
 
 use anyhow::{anyhow, bail, Context, Result};
 use axum::{
-    extract::{Json, State},
-    http::{HeaderMap, Method},
-    response::IntoResponse,
-    routing::{get, post},
-    Router,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, Json, Path, Query, Request, State,
+    },
+    http::{header, HeaderMap, Method},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
 };
 use borsh::BorshSerialize;
-use client_sdk::{contract_indexer::AppError, rest_client::NodeApiHttpClient};
+use client_sdk::contract_indexer::AppError;
 use hex;
 use hyli_modules::{
     bus::{BusClientSender, BusMessage, SharedMessageBus},
@@ -30,31 +33,85 @@ use opentelemetry::{
     KeyValue,
 };
 use orderbook::{
-    model::{AssetInfo, Order, OrderbookEvent, PairInfo, UserInfo, WithdrawDestination},
+    model::{
+        AssetInfo, CircuitBreaker, CircuitBreakerState, CollateralConfig, DustSweep, FeeTier,
+        MarginAccount, MarketStatus, Order, OrderId, OrderbookEvent, Pair, PairInfo, PerpConfig,
+        SessionKeyPermissions, Symbol, UserInfo, WithdrawDelay, WithdrawDestination, WithdrawLimit,
+    },
     transaction::{
-        AddSessionKeyPrivateInput, CancelOrderPrivateInput, CreateOrderPrivateInput,
-        OrderbookAction, PermissionedOrderbookAction, WithdrawPrivateInput,
+        AddSessionKeyPrivateInput, CancelOnDisconnectPrivateInput, CancelOrderPrivateInput,
+        CreateOrderPrivateInput, EscapePrivateInput, OnboardUsersPrivateInput, OnboardedUser,
+        OrderbookAction, PermissionedOrderbookAction, TransferPrivateInput,
+        UpdateFeeTiersPrivateInput, WithdrawPrivateInput,
     },
-    zk::smt::GetKey,
-    ORDERBOOK_ACCOUNT_IDENTITY,
+    utils::{split_sub_account, sub_account_identity, SignedAction},
+    zk::{
+        smt::{GetKey, UserBalance},
+        FullState,
+    },
+    INSURANCE_FUND_IDENTITY, ORDERBOOK_ACCOUNT_IDENTITY,
 };
 use reqwest::StatusCode;
-use sdk::{BlobTransaction, ContractAction, ContractName, Hashed, Identity, LaneId};
+use sdk::{
+    merkle_utils::BorshableMerkleProof, BlobTransaction, ContractAction, ContractName, Hashed,
+    Identity, LaneId,
+};
 use serde::{Deserialize, Serialize};
-use sqlx::query_scalar;
-use tokio::sync::{Mutex, RwLock};
+use sqlx::{query_scalar, PgPool};
+use tokio::sync::{broadcast::error::RecvError, Mutex, RwLock};
 use tower_http::cors::{Any, CorsLayer};
-use tracing::{debug, warn, Span};
+use tracing::{debug, info, warn, Instrument, Span};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
-    database::{DatabaseModuleCtx, DatabaseRequest, DatabaseService},
+    audit::{fingerprint, redact_secret, AuditEntry, AuditLog, AuditQuery, AuditRecord},
+    balance_feed::{BalanceFeed, BalanceFeedEvent, BalanceSubscription},
+    bridge::{
+        limits::{BridgeDirection, BridgeLimits},
+        BridgePause,
+    },
+    cancel_on_disconnect::{
+        CancelAuthorization, CancelOnDisconnectRegistry, LapsedSession,
+        MAX_CANCEL_ON_DISCONNECT_TIMEOUT_SECS,
+    },
+    clock::Clock,
+    collateral::AssetBackings,
+    conf::{FundingConfig, HealthConfig, LiquidationConfig, WithdrawalQueueConfig},
+    config_watcher::{ConfigWatcher, RuntimeSettings},
+    database::{
+        BlobOutbox, DatabaseModuleCtx, DatabaseRequest, DatabaseService, OrderTag, WorkerQueues,
+    },
+    funding::funding_payment,
+    health::{self, HealthCheck, HealthReport},
+    liquidation::liquidation_candidates,
+    node_client::NodeClient,
+    pair_locks::{PairLocks, StateReadSet},
     prover::OrderbookProverRequest,
+    rate_limit::{RateLimiter, RequestClass},
+    reconciliation::{OwedAsset, Reconciliation},
+    risk::{RiskLimits, RiskManager},
+    services::address_book_service::AddressBookService,
+    services::analytics_service::{AnalyticsService, BookDepth, PairAnalytics},
+    services::api_key_service::{ApiKeyIdentity, ApiKeyService},
     services::asset_service::AssetService,
+    services::checkpoint_service::{CheckpointService, MAX_CHECKPOINTS},
+    services::index_price_service::IndexPriceService,
+    services::ledger_service::{LedgerEntry, LedgerService, MAX_LEDGER_COMMITS},
+    services::prover_service::ProverService,
+    services::ticker_service::{Ticker, TickerService},
     services::user_service::UserService,
+    settlement_check::{SettlementCheck, SettlementStatus},
+    withdrawal_queue::releasable_withdrawals,
 };
 use rand::RngCore;
 
+mod grpc;
+mod openapi;
+#[cfg(feature = "test-mode")]
+mod test_mode;
+
 /// Metrics for tracking HTTP request performance
 #[derive(Clone)]
 pub struct AppMetrics {
@@ -72,6 +129,8 @@ pub struct AppMetrics {
     pub events_applied_count: Histogram<u64>,
     /// Event processing duration
     pub event_apply_duration: Histogram<f64>,
+    /// Count of order flow actions whose events were generated again after a conflicting write
+    pub orderbook_lock_conflicts: Counter<u64>,
 }
 
 impl AppMetrics {
@@ -127,6 +186,10 @@ impl AppMetrics {
                 .with_unit("us")
                 .with_boundaries(extended_buckets.clone())
                 .build(),
+            orderbook_lock_conflicts: meter
+                .u64_counter("orderbook.lock.conflicts")
+                .with_description("Count of actions executed again after a conflicting write")
+                .build(),
         }
     }
 
@@ -165,6 +228,12 @@ impl AppMetrics {
         );
     }
 
+    #[inline]
+    fn record_lock_conflict(&self, operation: &str) {
+        self.orderbook_lock_conflicts
+            .add(1, &[KeyValue::new("operation", operation.to_string())]);
+    }
+
     #[inline]
     fn record_event_apply(&self, duration: Duration, operation: &str) {
         self.event_apply_duration.record(
@@ -199,6 +268,9 @@ impl Default for AppMetrics {
 pub struct OrderbookModule {
     bus: OrderbookModuleBusClient,
     router_ctx: RouterCtx,
+    funding: FundingConfig,
+    liquidation: LiquidationConfig,
+    withdrawal_queue: WithdrawalQueueConfig,
 }
 
 pub struct OrderbookModuleCtx {
@@ -206,17 +278,44 @@ pub struct OrderbookModuleCtx {
     pub orderbook_cn: ContractName,
     pub lane_id: LaneId,
     pub default_state: orderbook::model::ExecuteState,
-    pub client: Arc<NodeApiHttpClient>,
+    pub client: Arc<NodeClient>,
     pub asset_service: Arc<RwLock<AssetService>>,
     pub user_service: Arc<RwLock<UserService>>,
     pub database_ctx: Arc<DatabaseModuleCtx>,
     pub admin_secret: String,
+    /// Default risk limits of users without limits of their own
+    pub risk_limits: RiskLimits,
+    pub balance_feed: Arc<BalanceFeed>,
+    pub asset_backings: Arc<AssetBackings>,
+    pub bridge_pause: Arc<BridgePause>,
+    pub bridge_limits: Arc<BridgeLimits>,
+    pub reconciliation: Arc<Reconciliation>,
+    /// State of the prover, when it runs alongside the server, that escape proofs are built on
+    pub proven_state: Option<Arc<Mutex<FullState>>>,
+    /// Frozen in test mode, see [`Clock`]
+    pub clock: Clock,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub funding: FundingConfig,
+    pub liquidation: LiquidationConfig,
+    pub withdrawal_queue: WithdrawalQueueConfig,
+    /// Serve `POST /deposit`, crediting users without checking that they sent the funds
+    pub trusted_deposits: bool,
+    pub health: HealthConfig,
+    /// Port of the gRPC API, which is not served when 0
+    pub grpc_server_port: u16,
+    pub audit_log: Arc<AuditLog>,
+    pub config_watcher: Arc<ConfigWatcher>,
+    pub settlement_check: Option<Arc<SettlementCheck>>,
 }
 
 #[derive(Debug, Clone)]
 pub enum OrderbookRequest {
     PendingDeposit(PendingDeposit),
     PendingWithdraw(PendingWithdraw),
+    /// New fee tier of each user, as computed by the fee tier module
+    UpdateFeeTiers(Vec<(String, FeeTier)>),
+    /// Index price of each pair, as pulled by the oracle module
+    UpdateIndexPrices(Vec<(Pair, u64)>),
 }
 
 impl BusMessage for OrderbookRequest {}
@@ -226,6 +325,8 @@ pub struct PendingDeposit {
     pub sender: Identity,
     pub contract_name: ContractName,
     pub amount: u128,
+    /// Trace the deposit was detected in, continued by its action
+    pub context: opentelemetry::Context,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -233,6 +334,9 @@ pub struct PendingWithdraw {
     pub destination: WithdrawDestination,
     pub contract_name: ContractName,
     pub amount: u64,
+    /// Trace of the settled withdraw, continued by its transfer
+    #[serde(skip)]
+    pub context: opentelemetry::Context,
 }
 
 module_bus_client! {
@@ -255,7 +359,7 @@ impl Module for OrderbookModule {
     type Context = Arc<OrderbookModuleCtx>;
 
     async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
-        let orderbook = Arc::new(Mutex::new(ctx.default_state.clone()));
+        let orderbook = Arc::new(RwLock::new(ctx.default_state.clone()));
 
         let router_bus = RouterBusClient::new_from_bus(bus.new_handle()).await;
         let bus = OrderbookModuleBusClient::new_from_bus(bus.new_handle()).await;
@@ -282,11 +386,14 @@ impl Module for OrderbookModule {
         );
 
         let database_service = DatabaseService::new(ctx.database_ctx.clone());
+        let risk_manager =
+            RiskManager::load(ctx.database_ctx.pool.clone(), ctx.risk_limits).await?;
         let router_ctx = RouterCtx {
             orderbook_cn: ctx.orderbook_cn.clone(),
             default_state: ctx.default_state.clone(),
             bus: router_bus.clone(),
             orderbook: orderbook.clone(),
+            pair_locks: Arc::new(PairLocks::default()),
             lane_id: ctx.lane_id.clone(),
             asset_service: ctx.asset_service.clone(),
             user_service: ctx.user_service.clone(),
@@ -295,6 +402,36 @@ impl Module for OrderbookModule {
             metrics: AppMetrics::new(),
             database_service: Arc::new(RwLock::new(database_service)),
             admin_secret: ctx.admin_secret.clone(),
+            cancel_on_disconnect: Arc::new(Mutex::new(CancelOnDisconnectRegistry::new(
+                ctx.clock.clone(),
+            ))),
+            risk_manager: Arc::new(RwLock::new(risk_manager)),
+            balance_feed: ctx.balance_feed.clone(),
+            prover_service: Arc::new(ProverService::new(ctx.database_ctx.pool.clone())),
+            blob_outbox: ctx.database_ctx.blob_outbox.clone(),
+            worker_queues: ctx.database_ctx.worker_queues.clone(),
+            address_book_service: Arc::new(AddressBookService::new(ctx.database_ctx.pool.clone())),
+            api_key_service: Arc::new(ApiKeyService::new(ctx.database_ctx.pool.clone())),
+            index_price_service: Arc::new(IndexPriceService::new(ctx.database_ctx.pool.clone())),
+            analytics_service: Arc::new(AnalyticsService::new(ctx.database_ctx.read_pool.clone())),
+            checkpoint_service: Arc::new(CheckpointService::new(
+                ctx.database_ctx.read_pool.clone(),
+                ctx.orderbook_cn.0.clone(),
+            )),
+            ledger_service: Arc::new(LedgerService::new(ctx.database_ctx.read_pool.clone())),
+            ticker_service: Arc::new(TickerService::new(ctx.database_ctx.read_pool.clone())),
+            asset_backings: ctx.asset_backings.clone(),
+            bridge_pause: ctx.bridge_pause.clone(),
+            bridge_limits: ctx.bridge_limits.clone(),
+            reconciliation: ctx.reconciliation.clone(),
+            proven_state: ctx.proven_state.clone(),
+            clock: ctx.clock.clone(),
+            rate_limiter: ctx.rate_limiter.clone(),
+            pool: ctx.database_ctx.pool.clone(),
+            health: ctx.health.clone(),
+            audit_log: ctx.audit_log.clone(),
+            config_watcher: ctx.config_watcher.clone(),
+            settlement_check: ctx.settlement_check.clone(),
         };
 
         let cors = CorsLayer::new()
@@ -302,45 +439,171 @@ impl Module for OrderbookModule {
             .allow_methods(vec![Method::GET, Method::POST])
             .allow_headers(Any);
 
-        let api = Router::new()
-            .route("/create_pair", post(create_pair))
-            .route("/add_session_key", post(add_session_key))
-            .route("/deposit", post(deposit))
-            .route("/create_order", post(create_order))
-            .route("/cancel_order", post(cancel_order))
-            .route("/withdraw", post(withdraw))
-            .route("/nonce", get(get_nonce))
-            .route("/admin/submit_prover_request", post(submit_prover_request))
+        // Deposits are credited from the settled token transfers to the orderbook account. The
+        // trusted endpoint credits them without any transfer, and is only served to local setups.
+        let api = OpenApiRouter::with_openapi(openapi::OrderbookApi::openapi());
+        let api = if ctx.trusted_deposits {
+            api.routes(routes!(deposit))
+        } else {
+            api
+        };
+        let api = api
+            .routes(routes!(add_session_key))
+            .routes(routes!(create_order))
+            .routes(routes!(create_orders))
+            .routes(routes!(cancel_order))
+            .routes(routes!(withdraw))
+            .routes(routes!(transfer))
+            .routes(routes!(sweep_dust))
+            .routes(routes!(cancel_on_disconnect))
+            .routes(routes!(balances_feed))
+            .routes(routes!(get_nonce))
+            .routes(routes!(get_saved_addresses, save_address, delete_address))
+            .routes(routes!(get_api_keys, create_api_key))
+            .routes(routes!(revoke_api_key))
+            .routes(routes!(get_risk_limits))
+            .routes(routes!(get_pending_withdrawals))
+            .routes(routes!(get_ledger))
+            .routes(routes!(get_positions))
+            .routes(routes!(get_margin_accounts))
+            .routes(routes!(get_sub_accounts))
+            .routes(routes!(get_insurance_fund))
+            .routes(routes!(get_circuit_breakers))
+            .routes(routes!(get_bridge_status))
+            .routes(routes!(get_bridge_limits))
+            .routes(routes!(get_reconciliation))
+            .routes(routes!(get_settlement_status))
+            .routes(routes!(get_index_price))
+            .routes(routes!(get_node_health))
+            .routes(routes!(get_healthz))
+            .routes(routes!(get_readyz))
+            .routes(routes!(get_prover_status))
+            .routes(routes!(get_pair_analytics))
+            .routes(routes!(get_ticker))
+            .routes(routes!(get_tickers))
+            .routes(routes!(get_checkpoints))
+            .routes(routes!(get_latest_checkpoint))
+            .routes(routes!(get_escape_proof))
+            .routes(routes!(submit_prover_request))
+            .routes(routes!(set_risk_limits))
+            .routes(routes!(set_withdraw_limits))
+            .routes(routes!(set_withdraw_delays))
+            .routes(routes!(review_withdrawal))
+            .routes(routes!(update_bridge_pause))
+            .routes(routes!(set_collaterals))
+            .routes(routes!(create_pair))
+            .routes(routes!(create_perp_market))
+            .routes(routes!(update_pair_status))
+            .routes(routes!(set_circuit_breakers))
+            .routes(routes!(onboard_users))
+            .routes(routes!(get_audit_log))
+            .routes(routes!(update_config))
             // FIXME: to be removed. Only here for debugging purposes
-            .route("/state", get(get_state))
-            .with_state(router_ctx.clone())
-            .layer(cors);
+            .routes(routes!(get_state));
+        let (api, openapi) = api.split_for_parts();
+        let api = api
+            .layer(middleware::from_fn_with_state(
+                router_ctx.clone(),
+                audit_admin_requests,
+            ))
+            .layer(middleware::from_fn_with_state(
+                router_ctx.clone(),
+                rate_limit,
+            ))
+            .with_state(router_ctx.clone());
+
+        #[cfg(feature = "test-mode")]
+        let api = api.merge(test_mode::routes().with_state(router_ctx.clone()));
+        let api = api.layer(cors);
 
         if let Ok(mut guard) = ctx.api.router.lock() {
             if let Some(router) = guard.take() {
                 guard.replace(router.merge(api));
             }
         }
+        if let Ok(mut guard) = ctx.api.openapi.lock() {
+            guard.merge(openapi);
+        }
+
+        if ctx.grpc_server_port != 0 {
+            let grpc_ctx = router_ctx.clone();
+            let port = ctx.grpc_server_port;
+            tokio::spawn(async move {
+                let _ = log_error!(grpc::serve(grpc_ctx, port).await, "gRPC API stopped");
+            });
+        }
 
-        Ok(OrderbookModule { bus, router_ctx })
+        Ok(OrderbookModule {
+            bus,
+            router_ctx,
+            funding: ctx.funding.clone(),
+            liquidation: ctx.liquidation.clone(),
+            withdrawal_queue: ctx.withdrawal_queue.clone(),
+        })
     }
 
     async fn run(&mut self) -> Result<()> {
+        let mut heartbeat_interval = tokio::time::interval(Duration::from_secs(1));
+        heartbeat_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        // The first settlement is one interval after startup, not on each restart
+        let funding_period = Duration::from_secs(self.funding.interval_secs.max(1));
+        let mut funding_interval =
+            tokio::time::interval_at(tokio::time::Instant::now() + funding_period, funding_period);
+        funding_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut liquidation_interval =
+            tokio::time::interval(Duration::from_secs(self.liquidation.interval_secs.max(1)));
+        liquidation_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut withdrawal_queue_interval = tokio::time::interval(Duration::from_secs(
+            self.withdrawal_queue.interval_secs.max(1),
+        ));
+        withdrawal_queue_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let reconciliation_secs = self.router_ctx.reconciliation.interval_secs();
+        let mut reconciliation_interval =
+            tokio::time::interval(Duration::from_secs(reconciliation_secs.max(1)));
+        reconciliation_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
         module_handle_messages! {
             on_self self,
 
             listen<OrderbookRequest> event => {
                 match event {
                     OrderbookRequest::PendingDeposit(deposit) => {
-                        _ = log_error!(self.execute_deposit(deposit)
-                            .await, "could not deposit transfer")
+                        let span = tracing::info_span!("pending_deposit", contract_name = %deposit.contract_name);
+                        span.set_parent(deposit.context.clone());
+                        _ = log_error!(self.router_ctx.execute_deposit(deposit)
+                            .instrument(span).await, "could not deposit transfer")
                     }
                     OrderbookRequest::PendingWithdraw(withdraw) => {
+                        let span = tracing::info_span!("pending_withdraw", contract_name = %withdraw.contract_name);
+                        span.set_parent(withdraw.context.clone());
                         _ =  log_error!(self.execute_withdraw(withdraw)
-                            .await, "could not withdraw")
+                            .instrument(span).await, "could not withdraw")
+                    }
+                    OrderbookRequest::UpdateFeeTiers(fee_tiers) => {
+                        _ = log_error!(self.execute_fee_tiers_update(fee_tiers)
+                            .await, "could not update fee tiers")
+                    }
+                    OrderbookRequest::UpdateIndexPrices(index_prices) => {
+                        _ = log_error!(self.execute_index_prices_update(index_prices)
+                            .await, "could not update index prices")
                     }
                 }
             }
+            _ = heartbeat_interval.tick() => {
+                _ = log_error!(self.router_ctx.cancel_lapsed_sessions().await, "could not cancel orders of lapsed sessions")
+            }
+            _ = funding_interval.tick(), if self.funding.interval_secs > 0 => {
+                _ = log_error!(self.execute_funding_settlement().await, "could not settle funding")
+            }
+            _ = liquidation_interval.tick(), if self.liquidation.interval_secs > 0 => {
+                _ = log_error!(self.execute_liquidations().await, "could not liquidate positions")
+            }
+            _ = withdrawal_queue_interval.tick(), if self.withdrawal_queue.interval_secs > 0 => {
+                _ = log_error!(self.execute_withdrawal_releases().await, "could not release withdrawals")
+            }
+            _ = reconciliation_interval.tick(), if reconciliation_secs > 0 => {
+                self.spawn_reconciliation().await
+            }
         };
 
         Ok(())
@@ -348,75 +611,12 @@ impl Module for OrderbookModule {
 }
 
 impl OrderbookModule {
-    async fn execute_deposit(&self, deposit: PendingDeposit) -> Result<()> {
-        let PendingDeposit {
-            sender,
-            contract_name,
-            amount,
-        } = deposit;
-        let asset_service = self.router_ctx.asset_service.read().await;
-
-        let Identity(user) = sender;
-        let Some(symbol) = asset_service
-            .get_symbol_from_contract_name(&contract_name.0)
-            .await
-        else {
-            bail!(
-                "Could not deposit: Unknown contract name: {}",
-                contract_name.0
-            );
-        };
-        let amount_u64 =
-            u64::try_from(amount).context("Deposit amount exceeds supported range (u64)")?;
-
-        let (action_id, user_info, events) = {
-            let mut orderbook = self.router_ctx.orderbook.lock().await;
-            let user_info = orderbook.get_user_info(&user).unwrap_or_else(|_| {
-                let mut salt = [0u8; 32];
-                rand::rng().fill_bytes(&mut salt);
-                UserInfo::new(user.clone(), salt.to_vec())
-            });
-
-            let events = orderbook
-                .deposit(&symbol, amount_u64, &user_info)
-                .map_err(|e| anyhow!("Failed to apply deposit on orderbook: {e}"))?;
-
-            orderbook
-                .apply_events(&user_info, &events)
-                .map_err(|e| anyhow!("Failed to update orderbook state after deposit: {e}"))?;
-
-            let action_id = self
-                .router_ctx
-                .action_id_counter
-                .fetch_add(1, Ordering::Relaxed);
-            (action_id, user_info, events)
-        };
-
-        let action_private_input = Vec::<u8>::new();
-
-        let orderbook_action = PermissionedOrderbookAction::Deposit {
-            symbol,
-            amount: amount_u64,
-        };
-
-        let _ = process_orderbook_action(
-            user_info,
-            events,
-            orderbook_action,
-            action_id,
-            &action_private_input,
-            &self.router_ctx,
-        )
-        .map_err(|AppError(_, inner)| anyhow!("Failed to submit deposit action: {inner}"))?;
-
-        Ok(())
-    }
-
     async fn execute_withdraw(&self, withdraw: PendingWithdraw) -> Result<()> {
         let PendingWithdraw {
             destination,
             contract_name,
             amount,
+            ..
         } = withdraw;
 
         if destination.network != "hyli" {
@@ -470,207 +670,3366 @@ impl OrderbookModule {
                 orderbook_action: orderbook_id_action,
                 tx_hash: tx_hash.clone(),
                 nonce: action_id,
+                trace_context: HashMap::new(),
             },
+            order_tags: HashMap::new(),
             context,
         })?;
         Ok(())
     }
-}
 
-#[derive(Clone)]
-#[allow(dead_code)]
-struct RouterCtx {
-    pub bus: RouterBusClient,
-    pub orderbook_cn: ContractName,
-    pub default_state: orderbook::model::ExecuteState,
-    pub orderbook: Arc<Mutex<orderbook::model::ExecuteState>>,
-    pub lane_id: LaneId,
-    pub asset_service: Arc<RwLock<AssetService>>,
-    pub user_service: Arc<RwLock<UserService>>,
-    pub client: Arc<NodeApiHttpClient>,
-    pub action_id_counter: Arc<AtomicU32>,
-    pub metrics: AppMetrics,
-    pub database_service: Arc<RwLock<DatabaseService>>,
-    pub admin_secret: String,
-}
+    async fn execute_fee_tiers_update(&self, fee_tiers: Vec<(String, FeeTier)>) -> Result<()> {
+        let (action_id, user_info, events, updates) = {
+            let mut orderbook = self.router_ctx.orderbook.write().await;
+
+            // Only submit tiers of registered users that actually changed
+            let updates: Vec<(String, FeeTier)> = fee_tiers
+                .into_iter()
+                .filter(|(user, fee_tier)| {
+                    user != ORDERBOOK_ACCOUNT_IDENTITY
+                        && orderbook
+                            .get_user_info(user)
+                            .is_ok_and(|info| info.nonce > 0 && info.fee_tier != *fee_tier)
+                })
+                .collect();
+            if updates.is_empty() {
+                debug!("No fee tier changes to submit");
+                return Ok(());
+            }
 
-// --------------------------------------------------------
-//     Headers
-// --------------------------------------------------------
+            let user_info = orderbook
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
 
-const IDENTITY_HEADER: &str = "x-identity";
-const PUBLIC_KEY_HEADER: &str = "x-public-key";
-const SIGNATURE_HEADER: &str = "x-signature";
+            let events = orderbook
+                .update_fee_tiers(&user_info, &updates)
+                .map_err(|e| anyhow!("Failed to update fee tiers on orderbook: {e}"))?;
 
-#[derive(Debug)]
-struct AuthHeaders {
-    identity: String,
-    public_key: Option<Vec<u8>>,
-    signature: Option<Vec<u8>>,
-}
+            orderbook.apply_events(&user_info, &events).map_err(|e| {
+                anyhow!("Failed to update orderbook state after fee tiers update: {e}")
+            })?;
 
-impl AuthHeaders {
-    fn from_headers(headers: &HeaderMap) -> Result<Self, AppError> {
-        let identity = headers
-            .get(IDENTITY_HEADER)
-            .and_then(|v| v.to_str().ok())
-            .ok_or_else(|| {
-                AppError(
-                    StatusCode::UNAUTHORIZED,
-                    anyhow::anyhow!("Missing identity"),
-                )
-            })?
-            .to_string();
+            let action_id = self
+                .router_ctx
+                .action_id_counter
+                .fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events, updates)
+        };
 
-        let public_key: Option<Vec<u8>> = headers
-            .get(PUBLIC_KEY_HEADER)
-            .and_then(|v| v.to_str().ok())
-            .and_then(|s| hex::decode(s).ok());
+        tracing::info!("Submitting fee tier update for {} users", updates.len());
 
-        let signature = headers
-            .get(SIGNATURE_HEADER)
-            .and_then(|v| v.to_str().ok())
-            .and_then(|s| hex::decode(s).ok());
+        let _ = process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::UpdateFeeTiers,
+            action_id,
+            &UpdateFeeTiersPrivateInput { updates },
+            &self.router_ctx,
+        )
+        .map_err(|AppError(_, inner)| anyhow!("Failed to submit fee tiers update: {inner}"))?;
 
-        Ok(AuthHeaders {
-            identity,
-            public_key,
-            signature,
-        })
+        Ok(())
     }
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct CreatePairRequest {
-    pub base_contract: String,
-    pub quote_contract: String,
-}
 
-#[derive(Serialize, Deserialize, Debug)]
-struct SubmitProverRequest {
-    pub secret: String,
-    pub blob_tx: BlobTransaction,
-    pub prover_request: OrderbookProverRequest,
-}
+    async fn execute_index_prices_update(&self, index_prices: Vec<(Pair, u64)>) -> Result<()> {
+        let ctx = &self.router_ctx;
+        let block_height = ctx.client.recent_block_height().await?.0;
+
+        let (user_info, prices) = {
+            let orderbook = ctx.orderbook.read().await;
+            // Only submit the prices tracked by a circuit breaker, or valuing margin accounts
+            let prices: Vec<(Pair, u64)> = index_prices
+                .into_iter()
+                .filter(|(pair, _)| {
+                    let tracked = orderbook
+                        .circuit_breakers
+                        .get(pair)
+                        .is_some_and(|breaker| !breaker.is_halted(block_height));
+                    let values_collateral = orderbook
+                        .assets_info
+                        .get(&pair.0)
+                        .and_then(|asset_info| asset_info.collateral.as_ref())
+                        .is_some_and(|collateral| collateral.config.quote == pair.1);
+                    tracked || values_collateral || orderbook.perp_markets.contains_key(pair)
+                })
+                .collect();
+            let user_info = orderbook
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
+            (user_info, prices)
+        };
+        if prices.is_empty() {
+            debug!("No circuit breaker nor margin account uses the index prices");
+            return Ok(());
+        }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct DepositRequest {
-    pub symbol: String,
-    pub amount: u64,
-}
+        let pairs: Vec<Pair> = prices.iter().map(|(pair, _)| pair.clone()).collect();
+        let (action_id, events) = execute_on_pairs(
+            ctx,
+            "update_index_prices",
+            "update_index_prices",
+            &pairs,
+            &user_info,
+            |orderbook| {
+                orderbook
+                    .update_index_prices(&user_info, &prices, block_height)
+                    .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))
+            },
+        )
+        .await
+        .map_err(|AppError(_, inner)| anyhow!("Failed to update index prices: {inner}"))?;
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct CancelOrderRequest {
-    pub order_id: String,
-}
+        let _ = process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::UpdateIndexPrices {
+                prices,
+                block_height,
+            },
+            action_id,
+            &(),
+            ctx,
+        )
+        .map_err(|AppError(_, inner)| anyhow!("Failed to submit index prices update: {inner}"))?;
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct WithdrawRequest {
-    pub symbol: String,
-    pub amount: u64,
-    pub destination: WithdrawDestination,
+        Ok(())
+    }
 }
 
-// API-friendly representation of OrderManager for JSON serialization
-#[derive(Debug, Clone, Serialize)]
-pub struct OrderManagerAPI {
-    pub orders: HashMap<String, Order>,
-    pub bid_orders: HashMap<String, HashMap<String, std::collections::VecDeque<String>>>,
-    pub ask_orders: HashMap<String, HashMap<String, std::collections::VecDeque<String>>>,
-    pub orders_owner: HashMap<String, String>,
-}
+impl OrderbookModule {
+    /// Settles the funding of the active perp markets whose index price is fresh, one action
+    /// per market
+    async fn execute_funding_settlement(&self) -> Result<()> {
+        let ctx = &self.router_ctx;
+
+        let markets: Vec<(Pair, BookDepth)> = {
+            let orderbook = ctx.orderbook.read().await;
+            // Halted and delisted markets do not trade, their positions pay no funding
+            orderbook
+                .perp_markets
+                .keys()
+                .filter(|pair| orderbook.pairs_status.get(*pair) == Some(&MarketStatus::Active))
+                .map(|pair| (pair.clone(), BookDepth::of(&orderbook.order_manager, pair)))
+                .collect()
+        };
 
-impl From<&orderbook::order_manager::OrderManager> for OrderManagerAPI {
-    fn from(manager: &orderbook::order_manager::OrderManager) -> Self {
-        let orders_owner = manager
-            .orders_owner
-            .iter()
-            .map(|(order_id, owner_key)| (order_id.clone(), hex::encode(owner_key.0.as_slice())))
-            .collect();
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+        let max_age_ms = self.funding.max_index_age_secs as i64 * 1000;
+        for (pair, depth) in markets {
+            let symbol = format!("{}/{}", pair.0, pair.1);
+            let index_price = ctx
+                .index_price_service
+                .latest(&symbol)
+                .await
+                .map_err(|e| anyhow!("Failed to read the index price of {symbol}: {}", e.1))?;
+            let Some(index_price) =
+                index_price.filter(|index| now_ms - index.updated_at <= max_age_ms)
+            else {
+                warn!("No fresh index price for {symbol}, its funding is not settled");
+                continue;
+            };
 
-        // Convert u64 price keys to strings and pair tuples to strings for JSON serialization
-        let bid_orders = manager
-            .bid_orders
-            .iter()
-            .map(|(pair, price_map)| {
-                let api_price_map = price_map
-                    .iter()
-                    .map(|(price, orders)| (price.to_string(), orders.clone()))
-                    .collect();
-                let pair_string = format!("{}-{}", pair.0, pair.1);
-                (pair_string, api_price_map)
-            })
-            .collect();
+            let payment = funding_payment(
+                &self.funding,
+                depth.best_bid,
+                depth.best_ask,
+                index_price.price as u64,
+            );
+            debug!(
+                "Funding of {symbol} is {payment} per unit (index price {}, best bid {:?}, best ask {:?})",
+                index_price.price, depth.best_bid, depth.best_ask
+            );
 
-        let ask_orders = manager
-            .ask_orders
-            .iter()
-            .map(|(pair, price_map)| {
-                let api_price_map = price_map
-                    .iter()
-                    .map(|(price, orders)| (price.to_string(), orders.clone()))
-                    .collect();
-                let pair_string = format!("{}-{}", pair.0, pair.1);
-                (pair_string, api_price_map)
-            })
-            .collect();
+            // Read for each market, as the operator nonce moves with each settlement
+            let user_info = ctx
+                .orderbook
+                .read()
+                .await
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
+            let (action_id, events) = execute_on_pairs(
+                ctx,
+                "settle_funding",
+                "settle_funding",
+                std::slice::from_ref(&pair),
+                &user_info,
+                |orderbook| {
+                    orderbook
+                        .settle_funding(&user_info, &pair, payment)
+                        .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))
+                },
+            )
+            .await
+            .map_err(|AppError(_, inner)| {
+                anyhow!("Failed to settle funding of {symbol}: {inner}")
+            })?;
 
-        OrderManagerAPI {
-            orders: manager.orders.clone(),
-            bid_orders,
-            ask_orders,
-            orders_owner,
+            let _ = process_orderbook_action(
+                user_info,
+                events,
+                PermissionedOrderbookAction::SettleFunding { pair, payment },
+                action_id,
+                &(),
+                ctx,
+            )
+            .map_err(|AppError(_, inner)| {
+                anyhow!("Failed to submit funding settlement of {symbol}: {inner}")
+            })?;
         }
+
+        Ok(())
     }
-}
 
-// API-friendly representation of the state for JSON serialization
-#[derive(Debug, Clone, Serialize)]
-pub struct ExecuteStateAPI {
-    pub assets_info: HashMap<String, AssetInfo>,
-    pub users_info: HashMap<String, UserInfo>,
-    pub balances: HashMap<String, HashMap<String, orderbook::model::Balance>>,
-    pub order_manager: OrderManagerAPI,
-}
+    /// Force-closes the positions of the accounts under their maintenance margin, one action
+    /// per position. A failed liquidation does not prevent the next ones.
+    async fn execute_liquidations(&self) -> Result<()> {
+        let ctx = &self.router_ctx;
+
+        let candidates = {
+            let orderbook = ctx.orderbook.read().await;
+            liquidation_candidates(&orderbook, self.liquidation.max_per_check)
+        };
+        if candidates.is_empty() {
+            return Ok(());
+        }
+        let block_height = ctx.client.recent_block_height().await?.0;
+
+        for (user, pair) in candidates {
+            let symbol = format!("{}/{}", pair.0, pair.1);
+            // Read for each position, as the operator nonce moves with each liquidation
+            let user_info = ctx
+                .orderbook
+                .read()
+                .await
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
+            // The operator nonce makes the id of the closing order unique
+            let order_id = format!("liquidation-{}", user_info.nonce);
+            let result = execute_on_pairs(
+                ctx,
+                "liquidate",
+                "liquidate",
+                std::slice::from_ref(&pair),
+                &user_info,
+                |orderbook| {
+                    orderbook
+                        .liquidate(&user_info, &user, &pair, &order_id, block_height)
+                        .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))
+                },
+            )
+            .await;
+            let (action_id, events) = match result {
+                Ok(result) => result,
+                Err(AppError(_, inner)) => {
+                    warn!("Failed to liquidate the {symbol} position of {user}: {inner}");
+                    continue;
+                }
+            };
+            info!("Liquidating the {symbol} position of {user} with order {order_id}");
+
+            let _ = process_orderbook_action(
+                user_info,
+                events,
+                PermissionedOrderbookAction::Liquidate {
+                    user: user.clone(),
+                    pair,
+                    order_id,
+                    block_height,
+                },
+                action_id,
+                &(),
+                ctx,
+            )
+            .map_err(|AppError(_, inner)| {
+                anyhow!("Failed to submit liquidation of the {symbol} position of {user}: {inner}")
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Reconciles the balances owed in each token backed asset against the tokens held by the
+    /// orderbook account. The held balances are read from the indexer in the background, not to
+    /// hold the module.
+    async fn spawn_reconciliation(&self) {
+        let ctx = &self.router_ctx;
+
+        let mut owed: BTreeMap<String, u128> = BTreeMap::new();
+        {
+            let orderbook = ctx.orderbook.read().await;
+            for (symbol, balances) in orderbook.balances.iter() {
+                *owed.entry(symbol.clone()).or_default() += balances
+                    .values()
+                    .map(|balance| balance.0 as u128)
+                    .sum::<u128>();
+            }
+            for user_info in orderbook.users_info.values() {
+                for withdrawal in user_info.pending_withdrawals.iter() {
+                    *owed.entry(withdrawal.symbol.clone()).or_default() +=
+                        withdrawal.amount as u128;
+                }
+            }
+        }
+
+        let owed_assets: Vec<OwedAsset> = {
+            let asset_service = ctx.asset_service.read().await;
+            owed.into_iter()
+                .filter_map(|(symbol, owed)| {
+                    let asset = asset_service.get_asset(&symbol)?;
+                    ctx.asset_backings.of(&asset.contract_name)?;
+                    Some(OwedAsset {
+                        symbol,
+                        contract_name: asset.contract_name.clone(),
+                        owed,
+                    })
+                })
+                .collect()
+        };
+
+        let reconciliation = ctx.reconciliation.clone();
+        tokio::spawn(async move { reconciliation.reconcile(owed_assets).await });
+    }
+
+    /// Releases the queued withdrawals whose delay is over, one action per withdrawal. They are
+    /// paid out by the bridge once the release settles. A failed release does not prevent the
+    /// next ones.
+    async fn execute_withdrawal_releases(&self) -> Result<()> {
+        let ctx = &self.router_ctx;
+
+        let block_height = ctx.client.recent_block_height().await?.0;
+        let releasable = {
+            let orderbook = ctx.orderbook.read().await;
+            releasable_withdrawals(
+                &orderbook,
+                block_height,
+                self.withdrawal_queue.max_per_check,
+            )
+        };
+
+        for (user, withdrawal) in releasable {
+            let (action_id, user_info, events) = {
+                let mut orderbook = ctx.orderbook.write().await;
+
+                let user_info = orderbook
+                    .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                    .unwrap_or_else(|_| {
+                        UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                    });
+
+                let events = match orderbook.release_withdrawal(
+                    &user_info,
+                    &user,
+                    &withdrawal,
+                    block_height,
+                ) {
+                    Ok(events) => events,
+                    Err(e) => {
+                        warn!(
+                            "Failed to release withdrawal {} of {user}: {e}",
+                            withdrawal.id
+                        );
+                        continue;
+                    }
+                };
+                orderbook
+                    .apply_events(&user_info, &events)
+                    .map_err(|e| anyhow!(e))?;
+
+                let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
+                (action_id, user_info, events)
+            };
+            info!(
+                "Releasing withdrawal {} of {user}: {} {}",
+                withdrawal.id, withdrawal.amount, withdrawal.symbol
+            );
+
+            let withdrawal_id = withdrawal.id;
+            let _ = process_orderbook_action(
+                user_info,
+                events,
+                PermissionedOrderbookAction::ReleaseWithdrawal {
+                    user: user.clone(),
+                    withdrawal,
+                    block_height,
+                },
+                action_id,
+                &(),
+                ctx,
+            )
+            .map_err(|AppError(_, inner)| {
+                anyhow!("Failed to submit release of withdrawal {withdrawal_id} of {user}: {inner}")
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+#[allow(dead_code)]
+struct RouterCtx {
+    pub bus: RouterBusClient,
+    pub orderbook_cn: ContractName,
+    pub default_state: orderbook::model::ExecuteState,
+    pub orderbook: Arc<RwLock<orderbook::model::ExecuteState>>,
+    pub pair_locks: Arc<PairLocks>,
+    pub lane_id: LaneId,
+    pub asset_service: Arc<RwLock<AssetService>>,
+    pub user_service: Arc<RwLock<UserService>>,
+    pub client: Arc<NodeClient>,
+    pub action_id_counter: Arc<AtomicU32>,
+    pub metrics: AppMetrics,
+    pub database_service: Arc<RwLock<DatabaseService>>,
+    pub admin_secret: String,
+    pub cancel_on_disconnect: Arc<Mutex<CancelOnDisconnectRegistry>>,
+    pub risk_manager: Arc<RwLock<RiskManager>>,
+    pub balance_feed: Arc<BalanceFeed>,
+    pub prover_service: Arc<ProverService>,
+    pub blob_outbox: Arc<BlobOutbox>,
+    pub worker_queues: Arc<WorkerQueues>,
+    pub address_book_service: Arc<AddressBookService>,
+    pub api_key_service: Arc<ApiKeyService>,
+    pub index_price_service: Arc<IndexPriceService>,
+    pub analytics_service: Arc<AnalyticsService>,
+    pub checkpoint_service: Arc<CheckpointService>,
+    pub ledger_service: Arc<LedgerService>,
+    pub ticker_service: Arc<TickerService>,
+    pub asset_backings: Arc<AssetBackings>,
+    pub bridge_pause: Arc<BridgePause>,
+    pub bridge_limits: Arc<BridgeLimits>,
+    pub reconciliation: Arc<Reconciliation>,
+    pub proven_state: Option<Arc<Mutex<FullState>>>,
+    pub clock: Clock,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub pool: PgPool,
+    pub health: HealthConfig,
+    pub audit_log: Arc<AuditLog>,
+    pub config_watcher: Arc<ConfigWatcher>,
+    pub settlement_check: Option<Arc<SettlementCheck>>,
+}
+
+impl RouterCtx {
+    async fn execute_deposit(&self, deposit: PendingDeposit) -> Result<()> {
+        let PendingDeposit {
+            sender,
+            contract_name,
+            amount,
+            ..
+        } = deposit;
+        let asset_service = self.asset_service.read().await;
+
+        let Identity(user) = sender;
+        let Some(symbol) = asset_service
+            .get_symbol_from_contract_name(&contract_name.0)
+            .await
+        else {
+            bail!(
+                "Could not deposit: Unknown contract name: {}",
+                contract_name.0
+            );
+        };
+        let amount_u64 =
+            u64::try_from(amount).context("Deposit amount exceeds supported range (u64)")?;
+
+        let (action_id, user_info, events) = {
+            let mut orderbook = self.orderbook.write().await;
+            let user_info = orderbook.get_user_info(&user).unwrap_or_else(|_| {
+                let mut salt = [0u8; 32];
+                rand::rng().fill_bytes(&mut salt);
+                UserInfo::new(user.clone(), salt.to_vec())
+            });
+
+            let events = orderbook
+                .deposit(&symbol, amount_u64, &user_info)
+                .map_err(|e| anyhow!("Failed to apply deposit on orderbook: {e}"))?;
+
+            orderbook
+                .apply_events(&user_info, &events)
+                .map_err(|e| anyhow!("Failed to update orderbook state after deposit: {e}"))?;
+
+            let action_id = self.action_id_counter.fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events)
+        };
+
+        let action_private_input = Vec::<u8>::new();
+
+        let orderbook_action = PermissionedOrderbookAction::Deposit {
+            symbol,
+            amount: amount_u64,
+        };
+
+        let _ = process_orderbook_action(
+            user_info,
+            events,
+            orderbook_action,
+            action_id,
+            &action_private_input,
+            self,
+        )
+        .map_err(|AppError(_, inner)| anyhow!("Failed to submit deposit action: {inner}"))?;
+
+        Ok(())
+    }
+
+    async fn cancel_lapsed_sessions(&self) -> Result<()> {
+        let lapsed = self.cancel_on_disconnect.lock().await.take_lapsed();
+        for session in lapsed {
+            _ = log_error!(
+                self.cancel_session_orders(session).await,
+                "could not cancel orders of lapsed session"
+            );
+        }
+        Ok(())
+    }
+
+    async fn cancel_session_orders(&self, session: LapsedSession) -> Result<()> {
+        let LapsedSession {
+            user,
+            public_key,
+            authorization,
+            timeout_secs,
+        } = session;
+        let CancelAuthorization {
+            nonce,
+            order_ids: signed_order_ids,
+            signature,
+        } = authorization;
+
+        // Cancellations change the books, so they are serialized with the order flow of their pairs
+        let pairs: Vec<Pair> = {
+            let orderbook = self.orderbook.read().await;
+            signed_order_ids
+                .iter()
+                .filter_map(|order_id| orderbook.order_manager.orders.get(order_id))
+                .map(|order| order.pair.clone())
+                .collect()
+        };
+        let _pair_guards = self.pair_locks.lock(&pairs).await;
+
+        let (action_id, user_info, events, order_ids) = {
+            let mut orderbook = self.orderbook.write().await;
+            let user_info = orderbook
+                .get_user_info(&user)
+                .map_err(|e| anyhow!("Could not cancel orders on disconnect: {e}"))?;
+            if user_info.nonce != nonce {
+                warn!(
+                    "Heartbeat of {user} lapsed, but its cancel-on-disconnect authorization was signed at nonce {nonce}, and the nonce is now {}",
+                    user_info.nonce
+                );
+                return Ok(());
+            }
+
+            // Orders that were filled or cancelled in the meantime are skipped, as in the contract
+            let user_key = user_info.get_key();
+            let order_ids: Vec<OrderId> = signed_order_ids
+                .iter()
+                .filter(|order_id| orderbook.get_order_owner(order_id) == Some(&user_key))
+                .cloned()
+                .collect();
+            if order_ids.is_empty() {
+                debug!("Heartbeat of {user} lapsed with no open order to cancel");
+                return Ok(());
+            }
+
+            let events = orderbook
+                .cancel_orders(&order_ids, &user_info)
+                .map_err(|e| anyhow!("Failed to cancel orders on orderbook: {e}"))?;
+
+            orderbook.apply_events(&user_info, &events).map_err(|e| {
+                anyhow!("Failed to update orderbook state after cancel on disconnect: {e}")
+            })?;
+
+            let action_id = self.action_id_counter.fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events, order_ids)
+        };
+
+        warn!(
+            "Heartbeat of {user} lapsed, cancelling {} orders placed with session key {}",
+            order_ids.len(),
+            hex::encode(&public_key)
+        );
+
+        let _ = process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::CancelOnDisconnect {
+                order_ids: signed_order_ids,
+            },
+            action_id,
+            &CancelOnDisconnectPrivateInput {
+                signature,
+                public_key,
+                timeout_secs,
+            },
+            self,
+        )
+        .map_err(|AppError(_, inner)| anyhow!("Failed to submit cancel on disconnect: {inner}"))?;
+
+        Ok(())
+    }
+}
+
+// --------------------------------------------------------
+//     Headers
+// --------------------------------------------------------
+
+const IDENTITY_HEADER: &str = "x-identity";
+const PUBLIC_KEY_HEADER: &str = "x-public-key";
+const SIGNATURE_HEADER: &str = "x-signature";
+const SESSION_PERMISSIONS_HEADER: &str = "x-session-permissions";
+const SESSION_PAIR_HEADER: &str = "x-session-pair";
+const API_KEY_HEADER: &str = "x-api-key";
+/// Name of the sub-account of the identity the request acts as, if any
+const SUB_ACCOUNT_HEADER: &str = "x-sub-account";
+/// Admin secret of the admin reads, which have no body to carry it
+const ADMIN_SECRET_HEADER: &str = "x-admin-secret";
+/// Operator making an admin request, recorded as its actor in the audit log
+const ADMIN_ACTOR_HEADER: &str = "x-admin-actor";
+
+#[derive(Debug)]
+struct AuthHeaders {
+    identity: String,
+    public_key: Option<Vec<u8>>,
+    signature: Option<Vec<u8>>,
+}
+
+impl AuthHeaders {
+    fn from_headers(headers: &HeaderMap) -> Result<Self, AppError> {
+        let identity = headers
+            .get(IDENTITY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| {
+                AppError(
+                    StatusCode::UNAUTHORIZED,
+                    anyhow::anyhow!("Missing identity"),
+                )
+            })?
+            .to_string();
+        let identity = select_sub_account(identity, headers)?;
+
+        let public_key: Option<Vec<u8>> = headers
+            .get(PUBLIC_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| hex::decode(s).ok());
+
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| hex::decode(s).ok());
+
+        Ok(AuthHeaders {
+            identity,
+            public_key,
+            signature,
+        })
+    }
+
+    /// Reads the headers, the identity being authenticated by the `x-api-key` header if set.
+    /// Without API key, the identity header is only trusted by the endpoints verifying a
+    /// signature, and by the contract for actions.
+    async fn authenticate(ctx: &RouterCtx, headers: &HeaderMap) -> Result<Self, AppError> {
+        let Some(api_key) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) else {
+            return Self::from_headers(headers);
+        };
+        let key = ctx.api_key_service.authenticate(api_key).await?;
+        Self::from_api_key(key, headers)
+    }
+
+    /// Headers of a request authenticated by an API key: the identity header, if set, must be
+    /// the one of the key, and the session key defaults to the one the key is bound to
+    fn from_api_key(key: ApiKeyIdentity, headers: &HeaderMap) -> Result<Self, AppError> {
+        let identity = headers.get(IDENTITY_HEADER).and_then(|v| v.to_str().ok());
+        if identity.is_some_and(|identity| identity != key.identity) {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!(
+                    "API key does not belong to identity {}",
+                    identity.unwrap_or_default()
+                ),
+            ));
+        }
+        let public_key: Option<Vec<u8>> = headers
+            .get(PUBLIC_KEY_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| hex::decode(s).ok());
+        let public_key = match (public_key, key.session_public_key) {
+            (Some(public_key), Some(bound)) if public_key != bound => {
+                return Err(AppError(
+                    StatusCode::FORBIDDEN,
+                    anyhow::anyhow!("API key is bound to another session key"),
+                ));
+            }
+            (public_key, bound) => public_key.or(bound),
+        };
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| hex::decode(s).ok());
+
+        Ok(AuthHeaders {
+            identity: select_sub_account(key.identity, headers)?,
+            public_key,
+            signature,
+        })
+    }
+}
+
+/// Identity of the sub-account selected by the `x-sub-account` header, or the identity itself
+fn select_sub_account(identity: String, headers: &HeaderMap) -> Result<String, AppError> {
+    let Some(name) = headers
+        .get(SUB_ACCOUNT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|name| !name.is_empty())
+    else {
+        return Ok(identity);
+    };
+    if split_sub_account(&identity).is_some() {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            anyhow::anyhow!("Sub-account {identity} cannot have sub-accounts"),
+        ));
+    }
+    Ok(sub_account_identity(&identity, name))
+}
+
+/// Scope requested for a new session key. Keys registered without these headers are unrestricted.
+fn session_key_scope_from_headers(
+    headers: &HeaderMap,
+) -> Result<(SessionKeyPermissions, Option<Pair>), AppError> {
+    let permissions = match headers
+        .get(SESSION_PERMISSIONS_HEADER)
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(value) => value
+            .parse::<SessionKeyPermissions>()
+            .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?,
+        None => SessionKeyPermissions::ALL,
+    };
+
+    let pair = match headers
+        .get(SESSION_PAIR_HEADER)
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(value) => {
+            let (base, quote) = value.split_once('/').ok_or_else(|| {
+                AppError(
+                    StatusCode::BAD_REQUEST,
+                    anyhow::anyhow!("Invalid session key pair {value}, expected BASE/QUOTE"),
+                )
+            })?;
+            Some((base.to_string(), quote.to_string()))
+        }
+        None => None,
+    };
+
+    Ok((permissions, pair))
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CreatePairRequest {
+    pub secret: String,
+    pub base_contract: String,
+    pub quote_contract: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct CreatePerpMarketRequest {
+    pub secret: String,
+    /// Symbol of the synthetic base asset, e.g. "BTC-PERP"
+    pub base_symbol: String,
+    pub base_scale: i16,
+    pub quote_contract: String,
+    pub initial_margin_bps: u64,
+    pub maintenance_margin_bps: u64,
+    /// Penalty paid to the insurance fund on liquidations, none by default
+    #[serde(default)]
+    pub liquidation_penalty_bps: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct UpdatePairStatusRequest {
+    pub secret: String,
+    #[schema(value_type = (String, String))]
+    pub pair: Pair,
+    /// "halted" to halt the pair, "active" to resume it, "delisted" to delist it
+    pub status: MarketStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct SubmitProverRequest {
+    pub secret: String,
+    #[schema(value_type = Object)]
+    pub blob_tx: BlobTransaction,
+    #[schema(value_type = Object)]
+    pub prover_request: OrderbookProverRequest,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct SetRiskLimitsRequest {
+    pub secret: String,
+    pub identity: String,
+    /// New limits of the identity, or None to fall back to the default limits
+    pub limits: Option<RiskLimits>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct SetWithdrawLimitsRequest {
+    pub secret: String,
+    /// New withdraw limit of each symbol, or None to remove the limit
+    #[schema(value_type = Vec<(String, Option<WithdrawLimit>)>)]
+    pub updates: Vec<(Symbol, Option<WithdrawLimit>)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct SetWithdrawDelaysRequest {
+    pub secret: String,
+    /// New withdraw delay of each symbol, or None to pay out every withdrawal directly
+    #[schema(value_type = Vec<(String, Option<WithdrawDelay>)>)]
+    pub updates: Vec<(Symbol, Option<WithdrawDelay>)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct ReviewWithdrawalRequest {
+    pub secret: String,
+    pub identity: String,
+    pub withdrawal_id: u32,
+    /// Rejected withdrawals are refunded to the balance of the user
+    pub approve: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct UpdateBridgePauseRequest {
+    pub secret: String,
+    pub paused: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct SetCollateralsRequest {
+    pub secret: String,
+    /// New collateral config of each symbol, or None to stop accepting it as collateral
+    #[schema(value_type = Vec<(String, Option<CollateralConfig>)>)]
+    pub updates: Vec<(Symbol, Option<CollateralConfig>)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct SetCircuitBreakersRequest {
+    pub secret: String,
+    /// New circuit breaker of each pair, or None to remove it
+    #[schema(value_type = Vec<((String, String), Option<CircuitBreaker>)>)]
+    pub updates: Vec<(Pair, Option<CircuitBreaker>)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct UpdateConfigRequest {
+    pub secret: String,
+    /// Runtime settings replacing the running ones: `log_filter`, `rate_limit`, `fees` and
+    /// `database_workers`, as in the config file. The config files are read again when None.
+    #[schema(value_type = Option<Object>)]
+    pub settings: Option<RuntimeSettings>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+struct UpdateConfigResponse {
+    /// Settings that changed
+    pub changed: Vec<String>,
+    #[schema(value_type = Object)]
+    pub settings: RuntimeSettings,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+struct InsuranceFundResponse {
+    /// Protocol identity owning the fund
+    pub identity: String,
+    /// Balances of the fund, by symbol
+    #[schema(value_type = BTreeMap<String, u64>)]
+    pub balances: BTreeMap<Symbol, u64>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+struct EscapeProofResponse {
+    /// Commitment the proofs are against, to compare with the onchain state commitment
+    pub state_commitment: String,
+    pub user_key: String,
+    /// Leaf of the user in the users info tree
+    pub user_info: UserInfo,
+    /// Borsh encoded `EscapePrivateInput` of the escape transaction, proving the user info leaf
+    pub private_input: String,
+    pub balances: Vec<EscapeBalance>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+struct EscapeBalance {
+    #[schema(value_type = String)]
+    pub symbol: Symbol,
+    /// Token contract the escaped amount is transferred on
+    pub contract_name: String,
+    /// Leaf of the user in the balances tree of the symbol
+    pub balance: u64,
+    /// Balance plus the quantity of the open orders, transferred by the escape
+    pub escaped: u64,
+    /// Borsh encoded `BorshableMerkleProof` of the balance leaf, unset without a balances tree
+    pub proof: Option<String>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+struct BridgeStatusResponse {
+    /// Whether the deposits and withdrawals of the bridge are held
+    pub paused: bool,
+    /// Pause of the orderbook contract, set through `/admin/bridge_pause`
+    pub contract_paused: bool,
+    /// Pause forced by the config, whatever the contract
+    pub forced: bool,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+struct CircuitBreakersResponse {
+    /// Block the halts are evaluated at
+    pub block_height: u64,
+    pub circuit_breakers: Vec<PairCircuitBreaker>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+struct PairCircuitBreaker {
+    #[schema(value_type = (String, String))]
+    pub pair: Pair,
+    #[serde(flatten)]
+    pub state: CircuitBreakerState,
+    /// Whether the orders that would trade on the pair are refused
+    pub halted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct OnboardUserRequest {
+    pub identity: String,
+    /// Hex encoded public key of the pre-approved session key
+    pub public_key: String,
+    /// Scope of the session key, in the format of the session permissions header. All by default.
+    pub permissions: Option<String>,
+    /// Pair the session key is restricted to
+    #[schema(value_type = Option<(String, String)>)]
+    pub pair: Option<Pair>,
+    /// Initial limits of the identity, the default limits applying otherwise
+    pub risk_limits: Option<RiskLimits>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct OnboardUsersRequest {
+    pub secret: String,
+    pub users: Vec<OnboardUserRequest>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct DepositRequest {
+    pub symbol: String,
+    pub amount: u64,
+}
+
+/// Longest client order id accepted on an order
+pub const MAX_CLIENT_ORDER_ID_LEN: usize = 64;
+/// Longest free-form tag accepted on an order
+pub const MAX_ORDER_TAG_LEN: usize = 128;
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct CreateOrderRequest {
+    #[serde(flatten)]
+    pub order: Order,
+    /// Identifier of the order in the client's own systems, echoed in order events
+    pub client_order_id: Option<String>,
+    /// Free-form label, echoed in order events
+    pub tag: Option<String>,
+}
+
+impl CreateOrderRequest {
+    fn order_tag(&self) -> Result<Option<OrderTag>, AppError> {
+        if self
+            .client_order_id
+            .as_ref()
+            .is_some_and(|id| id.len() > MAX_CLIENT_ORDER_ID_LEN)
+        {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!(
+                    "Client order id cannot be longer than {MAX_CLIENT_ORDER_ID_LEN} bytes"
+                ),
+            ));
+        }
+        if self
+            .tag
+            .as_ref()
+            .is_some_and(|tag| tag.len() > MAX_ORDER_TAG_LEN)
+        {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Order tag cannot be longer than {MAX_ORDER_TAG_LEN} bytes"),
+            ));
+        }
+
+        if self.client_order_id.is_none() && self.tag.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(OrderTag {
+            client_order_id: self.client_order_id.clone(),
+            tag: self.tag.clone(),
+        }))
+    }
+}
+
+/// Collects the client identifiers of orders, keyed by exchange order id
+fn order_tags(requests: &[CreateOrderRequest]) -> Result<HashMap<OrderId, OrderTag>, AppError> {
+    let mut order_tags = HashMap::new();
+    for request in requests {
+        if let Some(order_tag) = request.order_tag()? {
+            order_tags.insert(request.order.order_id.clone(), order_tag);
+        }
+    }
+    Ok(order_tags)
+}
+
+/// Orders placed atomically on several pairs, signed with a single signature
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CreateOrdersRequest {
+    pub orders: Vec<CreateOrderRequest>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CancelOrderRequest {
+    pub order_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct WithdrawRequest {
+    pub symbol: String,
+    pub amount: u64,
+    pub destination: WithdrawDestination,
+}
+
+/// Dust balances sold for `quote` in a single action, each by the market order of its sweep
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SweepDustRequest {
+    #[schema(value_type = String)]
+    pub quote: Symbol,
+    pub sweeps: Vec<DustSweep>,
+}
+
+/// Balance moved to another account of the orderbook, such as a sub-account of the caller
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TransferRequest {
+    pub to: String,
+    pub symbol: String,
+    pub amount: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SaveAddressRequest {
+    pub destination: WithdrawDestination,
+    pub label: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct DeleteAddressRequest {
+    pub destination: WithdrawDestination,
+}
+
+/// Signed with `{identity}:create_api_key:{label}`
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CreateApiKeyRequest {
+    pub label: String,
+    /// Bind the key to the session key signing the request: requests authenticated by the
+    /// API key can then only use that session key
+    #[serde(default)]
+    pub bind_session_key: bool,
+}
+
+/// Signed with `{identity}:revoke_api_key:{key_id}`
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RevokeApiKeyRequest {
+    pub key_id: i64,
+}
+
+/// Query parameters of the cancel-on-disconnect WebSocket.
+/// Passed in the query string as browsers cannot set headers on WebSocket connections.
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CancelOnDisconnectRequest {
+    pub identity: String,
+    /// Hex encoded session key
+    pub public_key: String,
+    /// Hex encoded signature of
+    /// `{identity}:{nonce}:cancel_on_disconnect:{timeout_secs}:{order_ids}`
+    pub signature: String,
+    pub timeout_secs: u64,
+    /// Current nonce of the identity
+    pub nonce: u32,
+    /// Comma separated ids of the orders to cancel, as in the signed message
+    #[serde(default)]
+    pub order_ids: String,
+}
+
+/// Message sent on the cancel-on-disconnect WebSocket once the user acted, to authorize the
+/// cancellation of its new set of orders at its new nonce
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct CancelOnDisconnectUpdate {
+    pub nonce: u32,
+    pub order_ids: Vec<OrderId>,
+    /// Hex encoded signature of
+    /// `{identity}:{nonce}:cancel_on_disconnect:{timeout_secs}:{order_ids}`
+    pub signature: String,
+}
+
+/// Query parameters of the prover status
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProverStatusRequest {
+    /// Tx to report the status of, instead of the latest ones
+    pub tx_hash: Option<String>,
+}
+
+/// Query parameters of the state checkpoints
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CheckpointsRequest {
+    /// Only the checkpoints of earlier commits, to page through them
+    pub before_commit_id: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Query parameters of the ledger of the user
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LedgerRequest {
+    /// Symbol of the asset
+    pub asset: String,
+    /// Only the entries of later commits, to page through them
+    pub after_commit_id: Option<i64>,
+    /// Maximum number of commits covered
+    pub limit: Option<i64>,
+}
+
+/// Query parameters of the balances WebSocket
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BalancesFeedRequest {
+    pub identity: String,
+}
+
+// API-friendly representation of OrderManager for JSON serialization
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OrderManagerAPI {
+    pub orders: HashMap<String, Order>,
+    #[schema(value_type = HashMap<String, HashMap<String, Vec<String>>>)]
+    pub bid_orders: HashMap<String, HashMap<String, std::collections::VecDeque<String>>>,
+    #[schema(value_type = HashMap<String, HashMap<String, Vec<String>>>)]
+    pub ask_orders: HashMap<String, HashMap<String, std::collections::VecDeque<String>>>,
+    pub orders_owner: HashMap<String, String>,
+}
+
+impl From<&orderbook::order_manager::OrderManager> for OrderManagerAPI {
+    fn from(manager: &orderbook::order_manager::OrderManager) -> Self {
+        let orders_owner = manager
+            .orders_owner
+            .iter()
+            .map(|(order_id, owner_key)| (order_id.clone(), hex::encode(owner_key.0.as_slice())))
+            .collect();
+
+        // Convert u64 price keys to strings and pair tuples to strings for JSON serialization
+        let bid_orders = manager
+            .bid_orders
+            .iter()
+            .map(|(pair, price_map)| {
+                let api_price_map = price_map
+                    .iter()
+                    .map(|(price, orders)| (price.to_string(), orders.iter().cloned().collect()))
+                    .collect();
+                let pair_string = format!("{}-{}", pair.0, pair.1);
+                (pair_string, api_price_map)
+            })
+            .collect();
+
+        let ask_orders = manager
+            .ask_orders
+            .iter()
+            .map(|(pair, price_map)| {
+                let api_price_map = price_map
+                    .iter()
+                    .map(|(price, orders)| (price.to_string(), orders.iter().cloned().collect()))
+                    .collect();
+                let pair_string = format!("{}-{}", pair.0, pair.1);
+                (pair_string, api_price_map)
+            })
+            .collect();
+
+        OrderManagerAPI {
+            orders: manager.orders.clone(),
+            bid_orders,
+            ask_orders,
+            orders_owner,
+        }
+    }
+}
+
+// API-friendly representation of the state for JSON serialization
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ExecuteStateAPI {
+    pub assets_info: HashMap<String, AssetInfo>,
+    pub users_info: HashMap<String, UserInfo>,
+    pub balances: HashMap<String, HashMap<String, orderbook::model::Balance>>,
+    pub order_manager: OrderManagerAPI,
+}
 
 impl From<&orderbook::model::ExecuteState> for ExecuteStateAPI {
     fn from(state: &orderbook::model::ExecuteState) -> Self {
         let balances = state
             .balances
             .iter()
-            .map(|(symbol, balance_map)| {
-                let api_balance_map = balance_map
-                    .iter()
-                    .map(|(key, balance)| (hex::encode(key.0.as_slice()), balance.clone()))
-                    .collect();
-                (symbol.clone(), api_balance_map)
+            .map(|(symbol, balance_map)| {
+                let api_balance_map = balance_map
+                    .iter()
+                    .map(|(key, balance)| (hex::encode(key.0.as_slice()), balance.clone()))
+                    .collect();
+                (symbol.clone(), api_balance_map)
+            })
+            .collect();
+
+        ExecuteStateAPI {
+            assets_info: state.assets_info.clone(),
+            users_info: state.users_info.clone(),
+            balances,
+            order_manager: OrderManagerAPI::from(&state.order_manager),
+        }
+    }
+}
+
+// --------------------------------------------------------
+//     Routes
+// --------------------------------------------------------
+#[utoipa::path(
+    get,
+    path = "/state",
+    tag = "status",
+    responses(
+        (status = 200, description = "Whole orderbook state, for debugging", body = ExecuteStateAPI),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_state(State(ctx): State<RouterCtx>) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_state";
+
+    let result = async {
+        let lock_start = Instant::now();
+        let orderbook = ctx.orderbook.read().await;
+        ctx.metrics.record_lock(lock_start.elapsed(), "get_state");
+
+        let api_state = ExecuteStateAPI::from(&*orderbook);
+        Ok(Json(api_state))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/submit_prover_request",
+    tag = "admin",
+    request_body = SubmitProverRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn submit_prover_request(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<SubmitProverRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "submit_prover_request";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+
+        let tx_hash = request.blob_tx.hashed();
+        if tx_hash != request.prover_request.tx_hash {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("tx_hash mismatch"),
+            ));
+        }
+
+        let mut bus = ctx.bus.clone();
+        let context = Span::current().context();
+        bus.send(DatabaseRequest::WriteEvents {
+            user: UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new()),
+            tx_hash: tx_hash.clone(),
+            blob_tx: request.blob_tx,
+            prover_request: request.prover_request,
+            order_tags: HashMap::new(),
+            context,
+        })?;
+
+        Ok(Json(tx_hash))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/risk_limits",
+    tag = "admin",
+    request_body = SetRiskLimitsRequest,
+    responses(
+        (status = 200, description = "Limits now applying to the identity", body = RiskLimits),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn set_risk_limits(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<SetRiskLimitsRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "set_risk_limits";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+
+        let mut risk_manager = ctx.risk_manager.write().await;
+        risk_manager
+            .set_limits(&request.identity, request.limits)
+            .await
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+        Ok(Json(risk_manager.limits_for(&request.identity)))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/withdraw_limits",
+    tag = "admin",
+    request_body = SetWithdrawLimitsRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn set_withdraw_limits(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<SetWithdrawLimitsRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "set_withdraw_limits";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+
+        let (action_id, user_info, events) = {
+            let mut orderbook = ctx.orderbook.write().await;
+
+            let user_info = orderbook
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
+
+            let events = orderbook
+                .update_withdraw_limits(&user_info, &request.updates)
+                .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?;
+
+            orderbook
+                .apply_events(&user_info, &events)
+                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+
+            let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events)
+        };
+
+        process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::UpdateWithdrawLimits {
+                updates: request.updates,
+            },
+            action_id,
+            &(),
+            &ctx,
+        )
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/withdraw_delays",
+    tag = "admin",
+    request_body = SetWithdrawDelaysRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn set_withdraw_delays(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<SetWithdrawDelaysRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "set_withdraw_delays";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+
+        let (action_id, user_info, events) = {
+            let mut orderbook = ctx.orderbook.write().await;
+
+            let user_info = orderbook
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
+
+            let events = orderbook
+                .update_withdraw_delays(&user_info, &request.updates)
+                .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?;
+
+            orderbook
+                .apply_events(&user_info, &events)
+                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+
+            let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events)
+        };
+
+        process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::UpdateWithdrawDelays {
+                updates: request.updates,
+            },
+            action_id,
+            &(),
+            &ctx,
+        )
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Approves a queued withdrawal, or rejects it and refunds the user
+#[utoipa::path(
+    post,
+    path = "/admin/withdrawals/review",
+    tag = "admin",
+    request_body = ReviewWithdrawalRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn review_withdrawal(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<ReviewWithdrawalRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "review_withdrawal";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+
+        let (action_id, user_info, events) = {
+            let mut orderbook = ctx.orderbook.write().await;
+
+            let user_info = orderbook
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
+
+            let events = orderbook
+                .review_withdrawal(
+                    &user_info,
+                    &request.identity,
+                    request.withdrawal_id,
+                    request.approve,
+                )
+                .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?;
+
+            orderbook
+                .apply_events(&user_info, &events)
+                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+
+            let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events)
+        };
+
+        info!(
+            "Withdrawal {} of {} {}",
+            request.withdrawal_id,
+            request.identity,
+            if request.approve {
+                "approved"
+            } else {
+                "rejected"
+            }
+        );
+
+        process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::ReviewWithdrawal {
+                user: request.identity,
+                withdrawal_id: request.withdrawal_id,
+                approve: request.approve,
+            },
+            action_id,
+            &(),
+            &ctx,
+        )
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Pauses or resumes the deposits and withdrawals of the bridge. Trading and the transfers to
+/// and from Hyli token contracts go on.
+#[utoipa::path(
+    post,
+    path = "/admin/bridge_pause",
+    tag = "admin",
+    request_body = UpdateBridgePauseRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn update_bridge_pause(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<UpdateBridgePauseRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "update_bridge_pause";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+
+        let (action_id, user_info, events) = {
+            let mut orderbook = ctx.orderbook.write().await;
+
+            let user_info = orderbook
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
+
+            let events = orderbook
+                .update_bridge_pause(&user_info, request.paused)
+                .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?;
+
+            orderbook
+                .apply_events(&user_info, &events)
+                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+            ctx.bridge_pause.set(request.paused);
+
+            let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events)
+        };
+        info!("Bridge paused: {}", request.paused);
+
+        process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::UpdateBridgePause {
+                paused: request.paused,
+            },
+            action_id,
+            &(),
+            &ctx,
+        )
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/collaterals",
+    tag = "admin",
+    request_body = SetCollateralsRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn set_collaterals(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<SetCollateralsRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "set_collaterals";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+
+        let (action_id, user_info, events) = {
+            let mut orderbook = ctx.orderbook.write().await;
+
+            let user_info = orderbook
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
+
+            let events = orderbook
+                .update_collaterals(&user_info, &request.updates)
+                .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?;
+
+            orderbook
+                .apply_events(&user_info, &events)
+                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+
+            let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events)
+        };
+
+        process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::UpdateCollaterals {
+                updates: request.updates,
+            },
+            action_id,
+            &(),
+            &ctx,
+        )
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/onboard_users",
+    tag = "admin",
+    request_body = OnboardUsersRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn onboard_users(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<OnboardUsersRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "onboard_users";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+
+        let mut users = Vec::with_capacity(request.users.len());
+        for user in &request.users {
+            let public_key =
+                hex::decode(user.public_key.trim_start_matches("0x")).map_err(|e| {
+                    AppError(
+                        StatusCode::BAD_REQUEST,
+                        anyhow::anyhow!("Invalid public key for {}: {e}", user.identity),
+                    )
+                })?;
+            let permissions = match &user.permissions {
+                Some(value) => value
+                    .parse::<SessionKeyPermissions>()
+                    .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?,
+                None => SessionKeyPermissions::ALL,
+            };
+            let mut salt = [0u8; 32];
+            rand::rng().fill_bytes(&mut salt);
+            users.push(OnboardedUser {
+                user: user.identity.clone(),
+                salt: salt.to_vec(),
+                public_key,
+                permissions,
+                pair: user.pair.clone(),
+            });
+        }
+
+        let (action_id, user_info, events) = {
+            let mut orderbook = ctx.orderbook.write().await;
+
+            let user_info = orderbook
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
+
+            let events = orderbook
+                .onboard_users(&user_info, &users)
+                .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?;
+
+            orderbook
+                .apply_events(&user_info, &events)
+                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+
+            let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events)
+        };
+
+        // Limits are enforced offchain, so they are only set once the identities are registered
+        {
+            let mut risk_manager = ctx.risk_manager.write().await;
+            for user in &request.users {
+                if let Some(limits) = user.risk_limits {
+                    risk_manager
+                        .set_limits(&user.identity, Some(limits))
+                        .await
+                        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+                }
+            }
+        }
+
+        process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::OnboardUsers,
+            action_id,
+            &OnboardUsersPrivateInput { users },
+            &ctx,
+        )
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Replaces the runtime settings of the config, as SIGHUP does, without a restart. Invalid
+/// settings are rejected and the running ones kept.
+#[utoipa::path(
+    post,
+    path = "/admin/config",
+    tag = "admin",
+    request_body = UpdateConfigRequest,
+    responses(
+        (status = 200, body = UpdateConfigResponse),
+        (status = 400, description = "Invalid settings"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn update_config(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+    Json(request): Json<UpdateConfigRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "update_config";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+        let actor = headers
+            .get(ADMIN_ACTOR_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("admin");
+
+        let changed = match request.settings {
+            Some(settings) => ctx.config_watcher.apply(settings, actor).await,
+            None => ctx.config_watcher.reload(actor).await,
+        }
+        .map_err(|e| AppError(StatusCode::BAD_REQUEST, e))?;
+        Ok(Json(UpdateConfigResponse {
+            changed,
+            settings: ctx.config_watcher.settings().await,
+        }))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Entries of the audit log, newest first. Reads have no body, so the admin secret is sent in
+/// the `x-admin-secret` header.
+#[utoipa::path(
+    get,
+    path = "/admin/audit_log",
+    tag = "admin",
+    params(
+        AuditQuery,
+        ("x-admin-secret" = String, Header, description = "Admin secret"),
+    ),
+    responses(
+        (status = 200, body = Vec<AuditRecord>),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, headers)))]
+async fn get_audit_log(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_audit_log";
+
+    let result = async {
+        let secret = headers
+            .get(ADMIN_SECRET_HEADER)
+            .and_then(|v| v.to_str().ok());
+        if secret != Some(ctx.admin_secret.as_str()) {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+        let entries = ctx
+            .audit_log
+            .entries(&query)
+            .await
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        Ok(Json(entries))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Circuit breaker state of the node client, for monitoring
+#[utoipa::path(
+    get,
+    path = "/node_health",
+    tag = "status",
+    responses(
+        (status = 200, body = NodeClientHealth),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_node_health(State(ctx): State<RouterCtx>) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_node_health";
+
+    let health = ctx.client.health();
+    ctx.metrics.record_request(request_start, endpoint, 200);
+
+    Ok(Json(health))
+}
+
+/// Liveness probe: fails when the orderbook state cannot be locked, i.e. the matching engine
+/// is stuck and the server should be restarted
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "status",
+    responses(
+        (status = 200, body = HealthReport),
+        (status = 503, description = "A check failed", body = HealthReport),
+    )
+)]
+async fn get_healthz(State(ctx): State<RouterCtx>) -> impl IntoResponse {
+    let timeout = Duration::from_millis(ctx.health.check_timeout_ms);
+    let report = HealthReport::new(vec![check_orderbook_lock(&ctx, timeout).await]);
+    (report.status_code(), Json(report))
+}
+
+/// Readiness probe: fails while the server cannot serve actions, or they do not settle
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "status",
+    responses(
+        (status = 200, body = HealthReport),
+        (status = 503, description = "A check failed", body = HealthReport),
+    )
+)]
+async fn get_readyz(State(ctx): State<RouterCtx>) -> impl IntoResponse {
+    let timeout = Duration::from_millis(ctx.health.check_timeout_ms);
+    let (database, settlement, orderbook_lock) = tokio::join!(
+        HealthCheck::run("database", timeout, health::check_database(&ctx.pool)),
+        HealthCheck::run(
+            "settlement",
+            timeout,
+            health::check_settlement(&ctx.pool, &ctx.health)
+        ),
+        check_orderbook_lock(&ctx, timeout),
+    );
+    let mut checks = vec![database, settlement, orderbook_lock];
+    if ctx.health.check_node {
+        checks.push(HealthCheck::run("node", timeout, health::check_node(&ctx.client)).await);
+    }
+    let report = HealthReport::new(checks);
+    (report.status_code(), Json(report))
+}
+
+async fn check_orderbook_lock(ctx: &RouterCtx, timeout: Duration) -> HealthCheck {
+    HealthCheck::run("orderbook_lock", timeout, async {
+        let _orderbook = ctx.orderbook.read().await;
+        Ok(None)
+    })
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/prover/status",
+    tag = "status",
+    params(ProverStatusRequest),
+    responses(
+        (status = 200, body = ProverStatus),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_prover_status(
+    State(ctx): State<RouterCtx>,
+    Query(request): Query<ProverStatusRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_prover_status";
+
+    let result = async {
+        let status = ctx
+            .prover_service
+            .get_status(request.tx_hash.as_deref())
+            .await?;
+        if request.tx_hash.is_some() && status.txs.is_empty() {
+            return Err(AppError(
+                StatusCode::NOT_FOUND,
+                anyhow!("No prover request found for this tx"),
+            ));
+        }
+        Ok(Json(status))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    get,
+    path = "/checkpoints",
+    tag = "status",
+    params(CheckpointsRequest),
+    responses(
+        (status = 200, description = "Most recent first", body = Vec<StateCheckpointAPI>),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_checkpoints(
+    State(ctx): State<RouterCtx>,
+    Query(request): Query<CheckpointsRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_checkpoints";
+
+    let result = async {
+        let checkpoints = ctx
+            .checkpoint_service
+            .list(
+                request.before_commit_id,
+                request.limit.unwrap_or(MAX_CHECKPOINTS),
+            )
+            .await?;
+        Ok(Json(checkpoints))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    get,
+    path = "/checkpoints/latest",
+    tag = "status",
+    responses(
+        (status = 200, body = StateCheckpointAPI),
+        (status = 404, description = "No checkpoint published yet"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_latest_checkpoint(
+    State(ctx): State<RouterCtx>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_latest_checkpoint";
+
+    let result = async {
+        let checkpoint = ctx
+            .checkpoint_service
+            .list(None, 1)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                AppError(
+                    StatusCode::NOT_FOUND,
+                    anyhow!("No checkpoint published yet"),
+                )
+            })?;
+        Ok(Json(checkpoint))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Balances of a user and their Merkle proofs against the state of the prover, which is the last
+/// settled commitment once the pending proofs have landed. The escape transaction is built from
+/// them with `orderbook::utils::escape_transaction`, to withdraw once the orderbook has stalled.
+#[utoipa::path(
+    get,
+    path = "/escape_proof/{identity}",
+    tag = "status",
+    params(("identity" = String, Path)),
+    responses(
+        (status = 200, body = EscapeProofResponse),
+        (status = 404, description = "Unknown identity"),
+        (status = 503, description = "The prover does not run alongside the server"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_escape_proof(
+    State(ctx): State<RouterCtx>,
+    Path(identity): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_escape_proof";
+
+    let result = async {
+        let Some(proven_state) = &ctx.proven_state else {
+            return Err(AppError(
+                StatusCode::SERVICE_UNAVAILABLE,
+                anyhow!("Escape proofs are only served when the prover runs alongside the server"),
+            ));
+        };
+        let full = proven_state.lock().await;
+
+        let user_info = full
+            .state
+            .get_user_info(&identity)
+            .map_err(|e| AppError(StatusCode::NOT_FOUND, anyhow!(e)))?;
+        let user_key = user_info.get_key();
+
+        let user_info_proof = full
+            .users_info_mt
+            .merkle_proof(std::iter::once(&user_info))
+            .map_err(|e| {
+                AppError(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    anyhow!("Could not prove the user info of {identity}: {e}"),
+                )
+            })?;
+        let private_input = borsh::to_vec(&EscapePrivateInput {
+            user_info: user_info.clone(),
+            user_info_proof: BorshableMerkleProof(user_info_proof),
+        })
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow!(e)))?;
+
+        let mut balances = Vec::new();
+        for (symbol, escaped) in full.state.escape_balances(&user_key) {
+            let Some(asset_info) = full.state.assets_info.get(&symbol) else {
+                continue;
+            };
+            let balance = full.state.get_balance(&user_info, &symbol);
+            let proof = match full.balances_mt.get(&symbol) {
+                Some(tree) => {
+                    let proof = tree
+                        .merkle_proof(std::iter::once(&UserBalance {
+                            user_key,
+                            balance: balance.clone(),
+                        }))
+                        .map_err(|e| {
+                            AppError(
+                                StatusCode::INTERNAL_SERVER_ERROR,
+                                anyhow!("Could not prove the {symbol} balance of {identity}: {e}"),
+                            )
+                        })?;
+                    let proof = borsh::to_vec(&BorshableMerkleProof(proof))
+                        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow!(e)))?;
+                    Some(hex::encode(proof))
+                }
+                None => None,
+            };
+            balances.push(EscapeBalance {
+                contract_name: asset_info.contract_name.0.clone(),
+                symbol,
+                balance: balance.0,
+                escaped: escaped.0,
+                proof,
+            });
+        }
+        balances.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+        Ok(Json(EscapeProofResponse {
+            state_commitment: hex::encode(full.commit().0),
+            user_key: hex::encode(<[u8; 32]>::from(user_key)),
+            user_info,
+            private_input: hex::encode(private_input),
+            balances,
+        }))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// `symbol` is the pair as `BASE-QUOTE`, or `BASE/QUOTE` once url-encoded
+#[utoipa::path(
+    get,
+    path = "/analytics/pair/{symbol}",
+    tag = "status",
+    params(("symbol" = String, Path, description = "Pair, as BASE-QUOTE")),
+    responses(
+        (status = 200, body = PairAnalytics),
+        (status = 404, description = "Unknown pair"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_pair_analytics(
+    State(ctx): State<RouterCtx>,
+    Path(symbol): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_pair_analytics";
+
+    let result = async {
+        let symbol = symbol.to_uppercase().replace('-', "/");
+        let Some((base, quote)) = symbol.split_once('/') else {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow!("Invalid pair {symbol}, expected BASE-QUOTE"),
+            ));
+        };
+        let pair = (base.to_string(), quote.to_string());
+
+        let instrument_id = ctx
+            .asset_service
+            .read()
+            .await
+            .get_instrument(&symbol)
+            .map(|instrument| instrument.instrument_id)
+            .ok_or_else(|| AppError(StatusCode::NOT_FOUND, anyhow!("Unknown pair {symbol}")))?;
+
+        let book = {
+            let lock_start = Instant::now();
+            let orderbook = ctx.orderbook.read().await;
+            ctx.metrics
+                .record_lock(lock_start.elapsed(), "get_pair_analytics");
+            BookDepth::of(&orderbook.order_manager, &pair)
+        };
+        let trades_24h = ctx.analytics_service.get_trade_stats(instrument_id).await?;
+
+        Ok(Json(PairAnalytics {
+            symbol,
+            book,
+            trades_24h,
+        }))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// 24h statistics of a pair. `symbol` is the pair as `BASE-QUOTE`, or `BASE/QUOTE` once
+/// url-encoded.
+#[utoipa::path(
+    get,
+    path = "/ticker/{symbol}",
+    tag = "status",
+    params(("symbol" = String, Path, description = "Pair, as BASE-QUOTE")),
+    responses(
+        (status = 200, body = Ticker),
+        (status = 404, description = "Unknown pair"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_ticker(
+    State(ctx): State<RouterCtx>,
+    Path(symbol): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_ticker";
+
+    let result = async {
+        let symbol = symbol.to_uppercase().replace('-', "/");
+        let instrument_id = ctx
+            .asset_service
+            .read()
+            .await
+            .get_instrument(&symbol)
+            .map(|instrument| instrument.instrument_id)
+            .ok_or_else(|| AppError(StatusCode::NOT_FOUND, anyhow!("Unknown pair {symbol}")))?;
+
+        let ticker = ctx
+            .ticker_service
+            .tickers([(instrument_id, symbol)])
+            .await?
+            .pop()
+            .context("Missing ticker")?;
+        Ok(Json(ticker))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// 24h statistics of all the pairs
+#[utoipa::path(
+    get,
+    path = "/tickers",
+    tag = "status",
+    responses(
+        (status = 200, body = Vec<Ticker>),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_tickers(State(ctx): State<RouterCtx>) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_tickers";
+
+    let result = async {
+        let mut instruments: Vec<(i64, String)> = ctx
+            .asset_service
+            .read()
+            .await
+            .instruments()
+            .map(|instrument| (instrument.instrument_id, instrument.symbol.clone()))
+            .collect();
+        instruments.sort_by(|a, b| a.1.cmp(&b.1));
+
+        let tickers = ctx.ticker_service.tickers(instruments).await?;
+        Ok(Json(tickers))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    get,
+    path = "/risk_limits",
+    tag = "account",
+    security(("identity" = []), ("api_key" = [])),
+    responses(
+        (status = 200, body = RiskLimits),
+        (status = 401, description = "Missing or invalid authentication"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_risk_limits(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_risk_limits";
+
+    let result = async {
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let limits = ctx.risk_manager.read().await.limits_for(&auth.identity);
+        Ok(Json(limits))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Withdrawals of the user waiting in the queue
+#[utoipa::path(
+    get,
+    path = "/withdrawals/pending",
+    tag = "account",
+    security(("identity" = []), ("api_key" = [])),
+    responses(
+        (status = 200, body = Vec<PendingWithdrawal>),
+        (status = 401, description = "Missing or invalid authentication"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_pending_withdrawals(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_pending_withdrawals";
+
+    let result = async {
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let orderbook = ctx.orderbook.read().await;
+        let pending_withdrawals = orderbook
+            .get_user_info(&auth.identity)
+            .map(|user_info| user_info.pending_withdrawals)
+            .unwrap_or_default();
+        Ok(Json(pending_withdrawals))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Changes of the available balance of an asset of the user, oldest first, with the balance after
+/// each of them. Deposits, withdrawals, fills and fees are told apart from the events of their
+/// commit. Reserves are the funds locked by resting orders, released when they are cancelled.
+#[utoipa::path(
+    get,
+    path = "/my/ledger",
+    tag = "account",
+    params(LedgerRequest),
+    security(("identity" = []), ("api_key" = [])),
+    responses(
+        (status = 200, body = Vec<LedgerEntry>),
+        (status = 401, description = "Missing or invalid authentication"),
+        (status = 404, description = "Unknown asset"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_ledger(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+    Query(request): Query<LedgerRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_ledger";
+
+    let result = async {
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let asset_id = ctx
+            .asset_service
+            .read()
+            .await
+            .get_asset(&request.asset)
+            .map(|asset| asset.asset_id)
+            .ok_or_else(|| {
+                AppError(
+                    StatusCode::NOT_FOUND,
+                    anyhow!("Unknown asset {}", request.asset),
+                )
+            })?;
+        let ledger = ctx
+            .ledger_service
+            .ledger(
+                &auth.identity,
+                asset_id,
+                &request.asset,
+                request.after_commit_id,
+                request.limit.unwrap_or(MAX_LEDGER_COMMITS),
+            )
+            .await?;
+        Ok(Json(ledger))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Open positions of the user on the perp markets
+#[utoipa::path(
+    get,
+    path = "/positions",
+    tag = "account",
+    security(("identity" = []), ("api_key" = [])),
+    responses(
+        (status = 200, body = Vec<Position>),
+        (status = 401, description = "Missing or invalid authentication"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_positions(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_positions";
+
+    let result = async {
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let orderbook = ctx.orderbook.read().await;
+        let positions = orderbook
+            .get_user_info(&auth.identity)
+            .map(|user_info| user_info.positions)
+            .unwrap_or_default();
+        Ok(Json(positions))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Cross-margin accounts of the user, one per quote of its positions and debts
+#[utoipa::path(
+    get,
+    path = "/margin",
+    tag = "account",
+    security(("identity" = []), ("api_key" = [])),
+    responses(
+        (status = 200, body = Vec<MarginAccount>),
+        (status = 401, description = "Missing or invalid authentication"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_margin_accounts(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_margin_accounts";
+
+    let result = async {
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let orderbook = ctx.orderbook.read().await;
+        let Ok(user_info) = orderbook.get_user_info(&auth.identity) else {
+            return Ok(Json(Vec::new()));
+        };
+        let accounts = user_info
+            .margin_quotes()
+            .iter()
+            .map(|quote| orderbook.get_margin_account(&user_info, quote))
+            .collect::<Result<Vec<MarginAccount>, String>>()
+            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+        Ok(Json(accounts))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Names of the sub-accounts registered under the identity, selected with the `x-sub-account`
+/// header
+#[utoipa::path(
+    get,
+    path = "/sub_accounts",
+    tag = "account",
+    security(("identity" = []), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Names of the sub-accounts", body = Vec<String>),
+        (status = 401, description = "Missing or invalid authentication"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_sub_accounts(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_sub_accounts";
+
+    let result = async {
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let orderbook = ctx.orderbook.read().await;
+        let mut names: Vec<String> = orderbook
+            .users_info
+            .values()
+            .filter(|user| user.sub_account)
+            .filter_map(|user| match split_sub_account(&user.user) {
+                Some((parent, name)) if parent == auth.identity => Some(name.to_string()),
+                _ => None,
             })
             .collect();
+        names.sort();
+        Ok(Json(names))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Balances of the insurance fund, collecting liquidation penalties and absorbing the losses of
+/// bankrupt accounts
+#[utoipa::path(
+    get,
+    path = "/insurance_fund",
+    tag = "status",
+    responses(
+        (status = 200, body = InsuranceFundResponse),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_insurance_fund(State(ctx): State<RouterCtx>) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_insurance_fund";
+
+    let result = async {
+        let orderbook = ctx.orderbook.read().await;
+        let balances = match orderbook.get_user_info(INSURANCE_FUND_IDENTITY) {
+            Ok(fund) => orderbook
+                .balances
+                .keys()
+                .map(|symbol| (symbol.clone(), orderbook.get_balance(&fund, symbol).0))
+                .filter(|(_, balance)| *balance > 0)
+                .collect(),
+            // The fund is registered on its first penalty
+            Err(_) => BTreeMap::new(),
+        };
+        Ok(Json(InsuranceFundResponse {
+            identity: INSURANCE_FUND_IDENTITY.to_string(),
+            balances,
+        }))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    get,
+    path = "/nonce",
+    tag = "account",
+    security(("identity" = []), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Nonce the next action is signed at", body = u32),
+        (status = 401, description = "Missing or invalid authentication"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx), name="GET /nonce", fields(http.uri = "/nonce", http.method = "GET")))]
+async fn get_nonce(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_nonce";
+
+    let result = async {
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let user = auth.identity;
+
+        // TODO: do some checks on headers to verify identify the user
+
+        let lock_start = Instant::now();
+        let orderbook = ctx.orderbook.read().await;
+        ctx.metrics.record_lock(lock_start.elapsed(), "get_nonce");
+
+        let nonce = orderbook
+            .get_user_info(&user)
+            .map(|u| u.nonce)
+            .unwrap_or_default();
+
+        Ok(Json(nonce))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    get,
+    path = "/account/addresses",
+    tag = "account",
+    security(("identity" = []), ("api_key" = [])),
+    responses(
+        (status = 200, body = Vec<SavedAddress>),
+        (status = 401, description = "Missing or invalid authentication"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_saved_addresses(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_saved_addresses";
+
+    let result = async {
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let addresses = ctx.address_book_service.list(&auth.identity).await?;
+        Ok(Json(addresses))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/account/addresses",
+    tag = "account",
+    request_body = SaveAddressRequest,
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, body = SavedAddress),
+        (status = 401, description = "Missing or invalid authentication"),
+        (status = 400, description = "Invalid destination or label"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn save_address(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+    Json(request): Json<SaveAddressRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "save_address";
+
+    let result = async {
+        let user = verify_address_book_request(&ctx, &headers, |user| {
+            orderbook::utils::save_address_message(user, &request.destination, &request.label)
+        })
+        .await?;
+        let address = ctx
+            .address_book_service
+            .save(&user, &request.destination, &request.label)
+            .await?;
+        Ok(Json(address))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    delete,
+    path = "/account/addresses",
+    tag = "account",
+    request_body = DeleteAddressRequest,
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, description = "Address deleted"),
+        (status = 401, description = "Missing or invalid authentication"),
+        (status = 404, description = "No such saved address"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn delete_address(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+    Json(request): Json<DeleteAddressRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "delete_address";
+
+    let result = async {
+        let user = verify_address_book_request(&ctx, &headers, |user| {
+            orderbook::utils::delete_address_message(user, &request.destination)
+        })
+        .await?;
+        if !ctx
+            .address_book_service
+            .delete(&user, &request.destination)
+            .await?
+        {
+            return Err(AppError(
+                StatusCode::NOT_FOUND,
+                anyhow!("No saved address for this destination"),
+            ));
+        }
+        Ok(Json(()))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+/// Verifies that a change to the address book is signed by a session key allowed to withdraw,
+/// returning the identity of the user
+async fn verify_address_book_request(
+    ctx: &RouterCtx,
+    headers: &HeaderMap,
+    message: impl FnOnce(&str) -> String,
+) -> Result<String, AppError> {
+    let (user_info, public_key) = verify_signed_request(ctx, headers, message).await?;
+    orderbook::utils::verify_session_key_scope(
+        &user_info,
+        &public_key,
+        SessionKeyPermissions::WITHDRAW,
+        None,
+    )
+    .map_err(|e| AppError(StatusCode::FORBIDDEN, anyhow!(e)))?;
+
+    Ok(user_info.user)
+}
+
+/// Verifies that a request is signed by a session key of the user,
+/// returning the user and the session key
+async fn verify_signed_request(
+    ctx: &RouterCtx,
+    headers: &HeaderMap,
+    message: impl FnOnce(&str) -> String,
+) -> Result<(UserInfo, Vec<u8>), AppError> {
+    let auth = AuthHeaders::authenticate(ctx, headers).await?;
+    let (Some(public_key), Some(signature)) = (auth.public_key, auth.signature) else {
+        return Err(AppError(
+            StatusCode::UNAUTHORIZED,
+            anyhow!("Missing public key or signature in headers"),
+        ));
+    };
 
-        ExecuteStateAPI {
-            assets_info: state.assets_info.clone(),
-            users_info: state.users_info.clone(),
-            balances,
-            order_manager: OrderManagerAPI::from(&state.order_manager),
+    let user_info = {
+        let user_service = ctx.user_service.read().await;
+        user_service.get_user_info(&auth.identity).await?
+    };
+    orderbook::utils::verify_user_signature_authorization(
+        &user_info,
+        &public_key,
+        &message(&user_info.user),
+        &signature,
+    )
+    .map_err(|e| {
+        AppError(
+            StatusCode::UNAUTHORIZED,
+            anyhow!("Failed to verify user signature authorization: {e}"),
+        )
+    })?;
+
+    Ok((user_info, public_key))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api_keys",
+    tag = "account",
+    security(("identity" = []), ("api_key" = [])),
+    responses(
+        (status = 200, body = Vec<ApiKey>),
+        (status = 401, description = "Missing or invalid authentication"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_api_keys(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_api_keys";
+
+    let result = async {
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let api_keys = ctx.api_key_service.list(&auth.identity).await?;
+        Ok(Json(api_keys))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/api_keys",
+    tag = "account",
+    request_body = CreateApiKeyRequest,
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, description = "Created key, only returned once", body = CreatedApiKey),
+        (status = 401, description = "Missing or invalid authentication"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn create_api_key(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "create_api_key";
+
+    let result = async {
+        let (user_info, public_key) = verify_signed_request(&ctx, &headers, |user| {
+            orderbook::utils::create_api_key_message(user, &request.label)
+        })
+        .await?;
+        let session_public_key = request.bind_session_key.then_some(public_key.as_slice());
+        let api_key = ctx
+            .api_key_service
+            .create(&user_info.user, &request.label, session_public_key)
+            .await?;
+        Ok(Json(api_key))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/api_keys/revoke",
+    tag = "account",
+    request_body = RevokeApiKeyRequest,
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, description = "Key revoked"),
+        (status = 401, description = "Missing or invalid authentication"),
+        (status = 404, description = "No such API key"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn revoke_api_key(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+    Json(request): Json<RevokeApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "revoke_api_key";
+
+    let result = async {
+        let (user_info, _) = verify_signed_request(&ctx, &headers, |user| {
+            orderbook::utils::revoke_api_key_message(user, request.key_id)
+        })
+        .await?;
+        if !ctx
+            .api_key_service
+            .revoke(&user_info.user, request.key_id)
+            .await?
+        {
+            return Err(AppError(
+                StatusCode::NOT_FOUND,
+                anyhow!("No active API key {}", request.key_id),
+            ));
+        }
+        Ok(Json(()))
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/create_pair",
+    tag = "admin",
+    request_body = CreatePairRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn create_pair(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<CreatePairRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "create_pair";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
+
+        if request.base_contract == request.quote_contract {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Base and quote asset cannot be the same"),
+            ));
+        }
+
+        let CreatePairRequest {
+            base_contract,
+            quote_contract,
+            ..
+        } = request;
+
+        let asset_service = ctx.asset_service.read().await;
+
+        let base_asset = asset_service
+            .get_asset_from_contract_name(&base_contract)
+            .await
+            .ok_or(AppError(
+                StatusCode::NOT_FOUND,
+                anyhow::anyhow!("Base asset not found: {base_contract}"),
+            ))?;
+        let quote_asset = asset_service
+            .get_asset_from_contract_name(&quote_contract)
+            .await
+            .ok_or(AppError(
+                StatusCode::NOT_FOUND,
+                anyhow::anyhow!("Quote asset not found: {quote_contract}"),
+            ))?;
+
+        if base_asset.scale >= 20 {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!(
+                    "Unsupported pair scale: base_scale >= 20: {}",
+                    base_asset.scale
+                ),
+            ));
+        }
+        if quote_asset.scale >= 20 {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!(
+                    "Unsupported pair scale: quote_scale >= 20: {}",
+                    quote_asset.scale
+                ),
+            ));
         }
+
+        ctx.asset_backings.check_pair(base_asset, quote_asset)?;
+
+        let base_info = AssetInfo::new(base_asset.scale as u64, base_contract.into());
+        let quote_info = AssetInfo::new(quote_asset.scale as u64, quote_contract.into());
+
+        let info = PairInfo {
+            base: base_info,
+            quote: quote_info,
+        };
+        let pair = (base_asset.symbol.clone(), quote_asset.symbol.clone());
+        drop(asset_service);
+
+        let operation_start = Instant::now();
+        let (action_id, user_info, events) = {
+            let lock_start = Instant::now();
+            let mut orderbook = ctx.orderbook.write().await;
+            ctx.metrics.record_lock(lock_start.elapsed(), "create_pair");
+
+            let user_info = orderbook
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
+
+            let method_start = Instant::now();
+            let events = orderbook
+                .create_pair(&pair, &info)
+                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+            ctx.metrics
+                .record_method(method_start.elapsed(), "create_pair");
+
+            let apply_start = Instant::now();
+            orderbook
+                .apply_events(&user_info, &events)
+                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+            ctx.metrics
+                .record_event_apply(apply_start.elapsed(), "create_pair");
+
+            let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events)
+        };
+        ctx.metrics
+            .record_operation(operation_start.elapsed(), "create_pair");
+
+        let action_private_input = Vec::<u8>::new();
+        let orderbook_action = PermissionedOrderbookAction::CreatePair { pair, info };
+
+        process_orderbook_action(
+            user_info,
+            events,
+            orderbook_action,
+            action_id,
+            &action_private_input,
+            &ctx,
+        )
     }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
 }
 
-// --------------------------------------------------------
-//     Routes
-// --------------------------------------------------------
-#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
-async fn get_state(State(ctx): State<RouterCtx>) -> Result<impl IntoResponse, AppError> {
-    let request_start = Instant::now();
-    let endpoint = "get_state";
+/// Creates a perp market, registering its synthetic base asset
+#[utoipa::path(
+    post,
+    path = "/admin/create_perp_market",
+    tag = "admin",
+    request_body = CreatePerpMarketRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn create_perp_market(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<CreatePerpMarketRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "create_perp_market";
+
+    let result = async {
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
+
+        if request.base_symbol.is_empty() || request.base_symbol.contains('/') {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Invalid base symbol: {}", request.base_symbol),
+            ));
+        }
+        if !(0..20).contains(&request.base_scale) {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!(
+                    "Unsupported pair scale: base_scale >= 20: {}",
+                    request.base_scale
+                ),
+            ));
+        }
+
+        let mut asset_service = ctx.asset_service.write().await;
+        let quote_asset = asset_service
+            .get_asset_from_contract_name(&request.quote_contract)
+            .await
+            .ok_or(AppError(
+                StatusCode::NOT_FOUND,
+                anyhow::anyhow!("Quote asset not found: {}", request.quote_contract),
+            ))?;
+        ctx.asset_backings.check_asset(quote_asset)?;
+        let quote_info = AssetInfo::new(
+            quote_asset.scale as u64,
+            quote_asset.contract_name.clone().into(),
+        );
+        let quote_symbol = quote_asset.symbol.clone();
+
+        let base_asset = asset_service
+            .add_synthetic_asset(&request.base_symbol, request.base_scale)
+            .await?;
+        if base_asset.contract_name != request.base_symbol.to_lowercase()
+            || base_asset.scale != request.base_scale
+        {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!(
+                    "Asset {} already exists and is not a synthetic asset of scale {}",
+                    request.base_symbol,
+                    request.base_scale
+                ),
+            ));
+        }
+        let info = PairInfo {
+            base: AssetInfo::new(
+                base_asset.scale as u64,
+                base_asset.contract_name.clone().into(),
+            ),
+            quote: quote_info,
+        };
+        let pair = (base_asset.symbol.clone(), quote_symbol);
+        drop(asset_service);
+
+        let config = PerpConfig {
+            initial_margin_bps: request.initial_margin_bps,
+            maintenance_margin_bps: request.maintenance_margin_bps,
+            liquidation_penalty_bps: request.liquidation_penalty_bps,
+        };
+
+        let operation_start = Instant::now();
+        let (action_id, user_info, events) = {
+            let lock_start = Instant::now();
+            let mut orderbook = ctx.orderbook.write().await;
+            ctx.metrics
+                .record_lock(lock_start.elapsed(), "create_perp_market");
+
+            let user_info = orderbook
+                .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+                .unwrap_or_else(|_| {
+                    UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new())
+                });
 
-    let result = async {
-        let lock_start = Instant::now();
-        let orderbook = ctx.orderbook.lock().await;
-        ctx.metrics.record_lock(lock_start.elapsed(), "get_state");
+            let method_start = Instant::now();
+            let events = orderbook
+                .create_perp_market(&user_info, &pair, &info, &config)
+                .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?;
+            ctx.metrics
+                .record_method(method_start.elapsed(), "create_perp_market");
 
-        let api_state = ExecuteStateAPI::from(&*orderbook);
-        Ok(Json(api_state))
+            let apply_start = Instant::now();
+            orderbook
+                .apply_events(&user_info, &events)
+                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+            ctx.metrics
+                .record_event_apply(apply_start.elapsed(), "create_perp_market");
+
+            let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
+            (action_id, user_info, events)
+        };
+        ctx.metrics
+            .record_operation(operation_start.elapsed(), "create_perp_market");
+
+        process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::CreatePerpMarket { pair, info, config },
+            action_id,
+            &(),
+            &ctx,
+        )
     }
     .await;
 
@@ -683,14 +4042,27 @@ async fn get_state(State(ctx): State<RouterCtx>) -> Result<impl IntoResponse, Ap
     result
 }
 
+/// Halts, resumes or delists a pair. The pair lock is held so that no order of the pair is
+/// placed in between.
+#[utoipa::path(
+    post,
+    path = "/admin/pair_status",
+    tag = "admin",
+    request_body = UpdatePairStatusRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
 #[axum::debug_handler]
 #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
-async fn submit_prover_request(
+async fn update_pair_status(
     State(ctx): State<RouterCtx>,
-    Json(request): Json<SubmitProverRequest>,
+    Json(request): Json<UpdatePairStatusRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let request_start = Instant::now();
-    let endpoint = "submit_prover_request";
+    let endpoint = "update_pair_status";
 
     let result = async {
         if request.secret != ctx.admin_secret {
@@ -699,26 +4071,40 @@ async fn submit_prover_request(
                 anyhow::anyhow!("Invalid secret"),
             ));
         }
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
 
-        let tx_hash = request.blob_tx.hashed();
-        if tx_hash != request.prover_request.tx_hash {
-            return Err(AppError(
-                StatusCode::BAD_REQUEST,
-                anyhow::anyhow!("tx_hash mismatch"),
-            ));
-        }
+        let user_info = ctx
+            .orderbook
+            .read()
+            .await
+            .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+            .unwrap_or_else(|_| UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new()));
 
-        let mut bus = ctx.bus.clone();
-        let context = Span::current().context();
-        bus.send(DatabaseRequest::WriteEvents {
-            user: UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new()),
-            tx_hash: tx_hash.clone(),
-            blob_tx: request.blob_tx,
-            prover_request: request.prover_request,
-            context,
-        })?;
+        let (action_id, events) = execute_on_pairs(
+            &ctx,
+            "update_pair_status",
+            "update_pair_status",
+            std::slice::from_ref(&request.pair),
+            &user_info,
+            |orderbook| {
+                orderbook
+                    .update_pair_status(&user_info, &request.pair, request.status)
+                    .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))
+            },
+        )
+        .await?;
 
-        Ok(Json(tx_hash))
+        process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::UpdatePairStatus {
+                pair: request.pair,
+                status: request.status,
+            },
+            action_id,
+            &(),
+            &ctx,
+        )
     }
     .await;
 
@@ -731,30 +4117,73 @@ async fn submit_prover_request(
     result
 }
 
-#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx), name="GET /nonce", fields(http.uri = "/nonce", http.method = "GET")))]
-async fn get_nonce(
+/// Sets or removes circuit breakers. The pair locks are held so that no order of the pairs
+/// is placed in between.
+#[utoipa::path(
+    post,
+    path = "/admin/circuit_breakers",
+    tag = "admin",
+    request_body = SetCircuitBreakersRequest,
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid secret"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, request)))]
+async fn set_circuit_breakers(
     State(ctx): State<RouterCtx>,
-    headers: HeaderMap,
+    Json(request): Json<SetCircuitBreakersRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let request_start = Instant::now();
-    let endpoint = "get_nonce";
+    let endpoint = "set_circuit_breakers";
 
     let result = async {
-        let auth = AuthHeaders::from_headers(&headers)?;
-        let user = auth.identity;
-
-        // TODO: do some checks on headers to verify identify the user
+        if request.secret != ctx.admin_secret {
+            return Err(AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Invalid secret"),
+            ));
+        }
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
 
-        let lock_start = Instant::now();
-        let orderbook = ctx.orderbook.lock().await;
-        ctx.metrics.record_lock(lock_start.elapsed(), "get_nonce");
+        let user_info = ctx
+            .orderbook
+            .read()
+            .await
+            .get_user_info(ORDERBOOK_ACCOUNT_IDENTITY)
+            .unwrap_or_else(|_| UserInfo::new(ORDERBOOK_ACCOUNT_IDENTITY.to_string(), Vec::new()));
 
-        let nonce = orderbook
-            .get_user_info(&user)
-            .map(|u| u.nonce)
-            .unwrap_or_default();
+        let pairs: Vec<Pair> = request
+            .updates
+            .iter()
+            .map(|(pair, _)| pair.clone())
+            .collect();
+        let (action_id, events) = execute_on_pairs(
+            &ctx,
+            "set_circuit_breakers",
+            "update_circuit_breakers",
+            &pairs,
+            &user_info,
+            |orderbook| {
+                orderbook
+                    .update_circuit_breakers(&user_info, &request.updates)
+                    .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))
+            },
+        )
+        .await?;
 
-        Ok(Json(nonce))
+        process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::UpdateCircuitBreakers {
+                updates: request.updates,
+            },
+            action_id,
+            &(),
+            &ctx,
+        )
     }
     .await;
 
@@ -767,123 +4196,199 @@ async fn get_nonce(
     result
 }
 
-#[axum::debug_handler]
+/// Circuit breakers of the pairs, and whether they currently halt trading
+#[utoipa::path(
+    get,
+    path = "/circuit_breakers",
+    tag = "status",
+    responses(
+        (status = 200, body = CircuitBreakersResponse),
+    )
+)]
 #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
-async fn create_pair(
-    State(ctx): State<RouterCtx>,
-    headers: HeaderMap,
-    Json(request): Json<CreatePairRequest>,
-) -> Result<impl IntoResponse, AppError> {
+async fn get_circuit_breakers(State(ctx): State<RouterCtx>) -> Result<impl IntoResponse, AppError> {
     let request_start = Instant::now();
-    let endpoint = "create_pair";
+    let endpoint = "get_circuit_breakers";
 
     let result = async {
-        let auth = AuthHeaders::from_headers(&headers)?;
+        let block_height = order_block_height(&ctx).await?;
+        let orderbook = ctx.orderbook.read().await;
+        let mut circuit_breakers: Vec<PairCircuitBreaker> = orderbook
+            .circuit_breakers
+            .iter()
+            .map(|(pair, state)| PairCircuitBreaker {
+                pair: pair.clone(),
+                state: state.clone(),
+                halted: state.is_halted(block_height),
+            })
+            .collect();
+        circuit_breakers.sort_by(|a, b| a.pair.cmp(&b.pair));
 
-        if request.base_contract == request.quote_contract {
-            return Err(AppError(
-                StatusCode::BAD_REQUEST,
-                anyhow::anyhow!("Base and quote asset cannot be the same"),
-            ));
-        }
+        Ok(Json(CircuitBreakersResponse {
+            block_height,
+            circuit_breakers,
+        }))
+    }
+    .await;
 
-        let user = auth.identity;
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
 
-        let CreatePairRequest {
-            base_contract,
-            quote_contract,
-        } = request;
+    result
+}
 
-        let asset_service = ctx.asset_service.read().await;
+/// Whether the deposits and withdrawals of the bridge are paused, and by what
+#[utoipa::path(
+    get,
+    path = "/bridge/status",
+    tag = "status",
+    responses(
+        (status = 200, body = BridgeStatusResponse),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_bridge_status(State(ctx): State<RouterCtx>) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_bridge_status";
 
-        let base_asset = asset_service
-            .get_asset_from_contract_name(&base_contract)
-            .await
-            .ok_or(AppError(
-                StatusCode::NOT_FOUND,
-                anyhow::anyhow!("Base asset not found: {base_contract}"),
-            ))?;
-        let quote_asset = asset_service
-            .get_asset_from_contract_name(&quote_contract)
-            .await
-            .ok_or(AppError(
-                StatusCode::NOT_FOUND,
-                anyhow::anyhow!("Quote asset not found: {quote_contract}"),
-            ))?;
+    let contract_paused = ctx.orderbook.read().await.bridge_paused;
+    let response = BridgeStatusResponse {
+        paused: ctx.bridge_pause.is_paused(),
+        contract_paused,
+        forced: ctx.bridge_pause.is_forced(),
+    };
 
-        if base_asset.scale >= 20 {
-            return Err(AppError(
-                StatusCode::BAD_REQUEST,
-                anyhow::anyhow!(
-                    "Unsupported pair scale: base_scale >= 20: {}",
-                    base_asset.scale
-                ),
-            ));
-        }
-        if quote_asset.scale >= 20 {
-            return Err(AppError(
-                StatusCode::BAD_REQUEST,
-                anyhow::anyhow!(
-                    "Unsupported pair scale: quote_scale >= 20: {}",
-                    quote_asset.scale
-                ),
-            ));
-        }
+    ctx.metrics.record_request(request_start, endpoint, 200);
 
-        let base_info = AssetInfo::new(base_asset.scale as u64, base_contract.into());
-        let quote_info = AssetInfo::new(quote_asset.scale as u64, quote_contract.into());
+    Ok(Json(response))
+}
 
-        let info = PairInfo {
-            base: base_info,
-            quote: quote_info,
-        };
-        let pair = (base_asset.symbol.clone(), quote_asset.symbol.clone());
-        drop(asset_service);
+/// Last reconciliation of the balances owed on the orderbook against the tokens it holds
+#[utoipa::path(
+    get,
+    path = "/reconciliation",
+    tag = "status",
+    responses(
+        (status = 200, body = ReconciliationReport),
+        (status = 404, description = "No reconciliation ran yet"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_reconciliation(State(ctx): State<RouterCtx>) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_reconciliation";
 
-        let operation_start = Instant::now();
-        let (action_id, user_info, events) = {
-            let lock_start = Instant::now();
-            let mut orderbook = ctx.orderbook.lock().await;
-            ctx.metrics.record_lock(lock_start.elapsed(), "create_pair");
+    let result = ctx.reconciliation.last_report().map(Json).ok_or_else(|| {
+        AppError(
+            StatusCode::NOT_FOUND,
+            anyhow!("No reconciliation has run yet"),
+        )
+    });
 
-            // Get user_info if exists, otherwise create a new one with random salt
-            let user_info = orderbook.get_user_info(&user).unwrap_or_else(|_| {
-                let mut salt = [0u8; 32];
-                rand::rng().fill_bytes(&mut salt);
-                UserInfo::new(user.clone(), salt.to_vec())
-            });
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
 
-            let method_start = Instant::now();
-            let events = orderbook
-                .create_pair(&pair, &info)
-                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
-            ctx.metrics
-                .record_method(method_start.elapsed(), "create_pair");
+    result
+}
 
-            let apply_start = Instant::now();
-            orderbook
-                .apply_events(&user_info, &events)
-                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
-            ctx.metrics
-                .record_event_apply(apply_start.elapsed(), "create_pair");
+/// Last commit whose settled commitment matched the local one, or the commit the settled state
+/// diverged at
+#[utoipa::path(
+    get,
+    path = "/settlement/status",
+    tag = "status",
+    responses(
+        (status = 200, body = SettlementStatus),
+        (status = 404, description = "The settlement check is disabled"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_settlement_status(
+    State(ctx): State<RouterCtx>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_settlement_status";
 
-            let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
-            (action_id, user_info, events)
-        };
-        ctx.metrics
-            .record_operation(operation_start.elapsed(), "create_pair");
+    let result = ctx
+        .settlement_check
+        .as_ref()
+        .map(|check| Json(check.status()))
+        .ok_or_else(|| {
+            AppError(
+                StatusCode::NOT_FOUND,
+                anyhow!("The settlement check is disabled"),
+            )
+        });
 
-        let action_private_input = Vec::<u8>::new();
-        let orderbook_action = PermissionedOrderbookAction::CreatePair { pair, info };
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
 
-        process_orderbook_action(
-            user_info,
-            events,
-            orderbook_action,
-            action_id,
-            &action_private_input,
-            &ctx,
-        )
+    result
+}
+
+/// Amounts bridged over the rolling window of each limit of the bridge
+#[utoipa::path(
+    get,
+    path = "/bridge/limits",
+    tag = "status",
+    responses(
+        (status = 200, body = Vec<BridgeLimitUsage>),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_bridge_limits(State(ctx): State<RouterCtx>) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_bridge_limits";
+
+    let response = ctx.bridge_limits.usage();
+
+    ctx.metrics.record_request(request_start, endpoint, 200);
+
+    Ok(Json(response))
+}
+
+/// Latest index price of a pair. `symbol` is the pair as `BASE-QUOTE`, or `BASE/QUOTE` once
+/// url-encoded.
+#[utoipa::path(
+    get,
+    path = "/index_price/{symbol}",
+    tag = "status",
+    params(("symbol" = String, Path, description = "Pair, as BASE-QUOTE")),
+    responses(
+        (status = 200, body = IndexPrice),
+        (status = 404, description = "No index price for the pair"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn get_index_price(
+    State(ctx): State<RouterCtx>,
+    Path(symbol): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "get_index_price";
+
+    let result = async {
+        let symbol = symbol.to_uppercase().replace('-', "/");
+        let index_price = ctx
+            .index_price_service
+            .latest(&symbol)
+            .await?
+            .ok_or_else(|| {
+                AppError(
+                    StatusCode::NOT_FOUND,
+                    anyhow!("No index price for {symbol}"),
+                )
+            })?;
+        Ok(Json(index_price))
     }
     .await;
 
@@ -896,6 +4401,32 @@ async fn create_pair(
     result
 }
 
+/// Block the circuit breakers are checked at when placing orders. Lagging behind the chain
+/// only makes halts last longer, so a recently fetched height is enough.
+async fn order_block_height(ctx: &RouterCtx) -> Result<u64, AppError> {
+    let height = ctx.client.recent_block_height().await.map_err(|e| {
+        AppError(
+            StatusCode::SERVICE_UNAVAILABLE,
+            anyhow::anyhow!("Could not fetch the current block height: {e}"),
+        )
+    })?;
+    Ok(height.0)
+}
+
+#[utoipa::path(
+    post,
+    path = "/add_session_key",
+    tag = "actions",
+    params(("x-session-permissions" = Option<String>, Header, description = "Actions the key may sign: trade_only, cancel_only or all (default)"), ("x-session-pair" = Option<String>, Header, description = "Only pair the key may trade on, as BASE/QUOTE")),
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid action or signature"),
+        (status = 401, description = "Missing identity, session key or signature"),
+        (status = 403, description = "Not allowed to the session key, or refused by the risk checks"),
+        (status = 429, description = "Rate limit exceeded"),
+    )
+)]
 #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
 async fn add_session_key(
     State(ctx): State<RouterCtx>,
@@ -905,12 +4436,14 @@ async fn add_session_key(
     let endpoint = "add_session_key";
 
     let result = async {
-        let auth = AuthHeaders::from_headers(&headers)?;
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
         let user = auth.identity;
         let public_key = auth.public_key.expect("Missing public key in headers");
+        let (permissions, pair) = session_key_scope_from_headers(&headers)?;
 
         debug!(
-            "Adding session key for user {user} with public key {}",
+            "Adding session key for user {user} with public key {} (permissions: {permissions:?}, pair: {pair:?})",
             hex::encode(&public_key)
         );
 
@@ -918,7 +4451,7 @@ async fn add_session_key(
         // FIXME: locking here makes locking another time in execute_orderbook_action ...
         let (action_id, user_info, events) = {
             let lock_start = Instant::now();
-            let mut orderbook = ctx.orderbook.lock().await;
+            let mut orderbook = ctx.orderbook.write().await;
             ctx.metrics
                 .record_lock(lock_start.elapsed(), "add_session_key");
 
@@ -927,17 +4460,38 @@ async fn add_session_key(
                 orderbook.users_info
             );
 
-            // Get user_info if exists, otherwise create a new one with random salt
-            let user_info = orderbook.get_user_info(&user).unwrap_or_else(|_| {
-                debug!("Creating new user info for user {user}");
-                let mut salt = [0u8; 32];
-                rand::rng().fill_bytes(&mut salt);
-                UserInfo::new(user.clone(), salt.to_vec())
-            });
+            // Get user_info if exists, otherwise create a new one with random salt. Sub-accounts
+            // are keyed with the salt of their parent, that must be registered first.
+            let user_info = match (orderbook.get_user_info(&user), split_sub_account(&user)) {
+                (Ok(user_info), _) => user_info,
+                (Err(_), Some((parent, name))) => {
+                    let parent_info = orderbook.get_user_info(parent).map_err(|_| {
+                        AppError(
+                            StatusCode::BAD_REQUEST,
+                            anyhow::anyhow!(
+                                "Parent identity {parent} of sub-account {user} is not registered"
+                            ),
+                        )
+                    })?;
+                    debug!("Creating new sub-account {user}");
+                    UserInfo::new_sub_account(&parent_info, name)
+                }
+                (Err(_), None) => {
+                    debug!("Creating new user info for user {user}");
+                    let mut salt = [0u8; 32];
+                    rand::rng().fill_bytes(&mut salt);
+                    UserInfo::new(user.clone(), salt.to_vec())
+                }
+            };
             debug!("User info: {:?}", user_info);
 
             let method_start = Instant::now();
-            let res = orderbook.add_session_key(user_info.clone(), &public_key);
+            let res = orderbook.add_session_key(
+                user_info.clone(),
+                &public_key,
+                permissions,
+                pair.clone(),
+            );
             ctx.metrics
                 .record_method(method_start.elapsed(), "add_session_key");
             let events = match res {
@@ -946,6 +4500,13 @@ async fn add_session_key(
                     if e.contains("already exists") {
                         debug!("Session key already exists for user {user}. {e}");
                         return Err(AppError(StatusCode::NOT_MODIFIED, anyhow::anyhow!(e)));
+                    } else if e.contains("permission")
+                        || e.contains("unknown pair")
+                        || e.contains("not supported")
+                        || e.contains("Invalid identity")
+                        || e.contains("Invalid session key")
+                    {
+                        return Err(AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)));
                     } else {
                         return Err(AppError(
                             StatusCode::INTERNAL_SERVER_ERROR,
@@ -970,6 +4531,8 @@ async fn add_session_key(
 
         let action_private_input = &AddSessionKeyPrivateInput {
             new_public_key: public_key,
+            permissions,
+            pair,
         };
 
         let orderbook_action = PermissionedOrderbookAction::AddSessionKey;
@@ -994,6 +4557,19 @@ async fn add_session_key(
     result
 }
 
+/// Credits a deposit without checking any token transfer, only served with
+/// `deposits.trusted_endpoint`
+#[utoipa::path(
+    post,
+    path = "/deposit",
+    tag = "actions",
+    request_body = DepositRequest,
+    security(("identity" = []), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 401, description = "Missing identity"),
+    )
+)]
 #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
 async fn deposit(
     State(ctx): State<RouterCtx>,
@@ -1004,9 +4580,9 @@ async fn deposit(
     let endpoint = "deposit";
 
     let result = async {
-        let auth = AuthHeaders::from_headers(&headers)?;
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
         let user = auth.identity;
-        // TODO: Check that the user actually has sent the funds to the contract before proceeding to deposit
 
         debug!(
             "Depositing {} {} for user {user}",
@@ -1016,7 +4592,7 @@ async fn deposit(
         let operation_start = Instant::now();
         let (action_id, user_info, events) = {
             let lock_start = Instant::now();
-            let mut orderbook = ctx.orderbook.lock().await;
+            let mut orderbook = ctx.orderbook.write().await;
             ctx.metrics.record_lock(lock_start.elapsed(), "deposit");
 
             // Get user_info if exists, otherwise create a new one with random salt
@@ -1072,33 +4648,144 @@ async fn deposit(
     result
 }
 
+/// Generates the events of an order flow action on some pairs and applies them to the light state.
+///
+/// Actions on different pairs only contend on their pair locks: events are generated under a
+/// shared read lock of the state, and applied under a short write lock. The users, balances and
+/// orders they were generated from are shared between pairs, so they are checked again before
+/// applying, and the events are generated once more under the write lock if any of them changed.
+async fn execute_on_pairs<F>(
+    ctx: &RouterCtx,
+    operation: &str,
+    method: &str,
+    pairs: &[Pair],
+    user_info: &UserInfo,
+    generate: F,
+) -> Result<(u32, Vec<OrderbookEvent>), AppError>
+where
+    F: Fn(&orderbook::model::ExecuteState) -> Result<Vec<OrderbookEvent>, AppError>,
+{
+    apply_on_pairs(
+        &ctx.pair_locks,
+        &ctx.orderbook,
+        &ctx.action_id_counter,
+        &ctx.metrics,
+        operation,
+        method,
+        pairs,
+        user_info,
+        generate,
+    )
+    .await
+}
+
+/// Locking and conflict handling of [`execute_on_pairs`]. The action id is taken under the
+/// write lock, so that actions are numbered in the order they are applied.
+#[allow(clippy::too_many_arguments)]
+async fn apply_on_pairs<F>(
+    pair_locks: &PairLocks,
+    orderbook: &RwLock<orderbook::model::ExecuteState>,
+    action_id_counter: &AtomicU32,
+    metrics: &AppMetrics,
+    operation: &str,
+    method: &str,
+    pairs: &[Pair],
+    user_info: &UserInfo,
+    generate: F,
+) -> Result<(u32, Vec<OrderbookEvent>), AppError>
+where
+    F: Fn(&orderbook::model::ExecuteState) -> Result<Vec<OrderbookEvent>, AppError>,
+{
+    let lock_start = Instant::now();
+    let _pair_guards = pair_locks.lock(pairs).await;
+    metrics.record_lock(lock_start.elapsed(), &format!("{operation}:pairs"));
+
+    let (events, read_set) = {
+        let lock_start = Instant::now();
+        let orderbook = orderbook.read().await;
+        metrics.record_lock(lock_start.elapsed(), &format!("{operation}:read"));
+
+        let method_start = Instant::now();
+        let events = generate(&orderbook)?;
+        metrics.record_method(method_start.elapsed(), method);
+        let read_set = StateReadSet::capture(&orderbook, user_info, &events);
+        (events, read_set)
+    };
+
+    let lock_start = Instant::now();
+    let mut orderbook = orderbook.write().await;
+    metrics.record_lock(lock_start.elapsed(), &format!("{operation}:write"));
+    let operation_start = Instant::now();
+
+    let events = if read_set.is_current(&orderbook) {
+        events
+    } else {
+        debug!("State changed while executing {operation}, generating its events again");
+        metrics.record_lock_conflict(operation);
+        generate(&orderbook)?
+    };
+
+    let apply_start = Instant::now();
+    log_error!(
+        orderbook
+            .apply_events(user_info, &events)
+            .map_err(|e| anyhow::anyhow!(e)),
+        "Failed to apply events"
+    )
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    metrics.record_event_apply(apply_start.elapsed(), operation);
+
+    let action_id = action_id_counter.fetch_add(1, Ordering::Relaxed);
+    metrics.record_operation(operation_start.elapsed(), operation);
+    metrics.record_events_applied(events.len(), operation);
+    Ok((action_id, events))
+}
+
+#[utoipa::path(
+    post,
+    path = "/create_order",
+    tag = "actions",
+    request_body = CreateOrderRequest,
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid action or signature"),
+        (status = 401, description = "Missing identity, session key or signature"),
+        (status = 403, description = "Not allowed to the session key, or refused by the risk checks"),
+        (status = 429, description = "Rate limit exceeded"),
+    )
+)]
 #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
 async fn create_order(
     State(ctx): State<RouterCtx>,
     headers: HeaderMap,
-    Json(request): Json<Order>,
+    Json(request): Json<CreateOrderRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let request_start = Instant::now();
     let endpoint = "create_order";
 
     let result = async {
-        let auth = AuthHeaders::from_headers(&headers)?;
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
         let user = auth.identity;
         let public_key = auth.public_key.expect("Missing public key in headers");
         let signature = auth.signature.expect("Missing signature in headers");
+        let order_tags = order_tags(std::slice::from_ref(&request))?;
+        let request = request.order;
 
         let user_info = {
             let user_service = ctx.user_service.read().await;
             user_service.get_user_info(&user).await?
         };
 
-        orderbook::utils::verify_user_signature_authorization(
+        orderbook::utils::verify_user_action_authorization(
             &user_info,
             &public_key,
-            &format!(
-                "{}:{}:create_order:{}",
-                user_info.user, user_info.nonce, request.order_id
-            ),
+            &SignedAction::CreateOrder {
+                user: &user_info.user,
+                nonce: user_info.nonce,
+                order: &request,
+            },
             &signature,
         )
         .map_err(|e| {
@@ -1107,77 +4794,448 @@ async fn create_order(
                 anyhow::anyhow!("Failed to verify user signature authorization: {e}"),
             )
         })?;
+        orderbook::utils::verify_session_key_scope(
+            &user_info,
+            &public_key,
+            SessionKeyPermissions::CREATE_ORDER,
+            Some(&request.pair),
+        )
+        .map_err(|e| AppError(StatusCode::FORBIDDEN, anyhow::anyhow!(e)))?;
 
         debug!("Creating order for user {user}. Order: {:?}", request);
 
-        let (
-            action_id,
+        let block_height = order_block_height(&ctx).await?;
+        let (action_id, events) = {
+            let risk_manager = ctx.risk_manager.read().await;
+            execute_on_pairs(
+                &ctx,
+                "create_order",
+                "execute_order",
+                std::slice::from_ref(&request.pair),
+                &user_info,
+                |orderbook| {
+                    // Pre-trade risk checks are evaluated against the state the order will execute on
+                    risk_manager
+                        .check_order(orderbook, &user_info, &request)
+                        .map_err(|e| AppError(StatusCode::FORBIDDEN, anyhow::anyhow!(e)))?;
+                    log_warn!(
+                        orderbook
+                            .execute_order(&user_info, request.clone(), block_height)
+                            .map_err(|e| anyhow::anyhow!(e)),
+                        "Failed to execute order"
+                    )
+                    .map_err(|e| AppError(StatusCode::BAD_REQUEST, e))
+                },
+            )
+            .await?
+        };
+
+        let action_private_input = &CreateOrderPrivateInput {
+            public_key,
+            signature,
+            block_height,
+        };
+
+        let orderbook_action = PermissionedOrderbookAction::CreateOrder(request);
+
+        process_tagged_orderbook_action(
             user_info,
             events,
-            lock_duration,
-            method_duration,
-            apply_duration,
-            operation_duration,
-        ) = {
-            let lock_start = Instant::now();
-            let mut orderbook = ctx.orderbook.lock().await;
-            let lock_duration = lock_start.elapsed();
-            let operation_start = Instant::now();
+            orderbook_action,
+            action_id,
+            action_private_input,
+            order_tags,
+            &ctx,
+        )
+    }
+    .await;
 
-            let method_start = Instant::now();
-            let events = log_warn!(
-                orderbook
-                    .execute_order(&user_info, request.clone())
-                    .map_err(|e| anyhow::anyhow!(e)),
-                "Failed to execute order"
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/create_orders",
+    tag = "actions",
+    request_body = CreateOrdersRequest,
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid action or signature"),
+        (status = 401, description = "Missing identity, session key or signature"),
+        (status = 403, description = "Not allowed to the session key, or refused by the risk checks"),
+        (status = 429, description = "Rate limit exceeded"),
+    )
+)]
+#[axum::debug_handler]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn create_orders(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+    Json(request): Json<CreateOrdersRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "create_orders";
+
+    let result = async {
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let user = auth.identity;
+        let public_key = auth.public_key.ok_or_else(|| {
+            AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Missing public key in headers"),
+            )
+        })?;
+        let signature = auth.signature.ok_or_else(|| {
+            AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Missing signature in headers"),
             )
-            .map_err(|e| AppError(StatusCode::BAD_REQUEST, e))?;
-            let method_duration = method_start.elapsed();
+        })?;
+        let order_tags = order_tags(&request.orders)?;
+        let orders: Vec<Order> = request
+            .orders
+            .into_iter()
+            .map(|request| request.order)
+            .collect();
 
-            let apply_start = Instant::now();
-            log_error!(
-                orderbook
-                    .apply_events(&user_info, &events)
-                    .map_err(|e| anyhow::anyhow!(e)),
-                "Failed to apply events"
+        let user_info = {
+            let user_service = ctx.user_service.read().await;
+            user_service.get_user_info(&user).await?
+        };
+
+        orderbook::utils::verify_user_action_authorization(
+            &user_info,
+            &public_key,
+            &SignedAction::CreateOrders {
+                user: &user_info.user,
+                nonce: user_info.nonce,
+                orders: &orders,
+            },
+            &signature,
+        )
+        .map_err(|e| {
+            AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Failed to verify user signature authorization: {e}"),
             )
-            .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
-            let apply_duration = apply_start.elapsed();
-            let operation_duration = operation_start.elapsed();
+        })?;
+        for order in &orders {
+            orderbook::utils::verify_session_key_scope(
+                &user_info,
+                &public_key,
+                SessionKeyPermissions::CREATE_ORDER,
+                Some(&order.pair),
+            )
+            .map_err(|e| AppError(StatusCode::FORBIDDEN, anyhow::anyhow!(e)))?;
+        }
 
-            let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
-            (
-                action_id,
-                user_info,
-                events,
-                lock_duration,
-                method_duration,
-                apply_duration,
-                operation_duration,
+        debug!("Creating {} atomic orders for user {user}", orders.len());
+
+        let block_height = order_block_height(&ctx).await?;
+        let pairs: Vec<Pair> = orders.iter().map(|order| order.pair.clone()).collect();
+        let (action_id, events) = {
+            let risk_manager = ctx.risk_manager.read().await;
+            execute_on_pairs(
+                &ctx,
+                "create_orders",
+                "execute_orders",
+                &pairs,
+                &user_info,
+                |orderbook| {
+                    for order in &orders {
+                        risk_manager
+                            .check_order(orderbook, &user_info, order)
+                            .map_err(|e| AppError(StatusCode::FORBIDDEN, anyhow::anyhow!(e)))?;
+                    }
+                    log_warn!(
+                        orderbook
+                            .execute_orders(&user_info, &orders, block_height)
+                            .map_err(|e| anyhow::anyhow!(e)),
+                        "Failed to execute atomic orders"
+                    )
+                    .map_err(|e| AppError(StatusCode::BAD_REQUEST, e))
+                },
             )
+            .await?
         };
-        ctx.metrics.record_lock(lock_duration, "create_order");
-        ctx.metrics.record_method(method_duration, "execute_order");
-        ctx.metrics
-            .record_event_apply(apply_duration, "create_order");
-        ctx.metrics
-            .record_operation(operation_duration, "create_order");
-        ctx.metrics
-            .record_events_applied(events.len(), "create_order");
 
-        let action_private_input = &CreateOrderPrivateInput {
+        process_tagged_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::CreateOrders(orders),
+            action_id,
+            &CreateOrderPrivateInput {
+                public_key,
+                signature,
+                block_height,
+            },
+            order_tags,
+            &ctx,
+        )
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/sweep_dust",
+    tag = "actions",
+    request_body = SweepDustRequest,
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid action or signature"),
+        (status = 401, description = "Missing identity, session key or signature"),
+        (status = 403, description = "Not allowed to the session key, or refused by the risk checks"),
+        (status = 429, description = "Rate limit exceeded"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn sweep_dust(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+    Json(request): Json<SweepDustRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "sweep_dust";
+
+    let result = async {
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let user = auth.identity;
+        let public_key = auth.public_key.ok_or_else(|| {
+            AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Missing public key in headers"),
+            )
+        })?;
+        let signature = auth.signature.ok_or_else(|| {
+            AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Missing signature in headers"),
+            )
+        })?;
+
+        let user_info = {
+            let user_service = ctx.user_service.read().await;
+            user_service.get_user_info(&user).await?
+        };
+
+        orderbook::utils::verify_user_action_authorization(
+            &user_info,
+            &public_key,
+            &SignedAction::SweepDust {
+                user: &user_info.user,
+                nonce: user_info.nonce,
+                quote: &request.quote,
+                sweeps: &request.sweeps,
+            },
+            &signature,
+        )
+        .map_err(|e| {
+            AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Failed to verify user signature authorization: {e}"),
+            )
+        })?;
+        let pairs: Vec<Pair> = request
+            .sweeps
+            .iter()
+            .map(|sweep| (sweep.symbol.clone(), request.quote.clone()))
+            .collect();
+        for pair in &pairs {
+            orderbook::utils::verify_session_key_scope(
+                &user_info,
+                &public_key,
+                SessionKeyPermissions::CREATE_ORDER,
+                Some(pair),
+            )
+            .map_err(|e| AppError(StatusCode::FORBIDDEN, anyhow::anyhow!(e)))?;
+        }
+
+        debug!(
+            "Sweeping the dust of {} assets into {} for user {user}",
+            request.sweeps.len(),
+            request.quote
+        );
+
+        let block_height = order_block_height(&ctx).await?;
+        let (action_id, events) = execute_on_pairs(
+            &ctx,
+            "sweep_dust",
+            "sweep_dust",
+            &pairs,
+            &user_info,
+            |orderbook| {
+                orderbook
+                    .sweep_dust(&user_info, &request.quote, &request.sweeps, block_height)
+                    .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))
+            },
+        )
+        .await?;
+
+        process_orderbook_action(
+            user_info,
+            events,
+            PermissionedOrderbookAction::SweepDust {
+                quote: request.quote,
+                sweeps: request.sweeps,
+            },
+            action_id,
+            &CreateOrderPrivateInput {
+                public_key,
+                signature,
+                block_height,
+            },
+            &ctx,
+        )
+    }
+    .await;
+
+    let status = match &result {
+        Ok(_) => 200,
+        Err(AppError(status, _)) => status.as_u16(),
+    };
+    ctx.metrics.record_request(request_start, endpoint, status);
+
+    result
+}
+
+#[utoipa::path(
+    post,
+    path = "/cancel_order",
+    tag = "actions",
+    request_body = CancelOrderRequest,
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid action or signature"),
+        (status = 401, description = "Missing identity, session key or signature"),
+        (status = 403, description = "Not allowed to the session key, or refused by the risk checks"),
+        (status = 429, description = "Rate limit exceeded"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
+async fn cancel_order(
+    State(ctx): State<RouterCtx>,
+    headers: HeaderMap,
+    Json(request): Json<CancelOrderRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let request_start = Instant::now();
+    let endpoint = "cancel_order";
+
+    let result = async {
+        ensure_write_capacity(&ctx, Exposure::Reduces)?;
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
+        let user = auth.identity;
+        let public_key = auth.public_key.expect("Missing public key in headers");
+        let signature = auth.signature.expect("Missing signature in headers");
+
+        let user_info = {
+            let user_service = ctx.user_service.read().await;
+            user_service.get_user_info(&user).await?
+        };
+
+        orderbook::utils::verify_user_action_authorization(
+            &user_info,
+            &public_key,
+            &SignedAction::CancelOrder {
+                user: &user_info.user,
+                nonce: user_info.nonce,
+                order_id: &request.order_id,
+            },
+            &signature,
+        )
+        .map_err(|e| {
+            AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Failed to verify user signature authorization: {e}"),
+            )
+        })?;
+
+        debug!(
+            "Cancelling order for user {user}. Order ID: {}",
+            request.order_id
+        );
+
+        // The pair of an order never changes, it is looked up before locking it
+        let pair = ctx
+            .orderbook
+            .read()
+            .await
+            .order_manager
+            .orders
+            .get(&request.order_id)
+            .map(|order| order.pair.clone());
+        let (action_id, events) = execute_on_pairs(
+            &ctx,
+            "cancel_order",
+            "cancel_order",
+            pair.as_slice(),
+            &user_info,
+            |orderbook| {
+                let Some(order_owner) = orderbook.get_order_owner(&request.order_id) else {
+                    return Err(AppError(
+                        StatusCode::BAD_REQUEST,
+                        anyhow::anyhow!("Order not found: {}", request.order_id),
+                    ));
+                };
+                if user_info.get_key() != *order_owner {
+                    return Err(AppError(
+                        StatusCode::UNAUTHORIZED,
+                        anyhow::anyhow!("You are not the owner of this order"),
+                    ));
+                }
+                orderbook::utils::verify_session_key_scope(
+                    &user_info,
+                    &public_key,
+                    SessionKeyPermissions::CANCEL,
+                    orderbook
+                        .order_manager
+                        .orders
+                        .get(&request.order_id)
+                        .map(|order| &order.pair),
+                )
+                .map_err(|e| AppError(StatusCode::FORBIDDEN, anyhow::anyhow!(e)))?;
+
+                orderbook
+                    .cancel_order(request.order_id.clone(), &user_info)
+                    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))
+            },
+        )
+        .await?;
+
+        let action_private_input = CancelOrderPrivateInput {
             public_key,
             signature,
         };
 
-        let orderbook_action = PermissionedOrderbookAction::CreateOrder(request);
+        let orderbook_action = PermissionedOrderbookAction::Cancel {
+            order_id: request.order_id.clone(),
+        };
 
         process_orderbook_action(
             user_info,
             events,
             orderbook_action,
             action_id,
-            action_private_input,
+            &action_private_input,
             &ctx,
         )
     }
@@ -1192,17 +5250,32 @@ async fn create_order(
     result
 }
 
+#[utoipa::path(
+    post,
+    path = "/withdraw",
+    tag = "actions",
+    request_body = WithdrawRequest,
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid action or signature"),
+        (status = 401, description = "Missing identity, session key or signature"),
+        (status = 403, description = "Not allowed to the session key, or refused by the risk checks"),
+        (status = 429, description = "Rate limit exceeded"),
+    )
+)]
 #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
-async fn cancel_order(
+async fn withdraw(
     State(ctx): State<RouterCtx>,
     headers: HeaderMap,
-    Json(request): Json<CancelOrderRequest>,
+    Json(request): Json<WithdrawRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let request_start = Instant::now();
-    let endpoint = "cancel_order";
+    let endpoint = "withdraw";
 
     let result = async {
-        let auth = AuthHeaders::from_headers(&headers)?;
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
         let user = auth.identity;
         let public_key = auth.public_key.expect("Missing public key in headers");
         let signature = auth.signature.expect("Missing signature in headers");
@@ -1212,13 +5285,15 @@ async fn cancel_order(
             user_service.get_user_info(&user).await?
         };
 
-        orderbook::utils::verify_user_signature_authorization(
+        orderbook::utils::verify_user_action_authorization(
             &user_info,
             &public_key,
-            &format!(
-                "{}:{}:cancel:{}",
-                user_info.user, user_info.nonce, request.order_id
-            ),
+            &SignedAction::Withdraw {
+                user: &user_info.user,
+                nonce: user_info.nonce,
+                symbol: &request.symbol,
+                amount: request.amount,
+            },
             &signature,
         )
         .map_err(|e| {
@@ -1227,69 +5302,174 @@ async fn cancel_order(
                 anyhow::anyhow!("Failed to verify user signature authorization: {e}"),
             )
         })?;
+        orderbook::utils::verify_session_key_scope(
+            &user_info,
+            &public_key,
+            SessionKeyPermissions::WITHDRAW,
+            None,
+        )
+        .map_err(|e| AppError(StatusCode::FORBIDDEN, anyhow::anyhow!(e)))?;
+
+        if request.destination.network != "hyli" && ctx.bridge_pause.is_paused() {
+            return Err(AppError(
+                StatusCode::SERVICE_UNAVAILABLE,
+                anyhow::anyhow!("Withdrawals to {} are paused", request.destination.network),
+            ));
+        }
+        if request.destination.network != "hyli" {
+            let contract_name = ctx
+                .asset_service
+                .read()
+                .await
+                .get_contract_name_from_symbol(&request.symbol)
+                .await;
+            if let Some(contract_name) = contract_name {
+                if let Err(e) = ctx.bridge_limits.check(
+                    &contract_name.0,
+                    BridgeDirection::Withdraw,
+                    request.amount as u128,
+                ) {
+                    ctx.bridge_limits
+                        .rejected(&contract_name.0, BridgeDirection::Withdraw);
+                    return Err(AppError(StatusCode::TOO_MANY_REQUESTS, anyhow::anyhow!(e)));
+                }
+            }
+        }
 
         debug!(
-            "Cancelling order for user {user}. Order ID: {}",
-            request.order_id
+            "Withdrawing {} {} for user {user}",
+            request.amount, request.symbol
         );
 
+        // Withdraw limits are accounted at the latest block, that the tx cannot land before
+        let block_height = ctx
+            .client
+            .get_block_height()
+            .await
+            .map_err(|e| {
+                AppError(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    anyhow::anyhow!("Could not fetch the current block height: {e}"),
+                )
+            })?
+            .0;
+
         let operation_start = Instant::now();
-        let (action_id, user_info, events) = {
+        let (action_id, user_info, events, queued) = {
             let lock_start = Instant::now();
-            let mut orderbook = ctx.orderbook.lock().await;
-            ctx.metrics
-                .record_lock(lock_start.elapsed(), "cancel_order");
+            let mut orderbook = ctx.orderbook.write().await;
+            ctx.metrics.record_lock(lock_start.elapsed(), "withdraw");
 
-            let Some(order_owner) = orderbook.get_order_owner(&request.order_id) else {
+            let balance = orderbook.get_balance(&user_info, &request.symbol);
+            if balance.0 < request.amount {
                 return Err(AppError(
                     StatusCode::BAD_REQUEST,
-                    anyhow::anyhow!("Order not found: {}", request.order_id),
+                    anyhow::anyhow!(
+                        "Not enough balance: withdrawing {} {} while having {}",
+                        request.amount,
+                        request.symbol,
+                        balance.0
+                    ),
                 ));
             };
-            if user_info.get_key() != *order_owner {
-                return Err(AppError(
-                    StatusCode::UNAUTHORIZED,
-                    anyhow::anyhow!("You are not the owner of this order"),
-                ));
-            }
 
+            // Large withdrawals go through the queue, and are paid out once released
+            let queued = orderbook
+                .withdraw_delay(&request.symbol, request.amount)
+                .is_some();
             let method_start = Instant::now();
-            let events = orderbook
-                .cancel_order(request.order_id.clone(), &user_info)
-                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+            let events = if queued {
+                orderbook.request_withdrawal(
+                    &request.symbol,
+                    request.amount,
+                    &request.destination,
+                    block_height,
+                    &user_info,
+                )
+            } else {
+                orderbook.withdraw(&request.symbol, &request.amount, block_height, &user_info)
+            }
+            .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?;
             ctx.metrics
-                .record_method(method_start.elapsed(), "cancel_order");
+                .record_method(method_start.elapsed(), "withdraw");
 
             let apply_start = Instant::now();
             orderbook
                 .apply_events(&user_info, &events)
                 .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
             ctx.metrics
-                .record_event_apply(apply_start.elapsed(), "cancel_order");
+                .record_event_apply(apply_start.elapsed(), "withdraw");
 
             let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
-            (action_id, user_info, events)
+            (action_id, user_info, events, queued)
         };
         ctx.metrics
-            .record_operation(operation_start.elapsed(), "cancel_order");
+            .record_operation(operation_start.elapsed(), "withdraw");
 
-        let action_private_input = CancelOrderPrivateInput {
+        // The signature covers the nonce of the user, telling apart identical withdrawals
+        let audit_entry = ctx
+            .audit_log
+            .is_large_withdrawal(&request.symbol, request.amount)
+            .then(|| {
+                let details = serde_json::json!({ "request": &request, "queued": queued });
+                AuditEntry {
+                    action: "withdraw".to_string(),
+                    actor: user_info.user.clone(),
+                    fingerprint: fingerprint(&[
+                        user_info.user.as_bytes(),
+                        &signature,
+                        details.to_string().as_bytes(),
+                    ]),
+                    status: None,
+                    details,
+                }
+            });
+
+        let action_private_input = WithdrawPrivateInput {
             public_key,
             signature,
         };
 
-        let orderbook_action = PermissionedOrderbookAction::Cancel {
-            order_id: request.order_id.clone(),
+        let user = user_info.user.clone();
+        let destination = request.destination.clone();
+        let orderbook_action = if queued {
+            PermissionedOrderbookAction::RequestWithdrawal {
+                symbol: request.symbol,
+                amount: request.amount,
+                destination: request.destination,
+                block_height,
+            }
+        } else {
+            PermissionedOrderbookAction::Withdraw {
+                symbol: request.symbol,
+                amount: request.amount,
+                destination: request.destination,
+                block_height,
+            }
         };
 
-        process_orderbook_action(
+        let response = process_orderbook_action(
             user_info,
             events,
             orderbook_action,
             action_id,
             &action_private_input,
             &ctx,
-        )
+        )?;
+
+        // The address book is only a convenience, the withdrawal goes through regardless
+        if let Err(AppError(_, e)) = ctx
+            .address_book_service
+            .mark_used(&user, &destination)
+            .await
+        {
+            warn!("Could not record the use of a saved address of {user}: {e:#}");
+        }
+        if let Some(entry) = audit_entry {
+            ctx.audit_log.record(entry).await;
+        }
+
+        Ok(response)
     }
     .await;
 
@@ -1302,17 +5482,32 @@ async fn cancel_order(
     result
 }
 
+#[utoipa::path(
+    post,
+    path = "/transfer",
+    tag = "actions",
+    request_body = TransferRequest,
+    security(("identity" = [], "session_key" = [], "signature" = []), ("api_key" = [], "signature" = [])),
+    responses(
+        (status = 200, description = "Hash of the blob transaction of the action", body = String),
+        (status = 400, description = "Invalid action or signature"),
+        (status = 401, description = "Missing identity, session key or signature"),
+        (status = 403, description = "Not allowed to the session key, or refused by the risk checks"),
+        (status = 429, description = "Rate limit exceeded"),
+    )
+)]
 #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx)))]
-async fn withdraw(
+async fn transfer(
     State(ctx): State<RouterCtx>,
     headers: HeaderMap,
-    Json(request): Json<WithdrawRequest>,
+    Json(request): Json<TransferRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let request_start = Instant::now();
-    let endpoint = "withdraw";
+    let endpoint = "transfer";
 
     let result = async {
-        let auth = AuthHeaders::from_headers(&headers)?;
+        ensure_write_capacity(&ctx, Exposure::Adds)?;
+        let auth = AuthHeaders::authenticate(&ctx, &headers).await?;
         let user = auth.identity;
         let public_key = auth.public_key.expect("Missing public key in headers");
         let signature = auth.signature.expect("Missing signature in headers");
@@ -1322,13 +5517,16 @@ async fn withdraw(
             user_service.get_user_info(&user).await?
         };
 
-        orderbook::utils::verify_user_signature_authorization(
+        orderbook::utils::verify_user_action_authorization(
             &user_info,
             &public_key,
-            &format!(
-                "{}:{}:withdraw:{}:{}",
-                user_info.user, user_info.nonce, request.symbol, request.amount
-            ),
+            &SignedAction::Transfer {
+                user: &user_info.user,
+                nonce: user_info.nonce,
+                to: &request.to,
+                symbol: &request.symbol,
+                amount: request.amount,
+            },
             &signature,
         )
         .map_err(|e| {
@@ -1337,60 +5535,54 @@ async fn withdraw(
                 anyhow::anyhow!("Failed to verify user signature authorization: {e}"),
             )
         })?;
+        orderbook::utils::verify_session_key_scope(
+            &user_info,
+            &public_key,
+            SessionKeyPermissions::WITHDRAW,
+            None,
+        )
+        .map_err(|e| AppError(StatusCode::FORBIDDEN, anyhow::anyhow!(e)))?;
 
         debug!(
-            "Withdrawing {} {} for user {user}",
-            request.amount, request.symbol
+            "Transferring {} {} from {user} to {}",
+            request.amount, request.symbol, request.to
         );
 
         let operation_start = Instant::now();
         let (action_id, user_info, events) = {
             let lock_start = Instant::now();
-            let mut orderbook = ctx.orderbook.lock().await;
-            ctx.metrics.record_lock(lock_start.elapsed(), "withdraw");
-
-            let balance = orderbook.get_balance(&user_info, &request.symbol);
-            if balance.0 < request.amount {
-                return Err(AppError(
-                    StatusCode::BAD_REQUEST,
-                    anyhow::anyhow!(
-                        "Not enough balance: withdrawing {} {} while having {}",
-                        request.amount,
-                        request.symbol,
-                        balance.0
-                    ),
-                ));
-            };
+            let mut orderbook = ctx.orderbook.write().await;
+            ctx.metrics.record_lock(lock_start.elapsed(), "transfer");
 
             let method_start = Instant::now();
             let events = orderbook
-                .withdraw(&request.symbol, &request.amount, &user_info)
-                .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+                .transfer(&request.to, &request.symbol, request.amount, &user_info)
+                .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))?;
             ctx.metrics
-                .record_method(method_start.elapsed(), "withdraw");
+                .record_method(method_start.elapsed(), "transfer");
 
             let apply_start = Instant::now();
             orderbook
                 .apply_events(&user_info, &events)
                 .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
             ctx.metrics
-                .record_event_apply(apply_start.elapsed(), "withdraw");
+                .record_event_apply(apply_start.elapsed(), "transfer");
 
             let action_id = ctx.action_id_counter.fetch_add(1, Ordering::Relaxed);
             (action_id, user_info, events)
         };
         ctx.metrics
-            .record_operation(operation_start.elapsed(), "withdraw");
+            .record_operation(operation_start.elapsed(), "transfer");
 
-        let action_private_input = WithdrawPrivateInput {
+        let action_private_input = TransferPrivateInput {
             public_key,
             signature,
         };
 
-        let orderbook_action = PermissionedOrderbookAction::Withdraw {
+        let orderbook_action = PermissionedOrderbookAction::Transfer {
+            to: request.to,
             symbol: request.symbol,
             amount: request.amount,
-            destination: request.destination,
         };
 
         process_orderbook_action(
@@ -1413,6 +5605,405 @@ async fn withdraw(
     result
 }
 
+/// Arms cancel-on-disconnect for a session key. Every message received on the socket is a
+/// heartbeat: if none is received for `timeout_secs`, the orders the key signed for are cancelled.
+/// The authorization is bound to the nonce: once the user acted, a `CancelOnDisconnectUpdate`
+/// message signs for its orders again.
+#[utoipa::path(
+    get,
+    path = "/cancel_on_disconnect",
+    tag = "feeds",
+    params(CancelOnDisconnectRequest),
+    responses(
+        (status = 101, description = "Armed: every message is a heartbeat"),
+        (status = 400, description = "Invalid timeout or signature"),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, ws)))]
+async fn cancel_on_disconnect(
+    State(ctx): State<RouterCtx>,
+    Query(request): Query<CancelOnDisconnectRequest>,
+    ws: WebSocketUpgrade,
+) -> Result<impl IntoResponse, AppError> {
+    let public_key = hex::decode(&request.public_key).map_err(|e| {
+        AppError(
+            StatusCode::BAD_REQUEST,
+            anyhow::anyhow!("Invalid public key: {e}"),
+        )
+    })?;
+    let signature = hex::decode(&request.signature).map_err(|e| {
+        AppError(
+            StatusCode::BAD_REQUEST,
+            anyhow::anyhow!("Invalid signature: {e}"),
+        )
+    })?;
+    if request.timeout_secs == 0 || request.timeout_secs > MAX_CANCEL_ON_DISCONNECT_TIMEOUT_SECS {
+        return Err(AppError(
+            StatusCode::BAD_REQUEST,
+            anyhow::anyhow!(
+                "Timeout must be between 1 and {MAX_CANCEL_ON_DISCONNECT_TIMEOUT_SECS} seconds"
+            ),
+        ));
+    }
+
+    let user_info = {
+        let user_service = ctx.user_service.read().await;
+        user_service.get_user_info(&request.identity).await?
+    };
+
+    let order_ids: Vec<OrderId> = request
+        .order_ids
+        .split(',')
+        .filter(|order_id| !order_id.is_empty())
+        .map(str::to_string)
+        .collect();
+    let authorization = verify_cancel_authorization(
+        &user_info,
+        &public_key,
+        request.timeout_secs,
+        request.nonce,
+        order_ids,
+        signature,
+    )?;
+    orderbook::utils::verify_session_key_scope(
+        &user_info,
+        &public_key,
+        SessionKeyPermissions::CANCEL,
+        None,
+    )
+    .map_err(|e| AppError(StatusCode::FORBIDDEN, anyhow::anyhow!(e)))?;
+
+    debug!(
+        "Arming cancel on disconnect for user {} with session key {} (timeout: {}s)",
+        user_info.user,
+        hex::encode(&public_key),
+        request.timeout_secs
+    );
+    ctx.cancel_on_disconnect.lock().await.arm(
+        user_info.user.clone(),
+        public_key.clone(),
+        authorization,
+        request.timeout_secs,
+    );
+
+    let registry = ctx.cancel_on_disconnect.clone();
+    let timeout_secs = request.timeout_secs;
+    Ok(ws.on_upgrade(move |socket| {
+        cancel_on_disconnect_heartbeats(socket, registry, user_info, public_key, timeout_secs)
+    }))
+}
+
+/// Checks the signature of a cancel-on-disconnect authorization
+fn verify_cancel_authorization(
+    user_info: &UserInfo,
+    public_key: &[u8],
+    timeout_secs: u64,
+    nonce: u32,
+    order_ids: Vec<OrderId>,
+    signature: Vec<u8>,
+) -> Result<CancelAuthorization, AppError> {
+    orderbook::utils::verify_user_signature_authorization(
+        user_info,
+        &public_key.to_vec(),
+        &orderbook::utils::cancel_on_disconnect_message(
+            &user_info.user,
+            nonce,
+            timeout_secs,
+            &order_ids,
+        ),
+        &signature,
+    )
+    .map_err(|e| {
+        AppError(
+            StatusCode::BAD_REQUEST,
+            anyhow::anyhow!("Failed to verify user signature authorization: {e}"),
+        )
+    })?;
+    Ok(CancelAuthorization {
+        nonce,
+        order_ids,
+        signature,
+    })
+}
+
+async fn cancel_on_disconnect_heartbeats(
+    mut socket: WebSocket,
+    registry: Arc<Mutex<CancelOnDisconnectRegistry>>,
+    user_info: UserInfo,
+    public_key: Vec<u8>,
+    timeout_secs: u64,
+) {
+    let user = user_info.user.clone();
+    while let Some(Ok(message)) = socket.recv().await {
+        if let Message::Close(_) = message {
+            break;
+        }
+        let update = match &message {
+            Message::Text(text) => serde_json::from_str::<CancelOnDisconnectUpdate>(text).ok(),
+            _ => None,
+        };
+        let armed = match update {
+            Some(update) => {
+                let authorization = hex::decode(&update.signature)
+                    .map_err(|e| AppError(StatusCode::BAD_REQUEST, anyhow::anyhow!(e)))
+                    .and_then(|signature| {
+                        verify_cancel_authorization(
+                            &user_info,
+                            &public_key,
+                            timeout_secs,
+                            update.nonce,
+                            update.order_ids,
+                            signature,
+                        )
+                    });
+                match authorization {
+                    Ok(authorization) => {
+                        registry
+                            .lock()
+                            .await
+                            .update(&user, &public_key, authorization)
+                    }
+                    Err(AppError(_, e)) => {
+                        warn!("Ignoring invalid cancel on disconnect update of {user}: {e:#}");
+                        registry.lock().await.heartbeat(&user, &public_key)
+                    }
+                }
+            }
+            None => registry.lock().await.heartbeat(&user, &public_key),
+        };
+        if !armed {
+            // The heartbeat lapsed and the orders were cancelled: the client has to re-arm
+            _ = socket.send(Message::Close(None)).await;
+            break;
+        }
+    }
+    // Disconnecting does not disarm: orders are cancelled once the timeout lapses
+    debug!(
+        "Cancel on disconnect socket of user {user} closed for session key {}",
+        hex::encode(&public_key)
+    );
+}
+
+/// Streams the balances of a user on the `balances@{identity}` channel: the current balances
+/// first, then every change once applied, and again flagged as settled when its commit settles.
+#[utoipa::path(
+    get,
+    path = "/balances",
+    tag = "feeds",
+    params(BalancesFeedRequest),
+    responses(
+        (status = 101, description = "Each message is a balance update", body = BalanceUpdate),
+    )
+)]
+#[cfg_attr(feature = "instrumentation", tracing::instrument(skip(ctx, ws)))]
+async fn balances_feed(
+    State(ctx): State<RouterCtx>,
+    Query(request): Query<BalancesFeedRequest>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    // Subscribing before reading the state so that no change is missed in between
+    let receiver = ctx.balance_feed.subscribe();
+    ws.on_upgrade(move |socket| stream_balances(socket, ctx, request.identity, receiver))
+}
+
+async fn stream_balances(
+    mut socket: WebSocket,
+    ctx: RouterCtx,
+    user: String,
+    mut receiver: tokio::sync::broadcast::Receiver<BalanceFeedEvent>,
+) {
+    let mut subscription = BalanceSubscription::new(user);
+    let mut updates = {
+        let orderbook = ctx.orderbook.read().await;
+        subscription.on_commit(&orderbook, latest_commit_id(&ctx), None)
+    };
+
+    loop {
+        for update in updates.drain(..) {
+            let Ok(text) = serde_json::to_string(&update) else {
+                continue;
+            };
+            if socket.send(Message::Text(text.into())).await.is_err() {
+                return;
+            }
+        }
+
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Ok(BalanceFeedEvent::Changed { commit_id, tx_hash, users }) => {
+                    if users.contains(subscription.user()) {
+                        let orderbook = ctx.orderbook.read().await;
+                        updates = subscription.on_commit(&orderbook, commit_id, Some(&tx_hash));
+                    }
+                }
+                Ok(BalanceFeedEvent::Settled { commit_id }) => {
+                    updates = subscription.on_settled(commit_id);
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!(
+                        "Balances feed of user {} lagged by {skipped} events, resyncing",
+                        subscription.user()
+                    );
+                    let orderbook = ctx.orderbook.read().await;
+                    updates = subscription.on_commit(&orderbook, latest_commit_id(&ctx), None);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    debug!("Balances socket of user {} closed", subscription.user());
+}
+
+/// Rate limits the requests per identity and per client IP: actions (POST) and reads (GET)
+/// have their own buckets. Refused requests get a 429 with the seconds to wait in Retry-After.
+async fn rate_limit(State(ctx): State<RouterCtx>, request: Request, next: Next) -> Response {
+    let class = if request.method() == Method::GET {
+        RequestClass::MarketData
+    } else {
+        RequestClass::Orders
+    };
+    let identity = request
+        .headers()
+        .get(IDENTITY_HEADER)
+        .and_then(|v| v.to_str().ok());
+    let ip = client_ip(&request);
+
+    if let Err(retry_after) = ctx.rate_limiter.check(class, identity, ip.as_deref()) {
+        let retry_after_secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after_secs.to_string())],
+            "Rate limit exceeded",
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+/// Admin requests are read whole to be recorded, up to the default body size limit of the REST API
+const MAX_AUDITED_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// Records the admin actions in the audit log, whatever their outcome. The admin secret being
+/// shared, the actor is the operator named by the `x-admin-actor` header, "admin" otherwise.
+async fn audit_admin_requests(
+    State(ctx): State<RouterCtx>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() == Method::GET || !request.uri().path().starts_with("/admin/") {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, MAX_AUDITED_BODY_SIZE).await else {
+        return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+    };
+    let request = Request::from_parts(parts, axum::body::Body::from(body.clone()));
+
+    let action = request.uri().path().to_string();
+    let method = request.method().to_string();
+    let header_value = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let actor = header_value(ADMIN_ACTOR_HEADER).unwrap_or_else(|| "admin".to_string());
+    let user_agent = header_value(header::USER_AGENT.as_str()).unwrap_or_default();
+    let ip = client_ip(&request).unwrap_or_default();
+    let body = serde_json::from_slice(&body)
+        .map(redact_secret)
+        .unwrap_or_default();
+
+    let response = next.run(request).await;
+
+    let fingerprint = fingerprint(&[
+        method.as_bytes(),
+        action.as_bytes(),
+        ip.as_bytes(),
+        user_agent.as_bytes(),
+        body.to_string().as_bytes(),
+    ]);
+    ctx.audit_log
+        .record(AuditEntry {
+            action,
+            actor,
+            fingerprint,
+            status: Some(response.status().as_u16()),
+            details: serde_json::json!({ "client_ip": ip, "request": body }),
+        })
+        .await;
+    response
+}
+
+/// IP of the client, as forwarded by the proxy in front of the server if any
+fn client_ip(request: &Request) -> Option<String> {
+    request
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .map(|ip| ip.trim().to_string())
+        .or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        })
+}
+
+/// Commit id of the last action sent
+fn latest_commit_id(ctx: &RouterCtx) -> i64 {
+    ctx.action_id_counter.load(Ordering::Relaxed) as i64 - 1
+}
+
+/// How an action changes the exposure of its user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Exposure {
+    Adds,
+    /// Cancellations, accepted whatever the write capacity so that users can always reduce
+    /// their exposure
+    Reduces,
+}
+
+/// Refuses actions adding exposure while the database workers are saturated, too many blob
+/// transactions wait to be sent, or the settled state diverged from the orderbook
+fn ensure_write_capacity(ctx: &RouterCtx, exposure: Exposure) -> Result<(), AppError> {
+    check_write_capacity(
+        ctx.settlement_check.as_deref(),
+        &ctx.worker_queues,
+        &ctx.blob_outbox,
+        exposure,
+    )
+}
+
+fn check_write_capacity(
+    settlement_check: Option<&SettlementCheck>,
+    worker_queues: &WorkerQueues,
+    blob_outbox: &BlobOutbox,
+    exposure: Exposure,
+) -> Result<(), AppError> {
+    if exposure == Exposure::Reduces {
+        return Ok(());
+    }
+    if let Some(settlement_check) = settlement_check {
+        settlement_check
+            .check_active()
+            .map_err(|e| AppError(StatusCode::SERVICE_UNAVAILABLE, e))?;
+    }
+    worker_queues
+        .check_capacity()
+        .map_err(|e| AppError(StatusCode::TOO_MANY_REQUESTS, e))?;
+    blob_outbox
+        .check_capacity()
+        .map_err(|e| AppError(StatusCode::SERVICE_UNAVAILABLE, e))
+}
+
 #[cfg_attr(
     feature = "instrumentation",
     tracing::instrument(skip(ctx, action_private_input))
@@ -1424,6 +6015,27 @@ fn process_orderbook_action<T: BorshSerialize>(
     action_id: u32,
     action_private_input: &T,
     ctx: &RouterCtx,
+) -> Result<impl IntoResponse, AppError> {
+    process_tagged_orderbook_action(
+        user_info,
+        events,
+        orderbook_action,
+        action_id,
+        action_private_input,
+        HashMap::new(),
+        ctx,
+    )
+}
+
+/// Same as `process_orderbook_action`, also storing the client identifiers of the created orders
+fn process_tagged_orderbook_action<T: BorshSerialize>(
+    user_info: UserInfo,
+    events: Vec<OrderbookEvent>,
+    orderbook_action: PermissionedOrderbookAction,
+    action_id: u32,
+    action_private_input: &T,
+    order_tags: HashMap<OrderId, OrderTag>,
+    ctx: &RouterCtx,
 ) -> Result<impl IntoResponse, AppError> {
     let blob_tx = BlobTransaction::new(
         ORDERBOOK_ACCOUNT_IDENTITY,
@@ -1434,6 +6046,10 @@ fn process_orderbook_action<T: BorshSerialize>(
     );
     let tx_hash = blob_tx.hashed();
 
+    // Balances are pushed as soon as the events are applied, before the tx settles
+    ctx.balance_feed
+        .publish_events(action_id as i64, &tx_hash, &events);
+
     let action_private_input = borsh::to_vec(action_private_input).map_err(|e| {
         AppError(
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -1448,6 +6064,7 @@ fn process_orderbook_action<T: BorshSerialize>(
         orderbook_action,
         tx_hash: tx_hash.clone(),
         nonce: action_id,
+        trace_context: HashMap::new(),
     };
 
     // Write events directly using database service
@@ -1459,7 +6076,243 @@ fn process_orderbook_action<T: BorshSerialize>(
         tx_hash: tx_hash.clone(),
         blob_tx,
         prover_request,
+        order_tags,
         context,
     })?;
     Ok(Json(tx_hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use orderbook::model::{Balance, ExecuteState};
+
+    use super::*;
+    use crate::conf::{BlobOutboxConfig, DatabaseWorkersConfig, OutboxOverflowPolicy};
+
+    const PAIR: (&str, &str) = ("ETH", "USDC");
+
+    fn pairs() -> [Pair; 1] {
+        [(PAIR.0.to_string(), PAIR.1.to_string())]
+    }
+
+    fn deposit(state: &ExecuteState, user_info: &UserInfo, amount: u64) -> Vec<OrderbookEvent> {
+        vec![OrderbookEvent::BalanceUpdated {
+            user: user_info.user.clone(),
+            symbol: PAIR.1.to_string(),
+            amount: state.get_balance(user_info, PAIR.1).0 + amount,
+        }]
+    }
+
+    #[tokio::test]
+    async fn events_are_applied_as_generated_without_conflict() {
+        let orderbook = RwLock::new(ExecuteState::default());
+        let user_info = UserInfo::new("alice@wallet".to_string(), b"alice".to_vec());
+        let action_id_counter = AtomicU32::new(3);
+        let generations = AtomicU32::new(0);
+
+        let (action_id, events) = apply_on_pairs(
+            &PairLocks::default(),
+            &orderbook,
+            &action_id_counter,
+            &AppMetrics::new(),
+            "deposit",
+            "deposit",
+            &pairs(),
+            &user_info,
+            |state| {
+                generations.fetch_add(1, Ordering::Relaxed);
+                Ok(deposit(state, &user_info, 10))
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(action_id, 3);
+        assert_eq!(action_id_counter.load(Ordering::Relaxed), 4);
+        assert_eq!(generations.load(Ordering::Relaxed), 1);
+        assert_eq!(events, deposit(&ExecuteState::default(), &user_info, 10));
+        assert_eq!(
+            orderbook.read().await.get_balance(&user_info, PAIR.1),
+            Balance(10)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn events_are_generated_again_when_the_state_changed() {
+        let orderbook = Arc::new(RwLock::new(ExecuteState::default()));
+        let user_info = UserInfo::new("alice@wallet".to_string(), b"alice".to_vec());
+        let generations = AtomicU32::new(0);
+        let writer = std::sync::Mutex::new(None);
+
+        let (action_id, events) = apply_on_pairs(
+            &PairLocks::default(),
+            &orderbook,
+            &AtomicU32::new(0),
+            &AppMetrics::new(),
+            "deposit",
+            "deposit",
+            &pairs(),
+            &user_info,
+            |state| {
+                if generations.fetch_add(1, Ordering::Relaxed) == 0 {
+                    // An action on another pair credits the user while the events are generated
+                    let concurrent = orderbook.clone();
+                    let key = user_info.get_key();
+                    *writer.lock().unwrap() = Some(tokio::spawn(async move {
+                        concurrent
+                            .write()
+                            .await
+                            .update_balances(PAIR.1, vec![(key, Balance(100))])
+                            .unwrap();
+                    }));
+                    // The lock is fair: readers wait once the write is queued
+                    while orderbook.try_read().is_ok() {
+                        std::hint::spin_loop();
+                    }
+                }
+                Ok(deposit(state, &user_info, 10))
+            },
+        )
+        .await
+        .unwrap();
+        writer.into_inner().unwrap().unwrap().await.unwrap();
+
+        assert_eq!(action_id, 0);
+        assert_eq!(generations.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            events,
+            vec![OrderbookEvent::BalanceUpdated {
+                user: user_info.user.clone(),
+                symbol: PAIR.1.to_string(),
+                amount: 110,
+            }]
+        );
+        assert_eq!(
+            orderbook.read().await.get_balance(&user_info, PAIR.1),
+            Balance(110)
+        );
+    }
+
+    fn worker_queues() -> WorkerQueues {
+        WorkerQueues::new(DatabaseWorkersConfig {
+            count: 1,
+            queue_capacity: 10,
+        })
+    }
+
+    fn overflowing_outbox() -> BlobOutbox {
+        let outbox = BlobOutbox::new(BlobOutboxConfig {
+            retry_backoff_ms: 100,
+            max_retry_backoff_ms: 1_000,
+            max_pending: 1,
+            overflow_policy: OutboxOverflowPolicy::Reject,
+        });
+        outbox.on_enqueued();
+        outbox
+    }
+
+    #[test]
+    fn actions_adding_exposure_are_refused_while_the_outbox_overflows() {
+        let AppError(status, _) = check_write_capacity(
+            None,
+            &worker_queues(),
+            &overflowing_outbox(),
+            Exposure::Adds,
+        )
+        .unwrap_err();
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[test]
+    fn cancellations_bypass_the_write_capacity() {
+        assert!(check_write_capacity(
+            None,
+            &worker_queues(),
+            &overflowing_outbox(),
+            Exposure::Reduces
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn actions_adding_exposure_are_refused_while_the_workers_are_saturated() {
+        let worker_queues = worker_queues();
+        for _ in 0..10 {
+            worker_queues.on_queued();
+        }
+        let outbox = BlobOutbox::new(BlobOutboxConfig::default());
+        let AppError(status, _) =
+            check_write_capacity(None, &worker_queues, &outbox, Exposure::Adds).unwrap_err();
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(check_write_capacity(None, &worker_queues, &outbox, Exposure::Reduces).is_ok());
+    }
+
+    fn api_key(session_public_key: Option<Vec<u8>>) -> ApiKeyIdentity {
+        ApiKeyIdentity {
+            identity: "alice@wallet".to_string(),
+            session_public_key,
+        }
+    }
+
+    fn headers(entries: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entries {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn api_key_authenticates_its_identity() {
+        let auth = AuthHeaders::from_api_key(api_key(None), &headers(&[])).unwrap();
+        assert_eq!(auth.identity, "alice@wallet");
+        assert_eq!(auth.public_key, None);
+
+        let auth = AuthHeaders::from_api_key(
+            api_key(None),
+            &headers(&[
+                (IDENTITY_HEADER, "alice@wallet"),
+                (PUBLIC_KEY_HEADER, "0a0b"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(auth.identity, "alice@wallet");
+        assert_eq!(auth.public_key, Some(vec![0x0a, 0x0b]));
+    }
+
+    #[test]
+    fn api_key_of_another_identity_is_refused() {
+        let AppError(status, _) =
+            AuthHeaders::from_api_key(api_key(None), &headers(&[(IDENTITY_HEADER, "bob@wallet")]))
+                .unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn api_key_signs_with_the_session_key_it_is_bound_to() {
+        let auth = AuthHeaders::from_api_key(api_key(Some(vec![1, 2])), &headers(&[])).unwrap();
+        assert_eq!(auth.public_key, Some(vec![1, 2]));
+
+        let auth = AuthHeaders::from_api_key(
+            api_key(Some(vec![1, 2])),
+            &headers(&[(PUBLIC_KEY_HEADER, "0102")]),
+        )
+        .unwrap();
+        assert_eq!(auth.public_key, Some(vec![1, 2]));
+
+        let AppError(status, _) = AuthHeaders::from_api_key(
+            api_key(Some(vec![1, 2])),
+            &headers(&[(PUBLIC_KEY_HEADER, "0303")]),
+        )
+        .unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[test]
+    fn api_key_selects_sub_accounts_of_its_identity() {
+        let auth =
+            AuthHeaders::from_api_key(api_key(None), &headers(&[(SUB_ACCOUNT_HEADER, "hedge")]))
+                .unwrap();
+        assert_eq!(auth.identity, sub_account_identity("alice@wallet", "hedge"));
+    }
+}
@@ -0,0 +1,277 @@
+//! gRPC API served alongside the REST one, from `proto/orderbook.proto`. Order entry goes
+//! through the same handlers as `POST /create_order` and `POST /cancel_order`, authenticated by
+//! the same headers sent as metadata.
+
+use std::{net::SocketAddr, pin::Pin};
+
+use axum::{extract::State, http::HeaderMap, response::IntoResponse, Json};
+use client_sdk::contract_indexer::AppError;
+use futures::{stream, Stream};
+use orderbook::{
+    model::{ExecuteState, Order, OrderSide, OrderType, Pair},
+    order_manager::PriceLevel,
+};
+use reqwest::StatusCode;
+use sdk::TxHash;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tonic::{Request, Response, Status};
+use tracing::info;
+
+use super::{
+    latest_commit_id, CancelOrderRequest, CreateOrderRequest, RouterCtx, API_KEY_HEADER,
+    IDENTITY_HEADER, PUBLIC_KEY_HEADER, SIGNATURE_HEADER,
+};
+use crate::{balance_feed::BalanceFeedEvent, rate_limit::RequestClass};
+
+pub mod proto {
+    tonic::include_proto!("hyliquid.orderbook.v1");
+}
+
+use proto::orderbook_server::{Orderbook, OrderbookServer};
+
+/// Levels sent on each side of the book when the request leaves the depth unset
+const DEFAULT_BOOK_DEPTH: usize = 20;
+
+pub(super) async fn serve(ctx: RouterCtx, port: u16) -> anyhow::Result<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    info!("📡 Serving the gRPC API on {addr}");
+    tonic::transport::Server::builder()
+        .add_service(OrderbookServer::new(OrderbookService { ctx }))
+        .serve(addr)
+        .await?;
+    Ok(())
+}
+
+struct OrderbookService {
+    ctx: RouterCtx,
+}
+
+impl OrderbookService {
+    /// Headers of an action, from the metadata of its request, once rate limited as the REST
+    /// actions are
+    fn action_headers<T>(&self, request: &Request<T>) -> Result<HeaderMap, Status> {
+        let headers = request.metadata().clone().into_headers();
+        let identity = headers.get(IDENTITY_HEADER).and_then(|v| v.to_str().ok());
+        let ip = request.remote_addr().map(|addr| addr.ip().to_string());
+        if let Err(retry_after) =
+            self.ctx
+                .rate_limiter
+                .check(RequestClass::Orders, identity, ip.as_deref())
+        {
+            return Err(Status::resource_exhausted(format!(
+                "Rate limit exceeded, retry after {}s",
+                retry_after.as_secs_f64().ceil().max(1.0)
+            )));
+        }
+
+        // The session key may only be omitted when the API key is bound to one
+        if !headers.contains_key(SIGNATURE_HEADER)
+            || !(headers.contains_key(PUBLIC_KEY_HEADER) || headers.contains_key(API_KEY_HEADER))
+        {
+            return Err(Status::unauthenticated(
+                "Missing x-public-key or x-signature metadata",
+            ));
+        }
+        Ok(headers)
+    }
+}
+
+#[tonic::async_trait]
+impl Orderbook for OrderbookService {
+    async fn create_order(
+        &self,
+        request: Request<proto::CreateOrderRequest>,
+    ) -> Result<Response<proto::ActionReply>, Status> {
+        let headers = self.action_headers(&request)?;
+        let request = request.into_inner();
+        let order = request
+            .order
+            .ok_or_else(|| Status::invalid_argument("Missing order"))?;
+        let request = CreateOrderRequest {
+            order: order.try_into()?,
+            client_order_id: request.client_order_id,
+            tag: request.tag,
+        };
+        action_reply(super::create_order(State(self.ctx.clone()), headers, Json(request)).await)
+            .await
+    }
+
+    async fn cancel_order(
+        &self,
+        request: Request<proto::CancelOrderRequest>,
+    ) -> Result<Response<proto::ActionReply>, Status> {
+        let headers = self.action_headers(&request)?;
+        let request = CancelOrderRequest {
+            order_id: request.into_inner().order_id,
+        };
+        action_reply(super::cancel_order(State(self.ctx.clone()), headers, Json(request)).await)
+            .await
+    }
+
+    type StreamBookStream = Pin<Box<dyn Stream<Item = Result<proto::BookSnapshot, Status>> + Send>>;
+
+    async fn stream_book(
+        &self,
+        request: Request<proto::StreamBookRequest>,
+    ) -> Result<Response<Self::StreamBookStream>, Status> {
+        let request = request.into_inner();
+        let symbol = format!("{}/{}", request.base, request.quote);
+        if self
+            .ctx
+            .asset_service
+            .read()
+            .await
+            .get_instrument(&symbol)
+            .is_none()
+        {
+            return Err(Status::not_found(format!("Unknown pair {symbol}")));
+        }
+
+        let book = BookStream {
+            // Subscribing before reading the book so that no change is missed in between
+            receiver: self.ctx.balance_feed.subscribe(),
+            ctx: self.ctx.clone(),
+            pair: (request.base, request.quote),
+            depth: match request.depth {
+                0 => DEFAULT_BOOK_DEPTH,
+                depth => depth as usize,
+            },
+            last: None,
+        };
+        Ok(Response::new(Box::pin(stream::unfold(
+            book,
+            |mut book| async move {
+                let snapshot = book.next_snapshot().await?;
+                Some((Ok(snapshot), book))
+            },
+        ))))
+    }
+}
+
+/// Book of a pair streamed to a client. Every commit applied to the state changes the balances
+/// of the users it involves, so the balance feed tells when the book may have changed.
+struct BookStream {
+    ctx: RouterCtx,
+    receiver: Receiver<BalanceFeedEvent>,
+    pair: Pair,
+    depth: usize,
+    last: Option<proto::BookSnapshot>,
+}
+
+impl BookStream {
+    /// Current book on the first call, then the book once it changed. None once the feed closed.
+    async fn next_snapshot(&mut self) -> Option<proto::BookSnapshot> {
+        loop {
+            if self.last.is_some() {
+                match self.receiver.recv().await {
+                    Ok(BalanceFeedEvent::Changed { .. }) | Err(RecvError::Lagged(_)) => {}
+                    Ok(BalanceFeedEvent::Settled { .. }) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+
+            let snapshot = {
+                let orderbook = self.ctx.orderbook.read().await;
+                proto::BookSnapshot {
+                    commit_id: latest_commit_id(&self.ctx),
+                    bids: book_levels(&orderbook, &self.pair, OrderSide::Bid, self.depth),
+                    asks: book_levels(&orderbook, &self.pair, OrderSide::Ask, self.depth),
+                }
+            };
+            if self
+                .last
+                .as_ref()
+                .is_some_and(|last| last.bids == snapshot.bids && last.asks == snapshot.asks)
+            {
+                continue;
+            }
+            self.last = Some(snapshot.clone());
+            return Some(snapshot);
+        }
+    }
+}
+
+/// Best `depth` levels of a side of the book, best first
+fn book_levels(
+    orderbook: &ExecuteState,
+    pair: &Pair,
+    side: OrderSide,
+    depth: usize,
+) -> Vec<proto::BookLevel> {
+    let order_manager = &orderbook.order_manager;
+    let Some(levels) = order_manager.side_map(&side).get(pair) else {
+        return Vec::new();
+    };
+    let level = |(price, order_ids): (&u64, &PriceLevel)| proto::BookLevel {
+        price: *price,
+        quantity: order_ids
+            .iter()
+            .filter_map(|order_id| order_manager.orders.get(order_id))
+            .map(|order| order.quantity)
+            .sum(),
+        orders: order_ids.len() as u32,
+    };
+    match side {
+        OrderSide::Bid => levels.iter().rev().take(depth).map(level).collect(),
+        OrderSide::Ask => levels.iter().take(depth).map(level).collect(),
+    }
+}
+
+/// Transaction hash returned by a REST handler
+async fn action_reply(
+    result: Result<impl IntoResponse, AppError>,
+) -> Result<Response<proto::ActionReply>, Status> {
+    let response = result.map_err(app_error_status)?.into_response();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|e| Status::internal(format!("Failed to read the action response: {e}")))?;
+    let tx_hash: TxHash = serde_json::from_slice(&body)
+        .map_err(|e| Status::internal(format!("Invalid action response: {e}")))?;
+    Ok(Response::new(proto::ActionReply { tx_hash: tx_hash.0 }))
+}
+
+fn app_error_status(AppError(status, error): AppError) -> Status {
+    let message = format!("{error:#}");
+    match status {
+        StatusCode::BAD_REQUEST => Status::invalid_argument(message),
+        StatusCode::UNAUTHORIZED => Status::unauthenticated(message),
+        StatusCode::FORBIDDEN => Status::permission_denied(message),
+        StatusCode::NOT_FOUND => Status::not_found(message),
+        StatusCode::CONFLICT => Status::failed_precondition(message),
+        StatusCode::TOO_MANY_REQUESTS => Status::resource_exhausted(message),
+        StatusCode::SERVICE_UNAVAILABLE => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
+
+impl TryFrom<proto::Order> for Order {
+    type Error = Status;
+
+    fn try_from(order: proto::Order) -> Result<Self, Status> {
+        let order_side = match order.order_side() {
+            proto::OrderSide::Bid => OrderSide::Bid,
+            proto::OrderSide::Ask => OrderSide::Ask,
+            proto::OrderSide::Unspecified => {
+                return Err(Status::invalid_argument("Missing order side"))
+            }
+        };
+        let order_type = match order.order_type() {
+            proto::OrderType::Market => OrderType::Market,
+            proto::OrderType::Limit => OrderType::Limit,
+            proto::OrderType::Stop => OrderType::Stop,
+            proto::OrderType::StopLimit => OrderType::StopLimit,
+            proto::OrderType::StopMarket => OrderType::StopMarket,
+            proto::OrderType::Unspecified => {
+                return Err(Status::invalid_argument("Missing order type"))
+            }
+        };
+        Ok(Order {
+            order_id: order.order_id,
+            order_type,
+            order_side,
+            price: order.price,
+            pair: (order.base, order.quote),
+            quantity: order.quantity,
+        })
+    }
+}
@@ -0,0 +1,59 @@
+//! OpenAPI description of the orderbook routes, merged into the spec served by the REST API.
+//! Each route documents its own path through `#[utoipa::path]`; this holds what they share.
+
+use utoipa::{
+    openapi::{
+        security::{ApiKey, ApiKeyValue, SecurityScheme},
+        OpenApi,
+    },
+    Modify,
+};
+
+use super::{API_KEY_HEADER, IDENTITY_HEADER, PUBLIC_KEY_HEADER, SIGNATURE_HEADER};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    modifiers(&AuthHeaders),
+    tags(
+        (name = "actions", description = "Actions signed by a session key of the user, returning the hash of the blob transaction they are sent in"),
+        (name = "account", description = "Settings and state of the account of the identity header"),
+        (name = "status", description = "Markets, bridge, prover and health status"),
+        (name = "feeds", description = "WebSocket feeds, configured by their query parameters"),
+        (name = "admin", description = "Operator endpoints, authenticated by the admin secret of the request body, or of the x-admin-secret header for reads, and recorded in the audit log"),
+    )
+)]
+pub(super) struct OrderbookApi;
+
+/// Headers authenticating the requests. The identity header is only trusted alone by the
+/// reads of an account: actions also carry the session key and its signature of the action
+/// message, and the API key stands for the identity. `x-sub-account` selects a sub-account of
+/// the authenticated identity.
+struct AuthHeaders;
+
+impl Modify for AuthHeaders {
+    fn modify(&self, openapi: &mut OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        for (name, header, description) in [
+            ("identity", IDENTITY_HEADER, "Identity of the user"),
+            (
+                "session_key",
+                PUBLIC_KEY_HEADER,
+                "Hex encoded public key of a session key of the user",
+            ),
+            (
+                "signature",
+                SIGNATURE_HEADER,
+                "Hex encoded signature of the action message by the session key",
+            ),
+            ("api_key", API_KEY_HEADER, "API key created by the user"),
+        ] {
+            components.add_security_scheme(
+                name,
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                    header,
+                    description,
+                ))),
+            );
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Endpoints driving the server from integration tests (e.g. hylix scenarios), only built with
+//! the `test-mode` feature. They skip authentication: never enable it in a deployment.
+
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Json, Router,
+};
+use client_sdk::contract_indexer::AppError;
+use reqwest::StatusCode;
+use sdk::{ContractName, Identity};
+use serde::{Deserialize, Serialize};
+
+use super::{PendingDeposit, RouterCtx};
+
+pub(super) fn routes() -> Router<RouterCtx> {
+    Router::new()
+        .route("/test/deposit", post(inject_deposit))
+        .route("/test/clock", get(get_clock))
+        .route("/test/clock/advance", post(advance_clock))
+}
+
+#[derive(Debug, Deserialize)]
+struct InjectDepositRequest {
+    identity: String,
+    contract_name: String,
+    amount: u128,
+}
+
+#[derive(Debug, Serialize)]
+struct ClockResponse {
+    /// Time elapsed on the server clock since it started
+    elapsed_ms: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdvanceClockRequest {
+    millis: u64,
+}
+
+/// Credits a user as if the token transfer of a deposit had settled, without the transfer
+async fn inject_deposit(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<InjectDepositRequest>,
+) -> Result<Json<()>, AppError> {
+    ctx.execute_deposit(PendingDeposit {
+        sender: Identity(request.identity),
+        contract_name: ContractName(request.contract_name),
+        amount: request.amount,
+        context: Default::default(),
+    })
+    .await
+    .map_err(|e| AppError(StatusCode::BAD_REQUEST, e))?;
+    Ok(Json(()))
+}
+
+async fn get_clock(State(ctx): State<RouterCtx>) -> Json<ClockResponse> {
+    Json(ClockResponse {
+        elapsed_ms: ctx.clock.elapsed().as_millis() as u64,
+    })
+}
+
+/// Moves the clock forward, and cancels the orders of the sessions whose heartbeat lapsed
+/// before answering, so that tests do not wait for the next heartbeat check
+async fn advance_clock(
+    State(ctx): State<RouterCtx>,
+    Json(request): Json<AdvanceClockRequest>,
+) -> Result<Json<ClockResponse>, AppError> {
+    ctx.clock.advance(Duration::from_millis(request.millis));
+    ctx.cancel_lapsed_sessions()
+        .await
+        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(get_clock(State(ctx)).await)
+}
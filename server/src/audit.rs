@@ -0,0 +1,213 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use sqlx::{PgPool, Row};
+use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex};
+use tracing::error;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::conf::AuditConfig;
+
+/// Maximum number of entries served by a page of the audit log
+pub const MAX_AUDIT_ENTRIES: i64 = 500;
+
+/// Privileged action, recorded in the audit log
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// Route of an admin request, or kind of the action, e.g. "bridge_claim"
+    pub action: String,
+    /// Identity of the user, or operator of an admin request
+    pub actor: String,
+    /// See [`fingerprint`]
+    pub fingerprint: String,
+    /// Response status of an admin request, None for the other actions, recorded once done
+    pub status: Option<u16>,
+    /// Parameters of the action, without the admin secret
+    pub details: serde_json::Value,
+}
+
+/// Entry of the audit log, as served by `GET /admin/audit_log`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuditRecord {
+    pub audit_id: i64,
+    pub action: String,
+    pub actor: String,
+    pub fingerprint: String,
+    pub status: Option<i16>,
+    #[schema(value_type = Object)]
+    pub details: serde_json::Value,
+    /// Unix timestamp in milliseconds
+    pub created_at: i64,
+}
+
+/// Filters of the audit log, newest entries first
+#[derive(Debug, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    pub action: Option<String>,
+    pub actor: Option<String>,
+    /// Entries before this one, to read the next page
+    pub before_audit_id: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Append-only log of the privileged actions: admin requests, bridge address claims, large
+/// withdrawals and config changes. Entries are inserted in `audit_log`, which refuses updates
+/// and deletes, and appended as JSON lines to the file of the config, a copy that does not
+/// depend on the database.
+pub struct AuditLog {
+    pool: PgPool,
+    file: Option<Mutex<File>>,
+    /// Withdrawals of an asset above its threshold are logged
+    withdraw_thresholds: HashMap<String, u64>,
+}
+
+impl AuditLog {
+    pub async fn new(pool: PgPool, config: &AuditConfig, data_directory: &Path) -> Result<Self> {
+        let file = if config.file.is_empty() {
+            None
+        } else {
+            let path = data_directory.join(&config.file);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await
+                .with_context(|| format!("opening the audit log file {}", path.display()))?;
+            Some(Mutex::new(file))
+        };
+        Ok(AuditLog {
+            pool,
+            file,
+            withdraw_thresholds: config
+                .withdraw_thresholds
+                .iter()
+                .map(|threshold| (threshold.symbol.clone(), threshold.amount))
+                .collect(),
+        })
+    }
+
+    /// Whether a withdrawal of `amount` of `symbol` is to be logged
+    pub fn is_large_withdrawal(&self, symbol: &str, amount: u64) -> bool {
+        self.withdraw_thresholds
+            .get(symbol)
+            .is_some_and(|threshold| amount > *threshold)
+    }
+
+    /// Records `entry` in the database and the file. The action already happened, so failures
+    /// are logged rather than returned.
+    pub async fn record(&self, entry: AuditEntry) {
+        if let Err(e) = self.insert(&entry).await {
+            error!(action = %entry.action, actor = %entry.actor, "Could not record the audit entry: {e:#}");
+        }
+        if let Some(file) = &self.file {
+            if let Err(e) = append_line(file, &entry).await {
+                error!(action = %entry.action, actor = %entry.actor, "Could not write the audit entry to its file: {e:#}");
+            }
+        }
+    }
+
+    async fn insert(&self, entry: &AuditEntry) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO audit_log (action, actor, fingerprint, status, details)
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(&entry.action)
+        .bind(&entry.actor)
+        .bind(&entry.fingerprint)
+        .bind(entry.status.map(|status| status as i16))
+        .bind(&entry.details)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Entries matching `query`, newest first
+    pub async fn entries(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        let rows = sqlx::query(
+            "
+            SELECT audit_id, action, actor, fingerprint, status, details,
+                (EXTRACT(EPOCH FROM created_at) * 1000)::bigint AS created_at
+            FROM audit_log
+            WHERE ($1::text IS NULL OR action = $1)
+                AND ($2::text IS NULL OR actor = $2)
+                AND ($3::bigint IS NULL OR audit_id < $3)
+            ORDER BY audit_id DESC
+            LIMIT $4
+            ",
+        )
+        .bind(&query.action)
+        .bind(&query.actor)
+        .bind(query.before_audit_id)
+        .bind(
+            query
+                .limit
+                .unwrap_or(MAX_AUDIT_ENTRIES)
+                .clamp(1, MAX_AUDIT_ENTRIES),
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| AuditRecord {
+                audit_id: row.get("audit_id"),
+                action: row.get("action"),
+                actor: row.get("actor"),
+                fingerprint: row.get("fingerprint"),
+                status: row.get("status"),
+                details: row.get("details"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+}
+
+async fn append_line(file: &Mutex<File>, entry: &AuditEntry) -> Result<()> {
+    #[derive(Serialize)]
+    struct Line<'a> {
+        time: u64,
+        #[serde(flatten)]
+        entry: &'a AuditEntry,
+    }
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let mut line = serde_json::to_vec(&Line { time, entry })?;
+    line.push(b'\n');
+
+    let mut file = file.lock().await;
+    file.write_all(&line).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+/// Hex encoded SHA3-256 digest of the parts of a request, e.g. its method, path, client and
+/// body. Each part is prefixed by its length, so that they cannot run into each other.
+pub fn fingerprint(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha3_256::new();
+    for part in parts {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// `details` without the admin secret, that requests carry in their body
+pub fn redact_secret(mut details: serde_json::Value) -> serde_json::Value {
+    if let Some(object) = details.as_object_mut() {
+        object.remove("secret");
+    }
+    details
+}
@@ -0,0 +1,163 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use orderbook::{
+    model::{ExecuteState, OrderbookEvent},
+    zk::smt::GetKey,
+};
+use sdk::TxHash;
+use serde::Serialize;
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+/// Number of feed events buffered for slow subscribers, which resync once they lag further behind
+const BALANCE_FEED_CAPACITY: usize = 4_096;
+
+#[derive(Debug, Clone)]
+pub enum BalanceFeedEvent {
+    /// The balances of these users changed in a commit, whose events were applied to the state
+    Changed {
+        commit_id: i64,
+        tx_hash: TxHash,
+        users: Arc<BTreeSet<String>>,
+    },
+    /// Every commit up to this one settled successfully
+    Settled { commit_id: i64 },
+}
+
+/// Broadcasts balance changes as soon as the events of an action are applied, before they settle
+pub struct BalanceFeed {
+    sender: broadcast::Sender<BalanceFeedEvent>,
+}
+
+impl Default for BalanceFeed {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(BALANCE_FEED_CAPACITY);
+        BalanceFeed { sender }
+    }
+}
+
+impl BalanceFeed {
+    pub fn subscribe(&self) -> broadcast::Receiver<BalanceFeedEvent> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish_events(&self, commit_id: i64, tx_hash: &TxHash, events: &[OrderbookEvent]) {
+        let users: BTreeSet<String> = events
+            .iter()
+            .filter_map(|event| match event {
+                OrderbookEvent::BalanceUpdated { user, .. } => Some(user.clone()),
+                _ => None,
+            })
+            .collect();
+        if users.is_empty() {
+            return;
+        }
+        // Sending only fails when nobody is subscribed
+        let _ = self.sender.send(BalanceFeedEvent::Changed {
+            commit_id,
+            tx_hash: tx_hash.clone(),
+            users: Arc::new(users),
+        });
+    }
+
+    pub fn publish_settled(&self, commit_id: i64) {
+        let _ = self.sender.send(BalanceFeedEvent::Settled { commit_id });
+    }
+}
+
+/// Balance of an asset pushed on the `balances@{identity}` channel
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct BalanceUpdate {
+    pub channel: String,
+    pub symbol: String,
+    /// Balance that can be spent or withdrawn
+    pub available: u64,
+    /// Amount locked in open orders
+    pub locked: u64,
+    /// Whether the commit this balance was last changed in has settled
+    pub settled: bool,
+    pub commit_id: i64,
+    pub tx_hash: Option<String>,
+}
+
+/// Balances last sent to a subscriber, so that only those which changed are pushed
+pub struct BalanceSubscription {
+    user: String,
+    sent: BTreeMap<String, BalanceUpdate>,
+}
+
+impl BalanceSubscription {
+    pub fn new(user: String) -> Self {
+        BalanceSubscription {
+            user,
+            sent: BTreeMap::new(),
+        }
+    }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    /// Reads the balances of the user once a commit was applied, returning those which changed.
+    /// The state may already include later commits: unchanged balances still move to this commit
+    /// so that they are not reported as settled too early.
+    pub fn on_commit(
+        &mut self,
+        state: &ExecuteState,
+        commit_id: i64,
+        tx_hash: Option<&TxHash>,
+    ) -> Vec<BalanceUpdate> {
+        let Ok(user_info) = state.get_user_info(&self.user) else {
+            return Vec::new();
+        };
+        let user_key = user_info.get_key();
+        let locked = state.get_locked_balances(&user_info);
+        let tx_hash = tx_hash.map(|tx_hash| tx_hash.0.clone());
+
+        let mut updates = Vec::new();
+        for (symbol, balances) in &state.balances {
+            let Some(balance) = balances.get(&user_key) else {
+                continue;
+            };
+            let available = balance.0;
+            let locked = locked.get(symbol).copied().unwrap_or_default();
+            match self.sent.get_mut(symbol) {
+                Some(sent) if sent.available == available && sent.locked == locked => {
+                    if !sent.settled && sent.commit_id < commit_id {
+                        sent.commit_id = commit_id;
+                        sent.tx_hash = tx_hash.clone();
+                    }
+                }
+                _ => {
+                    let update = BalanceUpdate {
+                        channel: format!("balances@{}", self.user),
+                        symbol: symbol.clone(),
+                        available,
+                        locked,
+                        settled: false,
+                        commit_id,
+                        tx_hash: tx_hash.clone(),
+                    };
+                    self.sent.insert(symbol.clone(), update.clone());
+                    updates.push(update);
+                }
+            }
+        }
+        updates
+    }
+
+    /// Marks the balances last changed at or before `commit_id` as settled, returning them
+    pub fn on_settled(&mut self, commit_id: i64) -> Vec<BalanceUpdate> {
+        self.sent
+            .values_mut()
+            .filter(|sent| !sent.settled && sent.commit_id <= commit_id)
+            .map(|sent| {
+                sent.settled = true;
+                sent.clone()
+            })
+            .collect()
+    }
+}
@@ -1,8 +1,6 @@
 use anyhow::{Context, Result};
 use axum::Router;
 use clap::Parser;
-use client_sdk::helpers::sp1::SP1Prover;
-use contracts::ORDERBOOK_ELF;
 use hyli_modules::{
     bus::{metrics::BusMetrics, SharedMessageBus},
     modules::{
@@ -10,17 +8,17 @@ use hyli_modules::{
         rest::{RestApi, RestApiRunContext},
         BuildApiContextInner, ModulesHandler,
     },
-    utils::logger::setup_otlp,
 };
-use prometheus::Registry;
 use sdk::{api::NodeInfo, info};
 use server::{
     conf::Conf,
-    prover::{OrderbookProverCtx, OrderbookProverModule},
+    prover::{proving_backend, OrderbookProverCtx, OrderbookProverModule, ProverMetrics},
     setup::{setup_database, setup_services, ServiceContext},
+    snapshot::SnapshotStore,
+    telemetry::setup_tracing,
 };
-use sp1_sdk::{Prover, ProverClient};
 use std::{collections::HashSet, sync::Arc, time::Duration};
+use tokio::sync::Mutex;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -34,6 +32,11 @@ pub struct Args {
     #[arg(long, default_value = "false")]
     pub no_check: bool,
 
+    /// Execute txs natively and submit their outputs as proofs of the test verifier,
+    /// for integration and load tests against a node accepting them
+    #[arg(long, default_value = "false")]
+    pub mock_prover: bool,
+
     #[arg(long, default_value = "orderbook")]
     pub orderbook_cn: String,
 }
@@ -43,8 +46,6 @@ fn main() -> Result<()> {
     let args = Args::parse();
     let config = Conf::new(args.config_file.clone()).context("reading config file")?;
 
-    setup_otlp(&config.log_format, "hyliquid".to_string(), args.tracing)?;
-
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         // Results in poor threading performance otherwise.
@@ -55,7 +56,18 @@ fn main() -> Result<()> {
 }
 
 async fn actual_main(args: Args, config: Conf) -> Result<()> {
+    // The OTLP exporter is built within the runtime it sends the spans from
+    setup_tracing(
+        &config.log_format,
+        &config.log_filter,
+        &config.tracing,
+        args.tracing,
+    )?;
     let config = Arc::new(config);
+
+    // Installed before any meter is created, so that the metrics are exported to the registry
+    let registry = hyli_modules::telemetry::init_prometheus_registry_meter_provider()?;
+
     info!("Starting autoprover with config: {:?}", &config);
 
     let pool = setup_database(&config, false).await?;
@@ -65,12 +77,26 @@ async fn actual_main(args: Args, config: Conf) -> Result<()> {
         bridge_service: _,
         book_service,
         node_client,
+        resilient_node_client,
         indexer_client,
         validator_lane_id,
     } = setup_services(&config, pool.clone(), false, false).await?;
 
     let secret = config.secret.clone();
 
+    // Restarts from the full state checkpointed by a server sharing the data directory
+    let snapshots = if config.snapshot.enabled {
+        std::fs::create_dir_all(&config.data_directory).context("creating data directory")?;
+        Some(SnapshotStore::open(
+            &config.data_directory,
+            pool.clone(),
+            secret.clone(),
+            validator_lane_id.clone(),
+        )?)
+    } else {
+        None
+    };
+
     let last_settled_tx = server::init::get_last_settled_tx(
         asset_service.clone(),
         false,
@@ -89,18 +115,28 @@ async fn actual_main(args: Args, config: Conf) -> Result<()> {
         !args.no_check,
         &last_settled_tx,
         false,
+        snapshots.as_ref(),
+        &pool,
     )
     .await
     .map_err(|e| anyhow::Error::msg(e.1))?;
 
-    info!("Setup sp1 prover client");
-    let local_client = ProverClient::builder().cpu().build();
-    let (pk, _) = local_client.setup(ORDERBOOK_ELF);
-
-    info!("Building Proving Key");
-    let prover = SP1Prover::new(pk).await;
-
-    let _ = hyli_modules::telemetry::init_prometheus_registry_meter_provider()?;
+    let settled_commit_id = match &last_settled_tx {
+        Some(tx_hash) => asset_service
+            .read()
+            .await
+            .get_commit_id_from_tx_hash(tx_hash)
+            .await
+            .unwrap_or(0),
+        None => 0,
+    };
+
+    let backend = proving_backend(
+        config.prover_backend,
+        &config.prover_network,
+        args.mock_prover,
+    )?;
+    let prover = backend.prover().await?;
 
     let bus = SharedMessageBus::new(BusMetrics::global());
     std::fs::create_dir_all(&config.data_directory).context("creating data directory")?;
@@ -111,12 +147,21 @@ async fn actual_main(args: Args, config: Conf) -> Result<()> {
     });
 
     let orderbook_prover_ctx = Arc::new(OrderbookProverCtx {
-        node_client: node_client.clone(),
+        node_client: resilient_node_client,
         orderbook_cn: args.orderbook_cn.clone().into(),
-        prover: Arc::new(prover),
+        direct_prover: backend.direct_prover()?,
+        backend,
+        prover,
         lane_id: validator_lane_id,
-        initial_orderbook: full_state,
+        orderbook: Arc::new(Mutex::new(full_state)),
+        initial_commit_id: settled_commit_id,
         pool: pool.clone(),
+        max_txs_per_proof: config.max_txs_per_proof,
+        proof_batch_window: Duration::from_millis(config.proof_batch_window_ms),
+        proving_workers: config.proving_workers,
+        max_proof_reordering: config.max_proof_reordering,
+        metrics: ProverMetrics::new(),
+        balance_feed: None,
     });
 
     let mut handler = ModulesHandler::new(&bus, config.data_directory.clone()).await;
@@ -153,7 +198,7 @@ async fn actual_main(args: Args, config: Conf) -> Result<()> {
         .build_module::<RestApi>(RestApiRunContext {
             port: config.rest_server_port + 1,
             max_body_size: config.rest_server_max_body_size,
-            registry: Registry::new(),
+            registry,
             router,
             openapi,
             info: NodeInfo {
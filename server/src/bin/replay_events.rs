@@ -0,0 +1,147 @@
+//! Replays the `contract_events` table from genesis through `ExecuteState::apply_events`,
+//! and checks the rebuilt state against the onchain commitment of every settled commit.
+//! Stops at the first divergence, with the events of the commit and the state diff.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use client_sdk::rest_client::{NodeApiClient, NodeApiHttpClient};
+use hyli_modules::utils::logger::setup_tracing;
+use orderbook::model::ExecuteState;
+use sdk::{info, LaneId, StateCommitment};
+use server::{
+    replay::{
+        commit_state, fetch_commit_events, fetch_onchain_commitments, log_state_diff, CommitEvents,
+        OnchainCommitment,
+    },
+    setup::setup_database,
+};
+use tracing::{error, warn};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    #[arg(long, default_value = "config.toml")]
+    pub config_file: Vec<String>,
+
+    #[arg(long, default_value = "orderbook")]
+    pub orderbook_cn: String,
+
+    /// Last commit_id to replay. All commits are replayed by default.
+    #[arg(long)]
+    pub to_commit_id: Option<i64>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    setup_tracing("full", "replay_events".to_string()).unwrap();
+
+    let args = Args::parse();
+    let config =
+        server::conf::Conf::new(args.config_file.clone()).context("reading config file")?;
+    let orderbook_cn = config.tenant.prefixed(&args.orderbook_cn);
+
+    let pool = setup_database(&config, false)
+        .await
+        .context("setting up database")?;
+
+    let node_client = NodeApiHttpClient::new(config.node_url.clone())?;
+    let lane_id = node_client
+        .get_node_info()
+        .await?
+        .pubkey
+        .map(LaneId::new)
+        .context("node has no validator pubkey")?;
+    let secret = config.secret.clone();
+
+    let commits = fetch_commit_events(&pool, None, args.to_commit_id).await?;
+    let onchain_commitments =
+        fetch_onchain_commitments(&config.indexer_database_url, &orderbook_cn, None).await?;
+    info!(
+        "Replaying {} commits against {} settled commitments",
+        commits.len(),
+        onchain_commitments.len()
+    );
+
+    let mut state = ExecuteState::default();
+    // Commitment of `state`, only computed when compared to an onchain one
+    let mut commitment: Option<StateCommitment> = None;
+    let mut checked = 0;
+    let mut unsettled = 0;
+
+    for commit in &commits {
+        let onchain = commit
+            .tx_hash
+            .as_ref()
+            .and_then(|tx_hash| onchain_commitments.get(&(tx_hash.clone(), commit.blob_index)));
+
+        if let Some(onchain) = onchain {
+            let initial_state = match commitment.take() {
+                Some(commitment) => commitment,
+                None => commit_state(&state, &secret, &lane_id)?,
+            };
+            if initial_state.0 != onchain.initial_state {
+                // Events of earlier commits are missing, or were applied without settling
+                report_divergence(commit, onchain, &onchain.initial_state, &initial_state);
+                bail!(
+                    "State diverged before commit {}: initial state does not match",
+                    commit.commit_id
+                );
+            }
+        }
+
+        state
+            .apply_events(&commit.user_info, &commit.events)
+            .map_err(|e| anyhow!("Could not apply events of commit {}: {e}", commit.commit_id))?;
+        commitment = None;
+
+        let Some(onchain) = onchain else {
+            unsettled += 1;
+            continue;
+        };
+
+        let next_state = commit_state(&state, &secret, &lane_id)?;
+        if next_state.0 != onchain.next_state {
+            report_divergence(commit, onchain, &onchain.next_state, &next_state);
+            bail!("State diverged at commit {}", commit.commit_id);
+        }
+        commitment = Some(next_state);
+        checked += 1;
+    }
+
+    info!(
+        "✅ Replayed {} commits: {} match their onchain commitment, {} are not settled",
+        commits.len(),
+        checked,
+        unsettled
+    );
+
+    Ok(())
+}
+
+fn report_divergence(
+    commit: &CommitEvents,
+    onchain: &OnchainCommitment,
+    expected: &[u8],
+    rebuilt: &StateCommitment,
+) {
+    error!(
+        "❌ Divergence at commit {} (tx {}, block {})",
+        commit.commit_id,
+        commit.tx_hash.as_deref().unwrap_or("unknown"),
+        onchain.block_height
+    );
+    if !onchain.success {
+        warn!("The tx failed onchain, while its events were stored");
+    }
+
+    warn!("Events of user {}:", commit.user_info.user);
+    for event in &commit.events {
+        warn!("  {}", event);
+    }
+
+    log_state_diff(
+        "onchain vs rebuilt",
+        &StateCommitment(expected.to_vec()),
+        rebuilt,
+    );
+}
@@ -0,0 +1,350 @@
+//! Replays a single orderbook tx from its stored prover request, on the state rebuilt from the
+//! events of the previous commits. The events and commitments of the light execution, the full
+//! state and the guest execution are printed side by side, along with the onchain ones.
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use client_sdk::rest_client::{NodeApiClient, NodeApiHttpClient};
+use hyli_modules::utils::logger::setup_tracing;
+use orderbook::{
+    model::OrderbookEvent,
+    transaction::{OrderbookAction, PermissionedOrderbookAction, PermissionedPrivateInput},
+    zk::{FullState, ZkVmState},
+    ORDERBOOK_ACCOUNT_IDENTITY,
+};
+use sdk::{
+    guest, info, BlobIndex, BlobTransaction, BlockHeight, Calldata, ContractName, LaneId,
+    StateCommitment, TxContext,
+};
+use server::{
+    prover::OrderbookProverRequest,
+    replay::{fetch_onchain_commitments, log_state_diff, replay_light_state},
+    setup::setup_database,
+};
+use sqlx::{types::Json, Row};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    #[arg(long, default_value = "config.toml")]
+    pub config_file: Vec<String>,
+
+    #[arg(long, default_value = "orderbook")]
+    pub orderbook_cn: String,
+
+    /// Hash of the blob tx to replay
+    pub tx_hash: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    setup_tracing("full", "replay_tx".to_string()).unwrap();
+
+    let args = Args::parse();
+    let config =
+        server::conf::Conf::new(args.config_file.clone()).context("reading config file")?;
+    let orderbook_cn = ContractName(config.tenant.prefixed(&args.orderbook_cn));
+    let tx_hash = args.tx_hash.trim_start_matches("0x").to_string();
+
+    let pool = setup_database(&config, false)
+        .await
+        .context("setting up database")?;
+
+    let node_client = NodeApiHttpClient::new(config.node_url.clone())?;
+    let lane_id = node_client
+        .get_node_info()
+        .await?
+        .pubkey
+        .map(LaneId::new)
+        .context("node has no validator pubkey")?;
+    let secret = config.secret.clone();
+
+    // The action may have been sent along with others, in a blob tx of its own hash
+    let (commit_id, sent_tx_hash, blob_index): (i64, String, i32) = sqlx::query(
+        "
+        SELECT c.commit_id, COALESCE(o.sent_tx_hash, c.tx_hash) AS sent_tx_hash,
+            COALESCE(o.blob_index, 0) AS blob_index
+        FROM commits c
+        LEFT JOIN blob_tx_outbox o ON o.commit_id = c.commit_id
+        WHERE c.tx_hash = $1
+        ",
+    )
+    .bind(&tx_hash)
+    .fetch_optional(&pool)
+    .await?
+    .map(|row| {
+        (
+            row.get("commit_id"),
+            row.get("sent_tx_hash"),
+            row.get("blob_index"),
+        )
+    })
+    .with_context(|| format!("No commit found for tx {tx_hash}"))?;
+
+    // Settled requests are deleted by the prover, with the private input needed to replay them
+    let request: OrderbookProverRequest =
+        sqlx::query("SELECT request FROM prover_requests WHERE tx_hash = $1")
+            .bind(&tx_hash)
+            .fetch_optional(&pool)
+            .await?
+            .map(|row| serde_json::from_slice(&row.get::<Vec<u8>, _>("request")))
+            .transpose()?
+            .with_context(|| {
+                format!("No prover request for tx {tx_hash}: it was deleted once the tx settled")
+            })?;
+
+    let blob_tx = sqlx::query_scalar::<_, Json<BlobTransaction>>(
+        "SELECT blob_tx FROM blob_tx_outbox WHERE commit_id = $1",
+    )
+    .bind(commit_id)
+    .fetch_optional(&pool)
+    .await?
+    .map(|blob_tx| blob_tx.0);
+
+    println!("Tx {tx_hash} (commit {commit_id})");
+    if sent_tx_hash != tx_hash {
+        println!("  sent in blob tx {sent_tx_hash}, at blob index {blob_index}");
+    }
+    println!("  action: {:?}", request.orderbook_action);
+    println!("  user: {}", request.user_info.user);
+    check_blob(
+        blob_tx.as_ref(),
+        &orderbook_cn,
+        &request.orderbook_action,
+        request.nonce,
+    );
+
+    let mut onchain = fetch_onchain_commitments(
+        &config.indexer_database_url,
+        &orderbook_cn.0,
+        Some(&sent_tx_hash),
+    )
+    .await?
+    .remove(&(sent_tx_hash.clone(), blob_index));
+    let block_height = match &onchain {
+        Some(onchain) => BlockHeight(onchain.block_height as u64),
+        None => node_client.get_block_height().await?,
+    };
+
+    let light = replay_light_state(&pool, commit_id - 1).await?;
+
+    // Light execution, as done by the server when handling the action
+    let light_events = light.generate_permissioned_execution_events(
+        &request.user_info,
+        request.orderbook_action.clone(),
+        &request.action_private_input,
+    );
+
+    // Full state, as maintained by the prover from the stored events
+    let mut full = FullState::from_data(&light, secret.clone(), lane_id.clone(), block_height)
+        .map_err(|e| anyhow!("Could not build full state: {e}"))?;
+    let full_initial_state = full.commit();
+    let commitment_metadata = full
+        .derive_zkvm_commitment_metadata_from_events(
+            &request.user_info,
+            &request.events,
+            &request.orderbook_action,
+        )
+        .map_err(|e| anyhow!("Could not derive zkvm commitment metadata: {e}"))?;
+    full.apply_events_and_update_roots(&request.user_info, request.events.clone())
+        .map_err(|e| anyhow!("Could not apply stored events to full state: {e}"))?;
+    let full_next_state = full.commit();
+
+    // Guest execution, as proven
+    let calldata = Calldata {
+        identity: ORDERBOOK_ACCOUNT_IDENTITY.into(),
+        tx_hash: request.tx_hash.clone(),
+        blobs: vec![OrderbookAction::PermissionedOrderbookAction(
+            request.orderbook_action.clone(),
+            request.nonce,
+        )
+        .as_blob(orderbook_cn.clone())]
+        .into(),
+        tx_blob_count: 1,
+        index: BlobIndex(0),
+        private_input: borsh::to_vec(&PermissionedPrivateInput {
+            secret: secret.clone(),
+            user_info: request.user_info.clone(),
+            private_input: request.action_private_input.clone(),
+        })?,
+        tx_ctx: Some(TxContext {
+            lane_id: lane_id.clone(),
+            block_height,
+            ..Default::default()
+        }),
+    };
+    let outputs = guest::execute::<ZkVmState>(&commitment_metadata, &[calldata]);
+    let guest_output = outputs
+        .first()
+        .ok_or_else(|| anyhow!("Guest execution returned no output"))?;
+    let guest_events: Result<Vec<OrderbookEvent>, String> = if guest_output.success {
+        borsh::from_slice(&guest_output.program_outputs)
+            .map_err(|e| format!("Could not decode guest events: {e}"))
+    } else {
+        Err(String::from_utf8_lossy(&guest_output.program_outputs).into_owned())
+    };
+
+    println!();
+    println!("Events (stored | light | guest):");
+    let light_events = print_events(&request.events, light_events, guest_events);
+
+    println!();
+    println!("Commitments:");
+    let onchain_initial = onchain
+        .as_ref()
+        .map(|onchain| StateCommitment(onchain.initial_state.clone()));
+    let onchain_next = onchain
+        .as_mut()
+        .map(|onchain| StateCommitment(std::mem::take(&mut onchain.next_state)));
+    let initial_matches = print_commitments(
+        "initial",
+        &full_initial_state,
+        &guest_output.initial_state,
+        onchain_initial.as_ref(),
+    );
+    let next_matches = print_commitments(
+        "next",
+        &full_next_state,
+        &guest_output.next_state,
+        onchain_next.as_ref(),
+    );
+    if let Some(onchain) = &onchain {
+        println!(
+            "  settled at block {} ({})",
+            onchain.block_height,
+            if onchain.success {
+                "success"
+            } else {
+                "failure"
+            }
+        );
+    } else {
+        println!("  not settled yet");
+    }
+
+    if light_events && initial_matches && next_matches && guest_output.success {
+        info!("✅ Light, full and guest executions agree");
+        Ok(())
+    } else {
+        bail!("Executions of tx {tx_hash} diverge")
+    }
+}
+
+/// Checks that the blob sent onchain carries the action of the prover request
+fn check_blob(
+    blob_tx: Option<&BlobTransaction>,
+    orderbook_cn: &ContractName,
+    action: &PermissionedOrderbookAction,
+    nonce: u32,
+) {
+    let Some(blob_tx) = blob_tx else {
+        println!("  blob: not found in the outbox");
+        return;
+    };
+
+    let sent = blob_tx
+        .blobs
+        .iter()
+        .find(|blob| &blob.contract_name == orderbook_cn)
+        .and_then(|blob| borsh::from_slice::<OrderbookAction>(&blob.data.0).ok());
+    match sent {
+        Some(OrderbookAction::PermissionedOrderbookAction(sent_action, sent_nonce))
+            if &sent_action == action && sent_nonce == nonce =>
+        {
+            println!("  blob: ✅ matches the prover request");
+        }
+        sent => println!("  blob: ❌ carries {sent:?}"),
+    }
+}
+
+/// Prints the stored, light and guest events side by side. Guest events are compared to the
+/// public stored events only, as the others are filtered out of the contract outputs.
+/// Returns whether they all match.
+fn print_events(
+    stored: &[OrderbookEvent],
+    light: Result<Vec<OrderbookEvent>, String>,
+    guest: Result<Vec<OrderbookEvent>, String>,
+) -> bool {
+    let mut matches = true;
+
+    match &light {
+        Ok(light) => {
+            for index in 0..stored.len().max(light.len()) {
+                let stored_event = stored.get(index);
+                let light_event = light.get(index);
+                let same = stored_event == light_event;
+                matches &= same;
+                println!(
+                    "  {} [{index}] {} | {}",
+                    if same { "✅" } else { "❌" },
+                    display(stored_event),
+                    if same {
+                        "same".to_string()
+                    } else {
+                        display(light_event)
+                    }
+                );
+            }
+        }
+        Err(e) => {
+            matches = false;
+            for event in stored {
+                println!("     {event}");
+            }
+            println!("  ❌ light execution failed: {e}");
+        }
+    }
+
+    match &guest {
+        Ok(guest) => {
+            let public: Vec<&OrderbookEvent> =
+                stored.iter().filter(|event| !event.is_private()).collect();
+            let guest: Vec<&OrderbookEvent> = guest.iter().collect();
+            if public == guest {
+                println!("  ✅ guest outputs the {} public events", guest.len());
+            } else {
+                matches = false;
+                println!("  ❌ guest outputs differ from the public stored events:");
+                for event in guest {
+                    println!("     {event}");
+                }
+            }
+        }
+        Err(e) => {
+            matches = false;
+            println!("  ❌ guest execution failed: {e}");
+        }
+    }
+
+    matches
+}
+
+fn display(event: Option<&OrderbookEvent>) -> String {
+    event.map_or_else(|| "-".to_string(), |event| event.to_string())
+}
+
+/// Prints whether the full, guest and onchain commitments agree, logging their diffs otherwise
+fn print_commitments(
+    label: &str,
+    full: &StateCommitment,
+    guest: &StateCommitment,
+    onchain: Option<&StateCommitment>,
+) -> bool {
+    let mark = |same: bool| if same { "✅" } else { "❌" };
+    let guest_matches = full == guest;
+    let onchain_matches = onchain.map(|onchain| full == onchain);
+    println!(
+        "  {label}: full vs guest {}, full vs onchain {}",
+        mark(guest_matches),
+        onchain_matches.map_or("-", mark)
+    );
+
+    if !guest_matches {
+        log_state_diff(&format!("{label}: guest vs full"), guest, full);
+    }
+    if let (Some(onchain), Some(false)) = (onchain, onchain_matches) {
+        log_state_diff(&format!("{label}: onchain vs full"), onchain, full);
+    }
+
+    guest_matches && onchain_matches.unwrap_or(true)
+}
@@ -32,6 +32,10 @@ pub struct Args {
     #[arg(long, default_value = "tx_sender")]
     pub identity: String,
 
+    /// Admin secret of the server, required to create pairs
+    #[arg(long, default_value = "admin_secret")]
+    pub admin_secret: String,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -163,15 +167,19 @@ async fn main() -> Result<()> {
             contract_name2,
         } => {
             let request = CreatePairRequest {
+                secret: args.admin_secret,
                 base_contract: contract_name1,
                 quote_contract: contract_name2,
             };
 
-            tracing::info!("Sending create pair request: {:?}", request);
+            tracing::info!(
+                "Sending create pair request for {}/{}",
+                request.base_contract,
+                request.quote_contract
+            );
 
             let response = client
-                .post(format!("{}/create_pair", args.server_url))
-                .header("x-identity", args.identity)
+                .post(format!("{}/admin/create_pair", args.server_url))
                 .header("Content-Type", "application/json")
                 .json(&request)
                 .send()
@@ -499,10 +507,9 @@ async fn main() -> Result<()> {
 
             // Create pair
             let response = client
-                .post(format!("{}/create_pair", args.server_url))
-                .header("x-identity", args.identity.clone())
+                .post(format!("{}/admin/create_pair", args.server_url))
                 .header("Content-Type", "application/json")
-                .json(&serde_json::json!({ "base_contract": asset_1.contract_name.clone(), "quote_contract": asset_2.contract_name.clone() }))
+                .json(&serde_json::json!({ "secret": args.admin_secret.clone(), "base_contract": asset_1.contract_name.clone(), "quote_contract": asset_2.contract_name.clone() }))
                 .send()
                 .await
                 .context("Failed to send request to server")?;
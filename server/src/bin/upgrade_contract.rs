@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     env, fs,
     io::{self, Write},
     path::{Path, PathBuf},
@@ -26,6 +27,9 @@ struct Args {
     /// Directory containing the ELF and VK files
     #[arg(long = "elf-dir", value_name = "DIR", default_value = "elf")]
     elf_dir: PathBuf,
+    /// Toolchain the ELF was built with: "sp1", or "risc0" for the `*_risc0` artifacts
+    #[arg(long, default_value = "sp1")]
+    toolchain: String,
 }
 
 #[derive(Serialize)]
@@ -61,7 +65,7 @@ async fn main() -> Result<()> {
     let client =
         NodeApiHttpClient::new(node_url.clone()).context("Failed to create NodeApiHttpClient")?;
 
-    let (elf_bytes, vk_bytes) = read_contract_artifacts(&args.elf_dir)?;
+    let (elf_bytes, vk_bytes) = read_contract_artifacts(&args.elf_dir, &args.toolchain)?;
 
     // Create the program ID from VK
     let program_id = ProgramId::from(vk_bytes.as_slice());
@@ -146,7 +150,7 @@ async fn main() -> Result<()> {
         &elf_bytes,
         &hex::encode(&program_id.0),
         &contract_name.to_string(),
-        "sp1",
+        &args.toolchain,
         None,
     )
     .await
@@ -162,6 +166,7 @@ async fn main() -> Result<()> {
         nonce: action_id,
         action_private_input: vec![1, 2, 3],
         tx_hash: tx_hash.clone(),
+        trace_context: HashMap::new(),
     };
 
     let endpoint = format!(
@@ -197,7 +202,9 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-fn read_contract_artifacts(dir: &Path) -> Result<(Vec<u8>, Vec<u8>)> {
+/// Reads the ELF and VK built with `toolchain`. Risc0 artifacts have a `_risc0` infix,
+/// so that they can live next to the SP1 ones.
+fn read_contract_artifacts(dir: &Path, toolchain: &str) -> Result<(Vec<u8>, Vec<u8>)> {
     let entries = fs::read_dir(dir).with_context(|| {
         format!(
             "Failed to read contract artifacts directory {}",
@@ -209,14 +216,19 @@ fn read_contract_artifacts(dir: &Path) -> Result<(Vec<u8>, Vec<u8>)> {
     for entry in entries {
         let entry = entry.context("Failed to read directory entry")?;
         let path = entry.path();
-        if path.is_file() {
+        let is_risc0 = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.contains("_risc0"));
+        if path.is_file() && is_risc0 == (toolchain == "risc0") {
             files.push(path);
         }
     }
 
     if files.len() != 2 {
         bail!(
-            "Expected exactly 2 files in {}, found {}",
+            "Expected exactly 2 {} files in {}, found {}",
+            toolchain,
             dir.display(),
             files.len()
         );
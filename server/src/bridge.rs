@@ -1,4 +1,10 @@
-use std::{str::FromStr, sync::Arc};
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use alloy::primitives::{Address, Signature, U256};
 use axum::{
@@ -15,44 +21,52 @@ use hyli_modules::{
     log_error, module_bus_client, module_handle_messages,
     modules::{BuildApiContextInner, Module},
 };
-use hyli_smt_token::SmtTokenAction;
 use orderbook::{
     transaction::{OrderbookAction, PermissionedOrderbookAction},
     ORDERBOOK_ACCOUNT_IDENTITY,
 };
 use reqwest::Method;
-use sdk::{
-    BlobTransaction, ContractName, NodeStateEvent, StatefulEvent, StructuredBlob,
-    UnsettledBlobTransaction,
-};
+use sdk::{BlobTransaction, ContractName, NodeStateEvent, StatefulEvent, UnsettledBlobTransaction};
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use tokio::sync::RwLock;
 use tower_http::cors::{Any, CorsLayer};
-use tracing::{error, info, warn};
+use tracing::{error, info, warn, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 use crate::{
     app::{OrderbookRequest, PendingDeposit, PendingWithdraw},
-    bridge::eth::{EthClient, EthListener, EthSendResult},
+    audit::{fingerprint, AuditEntry, AuditLog},
+    bridge::{
+        eth::{EthClient, EthListener, EthSendResult},
+        limits::{BridgeDirection, BridgeLimits},
+    },
     conf::BridgeConfig,
-    services::{asset_service::AssetService, bridge_service::BridgeService},
+    services::{
+        asset_service::AssetService,
+        bridge_service::{BridgeService, EthTransaction},
+    },
 };
 
 pub mod eth;
+pub mod limits;
 pub mod utils;
 
 pub struct BridgeModule {
     bus: BridgeModuleBusClient,
     eth_ws_url: String,
-    collateral_token_cn: ContractName, // Collateral token contract name on Hyli side
-    eth_contract_address: Address,     // Collateral token address name on Ethereum side
     eth_contract_vault_address: Address,
-    eth_client: Arc<EthClient>,
+    tokens: Arc<BridgedTokens>,
     bridge_service: Arc<RwLock<BridgeService>>,
     asset_service: Arc<RwLock<AssetService>>,
     orderbook_cn: ContractName,
+    bridge_pause: Arc<BridgePause>,
+    bridge_limits: Arc<BridgeLimits>,
+    /// Settled withdrawals to Ethereum waiting for the bridge to be resumed or for their limit
+    /// to free up
+    held_withdraws: Vec<PendingWithdraw>,
 }
 
 pub struct BridgeModuleCtx {
@@ -63,13 +77,62 @@ pub struct BridgeModuleCtx {
     pub bridge_service: Arc<RwLock<BridgeService>>,
     pub asset_service: Arc<RwLock<AssetService>>,
     pub orderbook_cn: ContractName,
+    pub bridge_pause: Arc<BridgePause>,
+    pub bridge_limits: Arc<BridgeLimits>,
+    pub audit_log: Arc<AuditLog>,
+}
+
+/// Pause of the deposits and withdrawals of the bridge: set on the orderbook contract by the
+/// operator, or forced by the config whatever the contract
+pub struct BridgePause {
+    forced: bool,
+    paused: AtomicBool,
 }
 
+impl BridgePause {
+    pub fn new(forced: bool, paused: bool) -> Self {
+        BridgePause {
+            forced,
+            paused: AtomicBool::new(paused),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.forced || self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn is_forced(&self) -> bool {
+        self.forced
+    }
+
+    /// Mirrors the pause of the orderbook contract
+    pub fn set(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+}
+
+/// ERC20 token held by the vault, with the Hyli token contract it is credited to
+struct BridgedToken {
+    contract_name: ContractName,
+    eth_contract_address: Address,
+    eth_client: Arc<EthClient>,
+    /// Symbol and ERC20 decimals of the listed tokens, whose amounts are converted to the scale
+    /// of the asset. Amounts of the collateral token are bridged as they are.
+    decimals: Option<(String, u32)>,
+}
+
+/// Collateral token first, then the tokens listed in the bridge config
+struct BridgedTokens(Vec<BridgedToken>);
+
 #[derive(Clone)]
 struct BridgeRouterCtx {
     bridge_service: Arc<RwLock<BridgeService>>,
+    asset_service: Arc<RwLock<AssetService>>,
     bus: RouterBusClient,
-    collateral_token_cn: ContractName,
+    tokens: Arc<BridgedTokens>,
+    bridge_pause: Arc<BridgePause>,
+    bridge_limits: Arc<BridgeLimits>,
+    audit_log: Arc<AuditLog>,
 }
 
 module_bus_client! {
@@ -95,15 +158,19 @@ impl Module for BridgeModule {
         let router_bus = RouterBusClient::new_from_bus(bus.new_handle()).await;
         let bus = BridgeModuleBusClient::new_from_bus(bus.new_handle()).await;
 
-        let eth_contract_address = Address::from_str(&ctx.bridge_config.eth_contract_address)
-            .context("parsing Ethereum contract address")?;
         let vault_address = Address::from_str(&ctx.bridge_config.eth_contract_vault_address)
             .context("parsing Ethereum vault address")?;
+        let tokens =
+            Arc::new(BridgedTokens::new(&ctx.bridge_config, &ctx.collateral_token_cn).await?);
 
         let claim_state = BridgeRouterCtx {
             bridge_service: ctx.bridge_service.clone(),
+            asset_service: ctx.asset_service.clone(),
             bus: router_bus,
-            collateral_token_cn: ctx.collateral_token_cn.clone(),
+            tokens: tokens.clone(),
+            bridge_pause: ctx.bridge_pause.clone(),
+            bridge_limits: ctx.bridge_limits.clone(),
+            audit_log: ctx.audit_log.clone(),
         };
 
         let cors = CorsLayer::new()
@@ -123,48 +190,50 @@ impl Module for BridgeModule {
             }
         }
 
-        let eth_client = Arc::new(
-            EthClient::new(
-                &ctx.bridge_config.eth_rpc_http_url,
-                &ctx.bridge_config.eth_signer_private_key,
-                eth_contract_address,
-            )
-            .await
-            .context("initializing Ethereum client")?,
-        );
-
         Ok(BridgeModule {
             bus,
-            collateral_token_cn: ctx.collateral_token_cn.clone(),
             eth_ws_url: ctx.bridge_config.eth_rpc_ws_url.clone(),
-            eth_contract_address,
             eth_contract_vault_address: vault_address,
-            eth_client,
+            tokens,
             asset_service: ctx.asset_service.clone(),
             bridge_service: ctx.bridge_service.clone(),
             orderbook_cn: ctx.orderbook_cn.clone(),
+            bridge_pause: ctx.bridge_pause.clone(),
+            bridge_limits: ctx.bridge_limits.clone(),
+            held_withdraws: Vec::new(),
         })
     }
 
     async fn run(&mut self) -> Result<()> {
-        let eth_listener =
-            EthListener::connect(&self.eth_ws_url, self.eth_contract_address).await?;
+        let mut eth_listeners = Vec::with_capacity(self.tokens.0.len());
+        for token in self.tokens.0.iter() {
+            eth_listeners
+                .push(EthListener::connect(&self.eth_ws_url, token.eth_contract_address).await?);
+        }
 
-        _ = log_error!(self.catch_up_eth(&eth_listener).await, "Catching up on Eth");
+        _ = log_error!(
+            self.catch_up_eth(&eth_listeners).await,
+            "Catching up on Eth"
+        );
 
         info!("Connected to Ethereum node, listening for events...");
 
         let vault_address = self.eth_contract_vault_address;
 
-        info!(
-            "Listening for Transfer events to vault: {:?} on contract {:?}",
-            self.eth_contract_vault_address, self.eth_contract_address
-        );
-
-        let mut to_vault_stream = eth_listener.stream_transfers_to(vault_address).await?;
+        let mut to_vault_streams = Vec::with_capacity(eth_listeners.len());
+        for (token, eth_listener) in self.tokens.0.iter().zip(eth_listeners.iter()) {
+            info!(
+                "Listening for Transfer events to vault: {:?} on contract {:?} ({})",
+                vault_address, token.eth_contract_address, token.contract_name
+            );
+            to_vault_streams.push(Box::pin(
+                eth_listener.stream_transfers_to(vault_address).await?,
+            ));
+        }
+        let mut to_vault_stream = futures::stream::select_all(to_vault_streams);
 
         // There are actually three distinct flows:
-        // - Flow 1: USDC token (on Eth) -> Orderbook (on Hyli): this only happens on one contract (say USDC).
+        // - Flow 1: bridged token (on Eth) -> Orderbook (on Hyli): this happens on each bridged token contract.
         //   1. User sends token on eth to vault address
         //   2. We detect the transfer event, and create a corresponding tx on Hyli
 
@@ -172,7 +241,7 @@ impl Module for BridgeModule {
         //   1. User sends a withdraw action to the orderbook contract on Hyli, specifiying a Hyli identity
         //   2. We detect the settled tx event, and send a corresponding transfer on Hyli token contract
 
-        // - Flow 3: USDC from Orderbook (on Hyli) -> USDC token (on Eth): this only happens on one contract (say USDC).
+        // - Flow 3: bridged token from Orderbook (on Hyli) -> same token (on Eth): this happens on each bridged token contract.
         //   1. User sends a withdraw action to the orderbook contract on Hyli, specifiying an Eth address
         //   2. We detect the settled tx event, and send a corresponding transfer on Eth
 
@@ -213,29 +282,70 @@ impl BridgeModule {
                 }
             }
         }
+
+        // Deposits and withdrawals held by a pause or by the limits are retried on each block
+        if !self.bridge_pause.is_paused() {
+            self.release_held().await?;
+        }
         Ok(())
     }
 
-    async fn handle_settled_tx(&mut self, tx: &UnsettledBlobTransaction) -> Result<()> {
-        let transfers = self.extract_relevant_transfers(&tx.tx).await;
-        let withdraws = self.extract_relevant_withdraws(&tx.tx).await;
+    /// Credits the deposits of the claimed addresses and pays out the withdrawals held while the
+    /// bridge was paused or over its limits. Deposits of unclaimed addresses are credited on
+    /// claim.
+    async fn release_held(&mut self) -> Result<()> {
+        let held_deposits = self
+            .bridge_service
+            .read()
+            .await
+            .claimed_pending_eth_transactions()
+            .await?;
+        for (hyli_identity, eth_tx) in held_deposits {
+            _ = log_error!(
+                self.credit_eth_deposit(hyli_identity, eth_tx).await,
+                "crediting held Ethereum deposit"
+            );
+        }
+        for withdraw in std::mem::take(&mut self.held_withdraws) {
+            if self.eth_withdraw_hold(&withdraw).is_some() {
+                self.held_withdraws.push(withdraw);
+                continue;
+            }
+            _ = log_error!(
+                self.handle_eth_withdraw(&withdraw).await,
+                "processing held Ethereum withdraw"
+            );
+        }
+        Ok(())
+    }
 
+    /// Why a withdrawal to Ethereum is held rather than paid out now. A withdrawal that is not
+    /// held is accounted in its limit.
+    fn eth_withdraw_hold(&self, withdraw: &PendingWithdraw) -> Option<String> {
+        if self.bridge_pause.is_paused() {
+            return Some("bridge is paused".to_string());
+        }
+        self.bridge_limits
+            .record(
+                &withdraw.contract_name.0,
+                BridgeDirection::Withdraw,
+                withdraw.amount as u128,
+            )
+            .err()
+    }
+
+    async fn handle_settled_tx(&mut self, tx: &UnsettledBlobTransaction) -> Result<()> {
         let tx_hash = tx.tx_id.1.clone();
+        let span = tracing::info_span!("bridge_settled_tx", tx_hash = %tx_hash);
+
+        // Deposits from transfers to the orderbook are credited by the deposit watcher
+        let withdraws = self
+            .extract_relevant_withdraws(&tx.tx, span.context())
+            .await;
+
         // TODO: do not re-process already processed txs
         // state.add_hyli_pending_transaction(tx_hash);
 
-        // Handle deposits (transfers to orderbook)
-        for transfer in transfers {
-            sdk::info!(
-                tx_hash = ?tx_hash.0,
-                token = %transfer.contract_name,
-                sender = ?transfer.sender.0,
-                amount = transfer.amount,
-                "Settled deposit transfer detected",
-            );
-            self.bus.send(OrderbookRequest::PendingDeposit(transfer))?;
-        }
-
         // Handle withdraws (orderbook withdraw actions)
         for withdraw in withdraws {
             sdk::info!(
@@ -251,6 +361,19 @@ impl BridgeModule {
             if withdraw.destination.network == "ethereum-mainnet"
                 || withdraw.destination.network == "ethereum-sepolia"
             {
+                if let Some(reason) = self.eth_withdraw_hold(&withdraw) {
+                    warn!(
+                        address = %withdraw.destination.address,
+                        amount = withdraw.amount,
+                        "Holding the withdraw: {reason}"
+                    );
+                    if !self.bridge_pause.is_paused() {
+                        self.bridge_limits
+                            .rejected(&withdraw.contract_name.0, BridgeDirection::Withdraw);
+                    }
+                    self.held_withdraws.push(withdraw);
+                    continue;
+                }
                 // TODO: use outputed tx_hash to track the withdraw on Eth side
                 // TODO: if the withdraw fails (e.g. insufficient balance), we need to handle it properly in order to redo it
                 let _eth_send_result = log_error!(
@@ -265,35 +388,11 @@ impl BridgeModule {
         Ok(())
     }
 
-    async fn extract_relevant_transfers(&self, tx: &BlobTransaction) -> Vec<PendingDeposit> {
-        let mut transfers = Vec::new();
-        for blob in tx.blobs.iter() {
-            let Ok(structured) = StructuredBlob::<SmtTokenAction>::try_from(blob.clone()) else {
-                continue;
-            };
-
-            if let SmtTokenAction::Transfer {
-                sender,
-                recipient,
-                amount,
-            } = structured.data.parameters
-            {
-                if recipient.0 != ORDERBOOK_ACCOUNT_IDENTITY {
-                    continue;
-                }
-
-                transfers.push(PendingDeposit {
-                    sender,
-                    contract_name: blob.contract_name.clone(),
-                    amount,
-                });
-            }
-        }
-
-        transfers
-    }
-
-    async fn extract_relevant_withdraws(&self, tx: &BlobTransaction) -> Vec<PendingWithdraw> {
+    async fn extract_relevant_withdraws(
+        &self,
+        tx: &BlobTransaction,
+        context: opentelemetry::Context,
+    ) -> Vec<PendingWithdraw> {
         let asset_service = self.asset_service.read().await;
 
         let mut withdraws = Vec::new();
@@ -307,26 +406,33 @@ impl BridgeModule {
                 continue;
             };
 
-            if let OrderbookAction::PermissionedOrderbookAction(
-                PermissionedOrderbookAction::Withdraw {
-                    symbol,
-                    amount,
-                    destination,
-                },
-                _,
-            ) = action
-            {
-                let Some(contract_name) =
-                    asset_service.get_contract_name_from_symbol(&symbol).await
-                else {
-                    continue;
-                };
-                withdraws.push(PendingWithdraw {
-                    destination,
-                    contract_name,
-                    amount,
-                });
-            }
+            // Queued withdrawals are paid out once released, not when requested
+            let (symbol, amount, destination) = match action {
+                OrderbookAction::PermissionedOrderbookAction(
+                    PermissionedOrderbookAction::Withdraw {
+                        symbol,
+                        amount,
+                        destination,
+                        ..
+                    },
+                    _,
+                ) => (symbol, amount, destination),
+                OrderbookAction::PermissionedOrderbookAction(
+                    PermissionedOrderbookAction::ReleaseWithdrawal { withdrawal, .. },
+                    _,
+                ) => (withdrawal.symbol, withdrawal.amount, withdrawal.destination),
+                _ => continue,
+            };
+            let Some(contract_name) = asset_service.get_contract_name_from_symbol(&symbol).await
+            else {
+                continue;
+            };
+            withdraws.push(PendingWithdraw {
+                destination,
+                contract_name,
+                amount,
+                context: context.clone(),
+            });
         }
 
         withdraws
@@ -337,9 +443,16 @@ impl BridgeModule {
             format!("parsing Ethereum address {}", withdraw.destination.address)
         })?;
 
-        let amount = U256::from(withdraw.amount);
+        let token = self
+            .tokens
+            .by_contract_name(&withdraw.contract_name)
+            .with_context(|| format!("{} is not bridged to Ethereum", withdraw.contract_name))?;
+        let amount = token
+            .to_eth_amount(withdraw.amount, &self.asset_service)
+            .await?;
 
-        self.eth_client
+        token
+            .eth_client
             .get_token_balance(self.eth_contract_vault_address)
             .await
             .and_then(|balance| {
@@ -352,7 +465,7 @@ impl BridgeModule {
                 }
             })?;
 
-        let result = self
+        let result = token
             .eth_client
             .transfer(to, amount)
             .await
@@ -376,10 +489,16 @@ impl BridgeModule {
             return Ok(());
         }
 
+        let Some(token) = self.tokens.by_eth_address(&eth_tx.token) else {
+            warn!(tx = ?eth_tx.tx_hash, token = ?eth_tx.token, "Skipping transfer of a token that is not bridged");
+            return Ok(());
+        };
+
         info!(
-            "🔵👀 ETH to vault detected: sender {} amount {} wei",
+            "🔵👀 ETH to vault detected: sender {} amount {} wei of {}",
             format!("{:?}", eth_tx.from).get(0..6).unwrap_or(""),
-            eth_tx.amount
+            eth_tx.amount,
+            token.contract_name
         );
 
         let bridge_service = self.bridge_service.read().await;
@@ -404,20 +523,69 @@ impl BridgeModule {
             return Ok(());
         };
 
-        let hyli_amount = u128::try_from(eth_tx.amount).expect("Amount too large");
+        if self.bridge_pause.is_paused() {
+            info!(tx = ?eth_tx.tx_hash, "Bridge is paused, holding the deposit until it is resumed");
+            bridge_service.add_eth_pending_transaction(eth_tx).await?;
+            return Ok(());
+        }
+        drop(bridge_service);
+
+        self.credit_eth_deposit(hyli_identity, eth_tx).await
+    }
+
+    async fn credit_eth_deposit(
+        &mut self,
+        hyli_identity: String,
+        eth_tx: EthTransaction,
+    ) -> Result<()> {
+        let token = self
+            .tokens
+            .by_eth_address(&eth_tx.token)
+            .with_context(|| format!("{:?} is not a bridged token", eth_tx.token))?;
+        let hyli_amount = token
+            .to_hyli_amount(eth_tx.amount, &self.asset_service)
+            .await?;
+        if let Err(e) = self.bridge_limits.record(
+            &token.contract_name.0,
+            BridgeDirection::Deposit,
+            hyli_amount,
+        ) {
+            // Retried on each block until the window frees up
+            let newly_held = self
+                .bridge_service
+                .read()
+                .await
+                .add_eth_pending_transaction(eth_tx.clone())
+                .await?;
+            if newly_held {
+                warn!(tx = ?eth_tx.tx_hash, "Holding the deposit: {e}");
+                self.bridge_limits
+                    .rejected(&token.contract_name.0, BridgeDirection::Deposit);
+            }
+            return Ok(());
+        }
 
+        let span = tracing::info_span!("bridge_deposit", eth_tx = ?eth_tx.tx_hash);
         let deposit = PendingDeposit {
             sender: hyli_identity.into(),
-            contract_name: self.collateral_token_cn.clone(),
+            contract_name: token.contract_name.clone(),
             amount: hyli_amount,
+            context: span.context(),
         };
         self.bus.send(OrderbookRequest::PendingDeposit(deposit))?;
         // TODO: instead of marking as processed right away, wait for confirmation from orderbook settled txs
-        bridge_service.mark_eth_processed(eth_tx.tx_hash).await?;
+        self.bridge_service
+            .read()
+            .await
+            .mark_eth_processed(eth_tx.tx_hash)
+            .await?;
         Ok(())
     }
 
-    async fn catch_up_eth(&mut self, listener: &EthListener) -> Result<()> {
+    async fn catch_up_eth(&mut self, listeners: &[EthListener]) -> Result<()> {
+        let Some(listener) = listeners.first() else {
+            return Ok(());
+        };
         let (from_block, latest, vault) = {
             let bridge_service = self.bridge_service.read().await;
             let from_block = bridge_service.eth_last_block().await?.saturating_add(1);
@@ -442,11 +610,13 @@ impl BridgeModule {
                 "Catching up on ETH events"
             );
 
-            for log in listener
-                .fetch_transfers_to_range(vault, chunk_start, chunk_end)
-                .await?
-            {
-                self.handle_eth_to_vault_log(log).await?;
+            for listener in listeners {
+                for log in listener
+                    .fetch_transfers_to_range(vault, chunk_start, chunk_end)
+                    .await?
+                {
+                    self.handle_eth_to_vault_log(log).await?;
+                }
             }
         }
 
@@ -455,6 +625,128 @@ impl BridgeModule {
     }
 }
 
+impl BridgedTokens {
+    async fn new(config: &BridgeConfig, collateral_token_cn: &ContractName) -> Result<Self> {
+        let mut tokens = Vec::with_capacity(config.tokens.len() + 1);
+        tokens.push(
+            BridgedToken::new(
+                config,
+                collateral_token_cn.clone(),
+                &config.eth_contract_address,
+                None,
+            )
+            .await?,
+        );
+        for token in config.tokens.iter() {
+            tokens.push(
+                BridgedToken::new(
+                    config,
+                    token.contract_name.clone().into(),
+                    &token.eth_contract_address,
+                    Some((token.symbol.clone(), token.decimals)),
+                )
+                .await?,
+            );
+        }
+        Ok(BridgedTokens(tokens))
+    }
+
+    fn by_eth_address(&self, address: &Address) -> Option<&BridgedToken> {
+        self.0
+            .iter()
+            .find(|token| &token.eth_contract_address == address)
+    }
+
+    fn by_contract_name(&self, contract_name: &ContractName) -> Option<&BridgedToken> {
+        self.0
+            .iter()
+            .find(|token| &token.contract_name == contract_name)
+    }
+}
+
+impl BridgedToken {
+    async fn new(
+        config: &BridgeConfig,
+        contract_name: ContractName,
+        eth_contract_address: &str,
+        decimals: Option<(String, u32)>,
+    ) -> Result<Self> {
+        let eth_contract_address = Address::from_str(eth_contract_address)
+            .with_context(|| format!("parsing Ethereum contract address of {contract_name}"))?;
+        let eth_client = Arc::new(
+            EthClient::new(
+                &config.eth_rpc_http_url,
+                &config.eth_signer_private_key,
+                eth_contract_address,
+            )
+            .await
+            .with_context(|| format!("initializing Ethereum client of {contract_name}"))?,
+        );
+        Ok(BridgedToken {
+            contract_name,
+            eth_contract_address,
+            eth_client,
+            decimals,
+        })
+    }
+
+    /// Scale of the asset and ERC20 decimals of the token, None when amounts are not converted
+    async fn scales(&self, asset_service: &RwLock<AssetService>) -> Result<Option<(u32, u32)>> {
+        let Some((symbol, decimals)) = &self.decimals else {
+            return Ok(None);
+        };
+        let asset_service = asset_service.read().await;
+        let asset = asset_service
+            .get_asset(symbol)
+            .with_context(|| format!("unknown asset {symbol} for {}", self.contract_name))?;
+        let scale = u32::try_from(asset.scale)
+            .with_context(|| format!("negative scale of asset {symbol}"))?;
+        Ok(Some((scale, *decimals)))
+    }
+
+    /// Amount credited on Hyli for an ERC20 amount, truncated to the scale of the asset
+    async fn to_hyli_amount(
+        &self,
+        amount: U256,
+        asset_service: &RwLock<AssetService>,
+    ) -> Result<u128> {
+        let hyli_amount = match self.scales(asset_service).await? {
+            Some((scale, decimals)) if decimals > scale => {
+                let unit = U256::from(10).pow(U256::from(decimals - scale));
+                if amount % unit != U256::ZERO {
+                    warn!(
+                        token = %self.contract_name,
+                        "Truncating {amount} to the scale of the asset, {} is not credited",
+                        amount % unit
+                    );
+                }
+                amount / unit
+            }
+            Some((scale, decimals)) => amount
+                .checked_mul(U256::from(10).pow(U256::from(scale - decimals)))
+                .context("amount too large")?,
+            None => amount,
+        };
+        u128::try_from(hyli_amount).context("amount too large to fit into u128")
+    }
+
+    /// ERC20 amount sent for an amount withdrawn on Hyli
+    async fn to_eth_amount(
+        &self,
+        amount: u128,
+        asset_service: &RwLock<AssetService>,
+    ) -> Result<U256> {
+        let amount = U256::from(amount);
+        Ok(match self.scales(asset_service).await? {
+            Some((scale, decimals)) if decimals > scale => amount
+                .checked_mul(U256::from(10).pow(U256::from(decimals - scale)))
+                .context("amount too large")?,
+            Some((scale, decimals)) => amount / U256::from(10).pow(U256::from(scale - decimals)),
+            None => amount,
+        })
+    }
+}
+
 // --------------------------------------------------------
 //     Routes
 // --------------------------------------------------------
@@ -563,19 +855,56 @@ async fn claim(
         .record_eth_identity_binding(eth_address, request.user_identity.clone())
         .await
         .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+    claim_state
+        .audit_log
+        .record(AuditEntry {
+            action: "bridge_claim".to_string(),
+            actor: request.user_identity.clone(),
+            fingerprint: fingerprint(&[
+                request.user_identity.as_bytes(),
+                eth_address.as_slice(),
+                &signature_bytes,
+            ]),
+            status: None,
+            details: serde_json::json!({
+                "chain": request.chain,
+                "eth_address": format!("{eth_address:#x}"),
+            }),
+        })
+        .await;
+
+    // Pending deposits of the address are credited by the bridge module once it is resumed
+    if claim_state.bridge_pause.is_paused() {
+        return Ok(Json("ok"));
+    }
 
     for eth_tx in pending_eth_txs {
-        let hyli_amount = u128::try_from(eth_tx.amount).map_err(|_| {
-            AppError(
-                StatusCode::INTERNAL_SERVER_ERROR,
-                anyhow::anyhow!("amount too large to fit into u128"),
-            )
-        })?;
+        let Some(token) = claim_state.tokens.by_eth_address(&eth_tx.token) else {
+            warn!(tx = ?eth_tx.tx_hash, token = ?eth_tx.token, "Skipping pending deposit of a token that is no longer bridged");
+            continue;
+        };
+        let hyli_amount = token
+            .to_hyli_amount(eth_tx.amount, &claim_state.asset_service)
+            .await
+            .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, err))?;
+        // Deposits over the limit stay pending and are credited by the bridge module later on
+        if let Err(e) = claim_state.bridge_limits.record(
+            &token.contract_name.0,
+            BridgeDirection::Deposit,
+            hyli_amount,
+        ) {
+            warn!(tx = ?eth_tx.tx_hash, "Holding the deposit: {e}");
+            claim_state
+                .bridge_limits
+                .rejected(&token.contract_name.0, BridgeDirection::Deposit);
+            continue;
+        }
 
         let deposit = PendingDeposit {
             sender: request.user_identity.clone().into(),
-            contract_name: claim_state.collateral_token_cn.clone(),
+            contract_name: token.contract_name.clone(),
             amount: hyli_amount,
+            context: Span::current().context(),
         };
 
         sdk::info!(
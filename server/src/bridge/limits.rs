@@ -0,0 +1,176 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use opentelemetry::{metrics::Counter, KeyValue};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::conf::BridgeLimitConfig;
+
+/// Way an amount goes through the bridge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeDirection {
+    Deposit,
+    Withdraw,
+}
+
+impl BridgeDirection {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BridgeDirection::Deposit => "deposit",
+            BridgeDirection::Withdraw => "withdraw",
+        }
+    }
+}
+
+/// Amount bridged of a token over its rolling window, against its limit
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BridgeLimitUsage {
+    pub contract_name: String,
+    pub direction: BridgeDirection,
+    pub window_secs: u64,
+    pub max: u64,
+    pub used: u128,
+}
+
+/// Rolling window limits of the amounts bridged of each token, per direction. Amounts are in
+/// the unit of the Hyli token contract.
+pub struct BridgeLimits {
+    limits: HashMap<String, BridgeLimitConfig>,
+    windows: std::sync::Mutex<HashMap<(String, BridgeDirection), VecDeque<(Instant, u128)>>>,
+    rejected: Counter<u64>,
+}
+
+impl BridgeLimits {
+    pub fn new(limits: &[BridgeLimitConfig]) -> Self {
+        let meter = opentelemetry::global::meter("app");
+        BridgeLimits {
+            limits: limits
+                .iter()
+                .map(|limit| (limit.contract_name.clone(), limit.clone()))
+                .collect(),
+            windows: Default::default(),
+            rejected: meter
+                .u64_counter("bridge.limited")
+                .with_description(
+                    "Bridged amounts held or refused by the limits, by token and direction",
+                )
+                .build(),
+        }
+    }
+
+    /// Checks that `amount` can be bridged now, without accounting it
+    pub fn check(
+        &self,
+        contract_name: &str,
+        direction: BridgeDirection,
+        amount: u128,
+    ) -> Result<(), String> {
+        self.account(contract_name, direction, amount, false)
+    }
+
+    /// Accounts `amount` as bridged now, or refuses it when it would exceed the limit
+    pub fn record(
+        &self,
+        contract_name: &str,
+        direction: BridgeDirection,
+        amount: u128,
+    ) -> Result<(), String> {
+        self.account(contract_name, direction, amount, true)
+    }
+
+    /// Counts an amount held or refused by the limits. Held amounts are counted once, not on
+    /// each retry.
+    pub fn rejected(&self, contract_name: &str, direction: BridgeDirection) {
+        self.rejected.add(
+            1,
+            &[
+                KeyValue::new("contract_name", contract_name.to_string()),
+                KeyValue::new("direction", direction.as_str()),
+            ],
+        );
+    }
+
+    /// Usage of every configured limit
+    pub fn usage(&self) -> Vec<BridgeLimitUsage> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().expect("bridge limit windows poisoned");
+        let mut usage = Vec::new();
+        for (contract_name, limit) in self.limits.iter() {
+            for (direction, max) in [
+                (BridgeDirection::Deposit, limit.max_deposit),
+                (BridgeDirection::Withdraw, limit.max_withdraw),
+            ] {
+                let Some(max) = max else {
+                    continue;
+                };
+                let window = windows
+                    .entry((contract_name.clone(), direction))
+                    .or_default();
+                usage.push(BridgeLimitUsage {
+                    contract_name: contract_name.clone(),
+                    direction,
+                    window_secs: limit.window_secs,
+                    max,
+                    used: Self::used(window, now, limit.window_secs),
+                });
+            }
+        }
+        usage.sort_by(|a, b| {
+            (&a.contract_name, a.direction.as_str()).cmp(&(&b.contract_name, b.direction.as_str()))
+        });
+        usage
+    }
+
+    fn account(
+        &self,
+        contract_name: &str,
+        direction: BridgeDirection,
+        amount: u128,
+        record: bool,
+    ) -> Result<(), String> {
+        let Some(limit) = self.limits.get(contract_name) else {
+            return Ok(());
+        };
+        let max = match direction {
+            BridgeDirection::Deposit => limit.max_deposit,
+            BridgeDirection::Withdraw => limit.max_withdraw,
+        };
+        let Some(max) = max else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        let mut windows = self.windows.lock().expect("bridge limit windows poisoned");
+        let window = windows
+            .entry((contract_name.to_string(), direction))
+            .or_default();
+        let used = Self::used(window, now, limit.window_secs);
+        if used.saturating_add(amount) > max as u128 {
+            return Err(format!(
+                "{} limit of {contract_name} reached: {used} of {max} used over the last {}s, {amount} more requested",
+                direction.as_str(),
+                limit.window_secs
+            ));
+        }
+        if record {
+            window.push_back((now, amount));
+        }
+        Ok(())
+    }
+
+    /// Amount of the window bridged over the last `window_secs`, dropping the older ones
+    fn used(window: &mut VecDeque<(Instant, u128)>, now: Instant, window_secs: u64) -> u128 {
+        let window_len = Duration::from_secs(window_secs);
+        while window
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) >= window_len)
+        {
+            window.pop_front();
+        }
+        window.iter().map(|(_, amount)| amount).sum()
+    }
+}
@@ -14,6 +14,7 @@ pub fn log_to_eth_transaction(log: alloy::rpc::types::Log) -> EthTransaction {
         block_number: log.block_number.unwrap_or_default(),
         from,
         to,
+        token: log.address(),
         amount: U256::from(amount),
         timestamp: SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
@@ -0,0 +1,128 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use hyli_modules::log_error;
+use opentelemetry::{
+    metrics::{Meter, ObservableCounter},
+    KeyValue,
+};
+use sqlx::{PgConnection, PgPool};
+
+const COMMITS_TOTAL: &str = "orderbook.commits.total";
+const TRADES_TOTAL: &str = "orderbook.trades.total";
+const VOLUME_TOTAL: &str = "orderbook.volume.total";
+
+/// Counter name and pair, empty for global counters
+type CounterKey = (String, String);
+
+/// Monotonic business counters (commits, trades and traded volume per pair). They are kept in
+/// the `metric_counters` table, updated in the transaction persisting each commit, and exported
+/// from their persisted totals so that they do not start over from zero on restart.
+pub struct BusinessCounters {
+    values: Arc<Mutex<HashMap<CounterKey, u64>>>,
+    _instruments: Vec<ObservableCounter<u64>>,
+}
+
+impl BusinessCounters {
+    /// Loads the persisted totals and registers the counters on the global meter provider
+    pub async fn load(pool: &PgPool) -> Result<Self> {
+        let meter = opentelemetry::global::meter("orderbook");
+        Self::load_with_meter(pool, meter).await
+    }
+
+    pub async fn load_with_meter(pool: &PgPool, meter: Meter) -> Result<Self> {
+        let rows: Vec<(String, String, i64)> = log_error!(
+            sqlx::query_as("SELECT name, pair, value FROM metric_counters")
+                .fetch_all(pool)
+                .await,
+            "Failed to load metric counters"
+        )?;
+        let values = Arc::new(Mutex::new(
+            rows.into_iter()
+                .map(|(name, pair, value)| ((name, pair), value.max(0) as u64))
+                .collect::<HashMap<_, _>>(),
+        ));
+
+        let instruments = [
+            (COMMITS_TOTAL, "Number of commits persisted", "commits"),
+            (TRADES_TOTAL, "Number of trades by pair", "trades"),
+            (VOLUME_TOTAL, "Traded base quantity by pair", "units"),
+        ]
+        .into_iter()
+        .map(|(name, description, unit)| {
+            let values = values.clone();
+            meter
+                .u64_observable_counter(name)
+                .with_description(description)
+                .with_unit(unit)
+                .with_callback(move |observer| {
+                    let values = values.lock().expect("business counters poisoned");
+                    for ((counter, pair), value) in values.iter() {
+                        if counter != name {
+                            continue;
+                        }
+                        if pair.is_empty() {
+                            observer.observe(*value, &[]);
+                        } else {
+                            observer.observe(*value, &[KeyValue::new("pair", pair.clone())]);
+                        }
+                    }
+                })
+                .build()
+        })
+        .collect();
+
+        Ok(BusinessCounters {
+            values,
+            _instruments: instruments,
+        })
+    }
+
+    /// Adds the commit and its trades, inserted beforehand in the same transaction, to the
+    /// persisted counters. Returns their new totals, to hand to [`Self::update`] once committed.
+    pub async fn record_commit(
+        conn: &mut PgConnection,
+        commit_id: i64,
+    ) -> Result<Vec<(String, String, i64)>> {
+        let totals = log_error!(
+            sqlx::query_as(
+                "
+                WITH trades AS (
+                    SELECT i.symbol, count(*) AS trades, sum(t.qty)::bigint AS volume
+                    FROM trade_events t JOIN instruments i ON i.instrument_id = t.instrument_id
+                    WHERE t.commit_id = $1
+                    GROUP BY i.symbol
+                ), increments (name, pair, value) AS (
+                    SELECT $2::text, ''::text, 1::bigint
+                    UNION ALL SELECT $3, symbol, trades FROM trades
+                    UNION ALL SELECT $4, symbol, volume FROM trades
+                )
+                INSERT INTO metric_counters (name, pair, value)
+                SELECT name, pair, value FROM increments
+                ON CONFLICT (name, pair) DO UPDATE SET value = metric_counters.value + EXCLUDED.value, updated_at = now()
+                RETURNING name, pair, value
+                "
+            )
+            .bind(commit_id)
+            .bind(COMMITS_TOTAL)
+            .bind(TRADES_TOTAL)
+            .bind(VOLUME_TOTAL)
+            .fetch_all(&mut *conn)
+            .await,
+            "Failed to update metric counters"
+        )?;
+        Ok(totals)
+    }
+
+    /// Workers commit concurrently, so an older total may come after a newer one
+    pub fn update(&self, totals: Vec<(String, String, i64)>) {
+        let mut values = self.values.lock().expect("business counters poisoned");
+        for (name, pair, value) in totals {
+            let current = values.entry((name, pair)).or_default();
+            *current = (*current).max(value.max(0) as u64);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use orderbook::model::OrderId;
+
+use crate::clock::Clock;
+
+/// Longest heartbeat timeout a session key can arm cancel-on-disconnect with
+pub const MAX_CANCEL_ON_DISCONNECT_TIMEOUT_SECS: u64 = 3_600;
+
+/// Orders a session key authorized the server to cancel, signed at the nonce of the user
+#[derive(Debug, Clone)]
+pub struct CancelAuthorization {
+    pub nonce: u32,
+    pub order_ids: Vec<OrderId>,
+    pub signature: Vec<u8>,
+}
+
+/// Session key that armed cancel-on-disconnect
+#[derive(Debug)]
+struct ArmedSession {
+    authorization: CancelAuthorization,
+    timeout_secs: u64,
+    last_heartbeat: Instant,
+}
+
+/// Session key whose heartbeat lapsed, with everything needed to cancel its orders
+#[derive(Debug)]
+pub struct LapsedSession {
+    pub user: String,
+    pub public_key: Vec<u8>,
+    pub authorization: CancelAuthorization,
+    pub timeout_secs: u64,
+}
+
+/// Tracks heartbeats of session keys that armed cancel-on-disconnect.
+/// Sessions are kept in memory only: a restart disarms them all.
+#[derive(Debug, Default)]
+pub struct CancelOnDisconnectRegistry {
+    sessions: HashMap<(String, Vec<u8>), ArmedSession>,
+    clock: Clock,
+}
+
+impl CancelOnDisconnectRegistry {
+    pub fn new(clock: Clock) -> Self {
+        CancelOnDisconnectRegistry {
+            sessions: HashMap::new(),
+            clock,
+        }
+    }
+
+    /// Arms (or re-arms) cancel-on-disconnect for a session key
+    pub fn arm(
+        &mut self,
+        user: String,
+        public_key: Vec<u8>,
+        authorization: CancelAuthorization,
+        timeout_secs: u64,
+    ) {
+        let now = self.clock.now();
+        self.sessions.insert(
+            (user, public_key),
+            ArmedSession {
+                authorization,
+                timeout_secs,
+                last_heartbeat: now,
+            },
+        );
+    }
+
+    /// Replaces the authorization of an armed session, once the user acted and its orders
+    /// changed. Counts as a heartbeat. Returns false if the session is not armed anymore.
+    pub fn update(
+        &mut self,
+        user: &str,
+        public_key: &[u8],
+        authorization: CancelAuthorization,
+    ) -> bool {
+        match self
+            .sessions
+            .get_mut(&(user.to_string(), public_key.to_vec()))
+        {
+            Some(session) => {
+                session.authorization = authorization;
+                session.last_heartbeat = self.clock.now();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records a heartbeat. Returns false if the session is not armed anymore.
+    pub fn heartbeat(&mut self, user: &str, public_key: &[u8]) -> bool {
+        match self
+            .sessions
+            .get_mut(&(user.to_string(), public_key.to_vec()))
+        {
+            Some(session) => {
+                session.last_heartbeat = self.clock.now();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Disarms and returns the sessions whose heartbeat lapsed
+    pub fn take_lapsed(&mut self) -> Vec<LapsedSession> {
+        let now = self.clock.now();
+        let lapsed_keys: Vec<(String, Vec<u8>)> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| {
+                now.duration_since(session.last_heartbeat)
+                    >= Duration::from_secs(session.timeout_secs)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        lapsed_keys
+            .into_iter()
+            .filter_map(|key| {
+                let session = self.sessions.remove(&key)?;
+                let (user, public_key) = key;
+                Some(LapsedSession {
+                    user,
+                    public_key,
+                    authorization: session.authorization,
+                    timeout_secs: session.timeout_secs,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hyli_modules::log_error;
+use k256::ecdsa::{signature::DigestSigner, Signature, SigningKey};
+use orderbook::ORDERBOOK_ACCOUNT_IDENTITY;
+use sdk::{Blob, BlobData, BlobTransaction, ContractName};
+use sha3::{Digest, Sha3_256};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::{conf::CheckpointConfig, node_client::NodeClient};
+
+/// Claim of the state commitment after a commit, signed by the server
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct StateCheckpoint {
+    pub orderbook: String,
+    pub commit_id: i64,
+    pub state_commitment: Vec<u8>,
+    pub da_height: u64,
+    pub timestamp_ms: u64,
+}
+
+impl StateCheckpoint {
+    /// Message whose SHA3-256 digest is signed, as session keys sign actions
+    pub fn message(&self) -> String {
+        format!(
+            "{}:checkpoint:{}:{}:{}:{}",
+            self.orderbook,
+            self.commit_id,
+            hex::encode(&self.state_commitment),
+            self.da_height,
+            self.timestamp_ms
+        )
+    }
+}
+
+/// Checkpoint and signature, as published to the DA
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct SignedStateCheckpoint {
+    pub checkpoint: StateCheckpoint,
+    /// SEC1 compressed secp256k1 public key
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+}
+
+/// Signs checkpoints of the settled state, and publishes them through the `/checkpoints`
+/// endpoints and optionally the DA, so that the claims of the server can later be compared
+/// with the settled state commitments to detect equivocation.
+pub struct CheckpointPublisher {
+    pool: PgPool,
+    client: Arc<NodeClient>,
+    orderbook_cn: ContractName,
+    signing_key: SigningKey,
+    da_contract_name: Option<ContractName>,
+}
+
+impl CheckpointPublisher {
+    pub fn new(
+        config: &CheckpointConfig,
+        pool: PgPool,
+        client: Arc<NodeClient>,
+        orderbook_cn: ContractName,
+    ) -> Result<Self> {
+        let key = hex::decode(config.signing_key.trim_start_matches("0x"))
+            .context("decoding the checkpoint signing key")?;
+        let signing_key = SigningKey::from_slice(&key).context("invalid checkpoint signing key")?;
+        Ok(CheckpointPublisher {
+            pool,
+            client,
+            orderbook_cn,
+            signing_key,
+            da_contract_name: (!config.da_contract_name.is_empty())
+                .then(|| config.da_contract_name.clone().into()),
+        })
+    }
+
+    pub async fn publish(&self, commit_id: i64, state_commitment: Vec<u8>) -> Result<()> {
+        let da_height = self.client.get_block_height().await?.0;
+        let checkpoint = StateCheckpoint {
+            orderbook: self.orderbook_cn.0.clone(),
+            commit_id,
+            state_commitment,
+            da_height,
+            timestamp_ms: SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+        };
+        let signature: Signature = self
+            .signing_key
+            .sign_digest(Sha3_256::new_with_prefix(checkpoint.message()));
+        let signed = SignedStateCheckpoint {
+            checkpoint,
+            public_key: self
+                .signing_key
+                .verifying_key()
+                .to_encoded_point(true)
+                .as_bytes()
+                .to_vec(),
+            signature: signature.to_bytes().to_vec(),
+        };
+
+        let da_tx_hash = match &self.da_contract_name {
+            Some(contract_name) => {
+                let blob_tx = BlobTransaction::new(
+                    ORDERBOOK_ACCOUNT_IDENTITY,
+                    vec![Blob {
+                        contract_name: contract_name.clone(),
+                        data: BlobData(borsh::to_vec(&signed)?),
+                    }],
+                );
+                Some(log_error!(
+                    self.client.send_tx_blob(blob_tx).await,
+                    "Failed to publish state checkpoint to the DA"
+                )?)
+            }
+            None => None,
+        };
+
+        let checkpoint = &signed.checkpoint;
+        sqlx::query(
+            "INSERT INTO state_checkpoints (commit_id, state_commitment, da_height, timestamp_ms, public_key, signature, da_tx_hash)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (commit_id) DO NOTHING",
+        )
+        .bind(checkpoint.commit_id)
+        .bind(&checkpoint.state_commitment)
+        .bind(checkpoint.da_height as i64)
+        .bind(checkpoint.timestamp_ms as i64)
+        .bind(&signed.public_key)
+        .bind(&signed.signature)
+        .bind(da_tx_hash.map(|tx_hash| tx_hash.0))
+        .execute(&self.pool)
+        .await?;
+
+        info!(
+            "📜 Published state checkpoint at commit {} (DA height {})",
+            checkpoint.commit_id, checkpoint.da_height
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Time source of the server logic depending on elapsed time, such as heartbeat timeouts.
+///
+/// A frozen clock only moves when advanced, so that tests decide when timeouts lapse
+/// instead of racing against wall time.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    origin: Instant,
+    frozen: bool,
+    advanced_ms: Arc<AtomicU64>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock {
+            origin: Instant::now(),
+            frozen: false,
+            advanced_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Clock {
+    pub fn frozen() -> Self {
+        Clock {
+            frozen: true,
+            ..Clock::default()
+        }
+    }
+
+    pub fn now(&self) -> Instant {
+        let advanced = Duration::from_millis(self.advanced_ms.load(Ordering::Relaxed));
+        if self.frozen {
+            self.origin + advanced
+        } else {
+            Instant::now() + advanced
+        }
+    }
+
+    /// Time elapsed since the clock was created, advances included
+    pub fn elapsed(&self) -> Duration {
+        self.now().duration_since(self.origin)
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.advanced_ms
+            .fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use client_sdk::contract_indexer::AppError;
+use reqwest::StatusCode;
+use sdk::ContractName;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+use crate::conf::{CollateralConfig, CollateralPolicy};
+use crate::services::asset_service::Asset;
+
+/// Where the balances of an asset are deposited from and withdrawn to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetBacking {
+    /// A token contract on Hyli
+    HyliToken,
+    /// An ERC20 token, bridged from Ethereum
+    Bridge,
+}
+
+impl AssetBacking {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AssetBacking::HyliToken => "hyli_token",
+            AssetBacking::Bridge => "bridge",
+        }
+    }
+}
+
+/// Backing of the assets, checked on pair creation so that no pair is created on an asset
+/// users could deposit but never withdraw
+pub struct AssetBackings {
+    policy: CollateralPolicy,
+    hyli_tokens: HashSet<String>,
+    /// Collateral token and listed ERC20 tokens, when the bridge is enabled
+    bridged_tokens: HashSet<String>,
+}
+
+impl AssetBackings {
+    pub fn new(config: &CollateralConfig, bridged_tokens: Vec<ContractName>) -> Self {
+        AssetBackings {
+            policy: config.policy,
+            hyli_tokens: config.hyli_tokens.iter().cloned().collect(),
+            bridged_tokens: bridged_tokens.into_iter().map(|token| token.0).collect(),
+        }
+    }
+
+    pub fn of(&self, contract_name: &str) -> Option<AssetBacking> {
+        if self.bridged_tokens.contains(contract_name) {
+            Some(AssetBacking::Bridge)
+        } else if self.hyli_tokens.contains(contract_name) {
+            Some(AssetBacking::HyliToken)
+        } else {
+            None
+        }
+    }
+
+    /// Checks that both legs of a new pair are backed, as per the policy
+    pub fn check_pair(&self, base: &Asset, quote: &Asset) -> Result<(), AppError> {
+        self.check_asset(base)?;
+        self.check_asset(quote)
+    }
+
+    /// Checks that an asset traded by a new pair is backed, as per the policy. Only the quote
+    /// asset of a perp market is checked, as its base asset is never delivered.
+    pub fn check_asset(&self, asset: &Asset) -> Result<(), AppError> {
+        if self.of(&asset.contract_name).is_some() {
+            return Ok(());
+        }
+        match self.policy {
+            CollateralPolicy::Reject => Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!(
+                    "Asset {} ({}) has no registered token or bridge backing, it could not be withdrawn",
+                    asset.symbol,
+                    asset.contract_name
+                ),
+            )),
+            CollateralPolicy::Warn => {
+                warn!(
+                    "Creating a pair on asset {} ({}), which has no registered token or bridge backing",
+                    asset.symbol, asset.contract_name
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Records the backing of every asset in the database, for the API to serve it
+    pub async fn persist(&self, pool: &PgPool) -> Result<()> {
+        let contract_names: Vec<String> = sqlx::query_scalar("SELECT contract_name FROM assets")
+            .fetch_all(pool)
+            .await?;
+        let mut tx = pool.begin().await?;
+        for contract_name in contract_names {
+            let backing = self.of(&contract_name);
+            if backing.is_none() {
+                info!("Asset {contract_name} has no registered token or bridge backing");
+            }
+            sqlx::query("UPDATE assets SET backing = $2 WHERE contract_name = $1")
+                .bind(&contract_name)
+                .bind(backing.map(|backing| backing.as_str()))
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
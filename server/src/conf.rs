@@ -1,29 +1,63 @@
 use config::{Config, Environment, File};
 use hyli_modules::modules::websocket::WebSocketConfig;
+use orderbook::model::{FeeTier, MAX_FEE_BPS};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::{risk::RiskLimits, storage::NotifierKind};
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct Conf {
     pub id: String,
-    /// The log format to use - "json", "node" or "full" (default)
+    /// The log format to use - "json" or "full" (default)
     pub log_format: String,
+    /// Directives filtering the logs, e.g. "info,server::database=debug". `RUST_LOG` is used
+    /// when empty. Applied again when the config is reloaded.
+    #[serde(default)]
+    pub log_filter: String,
+    /// Export of the spans to an OpenTelemetry collector
+    #[serde(default)]
+    pub tracing: TracingConfig,
     /// Directory name to store node state.
     pub data_directory: PathBuf,
     /// When running only the indexer, the address of the DA server to connect to
     pub da_read_from: String,
     pub node_url: String,
+    /// Timeouts, retries and circuit breaking of the requests to the node
+    #[serde(default)]
+    pub node_client: NodeClientConfig,
     pub indexer_url: String,
     pub indexer_database_url: String,
 
     pub database_url: String,
     pub database_name: String,
+    /// How the feeds are notified of the committed writes: `postgres` for the `pg_notify`
+    /// channels the API server listens to, `in_process` for broadcast channels of the server
+    #[serde(default)]
+    pub notifications: NotifierKind,
 
     pub rest_server_port: u16,
     pub rest_server_max_body_size: usize,
+    /// Port of the gRPC API, served alongside the REST API. Not served when 0.
+    #[serde(default)]
+    pub grpc_server_port: u16,
 
     pub buffer_blocks: u32,
+    /// Maximum number of txs proven together in a single proof
     pub max_txs_per_proof: usize,
+    /// How long sequenced txs wait for more txs to be batched with before being proven
+    pub proof_batch_window_ms: u64,
+    /// Number of batches proven concurrently
+    pub proving_workers: usize,
+    /// How many times a batch waiting for a proving worker can be overtaken by cheaper ones.
+    /// Batches are proven in commit order when 0.
+    #[serde(default)]
+    pub max_proof_reordering: usize,
+    /// zkVM the orderbook guest is proven with
+    pub prover_backend: ProverBackendKind,
+    /// Proving on the Succinct prover network, with the sp1 backend
+    #[serde(default)]
+    pub prover_network: ProverNetworkConfig,
     pub tx_working_window_size: usize,
 
     /// Secret used to derive commitments (configured per deployment)
@@ -38,6 +72,567 @@ pub struct Conf {
 
     /// URL to trigger L2 book updates
     pub trigger_url: String,
+
+    /// Volume-tiered trading fees configuration
+    pub fees: FeeConfig,
+
+    /// Default pre-trade risk limits, overridable per identity through the admin API
+    #[serde(default)]
+    pub risk: RiskLimits,
+
+    /// Tenant namespace, for white-label deployments sharing the same node and Postgres server
+    #[serde(default)]
+    pub tenant: TenantConfig,
+
+    /// Snapshots of the orderbook state in the data directory
+    #[serde(default)]
+    pub snapshot: SnapshotConfig,
+
+    /// Packing of consecutive actions into a single blob transaction
+    #[serde(default)]
+    pub blob_batch: BlobBatchConfig,
+
+    /// Retries and overflow policy of the blob transactions waiting in the outbox
+    #[serde(default)]
+    pub blob_outbox: BlobOutboxConfig,
+
+    /// Workers persisting the events of the actions
+    #[serde(default)]
+    pub database_workers: DatabaseWorkersConfig,
+
+    /// Partitioning and retention of the order, trade and balance events
+    #[serde(default)]
+    pub event_retention: EventRetentionConfig,
+
+    /// Signed checkpoints of the settled state
+    #[serde(default)]
+    pub checkpoint: CheckpointConfig,
+
+    /// Read-only replica serving the query endpoints
+    #[serde(default)]
+    pub read_replica: ReadReplicaConfig,
+
+    /// Backing of the assets pairs are created on
+    #[serde(default)]
+    pub collateral: CollateralConfig,
+
+    /// Rate limits of the REST API
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+
+    /// Thresholds of the readiness probe
+    #[serde(default)]
+    pub health: HealthConfig,
+
+    /// Index prices of the pairs, fed to their circuit breakers
+    #[serde(default)]
+    pub oracle: OracleConfig,
+
+    /// Funding of the perp markets, settled against their index prices
+    #[serde(default)]
+    pub funding: FundingConfig,
+
+    /// Liquidation of the positions of under-margined accounts
+    #[serde(default)]
+    pub liquidation: LiquidationConfig,
+
+    /// Release of the large withdrawals once their delay is over
+    #[serde(default)]
+    pub withdrawal_queue: WithdrawalQueueConfig,
+
+    /// Detection of the deposits from the blocks of the DA
+    #[serde(default)]
+    pub deposits: DepositConfig,
+
+    /// Reconciliation of the balances owed on the orderbook against the tokens it holds
+    #[serde(default)]
+    pub reconciliation: ReconciliationConfig,
+
+    /// Append-only log of the privileged actions
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    /// Verification of the settled commitments against the local commits
+    #[serde(default)]
+    pub settlement_check: SettlementCheckConfig,
+}
+
+/// zkVM the orderbook guest is compiled for and proven with.
+/// The contract is registered with the verifier of the backend.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProverBackendKind {
+    #[default]
+    Sp1,
+    Risc0,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct TracingConfig {
+    /// Export the spans over OTLP, as the `--tracing` flag does
+    pub enabled: bool,
+    /// gRPC endpoint of the OTLP collector
+    pub endpoint: String,
+    /// Share of the traces started here that are exported, from 0 to 1. Traces continued from
+    /// an exported parent, such as the proof of an action, are exported with it.
+    pub sampling_ratio: f64,
+    /// Service the spans are exported as
+    pub service_name: String,
+}
+
+impl TracingConfig {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if !(0.0..=1.0).contains(&self.sampling_ratio) {
+            anyhow::bail!(
+                "Invalid tracing sampling ratio {}, expected a share between 0 and 1",
+                self.sampling_ratio
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct NodeClientConfig {
+    /// Timeout of each request to the node, in milliseconds
+    pub timeout_ms: u64,
+    /// Number of times a failed request is sent again
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on each following one, in milliseconds.
+    /// Up to half of it is added as jitter.
+    pub retry_backoff_ms: u64,
+    /// Consecutive failures after which the circuit opens, and requests fail fast
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a request probes the node again, in seconds
+    pub open_duration_secs: u64,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct ProverNetworkConfig {
+    /// Send proof requests to the prover network instead of proving on the local CPU
+    pub enabled: bool,
+    /// RPC endpoint of the prover network. The SP1 default endpoint is used when empty.
+    pub rpc_url: String,
+    /// Hex encoded key signing the proof requests, preferably set through
+    /// the HYLI_PROVER_NETWORK__PRIVATE_KEY environment variable
+    pub private_key: String,
+    /// How long a proof request may take before it is considered failed, in seconds
+    pub timeout_secs: u64,
+    /// Number of times a failed proof request is sent again
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on each following one, in milliseconds
+    pub retry_backoff_ms: u64,
+    /// Prove on the local CPU once the retries are exhausted, instead of failing the batch
+    pub fallback_to_local: bool,
+}
+
+// The configuration is logged on startup, without the key signing proof requests
+impl std::fmt::Debug for ProverNetworkConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProverNetworkConfig")
+            .field("enabled", &self.enabled)
+            .field("rpc_url", &self.rpc_url)
+            .field("timeout_secs", &self.timeout_secs)
+            .field("max_retries", &self.max_retries)
+            .field("retry_backoff_ms", &self.retry_backoff_ms)
+            .field("fallback_to_local", &self.fallback_to_local)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct BlobBatchConfig {
+    /// How long an action waits for the following ones to be sent along, in milliseconds.
+    /// Each action is sent in its own blob transaction when 0.
+    pub window_ms: u64,
+    /// Maximum number of actions packed into a single blob transaction
+    pub max_actions: usize,
+}
+
+impl BlobBatchConfig {
+    pub fn max_actions(&self) -> usize {
+        if self.window_ms == 0 {
+            1
+        } else {
+            self.max_actions.max(1)
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseWorkersConfig {
+    /// Number of workers writing events concurrently
+    pub count: usize,
+    /// Requests queued per worker. Once every queue is full, new actions are refused with a 429.
+    pub queue_capacity: usize,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct EventRetentionConfig {
+    /// Number of commits per partition of the event tables
+    pub partition_commits: u64,
+    /// Partitions whose last commit is older than this are archived and dropped.
+    /// Events are kept forever when 0.
+    pub retention_days: u64,
+    /// Directory the partitions are written to as CSV files before being dropped, e.g. a
+    /// mounted object storage bucket. Partitions are dropped without being archived when empty.
+    pub archive_dir: String,
+    /// How often partitions are created ahead of the commits and pruned, in seconds
+    pub interval_secs: u64,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct BlobOutboxConfig {
+    /// Delay before the pending blob transactions are sent again after a failure,
+    /// doubled on each following one, in milliseconds
+    pub retry_backoff_ms: u64,
+    /// Upper bound of the delay between two attempts, in milliseconds
+    pub max_retry_backoff_ms: u64,
+    /// Number of pending blob transactions above which the overflow policy applies.
+    /// Unlimited when 0.
+    pub max_pending: usize,
+    pub overflow_policy: OutboxOverflowPolicy,
+}
+
+/// What happens to new actions while the outbox holds more than `max_pending` blob transactions
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxOverflowPolicy {
+    /// Actions that add exposure are refused with a 503 until the outbox drains.
+    /// Cancellations are still accepted.
+    #[default]
+    Reject,
+    /// Actions are accepted, and the overflow is only logged
+    Warn,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct HealthConfig {
+    /// Time each check of a probe has to complete, in milliseconds
+    pub check_timeout_ms: u64,
+    /// Readiness fails while more txs wait to settle. Unchecked when 0.
+    pub max_pending_txs: i64,
+    /// Readiness fails while the oldest tx waiting to settle was sent longer ago than this,
+    /// e.g. when the prover or the DA listener is stuck. Unchecked when 0.
+    pub max_settlement_delay_secs: u64,
+    /// Whether readiness requires the node to answer. Not checked when running offline.
+    pub check_node: bool,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    /// Actions (POST requests): orders, cancellations, deposits, withdrawals...
+    pub orders: RateLimit,
+    /// Reads (GET requests)
+    pub market_data: RateLimit,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Sustained requests per second of an identity (`x-identity` header). Unlimited when 0.
+    pub identity_per_sec: u32,
+    /// Sustained requests per second of a client IP, all identities included. Unlimited when 0.
+    pub ip_per_sec: u32,
+    /// Seconds worth of requests a client may send at once above its sustained rate
+    pub burst_secs: u32,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct CollateralConfig {
+    pub policy: CollateralPolicy,
+    /// Token contracts on Hyli, which assets are deposited from and withdrawn to.
+    /// The collateral token is also backed by the bridge when the bridge is enabled.
+    pub hyli_tokens: Vec<String>,
+}
+
+/// What happens when a pair is created on an asset with no backing, that users could deposit
+/// (e.g. by a transfer to the orderbook) but never withdraw
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollateralPolicy {
+    /// The pair is refused with a 400
+    #[default]
+    Reject,
+    /// The pair is created, and the missing backing only logged
+    Warn,
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct CheckpointConfig {
+    /// Sign and publish a checkpoint each time the state snapshot moves forward.
+    /// Requires snapshots to be enabled.
+    pub enabled: bool,
+    /// Hex encoded secp256k1 key signing the checkpoints, preferably set through
+    /// the HYLI_CHECKPOINT__SIGNING_KEY environment variable
+    pub signing_key: String,
+    /// Contract the checkpoints are sent to as blobs, to publish them on the DA.
+    /// They are only served by the API when empty.
+    pub da_contract_name: String,
+}
+
+// The configuration is logged on startup, without the key signing checkpoints
+impl std::fmt::Debug for CheckpointConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CheckpointConfig")
+            .field("enabled", &self.enabled)
+            .field("da_contract_name", &self.da_contract_name)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct ReadReplicaConfig {
+    /// URL of the replica, with or without the database name as for `database_url`.
+    /// Query endpoints read from the primary when empty.
+    pub url: String,
+    pub max_connections: u32,
+    /// How often the replica is checked, in seconds. Queries go to the primary while it is down.
+    pub health_check_interval_secs: u64,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    /// Restore the state from the last snapshot on boot, instead of rebuilding it from the database
+    pub enabled: bool,
+    /// How often the snapshot is moved forward to the last settled commit, in seconds
+    pub interval_secs: u64,
+}
+
+/// Namespace of a tenant. Each tenant runs its own server, whose orderbook contract, database
+/// (and thus prover queue) and data directory are isolated from the other tenants'.
+/// Admin secret and API credentials come from the tenant's own configuration file.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct TenantConfig {
+    /// Lowercase alphanumeric identifier of the tenant. Empty for single-tenant deployments.
+    pub id: String,
+}
+
+impl TenantConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.id.is_empty()
+    }
+
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        // The id ends up in contract and database names
+        if !self
+            .id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            anyhow::bail!(
+                "Invalid tenant id {:?}: only lowercase letters, digits and underscores are allowed",
+                self.id
+            );
+        }
+        Ok(())
+    }
+
+    /// Prefixes a contract or database name with the tenant id
+    pub fn prefixed(&self, name: &str) -> String {
+        if self.is_enabled() {
+            format!("{}_{name}", self.id)
+        } else {
+            name.to_string()
+        }
+    }
+
+    pub fn data_directory(&self, data_directory: &Path) -> PathBuf {
+        if self.is_enabled() {
+            data_directory.join(&self.id)
+        } else {
+            data_directory.to_path_buf()
+        }
+    }
+
+    /// Moves the database into the tenant namespace. Tenants get their own database rather
+    /// than a schema, as migrations and notification channels are not schema-qualified.
+    pub fn namespace_database(&self, database_url: &mut String, database_name: &mut String) {
+        if !self.is_enabled() {
+            return;
+        }
+        // The server URL is used to create the tenant database when it does not exist yet
+        if let Some(server_url) = database_url.strip_suffix(&format!("/{database_name}")) {
+            *database_url = server_url.to_string();
+        }
+        *database_name = self.prefixed(database_name);
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct OracleConfig {
+    /// How often the index prices are pulled, in seconds
+    pub interval_secs: u64,
+    /// Timeout of each request to a source, in milliseconds
+    pub timeout_ms: u64,
+    /// Sources of the index prices. The index price of a pair is the median of its sources.
+    /// No price is pulled when empty.
+    pub sources: Vec<OracleSource>,
+}
+
+/// Price of a pair, read from a JSON document: the response of a price feed, or the state of
+/// an oracle contract on Hyli served by the indexer
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct OracleSource {
+    /// Pair priced by the source, as BASE/QUOTE
+    pub symbol: String,
+    /// URL of the price feed
+    #[serde(default)]
+    pub url: String,
+    /// Oracle contract, read when no URL is set
+    #[serde(default)]
+    pub contract_name: String,
+    /// JSON pointer to the price in the document, e.g. "/data/price".
+    /// The price can be a number or a decimal string.
+    pub pointer: String,
+    /// The price is multiplied by 10^decimals into the price unit of the pair
+    #[serde(default)]
+    pub decimals: u32,
+}
+
+impl OracleConfig {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        for source in &self.sources {
+            if !source.symbol.contains('/') {
+                anyhow::bail!(
+                    "Invalid oracle source symbol {:?}, expected BASE/QUOTE",
+                    source.symbol
+                );
+            }
+            if source.url.is_empty() == source.contract_name.is_empty() {
+                anyhow::bail!(
+                    "Oracle source of {} needs either a url or a contract_name",
+                    source.symbol
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct FundingConfig {
+    /// How often the funding of the perp markets is settled, in seconds. Disabled when 0.
+    pub interval_secs: u64,
+    /// The premium of the book over the index price is paid over this many intervals
+    pub dampening: u64,
+    /// Cap of the funding paid per interval, in basis points of the index price
+    pub max_rate_bps: u64,
+    /// Index prices older than this are not settled against, in seconds
+    pub max_index_age_secs: u64,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidationConfig {
+    /// How often the margin accounts are checked, in seconds. Disabled when 0.
+    pub interval_secs: u64,
+    /// Maximum number of positions liquidated per check, the most under-margined accounts first
+    pub max_per_check: usize,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct WithdrawalQueueConfig {
+    /// How often the queued withdrawals are checked, in seconds. Disabled when 0.
+    pub interval_secs: u64,
+    /// Maximum number of withdrawals released per check, the oldest first
+    pub max_per_check: usize,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// File the entries are appended to as JSON lines, relative to the data directory.
+    /// Only recorded in the database when empty.
+    pub file: String,
+    /// Withdrawals above these amounts are logged
+    pub withdraw_thresholds: Vec<AuditWithdrawThreshold>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct AuditWithdrawThreshold {
+    pub symbol: String,
+    pub amount: u64,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct SettlementCheckConfig {
+    /// Check the commitments of the orderbook txs settled in the blocks of the DA
+    pub enabled: bool,
+    /// Refuse new actions once a settled commitment differs from the local one
+    pub halt_on_divergence: bool,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct ReconciliationConfig {
+    /// How often the balances are reconciled, in seconds. Disabled when 0.
+    pub interval_secs: u64,
+    /// Shortfall of the held tokens of an asset, in basis points of its owed balances, above
+    /// which an alert is raised
+    pub max_drift_bps: u64,
+    /// Timeout of the balance requests to the indexer
+    pub timeout_ms: u64,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct DepositConfig {
+    /// Credit the settled token transfers to the orderbook account, read from the DA
+    pub watch: bool,
+    /// First block read from the DA when the server starts without a DA position of its own.
+    /// Defaults to the current block of the node, not to credit the transfers of the past again.
+    pub start_block: Option<u64>,
+    /// Serve `POST /deposit`, crediting users without any transfer. Only for local setups.
+    pub trusted_endpoint: bool,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct FeeConfig {
+    /// How often users' fee tiers are recomputed, in seconds
+    pub refresh_interval_secs: u64,
+    /// Size of the rolling volume window, in days
+    pub volume_window_days: u32,
+    /// Asset the rolling volumes are counted in. Trades quoted in another asset are converted
+    /// with the index price of the `{quote}/{volume_asset}` pair, and not counted without one.
+    pub volume_asset: String,
+    /// Fee tiers, sorted by increasing volume threshold. No fee is charged when empty.
+    pub tiers: Vec<FeeTierConfig>,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct FeeTierConfig {
+    /// Minimum rolling volume, in whole units of the volume asset, to reach this tier
+    pub min_volume: u64,
+    pub maker_fee_bps: u16,
+    pub taker_fee_bps: u16,
+}
+
+impl FeeConfig {
+    /// Returns the highest tier reached by the given rolling volume
+    pub fn tier_for_volume(&self, volume: u64) -> FeeTier {
+        self.tiers
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, tier)| volume >= tier.min_volume)
+            .map(|(index, tier)| FeeTier {
+                tier: index as u8,
+                maker_fee_bps: tier.maker_fee_bps,
+                taker_fee_bps: tier.taker_fee_bps,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if !self.tiers.is_empty() && self.volume_asset.is_empty() {
+            anyhow::bail!("A volume asset is required to compute fee tiers");
+        }
+        for (index, tier) in self.tiers.iter().enumerate() {
+            if tier.maker_fee_bps > MAX_FEE_BPS || tier.taker_fee_bps > MAX_FEE_BPS {
+                anyhow::bail!("Fee tier {index} exceeds the maximum fee of {MAX_FEE_BPS} bps");
+            }
+            if index > 0 && tier.min_volume <= self.tiers[index - 1].min_volume {
+                anyhow::bail!("Fee tiers must be sorted by increasing volume threshold");
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -48,6 +643,38 @@ pub struct BridgeConfig {
     pub eth_rpc_ws_url: String,
     pub eth_rpc_http_url: String,
     pub eth_signer_private_key: String,
+    /// ERC20 tokens bridged besides the collateral token of `eth_contract_address`
+    #[serde(default)]
+    pub tokens: Vec<BridgedTokenConfig>,
+    /// Holds the deposits and withdrawals of the bridge whatever the pause of the orderbook
+    /// contract, e.g. when the Ethereum side is compromised
+    #[serde(default)]
+    pub paused: bool,
+    /// Rolling window caps of the amounts bridged, per Hyli token contract
+    #[serde(default)]
+    pub limits: Vec<BridgeLimitConfig>,
+}
+
+/// ERC20 token held by the vault, credited to a Hyli token contract
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct BridgedTokenConfig {
+    /// Symbol of the asset on the orderbook, whose scale the ERC20 amounts are converted to
+    pub symbol: String,
+    /// Token contract on Hyli whose balances are credited and withdrawn
+    pub contract_name: String,
+    pub eth_contract_address: String,
+    /// Decimals of the ERC20 amounts
+    pub decimals: u32,
+}
+
+/// Caps of the amounts of a token bridged over a rolling window, in the unit of its Hyli token
+/// contract. A direction without a cap is not limited.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct BridgeLimitConfig {
+    pub contract_name: String,
+    pub window_secs: u64,
+    pub max_deposit: Option<u64>,
+    pub max_withdraw: Option<u64>,
 }
 
 impl Conf {
@@ -69,6 +696,13 @@ impl Conf {
             )
             .build()?
             .try_deserialize()?;
+        conf.tenant.validate()?;
+        conf.oracle.validate()?;
+        conf.tracing.validate()?;
+        conf.fees.validate()?;
+        if conf.checkpoint.enabled && !conf.snapshot.enabled {
+            anyhow::bail!("State checkpoints are published on snapshots, which are disabled");
+        }
         Ok(conf)
     }
 }
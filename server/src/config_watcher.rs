@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::{Mutex, RwLock},
+};
+use tracing::{error, info};
+
+use crate::{
+    audit::{fingerprint, AuditEntry, AuditLog},
+    conf::{Conf, DatabaseWorkersConfig, FeeConfig, RateLimitConfig},
+    database::WorkerQueues,
+    rate_limit::RateLimiter,
+    telemetry,
+};
+
+/// Settings of the config that change without a restart. The others are only read at startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeSettings {
+    pub log_filter: String,
+    pub rate_limit: RateLimitConfig,
+    /// The refresh interval, and whether fee tiers are charged at all, only change on restart
+    pub fees: FeeConfig,
+    /// The queue capacity only changes on restart
+    pub database_workers: DatabaseWorkersConfig,
+}
+
+impl RuntimeSettings {
+    pub fn of(conf: &Conf) -> Self {
+        RuntimeSettings {
+            log_filter: conf.log_filter.clone(),
+            rate_limit: conf.rate_limit.clone(),
+            fees: conf.fees.clone(),
+            database_workers: conf.database_workers.clone(),
+        }
+    }
+
+    /// Checks that the settings can replace `current`
+    fn validate(&self, current: &RuntimeSettings) -> Result<()> {
+        telemetry::env_filter(&self.log_filter)?;
+        self.fees.validate()?;
+        if self.fees.tiers.is_empty() != current.fees.tiers.is_empty() {
+            bail!("Fee tiers are only enabled or disabled on restart");
+        }
+        if self.fees.refresh_interval_secs != current.fees.refresh_interval_secs {
+            bail!("fees.refresh_interval_secs only changes on restart");
+        }
+        if self.database_workers.count == 0 {
+            bail!("At least one database worker is required");
+        }
+        if self.database_workers.queue_capacity != current.database_workers.queue_capacity {
+            bail!("database_workers.queue_capacity only changes on restart");
+        }
+        Ok(())
+    }
+
+    /// Names of the settings that differ from `current`
+    fn changes(&self, current: &RuntimeSettings) -> Result<Vec<String>> {
+        let (serde_json::Value::Object(new), serde_json::Value::Object(current)) =
+            (serde_json::to_value(self)?, serde_json::to_value(current)?)
+        else {
+            bail!("Runtime settings are not serialized as an object");
+        };
+        Ok(new
+            .into_iter()
+            .filter(|(name, value)| current.get(name) != Some(value))
+            .map(|(name, _)| name)
+            .collect())
+    }
+}
+
+/// Applies the runtime settings of the config once it changes: on SIGHUP, the config files are
+/// read again, and `POST /admin/config` sets them directly. Invalid updates are rejected as a
+/// whole, leaving the running settings as they were, and applied ones are recorded in the audit
+/// log.
+pub struct ConfigWatcher {
+    config_files: Vec<String>,
+    settings: Mutex<RuntimeSettings>,
+    rate_limiter: Arc<RateLimiter>,
+    fees: Arc<RwLock<FeeConfig>>,
+    worker_queues: Arc<WorkerQueues>,
+    audit_log: Arc<AuditLog>,
+}
+
+impl ConfigWatcher {
+    pub fn new(
+        config_files: Vec<String>,
+        conf: &Conf,
+        rate_limiter: Arc<RateLimiter>,
+        fees: Arc<RwLock<FeeConfig>>,
+        worker_queues: Arc<WorkerQueues>,
+        audit_log: Arc<AuditLog>,
+    ) -> Self {
+        ConfigWatcher {
+            config_files,
+            settings: Mutex::new(RuntimeSettings::of(conf)),
+            rate_limiter,
+            fees,
+            worker_queues,
+            audit_log,
+        }
+    }
+
+    pub async fn settings(&self) -> RuntimeSettings {
+        self.settings.lock().await.clone()
+    }
+
+    /// Reads the config files again, as at startup, and applies their runtime settings
+    pub async fn reload(&self, actor: &str) -> Result<Vec<String>> {
+        let conf = Conf::new(self.config_files.clone()).context("reading the config files")?;
+        self.apply(RuntimeSettings::of(&conf), actor).await
+    }
+
+    /// Applies `update` once validated, returning the names of the settings that changed
+    pub async fn apply(&self, update: RuntimeSettings, actor: &str) -> Result<Vec<String>> {
+        let mut settings = self.settings.lock().await;
+        update.validate(&settings)?;
+        let changed = update.changes(&settings)?;
+        if changed.is_empty() {
+            return Ok(changed);
+        }
+
+        if update.log_filter != settings.log_filter {
+            telemetry::set_log_filter(&update.log_filter)?;
+        }
+        self.rate_limiter.set_config(update.rate_limit.clone());
+        *self.fees.write().await = update.fees.clone();
+        self.worker_queues.set_count(update.database_workers.count);
+
+        let details = serde_json::json!({
+            "changed": changed,
+            "previous": *settings,
+            "settings": update,
+        });
+        self.audit_log
+            .record(AuditEntry {
+                action: "config_change".to_string(),
+                actor: actor.to_string(),
+                fingerprint: fingerprint(&[actor.as_bytes(), details.to_string().as_bytes()]),
+                status: None,
+                details,
+            })
+            .await;
+        info!(
+            "Applied the config changes of {actor}: {}",
+            changed.join(", ")
+        );
+        *settings = update;
+        Ok(changed)
+    }
+
+    /// Reloads the config files on each SIGHUP
+    pub async fn watch_hangups(self: Arc<Self>) {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                error!(
+                    "Could not listen to SIGHUP, the config is only reloaded through the API: {e}"
+                );
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            match self.reload("sighup").await {
+                Ok(changed) if changed.is_empty() => info!("Config reloaded, nothing changed"),
+                Ok(_) => {}
+                Err(e) => error!("Rejected the reloaded config: {e:#}"),
+            }
+        }
+    }
+}
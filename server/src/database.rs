@@ -1,10 +1,10 @@
-use std::collections::{HashMap, HashSet};
-use std::sync::atomic::AtomicUsize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use client_sdk::rest_client::{NodeApiClient, NodeApiHttpClient};
+use futures::future::BoxFuture;
 use hyli_modules::{
     bus::{BusMessage, SharedMessageBus},
     log_error, module_bus_client, module_handle_messages,
@@ -15,16 +15,31 @@ use opentelemetry::{
     metrics::{Histogram, Meter, UpDownCounter},
     KeyValue,
 };
-use orderbook::model::{OrderId, OrderbookEvent, UserInfo};
+use orderbook::{
+    model::{MarketStatus, Order, OrderId, OrderbookEvent, UserInfo},
+    utils::split_sub_account,
+    ORDERBOOK_ACCOUNT_IDENTITY,
+};
 use reqwest::StatusCode;
 use sdk::{BlobTransaction, TxHash};
+use serde::{Deserialize, Serialize};
 use sqlx::types::Json;
-use sqlx::PgPool;
-use tokio::sync::{mpsc, RwLock};
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder};
+use tokio::sync::{mpsc, Notify, RwLock};
 use tracing::{debug, info, Instrument};
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+use crate::business_metrics::BusinessCounters;
+use crate::conf::{
+    BlobBatchConfig, BlobOutboxConfig, DatabaseWorkersConfig, EventRetentionConfig,
+    OutboxOverflowPolicy,
+};
+use crate::event_retention::EventRetention;
+use crate::node_client::NodeClient;
+use crate::read_replica::ReadPool;
 use crate::services::user_service::UserService;
+use crate::snapshot::SnapshotStore;
+use crate::storage::{Notification, Notifier, Storage};
 use crate::{prover::OrderbookProverRequest, services::asset_service::AssetService};
 
 /// Metrics for tracking database operation durations
@@ -50,6 +65,8 @@ pub struct DatabaseMetrics {
     pub order_update_duration: Histogram<f64>,
     /// Duration of user operations
     pub user_ops_duration: Histogram<f64>,
+    /// Duration of the batched order, trade and balance events insert
+    pub event_rows_insert_duration: Histogram<f64>,
     /// Duration of prover request insert
     pub prover_request_insert_duration: Histogram<f64>,
     /// Duration of contract events insert
@@ -64,6 +81,10 @@ pub struct DatabaseMetrics {
     pub worker_queue_depth: UpDownCounter<i64>,
     /// Total number of active workers
     pub worker_count: UpDownCounter<i64>,
+    /// Requests the worker queues can hold, to compute their saturation
+    pub worker_queue_capacity: UpDownCounter<i64>,
+    /// Number of workers processing a request
+    pub workers_busy: UpDownCounter<i64>,
 }
 
 impl DatabaseMetrics {
@@ -144,6 +165,14 @@ impl DatabaseMetrics {
                 .with_unit("s")
                 .with_boundaries(latency_buckets.clone())
                 .build(),
+            event_rows_insert_duration: meter
+                .f64_histogram("db.event_rows.insert.duration")
+                .with_description(
+                    "Duration of the batched order, trade and balance events insert in seconds",
+                )
+                .with_unit("s")
+                .with_boundaries(latency_buckets.clone())
+                .build(),
             prover_request_insert_duration: meter
                 .f64_histogram("db.prover_request.insert.duration")
                 .with_description("Duration of prover request insert in seconds")
@@ -184,6 +213,16 @@ impl DatabaseMetrics {
                 .with_description("Total number of active database workers")
                 .with_unit("workers")
                 .build(),
+            worker_queue_capacity: meter
+                .i64_up_down_counter("db.worker.queue.capacity")
+                .with_description("Number of requests the worker queues can hold")
+                .with_unit("requests")
+                .build(),
+            workers_busy: meter
+                .i64_up_down_counter("db.worker.busy")
+                .with_description("Number of database workers processing a request")
+                .with_unit("workers")
+                .build(),
         }
     }
 
@@ -201,6 +240,129 @@ impl Default for DatabaseMetrics {
     }
 }
 
+/// Identifiers given by the client to an order, distinct from the exchange order id.
+/// They are only stored offchain: neither the contract nor the prover see them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderTag {
+    pub client_order_id: Option<String>,
+    pub tag: Option<String>,
+}
+
+/// Requests queued to the database workers, shared with the handlers enqueueing actions so that
+/// they refuse new ones once every worker queue is full. The number of workers changes when the
+/// config is reloaded, and the database module spawns or stops workers on its next request.
+pub struct WorkerQueues {
+    count: AtomicUsize,
+    queue_capacity: usize,
+    queued: AtomicUsize,
+}
+
+impl WorkerQueues {
+    pub fn new(config: DatabaseWorkersConfig) -> Self {
+        WorkerQueues {
+            count: AtomicUsize::new(config.count.max(1)),
+            queue_capacity: config.queue_capacity.max(1),
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn set_count(&self, count: usize) {
+        self.count.store(count.max(1), Ordering::Relaxed);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.count() * self.queue_capacity
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Fails when every worker queue is full
+    pub fn check_capacity(&self) -> Result<()> {
+        if self.queued() >= self.capacity() {
+            anyhow::bail!(
+                "{} database writes are waiting for a worker, retry later",
+                self.queued()
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn on_queued(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_dequeued(&self) {
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Blob transactions waiting in the `blob_tx_outbox` table to be sent, in commit order.
+/// Rows are inserted in the same database transaction as the events of their action, and only
+/// committed rows are sent by the [`BlobDispatcher`], so that the chain never sees an action
+/// missing from the database.
+/// The count is refreshed from the table on each flush, and bumped as actions are enqueued.
+pub struct BlobOutbox {
+    config: BlobOutboxConfig,
+    pending: AtomicUsize,
+    /// Wakes the dispatcher up once new rows are committed
+    committed: Notify,
+}
+
+impl BlobOutbox {
+    pub fn new(config: BlobOutboxConfig) -> Self {
+        BlobOutbox {
+            config,
+            pending: AtomicUsize::new(0),
+            committed: Notify::new(),
+        }
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    fn is_overflowing(&self) -> bool {
+        self.config.max_pending > 0 && self.pending() >= self.config.max_pending
+    }
+
+    /// Fails when the outbox overflows and the policy refuses new actions
+    pub fn check_capacity(&self) -> Result<()> {
+        if self.is_overflowing() && self.config.overflow_policy == OutboxOverflowPolicy::Reject {
+            anyhow::bail!(
+                "{} blob transactions are waiting to be sent, retry later",
+                self.pending()
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn on_enqueued(&self) {
+        self.committed.notify_one();
+        let pending = self.pending.fetch_add(1, Ordering::Relaxed) + 1;
+        if pending == self.config.max_pending {
+            tracing::warn!(
+                "Blob transaction outbox reached {pending} pending transactions (policy: {:?})",
+                self.config.overflow_policy
+            );
+        }
+    }
+
+    /// Delay before the next attempt, after `failures` consecutive failed ones
+    fn retry_backoff(&self, failures: u32) -> Duration {
+        let backoff = self
+            .config
+            .retry_backoff_ms
+            .saturating_mul(1u64 << failures.saturating_sub(1).min(16));
+        Duration::from_millis(backoff.min(self.config.max_retry_backoff_ms.max(1)))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum DatabaseRequest {
     WriteEvents {
@@ -208,6 +370,8 @@ pub enum DatabaseRequest {
         tx_hash: TxHash,
         blob_tx: BlobTransaction,
         prover_request: OrderbookProverRequest,
+        /// Client identifiers of the orders created by this action
+        order_tags: HashMap<OrderId, OrderTag>,
         context: Context,
     },
 }
@@ -225,11 +389,22 @@ module_bus_client! {
 
 pub struct DatabaseModuleCtx {
     pub pool: PgPool,
+    /// Pool of the query endpoints, reading from the replica when there is one
+    pub read_pool: Arc<ReadPool>,
     pub user_service: Arc<RwLock<UserService>>,
     pub asset_service: Arc<RwLock<AssetService>>,
-    pub client: Arc<NodeApiHttpClient>,
+    pub client: Arc<NodeClient>,
     pub no_blobs: bool,
     pub metrics: DatabaseMetrics,
+    /// Snapshots of the light state, whose WAL is appended once events are persisted
+    pub snapshots: Option<Arc<SnapshotStore>>,
+    pub blob_batch: BlobBatchConfig,
+    pub blob_outbox: Arc<BlobOutbox>,
+    pub worker_queues: Arc<WorkerQueues>,
+    pub counters: Arc<BusinessCounters>,
+    pub event_retention: EventRetentionConfig,
+    /// Notifies the feeds of the committed writes
+    pub notifier: Arc<dyn Notifier>,
 }
 
 /// Service for database operations that can be called directly
@@ -246,20 +421,33 @@ impl DatabaseService {
     /// Write events to the database and optionally send blob transaction
     #[cfg_attr(
         feature = "instrumentation",
-        tracing::instrument(skip(self, user, tx_hash, blob_tx, prover_request, context))
+        tracing::instrument(
+            skip(self, user, tx_hash, blob_tx, prover_request, order_tags, context),
+            fields(commit_id = prover_request.nonce, tx_hash = %tx_hash)
+        )
     )]
     pub async fn write_events(
         &self,
         user: UserInfo,
         tx_hash: TxHash,
         blob_tx: BlobTransaction,
-        prover_request: OrderbookProverRequest,
+        mut prover_request: OrderbookProverRequest,
+        order_tags: HashMap<OrderId, OrderTag>,
         context: Context,
     ) -> Result<()> {
         tracing::Span::current().set_parent(context);
+        // Stored with the request, for the prover to continue the trace of the action
+        prover_request.trace_context =
+            crate::telemetry::inject_context(&tracing::Span::current().context());
         log_error!(
-            self.write_events_internal(&user, tx_hash.clone(), &blob_tx, &prover_request)
-                .await,
+            self.write_events_internal(
+                &user,
+                tx_hash.clone(),
+                &blob_tx,
+                &prover_request,
+                &order_tags
+            )
+            .await,
             "Failed to write events"
         )?;
         Ok(())
@@ -272,13 +460,23 @@ impl DatabaseService {
         tx_hash: TxHash,
         blob_tx: &BlobTransaction,
         prover_request: &OrderbookProverRequest,
+        order_tags: &HashMap<OrderId, OrderTag>,
     ) -> Result<()> {
         let write_events_start = Instant::now();
         let user = &user_info.user;
         debug!("Writing events for user {user} with tx hash {tx_hash:#}");
-        use crate::services::asset_service::MarketStatus;
 
         let mut reload_instrument_map = false;
+        // The trades of a liquidation are taken by the liquidated user, not by the operator
+        let taker = prover_request
+            .events
+            .iter()
+            .find_map(|event| match event {
+                OrderbookEvent::Liquidation { user, .. } => Some(user),
+                _ => None,
+            })
+            .unwrap_or(user);
+        let mut liquidated_symbols: BTreeSet<String> = BTreeSet::new();
 
         let tx_begin_start = Instant::now();
         let mut tx = log_error!(
@@ -316,6 +514,7 @@ impl DatabaseService {
 
         debug!("Created commit with id {}", commit_id);
 
+        let mut event_rows = EventRows::default();
         for event in prover_request.events.clone() {
             let event_start = Instant::now();
             match event {
@@ -373,17 +572,7 @@ impl DatabaseService {
                         user, asset, amount
                     );
 
-                    log_error!(
-                        sqlx::query("INSERT INTO balance_events (commit_id, identity, asset_id, total, kind) VALUES ($1, $2, $3, $4, 'transfer')")
-                        .bind(commit_id)
-                        .bind(user)
-                        .bind(asset.asset_id)
-                        .bind(amount as i64)
-                        .execute(&mut *tx)
-                        .instrument(tracing::info_span!("create_balance_event"))
-                        .await,
-                        "Failed to create balance event"
-                    )?;
+                    event_rows.push_balance_event(user, asset.asset_id, amount as i64);
                     self.ctx.metrics.record(
                         &self.ctx.metrics.balance_update_duration,
                         balance_start,
@@ -408,41 +597,14 @@ impl DatabaseService {
                         "Creating order for user {} with instrument {:?} and order {:?}",
                         user, instrument, order
                     );
+                    let order_tag = order_tags.get(&order.order_id).cloned().unwrap_or_default();
 
-                    log_error!(
-                        sqlx::query("INSERT INTO orders (order_id, instrument_id, identity, side, type, price, qty)
-                                     VALUES ($1, $2, $3, $4, $5, $6, $7)")
-                        .bind(order.order_id.clone())
-                        .bind(instrument.instrument_id)
-                        .bind(user.clone())
-                        .bind(order.order_side.clone())
-                        .bind(order.order_type.clone())
-                        .bind(order.price.map(|p| p as i64))
-                        .bind(order.quantity as i64)
-                        .execute(&mut *tx)
-                        .instrument(tracing::info_span!("create_order"))
-                        .await,
-                        "Failed to create order"
-                    )?;
-
-                    log_error!(
-                        sqlx::query(
-                            "INSERT INTO order_events (commit_id, order_id, identity, instrument_id, side, type, price, qty, qty_filled, status)
-                            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 0, 'open')"
-                        )
-                        .bind(commit_id)
-                        .bind(order.order_id)
-                        .bind(user)
-                        .bind(instrument.instrument_id)
-                        .bind(order.order_side)
-                        .bind(order.order_type)
-                        .bind(order.price.map(|p| p as i64))
-                        .bind(order.quantity as i64)
-                        .execute(&mut *tx)
-                        .instrument(tracing::info_span!("create_order_event"))
-                        .await,
-                        "Failed to create order event"
-                    )?;
+                    event_rows.push_order_event(
+                        order.order_id.clone(),
+                        "open",
+                        Some(order.quantity as i64),
+                    );
+                    event_rows.push_order(order, instrument.instrument_id, order_tag);
                     self.ctx.metrics.record(
                         &self.ctx.metrics.order_create_duration,
                         order_create_start,
@@ -461,19 +623,7 @@ impl DatabaseService {
                     );
                     let order_cancel_start = Instant::now();
 
-                    log_error!(
-                        sqlx::query(
-                            "
-                            INSERT INTO order_events (commit_id, order_id, identity, instrument_id, side, type, price, qty, qty_filled, status)
-                            VALUES select $1, order_id, identity, instrument_id, side, type, price, qty, qty_filled, 'cancelled' from orders where order_id = $2"
-                        )
-                        .bind(commit_id)
-                        .bind(order_id)
-                        .execute(&mut *tx)
-                        .instrument(tracing::info_span!("create_order_event"))
-                        .await,
-                        "Failed to create order event"
-                    )?;
+                    event_rows.push_order_event(order_id, "cancelled", None);
                     self.ctx.metrics.record(
                         &self.ctx.metrics.order_cancel_duration,
                         order_cancel_start,
@@ -503,44 +653,13 @@ impl DatabaseService {
                             anyhow::anyhow!("Instrument not found: {}/{}", pair.0, pair.1)
                         })?;
 
-                    // TODO:have more data in the event to avoid the SELECT here
-                    log_error!(
-                        sqlx::query(
-                            "
-                            INSERT INTO order_events (commit_id, order_id, identity, instrument_id, side, type, price, qty, qty_filled, status)
-                            SELECT $1, order_id, identity, instrument_id, side, type, price, qty, qty, 'filled' FROM orders WHERE order_id = $2
-                            "
-                        )
-                        .bind(commit_id)
-                        .bind(order_id.clone())
-                        .execute(&mut *tx)
-                        .instrument(tracing::info_span!("create_order_event"))
-                        .await,
-                        "Failed to create order event"
-                    )?;
-
-                    // TODO:have more data in the event to avoid the SELECT here
-                    log_error!(
-                        sqlx::query(
-                            "
-                            WITH maker_order AS (
-                                SELECT * FROM orders WHERE order_id = $2
-                            )
-                            INSERT INTO trade_events (commit_id, maker_order_id, taker_order_id, instrument_id, price, qty, side, maker_identity, taker_identity)
-                            SELECT $1, $2, $3, $4, maker_order.price, maker_order.qty, get_other_side(maker_order.side), maker_order.identity, $5
-                            FROM maker_order
-                            "
-                        )
-                        .bind(commit_id)
-                        .bind(order_id)
-                        .bind(taker_order_id)
-                        .bind(instrument.instrument_id)
-                        .bind(user)
-                        .execute(&mut *tx)
-                        .instrument(tracing::info_span!("insert_trade_event"))
-                        .await,
-                        "Failed to insert trade event"
-                    )?;
+                    event_rows.push_order_event(order_id.clone(), "filled", Some(0));
+                    event_rows.push_trade_event(
+                        order_id,
+                        taker_order_id,
+                        instrument.instrument_id,
+                        None,
+                    );
                     self.ctx.metrics.record(
                         &self.ctx.metrics.order_execute_duration,
                         order_execute_start,
@@ -572,45 +691,17 @@ impl DatabaseService {
                             anyhow::anyhow!("Instrument not found: {}/{}", pair.0, pair.1)
                         })?;
 
-                    log_error!(
-                        sqlx::query(
-                            "
-                            INSERT INTO order_events (commit_id, order_id, identity, instrument_id, side, type, price, qty, qty_filled, status)
-                            SELECT $1, order_id, identity, instrument_id, side, type, price, qty, qty - $3, 'partially_filled' FROM orders WHERE order_id = $2
-                            "
-                        )
-                        .bind(commit_id)
-                        .bind(order_id.clone())
-                        .bind(remaining_quantity as i64)
-                        .execute(&mut *tx)
-                        .instrument(tracing::info_span!("create_order_event"))
-                        .await,
-                        "Failed to create order event"
-                    )?;
-
-                    // The trade insert query must be done before the order update query to be able to compute the executed quantity
-                    log_error!(
-                        sqlx::query(
-                            "
-                            WITH maker_order AS (
-                                SELECT * FROM orders WHERE order_id = $2
-                            )
-                            INSERT INTO trade_events (commit_id, maker_order_id, taker_order_id, instrument_id, price, qty, side, maker_identity, taker_identity)
-                            SELECT $1, $2, $3, $4, maker_order.price, $5, get_other_side(maker_order.side), maker_order.identity, $6
-                            FROM maker_order
-                            "
-                        )
-                        .bind(commit_id)
-                        .bind(order_id.clone())
-                        .bind(taker_order_id)
-                        .bind(instrument.instrument_id)
-                        .bind(executed_quantity as i64)
-                        .bind(user)
-                        .execute(&mut *tx)
-                        .instrument(tracing::info_span!("insert_trade_event"))
-                        .await,
-                        "Failed to insert trade event"
-                    )?;
+                    event_rows.push_order_event(
+                        order_id.clone(),
+                        "partially_filled",
+                        Some(remaining_quantity as i64),
+                    );
+                    event_rows.push_trade_event(
+                        order_id,
+                        taker_order_id,
+                        instrument.instrument_id,
+                        Some(executed_quantity as i64),
+                    );
                     self.ctx.metrics.record(
                         &self.ctx.metrics.order_update_duration,
                         order_update_start,
@@ -627,6 +718,7 @@ impl DatabaseService {
                     salt,
                     nonce,
                     session_keys,
+                    session_key_scopes,
                 } => {
                     let user_ops_start = Instant::now();
                     let fetched_user_id = self.ctx.user_service.read().await.get_nonce(&user).await;
@@ -634,14 +726,18 @@ impl DatabaseService {
                     if let Err(e) = fetched_user_id {
                         if e.0 == StatusCode::NOT_FOUND {
                             info!("Creating user {}", user);
+                            // New identities can only contain the separator as sub-accounts
+                            let sub_account = split_sub_account(&user);
                             log_error!(
                                 sqlx::query(
-                                    "INSERT INTO users (commit_id, identity, salt, nonce) VALUES ($1, $2, $3, $4) ON CONFLICT (identity) DO UPDATE SET nonce = EXCLUDED.nonce"
+                                    "INSERT INTO users (commit_id, identity, salt, nonce, parent_identity, sub_account) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (identity) DO UPDATE SET nonce = EXCLUDED.nonce"
                                 )
                                 .bind(commit_id)
                                 .bind(user.clone())
                                 .bind(salt)
                                 .bind(nonce as i64)
+                                .bind(sub_account.map(|(parent, _)| parent))
+                                .bind(sub_account.map(|(_, name)| name))
                                 .execute(&mut *tx)
                                 .await,
                                 "Failed to create user"
@@ -654,10 +750,11 @@ impl DatabaseService {
                     debug!("Setting user session keys for user {}", user);
 
                     log_error!(
-                        sqlx::query("INSERT INTO user_session_keys (commit_id, identity, session_keys) VALUES ($1, $2, $3)")
+                        sqlx::query("INSERT INTO user_session_keys (commit_id, identity, session_keys, session_key_scopes) VALUES ($1, $2, $3, $4)")
                         .bind(commit_id)
                         .bind(user)
                         .bind(session_keys)
+                        .bind(Json(session_key_scopes))
                         .execute(&mut *tx)
                         .instrument(tracing::info_span!("create_user_session_key"))
                         .await,
@@ -677,10 +774,13 @@ impl DatabaseService {
                 OrderbookEvent::NonceIncremented { user, nonce } => {
                     debug!("Incrementing nonce for user {}", user);
                     let user_ops_start = Instant::now();
+                    // Upsert: the orderbook account has no session key and is created on its first action
                     log_error!(
-                        sqlx::query("UPDATE users SET nonce = $1 WHERE identity = $2")
-                            .bind(nonce as i64)
+                        sqlx::query("INSERT INTO users (commit_id, identity, salt, nonce) VALUES ($1, $2, $3, $4) ON CONFLICT (identity) DO UPDATE SET nonce = EXCLUDED.nonce")
+                            .bind(commit_id)
                             .bind(user.clone())
+                            .bind(user_info.salt.clone())
+                            .bind(nonce as i64)
                             .execute(&mut *tx)
                             .instrument(tracing::info_span!("increment_nonce"))
                             .await,
@@ -708,145 +808,861 @@ impl DatabaseService {
                         &[KeyValue::new("event_type", "nonce_incremented")],
                     );
                 }
-            }
-        }
-
-        let prover_insert_start = Instant::now();
-        let json_data = log_error!(
-            serde_json::to_vec(&prover_request),
-            "Failed to serialize prover request"
-        )?;
-
-        log_error!(
-            sqlx::query(
-                "INSERT INTO prover_requests (commit_id, tx_hash, request) VALUES ($1, $2, $3)"
-            )
-            .bind(commit_id)
-            .bind(tx_hash.0.clone())
-            .bind(json_data)
-            .execute(&mut *tx)
-            .instrument(tracing::info_span!("insert_prover_request"))
-            .await,
-            "Failed to insert prover request"
-        )?;
-
-        self.ctx.metrics.record(
-            &self.ctx.metrics.prover_request_insert_duration,
-            prover_insert_start,
-            &[],
-        );
-
-        let contract_events_start = Instant::now();
-        let events_data = log_error!(
-            borsh::to_vec(&prover_request.events),
-            "Failed to serialize events"
-        )?;
-
-        let user_info_data =
-            log_error!(borsh::to_vec(&user_info), "Failed to serialize user info")?;
-
-        log_error!(
-            sqlx::query(
-                "INSERT INTO contract_events (commit_id, user_info, events) VALUES ($1, $2, $3)"
-            )
-            .bind(commit_id)
-            .bind(user_info_data)
-            .bind(events_data)
-            .execute(&mut *tx)
-            .instrument(tracing::info_span!("insert_contract_events"))
-            .await,
-            "Failed to insert contract events"
-        )?;
-        self.ctx.metrics.record(
-            &self.ctx.metrics.contract_events_insert_duration,
-            contract_events_start,
-            &[],
-        );
-
-        if !self.ctx.no_blobs {
-            log_error!(
-                sqlx::query(
-                    "INSERT INTO blob_tx_outbox (commit_id, tx_hash, blob_tx) VALUES ($1, $2, $3)"
-                )
-                .bind(commit_id)
-                .bind(tx_hash.0.clone())
-                .bind(Json(blob_tx.clone()))
-                .execute(&mut *tx)
-                .instrument(tracing::info_span!("insert_blob_outbox"))
-                .await,
-                "Failed to enqueue blob transaction"
-            )?;
-        }
-
-        let commit_start = Instant::now();
-        log_error!(
-            tx.commit()
-                .instrument(tracing::info_span!("commit_transaction"))
-                .await,
-            "Failed to commit transaction"
-        )?;
-        self.ctx.metrics.record(
-            &self.ctx.metrics.transaction_commit_duration,
-            commit_start,
-            &[],
-        );
-        debug!("Committed transaction with commit id {}", commit_id);
-
-        if reload_instrument_map {
-            let notify_start = Instant::now();
-            log_error!(
-                sqlx::query("select pg_notify('instruments', 'instruments')")
-                    .execute(&self.ctx.pool)
-                    .instrument(tracing::info_span!("notify_instruments"))
-                    .await,
-                "Failed to notify 'instruments'"
-            )?;
-            self.ctx.metrics.record(
-                &self.ctx.metrics.notification_duration,
-                notify_start,
-                &[KeyValue::new("channel", "instruments")],
-            );
-            let mut asset_service = self.ctx.asset_service.write().await;
-            asset_service
-                .reload_instrument_map()
-                .instrument(tracing::info_span!("reload_instrument_map"))
-                .await
-                .map_err(|e| anyhow::anyhow!("{}", e.1))?;
-        }
-
-        // Record the total duration of write_events
-        self.ctx.metrics.record(
-            &self.ctx.metrics.write_events_duration,
-            write_events_start,
-            &[],
-        );
-
-        Ok(())
-    }
-}
-
-#[derive(Default)]
-pub struct DatabaseAggregator {
-    executed_orders: HashSet<OrderId>,
-    cancelled_orders: HashSet<OrderId>,
-    updated_orders: HashMap<OrderId, u64>,
-    trigger_notify_trades: bool,
-    trigger_notify_orders: bool,
-    symbol_book_updated: HashSet<String>,
-    pub updated_balances: HashMap<(String, i64), u64>,
-}
-
-impl DatabaseAggregator {
-    pub fn create_order(&mut self, symbol: String) {
-        self.trigger_notify_orders = true;
-        self.symbol_book_updated.insert(symbol);
-    }
-
-    pub fn cancel_order(&mut self, order_id: OrderId, symbol: String) {
-        self.cancelled_orders.insert(order_id);
-        self.trigger_notify_orders = true;
-        self.symbol_book_updated.insert(symbol);
-    }
+                OrderbookEvent::FeeTierUpdated { user, fee_tier } => {
+                    debug!("Updating fee tier for user {}", user);
+                    let user_ops_start = Instant::now();
+                    log_error!(
+                        sqlx::query("INSERT INTO user_fee_tiers (commit_id, identity, tier, maker_fee_bps, taker_fee_bps) VALUES ($1, $2, $3, $4, $5)")
+                            .bind(commit_id)
+                            .bind(user)
+                            .bind(fee_tier.tier as i16)
+                            .bind(fee_tier.maker_fee_bps as i32)
+                            .bind(fee_tier.taker_fee_bps as i32)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_user_fee_tier"))
+                            .await,
+                        "Failed to insert user fee tier"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.user_ops_duration,
+                        user_ops_start,
+                        &[KeyValue::new("operation", "fee_tier_updated")],
+                    );
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "fee_tier_updated")],
+                    );
+                }
+                OrderbookEvent::WithdrawLimitUpdated { symbol, limit } => {
+                    debug!("Updating withdraw limit of {}", symbol);
+                    log_error!(
+                        sqlx::query("INSERT INTO asset_withdraw_limits (commit_id, symbol, max_amount, window_blocks) VALUES ($1, $2, $3, $4)")
+                            .bind(commit_id)
+                            .bind(symbol)
+                            .bind(limit.as_ref().map(|limit| limit.max_amount as i64))
+                            .bind(limit.as_ref().map(|limit| limit.window_blocks as i64))
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_asset_withdraw_limit"))
+                            .await,
+                        "Failed to insert asset withdraw limit"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "withdraw_limit_updated")],
+                    );
+                }
+                OrderbookEvent::PairStatusUpdated { pair, status } => {
+                    let symbol = format!("{}/{}", pair.0, pair.1);
+                    debug!("Updating status of {} to {:?}", symbol, status);
+                    log_error!(
+                        sqlx::query("UPDATE instruments SET status = $1 WHERE symbol = $2")
+                            .bind(status)
+                            .bind(&symbol)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("update_instrument_status"))
+                            .await,
+                        "Failed to update instrument status"
+                    )?;
+                    log_error!(
+                        sqlx::query("INSERT INTO instrument_status_updates (commit_id, symbol, status) VALUES ($1, $2, $3)")
+                            .bind(commit_id)
+                            .bind(&symbol)
+                            .bind(status)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_instrument_status_update"))
+                            .await,
+                        "Failed to insert instrument status update"
+                    )?;
+                    reload_instrument_map = true;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "pair_status_updated")],
+                    );
+                }
+                OrderbookEvent::CircuitBreakerUpdated { pair, config } => {
+                    let symbol = format!("{}/{}", pair.0, pair.1);
+                    debug!("Updating circuit breaker of {} to {:?}", symbol, config);
+                    log_error!(
+                        sqlx::query("INSERT INTO circuit_breaker_states (commit_id, symbol, max_move_bps, window_blocks, halt_blocks) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (symbol, commit_id) DO UPDATE SET max_move_bps = EXCLUDED.max_move_bps, window_blocks = EXCLUDED.window_blocks, halt_blocks = EXCLUDED.halt_blocks, reference_price = NULL, window_start = 0, halted_until = 0")
+                            .bind(commit_id)
+                            .bind(&symbol)
+                            .bind(config.as_ref().map(|config| config.max_move_bps as i64))
+                            .bind(config.as_ref().map(|config| config.window_blocks as i64))
+                            .bind(config.as_ref().map(|config| config.halt_blocks as i64))
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_circuit_breaker_state"))
+                            .await,
+                        "Failed to insert circuit breaker state"
+                    )?;
+                    // Setting a circuit breaker lifts a halt in progress
+                    log_error!(
+                        sqlx::query(
+                            "UPDATE instruments SET halted_until_block = 0 WHERE symbol = $1"
+                        )
+                        .bind(&symbol)
+                        .execute(&mut *tx)
+                        .instrument(tracing::info_span!("update_instrument_halt"))
+                        .await,
+                        "Failed to update instrument halt"
+                    )?;
+                    reload_instrument_map = true;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "circuit_breaker_updated")],
+                    );
+                }
+                OrderbookEvent::PriceWindowStarted {
+                    pair,
+                    reference_price,
+                    block_height,
+                } => {
+                    let symbol = format!("{}/{}", pair.0, pair.1);
+                    log_error!(
+                        sqlx::query("INSERT INTO circuit_breaker_states (commit_id, symbol, max_move_bps, window_blocks, halt_blocks, reference_price, window_start, halted_until) SELECT $1, symbol, max_move_bps, window_blocks, halt_blocks, $3, $4, halted_until FROM circuit_breaker_states WHERE symbol = $2 ORDER BY commit_id DESC LIMIT 1 ON CONFLICT (symbol, commit_id) DO UPDATE SET reference_price = EXCLUDED.reference_price, window_start = EXCLUDED.window_start")
+                            .bind(commit_id)
+                            .bind(&symbol)
+                            .bind(*reference_price as i64)
+                            .bind(*block_height as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_circuit_breaker_window"))
+                            .await,
+                        "Failed to insert circuit breaker window"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "price_window_started")],
+                    );
+                }
+                OrderbookEvent::CircuitBreakerTripped {
+                    pair,
+                    reference_price,
+                    price,
+                    block_height,
+                    halted_until,
+                } => {
+                    let symbol = format!("{}/{}", pair.0, pair.1);
+                    tracing::warn!(
+                        "Circuit breaker of {} tripped at block {}: price moved from {} to {}, halted until block {}",
+                        symbol, block_height, reference_price, price, halted_until
+                    );
+                    log_error!(
+                        sqlx::query("INSERT INTO circuit_breaker_states (commit_id, symbol, max_move_bps, window_blocks, halt_blocks, reference_price, window_start, halted_until) SELECT $1, symbol, max_move_bps, window_blocks, halt_blocks, $3, $4, $5 FROM circuit_breaker_states WHERE symbol = $2 ORDER BY commit_id DESC LIMIT 1 ON CONFLICT (symbol, commit_id) DO UPDATE SET reference_price = EXCLUDED.reference_price, window_start = EXCLUDED.window_start, halted_until = EXCLUDED.halted_until")
+                            .bind(commit_id)
+                            .bind(&symbol)
+                            .bind(*price as i64)
+                            .bind(*block_height as i64)
+                            .bind(*halted_until as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_circuit_breaker_trip"))
+                            .await,
+                        "Failed to insert circuit breaker trip"
+                    )?;
+                    log_error!(
+                        sqlx::query(
+                            "UPDATE instruments SET halted_until_block = $1 WHERE symbol = $2"
+                        )
+                        .bind(*halted_until as i64)
+                        .bind(&symbol)
+                        .execute(&mut *tx)
+                        .instrument(tracing::info_span!("update_instrument_halt"))
+                        .await,
+                        "Failed to update instrument halt"
+                    )?;
+                    reload_instrument_map = true;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "circuit_breaker_tripped")],
+                    );
+                }
+                OrderbookEvent::PerpMarketCreated { pair, config } => {
+                    let symbol = format!("{}/{}", pair.0, pair.1);
+                    debug!("Creating perp market {} with {:?}", symbol, config);
+                    log_error!(
+                        sqlx::query("INSERT INTO perp_markets (commit_id, symbol, initial_margin_bps, maintenance_margin_bps, liquidation_penalty_bps) VALUES ($1, $2, $3, $4, $5)")
+                            .bind(commit_id)
+                            .bind(&symbol)
+                            .bind(config.initial_margin_bps as i64)
+                            .bind(config.maintenance_margin_bps as i64)
+                            .bind(config.liquidation_penalty_bps as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_perp_market"))
+                            .await,
+                        "Failed to insert perp market"
+                    )?;
+                    log_error!(
+                        sqlx::query("UPDATE instruments SET kind = 'perp' WHERE symbol = $1")
+                            .bind(&symbol)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("update_instrument_kind"))
+                            .await,
+                        "Failed to update instrument kind"
+                    )?;
+                    reload_instrument_map = true;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "perp_market_created")],
+                    );
+                }
+                OrderbookEvent::FundingRateAccrued {
+                    pair,
+                    funding_index,
+                } => {
+                    let symbol = format!("{}/{}", pair.0, pair.1);
+                    log_error!(
+                        sqlx::query("INSERT INTO perp_markets (commit_id, symbol, initial_margin_bps, maintenance_margin_bps, liquidation_penalty_bps, funding_index, index_price) SELECT $1, symbol, initial_margin_bps, maintenance_margin_bps, liquidation_penalty_bps, $3, index_price FROM perp_markets WHERE symbol = $2 ORDER BY commit_id DESC LIMIT 1 ON CONFLICT (symbol, commit_id) DO UPDATE SET funding_index = EXCLUDED.funding_index")
+                            .bind(commit_id)
+                            .bind(&symbol)
+                            .bind(*funding_index)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_perp_funding_index"))
+                            .await,
+                        "Failed to insert perp funding index"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "funding_rate_accrued")],
+                    );
+                }
+                OrderbookEvent::PositionUpdated { user, position } => {
+                    debug!("Updating position of user {}", user);
+                    let user_ops_start = Instant::now();
+                    log_error!(
+                        sqlx::query("INSERT INTO user_positions (commit_id, identity, symbol, size, entry_price, margin, funding_index) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT (identity, symbol, commit_id) DO UPDATE SET size = EXCLUDED.size, entry_price = EXCLUDED.entry_price, margin = EXCLUDED.margin, funding_index = EXCLUDED.funding_index")
+                            .bind(commit_id)
+                            .bind(user)
+                            .bind(format!("{}/{}", position.pair.0, position.pair.1))
+                            .bind(position.size)
+                            .bind(position.entry_price as i64)
+                            .bind(position.margin as i64)
+                            .bind(position.funding_index)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_user_position"))
+                            .await,
+                        "Failed to insert user position"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.user_ops_duration,
+                        user_ops_start,
+                        &[KeyValue::new("operation", "position_updated")],
+                    );
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "position_updated")],
+                    );
+                }
+                OrderbookEvent::FundingPaid { user, pair, amount } => {
+                    debug!("Recording funding paid by user {}", user);
+                    log_error!(
+                        sqlx::query("INSERT INTO funding_payments (commit_id, identity, symbol, amount) VALUES ($1, $2, $3, $4)")
+                            .bind(commit_id)
+                            .bind(user)
+                            .bind(format!("{}/{}", pair.0, pair.1))
+                            .bind(*amount)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_funding_payment"))
+                            .await,
+                        "Failed to insert funding payment"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "funding_paid")],
+                    );
+                }
+                OrderbookEvent::CollateralUpdated { symbol, collateral } => {
+                    debug!("Updating collateral of {} to {:?}", symbol, collateral);
+                    // The price is kept while the collateral is valued in the same quote
+                    log_error!(
+                        sqlx::query("INSERT INTO asset_collaterals (commit_id, symbol, quote, weight_bps, price) VALUES ($1, $2, $3, $4, COALESCE((SELECT price FROM (SELECT quote, price FROM asset_collaterals WHERE symbol = $2 ORDER BY commit_id DESC LIMIT 1) latest WHERE quote = $3), 0)) ON CONFLICT (symbol, commit_id) DO UPDATE SET quote = EXCLUDED.quote, weight_bps = EXCLUDED.weight_bps, price = EXCLUDED.price")
+                            .bind(commit_id)
+                            .bind(symbol)
+                            .bind(collateral.as_ref().map(|config| config.quote.clone()))
+                            .bind(collateral.as_ref().map(|config| config.weight_bps as i64))
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_asset_collateral"))
+                            .await,
+                        "Failed to insert asset collateral"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "collateral_updated")],
+                    );
+                }
+                OrderbookEvent::IndexPriceUpdated { pair, price } => {
+                    let symbol = format!("{}/{}", pair.0, pair.1);
+                    log_error!(
+                        sqlx::query("INSERT INTO perp_markets (commit_id, symbol, initial_margin_bps, maintenance_margin_bps, liquidation_penalty_bps, funding_index, index_price) SELECT $1, symbol, initial_margin_bps, maintenance_margin_bps, liquidation_penalty_bps, funding_index, $3 FROM perp_markets WHERE symbol = $2 ORDER BY commit_id DESC LIMIT 1 ON CONFLICT (symbol, commit_id) DO UPDATE SET index_price = EXCLUDED.index_price")
+                            .bind(commit_id)
+                            .bind(&symbol)
+                            .bind(*price as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_perp_index_price"))
+                            .await,
+                        "Failed to insert perp index price"
+                    )?;
+                    log_error!(
+                        sqlx::query("INSERT INTO asset_collaterals (commit_id, symbol, quote, weight_bps, price) SELECT $1, symbol, quote, weight_bps, $4 FROM (SELECT symbol, quote, weight_bps FROM asset_collaterals WHERE symbol = $2 ORDER BY commit_id DESC LIMIT 1) latest WHERE quote = $3 ON CONFLICT (symbol, commit_id) DO UPDATE SET price = EXCLUDED.price")
+                            .bind(commit_id)
+                            .bind(&pair.0)
+                            .bind(&pair.1)
+                            .bind(*price as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_collateral_price"))
+                            .await,
+                        "Failed to insert collateral price"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "index_price_updated")],
+                    );
+                }
+                OrderbookEvent::DebtUpdated { user, debt } => {
+                    debug!("Updating debt of user {}", user);
+                    let user_ops_start = Instant::now();
+                    log_error!(
+                        sqlx::query("INSERT INTO user_debts (commit_id, identity, symbol, amount) VALUES ($1, $2, $3, $4) ON CONFLICT (identity, symbol, commit_id) DO UPDATE SET amount = EXCLUDED.amount")
+                            .bind(commit_id)
+                            .bind(user)
+                            .bind(&debt.symbol)
+                            .bind(debt.amount as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_user_debt"))
+                            .await,
+                        "Failed to insert user debt"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.user_ops_duration,
+                        user_ops_start,
+                        &[KeyValue::new("operation", "debt_updated")],
+                    );
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "debt_updated")],
+                    );
+                }
+                OrderbookEvent::MarginCalled { user, account } => {
+                    tracing::warn!(
+                        "Margin call for user {} on {}: equity {} under maintenance margin {}",
+                        user,
+                        account.quote,
+                        account.equity,
+                        account.maintenance_margin
+                    );
+                    log_error!(
+                        sqlx::query("INSERT INTO margin_calls (commit_id, identity, quote, equity, initial_margin, maintenance_margin) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (identity, quote, commit_id) DO NOTHING")
+                            .bind(commit_id)
+                            .bind(user)
+                            .bind(&account.quote)
+                            .bind(account.equity)
+                            .bind(account.initial_margin as i64)
+                            .bind(account.maintenance_margin as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_margin_call"))
+                            .await,
+                        "Failed to insert margin call"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "margin_called")],
+                    );
+                }
+                OrderbookEvent::Liquidation {
+                    user: liquidated,
+                    pair,
+                    size,
+                    account,
+                } => {
+                    let symbol = format!("{}/{}", pair.0, pair.1);
+                    info!(
+                        "Liquidating the {} position of {} on {}: equity {} under maintenance margin {}",
+                        size, liquidated, symbol, account.equity, account.maintenance_margin
+                    );
+                    log_error!(
+                        sqlx::query("INSERT INTO liquidations (commit_id, identity, symbol, size, equity, maintenance_margin) VALUES ($1, $2, $3, $4, $5, $6)")
+                            .bind(commit_id)
+                            .bind(&liquidated)
+                            .bind(&symbol)
+                            .bind(size)
+                            .bind(account.equity)
+                            .bind(account.maintenance_margin as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_liquidation"))
+                            .await,
+                        "Failed to insert liquidation"
+                    )?;
+                    liquidated_symbols.insert(symbol);
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "liquidation")],
+                    );
+                }
+                OrderbookEvent::InsuranceFundUpdated {
+                    user: liquidated,
+                    symbol,
+                    change,
+                } => {
+                    debug!(
+                        "Insurance fund updated by {} {} on the liquidation of {}",
+                        change, symbol, liquidated
+                    );
+                    log_error!(
+                        sqlx::query("INSERT INTO insurance_fund_events (commit_id, identity, symbol, change) VALUES ($1, $2, $3, $4)")
+                            .bind(commit_id)
+                            .bind(&liquidated)
+                            .bind(&symbol)
+                            .bind(change)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_insurance_fund_event"))
+                            .await,
+                        "Failed to insert insurance fund event"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "insurance_fund_updated")],
+                    );
+                }
+                OrderbookEvent::Transferred {
+                    from,
+                    to,
+                    symbol,
+                    amount,
+                } => {
+                    debug!("Transfer of {} {} from {} to {}", amount, symbol, from, to);
+                    log_error!(
+                        sqlx::query("INSERT INTO transfers (commit_id, from_identity, to_identity, symbol, amount) VALUES ($1, $2, $3, $4, $5)")
+                            .bind(commit_id)
+                            .bind(&from)
+                            .bind(&to)
+                            .bind(&symbol)
+                            .bind(amount as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_transfer"))
+                            .await,
+                        "Failed to insert transfer"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "transferred")],
+                    );
+                }
+                OrderbookEvent::DustSwept {
+                    user,
+                    quote,
+                    symbols,
+                    proceeds,
+                } => {
+                    debug!(
+                        "Dust of {:?} of {} swept for {} {}",
+                        symbols, user, proceeds, quote
+                    );
+                    log_error!(
+                        sqlx::query("INSERT INTO dust_sweeps (commit_id, identity, quote, symbols, proceeds) VALUES ($1, $2, $3, $4, $5)")
+                            .bind(commit_id)
+                            .bind(&user)
+                            .bind(&quote)
+                            .bind(&symbols)
+                            .bind(proceeds as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_dust_sweep"))
+                            .await,
+                        "Failed to insert dust sweep"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "dust_swept")],
+                    );
+                }
+                OrderbookEvent::WithdrawalRecorded { user, window } => {
+                    debug!("Recording withdrawal window for user {}", user);
+                    let user_ops_start = Instant::now();
+                    log_error!(
+                        sqlx::query("INSERT INTO user_withdrawal_windows (commit_id, identity, symbol, window_start, withdrawn) VALUES ($1, $2, $3, $4, $5)")
+                            .bind(commit_id)
+                            .bind(user)
+                            .bind(&window.symbol)
+                            .bind(window.window_start as i64)
+                            .bind(window.withdrawn as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_user_withdrawal_window"))
+                            .await,
+                        "Failed to insert user withdrawal window"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.user_ops_duration,
+                        user_ops_start,
+                        &[KeyValue::new("operation", "withdrawal_recorded")],
+                    );
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "withdrawal_recorded")],
+                    );
+                }
+                OrderbookEvent::BridgePauseUpdated { paused } => {
+                    debug!("Updating bridge pause to {}", paused);
+                    log_error!(
+                        sqlx::query(
+                            "INSERT INTO bridge_pauses (commit_id, paused) VALUES ($1, $2)"
+                        )
+                        .bind(commit_id)
+                        .bind(paused)
+                        .execute(&mut *tx)
+                        .instrument(tracing::info_span!("insert_bridge_pause"))
+                        .await,
+                        "Failed to insert bridge pause"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "bridge_pause_updated")],
+                    );
+                }
+                OrderbookEvent::WithdrawDelayUpdated { symbol, delay } => {
+                    debug!("Updating withdraw delay of {}", symbol);
+                    log_error!(
+                        sqlx::query("INSERT INTO asset_withdraw_delays (commit_id, symbol, threshold, delay_blocks, requires_approval) VALUES ($1, $2, $3, $4, $5)")
+                            .bind(commit_id)
+                            .bind(symbol)
+                            .bind(delay.as_ref().map(|delay| delay.threshold as i64))
+                            .bind(delay.as_ref().map(|delay| delay.delay_blocks as i64))
+                            .bind(delay.as_ref().map(|delay| delay.requires_approval))
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_asset_withdraw_delay"))
+                            .await,
+                        "Failed to insert asset withdraw delay"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "withdraw_delay_updated")],
+                    );
+                }
+                OrderbookEvent::WithdrawalQueued { user, withdrawal } => {
+                    debug!("Queueing withdrawal {} for user {}", withdrawal.id, user);
+                    let user_ops_start = Instant::now();
+                    log_error!(
+                        sqlx::query("INSERT INTO user_pending_withdrawals (commit_id, identity, withdrawal_id, symbol, amount, network, address, available_at, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)")
+                            .bind(commit_id)
+                            .bind(user)
+                            .bind(withdrawal.id as i64)
+                            .bind(&withdrawal.symbol)
+                            .bind(withdrawal.amount as i64)
+                            .bind(&withdrawal.destination.network)
+                            .bind(&withdrawal.destination.address)
+                            .bind(withdrawal.available_at as i64)
+                            .bind(if withdrawal.approved { "approved" } else { "queued" })
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_user_pending_withdrawal"))
+                            .await,
+                        "Failed to insert user pending withdrawal"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.user_ops_duration,
+                        user_ops_start,
+                        &[KeyValue::new("operation", "withdrawal_queued")],
+                    );
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "withdrawal_queued")],
+                    );
+                }
+                OrderbookEvent::WithdrawalReviewed {
+                    user,
+                    withdrawal_id,
+                    approved,
+                } => {
+                    debug!("Reviewing withdrawal {} of user {}", withdrawal_id, user);
+                    let user_ops_start = Instant::now();
+                    // The review does not carry the withdrawal, that is copied from its latest line
+                    log_error!(
+                        sqlx::query("INSERT INTO user_pending_withdrawals (commit_id, identity, withdrawal_id, symbol, amount, network, address, available_at, status) SELECT $1, identity, withdrawal_id, symbol, amount, network, address, available_at, $4 FROM user_pending_withdrawals WHERE identity = $2 AND withdrawal_id = $3 ORDER BY commit_id DESC LIMIT 1 ON CONFLICT (identity, withdrawal_id, commit_id) DO UPDATE SET status = EXCLUDED.status")
+                            .bind(commit_id)
+                            .bind(user)
+                            .bind(*withdrawal_id as i64)
+                            .bind(if *approved { "approved" } else { "rejected" })
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_user_pending_withdrawal"))
+                            .await,
+                        "Failed to insert user pending withdrawal"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.user_ops_duration,
+                        user_ops_start,
+                        &[KeyValue::new("operation", "withdrawal_reviewed")],
+                    );
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "withdrawal_reviewed")],
+                    );
+                }
+                OrderbookEvent::WithdrawalReleased { user, withdrawal } => {
+                    debug!("Releasing withdrawal {} of user {}", withdrawal.id, user);
+                    let user_ops_start = Instant::now();
+                    log_error!(
+                        sqlx::query("INSERT INTO user_pending_withdrawals (commit_id, identity, withdrawal_id, symbol, amount, network, address, available_at, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'released') ON CONFLICT (identity, withdrawal_id, commit_id) DO UPDATE SET status = EXCLUDED.status")
+                            .bind(commit_id)
+                            .bind(user)
+                            .bind(withdrawal.id as i64)
+                            .bind(&withdrawal.symbol)
+                            .bind(withdrawal.amount as i64)
+                            .bind(&withdrawal.destination.network)
+                            .bind(&withdrawal.destination.address)
+                            .bind(withdrawal.available_at as i64)
+                            .execute(&mut *tx)
+                            .instrument(tracing::info_span!("insert_user_pending_withdrawal"))
+                            .await,
+                        "Failed to insert user pending withdrawal"
+                    )?;
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.user_ops_duration,
+                        user_ops_start,
+                        &[KeyValue::new("operation", "withdrawal_released")],
+                    );
+                    self.ctx.metrics.record(
+                        &self.ctx.metrics.event_processing_duration,
+                        event_start,
+                        &[KeyValue::new("event_type", "withdrawal_released")],
+                    );
+                }
+            }
+        }
+
+        let event_rows_start = Instant::now();
+        event_rows.insert(&mut *tx, commit_id, taker).await?;
+        self.ctx.metrics.record(
+            &self.ctx.metrics.event_rows_insert_duration,
+            event_rows_start,
+            &[],
+        );
+        let counter_totals = BusinessCounters::record_commit(&mut *tx, commit_id).await?;
+
+        let prover_insert_start = Instant::now();
+        let json_data = log_error!(
+            serde_json::to_vec(&prover_request),
+            "Failed to serialize prover request"
+        )?;
+
+        log_error!(
+            sqlx::query(
+                "INSERT INTO prover_requests (commit_id, tx_hash, request) VALUES ($1, $2, $3)"
+            )
+            .bind(commit_id)
+            .bind(tx_hash.0.clone())
+            .bind(json_data)
+            .execute(&mut *tx)
+            .instrument(tracing::info_span!("insert_prover_request"))
+            .await,
+            "Failed to insert prover request"
+        )?;
+
+        self.ctx.metrics.record(
+            &self.ctx.metrics.prover_request_insert_duration,
+            prover_insert_start,
+            &[],
+        );
+
+        let contract_events_start = Instant::now();
+        let events_data = log_error!(
+            borsh::to_vec(&prover_request.events),
+            "Failed to serialize events"
+        )?;
+
+        let user_info_data =
+            log_error!(borsh::to_vec(&user_info), "Failed to serialize user info")?;
+
+        log_error!(
+            sqlx::query(
+                "INSERT INTO contract_events (commit_id, user_info, events) VALUES ($1, $2, $3)"
+            )
+            .bind(commit_id)
+            .bind(user_info_data)
+            .bind(events_data)
+            .execute(&mut *tx)
+            .instrument(tracing::info_span!("insert_contract_events"))
+            .await,
+            "Failed to insert contract events"
+        )?;
+        self.ctx.metrics.record(
+            &self.ctx.metrics.contract_events_insert_duration,
+            contract_events_start,
+            &[],
+        );
+
+        if !self.ctx.no_blobs {
+            log_error!(
+                sqlx::query(
+                    "INSERT INTO blob_tx_outbox (commit_id, tx_hash, blob_tx) VALUES ($1, $2, $3)"
+                )
+                .bind(commit_id)
+                .bind(tx_hash.0.clone())
+                .bind(Json(blob_tx.clone()))
+                .execute(&mut *tx)
+                .instrument(tracing::info_span!("insert_blob_outbox"))
+                .await,
+                "Failed to enqueue blob transaction"
+            )?;
+        }
+
+        let commit_start = Instant::now();
+        log_error!(
+            tx.commit()
+                .instrument(tracing::info_span!("commit_transaction"))
+                .await,
+            "Failed to commit transaction"
+        )?;
+        self.ctx.metrics.record(
+            &self.ctx.metrics.transaction_commit_duration,
+            commit_start,
+            &[],
+        );
+        self.ctx.counters.update(counter_totals);
+        if !self.ctx.no_blobs {
+            self.ctx.blob_outbox.on_enqueued();
+        }
+        debug!("Committed transaction with commit id {}", commit_id);
+
+        if let Some(snapshots) = &self.ctx.snapshots {
+            // A missing record only prevents the snapshot from moving forward until the next boot
+            _ = log_error!(
+                snapshots.append(commit_id, user_info, &prover_request.events),
+                "Failed to append events to the state WAL"
+            );
+        }
+
+        for symbol in liquidated_symbols {
+            notify(
+                &*self.ctx.notifier,
+                &self.ctx.metrics,
+                Notification::Liquidations { symbol },
+            )
+            .await?;
+        }
+
+        if reload_instrument_map {
+            notify(
+                &*self.ctx.notifier,
+                &self.ctx.metrics,
+                Notification::Instruments,
+            )
+            .await?;
+            let mut asset_service = self.ctx.asset_service.write().await;
+            asset_service
+                .reload_instrument_map()
+                .instrument(tracing::info_span!("reload_instrument_map"))
+                .await
+                .map_err(|e| anyhow::anyhow!("{}", e.1))?;
+        }
+
+        // Record the total duration of write_events
+        self.ctx.metrics.record(
+            &self.ctx.metrics.write_events_duration,
+            write_events_start,
+            &[],
+        );
+
+        Ok(())
+    }
+}
+
+impl Storage for DatabaseService {
+    fn write_events(&self, request: DatabaseRequest) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            match request {
+                DatabaseRequest::WriteEvents {
+                    user,
+                    tx_hash,
+                    blob_tx,
+                    prover_request,
+                    order_tags,
+                    context,
+                } => {
+                    DatabaseService::write_events(
+                        self,
+                        user,
+                        tx_hash,
+                        blob_tx,
+                        prover_request,
+                        order_tags,
+                        context,
+                    )
+                    .await
+                }
+            }
+        })
+    }
+
+    fn write_aggregates<'a>(
+        &'a self,
+        aggregator: &'a mut DatabaseAggregator,
+    ) -> BoxFuture<'a, Result<()>> {
+        Box::pin(aggregator.dump_to_db(&self.ctx.pool, &*self.ctx.notifier, &self.ctx.metrics))
+    }
+}
+
+/// Sends a notification once the writes it is about are committed
+async fn notify(
+    notifier: &dyn Notifier,
+    metrics: &DatabaseMetrics,
+    notification: Notification,
+) -> Result<()> {
+    let notify_start = Instant::now();
+    let channel = notification.channel();
+    log_error!(
+        notifier
+            .notify(notification)
+            .instrument(tracing::info_span!("notify", channel))
+            .await,
+        "Failed to send a notification"
+    )?;
+    metrics.record(
+        &metrics.notification_duration,
+        notify_start,
+        &[KeyValue::new("channel", channel)],
+    );
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct DatabaseAggregator {
+    executed_orders: HashSet<OrderId>,
+    cancelled_orders: HashSet<OrderId>,
+    updated_orders: HashMap<OrderId, u64>,
+    trigger_notify_trades: bool,
+    trigger_notify_orders: bool,
+    symbol_book_updated: HashSet<String>,
+    pub updated_balances: HashMap<(String, i64), u64>,
+}
+
+impl DatabaseAggregator {
+    pub fn create_order(&mut self, symbol: String) {
+        self.trigger_notify_orders = true;
+        self.symbol_book_updated.insert(symbol);
+    }
+
+    pub fn cancel_order(&mut self, order_id: OrderId, symbol: String) {
+        self.cancelled_orders.insert(order_id);
+        self.trigger_notify_orders = true;
+        self.symbol_book_updated.insert(symbol);
+    }
 
     pub fn execute_order(&mut self, order_id: OrderId, symbol: String) {
         self.updated_orders.remove(&order_id);
@@ -867,9 +1683,14 @@ impl DatabaseAggregator {
 
     #[cfg_attr(
         feature = "instrumentation",
-        tracing::instrument(skip(self, pool, metrics))
+        tracing::instrument(skip(self, pool, notifier, metrics))
     )]
-    pub async fn dump_to_db(&mut self, pool: &PgPool, metrics: &DatabaseMetrics) -> Result<()> {
+    pub async fn dump_to_db(
+        &mut self,
+        pool: &PgPool,
+        notifier: &dyn Notifier,
+        metrics: &DatabaseMetrics,
+    ) -> Result<()> {
         if self.symbol_book_updated.is_empty()
             && self.updated_orders.is_empty()
             && self.executed_orders.is_empty()
@@ -940,54 +1761,17 @@ impl DatabaseAggregator {
         // Send notifications after committing the transaction
         if self.trigger_notify_trades {
             debug!("Notifying trades");
-            let notify_start = Instant::now();
-            log_error!(
-                sqlx::query("select pg_notify('trades', 'trades')")
-                    .execute(pool)
-                    .instrument(tracing::info_span!("notify_trades"))
-                    .await,
-                "Failed to notify 'trades'"
-            )?;
-            metrics.record(
-                &metrics.notification_duration,
-                notify_start,
-                &[KeyValue::new("channel", "trades")],
-            );
+            notify(notifier, metrics, Notification::Trades).await?;
         }
 
         if self.trigger_notify_orders {
             debug!("Notifying orders");
-            let notify_start = Instant::now();
-            log_error!(
-                sqlx::query("select pg_notify('orders', 'orders')")
-                    .execute(pool)
-                    .instrument(tracing::info_span!("notify_orders"))
-                    .await,
-                "Failed to notify 'orders'"
-            )?;
-            metrics.record(
-                &metrics.notification_duration,
-                notify_start,
-                &[KeyValue::new("channel", "orders")],
-            );
+            notify(notifier, metrics, Notification::Orders).await?;
         }
 
         for symbol in self.symbol_book_updated.drain() {
             debug!("Notifying book for symbol {}", symbol);
-            let notify_start = Instant::now();
-            log_error!(
-                sqlx::query("select pg_notify('book', $1)")
-                    .bind(symbol)
-                    .execute(pool)
-                    .instrument(tracing::info_span!("notify_book"))
-                    .await,
-                "Failed to notify 'book'"
-            )?;
-            metrics.record(
-                &metrics.notification_duration,
-                notify_start,
-                &[KeyValue::new("channel", "book")],
-            );
+            notify(notifier, metrics, Notification::Book { symbol }).await?;
         }
 
         // Reset flags after sending notifications
@@ -1000,77 +1784,59 @@ impl DatabaseAggregator {
 
 pub struct DatabaseModule {
     ctx: Arc<DatabaseModuleCtx>,
+    storage: Arc<dyn Storage>,
     bus: DatabaseModuleBusClient,
-    worker_txs: Vec<mpsc::UnboundedSender<DatabaseRequest>>,
+    worker_txs: Vec<mpsc::Sender<DatabaseRequest>>,
     next_worker: std::sync::atomic::AtomicUsize,
     aggregator: DatabaseAggregator,
 }
 
+/// Writes the events of the requests queued to a worker, until its queue is dropped
+async fn run_worker(
+    ctx: Arc<DatabaseModuleCtx>,
+    storage: Arc<dyn Storage>,
+    worker_id: usize,
+    mut rx: mpsc::Receiver<DatabaseRequest>,
+) {
+    while let Some(request) = rx.recv().await {
+        // Decrement queue depth when worker starts processing
+        ctx.metrics.worker_queue_depth.add(-1, &[]);
+        ctx.worker_queues.on_dequeued();
+        ctx.metrics.workers_busy.add(1, &[]);
+
+        let result = storage.write_events(request).await;
+        ctx.metrics.workers_busy.add(-1, &[]);
+        if let Err(e) = result {
+            tracing::error!(
+                "Worker {} failed to process database request: {}",
+                worker_id,
+                e
+            );
+        }
+    }
+}
+
 impl Module for DatabaseModule {
     type Context = Arc<DatabaseModuleCtx>;
 
     async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
         let bus = DatabaseModuleBusClient::new_from_bus(bus.new_handle()).await;
-        let mut worker_txs = Vec::new();
-        let mut worker_rxs = Vec::new();
-
-        // Create 15 worker channels
-        let worker_count = 35;
-        for _ in 0..worker_count {
-            let (tx, rx) = mpsc::unbounded_channel();
-            worker_txs.push(tx);
-            worker_rxs.push(rx);
-        }
-
-        // Set the worker count metric
-        ctx.metrics.worker_count.add(worker_count as i64, &[]);
-
-        // Spawn worker tasks
-        for (worker_id, mut rx) in worker_rxs.into_iter().enumerate() {
-            let ctx = ctx.clone();
-            tokio::spawn(async move {
-                while let Some(request) = rx.recv().await {
-                    // Decrement queue depth when worker starts processing
-                    ctx.metrics.worker_queue_depth.add(-1, &[]);
 
-                    let service = DatabaseService::new(ctx.clone());
-                    let result = match request {
-                        DatabaseRequest::WriteEvents {
-                            user,
-                            tx_hash,
-                            blob_tx,
-                            prover_request,
-                            context,
-                        } => {
-                            service
-                                .write_events(
-                                    user.clone(),
-                                    tx_hash.clone(),
-                                    blob_tx.clone(),
-                                    prover_request.clone(),
-                                    context,
-                                )
-                                .await
-                        }
-                    };
-                    if let Err(e) = result {
-                        tracing::error!(
-                            "Worker {} failed to process database request: {}",
-                            worker_id,
-                            e
-                        );
-                    }
-                }
-            });
+        if !ctx.no_blobs {
+            tokio::spawn(BlobDispatcher::new(ctx.clone()).run());
         }
+        tokio::spawn(EventRetention::new(ctx.pool.clone(), ctx.event_retention.clone()).run());
 
-        Ok(DatabaseModule {
+        let mut module = DatabaseModule {
+            storage: Arc::new(DatabaseService::new(ctx.clone())),
             ctx,
             bus,
-            worker_txs,
+            worker_txs: Vec::new(),
             next_worker: AtomicUsize::new(0),
             aggregator: DatabaseAggregator::default(),
-        })
+        };
+        module.resize_workers();
+        Ok(module)
     }
 
     async fn run(&mut self) -> Result<()> {
@@ -1083,7 +1849,7 @@ impl DatabaseModule {
     pub async fn start(&mut self) -> Result<()> {
         // Handle incoming messages and dispatch to workers
 
-        let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
         interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         module_handle_messages! {
@@ -1093,24 +1859,73 @@ impl DatabaseModule {
                 log_error!(self.handle_database_request(cmd).await, "handle database request")?;
             }
              _ = interval.tick() => {
-                _ = log_error!(self.aggregator.dump_to_db(&self.ctx.pool, &self.ctx.metrics).await, "dump database aggregator to db");
-                if !self.ctx.no_blobs {
-                    log_error!(self.flush_blob_queue().await, "flush blob queue from tick")?;
-                }
+                _ = log_error!(self.storage.write_aggregates(&mut self.aggregator).await, "dump database aggregator to db");
             }
         };
         Ok(())
     }
 
+    /// Spawns or stops workers to match the count of the worker queues. Stopped workers finish
+    /// the requests already queued to them.
+    fn resize_workers(&mut self) {
+        let current = self.worker_txs.len();
+        let count = self.ctx.worker_queues.count();
+        if count == current {
+            return;
+        }
+        if count < current {
+            self.worker_txs.truncate(count);
+        }
+        for worker_id in current..count {
+            let (tx, rx) = mpsc::channel(self.ctx.worker_queues.queue_capacity);
+            self.worker_txs.push(tx);
+            tokio::spawn(run_worker(
+                self.ctx.clone(),
+                self.storage.clone(),
+                worker_id,
+                rx,
+            ));
+        }
+
+        let added = count as i64 - current as i64;
+        self.ctx.metrics.worker_count.add(added, &[]);
+        self.ctx
+            .metrics
+            .worker_queue_capacity
+            .add(added * self.ctx.worker_queues.queue_capacity as i64, &[]);
+        if current > 0 {
+            tracing::info!("Database workers resized from {current} to {count}");
+        }
+    }
+
     async fn dispatch_database_request(&mut self, request: &DatabaseRequest) -> Result<()> {
-        // Round-robin distribution to workers
-        let worker_index = self
-            .next_worker
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
-            % self.worker_txs.len();
-        self.worker_txs[worker_index].send(request.clone())?;
-        // Increment queue depth when dispatching a request
+        self.resize_workers();
+        // Counted before being sent, as a worker may pick the request up right away
+        self.ctx.worker_queues.on_queued();
         self.ctx.metrics.worker_queue_depth.add(1, &[]);
+
+        let result = self.send_to_worker(request).await;
+        if result.is_err() {
+            self.ctx.worker_queues.on_dequeued();
+            self.ctx.metrics.worker_queue_depth.add(-1, &[]);
+        }
+        result
+    }
+
+    async fn send_to_worker(&mut self, request: &DatabaseRequest) -> Result<()> {
+        // Round-robin distribution to workers, skipping those whose queue is full
+        let worker_count = self.worker_txs.len();
+        let first_worker = self.next_worker.fetch_add(1, Ordering::Relaxed) % worker_count;
+        for offset in 0..worker_count {
+            let worker_tx = &self.worker_txs[(first_worker + offset) % worker_count];
+            match worker_tx.try_send(request.clone()) {
+                Ok(()) => return Ok(()),
+                Err(mpsc::error::TrySendError::Full(_)) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        // The action was already applied, so it waits for a worker, holding back the bus
+        self.worker_txs[first_worker].send(request.clone()).await?;
         Ok(())
     }
 
@@ -1161,18 +1976,92 @@ impl DatabaseModule {
                         _ => {}
                     }
                 }
-                if !self.ctx.no_blobs {
-                    log_error!(
-                        self.flush_blob_queue().await,
-                        "flush blob queue from request"
-                    )?;
-                }
             }
         }
         Ok(())
     }
+}
+
+/// Sends the committed rows of the blob transaction outbox, in commit order
+pub struct BlobDispatcher {
+    ctx: Arc<DatabaseModuleCtx>,
+    /// Consecutive failures to send the pending blob transactions
+    failures: u32,
+    /// Pending blob transactions are not sent before then, after a failure
+    next_send_at: Option<Instant>,
+}
+
+impl BlobDispatcher {
+    pub fn new(ctx: Arc<DatabaseModuleCtx>) -> Self {
+        BlobDispatcher {
+            ctx,
+            failures: 0,
+            next_send_at: None,
+        }
+    }
+
+    /// Sends pending blob transactions as soon as rows are committed, and retries them on a tick
+    pub async fn run(mut self) {
+        let mut tick = Duration::from_secs(1);
+        if self.ctx.blob_batch.window_ms > 0 {
+            // Batched actions are sent once their window elapsed
+            tick = tick.min(Duration::from_millis(self.ctx.blob_batch.window_ms));
+        }
+        let mut interval = tokio::time::interval(tick);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                _ = self.ctx.blob_outbox.committed.notified() => {}
+                _ = interval.tick() => {}
+            }
+            // Pending blob transactions stay in the outbox, and are sent again on the next tick
+            _ = log_error!(self.flush().await, "flush blob outbox");
+        }
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self
+            .next_send_at
+            .is_some_and(|next_send_at| Instant::now() < next_send_at)
+        {
+            return Ok(());
+        }
+        let result = self.send_pending_blob_txs().await;
+
+        let pending = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM blob_tx_outbox WHERE status = 'pending'",
+        )
+        .fetch_one(&self.ctx.pool)
+        .await?;
+        self.ctx
+            .blob_outbox
+            .pending
+            .store(pending as usize, Ordering::Relaxed);
+
+        match &result {
+            Ok(()) => {
+                self.failures = 0;
+                self.next_send_at = None;
+            }
+            Err(_) => {
+                self.failures += 1;
+                let backoff = self.ctx.blob_outbox.retry_backoff(self.failures);
+                self.next_send_at = Some(Instant::now() + backoff);
+                tracing::warn!(
+                    "{pending} blob transactions pending, retrying in {backoff:?} (failure #{})",
+                    self.failures
+                );
+            }
+        }
+        result
+    }
+
+    /// Sends the pending blob transactions in commit order, stopping at the first failure
+    async fn send_pending_blob_txs(&self) -> Result<()> {
+        let max_actions = self.ctx.blob_batch.max_actions();
+        let window_secs = self.ctx.blob_batch.window_ms as f64 / 1000.0;
 
-    async fn flush_blob_queue(&mut self) -> Result<()> {
         loop {
             let last_sent_commit_id = sqlx::query_scalar::<_, Option<i64>>(
                 "SELECT MAX(commit_id) FROM blob_tx_outbox WHERE status = 'sent'",
@@ -1183,20 +2072,47 @@ impl DatabaseModule {
 
             let next_commit_id = last_sent_commit_id + 1;
 
-            let next = sqlx::query_as::<_, (i64, Json<BlobTransaction>)>(
-                "SELECT commit_id, blob_tx FROM blob_tx_outbox WHERE status = 'pending' AND commit_id = $1"
+            let pending = sqlx::query_as::<_, (i64, Json<BlobTransaction>, bool)>(
+                "SELECT commit_id, blob_tx, created_at <= now() - make_interval(secs => $3) FROM blob_tx_outbox WHERE status = 'pending' AND commit_id >= $1 ORDER BY commit_id LIMIT $2"
             )
             .bind(next_commit_id)
-            .fetch_optional(&self.ctx.pool)
+            .bind(max_actions as i64)
+            .bind(window_secs)
+            .fetch_all(&self.ctx.pool)
             .await?;
-            let Some((commit_id, blob_tx)) = next else {
+
+            // Only consecutive actions are sent together, so that blob txs stay in commit order
+            let batch: Vec<(i64, Json<BlobTransaction>, bool)> = pending
+                .into_iter()
+                .enumerate()
+                .take_while(|(offset, (commit_id, ..))| {
+                    *commit_id == next_commit_id + *offset as i64
+                })
+                .map(|(_, row)| row)
+                .collect();
+            let Some((_, _, window_elapsed)) = batch.first() else {
+                break;
+            };
+            if batch.len() < max_actions && !window_elapsed {
+                // Wait for more actions until the window of the first one elapsed
                 break;
+            }
+
+            let commit_ids: Vec<i64> = batch.iter().map(|(commit_id, ..)| *commit_id).collect();
+            let mut blob_txs = batch.into_iter().map(|(_, blob_tx, _)| blob_tx.0);
+            let blob_tx = if commit_ids.len() == 1 {
+                blob_txs.next().expect("batch has an action")
+            } else {
+                BlobTransaction::new(
+                    ORDERBOOK_ACCOUNT_IDENTITY,
+                    blob_txs.flat_map(|blob_tx| blob_tx.blobs).collect(),
+                )
             };
+            let sent_tx_hash = blob_tx.hashed();
 
-            let blob_tx = blob_tx.0;
             let blob_send_start = Instant::now();
             let send_res = log_error!(
-                self.ctx.client.send_tx_blob(blob_tx.clone()).await,
+                self.ctx.client.send_tx_blob(blob_tx).await,
                 "Failed to send blob tx"
             );
 
@@ -1207,17 +2123,17 @@ impl DatabaseModule {
             if let Err(e) = send_res {
                 log_error!(
                     sqlx::query(
-                        "UPDATE blob_tx_outbox SET attempts = attempts + 1, last_error = $2 WHERE commit_id = $1"
+                        "UPDATE blob_tx_outbox SET attempts = attempts + 1, last_error = $2 WHERE commit_id = ANY($1)"
                     )
-                    .bind(commit_id)
+                    .bind(&commit_ids)
                     .bind(e.to_string())
                     .execute(&self.ctx.pool)
                     .await,
                     "Failed to update blob transaction error"
                 )?;
                 tracing::warn!(
-                    "Failed to send blob transaction (commit_id {}, will retry): {:#}",
-                    commit_id,
+                    "Failed to send blob transaction (commit_ids {:?}, will retry): {:#}",
+                    commit_ids,
                     e
                 );
                 return Err(e);
@@ -1225,9 +2141,10 @@ impl DatabaseModule {
 
             log_error!(
                 sqlx::query(
-                    "UPDATE blob_tx_outbox SET status = 'sent', sent_at = now(), attempts = attempts + 1, last_error = NULL WHERE commit_id = $1"
+                    "UPDATE blob_tx_outbox SET status = 'sent', sent_at = now(), attempts = attempts + 1, last_error = NULL, sent_tx_hash = $2, blob_index = array_position($1, commit_id) - 1 WHERE commit_id = ANY($1)"
                 )
-                .bind(commit_id)
+                .bind(&commit_ids)
+                .bind(sent_tx_hash.0.clone())
                 .execute(&self.ctx.pool)
                 .await,
                 "Failed to mark blob transaction as sent"
@@ -1237,3 +2154,253 @@ impl DatabaseModule {
         Ok(())
     }
 }
+
+/// Order, trade and balance event rows of a commit. They are inserted with one statement per
+/// table once every event was processed, as a market order sweeping the book emits dozens of
+/// them. Rows keep the order of the events, so that event ids still follow it.
+#[derive(Default)]
+struct EventRows {
+    orders: Vec<(Order, i64, OrderTag)>,
+    order_event_ids: Vec<OrderId>,
+    order_event_statuses: Vec<&'static str>,
+    /// Quantity left after the event, or None to keep the filled quantity of the order
+    order_event_remaining: Vec<Option<i64>>,
+    trade_maker_order_ids: Vec<OrderId>,
+    trade_taker_order_ids: Vec<OrderId>,
+    trade_instrument_ids: Vec<i64>,
+    /// Traded quantity, or None when the whole maker order was filled
+    trade_qtys: Vec<Option<i64>>,
+    balance_identities: Vec<String>,
+    balance_asset_ids: Vec<i64>,
+    balance_totals: Vec<i64>,
+}
+
+impl EventRows {
+    fn push_order(&mut self, order: Order, instrument_id: i64, tag: OrderTag) {
+        self.orders.push((order, instrument_id, tag));
+    }
+
+    fn push_order_event(
+        &mut self,
+        order_id: OrderId,
+        status: &'static str,
+        remaining: Option<i64>,
+    ) {
+        self.order_event_ids.push(order_id);
+        self.order_event_statuses.push(status);
+        self.order_event_remaining.push(remaining);
+    }
+
+    fn push_trade_event(
+        &mut self,
+        maker_order_id: OrderId,
+        taker_order_id: OrderId,
+        instrument_id: i64,
+        qty: Option<i64>,
+    ) {
+        self.trade_maker_order_ids.push(maker_order_id);
+        self.trade_taker_order_ids.push(taker_order_id);
+        self.trade_instrument_ids.push(instrument_id);
+        self.trade_qtys.push(qty);
+    }
+
+    fn push_balance_event(&mut self, identity: String, asset_id: i64, total: i64) {
+        self.balance_identities.push(identity);
+        self.balance_asset_ids.push(asset_id);
+        self.balance_totals.push(total);
+    }
+
+    /// Orders go first, as order and trade events are read from them
+    async fn insert(self, conn: &mut PgConnection, commit_id: i64, taker: &str) -> Result<()> {
+        if !self.orders.is_empty() {
+            let mut query = QueryBuilder::<Postgres>::new(
+                "INSERT INTO orders (commit_id, order_id, instrument_id, identity, side, type, price, qty, client_order_id, tag) ",
+            );
+            query.push_values(self.orders, |mut row, (order, instrument_id, tag)| {
+                row.push_bind(commit_id)
+                    .push_bind(order.order_id)
+                    .push_bind(instrument_id)
+                    .push_bind(taker.to_string())
+                    .push_bind(order.order_side)
+                    .push_bind(order.order_type)
+                    .push_bind(order.price.map(|p| p as i64))
+                    .push_bind(order.quantity as i64)
+                    .push_bind(tag.client_order_id)
+                    .push_bind(tag.tag);
+            });
+            log_error!(
+                query
+                    .build()
+                    .execute(&mut *conn)
+                    .instrument(tracing::info_span!("create_orders"))
+                    .await,
+                "Failed to create orders"
+            )?;
+        }
+
+        if !self.order_event_ids.is_empty() {
+            // TODO:have more data in the events to avoid the join on orders here
+            log_error!(
+                sqlx::query(
+                    "
+                    INSERT INTO order_events (commit_id, order_id, identity, instrument_id, side, type, price, qty, qty_filled, status, client_order_id, tag)
+                    SELECT $1, o.order_id, o.identity, o.instrument_id, o.side, o.type, o.price, o.qty,
+                        COALESCE(o.qty - e.remaining, o.qty_filled), e.status::order_status, o.client_order_id, o.tag
+                    FROM UNNEST($2::text[], $3::text[], $4::bigint[]) WITH ORDINALITY AS e(order_id, status, remaining, ord)
+                    JOIN orders o ON o.order_id = e.order_id
+                    ORDER BY e.ord
+                    "
+                )
+                .bind(commit_id)
+                .bind(self.order_event_ids)
+                .bind(self.order_event_statuses)
+                .bind(self.order_event_remaining)
+                .execute(&mut *conn)
+                .instrument(tracing::info_span!("create_order_events"))
+                .await,
+                "Failed to create order events"
+            )?;
+        }
+
+        if !self.trade_maker_order_ids.is_empty() {
+            log_error!(
+                sqlx::query(
+                    "
+                    INSERT INTO trade_events (commit_id, maker_order_id, taker_order_id, instrument_id, price, qty, side, maker_identity, taker_identity)
+                    SELECT $1, e.maker_order_id, e.taker_order_id, e.instrument_id, m.price, COALESCE(e.qty, m.qty), get_other_side(m.side), m.identity, $2
+                    FROM UNNEST($3::text[], $4::text[], $5::bigint[], $6::bigint[]) WITH ORDINALITY AS e(maker_order_id, taker_order_id, instrument_id, qty, ord)
+                    JOIN orders m ON m.order_id = e.maker_order_id
+                    ORDER BY e.ord
+                    "
+                )
+                .bind(commit_id)
+                .bind(taker)
+                .bind(self.trade_maker_order_ids)
+                .bind(self.trade_taker_order_ids)
+                .bind(self.trade_instrument_ids)
+                .bind(self.trade_qtys)
+                .execute(&mut *conn)
+                .instrument(tracing::info_span!("insert_trade_events"))
+                .await,
+                "Failed to insert trade events"
+            )?;
+        }
+
+        if !self.balance_identities.is_empty() {
+            log_error!(
+                sqlx::query(
+                    "
+                    INSERT INTO balance_events (commit_id, identity, asset_id, total, kind)
+                    SELECT $1, e.identity, e.asset_id, e.total, 'transfer'
+                    FROM UNNEST($2::text[], $3::bigint[], $4::bigint[]) WITH ORDINALITY AS e(identity, asset_id, total, ord)
+                    ORDER BY e.ord
+                    "
+                )
+                .bind(commit_id)
+                .bind(self.balance_identities)
+                .bind(self.balance_asset_ids)
+                .bind(self.balance_totals)
+                .execute(&mut *conn)
+                .instrument(tracing::info_span!("create_balance_events"))
+                .await,
+                "Failed to create balance events"
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blob_outbox(max_pending: usize, overflow_policy: OutboxOverflowPolicy) -> BlobOutbox {
+        BlobOutbox::new(BlobOutboxConfig {
+            retry_backoff_ms: 100,
+            max_retry_backoff_ms: 1_000,
+            max_pending,
+            overflow_policy,
+        })
+    }
+
+    fn enqueue(outbox: &BlobOutbox, count: usize) {
+        for _ in 0..count {
+            outbox.on_enqueued();
+        }
+    }
+
+    #[test]
+    fn retry_backoff_doubles_then_caps() {
+        let outbox = blob_outbox(0, OutboxOverflowPolicy::Reject);
+        let backoffs: Vec<u64> = (1..=6)
+            .map(|failures| outbox.retry_backoff(failures).as_millis() as u64)
+            .collect();
+        assert_eq!(backoffs, vec![100, 200, 400, 800, 1_000, 1_000]);
+        assert_eq!(outbox.retry_backoff(u32::MAX), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn reject_policy_refuses_actions_once_the_outbox_overflows() {
+        let outbox = blob_outbox(3, OutboxOverflowPolicy::Reject);
+        enqueue(&outbox, 2);
+        assert!(outbox.check_capacity().is_ok());
+        enqueue(&outbox, 1);
+        assert!(outbox.check_capacity().is_err());
+    }
+
+    #[test]
+    fn warn_policy_accepts_actions_when_the_outbox_overflows() {
+        let outbox = blob_outbox(3, OutboxOverflowPolicy::Warn);
+        enqueue(&outbox, 5);
+        assert_eq!(outbox.pending(), 5);
+        assert!(outbox.check_capacity().is_ok());
+    }
+
+    #[test]
+    fn outbox_is_unlimited_without_max_pending() {
+        let outbox = blob_outbox(0, OutboxOverflowPolicy::Reject);
+        enqueue(&outbox, 1_000);
+        assert!(outbox.check_capacity().is_ok());
+    }
+
+    fn worker_queues(count: usize, queue_capacity: usize) -> WorkerQueues {
+        WorkerQueues::new(DatabaseWorkersConfig {
+            count,
+            queue_capacity,
+        })
+    }
+
+    #[test]
+    fn worker_queues_refuse_writes_once_every_queue_is_full() {
+        let queues = worker_queues(2, 3);
+        for _ in 0..5 {
+            queues.on_queued();
+        }
+        assert!(queues.check_capacity().is_ok());
+        queues.on_queued();
+        assert!(queues.check_capacity().is_err());
+        queues.on_dequeued();
+        assert!(queues.check_capacity().is_ok());
+    }
+
+    #[test]
+    fn more_workers_raise_the_capacity() {
+        let queues = worker_queues(1, 3);
+        for _ in 0..3 {
+            queues.on_queued();
+        }
+        assert!(queues.check_capacity().is_err());
+        queues.set_count(2);
+        assert_eq!(queues.capacity(), 6);
+        assert!(queues.check_capacity().is_ok());
+    }
+
+    #[test]
+    fn worker_queues_hold_at_least_one_request() {
+        let queues = worker_queues(0, 0);
+        assert_eq!(queues.capacity(), 1);
+        queues.set_count(0);
+        assert_eq!(queues.count(), 1);
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use hyli_modules::{
+    bus::{BusClientSender, SharedMessageBus},
+    log_error, module_bus_client, module_handle_messages,
+    modules::Module,
+};
+use hyli_smt_token::SmtTokenAction;
+use orderbook::ORDERBOOK_ACCOUNT_IDENTITY;
+use sdk::{NodeStateEvent, StatefulEvent, StructuredBlob, UnsettledBlobTransaction};
+use sqlx::PgPool;
+use tokio::sync::RwLock;
+use tracing::{debug, info};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::{
+    app::{OrderbookRequest, PendingDeposit},
+    services::asset_service::AssetService,
+};
+
+/// Credits deposits from the settled token transfers to the orderbook account, read from the
+/// blocks of the DA. Each transfer blob is recorded in `deposit_transfers` before it is credited,
+/// so that blocks read again after a restart are not credited twice.
+pub struct DepositWatcherModule {
+    bus: DepositWatcherModuleBusClient,
+    asset_service: Arc<RwLock<AssetService>>,
+    pool: PgPool,
+}
+
+pub struct DepositWatcherModuleCtx {
+    pub asset_service: Arc<RwLock<AssetService>>,
+    pub pool: PgPool,
+}
+
+module_bus_client! {
+#[derive(Debug)]
+pub struct DepositWatcherModuleBusClient {
+    sender(OrderbookRequest),
+    receiver(NodeStateEvent),
+}
+}
+
+impl Module for DepositWatcherModule {
+    type Context = Arc<DepositWatcherModuleCtx>;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = DepositWatcherModuleBusClient::new_from_bus(bus.new_handle()).await;
+
+        Ok(DepositWatcherModule {
+            bus,
+            asset_service: ctx.asset_service.clone(),
+            pool: ctx.pool.clone(),
+        })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        module_handle_messages! {
+            on_self self,
+            listen<NodeStateEvent> event => {
+                _ = log_error!(self.handle_node_state_event(event).await, "handle node state event")
+            }
+        };
+
+        Ok(())
+    }
+}
+
+impl DepositWatcherModule {
+    async fn handle_node_state_event(&mut self, event: NodeStateEvent) -> Result<()> {
+        match event {
+            NodeStateEvent::NewBlock(block) => {
+                for (_, stateful_event) in block.stateful_events.events.iter() {
+                    if let StatefulEvent::SettledTx(unsettled) = stateful_event {
+                        self.handle_settled_tx(unsettled).await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_settled_tx(&mut self, tx: &UnsettledBlobTransaction) -> Result<()> {
+        let tx_hash = &tx.tx_id.1;
+        for (blob_index, blob) in tx.tx.blobs.iter().enumerate() {
+            let Ok(structured) = StructuredBlob::<SmtTokenAction>::try_from(blob.clone()) else {
+                continue;
+            };
+            let SmtTokenAction::Transfer {
+                sender,
+                recipient,
+                amount,
+            } = structured.data.parameters
+            else {
+                continue;
+            };
+            if recipient.0 != ORDERBOOK_ACCOUNT_IDENTITY {
+                continue;
+            }
+            // Transfers of tokens that are not listed are left to the operator
+            if self
+                .asset_service
+                .read()
+                .await
+                .get_symbol_from_contract_name(&blob.contract_name.0)
+                .await
+                .is_none()
+            {
+                debug!(
+                    "Ignoring transfer of unlisted token {} to the orderbook",
+                    blob.contract_name.0
+                );
+                continue;
+            }
+
+            let inserted = sqlx::query(
+                "INSERT INTO deposit_transfers (tx_hash, blob_index, identity, contract_name, amount) VALUES ($1, $2, $3, $4, $5::numeric) ON CONFLICT DO NOTHING",
+            )
+            .bind(&tx_hash.0)
+            .bind(blob_index as i32)
+            .bind(&sender.0)
+            .bind(&blob.contract_name.0)
+            .bind(amount.to_string())
+            .execute(&self.pool)
+            .await
+            .context("recording deposit transfer")?
+            .rows_affected();
+            if inserted == 0 {
+                debug!(
+                    "Deposit transfer {}#{blob_index} was already credited",
+                    hex::encode(&tx_hash.0)
+                );
+                continue;
+            }
+
+            info!(
+                tx_hash = %hex::encode(&tx_hash.0),
+                token = %blob.contract_name,
+                sender = %sender.0,
+                amount,
+                "Settled deposit transfer detected",
+            );
+            let span = tracing::info_span!("deposit_transfer", tx_hash = %hex::encode(&tx_hash.0));
+            self.bus
+                .send(OrderbookRequest::PendingDeposit(PendingDeposit {
+                    sender,
+                    contract_name: blob.contract_name.clone(),
+                    amount,
+                    context: span.context(),
+                }))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+//! PostgreSQL server run by the orderbook itself, for single-node deployments and CI that
+//! should not need a database to be installed. Selected with `database_url = "embedded"`.
+//!
+//! The embedded server is a regular PostgreSQL, so migrations, queries and the `pg_notify`
+//! channels listened to by the API server work unchanged. Without the API server, notifications
+//! are sent in process instead, see [`crate::storage::NotifierKind`].
+
+use std::path::Path;
+
+use anyhow::Result;
+
+/// Value of `database_url` (and optionally `indexer_database_url`) selecting the embedded server
+pub const EMBEDDED_DATABASE_URL: &str = "embedded";
+
+#[cfg(feature = "embedded-db")]
+pub use embedded::EmbeddedDatabase;
+
+/// Placeholder, as the server is built without the embedded database
+#[cfg(not(feature = "embedded-db"))]
+pub struct EmbeddedDatabase;
+
+/// Starts the embedded server when a database URL is set to [`EMBEDDED_DATABASE_URL`], and
+/// points it to the server. The server stops when the returned handle is dropped.
+#[cfg(feature = "embedded-db")]
+pub async fn start_if_configured(
+    data_directory: &Path,
+    database_url: &mut String,
+    indexer_database_url: &mut String,
+) -> Result<Option<EmbeddedDatabase>> {
+    if *database_url != EMBEDDED_DATABASE_URL && *indexer_database_url != EMBEDDED_DATABASE_URL {
+        return Ok(None);
+    }
+    let database = EmbeddedDatabase::start(data_directory).await?;
+    if *database_url == EMBEDDED_DATABASE_URL {
+        // The orderbook database is created on connection, as for an external server
+        *database_url = database.server_url();
+    }
+    if *indexer_database_url == EMBEDDED_DATABASE_URL {
+        *indexer_database_url = database.database_url("hyli_indexer").await?;
+    }
+    Ok(Some(database))
+}
+
+#[cfg(not(feature = "embedded-db"))]
+pub async fn start_if_configured(
+    _data_directory: &Path,
+    database_url: &mut String,
+    indexer_database_url: &mut String,
+) -> Result<Option<EmbeddedDatabase>> {
+    if *database_url == EMBEDDED_DATABASE_URL || *indexer_database_url == EMBEDDED_DATABASE_URL {
+        anyhow::bail!(
+            "The embedded database is not available: the server was built without the embedded-db feature"
+        );
+    }
+    Ok(None)
+}
+
+#[cfg(feature = "embedded-db")]
+mod embedded {
+    use std::{
+        fs::{self, OpenOptions},
+        io::Write,
+        path::Path,
+    };
+
+    use anyhow::{Context, Result};
+    use postgresql_embedded::{PostgreSQL, Settings};
+    use rand::{distr::Alphanumeric, Rng};
+    use tracing::info;
+
+    pub struct EmbeddedDatabase {
+        postgresql: PostgreSQL,
+    }
+
+    impl EmbeddedDatabase {
+        /// Installs PostgreSQL on first use, in the home directory so that cleaning the data
+        /// directory does not download it again, and keeps its data in the data directory
+        pub async fn start(data_directory: &Path) -> Result<Self> {
+            let password_file = data_directory.join("postgresql_data.pwfile");
+            let settings = Settings {
+                data_dir: data_directory.join("postgresql_data"),
+                password: password(&password_file)?,
+                password_file,
+                temporary: false,
+                ..Settings::default()
+            };
+            let mut postgresql = PostgreSQL::new(settings);
+            postgresql
+                .setup()
+                .await
+                .context("installing the embedded database")?;
+            postgresql
+                .start()
+                .await
+                .context("starting the embedded database")?;
+            info!(
+                "Embedded database listening on port {}",
+                postgresql.settings().port
+            );
+            Ok(EmbeddedDatabase { postgresql })
+        }
+
+        /// URL of the server, without database
+        pub fn server_url(&self) -> String {
+            let settings = self.postgresql.settings();
+            format!(
+                "postgresql://{}:{}@{}:{}",
+                settings.username, settings.password, settings.host, settings.port
+            )
+        }
+
+        /// URL of a database, created when it does not exist yet
+        pub async fn database_url(&self, database_name: &str) -> Result<String> {
+            if !self.postgresql.database_exists(database_name).await? {
+                self.postgresql.create_database(database_name).await?;
+            }
+            Ok(self.postgresql.settings().url(database_name))
+        }
+    }
+
+    /// Password of the server, generated with its data and kept in a file only readable by the
+    /// server's user, which `initdb` reads it from
+    fn password(password_file: &Path) -> Result<String> {
+        if password_file.exists() {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(password_file, fs::Permissions::from_mode(0o600))
+                    .context("restricting the embedded database password file")?;
+            }
+            let password = fs::read_to_string(password_file)
+                .context("reading the embedded database password")?;
+            return Ok(password.trim_end().to_string());
+        }
+
+        let password: String = rand::rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect();
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(password_file)
+            .and_then(|mut file| file.write_all(password.as_bytes()))
+            .context("writing the embedded database password")?;
+        Ok(password)
+    }
+}
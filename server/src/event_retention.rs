@@ -0,0 +1,245 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use hyli_modules::log_error;
+use sqlx::{PgPool, Row};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+use crate::conf::EventRetentionConfig;
+
+/// Tables partitioned by commit range, see the `16_event_partitions` migration
+const EVENT_TABLES: [&str; 3] = ["order_events", "trade_events", "balance_events"];
+
+/// Maintains the commit range partitions of the event tables: creates them ahead of the
+/// commits, and once a partition is past the retention window, archives and drops it.
+///
+/// The state is rebuilt from the last order and balance events, so the ones still in effect
+/// (balances, and orders that are still open) are copied into the next partition, at its
+/// first commit, before the partition is dropped. Trades are history only.
+pub struct EventRetention {
+    pool: PgPool,
+    config: EventRetentionConfig,
+}
+
+struct EventPartition {
+    suffix: String,
+    from_commit: Option<i64>,
+    to_commit: i64,
+}
+
+impl EventRetention {
+    pub fn new(pool: PgPool, config: EventRetentionConfig) -> Self {
+        EventRetention { pool, config }
+    }
+
+    pub async fn run(self) {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.interval_secs.max(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            _ = log_error!(self.create_partitions().await, "create event partitions");
+            if self.config.retention_days > 0 {
+                _ = log_error!(self.prune().await, "prune event partitions");
+            }
+        }
+    }
+
+    /// Creates partitions until the next one starts a full partition past the last commit
+    async fn create_partitions(&self) -> Result<()> {
+        let partition_commits = self.config.partition_commits.max(1) as i64;
+        let last_commit: i64 =
+            sqlx::query_scalar("SELECT COALESCE(MAX(commit_id), -1) FROM commits")
+                .fetch_one(&self.pool)
+                .await?;
+        let mut from_commit: i64 =
+            sqlx::query_scalar("SELECT COALESCE(MAX(to_commit), 0) FROM event_partitions")
+                .fetch_one(&self.pool)
+                .await?;
+
+        while from_commit <= last_commit + partition_commits {
+            // A partition cannot cover rows of the default partition: they stay there
+            let mut in_default: Option<i64> = None;
+            for table in EVENT_TABLES {
+                let max_commit: Option<i64> =
+                    sqlx::query_scalar(&format!("SELECT MAX(commit_id) FROM {table}_default"))
+                        .fetch_one(&self.pool)
+                        .await?;
+                in_default = in_default.max(max_commit);
+            }
+            if let Some(in_default) = in_default.filter(|commit_id| *commit_id >= from_commit) {
+                warn!(
+                    "Events up to commit {in_default} were written to the default partitions, the next partition starts after them"
+                );
+                from_commit = in_default + 1;
+            }
+
+            let to_commit = from_commit + partition_commits;
+            let suffix = format!("c{from_commit}");
+            let mut tx = self.pool.begin().await?;
+            for table in EVENT_TABLES {
+                sqlx::query(&format!(
+                    "CREATE TABLE {table}_{suffix} PARTITION OF {table} FOR VALUES FROM ({from_commit}) TO ({to_commit})"
+                ))
+                .execute(&mut *tx)
+                .await
+                .with_context(|| format!("creating partition {table}_{suffix}"))?;
+            }
+            sqlx::query(
+                "INSERT INTO event_partitions (suffix, from_commit, to_commit) VALUES ($1, $2, $3)",
+            )
+            .bind(&suffix)
+            .bind(from_commit)
+            .bind(to_commit)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            info!("Created event partitions for commits {from_commit} to {to_commit}");
+            from_commit = to_commit;
+        }
+        Ok(())
+    }
+
+    /// Prunes the oldest partitions, as long as their last commit is past the retention window
+    async fn prune(&self) -> Result<()> {
+        let partitions = sqlx::query(
+            "
+            SELECT p.suffix, p.from_commit, p.to_commit,
+                -- Following commits are written to the next partitions
+                p.to_commit <= (SELECT MAX(commit_id) FROM commits)
+                AND COALESCE(
+                    (SELECT MAX(c.authored_at) FROM commits c
+                        WHERE c.commit_id < p.to_commit
+                            AND (p.from_commit IS NULL OR c.commit_id >= p.from_commit)),
+                    '-infinity'
+                ) < now() - make_interval(days => $1) AS expired
+            FROM event_partitions p
+            WHERE p.pruned_at IS NULL
+            ORDER BY p.to_commit
+            ",
+        )
+        .bind(self.config.retention_days as i32)
+        .fetch_all(&self.pool)
+        .await?;
+
+        for row in partitions {
+            // Partitions are pruned in order, so that the events in effect move forward
+            if !row.get::<Option<bool>, _>("expired").unwrap_or(false) {
+                break;
+            }
+            let partition = EventPartition {
+                suffix: row.get("suffix"),
+                from_commit: row.get("from_commit"),
+                to_commit: row.get("to_commit"),
+            };
+            self.prune_partition(&partition).await?;
+        }
+        Ok(())
+    }
+
+    async fn prune_partition(&self, partition: &EventPartition) -> Result<()> {
+        let suffix = &partition.suffix;
+        let archived_to = if self.config.archive_dir.is_empty() {
+            None
+        } else {
+            Some(self.archive(suffix).await?)
+        };
+
+        let mut tx = self.pool.begin().await?;
+        log_error!(
+            sqlx::query(&format!(
+                "
+                INSERT INTO balance_events (commit_id, identity, asset_id, total, reserved, kind, ref_order_id, ref_trade_signed_id, event_time)
+                SELECT $1, p.identity, p.asset_id, p.total, p.reserved, p.kind, p.ref_order_id, p.ref_trade_signed_id, p.event_time
+                FROM (
+                    SELECT DISTINCT ON (identity, asset_id) * FROM balance_events_{suffix}
+                    ORDER BY identity, asset_id, commit_id DESC, event_id DESC
+                ) p
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM balance_events b
+                    WHERE b.identity = p.identity AND b.asset_id = p.asset_id AND b.commit_id >= $1
+                )
+                "
+            ))
+            .bind(partition.to_commit)
+            .execute(&mut *tx)
+            .await,
+            "Failed to carry balance events forward"
+        )?;
+        log_error!(
+            sqlx::query(&format!(
+                "
+                INSERT INTO order_events (commit_id, order_id, identity, instrument_id, side, type, price, qty, qty_filled, status, event_time, client_order_id, tag)
+                SELECT $1, p.order_id, p.identity, p.instrument_id, p.side, p.type, p.price, p.qty, p.qty_filled, p.status, p.event_time, p.client_order_id, p.tag
+                FROM (
+                    SELECT DISTINCT ON (order_id) * FROM order_events_{suffix}
+                    ORDER BY order_id, commit_id DESC, event_id DESC
+                ) p
+                WHERE p.status IN ('open', 'partially_filled')
+                    AND NOT EXISTS (
+                        SELECT 1 FROM order_events o WHERE o.order_id = p.order_id AND o.commit_id >= $1
+                    )
+                "
+            ))
+            .bind(partition.to_commit)
+            .execute(&mut *tx)
+            .await,
+            "Failed to carry order events forward"
+        )?;
+        for table in EVENT_TABLES {
+            sqlx::query(&format!("DROP TABLE {table}_{suffix}"))
+                .execute(&mut *tx)
+                .await
+                .with_context(|| format!("dropping partition {table}_{suffix}"))?;
+        }
+        sqlx::query(
+            "UPDATE event_partitions SET pruned_at = now(), archived_to = $2 WHERE suffix = $1",
+        )
+        .bind(suffix)
+        .bind(&archived_to)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        info!(
+            "Pruned event partitions up to commit {} (from {:?}), archived to {:?}",
+            partition.to_commit, partition.from_commit, archived_to
+        );
+        Ok(())
+    }
+
+    /// Writes the partitions as CSV files in the archive directory, e.g. a mounted object
+    /// storage bucket. Files are written under a temporary name and renamed once complete.
+    async fn archive(&self, suffix: &str) -> Result<String> {
+        let archive_dir = PathBuf::from(&self.config.archive_dir);
+        tokio::fs::create_dir_all(&archive_dir)
+            .await
+            .with_context(|| format!("creating archive directory {}", archive_dir.display()))?;
+
+        let mut conn = self.pool.acquire().await?;
+        for table in EVENT_TABLES {
+            let path = archive_dir.join(format!("{table}_{suffix}.csv"));
+            let tmp_path = path.with_extension("csv.tmp");
+            let mut file = tokio::fs::File::create(&tmp_path)
+                .await
+                .with_context(|| format!("creating {}", tmp_path.display()))?;
+            let mut rows = conn
+                .copy_out_raw(&format!(
+                    "COPY {table}_{suffix} TO STDOUT WITH (FORMAT csv, HEADER)"
+                ))
+                .await?;
+            while let Some(chunk) = rows.next().await {
+                file.write_all(&chunk?).await?;
+            }
+            drop(rows);
+            file.sync_all().await?;
+            tokio::fs::rename(&tmp_path, &path).await?;
+        }
+        Ok(archive_dir
+            .join(format!("*_{suffix}.csv"))
+            .display()
+            .to_string())
+    }
+}
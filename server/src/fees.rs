@@ -0,0 +1,90 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use hyli_modules::{
+    bus::{BusClientSender, SharedMessageBus},
+    log_error, module_bus_client, module_handle_messages,
+    modules::Module,
+};
+use orderbook::model::FeeTier;
+use tokio::sync::RwLock;
+use tracing::{debug, info};
+
+use crate::{app::OrderbookRequest, conf::FeeConfig, services::user_service::UserService};
+
+/// Periodically recomputes users' fee tiers from their rolling traded volume,
+/// and asks the orderbook module to submit the ones that changed.
+/// The tiers and the volume window are read on each refresh, so that they change with the config.
+pub struct FeeTierModule {
+    bus: FeeTierModuleBusClient,
+    user_service: Arc<RwLock<UserService>>,
+    fee_config: Arc<RwLock<FeeConfig>>,
+}
+
+pub struct FeeTierModuleCtx {
+    pub user_service: Arc<RwLock<UserService>>,
+    pub fee_config: Arc<RwLock<FeeConfig>>,
+}
+
+module_bus_client! {
+#[derive(Debug)]
+pub struct FeeTierModuleBusClient {
+    sender(OrderbookRequest),
+}
+}
+
+impl Module for FeeTierModule {
+    type Context = Arc<FeeTierModuleCtx>;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = FeeTierModuleBusClient::new_from_bus(bus.new_handle()).await;
+
+        Ok(FeeTierModule {
+            bus,
+            user_service: ctx.user_service.clone(),
+            fee_config: ctx.fee_config.clone(),
+        })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        let refresh_interval_secs = self.fee_config.read().await.refresh_interval_secs;
+        let mut interval = tokio::time::interval(Duration::from_secs(refresh_interval_secs.max(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        module_handle_messages! {
+            on_self self,
+            _ = interval.tick() => {
+                _ = log_error!(self.refresh_fee_tiers().await, "refresh fee tiers");
+            }
+        };
+
+        Ok(())
+    }
+}
+
+impl FeeTierModule {
+    async fn refresh_fee_tiers(&mut self) -> Result<()> {
+        let fee_config = self.fee_config.read().await.clone();
+        let volumes = self
+            .user_service
+            .read()
+            .await
+            .get_rolling_volumes(fee_config.volume_window_days, &fee_config.volume_asset)
+            .await
+            .map_err(|e| anyhow!("Failed to get rolling volumes: {}", e.1))?;
+
+        let fee_tiers: Vec<(String, FeeTier)> = volumes
+            .into_iter()
+            .map(|(user, volume)| {
+                let fee_tier = fee_config.tier_for_volume(volume);
+                debug!("User {user} has a rolling volume of {volume}: {fee_tier:?}");
+                (user, fee_tier)
+            })
+            .collect();
+
+        info!("Computed fee tiers for {} users", fee_tiers.len());
+        self.bus.send(OrderbookRequest::UpdateFeeTiers(fee_tiers))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,18 @@
+use crate::conf::FundingConfig;
+
+/// Funding paid by a long position of one base unit over an interval, in the price unit of the
+/// pair: the premium of the book over the index price, dampened and capped.
+/// The book is at a premium when its best bid is above the index price, and at a discount when
+/// its best ask is below, so that a one-sided book only measures its side.
+pub fn funding_payment(
+    config: &FundingConfig,
+    best_bid: Option<u64>,
+    best_ask: Option<u64>,
+    index_price: u64,
+) -> i64 {
+    let premium = best_bid.map_or(0, |bid| bid.saturating_sub(index_price) as i128)
+        - best_ask.map_or(0, |ask| index_price.saturating_sub(ask) as i128);
+    let cap = index_price as i128 * config.max_rate_bps as i128 / 10_000;
+    let payment = (premium / config.dampening.max(1) as i128).clamp(-cap, cap);
+    payment.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
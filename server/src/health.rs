@@ -0,0 +1,111 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use reqwest::StatusCode;
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use utoipa::ToSchema;
+
+use crate::{conf::HealthConfig, node_client::NodeClient};
+
+/// Outcome of the checks of a probe, served as JSON
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthReport {
+    /// "ok" when every check passed, "fail" otherwise
+    pub status: &'static str,
+    pub checks: Vec<HealthCheck>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthCheck {
+    pub name: &'static str,
+    pub ok: bool,
+    /// What was measured, or why the check failed
+    pub detail: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl HealthReport {
+    pub fn new(checks: Vec<HealthCheck>) -> Self {
+        let ok = checks.iter().all(|check| check.ok);
+        HealthReport {
+            status: if ok { "ok" } else { "fail" },
+            checks,
+        }
+    }
+
+    /// 503 when a check failed, so that the probe fails
+    pub fn status_code(&self) -> StatusCode {
+        if self.checks.iter().all(|check| check.ok) {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+    }
+}
+
+impl HealthCheck {
+    /// Runs a check, which fails if it does not complete within the timeout
+    pub async fn run(
+        name: &'static str,
+        timeout: Duration,
+        check: impl Future<Output = Result<Option<String>>>,
+    ) -> Self {
+        let start = Instant::now();
+        let (ok, detail) = match tokio::time::timeout(timeout, check).await {
+            Ok(Ok(detail)) => (true, detail),
+            Ok(Err(e)) => (false, Some(format!("{e:#}"))),
+            Err(_) => (false, Some(format!("timed out after {timeout:?}"))),
+        };
+        HealthCheck {
+            name,
+            ok,
+            detail,
+            duration_ms: start.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+pub async fn check_database(pool: &PgPool) -> Result<Option<String>> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(None)
+}
+
+pub async fn check_node(client: &NodeClient) -> Result<Option<String>> {
+    let height = client.get_block_height().await?;
+    Ok(Some(format!("block height {}", height.0)))
+}
+
+/// Checks that actions settle: the prover keeps up, and the settled txs are read from the DA
+pub async fn check_settlement(pool: &PgPool, config: &HealthConfig) -> Result<Option<String>> {
+    let row = sqlx::query(
+        "SELECT COUNT(*) AS pending_txs,
+            COALESCE(EXTRACT(EPOCH FROM now() - MIN(created_at)), 0)::bigint AS oldest_unsettled_secs
+        FROM prover_requests",
+    )
+    .fetch_one(pool)
+    .await?;
+    let pending_txs: i64 = row.get("pending_txs");
+    let oldest_unsettled_secs: i64 = row.get("oldest_unsettled_secs");
+
+    if config.max_pending_txs > 0 && pending_txs > config.max_pending_txs {
+        bail!(
+            "{pending_txs} txs waiting to settle, above {}",
+            config.max_pending_txs
+        );
+    }
+    if config.max_settlement_delay_secs > 0
+        && oldest_unsettled_secs > config.max_settlement_delay_secs as i64
+    {
+        bail!(
+            "oldest unsettled tx was sent {oldest_unsettled_secs}s ago, above {}s",
+            config.max_settlement_delay_secs
+        );
+    }
+    Ok(Some(format!(
+        "{pending_txs} txs waiting to settle, oldest sent {oldest_unsettled_secs}s ago"
+    )))
+}
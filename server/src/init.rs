@@ -6,7 +6,8 @@ use client_sdk::{
 };
 use orderbook::{
     model::{
-        AssetInfo, Balance as OrderbookBalance, ExecuteState, Pair, PairInfo, Symbol, UserInfo,
+        AssetInfo, Balance as OrderbookBalance, CircuitBreakerState, ExecuteState, MarketStatus,
+        Pair, PairInfo, PerpMarket, Symbol, UserInfo,
     },
     order_manager::diff_maps,
     zk::{smt::GetKey, FullState, OrderManagerRoots, H256},
@@ -14,8 +15,9 @@ use orderbook::{
 use reqwest::StatusCode;
 use sdk::{
     api::{APIRegisterContract, TransactionStatusDb},
-    info, BlockHeight, ContractName, LaneId, ProgramId, StateCommitment, TxHash,
+    info, BlockHeight, ContractName, LaneId, ProgramId, StateCommitment, TxHash, Verifier,
 };
+use sqlx::PgPool;
 use std::{
     collections::{BTreeMap, HashMap},
     sync::Arc,
@@ -24,13 +26,16 @@ use std::{
 use tokio::{sync::RwLock, time::timeout};
 use tracing::{error, warn};
 
-use crate::services::{
-    asset_service::AssetService, book_service::BookService, user_service::UserService,
+use crate::{
+    replay::{fetch_commit_events, replay_light_state},
+    services::{asset_service::AssetService, book_service::BookService, user_service::UserService},
+    snapshot::SnapshotStore,
 };
 
 pub struct ContractInit {
     pub name: ContractName,
     pub program_id: ProgramId,
+    pub verifier: Verifier,
     pub initial_state: StateCommitment,
 }
 
@@ -72,7 +77,7 @@ async fn init_contract(
         Err(_) => {
             info!("🚀 Registering {} contract", contract.name);
             node.register_contract(APIRegisterContract {
-                verifier: sdk::verifiers::SP1_4.into(),
+                verifier: contract.verifier,
                 program_id: contract.program_id,
                 state_commitment: contract.initial_state,
                 contract_name: contract.name.clone(),
@@ -116,10 +121,21 @@ fn init_empty_orderbook(secret: Vec<u8>, lane_id: LaneId) -> (ExecuteState, Full
     (light, full)
 }
 
+/// Loads the orderbook state at the last settled commit, from its snapshot or from the database
+/// tables, and checks it against the onchain commitment when `check_commitment` is set. A state
+/// that does not match is rebuilt from the events of the commits instead of failing.
 #[allow(clippy::too_many_arguments)]
 #[cfg_attr(
     feature = "instrumentation",
-    tracing::instrument(skip(secret, asset_service, user_service, book_service, node))
+    tracing::instrument(skip(
+        secret,
+        asset_service,
+        user_service,
+        book_service,
+        node,
+        snapshots,
+        pool
+    ))
 )]
 pub async fn init_orderbook_from_database(
     lane_id: LaneId,
@@ -131,6 +147,8 @@ pub async fn init_orderbook_from_database(
     check_commitment: bool,
     last_settled_tx: &Option<TxHash>,
     offline: bool,
+    snapshots: Option<&SnapshotStore>,
+    pool: &PgPool,
 ) -> Result<(ExecuteState, FullState), AppError> {
     let asset_service = asset_service.read().await;
     let user_service = user_service.read().await;
@@ -170,8 +188,33 @@ pub async fn init_orderbook_from_database(
 
     info!("🔍 Commit id: {}", commit_id);
 
+    if let Some(snapshots) = snapshots {
+        match snapshots.restore(commit_id).await {
+            Ok(Some((light_orderbook, full_orderbook))) => {
+                info!("✅ Orderbook restored from snapshot");
+                if !check_commitment || offline {
+                    return Ok((light_orderbook, full_orderbook));
+                }
+                match check(node, light_orderbook, full_orderbook).await {
+                    Ok(restored) => return Ok(restored),
+                    Err(e) => warn!("⚠️ Snapshot does not match the onchain state: {:#}", e.1),
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("⚠️ Could not restore orderbook from snapshot: {e:#}"),
+        }
+        info!("🔍 Loading orderbook from database instead");
+    }
+
     let instruments = asset_service.get_all_instruments(commit_id).await?;
     let assets = asset_service.get_all_assets().await;
+    let withdraw_limits = asset_service.get_withdraw_limits(commit_id).await?;
+    let withdraw_delays = asset_service.get_withdraw_delays(commit_id).await?;
+    let pair_statuses = asset_service.get_pair_statuses(commit_id).await?;
+    let circuit_breakers = asset_service.get_circuit_breakers(commit_id).await?;
+    let perp_markets = asset_service.get_perp_markets(commit_id).await?;
+    let bridge_paused = asset_service.get_bridge_paused(commit_id).await?;
+    let collaterals = asset_service.get_collaterals(commit_id).await?;
 
     let mut pairs_info: HashMap<Pair, PairInfo> = HashMap::new();
     for (_, instrument) in instruments.iter() {
@@ -192,15 +235,25 @@ pub async fn init_orderbook_from_database(
             )
         })?;
 
-        let base_info = AssetInfo::new(
-            base_asset.scale as u64,
-            ContractName(base_asset.contract_name.clone()),
-        );
-
-        let quote_info = AssetInfo::new(
-            quote_asset.scale as u64,
-            ContractName(quote_asset.contract_name.clone()),
-        );
+        let base_info = AssetInfo {
+            withdraw_limit: withdraw_limits.get(&base_asset.symbol).cloned(),
+            withdraw_delay: withdraw_delays.get(&base_asset.symbol).cloned(),
+            collateral: collaterals.get(&base_asset.symbol).cloned(),
+            ..AssetInfo::new(
+                base_asset.scale as u64,
+                ContractName(base_asset.contract_name.clone()),
+            )
+        };
+
+        let quote_info = AssetInfo {
+            withdraw_limit: withdraw_limits.get(&quote_asset.symbol).cloned(),
+            withdraw_delay: withdraw_delays.get(&quote_asset.symbol).cloned(),
+            collateral: collaterals.get(&quote_asset.symbol).cloned(),
+            ..AssetInfo::new(
+                quote_asset.scale as u64,
+                ContractName(quote_asset.contract_name.clone()),
+            )
+        };
 
         pairs_info.insert(
             (base_asset.symbol.clone(), quote_asset.symbol.clone()),
@@ -255,23 +308,110 @@ pub async fn init_orderbook_from_database(
     // TODO: load properly the value
     let last_block_height = sdk::BlockHeight(0);
 
-    let light_orderbook = orderbook::model::ExecuteState::from_data(
+    let mut light_orderbook = orderbook::model::ExecuteState::from_data(
         pairs_info.clone(),
         order_manager.clone(),
         users_info.clone(),
         balances.clone(),
     )
     .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+    light_orderbook.pairs_status.extend(pair_statuses);
+    light_orderbook.circuit_breakers.extend(circuit_breakers);
+    light_orderbook.perp_markets.extend(perp_markets);
+    light_orderbook.bridge_paused = bridge_paused;
 
-    let full_orderbook = FullState::from_data(&light_orderbook, secret, lane_id, last_block_height)
-        .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
+    let full_orderbook = FullState::from_data(
+        &light_orderbook,
+        secret.clone(),
+        lane_id.clone(),
+        last_block_height,
+    )
+    .map_err(|e| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow::anyhow!(e)))?;
 
     if !check_commitment || offline {
         info!("🔍 Checking commitment is disabled, skipping");
         return Ok((light_orderbook, full_orderbook));
     }
 
-    check(node, light_orderbook, full_orderbook).await
+    match check(node, light_orderbook, full_orderbook).await {
+        Ok(checked) => Ok(checked),
+        Err(e) => {
+            warn!(
+                "⚠️ Orderbook loaded from database does not match the onchain state: {:#}",
+                e.1
+            );
+            repair_from_events(pool, node, commit_id, secret, lane_id, snapshots).await
+        }
+    }
+}
+
+/// Rebuilds the state at `commit_id` by replaying the events of the commits after the last known
+/// good state, for when the state loaded from the database tables does not match the onchain
+/// commitment. The last known good state is the state snapshot, which only moves forward to
+/// settled commits; without one, the events of every commit are replayed. The tables are left as
+/// they are: only the state the server runs with is repaired.
+async fn repair_from_events(
+    pool: &PgPool,
+    node: &NodeApiHttpClient,
+    commit_id: i64,
+    secret: Vec<u8>,
+    lane_id: LaneId,
+    snapshots: Option<&SnapshotStore>,
+) -> Result<(ExecuteState, FullState), AppError> {
+    let internal = |e: anyhow::Error| AppError(StatusCode::INTERNAL_SERVER_ERROR, e);
+
+    let last_good = match snapshots.map(SnapshotStore::last_good).transpose() {
+        Ok(Some(Some(last_good))) if last_good.0 <= commit_id => Some(last_good),
+        Ok(Some(Some((snapshot_commit_id, ..)))) => {
+            warn!("⚠️ State snapshot at commit {snapshot_commit_id} is ahead of commit {commit_id}, ignoring it");
+            None
+        }
+        Ok(_) => None,
+        Err(e) => {
+            warn!("⚠️ Could not read the state snapshot to repair from: {e:#}");
+            None
+        }
+    };
+
+    let (light_orderbook, full_orderbook) = match last_good {
+        Some((from, mut light_orderbook, mut full_orderbook)) => {
+            info!("🔧 Repairing orderbook from the snapshot at commit {from} and the events of the commits up to {commit_id}");
+            let commits = fetch_commit_events(pool, Some(from), Some(commit_id))
+                .await
+                .map_err(internal)?;
+            for commit in commits {
+                light_orderbook
+                    .apply_events(&commit.user_info, &commit.events)
+                    .and_then(|_| {
+                        full_orderbook
+                            .apply_events_and_update_roots(&commit.user_info, commit.events)
+                    })
+                    .map_err(|e| {
+                        internal(anyhow::anyhow!(
+                            "Could not apply events of commit {}: {e}",
+                            commit.commit_id
+                        ))
+                    })?;
+            }
+            (light_orderbook, full_orderbook)
+        }
+        None => {
+            info!("🔧 Repairing orderbook from the events of every commit up to {commit_id}");
+            let light_orderbook = replay_light_state(pool, commit_id)
+                .await
+                .map_err(internal)?;
+            let full_orderbook =
+                FullState::from_data(&light_orderbook, secret, lane_id, BlockHeight::default())
+                    .map_err(|e| internal(anyhow::anyhow!(e)))?;
+            (light_orderbook, full_orderbook)
+        }
+    };
+
+    let repaired = check(node, light_orderbook, full_orderbook).await?;
+    warn!(
+        "🔧 Orderbook repaired from the contract events: the database tables do not match them at commit {commit_id}"
+    );
+    Ok(repaired)
 }
 
 pub async fn get_last_settled_tx(
@@ -342,15 +482,55 @@ pub struct DebugStateCommitment {
     pub users_info_root: H256,
     pub balances_roots: BTreeMap<Symbol, H256>,
     pub assets: BTreeMap<Symbol, AssetInfo>,
+    pub pairs_status: BTreeMap<Pair, MarketStatus>,
+    pub circuit_breakers: BTreeMap<Pair, CircuitBreakerState>,
+    pub perp_markets: BTreeMap<Pair, PerpMarket>,
+    pub bridge_paused: bool,
     pub order_manager_roots: OrderManagerRoots,
     pub hashed_secret: [u8; 32],
     pub lane_id: LaneId,
     pub last_block_number: BlockHeight,
 }
 
+/// Layout of the commitments of states that use none of the fields committed since the first
+/// version of the contract (see `ParsedStateCommitment::encode`)
+#[derive(BorshDeserialize)]
+struct LegacyStateCommitment {
+    users_info_root: H256,
+    balances_roots: BTreeMap<Symbol, H256>,
+    assets: BTreeMap<Symbol, (u64, ContractName)>,
+    order_manager_roots: OrderManagerRoots,
+    hashed_secret: [u8; 32],
+    lane_id: LaneId,
+    last_block_number: BlockHeight,
+}
+
 impl From<StateCommitment> for DebugStateCommitment {
     fn from(value: StateCommitment) -> Self {
-        borsh::from_slice(&value.0).expect("Failed to deser DebugStateCommitment")
+        if let Ok(commitment) = borsh::from_slice(&value.0) {
+            return commitment;
+        }
+        let legacy: LegacyStateCommitment =
+            borsh::from_slice(&value.0).expect("Failed to deser DebugStateCommitment");
+        DebugStateCommitment {
+            users_info_root: legacy.users_info_root,
+            balances_roots: legacy.balances_roots,
+            assets: legacy
+                .assets
+                .into_iter()
+                .map(|(symbol, (scale, contract_name))| {
+                    (symbol, AssetInfo::new(scale, contract_name))
+                })
+                .collect(),
+            pairs_status: BTreeMap::new(),
+            circuit_breakers: BTreeMap::new(),
+            perp_markets: BTreeMap::new(),
+            bridge_paused: false,
+            order_manager_roots: legacy.order_manager_roots,
+            hashed_secret: legacy.hashed_secret,
+            lane_id: legacy.lane_id,
+            last_block_number: legacy.last_block_number,
+        }
     }
 }
 
@@ -393,6 +573,40 @@ impl DebugStateCommitment {
             diff.insert("symbols_info".to_string(), mismatches.join("; "));
         }
 
+        if self.pairs_status != other.pairs_status {
+            diff_maps(
+                &mut diff,
+                "pairs_status",
+                &self.pairs_status,
+                &other.pairs_status,
+            );
+        }
+
+        if self.circuit_breakers != other.circuit_breakers {
+            diff_maps(
+                &mut diff,
+                "circuit_breakers",
+                &self.circuit_breakers,
+                &other.circuit_breakers,
+            );
+        }
+
+        if self.perp_markets != other.perp_markets {
+            diff_maps(
+                &mut diff,
+                "perp_markets",
+                &self.perp_markets,
+                &other.perp_markets,
+            );
+        }
+
+        if self.bridge_paused != other.bridge_paused {
+            diff.insert(
+                "bridge_paused".to_string(),
+                format!("{} != {}", self.bridge_paused, other.bridge_paused),
+            );
+        }
+
         if self.lane_id != other.lane_id {
             diff.insert(
                 "lane_id".to_string(),
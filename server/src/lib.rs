@@ -1,9 +1,41 @@
 pub mod api;
 pub mod app;
+pub mod audit;
+pub mod balance_feed;
 pub mod bridge;
+pub mod business_metrics;
+pub mod cancel_on_disconnect;
+pub mod checkpoint;
+pub mod clock;
+pub mod collateral;
 pub mod conf;
+pub mod config_watcher;
 pub mod database;
+pub mod deposit_watcher;
+pub mod embedded_db;
+pub mod event_retention;
+pub mod fees;
+pub mod funding;
+pub mod health;
 pub mod init;
+pub mod liquidation;
+pub mod node_client;
+pub mod oracle;
+pub mod pair_locks;
 pub mod prover;
+pub mod proving_scheduler;
+pub mod rate_limit;
+pub mod read_replica;
+pub mod reconciliation;
+pub mod replay;
+pub mod risk;
+pub mod runner;
 pub mod services;
+pub mod settlement_check;
 pub mod setup;
+pub mod snapshot;
+pub mod storage;
+pub mod telemetry;
+#[cfg(feature = "test-mode")]
+pub mod test_harness;
+pub mod withdrawal_queue;
@@ -0,0 +1,47 @@
+use std::cmp::Reverse;
+
+use orderbook::model::{ExecuteState, MarketStatus, Pair};
+
+/// Positions to liquidate, the most under-margined accounts first: the largest position of each
+/// account under its maintenance margin, on an active market settled in the quote of the account.
+/// Once it is closed, the account is checked again with its other positions.
+pub fn liquidation_candidates(state: &ExecuteState, max: usize) -> Vec<(String, Pair)> {
+    let mut candidates: Vec<(i128, String, Pair)> = Vec::new();
+    for user_info in state.users_info.values() {
+        for quote in user_info.margin_quotes() {
+            let Ok(account) = state.get_margin_account(user_info, &quote) else {
+                continue;
+            };
+            let shortfall = account.maintenance_margin as i128 - account.equity as i128;
+            if shortfall <= 0 {
+                continue;
+            }
+            let largest = user_info
+                .positions
+                .iter()
+                .filter(|position| {
+                    position.pair.1 == quote
+                        && state.pairs_status.get(&position.pair) == Some(&MarketStatus::Active)
+                })
+                .max_by_key(|position| {
+                    let price = state
+                        .perp_markets
+                        .get(&position.pair)
+                        .map(|market| market.index_price)
+                        .filter(|price| *price > 0)
+                        .unwrap_or(position.entry_price);
+                    position.size.unsigned_abs() as u128 * price as u128
+                });
+            if let Some(position) = largest {
+                candidates.push((shortfall, user_info.user.clone(), position.pair.clone()));
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| (Reverse(a.0), &a.1, &a.2).cmp(&(Reverse(b.0), &b.1, &b.2)));
+    candidates
+        .into_iter()
+        .take(max)
+        .map(|(_, user, pair)| (user, pair))
+        .collect()
+}
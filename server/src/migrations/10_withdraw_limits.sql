@@ -0,0 +1,21 @@
+-- Append only, latest line (max commit_id) of a symbol is its current withdraw limit.
+-- NULL limits mean that withdrawals of the asset are not limited.
+CREATE TABLE asset_withdraw_limits (
+    commit_id bigint NOT NULL,
+    symbol TEXT NOT NULL,
+    max_amount bigint,
+    window_blocks bigint,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (symbol, commit_id)
+);
+
+-- Append only, latest line (max commit_id) of an identity and symbol is its current withdrawal window
+CREATE TABLE user_withdrawal_windows (
+    commit_id bigint NOT NULL,
+    identity TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    window_start bigint NOT NULL,
+    withdrawn bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (identity, symbol, commit_id)
+);
@@ -0,0 +1,7 @@
+-- Blob tx an action was sent in, and the index of its blob in that tx.
+-- Several consecutive actions share the same blob tx when blob batching is enabled.
+ALTER TABLE blob_tx_outbox ADD COLUMN sent_tx_hash text;
+ALTER TABLE blob_tx_outbox ADD COLUMN blob_index integer;
+
+CREATE INDEX blob_tx_outbox_sent_tx_hash_idx
+  ON blob_tx_outbox (sent_tx_hash);
@@ -0,0 +1,3 @@
+-- Proof tx submitted for a prover request, so that a restarted prover does not prove it again.
+ALTER TABLE prover_requests ADD COLUMN proof_tx_hash text;
+ALTER TABLE prover_requests ADD COLUMN proved_at timestamptz;
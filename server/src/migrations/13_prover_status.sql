@@ -0,0 +1,15 @@
+-- Proving progress of the actions, reported by the prover status API.
+ALTER TABLE prover_requests ADD COLUMN proving_started_at timestamptz;
+
+-- Outcome of the actions whose tx settled, once their prover requests are deleted.
+CREATE TABLE prover_settlements (
+  commit_id bigint PRIMARY KEY,
+  tx_hash text NOT NULL,
+  proof_tx_hash text,
+  status text NOT NULL CHECK (status IN ('settled', 'failed')),
+  proving_started_at timestamptz,
+  proved_at timestamptz,
+  settled_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX prover_settlements_tx_hash_idx ON prover_settlements (tx_hash);
@@ -0,0 +1,12 @@
+-- Withdrawal destinations saved by users, with a label. Only stored offchain:
+-- withdrawals can still be sent to any destination.
+CREATE TABLE withdrawal_addresses (
+    identity text NOT NULL,
+    network text NOT NULL,
+    address text NOT NULL,
+    label text NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    last_used_at timestamptz,
+    use_count integer NOT NULL DEFAULT 0,
+    PRIMARY KEY (identity, network, address)
+);
@@ -0,0 +1,23 @@
+-- Business counters exported as metrics, persisted so that they carry on across restarts.
+-- Rows without a pair are global counters.
+CREATE TABLE metric_counters (
+  name text NOT NULL,
+  pair text NOT NULL DEFAULT '',
+  value bigint NOT NULL DEFAULT 0,
+  updated_at timestamptz NOT NULL DEFAULT now(),
+  PRIMARY KEY (name, pair)
+);
+
+-- Seed the counters with the history already in the database
+INSERT INTO metric_counters (name, pair, value)
+SELECT 'orderbook.commits.total', '', count(*) FROM commits;
+
+INSERT INTO metric_counters (name, pair, value)
+SELECT 'orderbook.trades.total', i.symbol, count(*)
+FROM trade_events t JOIN instruments i ON i.instrument_id = t.instrument_id
+GROUP BY i.symbol;
+
+INSERT INTO metric_counters (name, pair, value)
+SELECT 'orderbook.volume.total', i.symbol, sum(t.qty)::bigint
+FROM trade_events t JOIN instruments i ON i.instrument_id = t.instrument_id
+GROUP BY i.symbol;
@@ -0,0 +1,63 @@
+-- order_events, trade_events and balance_events are partitioned by commit range, so that the
+-- partitions past the retention window can be archived and dropped as a whole.
+-- Existing rows are kept in a first `_legacy` partition, and rows outside of any partition
+-- land in a `_default` one. The server creates the following partitions ahead of the commits.
+
+-- Commit ranges [from_commit, to_commit) partitioning the three tables alike.
+-- The partition of each table is named after the table and the suffix.
+CREATE TABLE event_partitions (
+  suffix text PRIMARY KEY,
+  -- NULL for the legacy partition, which holds every commit before to_commit
+  from_commit bigint,
+  to_commit bigint NOT NULL,
+  created_at timestamptz NOT NULL DEFAULT now(),
+  -- Where the rows were archived before the partitions were dropped
+  archived_to text,
+  pruned_at timestamptz
+);
+
+CREATE FUNCTION partition_event_table(p_table text, p_id_column text, p_to_commit bigint)
+RETURNS void
+LANGUAGE plpgsql AS $$
+DECLARE
+  legacy text := p_table || '_legacy';
+BEGIN
+  EXECUTE format('ALTER TABLE %I RENAME TO %I', p_table, legacy);
+  -- The primary key of a partitioned table must include the partition key
+  EXECUTE format('ALTER TABLE %I DROP CONSTRAINT %I', legacy, p_table || '_pkey');
+  EXECUTE format('ALTER TABLE %I ADD PRIMARY KEY (%I, commit_id)', legacy, p_id_column);
+  EXECUTE format(
+    'CREATE TABLE %I (LIKE %I INCLUDING DEFAULTS INCLUDING CONSTRAINTS) PARTITION BY RANGE (commit_id)',
+    p_table, legacy
+  );
+  EXECUTE format('ALTER TABLE %I ADD PRIMARY KEY (%I, commit_id)', p_table, p_id_column);
+  EXECUTE format(
+    'ALTER SEQUENCE %I OWNED BY %I.%I',
+    p_table || '_' || p_id_column || '_seq', p_table, p_id_column
+  );
+  EXECUTE format(
+    'ALTER TABLE %I ATTACH PARTITION %I FOR VALUES FROM (MINVALUE) TO (%s)',
+    p_table, legacy, p_to_commit
+  );
+  EXECUTE format('CREATE TABLE %I PARTITION OF %I DEFAULT', p_table || '_default', p_table);
+END;
+$$;
+
+DO $$
+DECLARE
+  legacy_to_commit bigint := COALESCE((SELECT MAX(commit_id) FROM commits), -1) + 1;
+BEGIN
+  PERFORM partition_event_table('order_events', 'event_id', legacy_to_commit);
+  PERFORM partition_event_table('trade_events', 'trade_id', legacy_to_commit);
+  PERFORM partition_event_table('balance_events', 'event_id', legacy_to_commit);
+  INSERT INTO event_partitions (suffix, from_commit, to_commit) VALUES ('legacy', NULL, legacy_to_commit);
+END;
+$$;
+
+DROP FUNCTION partition_event_table(text, text, bigint);
+
+-- Indexes of the legacy tables, created on every partition
+CREATE INDEX order_events_commit_id_idx ON order_events (commit_id);
+CREATE INDEX trade_events_commit_id_idx ON trade_events (commit_id);
+CREATE INDEX trade_events_time_idx ON trade_events (trade_time);
+CREATE INDEX balance_events_identity_asset_commit_idx ON balance_events (identity, asset_id, commit_id);
@@ -0,0 +1,14 @@
+-- Signed claims of the state commitment after a commit, published so that third parties can
+-- compare them with what was settled onchain.
+CREATE TABLE state_checkpoints (
+  commit_id bigint PRIMARY KEY,
+  state_commitment bytea NOT NULL,
+  -- Height of the DA when the checkpoint was signed
+  da_height bigint NOT NULL,
+  -- Unix timestamp in milliseconds, as signed
+  timestamp_ms bigint NOT NULL,
+  public_key bytea NOT NULL,
+  signature bytea NOT NULL,
+  -- Blob transaction carrying the checkpoint, when published to the DA
+  da_tx_hash text
+);
@@ -0,0 +1,4 @@
+-- Where the balances of an asset come from and are withdrawn to, as configured on the server:
+-- a token contract on Hyli, or the bridge. NULL for assets with no backing.
+-- Refreshed by the server on startup, and served by the API with the assets.
+ALTER TABLE assets ADD COLUMN backing text CHECK (backing IN ('hyli_token', 'bridge'));
@@ -0,0 +1,17 @@
+-- API keys of programmatic traders, authenticating the x-api-key header as their identity.
+-- Only the SHA3-256 hash of a key is stored: the key itself is returned once, on creation.
+CREATE TABLE api_keys (
+    key_id bigserial PRIMARY KEY,
+    identity text NOT NULL,
+    key_hash bytea NOT NULL UNIQUE,
+    -- First characters of the key, for users to tell their keys apart
+    prefix text NOT NULL,
+    label text NOT NULL,
+    -- Session key the API key is bound to, if any: requests can only use that session key
+    session_public_key bytea,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    last_used_at timestamptz,
+    revoked_at timestamptz
+);
+
+CREATE INDEX api_keys_identity_idx ON api_keys (identity);
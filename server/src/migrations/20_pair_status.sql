@@ -0,0 +1,11 @@
+ALTER TYPE market_status ADD VALUE IF NOT EXISTS 'delisted';
+
+-- Append only, latest line (max commit_id) of an instrument is its status at that commit.
+-- Instruments without any line are active.
+CREATE TABLE instrument_status_updates (
+    commit_id bigint NOT NULL,
+    symbol TEXT NOT NULL,
+    status market_status NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (symbol, commit_id)
+);
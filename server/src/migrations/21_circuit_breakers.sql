@@ -0,0 +1,17 @@
+-- Block until which the orders that would trade on an instrument are refused by its circuit breaker
+ALTER TABLE instruments ADD COLUMN halted_until_block bigint NOT NULL DEFAULT 0;
+
+-- Append only, latest line (max commit_id) of an instrument is its circuit breaker at that commit.
+-- NULL configs mean that the instrument has no circuit breaker.
+CREATE TABLE circuit_breaker_states (
+    commit_id bigint NOT NULL,
+    symbol TEXT NOT NULL,
+    max_move_bps bigint,
+    window_blocks bigint,
+    halt_blocks bigint,
+    reference_price bigint,
+    window_start bigint NOT NULL DEFAULT 0,
+    halted_until bigint NOT NULL DEFAULT 0,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (symbol, commit_id)
+);
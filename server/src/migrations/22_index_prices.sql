@@ -0,0 +1,11 @@
+-- Append only, latest line of a pair is its current index price
+CREATE TABLE index_prices (
+    id bigserial PRIMARY KEY,
+    symbol TEXT NOT NULL,
+    -- Median of the prices of the sources that answered
+    price bigint NOT NULL,
+    sources int NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX index_prices_symbol_idx ON index_prices (symbol, id DESC);
@@ -0,0 +1,27 @@
+-- 'perp' instruments are perpetual futures: their base asset is synthetic and never delivered
+ALTER TABLE instruments ADD COLUMN kind TEXT NOT NULL DEFAULT 'spot' CHECK (kind IN ('spot', 'perp'));
+
+-- Append only, latest line (max commit_id) of a symbol is its perp market at that commit
+CREATE TABLE perp_markets (
+    commit_id bigint NOT NULL,
+    symbol TEXT NOT NULL,
+    initial_margin_bps bigint NOT NULL,
+    maintenance_margin_bps bigint NOT NULL,
+    funding_index bigint NOT NULL DEFAULT 0,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (symbol, commit_id)
+);
+
+-- Append only, latest line (max commit_id) of an identity and symbol is its current position.
+-- A zero size means that the position is closed.
+CREATE TABLE user_positions (
+    commit_id bigint NOT NULL,
+    identity TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    size bigint NOT NULL,
+    entry_price bigint NOT NULL,
+    margin bigint NOT NULL,
+    funding_index bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (identity, symbol, commit_id)
+);
@@ -0,0 +1,11 @@
+-- Funding paid by each position on each settlement, negative when received
+CREATE TABLE funding_payments (
+    commit_id bigint NOT NULL,
+    identity TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    amount bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (identity, symbol, commit_id)
+);
+
+CREATE INDEX funding_payments_symbol_idx ON funding_payments (symbol, commit_id);
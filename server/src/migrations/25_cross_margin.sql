@@ -0,0 +1,38 @@
+-- Append only, latest line (max commit_id) of a symbol is its current collateral config,
+-- with the last index price it is valued at. NULL configs mean that the asset is not a collateral.
+CREATE TABLE asset_collaterals (
+    commit_id bigint NOT NULL,
+    symbol TEXT NOT NULL,
+    quote TEXT,
+    weight_bps bigint,
+    price bigint NOT NULL DEFAULT 0,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (symbol, commit_id)
+);
+
+ALTER TABLE perp_markets ADD COLUMN index_price bigint NOT NULL DEFAULT 0;
+
+-- Append only, latest line (max commit_id) of an identity and symbol is its current debt.
+-- A zero amount means that the debt is repaid.
+CREATE TABLE user_debts (
+    commit_id bigint NOT NULL,
+    identity TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    amount bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (identity, symbol, commit_id)
+);
+
+-- Margin accounts whose equity fell under their maintenance margin
+CREATE TABLE margin_calls (
+    commit_id bigint NOT NULL,
+    identity TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    equity bigint NOT NULL,
+    initial_margin bigint NOT NULL,
+    maintenance_margin bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (identity, quote, commit_id)
+);
+
+CREATE INDEX margin_calls_commit_idx ON margin_calls (commit_id);
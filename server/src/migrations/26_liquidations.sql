@@ -0,0 +1,14 @@
+-- Positions force-closed on the book, with the margin account they were liquidated from
+CREATE TABLE liquidations (
+    liquidation_id bigserial PRIMARY KEY,
+    commit_id bigint NOT NULL,
+    identity TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    size bigint NOT NULL,
+    equity bigint NOT NULL,
+    maintenance_margin bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX liquidations_identity_idx ON liquidations (identity, commit_id);
+CREATE INDEX liquidations_symbol_idx ON liquidations (symbol, commit_id);
@@ -0,0 +1,14 @@
+ALTER TABLE perp_markets ADD COLUMN liquidation_penalty_bps bigint NOT NULL DEFAULT 0;
+
+-- Insurance fund movements on liquidations: penalties collected (positive change) and losses
+-- of bankrupt accounts absorbed (negative change)
+CREATE TABLE insurance_fund_events (
+    event_id bigserial PRIMARY KEY,
+    commit_id bigint NOT NULL,
+    identity TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    change bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX insurance_fund_events_symbol_idx ON insurance_fund_events (symbol, commit_id);
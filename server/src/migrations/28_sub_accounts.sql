@@ -0,0 +1,5 @@
+-- Sub-accounts are users named `<parent>/<name>`, keyed with the salt of their parent
+ALTER TABLE users ADD COLUMN parent_identity TEXT;
+ALTER TABLE users ADD COLUMN sub_account TEXT;
+
+CREATE INDEX users_parent_identity_idx ON users (parent_identity) WHERE parent_identity IS NOT NULL;
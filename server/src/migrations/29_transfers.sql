@@ -0,0 +1,13 @@
+-- Balances moved between accounts of the orderbook, without any onchain token transfer
+CREATE TABLE transfers (
+    transfer_id bigserial PRIMARY KEY,
+    commit_id bigint NOT NULL,
+    from_identity TEXT NOT NULL,
+    to_identity TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    amount bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX transfers_from_identity_idx ON transfers (from_identity, commit_id);
+CREATE INDEX transfers_to_identity_idx ON transfers (to_identity, commit_id);
@@ -0,0 +1,12 @@
+-- Dust balances of several assets sold for a quote asset in a single action
+CREATE TABLE dust_sweeps (
+    sweep_id bigserial PRIMARY KEY,
+    commit_id bigint NOT NULL,
+    identity TEXT NOT NULL,
+    quote TEXT NOT NULL,
+    symbols TEXT[] NOT NULL,
+    proceeds bigint NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX dust_sweeps_identity_idx ON dust_sweeps (identity, commit_id);
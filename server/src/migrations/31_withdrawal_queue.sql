@@ -0,0 +1,27 @@
+-- Append only, latest line (max commit_id) of a symbol is its current withdraw delay.
+-- NULL delays mean that withdrawals of the asset are never queued.
+CREATE TABLE asset_withdraw_delays (
+    commit_id bigint NOT NULL,
+    symbol TEXT NOT NULL,
+    threshold bigint,
+    delay_blocks bigint,
+    requires_approval boolean,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (symbol, commit_id)
+);
+
+-- Append only, latest line (max commit_id) of an identity and withdrawal id is the current state
+-- of a queued withdrawal. Only 'queued' and 'approved' withdrawals are still in the queue.
+CREATE TABLE user_pending_withdrawals (
+    commit_id bigint NOT NULL,
+    identity TEXT NOT NULL,
+    withdrawal_id bigint NOT NULL,
+    symbol TEXT NOT NULL,
+    amount bigint NOT NULL,
+    network TEXT NOT NULL,
+    address TEXT NOT NULL,
+    available_at bigint NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('queued', 'approved', 'rejected', 'released')),
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (identity, withdrawal_id, commit_id)
+);
@@ -0,0 +1,11 @@
+-- Settled token transfers to the orderbook account credited as deposits, one line per transfer blob.
+-- Blocks read again from the DA are not credited twice.
+CREATE TABLE deposit_transfers (
+    tx_hash bytea NOT NULL,
+    blob_index integer NOT NULL,
+    identity TEXT NOT NULL,
+    contract_name TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (tx_hash, blob_index)
+);
@@ -0,0 +1,2 @@
+-- ERC20 contract of the transfer, NULL for the collateral token transfers stored before
+ALTER TABLE bridge_eth_pending_txs ADD COLUMN token_address BYTEA;
@@ -0,0 +1,6 @@
+-- Append only, latest line (max commit_id) is the current pause of the bridge
+CREATE TABLE bridge_pauses (
+    commit_id bigint NOT NULL PRIMARY KEY,
+    paused boolean NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
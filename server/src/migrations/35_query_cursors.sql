@@ -0,0 +1,16 @@
+-- Query endpoints page through their rows by (commit_id, id), so that pages stay stable while
+-- rows are appended. Orders are ordered by the commit that created them.
+ALTER TABLE orders ADD COLUMN commit_id bigint;
+
+UPDATE orders o SET commit_id = e.commit_id
+FROM (SELECT order_id, MIN(commit_id) AS commit_id FROM order_events GROUP BY order_id) e
+WHERE e.order_id = o.order_id;
+
+UPDATE orders SET commit_id = 0 WHERE commit_id IS NULL;
+
+ALTER TABLE orders ALTER COLUMN commit_id SET NOT NULL;
+
+CREATE INDEX orders_identity_commit_idx ON orders (identity, commit_id, order_id);
+CREATE INDEX trade_events_taker_commit_idx ON trade_events (taker_identity, commit_id, trade_id);
+CREATE INDEX trade_events_maker_commit_idx ON trade_events (maker_identity, commit_id, trade_id);
+CREATE INDEX balance_events_identity_commit_idx ON balance_events (identity, commit_id, event_id);
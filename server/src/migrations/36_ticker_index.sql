@@ -0,0 +1,2 @@
+-- The ticker cache seeds the last price of each pair from its latest trade
+CREATE INDEX trade_events_instrument_trade_idx ON trade_events (instrument_id, trade_id);
@@ -0,0 +1,28 @@
+-- Privileged actions: admin requests, bridge address claims, large withdrawals and config changes.
+-- Entries are only ever appended.
+CREATE TABLE audit_log (
+    audit_id bigserial PRIMARY KEY,
+    action TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    -- Hash of the request, telling apart the entries of replayed or distinct requests
+    fingerprint TEXT NOT NULL,
+    -- Response status of an admin request, NULL for the other actions
+    status smallint,
+    details jsonb NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+
+CREATE INDEX audit_log_action_idx ON audit_log (action, audit_id);
+CREATE INDEX audit_log_actor_idx ON audit_log (actor, audit_id);
+
+CREATE FUNCTION audit_log_append_only()
+RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+  RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$;
+
+CREATE TRIGGER audit_log_append_only
+BEFORE UPDATE OR DELETE ON audit_log
+FOR EACH ROW EXECUTE FUNCTION audit_log_append_only();
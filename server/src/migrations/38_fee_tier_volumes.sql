@@ -0,0 +1,54 @@
+DROP FUNCTION get_rolling_user_volumes(interval);
+
+-- Rolling traded volume of each user over the given window, in whole units of the reference
+-- asset. Both the maker and the taker of a trade are credited with its notional.
+-- The notional of a trade is summed per quote asset, in whole units of that asset, then
+-- converted with the latest index price of the `{quote}/{reference}` pair. Volumes in a quote
+-- asset without such an index price are not counted.
+CREATE FUNCTION get_rolling_user_volumes(p_window interval, p_reference_asset text)
+RETURNS TABLE (identity text, volume numeric)
+LANGUAGE sql STABLE AS $$
+  WITH trades AS (
+    SELECT
+      t.maker_identity,
+      t.taker_identity,
+      quote_asset.symbol AS quote_symbol,
+      (t.price::numeric * t.qty::numeric)
+        / power(10::numeric, base_asset.scale)
+        / power(10::numeric, quote_asset.scale) AS notional
+    FROM trade_events t
+    JOIN instruments i      ON t.instrument_id = i.instrument_id
+    JOIN assets base_asset  ON i.base_asset_id = base_asset.asset_id
+    JOIN assets quote_asset ON i.quote_asset_id = quote_asset.asset_id
+    WHERE t.trade_time >= now() - p_window
+  ),
+  quote_volumes AS (
+    SELECT v.identity, v.quote_symbol, SUM(v.notional) AS volume
+    FROM (
+      SELECT maker_identity AS identity, quote_symbol, notional FROM trades
+      UNION ALL
+      SELECT taker_identity AS identity, quote_symbol, notional FROM trades
+    ) v
+    GROUP BY v.identity, v.quote_symbol
+  ),
+  -- Value of a whole unit of each quote asset, in whole units of the reference asset
+  rates AS (
+    SELECT reference.symbol AS quote_symbol, 1::numeric AS rate
+    FROM assets reference
+    WHERE reference.symbol = p_reference_asset
+    UNION ALL
+    (
+      SELECT DISTINCT ON (p.symbol)
+        split_part(p.symbol, '/', 1) AS quote_symbol,
+        p.price::numeric / power(10::numeric, reference.scale) AS rate
+      FROM index_prices p
+      JOIN assets reference ON reference.symbol = p_reference_asset
+      WHERE p.symbol = split_part(p.symbol, '/', 1) || '/' || p_reference_asset
+      ORDER BY p.symbol, p.id DESC
+    )
+  )
+  SELECT q.identity, SUM(q.volume * r.rate) AS volume
+  FROM quote_volumes q
+  JOIN rates r ON r.quote_symbol = q.quote_symbol
+  GROUP BY q.identity;
+$$;
@@ -0,0 +1,4 @@
+-- Scopes of the restricted session keys of a user, stored alongside the session keys
+-- snapshot of the same commit. Keys without a scope are unrestricted.
+ALTER TABLE user_session_keys
+  ADD COLUMN session_key_scopes jsonb NOT NULL DEFAULT '[]'::jsonb;
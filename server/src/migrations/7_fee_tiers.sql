@@ -0,0 +1,36 @@
+-- Append only, latest line (max commit_id) is the current fee tier of a user
+CREATE TABLE user_fee_tiers (
+    commit_id bigint NOT NULL,
+    identity TEXT NOT NULL,
+    tier smallint NOT NULL,
+    maker_fee_bps integer NOT NULL,
+    taker_fee_bps integer NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (identity, commit_id)
+);
+
+CREATE INDEX trade_events_trade_time_idx ON trade_events (trade_time);
+
+-- Rolling traded volume of each user over the given window, in quote asset units.
+-- Both the maker and the taker of a trade are credited with its notional.
+CREATE OR REPLACE FUNCTION get_rolling_user_volumes(p_window interval)
+RETURNS TABLE (identity text, volume numeric)
+LANGUAGE sql STABLE AS $$
+  WITH trades AS (
+    SELECT
+      t.maker_identity,
+      t.taker_identity,
+      (t.price::numeric * t.qty::numeric) / power(10::numeric, base_asset.scale) AS notional
+    FROM trade_events t
+    JOIN instruments i      ON t.instrument_id = i.instrument_id
+    JOIN assets base_asset  ON i.base_asset_id = base_asset.asset_id
+    WHERE t.trade_time >= now() - p_window
+  )
+  SELECT v.identity, SUM(v.notional) AS volume
+  FROM (
+    SELECT maker_identity AS identity, notional FROM trades
+    UNION ALL
+    SELECT taker_identity AS identity, notional FROM trades
+  ) v
+  GROUP BY v.identity;
+$$;
@@ -0,0 +1,8 @@
+-- Per identity pre-trade limits, managed through the admin API.
+-- NULL limits fall back to the defaults of the server configuration.
+CREATE TABLE risk_limits (
+    identity TEXT PRIMARY KEY,
+    max_order_notional bigint,
+    max_open_notional bigint,
+    updated_at timestamptz NOT NULL DEFAULT now()
+);
@@ -0,0 +1,7 @@
+-- Identifiers given by clients to their orders, echoed in order events so that
+-- trading systems can correlate fills with their own ids
+ALTER TABLE orders ADD COLUMN client_order_id text, ADD COLUMN tag text;
+ALTER TABLE order_events ADD COLUMN client_order_id text, ADD COLUMN tag text;
+
+CREATE INDEX orders_identity_client_order_id_idx ON orders (identity, client_order_id)
+    WHERE client_order_id IS NOT NULL;
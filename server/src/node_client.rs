@@ -0,0 +1,223 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use client_sdk::rest_client::{NodeApiClient, NodeApiHttpClient};
+use rand::Rng;
+use sdk::{BlobTransaction, BlockHeight, ProofTransaction, TxHash};
+use serde::Serialize;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::conf::NodeClientConfig;
+
+/// How long a block height fetched from the node is reused by [`NodeClient::recent_block_height`]
+const BLOCK_HEIGHT_MAX_AGE: Duration = Duration::from_millis(500);
+
+/// Node API client shared by the modules talking to the node.
+/// Each request is bounded by a timeout and retried with a jittered exponential backoff.
+/// After too many consecutive failures the circuit opens: requests fail fast until a single
+/// one is let through again, closing the circuit if it succeeds.
+pub struct NodeClient {
+    inner: Arc<NodeApiHttpClient>,
+    config: NodeClientConfig,
+    breaker: Mutex<CircuitBreaker>,
+    /// Last block height fetched, with when it was
+    block_height: Mutex<Option<(Instant, BlockHeight)>>,
+}
+
+#[derive(Default)]
+struct CircuitBreaker {
+    state: CircuitState,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+}
+
+#[derive(Default, Clone, Copy)]
+enum CircuitState {
+    #[default]
+    Closed,
+    Open {
+        until: Instant,
+    },
+    /// A single request is in flight to probe the node
+    HalfOpen {
+        since: Instant,
+    },
+}
+
+/// Health of the node connection, as seen by the circuit breaker
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct NodeClientHealth {
+    /// "closed", "open" or "half_open"
+    pub circuit: &'static str,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+impl NodeClient {
+    pub fn new(inner: Arc<NodeApiHttpClient>, config: NodeClientConfig) -> Self {
+        NodeClient {
+            inner,
+            config,
+            breaker: Mutex::new(CircuitBreaker::default()),
+            block_height: Mutex::new(None),
+        }
+    }
+
+    pub async fn send_tx_blob(&self, tx: BlobTransaction) -> Result<TxHash> {
+        self.call("send_tx_blob", move |client| {
+            let tx = tx.clone();
+            async move { client.send_tx_blob(tx).await }
+        })
+        .await
+    }
+
+    pub async fn send_tx_proof(&self, tx: ProofTransaction) -> Result<TxHash> {
+        self.call("send_tx_proof", move |client| {
+            let tx = tx.clone();
+            async move { client.send_tx_proof(tx).await }
+        })
+        .await
+    }
+
+    pub async fn get_block_height(&self) -> Result<BlockHeight> {
+        let height = self
+            .call("get_block_height", |client| async move {
+                client.get_block_height().await
+            })
+            .await?;
+        *self.block_height.lock().expect("block height poisoned") = Some((Instant::now(), height));
+        Ok(height)
+    }
+
+    /// Block height fetched at most [`BLOCK_HEIGHT_MAX_AGE`] ago, for the hot paths that only
+    /// need a height the chain already reached. Falls back to the last fetched one when the
+    /// node cannot be reached.
+    pub async fn recent_block_height(&self) -> Result<BlockHeight> {
+        let cached = *self.block_height.lock().expect("block height poisoned");
+        match cached {
+            Some((fetched_at, height)) if fetched_at.elapsed() < BLOCK_HEIGHT_MAX_AGE => Ok(height),
+            Some((_, height)) => Ok(self.get_block_height().await.unwrap_or(height)),
+            None => self.get_block_height().await,
+        }
+    }
+
+    /// Sends a request to the node through the timeout, retries and circuit breaker
+    pub async fn call<T, F, Fut>(&self, operation: &'static str, request: F) -> Result<T>
+    where
+        F: Fn(Arc<NodeApiHttpClient>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let mut backoff = Duration::from_millis(self.config.retry_backoff_ms);
+        let mut attempt = 0;
+        loop {
+            self.acquire(operation)?;
+
+            let result = match tokio::time::timeout(timeout, request(self.inner.clone())).await {
+                Ok(result) => result,
+                Err(_) => Err(anyhow!(
+                    "Node request {operation} timed out after {timeout:?}"
+                )),
+            };
+            let e = match result {
+                Ok(value) => {
+                    self.record_success();
+                    return Ok(value);
+                }
+                Err(e) => e,
+            };
+            self.record_failure(operation, &e);
+
+            if attempt >= self.config.max_retries {
+                return Err(e);
+            }
+            attempt += 1;
+            // Jitter keeps the modules from retrying against the node in lockstep
+            let jitter = rand::rng().random_range(0..=backoff.as_millis() as u64 / 2);
+            let delay = backoff + Duration::from_millis(jitter);
+            warn!(
+                "Node request {operation} failed, retry {attempt}/{} in {delay:?}: {e:#}",
+                self.config.max_retries
+            );
+            tokio::time::sleep(delay).await;
+            backoff *= 2;
+        }
+    }
+
+    pub fn health(&self) -> NodeClientHealth {
+        let breaker = self.breaker.lock().expect("node circuit breaker poisoned");
+        NodeClientHealth {
+            circuit: match breaker.state {
+                CircuitState::Closed => "closed",
+                CircuitState::Open { .. } => "open",
+                CircuitState::HalfOpen { .. } => "half_open",
+            },
+            consecutive_failures: breaker.consecutive_failures,
+            last_error: breaker.last_error.clone(),
+        }
+    }
+
+    /// Whether a request may be sent to the node
+    fn acquire(&self, operation: &str) -> Result<()> {
+        let mut breaker = self.breaker.lock().expect("node circuit breaker poisoned");
+        let now = Instant::now();
+        // A probe whose caller gave up before it completed does not hold the circuit half open
+        let probe_timeout = Duration::from_millis(self.config.timeout_ms);
+        match breaker.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open { until } if now >= until => {
+                breaker.state = CircuitState::HalfOpen { since: now };
+                Ok(())
+            }
+            CircuitState::HalfOpen { since } if now >= since + probe_timeout => {
+                breaker.state = CircuitState::HalfOpen { since: now };
+                Ok(())
+            }
+            CircuitState::Open { until } => Err(anyhow!(
+                "Node circuit is open, {operation} not sent (retrying in {:?})",
+                until.saturating_duration_since(now)
+            )),
+            CircuitState::HalfOpen { .. } => Err(anyhow!(
+                "Node circuit is half open, {operation} not sent while probing the node"
+            )),
+        }
+    }
+
+    fn record_success(&self) {
+        let mut breaker = self.breaker.lock().expect("node circuit breaker poisoned");
+        if !matches!(breaker.state, CircuitState::Closed) {
+            info!("✅ Node reachable again, closing the circuit");
+        }
+        breaker.state = CircuitState::Closed;
+        breaker.consecutive_failures = 0;
+    }
+
+    fn record_failure(&self, operation: &str, e: &anyhow::Error) {
+        let mut breaker = self.breaker.lock().expect("node circuit breaker poisoned");
+        breaker.consecutive_failures += 1;
+        breaker.last_error = Some(format!("{operation}: {e:#}"));
+
+        let should_open = match breaker.state {
+            CircuitState::HalfOpen { .. } => true,
+            CircuitState::Closed => {
+                breaker.consecutive_failures >= self.config.failure_threshold.max(1)
+            }
+            CircuitState::Open { .. } => false,
+        };
+        if should_open {
+            let open_duration = Duration::from_secs(self.config.open_duration_secs);
+            warn!(
+                "⚠️ {} consecutive node failures, opening the circuit for {open_duration:?}",
+                breaker.consecutive_failures
+            );
+            breaker.state = CircuitState::Open {
+                until: Instant::now() + open_duration,
+            };
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use hyli_modules::{
+    bus::{BusClientSender, SharedMessageBus},
+    log_error, module_bus_client, module_handle_messages,
+    modules::Module,
+};
+use orderbook::model::Pair;
+use tracing::{debug, warn};
+
+use crate::{
+    app::OrderbookRequest,
+    conf::{OracleConfig, OracleSource},
+    services::index_price_service::IndexPriceService,
+};
+
+/// Periodically pulls the index prices of the pairs from the oracle sources, stores them,
+/// and asks the orderbook module to feed them to the circuit breakers.
+pub struct OracleModule {
+    bus: OracleModuleBusClient,
+    config: OracleConfig,
+    indexer_url: String,
+    http: reqwest::Client,
+    index_price_service: Arc<IndexPriceService>,
+}
+
+pub struct OracleModuleCtx {
+    pub config: OracleConfig,
+    pub indexer_url: String,
+    pub index_price_service: Arc<IndexPriceService>,
+}
+
+module_bus_client! {
+#[derive(Debug)]
+pub struct OracleModuleBusClient {
+    sender(OrderbookRequest),
+}
+}
+
+impl Module for OracleModule {
+    type Context = Arc<OracleModuleCtx>;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = OracleModuleBusClient::new_from_bus(bus.new_handle()).await;
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_millis(ctx.config.timeout_ms.max(1)))
+            .build()
+            .context("building the oracle http client")?;
+
+        Ok(OracleModule {
+            bus,
+            config: ctx.config.clone(),
+            indexer_url: ctx.indexer_url.trim_end_matches('/').to_string(),
+            http,
+            index_price_service: ctx.index_price_service.clone(),
+        })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.interval_secs.max(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        module_handle_messages! {
+            on_self self,
+            _ = interval.tick() => {
+                _ = log_error!(self.refresh_index_prices().await, "refresh index prices");
+            }
+        };
+
+        Ok(())
+    }
+}
+
+impl OracleModule {
+    async fn refresh_index_prices(&mut self) -> Result<()> {
+        let this = &*self;
+        let pulls = futures::future::join_all(
+            this.config
+                .sources
+                .iter()
+                .map(|source| async move { (source, this.pull(source).await) }),
+        )
+        .await;
+
+        let mut prices: BTreeMap<&str, Vec<u64>> = BTreeMap::new();
+        for (source, price) in pulls {
+            match price {
+                Ok(price) => prices.entry(&source.symbol).or_default().push(price),
+                Err(e) => warn!("Failed to pull the index price of {}: {e:#}", source.symbol),
+            }
+        }
+
+        let mut index_prices: Vec<(Pair, u64)> = Vec::with_capacity(prices.len());
+        for (symbol, mut source_prices) in prices {
+            source_prices.sort_unstable();
+            let price = median(&source_prices);
+            debug!(
+                "Index price of {symbol} is {price}, from {} sources",
+                source_prices.len()
+            );
+            self.index_price_service
+                .record(symbol, price, source_prices.len())
+                .await
+                .map_err(|e| anyhow!("Failed to store the index price of {symbol}: {}", e.1))?;
+
+            let Some((base, quote)) = symbol.split_once('/') else {
+                continue;
+            };
+            index_prices.push(((base.to_string(), quote.to_string()), price));
+        }
+
+        if !index_prices.is_empty() {
+            self.bus
+                .send(OrderbookRequest::UpdateIndexPrices(index_prices))?;
+        }
+
+        Ok(())
+    }
+
+    /// Price of a source, in the price unit of its pair
+    async fn pull(&self, source: &OracleSource) -> Result<u64> {
+        let url = if source.url.is_empty() {
+            format!(
+                "{}/v1/indexer/contract/{}/state",
+                self.indexer_url, source.contract_name
+            )
+        } else {
+            source.url.clone()
+        };
+        let document: serde_json::Value = self
+            .http
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let price = match document.pointer(&source.pointer) {
+            Some(serde_json::Value::Number(number)) => number.to_string(),
+            Some(serde_json::Value::String(price)) => price.clone(),
+            Some(other) => bail!("{} is not a price: {other}", source.pointer),
+            None => bail!("no {} in the response of {url}", source.pointer),
+        };
+        scale_price(&price, source.decimals)
+    }
+}
+
+fn median(sorted: &[u64]) -> u64 {
+    let middle = sorted.len() / 2;
+    if sorted.len() % 2 == 1 {
+        sorted[middle]
+    } else {
+        ((sorted[middle - 1] as u128 + sorted[middle] as u128) / 2) as u64
+    }
+}
+
+/// Multiplies a decimal price by 10^decimals, truncating the digits below the unit
+fn scale_price(price: &str, decimals: u32) -> Result<u64> {
+    let price = price.trim();
+    let (integer, fraction) = price.split_once('.').unwrap_or((price, ""));
+    if integer.is_empty()
+        || !integer
+            .chars()
+            .chain(fraction.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        bail!("{price} is not a positive decimal price");
+    }
+    let fraction: String = fraction
+        .chars()
+        .chain(std::iter::repeat('0'))
+        .take(decimals as usize)
+        .collect();
+    let scaled = format!("{integer}{fraction}")
+        .parse::<u64>()
+        .with_context(|| format!("{price} scaled by 10^{decimals} overflows"))?;
+    if scaled == 0 {
+        bail!("{price} is zero once scaled by 10^{decimals}");
+    }
+    Ok(scaled)
+}
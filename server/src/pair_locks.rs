@@ -0,0 +1,252 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use orderbook::{
+    model::{Balance, ExecuteState, Order, OrderId, OrderbookEvent, Pair, UserInfo},
+    zk::{smt::GetKey, H256},
+    INSURANCE_FUND_IDENTITY,
+};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+/// Per-pair locks of the light orderbook state.
+/// Order flow on a pair is serialized by its lock, so that actions on different pairs
+/// only share short accesses to the global state.
+#[derive(Default)]
+pub struct PairLocks {
+    locks: std::sync::Mutex<HashMap<Pair, Arc<Mutex<()>>>>,
+}
+
+impl PairLocks {
+    /// Locks all the given pairs. Pairs are always locked in the same order,
+    /// so that concurrent actions spanning several pairs cannot deadlock.
+    pub async fn lock(&self, pairs: &[Pair]) -> Vec<OwnedMutexGuard<()>> {
+        let pairs: BTreeSet<&Pair> = pairs.iter().collect();
+        let locks: Vec<Arc<Mutex<()>>> = {
+            let mut locks = self.locks.lock().expect("pair locks poisoned");
+            pairs
+                .into_iter()
+                .map(|pair| locks.entry(pair.clone()).or_default().clone())
+                .collect()
+        };
+
+        let mut guards = Vec::with_capacity(locks.len());
+        for lock in locks {
+            guards.push(lock.lock_owned().await);
+        }
+        guards
+    }
+}
+
+/// Part of the state shared between pairs that the events of an action were generated from:
+/// the users and balances they update, and the orders they reference.
+/// Events generated under a read lock are only valid if it did not change before they are applied.
+pub struct StateReadSet {
+    users: Vec<(String, Option<UserInfo>)>,
+    balances: Vec<(String, H256, Balance)>,
+    orders: Vec<(OrderId, Option<Order>)>,
+}
+
+impl StateReadSet {
+    pub fn capture(state: &ExecuteState, user_info: &UserInfo, events: &[OrderbookEvent]) -> Self {
+        let mut users = BTreeSet::from([user_info.user.clone()]);
+        let mut balances = BTreeSet::new();
+        let mut orders = BTreeSet::new();
+        let mut margin_quotes = BTreeSet::new();
+        for event in events {
+            match event {
+                OrderbookEvent::BalanceUpdated { user, symbol, .. } => {
+                    users.insert(user.clone());
+                    balances.insert((user.clone(), symbol.clone()));
+                }
+                OrderbookEvent::PositionUpdated { user, position } => {
+                    users.insert(user.clone());
+                    margin_quotes.insert((user.clone(), position.pair.1.clone()));
+                }
+                OrderbookEvent::DebtUpdated { user, debt } => {
+                    users.insert(user.clone());
+                    margin_quotes.insert((user.clone(), debt.symbol.clone()));
+                }
+                OrderbookEvent::MarginCalled { user, account } => {
+                    users.insert(user.clone());
+                    margin_quotes.insert((user.clone(), account.quote.clone()));
+                }
+                OrderbookEvent::Liquidation { user, pair, .. } => {
+                    users.insert(user.clone());
+                    margin_quotes.insert((user.clone(), pair.1.clone()));
+                    // The insurance fund is read even when it does not move
+                    users.insert(INSURANCE_FUND_IDENTITY.to_string());
+                    balances.insert((INSURANCE_FUND_IDENTITY.to_string(), pair.1.clone()));
+                }
+                OrderbookEvent::OrderCreated { order } => {
+                    orders.insert(order.order_id.clone());
+                }
+                OrderbookEvent::OrderCancelled { order_id, .. } => {
+                    orders.insert(order_id.clone());
+                }
+                OrderbookEvent::OrderExecuted {
+                    order_id,
+                    taker_order_id,
+                    ..
+                }
+                | OrderbookEvent::OrderUpdate {
+                    order_id,
+                    taker_order_id,
+                    ..
+                } => {
+                    orders.insert(order_id.clone());
+                    orders.insert(taker_order_id.clone());
+                }
+                _ => {}
+            }
+        }
+
+        // Margin accounts of the users are valued from the balances of their quotes and collaterals
+        for user in &users {
+            let mut quotes: BTreeSet<String> = margin_quotes
+                .iter()
+                .filter(|(quote_user, _)| quote_user == user)
+                .map(|(_, quote)| quote.clone())
+                .collect();
+            if let Some(info) = state.users_info.get(user) {
+                quotes.extend(info.margin_quotes());
+            }
+            for symbol in state.margin_symbols(&quotes) {
+                balances.insert((user.clone(), symbol));
+            }
+        }
+
+        let balances = balances
+            .into_iter()
+            .filter_map(|(user, symbol)| {
+                // The acting user may not be registered in the state yet
+                let key = if user == user_info.user {
+                    user_info.get_key()
+                } else {
+                    state.users_info.get(&user)?.get_key()
+                };
+                let balance = Self::balance(state, &symbol, key);
+                Some((symbol, key, balance))
+            })
+            .collect();
+
+        StateReadSet {
+            users: users
+                .into_iter()
+                .map(|user| {
+                    let info = state.users_info.get(&user).cloned();
+                    (user, info)
+                })
+                .collect(),
+            balances,
+            orders: orders
+                .into_iter()
+                .map(|order_id| {
+                    let order = state.order_manager.orders.get(&order_id).cloned();
+                    (order_id, order)
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether the state still holds the values the events were generated from
+    pub fn is_current(&self, state: &ExecuteState) -> bool {
+        self.users
+            .iter()
+            .all(|(user, info)| state.users_info.get(user) == info.as_ref())
+            && self
+                .balances
+                .iter()
+                .all(|(symbol, key, balance)| Self::balance(state, symbol, *key) == *balance)
+            && self
+                .orders
+                .iter()
+                .all(|(order_id, order)| state.order_manager.orders.get(order_id) == order.as_ref())
+    }
+
+    fn balance(state: &ExecuteState, symbol: &str, key: H256) -> Balance {
+        state
+            .balances
+            .get(symbol)
+            .and_then(|balances| balances.get(&key).cloned())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn pair(base: &str, quote: &str) -> Pair {
+        (base.to_string(), quote.to_string())
+    }
+
+    fn alice() -> UserInfo {
+        UserInfo::new("alice@wallet".to_string(), b"alice".to_vec())
+    }
+
+    fn credit(user_info: &UserInfo, amount: u64) -> OrderbookEvent {
+        OrderbookEvent::BalanceUpdated {
+            user: user_info.user.clone(),
+            symbol: "USDC".to_string(),
+            amount,
+        }
+    }
+
+    #[tokio::test]
+    async fn actions_on_a_pair_are_serialized() {
+        let locks = PairLocks::default();
+        let eth = pair("ETH", "USDC");
+        let btc = pair("BTC", "USDC");
+
+        let _guards = locks.lock(std::slice::from_ref(&eth)).await;
+        let same_pair = tokio::time::timeout(Duration::from_millis(50), locks.lock(&[eth]));
+        assert!(same_pair.await.is_err());
+        let other_pair = tokio::time::timeout(Duration::from_millis(50), locks.lock(&[btc]));
+        assert_eq!(other_pair.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn pairs_are_locked_once_each() {
+        let locks = PairLocks::default();
+        let eth = pair("ETH", "USDC");
+        let btc = pair("BTC", "USDC");
+
+        let guards = locks.lock(&[eth.clone(), btc.clone(), eth.clone()]).await;
+        assert_eq!(guards.len(), 2);
+        drop(guards);
+        assert_eq!(locks.lock(&[btc, eth]).await.len(), 2);
+    }
+
+    #[test]
+    fn read_set_detects_changed_balances_of_the_users() {
+        let user_info = alice();
+        let mut state = ExecuteState::default();
+        let read_set = StateReadSet::capture(&state, &user_info, &[credit(&user_info, 10)]);
+        assert!(read_set.is_current(&state));
+
+        state
+            .update_balances("USDC", vec![(user_info.get_key(), Balance(5))])
+            .unwrap();
+        assert!(!read_set.is_current(&state));
+    }
+
+    #[test]
+    fn read_set_ignores_balances_it_did_not_read() {
+        let user_info = alice();
+        let bob = UserInfo::new("bob@wallet".to_string(), b"bob".to_vec());
+        let mut state = ExecuteState::default();
+        let read_set = StateReadSet::capture(&state, &user_info, &[credit(&user_info, 10)]);
+
+        state
+            .update_balances("USDC", vec![(bob.get_key(), Balance(5))])
+            .unwrap();
+        state
+            .update_balances("ETH", vec![(user_info.get_key(), Balance(5))])
+            .unwrap();
+        assert!(read_set.is_current(&state));
+    }
+}
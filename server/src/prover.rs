@@ -1,29 +1,449 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, bail, Context, Result};
 use client_sdk::{
     helpers::{sp1::SP1Prover, ClientSdkProver},
     rest_client::NodeApiClient,
 };
+use contracts::{ORDERBOOK_ELF, ORDERBOOK_VK};
+use futures::future::BoxFuture;
 use hyli_modules::{
     bus::SharedMessageBus,
     log_error, module_bus_client, module_handle_messages,
     modules::{contract_listener::ContractListenerEvent, Module},
 };
+use opentelemetry::{
+    metrics::{Histogram, Meter, UpDownCounter},
+    trace::{SpanContext, TraceContextExt},
+};
 use orderbook::{
     model::{OrderbookEvent, UserInfo},
     transaction::{OrderbookAction, PermissionedOrderbookAction, PermissionedPrivateInput},
-    zk::FullState,
+    zk::{FullState, ZkVmState},
     ORDERBOOK_ACCOUNT_IDENTITY,
 };
 use sdk::{
-    api::TransactionStatusDb, BlobIndex, Calldata, ContractName, LaneId, ProgramId,
-    ProofTransaction, TxHash,
+    api::TransactionStatusDb, guest, Blob, BlobIndex, Calldata, ContractName, LaneId, ProgramId,
+    ProofData, ProofTransaction, TxContext, TxHash, Verifier,
 };
 use serde::{Deserialize, Serialize};
+use sp1_sdk::{NetworkProver, Prover, ProverClient, SP1ProvingKey, SP1Stdin};
 use sqlx::{PgPool, Row};
-use tokio::sync::Mutex;
-use tracing::{debug, error, info, warn};
+use tokio::sync::{oneshot, Mutex};
+use tracing::{debug, error, info, warn, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::{
+    balance_feed::BalanceFeed,
+    conf::{ProverBackendKind, ProverNetworkConfig},
+    node_client::NodeClient,
+    proving_scheduler::ProvingScheduler,
+    telemetry,
+};
+
+pub type OrderbookProver = Arc<dyn ClientSdkProver<Vec<Calldata>> + Send + Sync>;
+
+/// zkVM the orderbook guest is compiled for and proven with
+pub trait ProvingBackend: Send + Sync {
+    /// Toolchain the guest ELFs are stored under in the registry
+    fn toolchain(&self) -> &'static str;
+    /// Verifier of the proofs, set when registering the contract
+    fn verifier(&self) -> Verifier;
+    /// ELF of the guest built along with the server
+    fn elf(&self) -> &'static [u8];
+    /// Program id of the guest built along with the server
+    fn program_id(&self) -> ProgramId;
+    /// Prover of the guest built along with the server
+    fn prover(&self) -> BoxFuture<'_, Result<OrderbookProver>>;
+    /// Prover of another program of the contract, fetched from the registry after an upgrade
+    fn prover_from_registry<'a>(
+        &'a self,
+        contract_name: &'a ContractName,
+        program_id: ProgramId,
+    ) -> BoxFuture<'a, Result<OrderbookProver>>;
+    /// Prover used instead of the client-sdk ones, if configured
+    fn direct_prover(&self) -> Result<Option<Arc<dyn DirectProver>>> {
+        Ok(None)
+    }
+}
+
+/// Prover producing the proof data itself, used instead of the client-sdk prover of the
+/// programs it proves
+pub trait DirectProver: Send + Sync {
+    /// Whether it proves the given program of the contract
+    fn proves(&self, program_id: &ProgramId) -> bool;
+    /// Verifier of its proofs
+    fn verifier(&self) -> Verifier;
+    /// Whether proofs it failed to produce are proven by the client-sdk prover instead
+    fn fallback_to_local(&self) -> bool;
+    fn prove<'a>(
+        &'a self,
+        commitment_metadata: &'a [u8],
+        calldata: &'a [Calldata],
+    ) -> BoxFuture<'a, Result<ProofData>>;
+}
+
+/// Backend selected in the configuration, mocked if `mock` is set
+pub fn proving_backend(
+    kind: ProverBackendKind,
+    network: &ProverNetworkConfig,
+    mock: bool,
+) -> Result<Arc<dyn ProvingBackend>> {
+    if network.enabled && kind != ProverBackendKind::Sp1 {
+        bail!("The prover network is only available with the sp1 prover backend");
+    }
+    let backend: Arc<dyn ProvingBackend> = match kind {
+        ProverBackendKind::Sp1 => Arc::new(Sp1Backend {
+            network: network.clone(),
+        }),
+        #[cfg(feature = "risc0")]
+        ProverBackendKind::Risc0 => Arc::new(risc0::Risc0Backend),
+        #[cfg(not(feature = "risc0"))]
+        ProverBackendKind::Risc0 => {
+            bail!("The risc0 prover backend requires building the server with the risc0 feature")
+        }
+    };
+    if mock {
+        warn!("⚠️ Using the mock prover: proofs are not checked by the node");
+        return Ok(Arc::new(MockBackend { inner: backend }));
+    }
+    Ok(backend)
+}
+
+pub struct Sp1Backend {
+    network: ProverNetworkConfig,
+}
+
+impl ProvingBackend for Sp1Backend {
+    fn toolchain(&self) -> &'static str {
+        "sp1"
+    }
+
+    fn verifier(&self) -> Verifier {
+        sdk::verifiers::SP1_4.into()
+    }
+
+    fn elf(&self) -> &'static [u8] {
+        ORDERBOOK_ELF
+    }
+
+    fn program_id(&self) -> ProgramId {
+        ORDERBOOK_VK.into()
+    }
+
+    fn prover(&self) -> BoxFuture<'_, Result<OrderbookProver>> {
+        Box::pin(async {
+            info!("Setup sp1 prover client");
+            let local_client = ProverClient::builder().cpu().build();
+            let (pk, _) = local_client.setup(ORDERBOOK_ELF);
+
+            info!("Building Proving Key");
+            let prover: OrderbookProver = Arc::new(SP1Prover::new(pk).await);
+            Ok(prover)
+        })
+    }
+
+    fn direct_prover(&self) -> Result<Option<Arc<dyn DirectProver>>> {
+        if !self.network.enabled {
+            return Ok(None);
+        }
+        let prover = RemoteProver::new(ORDERBOOK_ELF, ORDERBOOK_VK.into(), self.network.clone())?;
+        Ok(Some(Arc::new(prover)))
+    }
+
+    fn prover_from_registry<'a>(
+        &'a self,
+        contract_name: &'a ContractName,
+        program_id: ProgramId,
+    ) -> BoxFuture<'a, Result<OrderbookProver>> {
+        Box::pin(async move {
+            let prover = <SP1Prover as ClientSdkProver<Vec<Calldata>>>::new_from_registry(
+                contract_name,
+                program_id,
+            )
+            .await?;
+            let prover: OrderbookProver = Arc::new(prover);
+            Ok(prover)
+        })
+    }
+}
+
+/// Proves on the Succinct prover network, with requests signed by the configured key.
+/// Failed or timed out requests are retried with an exponential backoff.
+pub struct RemoteProver {
+    client: NetworkProver,
+    pk: SP1ProvingKey,
+    program_id: ProgramId,
+    config: ProverNetworkConfig,
+}
+
+impl RemoteProver {
+    pub fn new(elf: &[u8], program_id: ProgramId, config: ProverNetworkConfig) -> Result<Self> {
+        if config.private_key.is_empty() {
+            bail!("The prover network requires a private key to sign proof requests");
+        }
+
+        info!("Setup sp1 network prover client");
+        let mut builder = ProverClient::builder()
+            .network()
+            .private_key(&config.private_key);
+        if !config.rpc_url.is_empty() {
+            builder = builder.rpc_url(&config.rpc_url);
+        }
+        let client = builder.build();
+        let (pk, _) = client.setup(elf);
+
+        Ok(RemoteProver {
+            client,
+            pk,
+            program_id,
+            config,
+        })
+    }
+}
+
+impl DirectProver for RemoteProver {
+    // The network prover is set up from the ELF built along with the server
+    fn proves(&self, program_id: &ProgramId) -> bool {
+        *program_id == self.program_id
+    }
+
+    fn verifier(&self) -> Verifier {
+        sdk::verifiers::SP1_4.into()
+    }
+
+    fn fallback_to_local(&self) -> bool {
+        self.config.fallback_to_local
+    }
+
+    fn prove<'a>(
+        &'a self,
+        commitment_metadata: &'a [u8],
+        calldata: &'a [Calldata],
+    ) -> BoxFuture<'a, Result<ProofData>> {
+        Box::pin(async move {
+            // Same encoding as the local prover, read back by the guest environment
+            let mut stdin = SP1Stdin::new();
+            stdin.write_vec(borsh::to_vec(&(commitment_metadata, calldata))?);
+
+            let timeout = Duration::from_secs(self.config.timeout_secs);
+            let mut backoff = Duration::from_millis(self.config.retry_backoff_ms);
+            let mut attempt = 0;
+            loop {
+                let result = self
+                    .client
+                    .prove(&self.pk, &stdin)
+                    .compressed()
+                    .timeout(timeout)
+                    .run_async()
+                    .await;
+                match result {
+                    Ok(proof) => return Ok(ProofData(bincode::serialize(&proof)?)),
+                    Err(e) if attempt < self.config.max_retries => {
+                        attempt += 1;
+                        warn!(
+                            "Proof request to the prover network failed, retry {attempt}/{} in {backoff:?}: {e:#}",
+                            self.config.max_retries
+                        );
+                        tokio::time::sleep(backoff).await;
+                        backoff *= 2;
+                    }
+                    Err(e) => return Err(e.context("Proving on the prover network")),
+                }
+            }
+        })
+    }
+}
+
+/// Backend of integration tests and load tests. Txs are executed natively, and their outputs
+/// are submitted as proofs of the test verifier, that the node accepts as is.
+pub struct MockBackend {
+    inner: Arc<dyn ProvingBackend>,
+}
+
+impl ProvingBackend for MockBackend {
+    fn toolchain(&self) -> &'static str {
+        self.inner.toolchain()
+    }
+
+    fn verifier(&self) -> Verifier {
+        MOCK_VERIFIER.into()
+    }
+
+    fn elf(&self) -> &'static [u8] {
+        self.inner.elf()
+    }
+
+    fn program_id(&self) -> ProgramId {
+        self.inner.program_id()
+    }
+
+    fn prover(&self) -> BoxFuture<'_, Result<OrderbookProver>> {
+        self.inner.prover()
+    }
+
+    fn prover_from_registry<'a>(
+        &'a self,
+        contract_name: &'a ContractName,
+        program_id: ProgramId,
+    ) -> BoxFuture<'a, Result<OrderbookProver>> {
+        self.inner.prover_from_registry(contract_name, program_id)
+    }
+
+    fn direct_prover(&self) -> Result<Option<Arc<dyn DirectProver>>> {
+        Ok(Some(Arc::new(MockProver)))
+    }
+}
+
+/// Verifier of the node accepting the borsh encoded outputs of the program as its proof
+const MOCK_VERIFIER: &str = "test";
+
+pub struct MockProver;
+
+impl DirectProver for MockProver {
+    fn proves(&self, _program_id: &ProgramId) -> bool {
+        true
+    }
+
+    fn verifier(&self) -> Verifier {
+        MOCK_VERIFIER.into()
+    }
+
+    fn fallback_to_local(&self) -> bool {
+        false
+    }
+
+    fn prove<'a>(
+        &'a self,
+        commitment_metadata: &'a [u8],
+        calldata: &'a [Calldata],
+    ) -> BoxFuture<'a, Result<ProofData>> {
+        Box::pin(async move {
+            let outputs = guest::execute::<ZkVmState>(commitment_metadata, calldata);
+            Ok(ProofData(borsh::to_vec(&outputs)?))
+        })
+    }
+}
+
+#[cfg(feature = "risc0")]
+mod risc0 {
+    use client_sdk::helpers::risc0::Risc0Prover;
+    use contracts::{ORDERBOOK_RISC0_ELF, ORDERBOOK_RISC0_VK};
+
+    use super::*;
+
+    pub struct Risc0Backend;
+
+    impl ProvingBackend for Risc0Backend {
+        fn toolchain(&self) -> &'static str {
+            "risc0"
+        }
+
+        fn verifier(&self) -> Verifier {
+            sdk::verifiers::RISC0_3.into()
+        }
+
+        fn elf(&self) -> &'static [u8] {
+            ORDERBOOK_RISC0_ELF
+        }
+
+        fn program_id(&self) -> ProgramId {
+            ORDERBOOK_RISC0_VK.into()
+        }
+
+        fn prover(&self) -> BoxFuture<'_, Result<OrderbookProver>> {
+            Box::pin(async {
+                let image_id: [u8; 32] = ORDERBOOK_RISC0_VK
+                    .try_into()
+                    .context("Risc0 image id should be 32 bytes")?;
+                let prover: OrderbookProver =
+                    Arc::new(Risc0Prover::new(ORDERBOOK_RISC0_ELF.to_vec(), image_id));
+                Ok(prover)
+            })
+        }
+
+        fn prover_from_registry<'a>(
+            &'a self,
+            contract_name: &'a ContractName,
+            program_id: ProgramId,
+        ) -> BoxFuture<'a, Result<OrderbookProver>> {
+            Box::pin(async move {
+                let prover = <Risc0Prover as ClientSdkProver<Vec<Calldata>>>::new_from_registry(
+                    contract_name,
+                    program_id,
+                )
+                .await?;
+                let prover: OrderbookProver = Arc::new(prover);
+                Ok(prover)
+            })
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ProverMetrics {
+    /// Number of batches waiting for a proving worker
+    pub queue_depth: UpDownCounter<i64>,
+    /// Duration of proof generation
+    pub proving_duration: Histogram<f64>,
+    /// Time a proof waits for the proofs of the previous batches to be submitted
+    pub submission_wait_duration: Histogram<f64>,
+    /// Number of txs proven together
+    pub batch_size: Histogram<u64>,
+}
+
+impl ProverMetrics {
+    /// Create a new ProverMetrics instance with the global meter provider
+    pub fn new() -> Self {
+        let meter = opentelemetry::global::meter("prover");
+        Self::with_meter(meter)
+    }
+
+    /// Create a new ProverMetrics instance with a specific meter
+    pub fn with_meter(meter: Meter) -> Self {
+        // Proofs take from seconds to tens of minutes
+        let proving_buckets = vec![
+            0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 3600.0,
+        ];
+
+        Self {
+            queue_depth: meter
+                .i64_up_down_counter("prover.queue.depth")
+                .with_description("Number of batches waiting for a proving worker")
+                .with_unit("batches")
+                .build(),
+            proving_duration: meter
+                .f64_histogram("prover.proving.duration")
+                .with_description("Duration of proof generation in seconds")
+                .with_unit("s")
+                .with_boundaries(proving_buckets.clone())
+                .build(),
+            submission_wait_duration: meter
+                .f64_histogram("prover.submission.wait.duration")
+                .with_description(
+                    "Time a proof waits for the previous proofs to be submitted in seconds",
+                )
+                .with_unit("s")
+                .with_boundaries(proving_buckets)
+                .build(),
+            batch_size: meter
+                .u64_histogram("prover.batch.size")
+                .with_description("Number of txs proven together")
+                .with_unit("txs")
+                .build(),
+        }
+    }
+}
+
+impl Default for ProverMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct PendingTx {
@@ -39,6 +459,34 @@ pub struct OrderbookProverRequest {
     pub nonce: u32,
     pub action_private_input: Vec<u8>,
     pub tx_hash: TxHash,
+    /// W3C trace context of the action, set when its events are written, so that its proof
+    /// continues its trace
+    #[serde(default)]
+    pub trace_context: HashMap<String, String>,
+}
+
+/// Prover request of an action, as stored until its tx settles
+struct StoredProverRequest {
+    commit_id: i64,
+    request: OrderbookProverRequest,
+    /// Whether its proof was already submitted, before the prover restarted
+    proved: bool,
+}
+
+impl StoredProverRequest {
+    fn from_row(row: &sqlx::postgres::PgRow) -> Result<Self> {
+        let commit_id: i64 = row.get("commit_id");
+        let request_json: Vec<u8> = row.get("request");
+        let request =
+            serde_json::from_slice::<OrderbookProverRequest>(&request_json).map_err(|e| {
+                anyhow!("Failed to parse prover request JSON of commit {commit_id}: {e}")
+            })?;
+        Ok(StoredProverRequest {
+            commit_id,
+            request,
+            proved: row.get::<Option<String>, _>("proof_tx_hash").is_some(),
+        })
+    }
 }
 
 module_bus_client! {
@@ -49,12 +497,30 @@ module_bus_client! {
 }
 
 pub struct OrderbookProverCtx {
-    pub prover: Arc<dyn ClientSdkProver<Vec<Calldata>> + Send + Sync>,
+    pub backend: Arc<dyn ProvingBackend>,
+    pub prover: OrderbookProver,
+    /// Prover used instead of `prover` for the programs it proves, e.g. the prover network
+    pub direct_prover: Option<Arc<dyn DirectProver>>,
     pub orderbook_cn: ContractName,
     pub lane_id: LaneId,
-    pub node_client: Arc<dyn NodeApiClient + Send + Sync>,
-    pub initial_orderbook: FullState,
+    pub node_client: Arc<NodeClient>,
+    /// State the txs are proven on, starting at the settled state the orderbook was loaded at.
+    /// Shared with the escape proofs of the API when running alongside the server.
+    pub orderbook: Arc<Mutex<FullState>>,
+    /// Commit the initial orderbook was loaded at. Requests of later commits are proven on startup.
+    pub initial_commit_id: i64,
     pub pool: PgPool,
+    /// Maximum number of txs proven together in a single proof
+    pub max_txs_per_proof: usize,
+    /// How long sequenced txs wait for more txs to be batched with before being proven
+    pub proof_batch_window: Duration,
+    /// Number of batches proven concurrently. Proofs are still submitted in commit order.
+    pub proving_workers: usize,
+    /// How many times a batch waiting for a worker can be overtaken by cheaper later ones
+    pub max_proof_reordering: usize,
+    pub metrics: ProverMetrics,
+    /// Feed told when commits settle, when running alongside the server
+    pub balance_feed: Option<Arc<BalanceFeed>>,
 }
 
 #[derive(Clone)]
@@ -62,12 +528,35 @@ pub struct Ctx {
     pub orderbook: Arc<Mutex<FullState>>,
 }
 
+/// Txs sequenced since the last proof, proven together in a single zkvm execution
+struct ProofBatch {
+    /// State before the first tx of the batch, that the merged commitment metadata is proven
+    /// against. Not kept when txs are proven one by one.
+    initial_orderbook: Option<FullState>,
+    commit_ids: Vec<i64>,
+    commitment_metadata: Vec<Vec<u8>>,
+    calldata: Vec<Calldata>,
+    prover: OrderbookProver,
+    /// Number of events of the txs, estimating how long the batch takes to prove
+    cost: u64,
+    /// Spans of the txs in the traces of their actions, linked to the span proving the batch
+    spans: Vec<SpanContext>,
+}
+
 pub struct OrderbookProverModule {
     ctx: Arc<OrderbookProverCtx>,
     bus: OrderbookProverBusClient,
     orderbook: Arc<Mutex<FullState>>,
     current_program_id: ProgramId,
-    provers: HashMap<ProgramId, Arc<dyn ClientSdkProver<Vec<Calldata>> + Send + Sync>>,
+    provers: HashMap<ProgramId, OrderbookProver>,
+    batch: Option<ProofBatch>,
+    workers: Arc<ProvingScheduler>,
+    /// Order of creation of the next batch, for the scheduling of the workers
+    next_batch_seq: u64,
+    /// Resolved once the proof of the last batch handed to a worker has been submitted
+    last_submission: Option<oneshot::Receiver<()>>,
+    /// Sequenced txs whose requests were queued for proving, until they settle
+    queued_txs: HashSet<TxHash>,
 }
 
 impl Module for OrderbookProverModule {
@@ -75,15 +564,19 @@ impl Module for OrderbookProverModule {
 
     async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
         let bus = OrderbookProverBusClient::new_from_bus(bus.new_handle()).await;
-        let orderbook = Arc::new(Mutex::new(ctx.initial_orderbook.clone()));
+        let orderbook = ctx.orderbook.clone();
 
         let current_program_id = ctx
             .node_client
-            .get_contract(ctx.orderbook_cn.clone())
+            .call("get_contract", |client| {
+                let contract_name = ctx.orderbook_cn.clone();
+                async move { client.get_contract(contract_name).await }
+            })
             .await?
             .program_id;
         let mut provers = HashMap::new();
         provers.insert(ctx.prover.program_id(), ctx.prover.clone());
+        let workers = ProvingScheduler::new(ctx.proving_workers, ctx.max_proof_reordering);
 
         Ok(OrderbookProverModule {
             ctx,
@@ -91,6 +584,11 @@ impl Module for OrderbookProverModule {
             orderbook,
             provers,
             current_program_id,
+            batch: None,
+            workers,
+            next_batch_seq: 0,
+            last_submission: None,
+            queued_txs: HashSet::new(),
         })
     }
 
@@ -102,6 +600,14 @@ impl Module for OrderbookProverModule {
 
 impl OrderbookProverModule {
     pub async fn start(&mut self) -> Result<()> {
+        self.recover_pending_requests()
+            .await
+            .context("Recovering pending prover requests")?;
+
+        let mut interval =
+            tokio::time::interval(self.ctx.proof_batch_window.max(Duration::from_millis(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
         module_handle_messages! {
             on_self self,
 
@@ -112,21 +618,109 @@ impl OrderbookProverModule {
                     return Err(anyhow!("Hard failure in handle_node_state_event"));
                 }
             }
+            _ = interval.tick() => {
+                if log_error!(self.prove_batch(), "prove batch").is_err() {
+                    error!("❌ Exiting prover module");
+                    return Err(anyhow!("Hard failure in prove_batch"));
+                }
+            }
         };
         Ok(())
     }
 
+    /// Resumes proving the requests stored after the initial orderbook, in commit order, so
+    /// that the txs sequenced before a restart are still settled.
+    /// Requests whose proof was already submitted only catch the state up.
+    async fn recover_pending_requests(&mut self) -> Result<()> {
+        // Requests up to the initial orderbook are settled, even if their deletion was missed
+        sqlx::query("DELETE FROM prover_requests WHERE commit_id <= $1")
+            .bind(self.ctx.initial_commit_id)
+            .execute(&self.ctx.pool)
+            .await
+            .context("Deleting settled prover requests")?;
+
+        let rows = sqlx::query(
+            "SELECT p.commit_id, p.request, p.proof_tx_hash, COALESCE(o.sent_tx_hash, p.tx_hash) AS sent_tx_hash, o.status FROM prover_requests p LEFT JOIN blob_tx_outbox o ON o.commit_id = p.commit_id ORDER BY p.commit_id"
+        )
+        .fetch_all(&self.ctx.pool)
+        .await
+        .context("Fetching pending prover requests")?;
+
+        if rows.is_empty() {
+            return Ok(());
+        }
+        info!(
+            "Recovering {} prover requests after commit {}",
+            rows.len(),
+            self.ctx.initial_commit_id
+        );
+
+        // Consecutive requests sent in the same blob tx are proven together
+        let mut txs: Vec<(String, Option<String>, Vec<StoredProverRequest>)> = Vec::new();
+        for row in &rows {
+            let sent_tx_hash: String = row.get("sent_tx_hash");
+            let request = StoredProverRequest::from_row(row)?;
+            match txs.last_mut() {
+                Some((tx_hash, _, requests)) if *tx_hash == sent_tx_hash => requests.push(request),
+                _ => txs.push((sent_tx_hash, row.get("status"), vec![request])),
+            }
+        }
+
+        let mut recovered = 0;
+        for (tx_hash, status, requests) in txs {
+            let tx_hash = TxHash(tx_hash);
+            if status.as_deref() == Some("pending") {
+                // Not sent yet: it is proven once sequenced, like the following ones
+                break;
+            }
+
+            let tx_ctx = if requests.iter().all(|request| request.proved) {
+                TxContext::default()
+            } else {
+                let unsettled_tx = self
+                    .ctx
+                    .node_client
+                    .call("get_unsettled_tx", |client| {
+                        let tx_hash = tx_hash.clone();
+                        async move { client.get_unsettled_tx(tx_hash).await }
+                    })
+                    .await;
+                match unsettled_tx {
+                    Ok(unsettled_tx) => unsettled_tx.tx_context,
+                    Err(e) => {
+                        warn!(
+                            "⚠️ Could not fetch the context of {tx_hash:#}, its requests are proven once sequenced: {e:#}"
+                        );
+                        break;
+                    }
+                }
+            };
+
+            recovered += requests.len();
+            self.queue_prover_requests(tx_hash, requests, tx_ctx)
+                .await?;
+        }
+        self.prove_batch()?;
+
+        info!("✅ {recovered} prover requests recovered");
+        Ok(())
+    }
+
+    /// Builds the pending tx proving the blob at `index` of the sequenced tx,
+    /// which carries the actions of `blobs` when several actions were batched together
     async fn handle_prover_request(
         &mut self,
         request: OrderbookProverRequest,
+        tx_hash: &TxHash,
+        blobs: &[Blob],
+        index: usize,
     ) -> Result<PendingTx> {
         let OrderbookProverRequest {
             events,
             user_info,
             action_private_input,
             orderbook_action,
-            tx_hash,
-            nonce,
+            ..
         } = request;
         // The goal is to create commitment metadata that contains the proofs to be able to load the zkvm state into the zkvm
 
@@ -160,14 +754,9 @@ impl OrderbookProverModule {
         let calldata = Calldata {
             identity: ORDERBOOK_ACCOUNT_IDENTITY.into(),
             tx_hash: tx_hash.clone(),
-            blobs: vec![OrderbookAction::PermissionedOrderbookAction(
-                orderbook_action.clone(),
-                nonce,
-            )
-            .as_blob(self.ctx.orderbook_cn.clone())]
-            .into(),
-            tx_blob_count: 1,
-            index: BlobIndex(0),
+            blobs: blobs.to_vec().into(),
+            tx_blob_count: blobs.len(),
+            index: BlobIndex(index),
             private_input,
             tx_ctx: Default::default(), // Will be set when proving
         };
@@ -188,15 +777,31 @@ impl OrderbookProverModule {
                     | TransactionStatusDb::Failure
                     | TransactionStatusDb::TimedOut => {
                         info!("✨ {tx_hash:#} has settled: {status}");
+                        self.queued_txs.remove(&tx_hash);
 
-                        // Delete settled tx from the database
-                        log_error!(
-                            sqlx::query("DELETE FROM prover_requests WHERE tx_hash = $1")
-                                .bind(tx_hash.0.clone())
-                                .execute(&self.ctx.pool)
-                                .await,
+                        let outcome = match status {
+                            TransactionStatusDb::Success => "settled",
+                            _ => "failed",
+                        };
+
+                        // Delete settled tx from the database, with the actions batched in it,
+                        // keeping their outcome for the prover status
+                        let settled_commit_id: Option<i64> = log_error!(
+                            sqlx::query_scalar(
+                                "WITH settled AS (DELETE FROM prover_requests WHERE tx_hash = $1 OR commit_id IN (SELECT commit_id FROM blob_tx_outbox WHERE sent_tx_hash = $1) RETURNING commit_id, tx_hash, proof_tx_hash, proving_started_at, proved_at), recorded AS (INSERT INTO prover_settlements (commit_id, tx_hash, proof_tx_hash, status, proving_started_at, proved_at) SELECT commit_id, tx_hash, proof_tx_hash, $2, proving_started_at, proved_at FROM settled ON CONFLICT (commit_id) DO NOTHING) SELECT MAX(commit_id) FROM settled"
+                            )
+                            .bind(tx_hash.0.clone())
+                            .bind(outcome)
+                            .fetch_one(&self.ctx.pool)
+                            .await,
                             "Failed to delete settled txs from the database"
                         )?;
+
+                        if let (TransactionStatusDb::Success, Some(feed), Some(commit_id)) =
+                            (&status, &self.ctx.balance_feed, settled_commit_id)
+                        {
+                            feed.publish_settled(commit_id);
+                        }
                         Ok(())
                     }
                     _ => {
@@ -206,88 +811,294 @@ impl OrderbookProverModule {
                 }
             }
             ContractListenerEvent::SequencedTx(tx_hash, _indexed_blobs, tx_ctx) => {
-                // Query the database for the prover request
-                let row = sqlx::query("SELECT request FROM prover_requests WHERE tx_hash = $1")
-                    .bind(tx_hash.0.clone())
-                    .fetch_optional(&self.ctx.pool)
-                    .await?;
-
-                if let Some(row) = row {
-                    let request_json: Vec<u8> = row.get("request");
-                    let prover_request: OrderbookProverRequest =
-                        serde_json::from_slice(&request_json)
-                            .map_err(|e| anyhow!("Failed to parse prover request JSON: {e}"))?;
-
-                    if let PermissionedOrderbookAction::UpgradeContract(new_program_id) =
-                        &prover_request.orderbook_action
-                    {
-                        // Update current program ID if it's different
-                        if &self.current_program_id != new_program_id {
-                            info!(
-                                "Updating current program ID from {} to {}",
-                                self.current_program_id, new_program_id
-                            );
-                            self.current_program_id = new_program_id.clone();
-                        }
-                    }
+                if self.queued_txs.contains(&tx_hash) {
+                    debug!("{tx_hash:#} was already queued for proving on startup");
+                    return Ok(());
+                }
+
+                // Query the database for the prover requests of the actions sent in this tx
+                let rows = sqlx::query(
+                    "SELECT p.commit_id, p.request, p.proof_tx_hash FROM prover_requests p LEFT JOIN blob_tx_outbox o ON o.commit_id = p.commit_id WHERE COALESCE(o.sent_tx_hash, p.tx_hash) = $1 ORDER BY p.commit_id"
+                )
+                .bind(tx_hash.0.clone())
+                .fetch_all(&self.ctx.pool)
+                .await?;
+
+                if rows.is_empty() {
+                    error!("No prover request found for tx {tx_hash:#}");
+                    return Ok(());
+                }
 
-                    let prover = self.get_prover().await?;
+                let requests = rows
+                    .iter()
+                    .map(StoredProverRequest::from_row)
+                    .collect::<Result<Vec<_>>>()?;
 
-                    // Process the request to get the pending transaction
-                    let pending_tx = self.handle_prover_request(prover_request).await?;
+                self.queue_prover_requests(tx_hash, requests, tx_ctx).await
+            }
+        }
+    }
 
-                    let contract_name = self.ctx.orderbook_cn.clone();
-                    let node_client = self.ctx.node_client.clone();
-                    let tx_context_cloned = tx_ctx.clone();
-                    let tx_hash_cloned = tx_hash.clone();
+    /// Adds the requests of the actions sent in a sequenced tx to the proof batch, in commit order
+    async fn queue_prover_requests(
+        &mut self,
+        tx_hash: TxHash,
+        requests: Vec<StoredProverRequest>,
+        tx_ctx: TxContext,
+    ) -> Result<()> {
+        self.queued_txs.insert(tx_hash.clone());
 
-                    tokio::spawn(async move {
-                        let mut calldata = pending_tx.calldata;
+        // Blobs of the tx, one per action in commit order
+        let blobs: Vec<Blob> = requests
+            .iter()
+            .map(|stored| {
+                OrderbookAction::PermissionedOrderbookAction(
+                    stored.request.orderbook_action.clone(),
+                    stored.request.nonce,
+                )
+                .as_blob(self.ctx.orderbook_cn.clone())
+            })
+            .collect();
 
-                        calldata.tx_ctx = Some(tx_context_cloned);
+        for (index, stored) in requests.into_iter().enumerate() {
+            let StoredProverRequest {
+                commit_id,
+                request: prover_request,
+                proved,
+            } = stored;
 
-                        match prover
-                            .prove(pending_tx.commitment_metadata, vec![calldata])
-                            .await
-                        {
-                            Ok(proof) => {
-                                let tx = ProofTransaction {
-                                    contract_name: contract_name.clone(),
-                                    program_id: prover.program_id(),
-                                    verifier: prover.verifier(),
-                                    proof: proof.data,
-                                };
-
-                                info!("Proof took {:?} cycles", proof.metadata.cycles);
-
-                                match node_client.send_tx_proof(tx).await {
-                                    Ok(proof_tx_hash) => {
-                                        debug!("Successfully sent proof for {tx_hash_cloned:#}: {proof_tx_hash:#}");
-                                    }
-                                    Err(e) => {
-                                        error!(
-                                            "Failed to send proof for {tx_hash_cloned:#}: {e:#}"
-                                        );
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                bail!("failed to generate proof for {tx_hash_cloned:#}: {e:#}");
-                            }
-                        }
-                        Ok(())
-                    });
+            if let PermissionedOrderbookAction::UpgradeContract(new_program_id) =
+                &prover_request.orderbook_action
+            {
+                // Update current program ID if it's different
+                if &self.current_program_id != new_program_id {
+                    // Txs sequenced before the upgrade are proven with the previous program
+                    self.prove_batch()?;
+                    info!(
+                        "Updating current program ID from {} to {}",
+                        self.current_program_id, new_program_id
+                    );
+                    self.current_program_id = new_program_id.clone();
+                }
+            }
+
+            if proved {
+                // The batch must not span the state update of a request proven elsewhere
+                self.prove_batch()?;
+                debug!("Request of commit {commit_id} was already proven, catching up the state");
+                self.orderbook
+                    .lock()
+                    .await
+                    .apply_events_and_update_roots(&prover_request.user_info, prover_request.events)
+                    .map_err(|e| anyhow!("failed to execute orderbook tx: {e}"))?;
+                continue;
+            }
+
+            let prover = self.get_prover().await?;
+
+            if self.batch.is_none() {
+                let initial_orderbook = if self.ctx.max_txs_per_proof > 1 {
+                    Some(self.orderbook.lock().await.clone())
                 } else {
-                    error!("No prover request found for tx {tx_hash:#}");
+                    None
+                };
+                self.batch = Some(ProofBatch {
+                    initial_orderbook,
+                    commit_ids: Vec::new(),
+                    commitment_metadata: Vec::new(),
+                    calldata: Vec::new(),
+                    prover,
+                    cost: 0,
+                    spans: Vec::new(),
+                });
+            }
+
+            let cost = prover_request.events.len().max(1) as u64;
+
+            // Continues the trace of the action
+            let span = tracing::info_span!("prepare_proof", commit_id, tx_hash = %tx_hash);
+            span.set_parent(telemetry::extract_context(&prover_request.trace_context));
+
+            // Process the request to get the pending transaction
+            let mut pending_tx = self
+                .handle_prover_request(prover_request, &tx_hash, &blobs, index)
+                .instrument(span.clone())
+                .await?;
+            pending_tx.calldata.tx_ctx = Some(tx_ctx.clone());
+
+            let batch = self.batch.as_mut().expect("Batch should have been started");
+            batch.commit_ids.push(commit_id);
+            batch
+                .commitment_metadata
+                .push(pending_tx.commitment_metadata);
+            batch.calldata.push(pending_tx.calldata);
+            batch.cost += cost;
+            batch
+                .spans
+                .push(span.context().span().span_context().clone());
+            if batch.calldata.len() >= self.ctx.max_txs_per_proof {
+                self.prove_batch()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Proves the txs of the current batch in a single zkvm execution, and sends the proof
+    fn prove_batch(&mut self) -> Result<()> {
+        let Some(batch) = self.batch.take() else {
+            return Ok(());
+        };
+        let ProofBatch {
+            initial_orderbook,
+            commit_ids,
+            mut commitment_metadata,
+            calldata,
+            prover,
+            cost,
+            spans,
+        } = batch;
+        if calldata.is_empty() {
+            return Ok(());
+        }
+
+        let tx_hashes = calldata
+            .iter()
+            .map(|calldata| format!("{:#}", calldata.tx_hash))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let commitment_metadata = if commitment_metadata.len() == 1 {
+            commitment_metadata.remove(0)
+        } else {
+            initial_orderbook
+                .context("No initial state to prove the batch against")?
+                .merge_zkvm_commitment_metadata(&commitment_metadata)
+                .map_err(|e| anyhow!("Could not merge zkvm state for txs {tx_hashes}: {e}"))?
+        };
+
+        info!("Proving {} txs: {tx_hashes}", calldata.len());
+
+        let program_id = prover.program_id();
+        let direct_prover = self
+            .ctx
+            .direct_prover
+            .clone()
+            .filter(|direct_prover| direct_prover.proves(&program_id));
+
+        let contract_name = self.ctx.orderbook_cn.clone();
+        let node_client = self.ctx.node_client.clone();
+        let pool = self.ctx.pool.clone();
+        let metrics = self.ctx.metrics.clone();
+        let workers = self.workers.clone();
+        let seq = self.next_batch_seq;
+        self.next_batch_seq += 1;
+        let (submitted_tx, submitted_rx) = oneshot::channel();
+        let previous_submission = self.last_submission.replace(submitted_rx);
+
+        metrics.batch_size.record(calldata.len() as u64, &[]);
+        metrics.queue_depth.add(1, &[]);
+
+        let span =
+            tracing::info_span!("prove_batch", commit_ids = ?commit_ids, tx_hashes = %tx_hashes);
+        for link in spans.into_iter().filter(SpanContext::is_valid) {
+            span.add_link(link);
+        }
+
+        tokio::spawn(async move {
+            let permit = workers.acquire(seq, cost).await;
+            metrics.queue_depth.add(-1, &[]);
+
+            _ = log_error!(
+                sqlx::query(
+                    "UPDATE prover_requests SET proving_started_at = now() WHERE commit_id = ANY($1)"
+                )
+                .bind(&commit_ids)
+                .execute(&pool)
+                .await,
+                "Failed to mark requests being proven"
+            );
+
+            let proving_start = Instant::now();
+            let direct_proof = match &direct_prover {
+                Some(direct_prover) => Some((
+                    direct_prover.prove(&commitment_metadata, &calldata).await,
+                    direct_prover.verifier(),
+                    direct_prover.fallback_to_local(),
+                )),
+                None => None,
+            };
+            let proof = match direct_proof {
+                Some((Ok(proof), verifier, _)) => Ok((proof, verifier)),
+                Some((Err(e), _, false)) => Err(e),
+                direct_proof => {
+                    if let Some((Err(e), ..)) = direct_proof {
+                        warn!("⚠️ Could not prove {tx_hashes}, proving locally instead: {e:#}");
+                    }
+                    prover
+                        .prove(commitment_metadata, calldata)
+                        .await
+                        .map(|proof| {
+                            info!("Proof took {:?} cycles", proof.metadata.cycles);
+                            (proof.data, prover.verifier())
+                        })
+                }
+            };
+            metrics
+                .proving_duration
+                .record(proving_start.elapsed().as_secs_f64(), &[]);
+            drop(permit);
+
+            // Proofs are submitted in commit order. A dropped sender means that the previous
+            // batch failed to prove, which must not hold the following ones back.
+            let wait_start = Instant::now();
+            if let Some(previous_submission) = previous_submission {
+                _ = previous_submission.await;
+            }
+            metrics
+                .submission_wait_duration
+                .record(wait_start.elapsed().as_secs_f64(), &[]);
+
+            match proof {
+                Ok((proof, verifier)) => {
+                    let tx = ProofTransaction {
+                        contract_name: contract_name.clone(),
+                        program_id,
+                        verifier,
+                        proof,
+                    };
+
+                    match node_client.send_tx_proof(tx).await {
+                        Ok(proof_tx_hash) => {
+                            debug!("Successfully sent proof for {tx_hashes}: {proof_tx_hash:#}");
+                            // A restarted prover only catches the state up on these requests
+                            _ = log_error!(
+                                sqlx::query(
+                                    "UPDATE prover_requests SET proof_tx_hash = $2, proved_at = now() WHERE commit_id = ANY($1)"
+                                )
+                                .bind(&commit_ids)
+                                .bind(proof_tx_hash.0.clone())
+                                .execute(&pool)
+                                .await,
+                                "Failed to mark proven requests"
+                            );
+                        }
+                        Err(e) => {
+                            error!("Failed to send proof for {tx_hashes}: {e:#}");
+                        }
+                    }
+                }
+                Err(e) => {
+                    bail!("failed to generate proof for {tx_hashes}: {e:#}");
                 }
-                Ok(())
             }
+            _ = submitted_tx.send(());
+            Ok(())
         }
+        .instrument(span));
+
+        Ok(())
     }
 
-    async fn get_prover(
-        &mut self,
-    ) -> Result<Arc<dyn ClientSdkProver<Vec<Calldata>> + Send + Sync>> {
+    async fn get_prover(&mut self) -> Result<OrderbookProver> {
         let program_id = &self.current_program_id.clone();
         // If prover for current program ID does not exist, call add_prover
         if !self.provers.contains_key(program_id) {
@@ -304,14 +1115,14 @@ impl OrderbookProverModule {
     }
 
     async fn add_prover(&mut self, program_id: &ProgramId) -> Result<()> {
-        let prover = <SP1Prover as ClientSdkProver<Vec<Calldata>>>::new_from_registry(
-            &self.ctx.orderbook_cn,
-            program_id.clone(),
-        )
-        .await
-        .context("Creating new prover with updated ELF")?;
+        let prover = self
+            .ctx
+            .backend
+            .prover_from_registry(&self.ctx.orderbook_cn, program_id.clone())
+            .await
+            .context("Creating new prover with updated ELF")?;
 
-        self.provers.insert(program_id.clone(), Arc::new(prover));
+        self.provers.insert(program_id.clone(), prover);
 
         info!(
             cn =% self.ctx.orderbook_cn,
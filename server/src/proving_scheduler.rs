@@ -0,0 +1,112 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::oneshot;
+
+/// Hands the proving workers to the batches waiting for one. When several batches wait, the
+/// cheapest one goes first, so that cancels and deposits are not stuck behind large market
+/// sweeps. A batch can be overtaken at most `max_reordering` times, which bounds how long the
+/// oldest one waits. Proofs are still submitted in commit order.
+pub struct ProvingScheduler {
+    state: Mutex<SchedulerState>,
+    max_reordering: usize,
+}
+
+struct SchedulerState {
+    free_workers: usize,
+    /// Batches waiting for a worker, by order of creation
+    waiting: BTreeMap<u64, WaitingBatch>,
+}
+
+struct WaitingBatch {
+    cost: u64,
+    /// Number of later batches that were given a worker first
+    overtaken: usize,
+    ready: oneshot::Sender<()>,
+}
+
+/// Worker given to a batch, released when dropped
+pub struct ProvingPermit {
+    scheduler: Arc<ProvingScheduler>,
+}
+
+impl Drop for ProvingPermit {
+    fn drop(&mut self) {
+        let mut state = self.scheduler.lock();
+        state.free_workers += 1;
+        self.scheduler.dispatch(&mut state);
+    }
+}
+
+impl ProvingScheduler {
+    /// `max_reordering` at 0 gives the workers in the order batches were created
+    pub fn new(workers: usize, max_reordering: usize) -> Arc<Self> {
+        Arc::new(ProvingScheduler {
+            state: Mutex::new(SchedulerState {
+                free_workers: workers.max(1),
+                waiting: BTreeMap::new(),
+            }),
+            max_reordering,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SchedulerState> {
+        self.state.lock().expect("proving scheduler poisoned")
+    }
+
+    /// Waits for a worker. `seq` orders the batches by creation, and `cost` estimates how long
+    /// the batch takes to prove.
+    pub async fn acquire(self: &Arc<Self>, seq: u64, cost: u64) -> ProvingPermit {
+        let (ready, ready_rx) = oneshot::channel();
+        {
+            let mut state = self.lock();
+            state.waiting.insert(
+                seq,
+                WaitingBatch {
+                    cost,
+                    overtaken: 0,
+                    ready,
+                },
+            );
+            self.dispatch(&mut state);
+        }
+        // The sender is only dropped once it was used to hand the worker over
+        _ = ready_rx.await;
+        ProvingPermit {
+            scheduler: self.clone(),
+        }
+    }
+
+    fn dispatch(&self, state: &mut SchedulerState) {
+        while state.free_workers > 0 {
+            let Some(seq) = self.next_batch(state) else {
+                return;
+            };
+            for (_, batch) in state.waiting.range_mut(..seq) {
+                batch.overtaken += 1;
+            }
+            let batch = state.waiting.remove(&seq).expect("next batch is waiting");
+            // A batch whose task is gone does not take the worker
+            if batch.ready.send(()).is_ok() {
+                state.free_workers -= 1;
+            }
+        }
+    }
+
+    /// The oldest batch once it was overtaken too often, or else the cheapest one among the
+    /// oldest that may still overtake it
+    fn next_batch(&self, state: &SchedulerState) -> Option<u64> {
+        let (&oldest_seq, oldest) = state.waiting.first_key_value()?;
+        if oldest.overtaken >= self.max_reordering {
+            return Some(oldest_seq);
+        }
+        state
+            .waiting
+            .iter()
+            .take(self.max_reordering - oldest.overtaken + 1)
+            .min_by_key(|(seq, batch)| (batch.cost, **seq))
+            .map(|(seq, _)| *seq)
+    }
+}
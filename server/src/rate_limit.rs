@@ -0,0 +1,271 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use opentelemetry::{metrics::Counter, KeyValue};
+
+use crate::{
+    clock::Clock,
+    conf::{RateLimit, RateLimitConfig},
+};
+
+/// Buckets tracked before the idle ones are dropped
+const MAX_TRACKED_BUCKETS: usize = 100_000;
+
+/// Kind of request, each with its own limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestClass {
+    /// Actions: order placement and cancellation, deposits, withdrawals...
+    Orders,
+    /// Reads: order book, balances, state...
+    MarketData,
+}
+
+impl RequestClass {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RequestClass::Orders => "orders",
+            RequestClass::MarketData => "market_data",
+        }
+    }
+}
+
+/// Who a bucket limits
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Identity(String),
+    Ip(String),
+}
+
+impl Client {
+    fn scope(&self) -> &'static str {
+        match self {
+            Client::Identity(_) => "identity",
+            Client::Ip(_) => "ip",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token bucket rate limits of the REST API, per identity and per client IP. The limits are
+/// replaced when the config is reloaded, the buckets keeping their tokens.
+pub struct RateLimiter {
+    config: std::sync::RwLock<RateLimitConfig>,
+    buckets: std::sync::Mutex<HashMap<(RequestClass, Client), TokenBucket>>,
+    clock: Clock,
+    rejected: Counter<u64>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig, clock: Clock) -> Self {
+        let meter = opentelemetry::global::meter("app");
+        RateLimiter {
+            config: std::sync::RwLock::new(config),
+            buckets: Default::default(),
+            clock,
+            rejected: meter
+                .u64_counter("http.rate_limited")
+                .with_description("Requests refused by the rate limits, by class and scope")
+                .build(),
+        }
+    }
+
+    pub fn set_config(&self, config: RateLimitConfig) {
+        *self.config.write().expect("rate limit config poisoned") = config;
+    }
+
+    /// Takes a token from the buckets of the identity and of the IP, if any.
+    /// Returns how long to wait before retrying when one of them is empty.
+    pub fn check(
+        &self,
+        class: RequestClass,
+        identity: Option<&str>,
+        ip: Option<&str>,
+    ) -> Result<(), Duration> {
+        let config = self
+            .config
+            .read()
+            .expect("rate limit config poisoned")
+            .clone();
+        let limit = match class {
+            RequestClass::Orders => &config.orders,
+            RequestClass::MarketData => &config.market_data,
+        };
+        let clients = [
+            identity.map(|identity| {
+                (
+                    Client::Identity(identity.to_string()),
+                    limit.identity_per_sec,
+                )
+            }),
+            ip.map(|ip| (Client::Ip(ip.to_string()), limit.ip_per_sec)),
+        ];
+
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().expect("rate limit buckets poisoned");
+        if buckets.len() > MAX_TRACKED_BUCKETS {
+            Self::drop_idle_buckets(&config, &mut buckets, now);
+        }
+        for (client, per_sec) in clients.into_iter().flatten() {
+            if per_sec == 0 {
+                continue;
+            }
+            let capacity = Self::capacity(limit, per_sec);
+            let bucket = buckets
+                .entry((class, client.clone()))
+                .or_insert_with(|| TokenBucket {
+                    tokens: capacity,
+                    updated_at: now,
+                });
+            let refill = now.duration_since(bucket.updated_at).as_secs_f64() * per_sec as f64;
+            bucket.tokens = (bucket.tokens + refill).min(capacity);
+            bucket.updated_at = now;
+            if bucket.tokens < 1.0 {
+                self.rejected.add(
+                    1,
+                    &[
+                        KeyValue::new("class", class.as_str()),
+                        KeyValue::new("scope", client.scope()),
+                    ],
+                );
+                let missing = 1.0 - bucket.tokens;
+                return Err(Duration::from_secs_f64(missing / per_sec as f64));
+            }
+            bucket.tokens -= 1.0;
+        }
+        Ok(())
+    }
+
+    fn capacity(limit: &RateLimit, per_sec: u32) -> f64 {
+        (per_sec * limit.burst_secs.max(1)) as f64
+    }
+
+    /// Drops the buckets that refilled since their last request, which are the same as new ones
+    fn drop_idle_buckets(
+        config: &RateLimitConfig,
+        buckets: &mut HashMap<(RequestClass, Client), TokenBucket>,
+        now: Instant,
+    ) {
+        let max_refill_secs = config
+            .orders
+            .burst_secs
+            .max(config.market_data.burst_secs)
+            .max(1);
+        buckets.retain(|_, bucket| {
+            now.duration_since(bucket.updated_at) < Duration::from_secs(max_refill_secs as u64)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: Option<&str> = Some("10.0.0.1");
+
+    fn config(identity_per_sec: u32, ip_per_sec: u32) -> RateLimitConfig {
+        let limit = RateLimit {
+            identity_per_sec,
+            ip_per_sec,
+            burst_secs: 2,
+        };
+        RateLimitConfig {
+            orders: limit.clone(),
+            market_data: limit,
+        }
+    }
+
+    fn limiter(identity_per_sec: u32, ip_per_sec: u32) -> (RateLimiter, Clock) {
+        let clock = Clock::frozen();
+        let limiter = RateLimiter::new(config(identity_per_sec, ip_per_sec), clock.clone());
+        (limiter, clock)
+    }
+
+    #[test]
+    fn burst_is_refused_once_spent_until_refilled() {
+        let (limiter, clock) = limiter(5, 0);
+        for _ in 0..10 {
+            assert!(limiter
+                .check(RequestClass::Orders, Some("alice"), IP)
+                .is_ok());
+        }
+        let retry_after = limiter
+            .check(RequestClass::Orders, Some("alice"), IP)
+            .unwrap_err();
+        assert_eq!(retry_after.as_millis(), 200);
+
+        clock.advance(Duration::from_millis(200));
+        assert!(limiter
+            .check(RequestClass::Orders, Some("alice"), IP)
+            .is_ok());
+        assert!(limiter
+            .check(RequestClass::Orders, Some("alice"), IP)
+            .is_err());
+    }
+
+    #[test]
+    fn identities_and_classes_have_their_own_buckets() {
+        let (limiter, _) = limiter(1, 0);
+        for _ in 0..2 {
+            assert!(limiter
+                .check(RequestClass::Orders, Some("alice"), IP)
+                .is_ok());
+        }
+        assert!(limiter
+            .check(RequestClass::Orders, Some("alice"), IP)
+            .is_err());
+        assert!(limiter.check(RequestClass::Orders, Some("bob"), IP).is_ok());
+        assert!(limiter
+            .check(RequestClass::MarketData, Some("alice"), IP)
+            .is_ok());
+    }
+
+    #[test]
+    fn ip_limit_applies_to_all_identities() {
+        let (limiter, _) = limiter(0, 1);
+        assert!(limiter
+            .check(RequestClass::Orders, Some("alice"), IP)
+            .is_ok());
+        assert!(limiter.check(RequestClass::Orders, Some("bob"), IP).is_ok());
+        assert!(limiter
+            .check(RequestClass::Orders, Some("carol"), IP)
+            .is_err());
+        assert!(limiter
+            .check(RequestClass::Orders, Some("carol"), Some("10.0.0.2"))
+            .is_ok());
+    }
+
+    #[test]
+    fn zero_limits_are_unlimited() {
+        let (limiter, _) = limiter(0, 0);
+        for _ in 0..1_000 {
+            assert!(limiter
+                .check(RequestClass::Orders, Some("alice"), IP)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn reloaded_limits_apply_to_the_existing_buckets() {
+        let (limiter, clock) = limiter(1, 0);
+        for _ in 0..2 {
+            assert!(limiter
+                .check(RequestClass::Orders, Some("alice"), IP)
+                .is_ok());
+        }
+        limiter.set_config(config(10, 0));
+        clock.advance(Duration::from_millis(100));
+        assert!(limiter
+            .check(RequestClass::Orders, Some("alice"), IP)
+            .is_ok());
+        assert!(limiter
+            .check(RequestClass::Orders, Some("alice"), IP)
+            .is_err());
+    }
+}
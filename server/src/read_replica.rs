@@ -0,0 +1,112 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use tracing::{info, warn};
+
+use crate::conf::ReadReplicaConfig;
+
+/// Pool the query endpoints read from: a read-only replica when one is configured and
+/// reachable, the primary otherwise. Writes always go to the primary.
+///
+/// Reads from the replica lag behind the primary by its replication delay, so anything that
+/// must see its own writes (state rebuild, proving, risk checks) keeps using the primary.
+pub struct ReadPool {
+    primary: PgPool,
+    replica: Option<PgPool>,
+    replica_up: AtomicBool,
+}
+
+impl ReadPool {
+    /// Reads from the primary only
+    pub fn primary(primary: PgPool) -> Self {
+        ReadPool {
+            primary,
+            replica: None,
+            replica_up: AtomicBool::new(false),
+        }
+    }
+
+    /// Connects lazily to the replica, so that the server starts while it is down, and
+    /// checks its health every `health_check_interval_secs`.
+    pub async fn connect(
+        config: &ReadReplicaConfig,
+        database_name: &str,
+        primary: PgPool,
+    ) -> Result<Arc<Self>> {
+        if config.url.is_empty() {
+            return Ok(Arc::new(Self::primary(primary)));
+        }
+        // As for the primary, the URL may point to the server only
+        let url = if config.url.ends_with(database_name) {
+            config.url.clone()
+        } else {
+            format!("{}/{}", config.url, database_name)
+        };
+        let replica = PgPoolOptions::new()
+            .max_connections(config.max_connections)
+            .acquire_timeout(Duration::from_secs(1))
+            .connect_lazy(&url)
+            .context("Failed to configure the read replica pool")?;
+
+        let read_pool = Arc::new(ReadPool {
+            primary,
+            replica: Some(replica),
+            replica_up: AtomicBool::new(false),
+        });
+        read_pool.check_replica().await;
+        if !read_pool.replica_up.load(Ordering::Relaxed) {
+            warn!("Read replica is not reachable, reading from the primary until it is");
+        }
+
+        let interval_secs = config.health_check_interval_secs.max(1);
+        tokio::spawn(
+            read_pool
+                .clone()
+                .monitor(Duration::from_secs(interval_secs)),
+        );
+        Ok(read_pool)
+    }
+
+    /// Pool to run a read query on
+    pub fn pool(&self) -> &PgPool {
+        match &self.replica {
+            Some(replica) if self.replica_up.load(Ordering::Relaxed) => replica,
+            _ => &self.primary,
+        }
+    }
+
+    async fn monitor(self: Arc<Self>, period: Duration) {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            self.check_replica().await;
+        }
+    }
+
+    async fn check_replica(&self) {
+        let Some(replica) = &self.replica else {
+            return;
+        };
+        let up = match sqlx::query("SELECT 1").execute(replica).await {
+            Ok(_) => true,
+            Err(e) => {
+                if self.replica_up.load(Ordering::Relaxed) {
+                    warn!("Read replica is down, reading from the primary: {e:#}");
+                }
+                false
+            }
+        };
+        if up && !self.replica_up.load(Ordering::Relaxed) {
+            info!("Read replica is up, query endpoints read from it");
+        }
+        self.replica_up.store(up, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use opentelemetry::{
+    metrics::{Counter, Gauge},
+    KeyValue,
+};
+use orderbook::ORDERBOOK_ACCOUNT_IDENTITY;
+use serde::Serialize;
+use tracing::{debug, error};
+use utoipa::ToSchema;
+
+use crate::conf::ReconciliationConfig;
+
+/// Balances owed on the orderbook in an asset backed by a token contract
+#[derive(Debug, Clone)]
+pub struct OwedAsset {
+    pub symbol: String,
+    pub contract_name: String,
+    /// Balances of the users, and their queued withdrawals, that are not paid out yet
+    pub owed: u128,
+}
+
+/// Outcome of the last reconciliation, served by `GET /reconciliation`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReconciliationReport {
+    /// "ok" when every asset is backed within the threshold, "drift" otherwise
+    pub status: &'static str,
+    pub checked_at_ms: u64,
+    pub max_drift_bps: u64,
+    pub assets: Vec<AssetReconciliation>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AssetReconciliation {
+    pub symbol: String,
+    pub contract_name: String,
+    pub owed: u128,
+    /// Balance of the orderbook account on the token contract, unset when it could not be read
+    pub held: Option<u128>,
+    /// Held minus owed: negative when the balances are not fully backed
+    pub drift: Option<i128>,
+    /// Shortfall of the held amount, in basis points of the owed amount
+    pub shortfall_bps: Option<u64>,
+    pub alert: bool,
+    pub error: Option<String>,
+}
+
+/// Reconciles the balances owed on the orderbook in each token backed asset against the
+/// tokens the orderbook account holds on the token contract, as served by the indexer. An asset
+/// whose shortfall exceeds `max_drift_bps` raises an alert, logged, counted and served by the
+/// API. A surplus, e.g. from transfers that were not credited, is reported but not alerted on.
+pub struct Reconciliation {
+    config: ReconciliationConfig,
+    indexer_url: String,
+    http: reqwest::Client,
+    last_report: Mutex<Option<ReconciliationReport>>,
+    drift: Gauge<i64>,
+    alerts: Counter<u64>,
+}
+
+impl Reconciliation {
+    pub fn new(config: ReconciliationConfig, indexer_url: &str) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms.max(1)))
+            .build()
+            .context("building the reconciliation http client")?;
+        let meter = opentelemetry::global::meter("app");
+        Ok(Reconciliation {
+            config,
+            indexer_url: indexer_url.trim_end_matches('/').to_string(),
+            http,
+            last_report: Mutex::new(None),
+            drift: meter
+                .i64_gauge("reconciliation.drift")
+                .with_description("Held minus owed balances of each asset, negative when short")
+                .build(),
+            alerts: meter
+                .u64_counter("reconciliation.alerts")
+                .with_description(
+                    "Reconciliations of an asset whose shortfall exceeds the threshold",
+                )
+                .build(),
+        })
+    }
+
+    pub fn interval_secs(&self) -> u64 {
+        self.config.interval_secs
+    }
+
+    pub fn last_report(&self) -> Option<ReconciliationReport> {
+        self.last_report
+            .lock()
+            .expect("reconciliation report poisoned")
+            .clone()
+    }
+
+    /// Reads the held balance of each asset and compares it to its owed balances
+    pub async fn reconcile(&self, owed_assets: Vec<OwedAsset>) {
+        let held = futures::future::join_all(
+            owed_assets
+                .iter()
+                .map(|asset| self.held_balance(&asset.contract_name)),
+        )
+        .await;
+
+        let mut assets = Vec::with_capacity(owed_assets.len());
+        for (asset, held) in owed_assets.into_iter().zip(held) {
+            let reconciled = match held {
+                Ok(held) => self.compare(asset, held),
+                Err(e) => {
+                    error!(
+                        "Could not read the {} held by the orderbook: {e:#}",
+                        asset.symbol
+                    );
+                    AssetReconciliation {
+                        symbol: asset.symbol,
+                        contract_name: asset.contract_name,
+                        owed: asset.owed,
+                        held: None,
+                        drift: None,
+                        shortfall_bps: None,
+                        alert: false,
+                        error: Some(format!("{e:#}")),
+                    }
+                }
+            };
+            assets.push(reconciled);
+        }
+
+        let report = ReconciliationReport {
+            status: if assets.iter().any(|asset| asset.alert) {
+                "drift"
+            } else {
+                "ok"
+            },
+            checked_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            max_drift_bps: self.config.max_drift_bps,
+            assets,
+        };
+        *self
+            .last_report
+            .lock()
+            .expect("reconciliation report poisoned") = Some(report);
+    }
+
+    fn compare(&self, asset: OwedAsset, held: u128) -> AssetReconciliation {
+        let drift = held as i128 - asset.owed as i128;
+        let shortfall = asset.owed.saturating_sub(held);
+        let shortfall_bps = if shortfall == 0 {
+            0
+        } else if asset.owed == 0 {
+            u64::MAX
+        } else {
+            (shortfall.saturating_mul(10_000) / asset.owed).min(u64::MAX as u128) as u64
+        };
+        let alert = shortfall_bps > self.config.max_drift_bps;
+
+        let labels = [
+            KeyValue::new("symbol", asset.symbol.clone()),
+            KeyValue::new("contract_name", asset.contract_name.clone()),
+        ];
+        self.drift.record(
+            drift.clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+            &labels,
+        );
+        if alert {
+            self.alerts.add(1, &labels);
+            error!(
+                symbol = %asset.symbol,
+                owed = %asset.owed,
+                held = %held,
+                "Orderbook balances of {} are short of {shortfall} ({shortfall_bps} bps), above the {} bps threshold",
+                asset.symbol,
+                self.config.max_drift_bps
+            );
+        } else {
+            debug!(
+                "Reconciled {}: {held} held for {} owed",
+                asset.symbol, asset.owed
+            );
+        }
+
+        AssetReconciliation {
+            symbol: asset.symbol,
+            contract_name: asset.contract_name,
+            owed: asset.owed,
+            held: Some(held),
+            drift: Some(drift),
+            shortfall_bps: Some(shortfall_bps),
+            alert,
+            error: None,
+        }
+    }
+
+    /// Balance of the orderbook account on a token contract
+    async fn held_balance(&self, contract_name: &str) -> Result<u128> {
+        let url = format!(
+            "{}/v1/indexer/contract/{contract_name}/balance/{ORDERBOOK_ACCOUNT_IDENTITY}",
+            self.indexer_url
+        );
+        let document: serde_json::Value = self
+            .http
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match document.get("balance") {
+            Some(serde_json::Value::Number(number)) => Ok(number.to_string().parse()?),
+            Some(serde_json::Value::String(balance)) => Ok(balance.parse()?),
+            Some(other) => bail!("{other} is not a balance"),
+            None => bail!("no balance in the response of {url}"),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Helpers shared by the debugging binaries that replay the persisted contract events.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context, Result};
+use orderbook::{
+    model::{ExecuteState, OrderbookEvent, UserInfo},
+    zk::FullState,
+};
+use sdk::{BlockHeight, LaneId, StateCommitment};
+use sqlx::{PgPool, Row};
+use tracing::{info, warn};
+
+use crate::init::DebugStateCommitment;
+
+/// Events stored for a commit, with the hash of the blob tx they were sent in
+pub struct CommitEvents {
+    pub commit_id: i64,
+    pub tx_hash: Option<String>,
+    /// Index of the action's blob, when several actions were batched in the same blob tx
+    pub blob_index: i32,
+    pub user_info: UserInfo,
+    pub events: Vec<OrderbookEvent>,
+}
+
+/// Commitments of a tx settled onchain, as indexed from its proof output
+pub struct OnchainCommitment {
+    pub initial_state: Vec<u8>,
+    pub next_state: Vec<u8>,
+    pub block_height: i64,
+    pub success: bool,
+}
+
+/// Events of every commit after `after_commit_id` up to `to_commit_id` (included), in commit
+/// order
+pub async fn fetch_commit_events(
+    pool: &PgPool,
+    after_commit_id: Option<i64>,
+    to_commit_id: Option<i64>,
+) -> Result<Vec<CommitEvents>> {
+    let rows = sqlx::query(
+        "
+        SELECT ce.commit_id, ce.user_info, ce.events,
+            COALESCE(o.sent_tx_hash, c.tx_hash) AS tx_hash,
+            COALESCE(o.blob_index, 0) AS blob_index
+        FROM contract_events ce
+        LEFT JOIN commits c ON c.commit_id = ce.commit_id
+        LEFT JOIN blob_tx_outbox o ON o.commit_id = ce.commit_id
+        WHERE ($1::bigint IS NULL OR ce.commit_id > $1)
+        AND ($2::bigint IS NULL OR ce.commit_id <= $2)
+        ORDER BY ce.commit_id ASC
+        ",
+    )
+    .bind(after_commit_id)
+    .bind(to_commit_id)
+    .fetch_all(pool)
+    .await
+    .context("fetching contract events")?;
+
+    rows.iter()
+        .map(|row| {
+            let commit_id: i64 = row.get("commit_id");
+            let user_info = borsh::from_slice(&row.get::<Vec<u8>, _>("user_info"))
+                .with_context(|| format!("invalid user info in commit {commit_id}"))?;
+            let events = borsh::from_slice(&row.get::<Vec<u8>, _>("events"))
+                .with_context(|| format!("invalid events in commit {commit_id}"))?;
+            Ok(CommitEvents {
+                commit_id,
+                tx_hash: row.get("tx_hash"),
+                blob_index: row.get("blob_index"),
+                user_info,
+                events,
+            })
+        })
+        .collect()
+}
+
+/// Light state after applying the events of every commit up to `to_commit_id` (included)
+pub async fn replay_light_state(pool: &PgPool, to_commit_id: i64) -> Result<ExecuteState> {
+    let commits = fetch_commit_events(pool, None, Some(to_commit_id)).await?;
+    info!("Replaying the events of {} commits", commits.len());
+
+    let mut state = ExecuteState::default();
+    for commit in &commits {
+        state
+            .apply_events(&commit.user_info, &commit.events)
+            .map_err(|e| anyhow!("Could not apply events of commit {}: {e}", commit.commit_id))?;
+    }
+    Ok(state)
+}
+
+/// Commitments settled onchain for the orderbook contract by blob tx hash and blob index,
+/// restricted to a single tx if `tx_hash` is given
+pub async fn fetch_onchain_commitments(
+    index_database_url: &str,
+    orderbook_cn: &str,
+    tx_hash: Option<&str>,
+) -> Result<HashMap<(String, i32), OnchainCommitment>> {
+    info!("Connecting to indexer database at {}", index_database_url);
+    let pool = PgPool::connect(index_database_url)
+        .await
+        .context("connecting to indexer database")?;
+    query_onchain_commitments(&pool, orderbook_cn, tx_hash).await
+}
+
+/// Same as [`fetch_onchain_commitments`], on a pool connected to the indexer database
+pub async fn query_onchain_commitments(
+    pool: &PgPool,
+    orderbook_cn: &str,
+    tx_hash: Option<&str>,
+) -> Result<HashMap<(String, i32), OnchainCommitment>> {
+    let rows = sqlx::query(
+        "
+        SELECT tx.tx_hash AS blob_tx_hash, tx.block_height, bpo.blob_index::integer AS blob_index,
+            bpo.hyli_output->>'initial_state' AS initial_state,
+            bpo.hyli_output->>'next_state' AS next_state,
+            bpo.hyli_output->>'success' AS success
+        FROM transactions tx
+        JOIN blob_proof_outputs bpo ON bpo.blob_tx_hash = tx.tx_hash
+        WHERE bpo.contract_name = $1
+        AND ($2::text IS NULL OR tx.tx_hash = $2)
+        ORDER BY tx.block_height, tx.index ASC
+        ",
+    )
+    .bind(orderbook_cn)
+    .bind(tx_hash)
+    .fetch_all(pool)
+    .await
+    .context("fetching settled commitments")?;
+
+    rows.iter()
+        .map(|row| {
+            let tx_hash: String = row.get("blob_tx_hash");
+            let initial_state = serde_json::from_str(row.get("initial_state"))
+                .with_context(|| format!("invalid initial state of tx {tx_hash}"))?;
+            let next_state = serde_json::from_str(row.get("next_state"))
+                .with_context(|| format!("invalid next state of tx {tx_hash}"))?;
+            let commitment = OnchainCommitment {
+                initial_state,
+                next_state,
+                block_height: row.get("block_height"),
+                success: row.get::<Option<String>, _>("success").as_deref() == Some("true"),
+            };
+            Ok(((tx_hash, row.get("blob_index")), commitment))
+        })
+        .collect()
+}
+
+/// Commitment of the full state built from a light state
+pub fn commit_state(
+    state: &ExecuteState,
+    secret: &[u8],
+    lane_id: &LaneId,
+) -> Result<StateCommitment> {
+    let full_state = FullState::from_data(
+        state,
+        secret.to_vec(),
+        lane_id.clone(),
+        BlockHeight::default(),
+    )
+    .map_err(|e| anyhow!("Could not build full state: {e}"))?;
+    Ok(full_state.commit())
+}
+
+/// Logs the parts of the state that differ between two commitments
+pub fn log_state_diff(label: &str, expected: &StateCommitment, actual: &StateCommitment) {
+    let expected = DebugStateCommitment::from(expected.clone());
+    let actual = DebugStateCommitment::from(actual.clone());
+    let diff = expected.diff(&actual);
+    if diff.is_empty() {
+        warn!("No diff keys found, but the commitments differ");
+    } else {
+        warn!("State diffs ({label}):");
+        for (key, value) in diff.iter() {
+            warn!("  {}: {}", key, value);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use orderbook::{
+    model::{ExecuteState, Order, OrderSide, UserInfo},
+    zk::smt::GetKey,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use tracing::info;
+use utoipa::ToSchema;
+
+/// Pre-trade limits of a user. Notionals are expressed in quote asset units.
+/// A limit left unset falls back to the configured default, and no limit applies if that is unset too.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RiskLimits {
+    /// Maximum notional of a single order
+    pub max_order_notional: Option<u64>,
+    /// Maximum notional of the resting orders of a user on a pair, including the new order
+    pub max_open_notional: Option<u64>,
+}
+
+impl RiskLimits {
+    fn or(self, fallback: RiskLimits) -> RiskLimits {
+        RiskLimits {
+            max_order_notional: self.max_order_notional.or(fallback.max_order_notional),
+            max_open_notional: self.max_open_notional.or(fallback.max_open_notional),
+        }
+    }
+}
+
+/// Inline risk checks, evaluated on orders before they reach the light execution.
+/// Per identity limits are persisted in the database and managed through the admin API.
+pub struct RiskManager {
+    pool: PgPool,
+    defaults: RiskLimits,
+    overrides: HashMap<String, RiskLimits>,
+}
+
+impl RiskManager {
+    pub async fn load(pool: PgPool, defaults: RiskLimits) -> Result<Self> {
+        let rows =
+            sqlx::query("SELECT identity, max_order_notional, max_open_notional FROM risk_limits")
+                .fetch_all(&pool)
+                .await?;
+
+        let overrides: HashMap<String, RiskLimits> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get("identity"),
+                    RiskLimits {
+                        max_order_notional: row
+                            .get::<Option<i64>, _>("max_order_notional")
+                            .map(|v| v.max(0) as u64),
+                        max_open_notional: row
+                            .get::<Option<i64>, _>("max_open_notional")
+                            .map(|v| v.max(0) as u64),
+                    },
+                )
+            })
+            .collect();
+        info!("Loaded risk limits of {} users", overrides.len());
+
+        Ok(RiskManager {
+            pool,
+            defaults,
+            overrides,
+        })
+    }
+
+    /// Limits that apply to a user
+    pub fn limits_for(&self, identity: &str) -> RiskLimits {
+        match self.overrides.get(identity) {
+            Some(limits) => limits.or(self.defaults),
+            None => self.defaults,
+        }
+    }
+
+    /// Sets the limits of a user, or removes them when `limits` is None
+    pub async fn set_limits(&mut self, identity: &str, limits: Option<RiskLimits>) -> Result<()> {
+        match limits {
+            Some(limits) => {
+                sqlx::query(
+                    "INSERT INTO risk_limits (identity, max_order_notional, max_open_notional)
+                     VALUES ($1, $2, $3)
+                     ON CONFLICT (identity) DO UPDATE
+                     SET max_order_notional = EXCLUDED.max_order_notional,
+                         max_open_notional = EXCLUDED.max_open_notional,
+                         updated_at = now()",
+                )
+                .bind(identity)
+                .bind(
+                    limits
+                        .max_order_notional
+                        .map(|v| v.min(i64::MAX as u64) as i64),
+                )
+                .bind(
+                    limits
+                        .max_open_notional
+                        .map(|v| v.min(i64::MAX as u64) as i64),
+                )
+                .execute(&self.pool)
+                .await?;
+                self.overrides.insert(identity.to_string(), limits);
+            }
+            None => {
+                sqlx::query("DELETE FROM risk_limits WHERE identity = $1")
+                    .bind(identity)
+                    .execute(&self.pool)
+                    .await?;
+                self.overrides.remove(identity);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks an order against the limits of the user.
+    /// Market orders are valued at the best price on the opposite side of the book.
+    pub fn check_order(
+        &self,
+        state: &ExecuteState,
+        user_info: &UserInfo,
+        order: &Order,
+    ) -> Result<(), String> {
+        let limits = self.limits_for(&user_info.user);
+        if limits.max_order_notional.is_none() && limits.max_open_notional.is_none() {
+            return Ok(());
+        }
+
+        let base_scale = state
+            .assets_info
+            .get(&order.pair.0)
+            .ok_or(format!("Asset info for {} not found", order.pair.0))?
+            .scale;
+
+        let price = match order.price {
+            Some(price) => price,
+            None => {
+                let counter_orders = match order.order_side {
+                    OrderSide::Bid => state.order_manager.ask_orders.get(&order.pair),
+                    OrderSide::Ask => state.order_manager.bid_orders.get(&order.pair),
+                };
+                let best_price = counter_orders.and_then(|levels| match order.order_side {
+                    OrderSide::Bid => levels.keys().next(),
+                    OrderSide::Ask => levels.keys().next_back(),
+                });
+                // Without liquidity, the market order will be rejected anyway
+                let Some(best_price) = best_price else {
+                    return Ok(());
+                };
+                *best_price
+            }
+        };
+
+        let order_notional = notional(price, order.quantity, base_scale);
+        if let Some(max_order_notional) = limits.max_order_notional {
+            if order_notional > max_order_notional as u128 {
+                return Err(format!(
+                    "Order notional {order_notional} exceeds the limit of {max_order_notional} for user {}",
+                    user_info.user
+                ));
+            }
+        }
+
+        if let Some(max_open_notional) = limits.max_open_notional {
+            let user_key = user_info.get_key();
+            let open_notional: u128 = state
+                .order_manager
+                .orders_owner
+                .iter()
+                .filter(|(_, owner)| **owner == user_key)
+                .filter_map(|(order_id, _)| state.order_manager.orders.get(order_id))
+                .filter(|open_order| open_order.pair == order.pair)
+                .map(|open_order| {
+                    notional(
+                        open_order.price.unwrap_or_default(),
+                        open_order.quantity,
+                        base_scale,
+                    )
+                })
+                .sum();
+
+            if open_notional + order_notional > max_open_notional as u128 {
+                return Err(format!(
+                    "Open notional {} on {}/{} would exceed the limit of {max_open_notional} for user {}",
+                    open_notional + order_notional,
+                    order.pair.0,
+                    order.pair.1,
+                    user_info.user
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Value of `quantity` base units at `price`, in quote asset units
+fn notional(price: u64, quantity: u64, base_scale: u64) -> u128 {
+    price as u128 * quantity as u128 / 10u128.pow(base_scale as u32)
+}
@@ -0,0 +1,538 @@
+use crate::{
+    api::{ApiModule, ApiModuleCtx},
+    app::{OrderbookModule, OrderbookModuleCtx},
+    audit::AuditLog,
+    balance_feed::BalanceFeed,
+    bridge::{limits::BridgeLimits, BridgeModule, BridgeModuleCtx, BridgePause},
+    business_metrics::BusinessCounters,
+    checkpoint::CheckpointPublisher,
+    clock::Clock,
+    collateral::AssetBackings,
+    conf::{Conf, HealthConfig},
+    config_watcher::ConfigWatcher,
+    database::{BlobOutbox, DatabaseModule, DatabaseModuleCtx, WorkerQueues},
+    deposit_watcher::{DepositWatcherModule, DepositWatcherModuleCtx},
+    fees::{FeeTierModule, FeeTierModuleCtx},
+    oracle::{OracleModule, OracleModuleCtx},
+    prover::{proving_backend, OrderbookProverCtx, OrderbookProverModule, ProverMetrics},
+    rate_limit::RateLimiter,
+    read_replica::ReadPool,
+    reconciliation::Reconciliation,
+    services::index_price_service::IndexPriceService,
+    settlement_check::{SettlementCheck, SettlementCheckModule, SettlementCheckModuleCtx},
+    setup::{setup_database, setup_services, ServiceContext},
+    snapshot::{SnapshotModule, SnapshotModuleCtx, SnapshotStore},
+};
+use anyhow::{Context, Result};
+use axum::Router;
+use clap::Parser;
+use hyli_modules::{
+    bus::{metrics::BusMetrics, SharedMessageBus},
+    modules::{
+        contract_listener::{ContractListener, ContractListenerConf},
+        da_listener::{DAListener, DAListenerConf},
+        rest::{RestApi, RestApiRunContext},
+        BuildApiContextInner, ModulesHandler,
+    },
+    utils::db::use_fresh_db,
+};
+use sdk::{api::NodeInfo, info, BlockHeight};
+use std::{collections::HashSet, sync::Arc, time::Duration};
+use tokio::sync::{Mutex, RwLock};
+use tracing::error;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    #[arg(long, default_value = "config.toml")]
+    pub config_file: Vec<String>,
+
+    #[arg(long, default_value = "orderbook")]
+    pub orderbook_cn: String,
+
+    #[arg(long, default_value = "oranj")] // This should be USDC contract or so
+    pub collateral_token_cn: String,
+
+    #[arg(long, default_value = "false")]
+    pub clean_db: bool,
+
+    #[arg(long, default_value = "false")]
+    pub no_check: bool,
+
+    #[arg(long, default_value = "false")]
+    pub no_prover: bool,
+
+    /// Execute txs natively and submit their outputs as proofs of the test verifier,
+    /// for integration and load tests against a node accepting them
+    #[arg(long, default_value = "false")]
+    pub mock_prover: bool,
+
+    #[arg(long, default_value = "false")]
+    pub bridge: bool,
+
+    #[arg(long, default_value = "false")]
+    pub offline: bool,
+
+    #[arg(long, default_value = "false")]
+    pub tracing: bool,
+
+    /// Clean the data directory before starting the server
+    /// Argument used by hylix tests commands
+    #[arg(long, default_value = "false")]
+    pub clean_data_directory: bool,
+
+    /// Server port (overrides config)
+    /// Argument used by hylix tests commands
+    #[arg(long)]
+    pub server_port: Option<u16>,
+}
+
+/// Runs the server until it is shut down. Logging is left to the caller, so that test
+/// harnesses embedding the server can set it up once for the whole process.
+pub async fn run(args: Args, mut config: Conf) -> Result<()> {
+    // Tenants share the node and the Postgres server, everything else is namespaced
+    config.data_directory = config.tenant.data_directory(&config.data_directory);
+    let orderbook_cn = config.tenant.prefixed(&args.orderbook_cn);
+
+    if args.clean_data_directory && std::fs::exists(&config.data_directory).unwrap_or(false) {
+        info!("Cleaning data directory: {:?}", &config.data_directory);
+        std::fs::remove_dir_all(&config.data_directory).context("cleaning data directory")?;
+    }
+
+    std::fs::create_dir_all(&config.data_directory).context("creating data directory")?;
+    // Stops the embedded database, if any, when the server exits
+    let _embedded_db = crate::embedded_db::start_if_configured(
+        &config.data_directory,
+        &mut config.database_url,
+        &mut config.indexer_database_url,
+    )
+    .await?;
+    let had_db_placeholder = config.database_url.contains("{db}");
+    use_fresh_db(&config.data_directory, &mut config.database_url).await?;
+    if had_db_placeholder {
+        if let Some((_, db_name)) = config.database_url.rsplit_once('/') {
+            if !db_name.is_empty() {
+                config.database_name = db_name.to_string();
+            }
+        }
+    }
+    config
+        .tenant
+        .namespace_database(&mut config.database_url, &mut config.database_name);
+
+    let config = Arc::new(config);
+
+    let registry = hyli_modules::telemetry::init_prometheus_registry_meter_provider()?;
+
+    info!("Starting orderbook with config: {:?}", &config);
+    info!("Args: {:?}", args);
+
+    let pool = setup_database(&config, args.clean_db).await?;
+    let read_pool =
+        ReadPool::connect(&config.read_replica, &config.database_name, pool.clone()).await?;
+    let ServiceContext {
+        user_service,
+        asset_service,
+        book_service,
+        node_client,
+        resilient_node_client,
+        indexer_client,
+        validator_lane_id,
+        bridge_service,
+    } = setup_services(&config, pool.clone(), args.offline, args.bridge).await?;
+
+    let secret = config.secret.clone();
+
+    let snapshots = if config.snapshot.enabled {
+        Some(Arc::new(SnapshotStore::open(
+            &config.data_directory,
+            pool.clone(),
+            secret.clone(),
+            validator_lane_id.clone(),
+        )?))
+    } else {
+        None
+    };
+
+    let last_settled_tx = crate::init::get_last_settled_tx(
+        asset_service.clone(),
+        args.offline,
+        &orderbook_cn.clone().into(),
+        &indexer_client,
+    )
+    .await?;
+
+    let (light_state, full_state) = crate::init::init_orderbook_from_database(
+        validator_lane_id.clone(),
+        secret.clone(),
+        asset_service.clone(),
+        user_service.clone(),
+        book_service.clone(),
+        &node_client,
+        !args.no_check,
+        &last_settled_tx,
+        args.offline,
+        snapshots.as_deref(),
+        &pool,
+    )
+    .await
+    .map_err(|e| anyhow::Error::msg(e.1))?;
+
+    let settled_commit_id = match &last_settled_tx {
+        Some(tx_hash) => asset_service
+            .read()
+            .await
+            .get_commit_id_from_tx_hash(tx_hash)
+            .await
+            .unwrap_or(0),
+        None => 0,
+    };
+
+    if let Some(snapshots) = &snapshots {
+        snapshots
+            .checkpoint(settled_commit_id, &light_state, &full_state)
+            .await
+            .context("writing state snapshot")?;
+    }
+
+    let backend = proving_backend(
+        config.prover_backend,
+        &config.prover_network,
+        args.mock_prover,
+    )?;
+
+    if !args.offline {
+        let contracts = vec![crate::init::ContractInit {
+            name: orderbook_cn.clone().into(),
+            program_id: backend.program_id(),
+            verifier: backend.verifier(),
+            initial_state: full_state.commit(),
+        }];
+
+        hyli_registry::upload_elf(
+            backend.elf(),
+            &hex::encode(backend.program_id().0),
+            &orderbook_cn,
+            backend.toolchain(),
+            None,
+        )
+        .await
+        .context("Uploading orderbook ELF to registry")?;
+
+        match crate::init::init_node(node_client.clone(), contracts, !args.no_check).await {
+            Ok(_) => {}
+            Err(e) => {
+                error!("Error initializing node: {:?}", e);
+                return Ok(());
+            }
+        }
+    }
+
+    let bus = SharedMessageBus::new(BusMetrics::global());
+
+    let mut handler = ModulesHandler::new(&bus, config.data_directory.clone()).await;
+
+    let api_ctx = Arc::new(BuildApiContextInner {
+        router: std::sync::Mutex::new(Some(Router::new())),
+        openapi: Default::default(),
+    });
+
+    let database_ctx = Arc::new(DatabaseModuleCtx {
+        pool: pool.clone(),
+        read_pool,
+        user_service: user_service.clone(),
+        asset_service: asset_service.clone(),
+        client: resilient_node_client.clone(),
+        no_blobs: args.offline,
+        metrics: crate::database::DatabaseMetrics::new(),
+        snapshots: snapshots.clone(),
+        blob_batch: config.blob_batch.clone(),
+        blob_outbox: Arc::new(BlobOutbox::new(config.blob_outbox.clone())),
+        worker_queues: Arc::new(WorkerQueues::new(config.database_workers.clone())),
+        counters: Arc::new(BusinessCounters::load(&pool).await?),
+        event_retention: config.event_retention.clone(),
+        notifier: crate::storage::notifier(config.notifications, &pool),
+    });
+
+    let balance_feed = Arc::new(BalanceFeed::default());
+
+    let mut bridged_tokens = Vec::new();
+    if args.bridge && !args.offline {
+        bridged_tokens.push(args.collateral_token_cn.clone().into());
+        for token in config.bridge.tokens.iter() {
+            bridged_tokens.push(token.contract_name.clone().into());
+        }
+    }
+    let asset_backings = Arc::new(AssetBackings::new(&config.collateral, bridged_tokens));
+    asset_backings.persist(&pool).await?;
+    let bridge_pause = Arc::new(BridgePause::new(
+        config.bridge.paused,
+        light_state.bridge_paused,
+    ));
+    let bridge_limits = Arc::new(BridgeLimits::new(&config.bridge.limits));
+    let reconciliation = Arc::new(Reconciliation::new(
+        config.reconciliation.clone(),
+        &config.indexer_url,
+    )?);
+
+    let audit_log = Arc::new(
+        AuditLog::new(pool.clone(), &config.audit, &config.data_directory)
+            .await
+            .context("opening the audit log")?,
+    );
+
+    // Settings applied again when the config is reloaded, on SIGHUP or by `POST /admin/config`
+    let rate_limiter = Arc::new(RateLimiter::new(
+        config.rate_limit.clone(),
+        // Wall time even when the test harness freezes its clock, so that its scenarios are
+        // not throttled until they advance it
+        Clock::default(),
+    ));
+    let fee_config = Arc::new(RwLock::new(config.fees.clone()));
+    let config_watcher = Arc::new(ConfigWatcher::new(
+        args.config_file.clone(),
+        &config,
+        rate_limiter.clone(),
+        fee_config.clone(),
+        database_ctx.worker_queues.clone(),
+        audit_log.clone(),
+    ));
+    tokio::spawn(config_watcher.clone().watch_hangups());
+
+    let run_prover = !args.no_prover && !args.offline;
+    let check_settlements = config.settlement_check.enabled && !args.offline;
+    let settlement_check = check_settlements.then(|| {
+        Arc::new(SettlementCheck::new(
+            &config.settlement_check,
+            settled_commit_id,
+        ))
+    });
+    let settled_state = check_settlements.then(|| full_state.clone());
+    let proven_state = Arc::new(Mutex::new(full_state));
+
+    let orderbook_ctx = Arc::new(OrderbookModuleCtx {
+        api: api_ctx.clone(),
+        orderbook_cn: orderbook_cn.clone().into(),
+        lane_id: validator_lane_id.clone(),
+        default_state: light_state.clone(),
+        asset_service: asset_service.clone(),
+        user_service: user_service.clone(),
+        client: resilient_node_client.clone(),
+        database_ctx: database_ctx.clone(),
+        admin_secret: config.admin_secret.clone(),
+        risk_limits: config.risk,
+        balance_feed: balance_feed.clone(),
+        asset_backings,
+        bridge_pause: bridge_pause.clone(),
+        bridge_limits: bridge_limits.clone(),
+        reconciliation,
+        proven_state: run_prover.then(|| proven_state.clone()),
+        clock: if cfg!(feature = "test-mode") {
+            Clock::frozen()
+        } else {
+            Clock::default()
+        },
+        rate_limiter,
+        funding: config.funding.clone(),
+        liquidation: config.liquidation.clone(),
+        withdrawal_queue: config.withdrawal_queue.clone(),
+        trusted_deposits: config.deposits.trusted_endpoint,
+        health: HealthConfig {
+            check_node: config.health.check_node && !args.offline,
+            ..config.health.clone()
+        },
+        grpc_server_port: config.grpc_server_port,
+        audit_log: audit_log.clone(),
+        config_watcher,
+        settlement_check: settlement_check.clone(),
+    });
+
+    let api_module_ctx = Arc::new(ApiModuleCtx {
+        api: api_ctx.clone(),
+        contract1_cn: orderbook_cn.clone().into(),
+    });
+
+    handler
+        .build_module::<OrderbookModule>(orderbook_ctx.clone())
+        .await?;
+
+    if run_prover {
+        let prover = backend.prover().await?;
+
+        let orderbook_prover_ctx = Arc::new(OrderbookProverCtx {
+            node_client: resilient_node_client.clone(),
+            orderbook_cn: orderbook_cn.clone().into(),
+            direct_prover: backend.direct_prover()?,
+            backend,
+            prover,
+            lane_id: validator_lane_id,
+            orderbook: proven_state,
+            initial_commit_id: settled_commit_id,
+            pool: pool.clone(),
+            max_txs_per_proof: config.max_txs_per_proof,
+            proof_batch_window: Duration::from_millis(config.proof_batch_window_ms),
+            proving_workers: config.proving_workers,
+            max_proof_reordering: config.max_proof_reordering,
+            metrics: ProverMetrics::new(),
+            balance_feed: Some(balance_feed.clone()),
+        });
+
+        handler
+            .build_module::<OrderbookProverModule>(orderbook_prover_ctx.clone())
+            .await?;
+
+        handler
+            .build_module::<ContractListener>(ContractListenerConf {
+                database_url: config.indexer_database_url.clone(),
+                data_directory: config.data_directory.clone(),
+                contracts: HashSet::from([orderbook_cn.clone().into()]),
+                poll_interval: Duration::from_secs(1),
+            })
+            .await?;
+    }
+
+    handler
+        .build_module::<DatabaseModule>(database_ctx.clone())
+        .await?;
+
+    handler
+        .build_module::<ApiModule>(api_module_ctx.clone())
+        .await?;
+
+    if !config.fees.tiers.is_empty() {
+        handler
+            .build_module::<FeeTierModule>(Arc::new(FeeTierModuleCtx {
+                user_service: user_service.clone(),
+                fee_config,
+            }))
+            .await?;
+    }
+
+    if !config.oracle.sources.is_empty() {
+        handler
+            .build_module::<OracleModule>(Arc::new(OracleModuleCtx {
+                config: config.oracle.clone(),
+                indexer_url: config.indexer_url.clone(),
+                index_price_service: Arc::new(IndexPriceService::new(pool.clone())),
+            }))
+            .await?;
+    }
+
+    if let Some(snapshots) = &snapshots {
+        let checkpoints = if config.checkpoint.enabled {
+            Some(Arc::new(CheckpointPublisher::new(
+                &config.checkpoint,
+                pool.clone(),
+                resilient_node_client.clone(),
+                orderbook_cn.clone().into(),
+            )?))
+        } else {
+            None
+        };
+        handler
+            .build_module::<SnapshotModule>(Arc::new(SnapshotModuleCtx {
+                store: snapshots.clone(),
+                interval_secs: config.snapshot.interval_secs,
+                checkpoints,
+            }))
+            .await?;
+    }
+
+    if config.deposits.watch && !args.offline {
+        handler
+            .build_module::<DepositWatcherModule>(Arc::new(DepositWatcherModuleCtx {
+                asset_service: asset_service.clone(),
+                pool: pool.clone(),
+            }))
+            .await?;
+    }
+
+    if let (Some(check), Some(state)) = (settlement_check, settled_state) {
+        handler
+            .build_module::<SettlementCheckModule>(Arc::new(SettlementCheckModuleCtx {
+                check,
+                pool: pool.clone(),
+                indexer_database_url: config.indexer_database_url.clone(),
+                orderbook_cn: orderbook_cn.clone().into(),
+                state,
+                settled_commit_id,
+            }))
+            .await?;
+    }
+
+    // Settled txs are read from the DA by the deposit watcher, by the settlement check, and by
+    // the bridge, that pays out the withdrawals
+    if (config.deposits.watch || check_settlements || args.bridge) && !args.offline {
+        let start_block = match config.deposits.start_block {
+            Some(start_block) => BlockHeight(start_block),
+            None => resilient_node_client
+                .get_block_height()
+                .await
+                .context("fetching the block to read the DA from")?,
+        };
+        handler
+            .build_module::<DAListener>(DAListenerConf {
+                data_directory: config.data_directory.clone(),
+                da_read_from: config.da_read_from.clone(),
+                start_block: Some(start_block),
+                timeout_client_secs: 10,
+            })
+            .await?;
+    }
+
+    if args.bridge && !args.offline {
+        let bridge_service = bridge_service
+            .expect("Bridge service should be initialized when the bridge flag is set");
+        handler
+            .build_module::<BridgeModule>(Arc::new(BridgeModuleCtx {
+                api: api_ctx.clone(),
+                collateral_token_cn: args.collateral_token_cn.clone().into(),
+                bridge_config: config.bridge.clone(),
+                pool: pool.clone(),
+                asset_service: asset_service.clone(),
+                bridge_service: bridge_service.clone(),
+                orderbook_cn: orderbook_cn.clone().into(),
+                bridge_pause: bridge_pause.clone(),
+                bridge_limits: bridge_limits.clone(),
+                audit_log: audit_log.clone(),
+            }))
+            .await?;
+    }
+
+    // Should come last so the other modules have nested their own routes.
+    #[allow(clippy::expect_used, reason = "Fail on misconfiguration")]
+    let router = api_ctx
+        .router
+        .lock()
+        .expect("Context router should be available.")
+        .take()
+        .expect("Context router should be available.");
+    #[allow(clippy::expect_used, reason = "Fail on misconfiguration")]
+    let openapi = api_ctx
+        .openapi
+        .lock()
+        .expect("OpenAPI should be available")
+        .clone();
+
+    handler
+        .build_module::<RestApi>(RestApiRunContext {
+            port: args.server_port.unwrap_or(config.rest_server_port),
+            max_body_size: config.rest_server_max_body_size,
+            registry,
+            router,
+            openapi,
+            info: NodeInfo {
+                id: config.id.clone(),
+                da_address: config.da_read_from.clone(),
+                pubkey: None,
+            },
+        })
+        .await?;
+
+    handler.start_modules().await?;
+
+    // Run until shut down or an error occurs
+    handler.exit_process().await?;
+
+    Ok(())
+}
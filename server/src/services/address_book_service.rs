@@ -0,0 +1,146 @@
+use client_sdk::contract_indexer::AppError;
+use orderbook::model::WithdrawDestination;
+use reqwest::StatusCode;
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use utoipa::ToSchema;
+
+/// Maximum number of withdrawal addresses saved by a user
+pub const MAX_SAVED_ADDRESSES: i64 = 100;
+
+/// Maximum length of a label, in characters
+pub const MAX_LABEL_LEN: usize = 64;
+
+/// Withdrawal destination saved by a user
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SavedAddress {
+    pub network: String,
+    pub address: String,
+    pub label: String,
+    /// Unix timestamp in milliseconds
+    pub created_at: i64,
+    /// Unix timestamp in milliseconds of the last withdrawal sent to this destination
+    pub last_used_at: Option<i64>,
+    pub use_count: i32,
+}
+
+pub struct AddressBookService {
+    pool: PgPool,
+}
+
+impl AddressBookService {
+    pub fn new(pool: PgPool) -> Self {
+        AddressBookService { pool }
+    }
+
+    /// Saved addresses of a user, most recently used first
+    pub async fn list(&self, identity: &str) -> Result<Vec<SavedAddress>, AppError> {
+        let rows = sqlx::query(
+            "SELECT network, address, label, use_count,
+                (EXTRACT(EPOCH FROM created_at) * 1000)::bigint AS created_at,
+                (EXTRACT(EPOCH FROM last_used_at) * 1000)::bigint AS last_used_at
+            FROM withdrawal_addresses
+            WHERE identity = $1
+            ORDER BY last_used_at DESC NULLS LAST, created_at DESC",
+        )
+        .bind(identity)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(saved_address_from_row).collect())
+    }
+
+    /// Saves an address, or renames it when it was already saved
+    pub async fn save(
+        &self,
+        identity: &str,
+        destination: &WithdrawDestination,
+        label: &str,
+    ) -> Result<SavedAddress, AppError> {
+        if destination.network.is_empty() || destination.address.is_empty() {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Network and address must not be empty"),
+            ));
+        }
+        if label.is_empty() || label.chars().count() > MAX_LABEL_LEN {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Label must be between 1 and {MAX_LABEL_LEN} characters long"),
+            ));
+        }
+
+        // The limit is checked in the insert, so that concurrent requests cannot exceed it
+        let row = sqlx::query(
+            "INSERT INTO withdrawal_addresses (identity, network, address, label)
+            SELECT $1, $2, $3, $4
+            WHERE (SELECT COUNT(*) FROM withdrawal_addresses WHERE identity = $1) < $5
+            ON CONFLICT (identity, network, address) DO UPDATE SET label = EXCLUDED.label
+            RETURNING network, address, label, use_count,
+                (EXTRACT(EPOCH FROM created_at) * 1000)::bigint AS created_at,
+                (EXTRACT(EPOCH FROM last_used_at) * 1000)::bigint AS last_used_at",
+        )
+        .bind(identity)
+        .bind(&destination.network)
+        .bind(&destination.address)
+        .bind(label)
+        .bind(MAX_SAVED_ADDRESSES)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.as_ref().map(saved_address_from_row).ok_or_else(|| {
+            AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("At most {MAX_SAVED_ADDRESSES} addresses can be saved"),
+            )
+        })
+    }
+
+    /// Deletes a saved address, returning whether it existed
+    pub async fn delete(
+        &self,
+        identity: &str,
+        destination: &WithdrawDestination,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "DELETE FROM withdrawal_addresses WHERE identity = $1 AND network = $2 AND address = $3",
+        )
+        .bind(identity)
+        .bind(&destination.network)
+        .bind(&destination.address)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Records a withdrawal to a destination, if the user saved it
+    pub async fn mark_used(
+        &self,
+        identity: &str,
+        destination: &WithdrawDestination,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            "UPDATE withdrawal_addresses SET last_used_at = now(), use_count = use_count + 1
+            WHERE identity = $1 AND network = $2 AND address = $3",
+        )
+        .bind(identity)
+        .bind(&destination.network)
+        .bind(&destination.address)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn saved_address_from_row(row: &sqlx::postgres::PgRow) -> SavedAddress {
+    SavedAddress {
+        network: row.get("network"),
+        address: row.get("address"),
+        label: row.get("label"),
+        created_at: row.get("created_at"),
+        last_used_at: row.get("last_used_at"),
+        use_count: row.get("use_count"),
+    }
+}
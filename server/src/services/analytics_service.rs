@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use client_sdk::contract_indexer::AppError;
+use orderbook::model::{OrderSide, Pair};
+use orderbook::order_manager::OrderManager;
+use serde::Serialize;
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::read_replica::ReadPool;
+
+/// Diagnostic view of a market, for operators
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PairAnalytics {
+    pub symbol: String,
+    pub book: BookDepth,
+    pub trades_24h: PairTradeStats,
+}
+
+/// Resting orders of a pair, from the in-memory book
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct BookDepth {
+    pub bid_orders: usize,
+    pub ask_orders: usize,
+    /// Sum of the remaining quantities of the resting orders
+    pub bid_quantity: u64,
+    pub ask_quantity: u64,
+    pub best_bid: Option<u64>,
+    pub best_ask: Option<u64>,
+}
+
+impl BookDepth {
+    pub fn of(order_manager: &OrderManager, pair: &Pair) -> Self {
+        let mut depth = BookDepth::default();
+        for side in [OrderSide::Bid, OrderSide::Ask] {
+            let Some(levels) = order_manager.side_map(&side).get(pair) else {
+                continue;
+            };
+            let order_ids = levels.values().flatten();
+            let orders = order_ids.clone().count();
+            let quantity = order_ids
+                .filter_map(|order_id| order_manager.orders.get(order_id))
+                .map(|order| order.quantity)
+                .sum();
+            match side {
+                OrderSide::Bid => {
+                    depth.bid_orders = orders;
+                    depth.bid_quantity = quantity;
+                    depth.best_bid = levels.keys().next_back().copied();
+                }
+                OrderSide::Ask => {
+                    depth.ask_orders = orders;
+                    depth.ask_quantity = quantity;
+                    depth.best_ask = levels.keys().next().copied();
+                }
+            }
+        }
+        depth
+    }
+}
+
+/// Trades of a pair over the last 24 hours
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PairTradeStats {
+    pub trade_count: i64,
+    /// Traded base quantity
+    pub volume: i64,
+    /// Price of the last trade, whenever it happened
+    pub last_price: Option<i64>,
+    /// Unix timestamp in milliseconds of the last trade
+    pub last_trade_at: Option<i64>,
+    /// Average time maker orders rested in the book before being filled, in milliseconds
+    pub avg_fill_latency_ms: Option<f64>,
+}
+
+pub struct AnalyticsService {
+    pool: Arc<ReadPool>,
+}
+
+impl AnalyticsService {
+    pub fn new(pool: Arc<ReadPool>) -> Self {
+        AnalyticsService { pool }
+    }
+
+    pub async fn get_trade_stats(&self, instrument_id: i64) -> Result<PairTradeStats, AppError> {
+        let row = sqlx::query(
+            "
+            WITH recent AS (
+                SELECT t.qty, t.trade_time - o.created_at AS fill_latency
+                FROM trade_events t
+                LEFT JOIN orders o ON o.order_id = t.maker_order_id
+                WHERE t.instrument_id = $1 AND t.trade_time > now() - interval '24 hours'
+            ), last_trade AS (
+                SELECT price, (EXTRACT(EPOCH FROM trade_time) * 1000)::bigint AS trade_time
+                FROM trade_events
+                WHERE instrument_id = $1
+                ORDER BY commit_id DESC, trade_id DESC
+                LIMIT 1
+            )
+            SELECT
+                (SELECT count(*) FROM recent) AS trade_count,
+                (SELECT COALESCE(sum(qty), 0)::bigint FROM recent) AS volume,
+                (SELECT (EXTRACT(EPOCH FROM avg(fill_latency)) * 1000)::float8 FROM recent) AS avg_fill_latency_ms,
+                (SELECT price FROM last_trade) AS last_price,
+                (SELECT trade_time FROM last_trade) AS last_trade_at
+            ",
+        )
+        .bind(instrument_id)
+        .fetch_one(self.pool.pool())
+        .await?;
+
+        Ok(PairTradeStats {
+            trade_count: row.get("trade_count"),
+            volume: row.get("volume"),
+            last_price: row.get("last_price"),
+            last_trade_at: row.get("last_trade_at"),
+            avg_fill_latency_ms: row.get("avg_fill_latency_ms"),
+        })
+    }
+}
@@ -0,0 +1,172 @@
+use client_sdk::contract_indexer::AppError;
+use rand::RngCore;
+use reqwest::StatusCode;
+use serde::Serialize;
+use sha3::{Digest, Sha3_256};
+use sqlx::{PgPool, Row};
+use utoipa::ToSchema;
+
+/// Maximum number of active API keys of a user
+pub const MAX_API_KEYS: i64 = 20;
+
+/// Maximum length of a label, in characters
+pub const MAX_LABEL_LEN: usize = 64;
+
+/// Prefix of the keys, so that they are recognizable when leaked
+const KEY_PREFIX: &str = "hlq_";
+
+/// API key of a user, without the key itself
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKey {
+    pub key_id: i64,
+    /// First characters of the key
+    pub prefix: String,
+    pub label: String,
+    /// Hex encoded session key the API key is bound to
+    pub session_public_key: Option<String>,
+    /// Unix timestamp in milliseconds
+    pub created_at: i64,
+    /// Unix timestamp in milliseconds of the last request authenticated with the key
+    pub last_used_at: Option<i64>,
+}
+
+/// API key, as returned once on creation
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+/// Identity authenticated by an API key
+#[derive(Debug)]
+pub struct ApiKeyIdentity {
+    pub identity: String,
+    pub session_public_key: Option<Vec<u8>>,
+}
+
+pub struct ApiKeyService {
+    pool: PgPool,
+}
+
+impl ApiKeyService {
+    pub fn new(pool: PgPool) -> Self {
+        ApiKeyService { pool }
+    }
+
+    /// Active API keys of a user, most recent first
+    pub async fn list(&self, identity: &str) -> Result<Vec<ApiKey>, AppError> {
+        let rows = sqlx::query(
+            "SELECT key_id, prefix, label, session_public_key,
+                (EXTRACT(EPOCH FROM created_at) * 1000)::bigint AS created_at,
+                (EXTRACT(EPOCH FROM last_used_at) * 1000)::bigint AS last_used_at
+            FROM api_keys
+            WHERE identity = $1 AND revoked_at IS NULL
+            ORDER BY key_id DESC",
+        )
+        .bind(identity)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(api_key_from_row).collect())
+    }
+
+    /// Creates a key, optionally bound to a session key of the user
+    pub async fn create(
+        &self,
+        identity: &str,
+        label: &str,
+        session_public_key: Option<&[u8]>,
+    ) -> Result<CreatedApiKey, AppError> {
+        if label.is_empty() || label.chars().count() > MAX_LABEL_LEN {
+            return Err(AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("Label must be between 1 and {MAX_LABEL_LEN} characters long"),
+            ));
+        }
+
+        let mut secret = [0u8; 32];
+        rand::rng().fill_bytes(&mut secret);
+        let key = format!("{KEY_PREFIX}{}", hex::encode(secret));
+
+        // The limit is checked in the insert, so that concurrent requests cannot exceed it
+        let row = sqlx::query(
+            "INSERT INTO api_keys (identity, key_hash, prefix, label, session_public_key)
+            SELECT $1, $2, $3, $4, $5
+            WHERE (SELECT COUNT(*) FROM api_keys WHERE identity = $1 AND revoked_at IS NULL) < $6
+            RETURNING key_id, prefix, label, session_public_key,
+                (EXTRACT(EPOCH FROM created_at) * 1000)::bigint AS created_at,
+                (EXTRACT(EPOCH FROM last_used_at) * 1000)::bigint AS last_used_at",
+        )
+        .bind(identity)
+        .bind(hash_key(&key))
+        .bind(&key[..KEY_PREFIX.len() + 8])
+        .bind(label)
+        .bind(session_public_key)
+        .bind(MAX_API_KEYS)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let api_key = row.as_ref().map(api_key_from_row).ok_or_else(|| {
+            AppError(
+                StatusCode::BAD_REQUEST,
+                anyhow::anyhow!("At most {MAX_API_KEYS} API keys can be active"),
+            )
+        })?;
+        Ok(CreatedApiKey { api_key, key })
+    }
+
+    /// Revokes a key, returning whether it was active
+    pub async fn revoke(&self, identity: &str, key_id: i64) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "UPDATE api_keys SET revoked_at = now()
+            WHERE identity = $1 AND key_id = $2 AND revoked_at IS NULL",
+        )
+        .bind(identity)
+        .bind(key_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Identity of an active key
+    pub async fn authenticate(&self, key: &str) -> Result<ApiKeyIdentity, AppError> {
+        let row = sqlx::query(
+            "UPDATE api_keys SET last_used_at = now()
+            WHERE key_hash = $1 AND revoked_at IS NULL
+            RETURNING identity, session_public_key",
+        )
+        .bind(hash_key(key))
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            AppError(
+                StatusCode::UNAUTHORIZED,
+                anyhow::anyhow!("Unknown or revoked API key"),
+            )
+        })?;
+
+        Ok(ApiKeyIdentity {
+            identity: row.get("identity"),
+            session_public_key: row.get("session_public_key"),
+        })
+    }
+}
+
+fn hash_key(key: &str) -> Vec<u8> {
+    Sha3_256::digest(key.as_bytes()).to_vec()
+}
+
+fn api_key_from_row(row: &sqlx::postgres::PgRow) -> ApiKey {
+    ApiKey {
+        key_id: row.get("key_id"),
+        prefix: row.get("prefix"),
+        label: row.get("label"),
+        session_public_key: row
+            .get::<Option<Vec<u8>>, _>("session_public_key")
+            .map(hex::encode),
+        created_at: row.get("created_at"),
+        last_used_at: row.get("last_used_at"),
+    }
+}
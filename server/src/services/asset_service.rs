@@ -1,6 +1,10 @@
 use std::collections::HashMap;
 
 use client_sdk::contract_indexer::AppError;
+use orderbook::model::{
+    CircuitBreaker, CircuitBreakerState, Collateral, CollateralConfig, MarketStatus, Pair,
+    PerpConfig, PerpMarket, WithdrawDelay, WithdrawLimit,
+};
 use sdk::{ContractName, TxHash};
 use sqlx::{PgPool, Row};
 use tracing::info;
@@ -14,14 +18,6 @@ pub struct Asset {
     pub step: i64,
 }
 
-#[derive(Debug, Clone, sqlx::Type)]
-#[sqlx(type_name = "market_status", rename_all = "lowercase")]
-pub enum MarketStatus {
-    Active,
-    Halted,
-    Closed,
-}
-
 #[derive(Debug)]
 pub struct Instrument {
     pub instrument_id: i64,
@@ -126,6 +122,10 @@ impl AssetService {
         self.instrument_map.get(symbol)
     }
 
+    pub fn instruments(&self) -> impl Iterator<Item = &Instrument> {
+        self.instrument_map.values()
+    }
+
     pub async fn get_all_instruments(
         &self,
         commit_id: i64,
@@ -154,6 +154,242 @@ impl AssetService {
             .collect())
     }
 
+    /// Withdraw limits of the assets at a given commit_id. Assets without a limit are omitted.
+    pub async fn get_withdraw_limits(
+        &self,
+        commit_id: i64,
+    ) -> Result<HashMap<String, WithdrawLimit>, AppError> {
+        let rows = sqlx::query(
+            "
+            SELECT DISTINCT ON (symbol) symbol, max_amount, window_blocks
+            FROM asset_withdraw_limits
+            WHERE commit_id <= $1
+            ORDER BY symbol, commit_id DESC
+            ",
+        )
+        .bind(commit_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let max_amount = row.get::<Option<i64>, _>("max_amount")?;
+                let window_blocks = row.get::<Option<i64>, _>("window_blocks")?;
+                Some((
+                    row.get("symbol"),
+                    WithdrawLimit {
+                        max_amount: max_amount as u64,
+                        window_blocks: window_blocks as u64,
+                    },
+                ))
+            })
+            .collect())
+    }
+
+    /// Withdraw delays of the assets at a given commit_id. Assets without a delay are omitted.
+    pub async fn get_withdraw_delays(
+        &self,
+        commit_id: i64,
+    ) -> Result<HashMap<String, WithdrawDelay>, AppError> {
+        let rows = sqlx::query(
+            "
+            SELECT DISTINCT ON (symbol) symbol, threshold, delay_blocks, requires_approval
+            FROM asset_withdraw_delays
+            WHERE commit_id <= $1
+            ORDER BY symbol, commit_id DESC
+            ",
+        )
+        .bind(commit_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let threshold = row.get::<Option<i64>, _>("threshold")?;
+                let delay_blocks = row.get::<Option<i64>, _>("delay_blocks")?;
+                let requires_approval = row.get::<Option<bool>, _>("requires_approval")?;
+                Some((
+                    row.get("symbol"),
+                    WithdrawDelay {
+                        threshold: threshold as u64,
+                        delay_blocks: delay_blocks as u64,
+                        requires_approval,
+                    },
+                ))
+            })
+            .collect())
+    }
+
+    /// Status of the pairs at a given commit_id. Pairs that never changed status are omitted.
+    pub async fn get_pair_statuses(
+        &self,
+        commit_id: i64,
+    ) -> Result<HashMap<Pair, MarketStatus>, AppError> {
+        let rows = sqlx::query(
+            "
+            SELECT DISTINCT ON (symbol) symbol, status
+            FROM instrument_status_updates
+            WHERE commit_id <= $1
+            ORDER BY symbol, commit_id DESC
+            ",
+        )
+        .bind(commit_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let symbol: String = row.get("symbol");
+                let (base, quote) = symbol.split_once('/')?;
+                Some(((base.to_string(), quote.to_string()), row.get("status")))
+            })
+            .collect())
+    }
+
+    /// Circuit breakers of the pairs at a given commit_id, with the prices they track.
+    /// Pairs without a circuit breaker are omitted.
+    pub async fn get_circuit_breakers(
+        &self,
+        commit_id: i64,
+    ) -> Result<HashMap<Pair, CircuitBreakerState>, AppError> {
+        let rows = sqlx::query(
+            "
+            SELECT DISTINCT ON (symbol) symbol, max_move_bps, window_blocks, halt_blocks,
+                reference_price, window_start, halted_until
+            FROM circuit_breaker_states
+            WHERE commit_id <= $1
+            ORDER BY symbol, commit_id DESC
+            ",
+        )
+        .bind(commit_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let symbol: String = row.get("symbol");
+                let (base, quote) = symbol.split_once('/')?;
+                let config = CircuitBreaker {
+                    max_move_bps: row.get::<Option<i64>, _>("max_move_bps")? as u64,
+                    window_blocks: row.get::<Option<i64>, _>("window_blocks")? as u64,
+                    halt_blocks: row.get::<Option<i64>, _>("halt_blocks")? as u64,
+                };
+                Some((
+                    (base.to_string(), quote.to_string()),
+                    CircuitBreakerState {
+                        config,
+                        reference_price: row
+                            .get::<Option<i64>, _>("reference_price")
+                            .map(|price| price as u64),
+                        window_start: row.get::<i64, _>("window_start") as u64,
+                        halted_until: row.get::<i64, _>("halted_until") as u64,
+                    },
+                ))
+            })
+            .collect())
+    }
+
+    /// Whether the bridge is paused at a given commit_id
+    pub async fn get_bridge_paused(&self, commit_id: i64) -> Result<bool, AppError> {
+        let paused: Option<bool> = sqlx::query_scalar(
+            "
+            SELECT paused
+            FROM bridge_pauses
+            WHERE commit_id <= $1
+            ORDER BY commit_id DESC
+            LIMIT 1
+            ",
+        )
+        .bind(commit_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(paused.unwrap_or(false))
+    }
+
+    /// Collaterals of the assets at a given commit_id, with the index price they are valued at.
+    /// Assets that are not a collateral are omitted.
+    pub async fn get_collaterals(
+        &self,
+        commit_id: i64,
+    ) -> Result<HashMap<String, Collateral>, AppError> {
+        let rows = sqlx::query(
+            "
+            SELECT DISTINCT ON (symbol) symbol, quote, weight_bps, price
+            FROM asset_collaterals
+            WHERE commit_id <= $1
+            ORDER BY symbol, commit_id DESC
+            ",
+        )
+        .bind(commit_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let quote = row.get::<Option<String>, _>("quote")?;
+                let weight_bps = row.get::<Option<i64>, _>("weight_bps")?;
+                Some((
+                    row.get("symbol"),
+                    Collateral {
+                        config: CollateralConfig {
+                            quote,
+                            weight_bps: weight_bps as u64,
+                        },
+                        price: row.get::<i64, _>("price") as u64,
+                    },
+                ))
+            })
+            .collect())
+    }
+
+    /// Perp markets of the pairs at a given commit_id, with their funding index and index price.
+    /// Spot pairs are omitted.
+    pub async fn get_perp_markets(
+        &self,
+        commit_id: i64,
+    ) -> Result<HashMap<Pair, PerpMarket>, AppError> {
+        let rows = sqlx::query(
+            "
+            SELECT DISTINCT ON (symbol) symbol, initial_margin_bps, maintenance_margin_bps,
+                liquidation_penalty_bps, funding_index, index_price
+            FROM perp_markets
+            WHERE commit_id <= $1
+            ORDER BY symbol, commit_id DESC
+            ",
+        )
+        .bind(commit_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|row| {
+                let symbol: String = row.get("symbol");
+                let (base, quote) = symbol.split_once('/')?;
+                Some((
+                    (base.to_string(), quote.to_string()),
+                    PerpMarket {
+                        config: PerpConfig {
+                            initial_margin_bps: row.get::<i64, _>("initial_margin_bps") as u64,
+                            maintenance_margin_bps: row.get::<i64, _>("maintenance_margin_bps")
+                                as u64,
+                            liquidation_penalty_bps: row.get::<i64, _>("liquidation_penalty_bps")
+                                as u64,
+                        },
+                        funding_index: row.get("funding_index"),
+                        index_price: row.get::<i64, _>("index_price") as u64,
+                    },
+                ))
+            })
+            .collect())
+    }
+
     pub async fn get_all_assets(&self) -> &HashMap<String, Asset> {
         &self.asset_map
     }
@@ -165,7 +401,7 @@ impl AssetService {
             .bind(instrument.qty_step)
             .bind(instrument.base_asset_id)
             .bind(instrument.quote_asset_id)
-            .bind(instrument.status.clone())
+            .bind(instrument.status)
             .execute(&self.pool)
             .await?;
 
@@ -211,14 +447,54 @@ impl AssetService {
         Ok(())
     }
 
-    /// Get commit_id from a given tx_hash
-    pub async fn get_commit_id_from_tx_hash(&self, tx_hash: &TxHash) -> Option<i64> {
-        let row = sqlx::query("SELECT commit_id FROM commits WHERE tx_hash = $1")
-            .bind(&tx_hash.0)
+    /// Registers the synthetic base asset of a perp market. It has no contract: its contract
+    /// name, the lowercase symbol, only identifies it. An asset already registered is returned.
+    pub async fn add_synthetic_asset(
+        &mut self,
+        symbol: &str,
+        scale: i16,
+    ) -> Result<&Asset, AppError> {
+        if !self.asset_map.contains_key(symbol) {
+            let contract_name = symbol.to_lowercase();
+            let asset_id: i64 = sqlx::query_scalar(
+                "INSERT INTO assets (contract_name, symbol, scale, step) VALUES ($1, $2, $3, 1) RETURNING asset_id",
+            )
+            .bind(&contract_name)
+            .bind(symbol)
+            .bind(scale)
             .fetch_one(&self.pool)
-            .await
-            .ok()?;
-        Some(row.get::<i64, _>("commit_id"))
+            .await?;
+
+            self.asset_map.insert(
+                symbol.to_string(),
+                Asset {
+                    asset_id,
+                    contract_name,
+                    symbol: symbol.to_string(),
+                    scale,
+                    step: 1,
+                },
+            );
+        }
+        Ok(&self.asset_map[symbol])
+    }
+
+    /// Get commit_id from a given tx_hash.
+    /// For a blob tx batching several actions, this is the commit_id of its last action.
+    pub async fn get_commit_id_from_tx_hash(&self, tx_hash: &TxHash) -> Option<i64> {
+        let row = sqlx::query(
+            "
+            SELECT COALESCE(
+                (SELECT MAX(commit_id) FROM blob_tx_outbox WHERE sent_tx_hash = $1),
+                (SELECT commit_id FROM commits WHERE tx_hash = $1)
+            ) AS commit_id
+            ",
+        )
+        .bind(&tx_hash.0)
+        .fetch_one(&self.pool)
+        .await
+        .ok()?;
+        row.get::<Option<i64>, _>("commit_id")
     }
 
     /// Get last tx_hash in commits table
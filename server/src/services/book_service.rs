@@ -1,8 +1,8 @@
-use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap};
 
 use client_sdk::contract_indexer::AppError;
 use orderbook::model::{Order, OrderSide, UserInfo};
-use orderbook::order_manager::OrderManager;
+use orderbook::order_manager::{OrderManager, PriceLevel};
 use orderbook::zk::smt::GetKey;
 use serde::Serialize;
 use sqlx::{PgPool, Row};
@@ -125,7 +125,7 @@ impl BookService {
             })
             .collect();
 
-        let buy_orders: HashMap<(String, String), BTreeMap<u64, VecDeque<String>>> = rows
+        let buy_orders: HashMap<(String, String), BTreeMap<u64, PriceLevel>> = rows
             .iter()
             .rev()
             .filter(|row| row.get::<OrderSide, _>("side") == OrderSide::Bid)
@@ -136,16 +136,16 @@ impl BookService {
                             .and_modify(|v| {
                                 v.push_front(row.get("order_id"));
                             })
-                            .or_insert(VecDeque::from([row.get("order_id")]));
+                            .or_insert(PriceLevel::from([row.get("order_id")]));
                     })
                     .or_insert(BTreeMap::from([(
                         row.get::<i64, _>("price") as u64,
-                        VecDeque::from([row.get("order_id")]),
+                        PriceLevel::from([row.get("order_id")]),
                     )]));
                 acc
             });
 
-        let sell_orders: HashMap<(String, String), BTreeMap<u64, VecDeque<String>>> = rows
+        let sell_orders: HashMap<(String, String), BTreeMap<u64, PriceLevel>> = rows
             .iter()
             .filter(|row| row.get::<OrderSide, _>("side") == OrderSide::Ask)
             .fold(HashMap::new(), |mut acc, row| {
@@ -155,11 +155,11 @@ impl BookService {
                             .and_modify(|v| {
                                 v.push_back(row.get("order_id"));
                             })
-                            .or_insert(VecDeque::from([row.get("order_id")]));
+                            .or_insert(PriceLevel::from([row.get("order_id")]));
                     })
                     .or_insert(BTreeMap::from([(
                         row.get::<i64, _>("price") as u64,
-                        VecDeque::from([row.get("order_id")]),
+                        PriceLevel::from([row.get("order_id")]),
                     )]));
                 acc
             });
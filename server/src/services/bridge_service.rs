@@ -2,7 +2,7 @@ use alloy::primitives::{Address, TxHash, U256};
 use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, Row};
-use std::convert::TryInto;
+use std::{convert::TryInto, str::FromStr};
 
 use crate::{bridge::eth::EthListener, conf};
 
@@ -12,7 +12,8 @@ pub struct EthTransaction {
     pub tx_hash: TxHash,
     pub block_number: u64,
     pub from: Address,
-    pub to: Address, // Vault address
+    pub to: Address,    // Vault address
+    pub token: Address, // ERC20 contract
     pub amount: U256,
     pub timestamp: u64,
     pub status: TxStatus,
@@ -49,11 +50,18 @@ impl TryFrom<&str> for TxStatus {
 #[derive(Clone)]
 pub struct BridgeService {
     pool: PgPool,
+    /// Token of the pending transactions stored before the token was recorded
+    collateral_token: Address,
 }
 
 impl BridgeService {
     pub async fn new(pool: PgPool, bridge_conf: &conf::BridgeConfig) -> Result<Self> {
-        let bridge_service = BridgeService { pool };
+        let collateral_token = Address::from_str(&bridge_conf.eth_contract_address)
+            .context("parsing Ethereum contract address")?;
+        let bridge_service = BridgeService {
+            pool,
+            collateral_token,
+        };
         let eth_contract_deploy_block = match bridge_conf.eth_contract_deploy_block {
             Some(block) => block,
             None => {
@@ -259,8 +267,9 @@ impl BridgeService {
 
         sqlx::query(
             "INSERT INTO bridge_eth_pending_txs
-                (tx_hash, block_number, from_address, to_address, amount, timestamp, status)
-             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                (tx_hash, block_number, from_address, to_address, amount, timestamp, status,
+                 token_address)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
         )
         .bind(tx_hash_to_vec(&tx.tx_hash))
         .bind(i64::try_from(tx.block_number).context("block number does not fit in i64")?)
@@ -269,6 +278,7 @@ impl BridgeService {
         .bind(u256_to_vec(&tx.amount))
         .bind(i64::try_from(tx.timestamp).context("timestamp does not fit in i64")?)
         .bind(tx.status.as_str())
+        .bind(address_to_vec(&tx.token))
         .execute(&self.pool)
         .await
         .context("inserting pending Ethereum transaction")?;
@@ -282,7 +292,7 @@ impl BridgeService {
     ) -> Result<Vec<EthTransaction>> {
         let rows = sqlx::query(
             "SELECT tx_hash, block_number, from_address, to_address,
-                    amount, timestamp, status
+                    amount, timestamp, status, token_address
              FROM bridge_eth_pending_txs
              WHERE from_address = $1",
         )
@@ -293,29 +303,60 @@ impl BridgeService {
 
         let mut transactions = Vec::with_capacity(rows.len());
         for row in rows {
-            let tx_hash_bytes: Vec<u8> = row.get("tx_hash");
-            let block_number: i64 = row.get("block_number");
-            let from_bytes: Vec<u8> = row.get("from_address");
-            let to_bytes: Vec<u8> = row.get("to_address");
-            let amount_bytes: Vec<u8> = row.get("amount");
-            let timestamp: i64 = row.get("timestamp");
-            let status: String = row.get("status");
-
-            transactions.push(EthTransaction {
-                tx_hash: bytes_to_tx_hash(&tx_hash_bytes)?,
-                block_number: u64::try_from(block_number)
-                    .context("stored block number is negative")?,
-                from: bytes_to_address(&from_bytes)?,
-                to: bytes_to_address(&to_bytes)?,
-                amount: bytes_to_u256(&amount_bytes)?,
-                timestamp: u64::try_from(timestamp).context("stored timestamp is negative")?,
-                status: TxStatus::try_from(status.as_str())?,
-            });
+            transactions.push(self.eth_transaction_from_row(&row)?);
         }
 
         Ok(transactions)
     }
 
+    /// Pending transactions of the addresses bound to a Hyli identity: the deposits held while
+    /// the bridge was paused
+    pub async fn claimed_pending_eth_transactions(&self) -> Result<Vec<(String, EthTransaction)>> {
+        let rows = sqlx::query(
+            "SELECT tx.tx_hash, tx.block_number, tx.from_address, tx.to_address,
+                    tx.amount, tx.timestamp, tx.status, tx.token_address, b.user_identity
+             FROM bridge_eth_pending_txs tx
+             JOIN bridge_eth_address_bindings b ON b.eth_address = tx.from_address
+             ORDER BY tx.block_number",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .context("fetching claimed pending Ethereum transactions")?;
+
+        let mut transactions = Vec::with_capacity(rows.len());
+        for row in rows {
+            let user_identity: String = row.get("user_identity");
+            transactions.push((user_identity, self.eth_transaction_from_row(&row)?));
+        }
+
+        Ok(transactions)
+    }
+
+    fn eth_transaction_from_row(&self, row: &sqlx::postgres::PgRow) -> Result<EthTransaction> {
+        let tx_hash_bytes: Vec<u8> = row.get("tx_hash");
+        let block_number: i64 = row.get("block_number");
+        let from_bytes: Vec<u8> = row.get("from_address");
+        let to_bytes: Vec<u8> = row.get("to_address");
+        let amount_bytes: Vec<u8> = row.get("amount");
+        let timestamp: i64 = row.get("timestamp");
+        let status: String = row.get("status");
+        let token_bytes: Option<Vec<u8>> = row.get("token_address");
+
+        Ok(EthTransaction {
+            tx_hash: bytes_to_tx_hash(&tx_hash_bytes)?,
+            block_number: u64::try_from(block_number).context("stored block number is negative")?,
+            from: bytes_to_address(&from_bytes)?,
+            to: bytes_to_address(&to_bytes)?,
+            amount: bytes_to_u256(&amount_bytes)?,
+            timestamp: u64::try_from(timestamp).context("stored timestamp is negative")?,
+            status: TxStatus::try_from(status.as_str())?,
+            token: match token_bytes {
+                Some(bytes) => bytes_to_address(&bytes)?,
+                None => self.collateral_token,
+            },
+        })
+    }
+
     pub async fn pending_eth_tx_count(&self) -> Result<usize> {
         let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM bridge_eth_pending_txs")
             .fetch_one(&self.pool)
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use client_sdk::contract_indexer::AppError;
+use serde::Serialize;
+use sqlx::Row;
+use utoipa::ToSchema;
+
+use crate::read_replica::ReadPool;
+
+/// Maximum number of checkpoints returned at once
+pub const MAX_CHECKPOINTS: i64 = 100;
+
+/// Signed checkpoint, as served by the API. The signature is over the SHA3-256 digest of
+/// `{orderbook}:checkpoint:{commit_id}:{state_commitment}:{da_height}:{timestamp_ms}`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StateCheckpointAPI {
+    pub orderbook: String,
+    pub commit_id: i64,
+    /// Hex encoded
+    pub state_commitment: String,
+    pub da_height: i64,
+    pub timestamp_ms: i64,
+    /// Hex encoded SEC1 compressed secp256k1 public key
+    pub public_key: String,
+    /// Hex encoded `r ‖ s`
+    pub signature: String,
+    pub da_tx_hash: Option<String>,
+}
+
+pub struct CheckpointService {
+    pool: Arc<ReadPool>,
+    orderbook: String,
+}
+
+impl CheckpointService {
+    pub fn new(pool: Arc<ReadPool>, orderbook: String) -> Self {
+        CheckpointService { pool, orderbook }
+    }
+
+    /// Latest checkpoints, or the ones up to `before_commit_id`, most recent first
+    pub async fn list(
+        &self,
+        before_commit_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<StateCheckpointAPI>, AppError> {
+        let rows = sqlx::query(
+            "SELECT commit_id, state_commitment, da_height, timestamp_ms, public_key, signature, da_tx_hash
+            FROM state_checkpoints
+            WHERE $1::bigint IS NULL OR commit_id < $1
+            ORDER BY commit_id DESC
+            LIMIT $2",
+        )
+        .bind(before_commit_id)
+        .bind(limit.clamp(1, MAX_CHECKPOINTS))
+        .fetch_all(self.pool.pool())
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| StateCheckpointAPI {
+                orderbook: self.orderbook.clone(),
+                commit_id: row.get("commit_id"),
+                state_commitment: hex::encode(row.get::<Vec<u8>, _>("state_commitment")),
+                da_height: row.get("da_height"),
+                timestamp_ms: row.get("timestamp_ms"),
+                public_key: hex::encode(row.get::<Vec<u8>, _>("public_key")),
+                signature: hex::encode(row.get::<Vec<u8>, _>("signature")),
+                da_tx_hash: row.get("da_tx_hash"),
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,57 @@
+use client_sdk::contract_indexer::AppError;
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use utoipa::ToSchema;
+
+/// Index price of a pair, as pulled from the oracle sources
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IndexPrice {
+    pub symbol: String,
+    pub price: i64,
+    /// Number of sources the price is the median of
+    pub sources: i32,
+    /// Unix timestamp in milliseconds
+    pub updated_at: i64,
+}
+
+pub struct IndexPriceService {
+    pool: PgPool,
+}
+
+impl IndexPriceService {
+    pub fn new(pool: PgPool) -> Self {
+        IndexPriceService { pool }
+    }
+
+    pub async fn record(&self, symbol: &str, price: u64, sources: usize) -> Result<(), AppError> {
+        sqlx::query("INSERT INTO index_prices (symbol, price, sources) VALUES ($1, $2, $3)")
+            .bind(symbol)
+            .bind(price as i64)
+            .bind(sources as i32)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Latest index price of a pair, if any was pulled
+    pub async fn latest(&self, symbol: &str) -> Result<Option<IndexPrice>, AppError> {
+        let row = sqlx::query(
+            "SELECT symbol, price, sources,
+                (EXTRACT(EPOCH FROM created_at) * 1000)::bigint AS updated_at
+            FROM index_prices
+            WHERE symbol = $1
+            ORDER BY id DESC
+            LIMIT 1",
+        )
+        .bind(symbol)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| IndexPrice {
+            symbol: row.get("symbol"),
+            price: row.get("price"),
+            sources: row.get("sources"),
+            updated_at: row.get("updated_at"),
+        }))
+    }
+}
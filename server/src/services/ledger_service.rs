@@ -0,0 +1,356 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use client_sdk::contract_indexer::AppError;
+use serde::Serialize;
+use sqlx::{postgres::PgRow, Row};
+use utoipa::ToSchema;
+
+use crate::read_replica::ReadPool;
+
+/// Maximum number of commits covered by a page of a ledger
+pub const MAX_LEDGER_COMMITS: i64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerEntryKind {
+    Deposit,
+    Withdrawal,
+    /// Funds locked by a resting order, or released once it is cancelled
+    Reserve,
+    /// Amount received on a trade, less the amount paid when taking
+    Fill,
+    /// Trading fees charged on the fills of a commit
+    Fee,
+    Transfer,
+    /// Funding paid, or received, by a perp position
+    Funding,
+    DustSweep,
+}
+
+/// Change of the available balance of an asset of a user
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LedgerEntry {
+    pub commit_id: i64,
+    pub kind: LedgerEntryKind,
+    /// Signed change of the balance
+    pub amount: i64,
+    /// Available balance once the entry applied
+    pub balance: i64,
+    /// Trade of a fill
+    pub trade_id: Option<i64>,
+    /// Order of a fill or a reserve
+    pub order_id: Option<String>,
+    /// Unix timestamp in milliseconds of the commit
+    pub time: i64,
+}
+
+/// Balance changes of a commit, told apart from the trades, orders, transfers, funding and dust
+/// sweeps recorded alongside it. The rest of the change is the fee of the fills, or else a
+/// deposit or a withdrawal.
+#[derive(Default)]
+struct CommitMovements {
+    entries: Vec<(LedgerEntryKind, i64, Option<i64>, Option<String>)>,
+    has_fills: bool,
+    has_dust_sweep: bool,
+}
+
+pub struct LedgerService {
+    pool: Arc<ReadPool>,
+}
+
+impl LedgerService {
+    pub fn new(pool: Arc<ReadPool>) -> Self {
+        LedgerService { pool }
+    }
+
+    /// Ledger of an asset of a user from `balance_events`, over the commits after
+    /// `after_commit_id`, oldest first
+    pub async fn ledger(
+        &self,
+        identity: &str,
+        asset_id: i64,
+        symbol: &str,
+        after_commit_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<LedgerEntry>, AppError> {
+        let commits = sqlx::query(
+            "
+            WITH events AS (
+                SELECT commit_id, event_id, total, event_time,
+                    LAG(total, 1, 0::bigint) OVER (ORDER BY commit_id, event_id) AS previous
+                FROM balance_events
+                WHERE identity = $1 AND asset_id = $2
+            )
+            SELECT commit_id,
+                (array_agg(previous ORDER BY event_id))[1] AS before,
+                (array_agg(total ORDER BY event_id DESC))[1] AS after,
+                (EXTRACT(EPOCH FROM max(event_time)) * 1000)::bigint AS time
+            FROM events
+            WHERE $3::bigint IS NULL OR commit_id > $3
+            GROUP BY commit_id
+            ORDER BY commit_id
+            LIMIT $4
+            ",
+        )
+        .bind(identity)
+        .bind(asset_id)
+        .bind(after_commit_id)
+        .bind(limit.clamp(1, MAX_LEDGER_COMMITS))
+        .fetch_all(self.pool.pool())
+        .await?;
+
+        let (Some(first), Some(last)) = (commits.first(), commits.last()) else {
+            return Ok(Vec::new());
+        };
+        let range = (
+            first.get::<i64, _>("commit_id"),
+            last.get::<i64, _>("commit_id"),
+        );
+        let mut movements = self.movements(identity, asset_id, symbol, range).await?;
+
+        let mut ledger = Vec::new();
+        for commit in &commits {
+            let commit_id: i64 = commit.get("commit_id");
+            let time: i64 = commit.get("time");
+            let mut balance: i64 = commit.get("before");
+            let after: i64 = commit.get("after");
+            let commit_movements = movements.remove(&commit_id).unwrap_or_default();
+
+            let mut push = |kind, amount, trade_id, order_id| {
+                balance += amount;
+                ledger.push(LedgerEntry {
+                    commit_id,
+                    kind,
+                    amount,
+                    balance,
+                    trade_id,
+                    order_id,
+                    time,
+                });
+            };
+            for (kind, amount, trade_id, order_id) in commit_movements.entries {
+                push(kind, amount, trade_id, order_id);
+            }
+            let rest = after - balance;
+            if rest != 0 {
+                let kind = if commit_movements.has_fills {
+                    LedgerEntryKind::Fee
+                } else if commit_movements.has_dust_sweep {
+                    LedgerEntryKind::DustSweep
+                } else if rest > 0 {
+                    LedgerEntryKind::Deposit
+                } else {
+                    LedgerEntryKind::Withdrawal
+                };
+                push(kind, rest, None, None);
+            }
+        }
+        Ok(ledger)
+    }
+
+    /// Known balance changes of the asset in the commits of `range`
+    async fn movements(
+        &self,
+        identity: &str,
+        asset_id: i64,
+        symbol: &str,
+        (from, to): (i64, i64),
+    ) -> Result<BTreeMap<i64, CommitMovements>, AppError> {
+        let mut movements: BTreeMap<i64, CommitMovements> = BTreeMap::new();
+
+        let transfers = sqlx::query(
+            "SELECT commit_id,
+                CASE WHEN to_identity = $1 THEN amount ELSE 0 END
+                    - CASE WHEN from_identity = $1 THEN amount ELSE 0 END AS amount
+            FROM transfers
+            WHERE (from_identity = $1 OR to_identity = $1) AND symbol = $2
+                AND commit_id BETWEEN $3 AND $4
+            ORDER BY transfer_id",
+        )
+        .bind(identity)
+        .bind(symbol)
+        .bind(from)
+        .bind(to)
+        .fetch_all(self.pool.pool())
+        .await?;
+        for row in transfers {
+            let entry = movements.entry(row.get("commit_id")).or_default();
+            entry
+                .entries
+                .push((LedgerEntryKind::Transfer, row.get("amount"), None, None));
+        }
+
+        // Funding is settled in the quote of the perp market
+        let funding = sqlx::query(
+            "SELECT commit_id, -amount AS amount
+            FROM funding_payments
+            WHERE identity = $1 AND split_part(symbol, '/', 2) = $2
+                AND commit_id BETWEEN $3 AND $4
+            ORDER BY commit_id, symbol",
+        )
+        .bind(identity)
+        .bind(symbol)
+        .bind(from)
+        .bind(to)
+        .fetch_all(self.pool.pool())
+        .await?;
+        for row in funding {
+            let entry = movements.entry(row.get("commit_id")).or_default();
+            entry
+                .entries
+                .push((LedgerEntryKind::Funding, row.get("amount"), None, None));
+        }
+
+        let sweeps = sqlx::query(
+            "SELECT commit_id, CASE WHEN quote = $2 THEN proceeds ELSE 0 END AS proceeds
+            FROM dust_sweeps
+            WHERE identity = $1 AND (quote = $2 OR $2 = ANY(symbols))
+                AND commit_id BETWEEN $3 AND $4
+            ORDER BY sweep_id",
+        )
+        .bind(identity)
+        .bind(symbol)
+        .bind(from)
+        .bind(to)
+        .fetch_all(self.pool.pool())
+        .await?;
+        for row in sweeps {
+            let entry = movements.entry(row.get("commit_id")).or_default();
+            entry.has_dust_sweep = true;
+            let proceeds: i64 = row.get("proceeds");
+            if proceeds != 0 {
+                entry
+                    .entries
+                    .push((LedgerEntryKind::DustSweep, proceeds, None, None));
+            }
+        }
+
+        // Resting orders lock what they would pay when filled, released when cancelled
+        let reserves = sqlx::query(
+            "SELECT o.commit_id, o.order_id, o.side::text AS side, o.price, o.qty,
+                i.base_asset_id, base.scale, false AS released
+            FROM orders o
+            JOIN instruments i ON i.instrument_id = o.instrument_id
+            JOIN assets base ON base.asset_id = i.base_asset_id
+            WHERE o.identity = $1 AND $2 IN (i.base_asset_id, i.quote_asset_id)
+                AND o.commit_id BETWEEN $3 AND $4
+            UNION ALL
+            SELECT e.commit_id, e.order_id, e.side::text AS side, e.price, e.qty - e.qty_filled,
+                i.base_asset_id, base.scale, true AS released
+            FROM order_events e
+            JOIN instruments i ON i.instrument_id = e.instrument_id
+            JOIN assets base ON base.asset_id = i.base_asset_id
+            WHERE e.identity = $1 AND e.status = 'cancelled'
+                AND $2 IN (i.base_asset_id, i.quote_asset_id)
+                AND e.commit_id BETWEEN $3 AND $4
+            ORDER BY commit_id",
+        )
+        .bind(identity)
+        .bind(asset_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(self.pool.pool())
+        .await?;
+        for row in reserves {
+            let leg = TradeLeg::of(&row, asset_id);
+            let locked = match row.get::<&str, _>("side") {
+                "bid" => leg.quote(),
+                _ => leg.base(),
+            };
+            let amount = if row.get("released") { locked } else { -locked };
+            if amount != 0 {
+                let entry = movements.entry(row.get("commit_id")).or_default();
+                entry.entries.push((
+                    LedgerEntryKind::Reserve,
+                    amount,
+                    None,
+                    Some(row.get("order_id")),
+                ));
+            }
+        }
+
+        let trades = sqlx::query(
+            "SELECT t.commit_id, t.trade_id, t.side::text AS side, t.price, t.qty,
+                t.taker_identity, t.maker_identity, t.taker_order_id, t.maker_order_id,
+                i.base_asset_id, base.scale
+            FROM trade_events t
+            JOIN instruments i ON i.instrument_id = t.instrument_id
+            JOIN assets base ON base.asset_id = i.base_asset_id
+            WHERE (t.taker_identity = $1 OR t.maker_identity = $1)
+                AND $2 IN (i.base_asset_id, i.quote_asset_id)
+                AND t.commit_id BETWEEN $3 AND $4
+            ORDER BY t.commit_id, t.trade_id",
+        )
+        .bind(identity)
+        .bind(asset_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(self.pool.pool())
+        .await?;
+        for row in trades {
+            let leg = TradeLeg::of(&row, asset_id);
+            // `side` is the side of the taker. The maker paid when its order was reserved.
+            let taker_bid = row.get::<&str, _>("side") == "bid";
+            let mut amount = 0;
+            let mut order_id = None;
+            if row.get::<&str, _>("maker_identity") == identity {
+                amount += if taker_bid { leg.quote() } else { leg.base() };
+                order_id = Some(row.get("maker_order_id"));
+            }
+            if row.get::<&str, _>("taker_identity") == identity {
+                amount += if taker_bid {
+                    leg.base() - leg.quote()
+                } else {
+                    leg.quote() - leg.base()
+                };
+                order_id = Some(row.get("taker_order_id"));
+            }
+            let entry = movements.entry(row.get("commit_id")).or_default();
+            entry.has_fills = true;
+            entry.entries.push((
+                LedgerEntryKind::Fill,
+                amount,
+                Some(row.get("trade_id")),
+                order_id,
+            ));
+        }
+
+        Ok(movements)
+    }
+}
+
+/// Quantity and price of an order or a trade, valued in the asset of the ledger: the base
+/// amount is the quantity, and the quote amount its notional, when the asset is of that side
+struct TradeLeg {
+    base: Option<i64>,
+    quote: Option<i64>,
+}
+
+impl TradeLeg {
+    fn of(row: &PgRow, asset_id: i64) -> Self {
+        let qty: i64 = row.get("qty");
+        let price: Option<i64> = row.get("price");
+        let scale: i16 = row.get("scale");
+        if row.get::<i64, _>("base_asset_id") == asset_id {
+            TradeLeg {
+                base: Some(qty),
+                quote: None,
+            }
+        } else {
+            let notional =
+                qty as i128 * price.unwrap_or_default() as i128 / 10i128.pow(scale as u32);
+            TradeLeg {
+                base: None,
+                quote: Some(notional as i64),
+            }
+        }
+    }
+
+    fn base(&self) -> i64 {
+        self.base.unwrap_or_default()
+    }
+
+    fn quote(&self) -> i64 {
+        self.quote.unwrap_or_default()
+    }
+}
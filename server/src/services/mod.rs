@@ -1,4 +1,12 @@
+pub mod address_book_service;
+pub mod analytics_service;
+pub mod api_key_service;
 pub mod asset_service;
 pub mod book_service;
 pub mod bridge_service;
+pub mod checkpoint_service;
+pub mod index_price_service;
+pub mod ledger_service;
+pub mod prover_service;
+pub mod ticker_service;
 pub mod user_service;
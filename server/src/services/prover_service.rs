@@ -0,0 +1,122 @@
+use client_sdk::contract_indexer::AppError;
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use utoipa::ToSchema;
+
+/// Number of txs listed by the prover status when no tx is requested
+const RECENT_TXS: i64 = 50;
+
+/// Number of the latest settled actions the average proving time is computed over
+const PROVING_TIME_WINDOW: i64 = 100;
+
+/// How far settlement lags behind the matching engine
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProverStatus {
+    pub latest_commit_id: i64,
+    pub latest_proven_commit_id: Option<i64>,
+    pub latest_settled_commit_id: Option<i64>,
+    /// Commits applied by the matching engine that have not settled yet
+    pub settlement_lag_commits: Option<i64>,
+    /// Actions waiting for a proving worker, or for their tx to be sequenced
+    pub pending_txs: i64,
+    /// Actions being proven, or whose proof was submitted but has not settled yet
+    pub proving_txs: i64,
+    /// From the pickup by a proving worker to the proof submission
+    pub avg_proving_time_ms: Option<f64>,
+    pub txs: Vec<TxSettlementStatus>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TxSettlementStatus {
+    pub commit_id: i64,
+    pub tx_hash: String,
+    /// "pending", "proving", "settled" or "failed"
+    pub status: String,
+    pub proof_tx_hash: Option<String>,
+}
+
+pub struct ProverService {
+    pool: PgPool,
+}
+
+impl ProverService {
+    pub fn new(pool: PgPool) -> Self {
+        ProverService { pool }
+    }
+
+    /// Returns the proving progress, with the status of `tx_hash` or of the latest txs
+    pub async fn get_status(&self, tx_hash: Option<&str>) -> Result<ProverStatus, AppError> {
+        let row = sqlx::query(
+            "
+        SELECT
+            (SELECT COALESCE(MAX(commit_id), 0) FROM commits) AS latest_commit_id,
+            (SELECT MAX(commit_id) FROM (
+                SELECT commit_id FROM prover_requests WHERE proof_tx_hash IS NOT NULL
+                UNION ALL
+                SELECT commit_id FROM prover_settlements WHERE proof_tx_hash IS NOT NULL
+            ) proven) AS latest_proven_commit_id,
+            (SELECT MAX(commit_id) FROM prover_settlements WHERE status = 'settled') AS latest_settled_commit_id,
+            (SELECT COUNT(*) FROM prover_requests
+                WHERE proving_started_at IS NULL AND proof_tx_hash IS NULL) AS pending_txs,
+            (SELECT COUNT(*) FROM prover_requests
+                WHERE proving_started_at IS NOT NULL OR proof_tx_hash IS NOT NULL) AS proving_txs,
+            (SELECT (AVG(EXTRACT(EPOCH FROM proved_at - proving_started_at)) * 1000)::float8 FROM (
+                SELECT proving_started_at, proved_at
+                FROM prover_settlements
+                WHERE proving_started_at IS NOT NULL AND proved_at IS NOT NULL
+                ORDER BY commit_id DESC
+                LIMIT $1
+            ) recent) AS avg_proving_time_ms
+        ",
+        )
+        .bind(PROVING_TIME_WINDOW)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let latest_commit_id: i64 = row.get("latest_commit_id");
+        let latest_settled_commit_id: Option<i64> = row.get("latest_settled_commit_id");
+
+        let txs = sqlx::query(
+            "
+        SELECT commit_id, tx_hash, status, proof_tx_hash FROM (
+            SELECT
+                commit_id,
+                tx_hash,
+                CASE WHEN proving_started_at IS NULL AND proof_tx_hash IS NULL
+                    THEN 'pending' ELSE 'proving' END AS status,
+                proof_tx_hash
+            FROM prover_requests
+            UNION ALL
+            SELECT commit_id, tx_hash, status, proof_tx_hash FROM prover_settlements
+        ) txs
+        WHERE $1::text IS NULL OR tx_hash = $1
+        ORDER BY commit_id DESC
+        LIMIT $2
+        ",
+        )
+        .bind(tx_hash)
+        .bind(RECENT_TXS)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| TxSettlementStatus {
+            commit_id: row.get("commit_id"),
+            tx_hash: row.get("tx_hash"),
+            status: row.get("status"),
+            proof_tx_hash: row.get("proof_tx_hash"),
+        })
+        .collect();
+
+        Ok(ProverStatus {
+            latest_commit_id,
+            latest_proven_commit_id: row.get("latest_proven_commit_id"),
+            latest_settled_commit_id,
+            settlement_lag_commits: latest_settled_commit_id
+                .map(|settled| latest_commit_id - settled),
+            pending_txs: row.get("pending_txs"),
+            proving_txs: row.get("proving_txs"),
+            avg_proving_time_ms: row.get("avg_proving_time_ms"),
+            txs,
+        })
+    }
+}
@@ -0,0 +1,222 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use client_sdk::contract_indexer::AppError;
+use serde::Serialize;
+use sqlx::Row;
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::read_replica::ReadPool;
+
+/// Window of the ticker statistics
+const TICKER_WINDOW_MS: i64 = 24 * 60 * 60 * 1000;
+/// Trades are aggregated per minute, so the window slides by the minute
+const BUCKET_MS: i64 = 60 * 1000;
+/// Minimum time between two reads of the new trades
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Trading statistics of a pair over the last 24 hours
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Ticker {
+    pub symbol: String,
+    /// Price of the last trade, whenever it happened
+    pub last_price: Option<i64>,
+    /// Price of the first trade of the window
+    pub open_price: Option<i64>,
+    pub high_price: Option<i64>,
+    pub low_price: Option<i64>,
+    /// Last price less the open price
+    pub price_change: Option<i64>,
+    pub price_change_percent: Option<f64>,
+    /// Traded base quantity
+    pub volume: i64,
+    pub trade_count: i64,
+    /// Unix timestamp in milliseconds of the last trade
+    pub last_trade_at: Option<i64>,
+}
+
+/// Trades of a pair in a minute
+struct Bucket {
+    start: i64,
+    open: i64,
+    high: i64,
+    low: i64,
+    volume: i64,
+    trade_count: i64,
+}
+
+#[derive(Default)]
+struct PairTrades {
+    buckets: VecDeque<Bucket>,
+    last_price: Option<i64>,
+    last_trade_at: Option<i64>,
+}
+
+impl PairTrades {
+    fn record(&mut self, price: i64, qty: i64, trade_time: i64) {
+        self.last_price = Some(price);
+        self.last_trade_at = Some(trade_time);
+        let start = trade_time - trade_time.rem_euclid(BUCKET_MS);
+        // Trades are read in id order, so a late trade time joins the last minute
+        match self.buckets.back_mut() {
+            Some(bucket) if bucket.start >= start => {
+                bucket.high = bucket.high.max(price);
+                bucket.low = bucket.low.min(price);
+                bucket.volume += qty;
+                bucket.trade_count += 1;
+            }
+            _ => self.buckets.push_back(Bucket {
+                start,
+                open: price,
+                high: price,
+                low: price,
+                volume: qty,
+                trade_count: 1,
+            }),
+        }
+    }
+
+    fn ticker(&mut self, symbol: String, now: i64) -> Ticker {
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| bucket.start + BUCKET_MS <= now - TICKER_WINDOW_MS)
+        {
+            self.buckets.pop_front();
+        }
+
+        let open_price = self.buckets.front().map(|bucket| bucket.open);
+        let price_change = self
+            .last_price
+            .zip(open_price)
+            .map(|(last, open)| last - open);
+        Ticker {
+            symbol,
+            last_price: self.last_price,
+            open_price,
+            high_price: self.buckets.iter().map(|bucket| bucket.high).max(),
+            low_price: self.buckets.iter().map(|bucket| bucket.low).min(),
+            price_change,
+            price_change_percent: price_change
+                .zip(open_price)
+                .filter(|(_, open)| *open != 0)
+                .map(|(change, open)| change as f64 * 100.0 / open as f64),
+            volume: self.buckets.iter().map(|bucket| bucket.volume).sum(),
+            trade_count: self.buckets.iter().map(|bucket| bucket.trade_count).sum(),
+            last_trade_at: self.last_trade_at,
+        }
+    }
+}
+
+#[derive(Default)]
+struct TickerCache {
+    pairs: HashMap<i64, PairTrades>,
+    /// Last trade aggregated, trades being read in id order
+    last_trade_id: Option<i64>,
+    refreshed_at: Option<Instant>,
+}
+
+/// 24h tickers of the pairs, aggregated in memory from `trade_events`. The trades of the window
+/// are loaded once, then each refresh only reads the trades added since the previous one.
+pub struct TickerService {
+    pool: Arc<ReadPool>,
+    cache: Mutex<TickerCache>,
+}
+
+impl TickerService {
+    pub fn new(pool: Arc<ReadPool>) -> Self {
+        TickerService {
+            pool,
+            cache: Mutex::new(TickerCache::default()),
+        }
+    }
+
+    /// Tickers of the given `(instrument_id, symbol)` pairs
+    pub async fn tickers(
+        &self,
+        instruments: impl IntoIterator<Item = (i64, String)>,
+    ) -> Result<Vec<Ticker>, AppError> {
+        let mut cache = self.cache.lock().await;
+        self.refresh(&mut cache).await?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        Ok(instruments
+            .into_iter()
+            .map(|(instrument_id, symbol)| {
+                cache
+                    .pairs
+                    .entry(instrument_id)
+                    .or_default()
+                    .ticker(symbol, now)
+            })
+            .collect())
+    }
+
+    async fn refresh(&self, cache: &mut TickerCache) -> Result<(), AppError> {
+        if cache
+            .refreshed_at
+            .is_some_and(|refreshed_at| refreshed_at.elapsed() < REFRESH_INTERVAL)
+        {
+            return Ok(());
+        }
+
+        if cache.last_trade_id.is_none() {
+            // Last price of the pairs that did not trade within the window
+            let last_trades = sqlx::query(
+                "
+                SELECT i.instrument_id, t.trade_id, t.price,
+                    (EXTRACT(EPOCH FROM t.trade_time) * 1000)::bigint AS trade_time
+                FROM instruments i
+                CROSS JOIN LATERAL (
+                    SELECT trade_id, price, trade_time
+                    FROM trade_events
+                    WHERE instrument_id = i.instrument_id
+                        AND trade_time <= now() - interval '24 hours'
+                    ORDER BY trade_id DESC
+                    LIMIT 1
+                ) t
+                ",
+            )
+            .fetch_all(self.pool.pool())
+            .await?;
+            for row in last_trades {
+                let pair = cache.pairs.entry(row.get("instrument_id")).or_default();
+                pair.last_price = Some(row.get("price"));
+                pair.last_trade_at = Some(row.get("trade_time"));
+            }
+        }
+
+        let trades = sqlx::query(
+            "
+            SELECT trade_id, instrument_id, price, qty,
+                (EXTRACT(EPOCH FROM trade_time) * 1000)::bigint AS trade_time
+            FROM trade_events
+            WHERE ($1::bigint IS NULL OR trade_id > $1)
+                AND trade_time > now() - interval '24 hours'
+            ORDER BY trade_id
+            ",
+        )
+        .bind(cache.last_trade_id)
+        .fetch_all(self.pool.pool())
+        .await?;
+
+        cache.last_trade_id = Some(cache.last_trade_id.unwrap_or_default());
+        for row in trades {
+            cache
+                .pairs
+                .entry(row.get("instrument_id"))
+                .or_default()
+                .record(row.get("price"), row.get("qty"), row.get("trade_time"));
+            cache.last_trade_id = Some(row.get("trade_id"));
+        }
+        cache.refreshed_at = Some(Instant::now());
+        Ok(())
+    }
+}
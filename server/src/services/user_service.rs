@@ -2,10 +2,12 @@ use std::collections::HashMap;
 
 use anyhow::Context;
 use client_sdk::contract_indexer::AppError;
-use orderbook::model::UserInfo;
+use orderbook::model::{
+    Debt, FeeTier, PendingWithdrawal, Position, SessionKeyScope, UserInfo, WithdrawalWindow,
+};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Row};
+use sqlx::{postgres::PgRow, types::Json, PgPool, Row};
 use tracing::debug;
 
 pub struct UserService {
@@ -33,19 +35,99 @@ impl UserService {
     #[cfg_attr(feature = "instrumentation", tracing::instrument(skip(self)))]
     pub async fn get_user_info(&self, user: &str) -> Result<UserInfo, AppError> {
         let row = sqlx::query(
-            "
+            r#"
             SELECT 
                 u.identity, 
                 u.salt, 
                 u.nonce, 
+                u.parent_identity,
                 (SELECT session_keys 
                  FROM user_session_keys 
                  WHERE identity = u.identity 
                  ORDER BY commit_id DESC 
-                 LIMIT 1) as session_keys
+                 LIMIT 1) as session_keys,
+                (SELECT session_key_scopes
+                 FROM user_session_keys
+                 WHERE identity = u.identity
+                 ORDER BY commit_id DESC
+                 LIMIT 1) as session_key_scopes,
+                uft.tier,
+                uft.maker_fee_bps,
+                uft.taker_fee_bps,
+                uww.withdrawal_windows,
+                up.positions,
+                ud.debts,
+                upw.pending_withdrawals
             FROM users u
+            LEFT JOIN LATERAL (
+                SELECT tier, maker_fee_bps, taker_fee_bps
+                FROM user_fee_tiers
+                WHERE identity = u.identity
+                ORDER BY commit_id DESC
+                LIMIT 1
+            ) uft ON true
+            LEFT JOIN LATERAL (
+                SELECT json_agg(json_build_object(
+                    'symbol', w.symbol,
+                    'window_start', w.window_start,
+                    'withdrawn', w.withdrawn
+                ) ORDER BY w.symbol COLLATE "C") AS withdrawal_windows
+                FROM (
+                    SELECT DISTINCT ON (symbol) symbol, window_start, withdrawn
+                    FROM user_withdrawal_windows
+                    WHERE identity = u.identity
+                    ORDER BY symbol, commit_id DESC
+                ) w
+            ) uww ON true
+            LEFT JOIN LATERAL (
+                SELECT json_agg(json_build_object(
+                    'pair', json_build_array(split_part(p.symbol, '/', 1), split_part(p.symbol, '/', 2)),
+                    'size', p.size,
+                    'entry_price', p.entry_price,
+                    'margin', p.margin,
+                    'funding_index', p.funding_index
+                )) AS positions
+                FROM (
+                    SELECT DISTINCT ON (symbol) symbol, size, entry_price, margin, funding_index
+                    FROM user_positions
+                    WHERE identity = u.identity
+                    ORDER BY symbol, commit_id DESC
+                ) p
+                WHERE p.size <> 0
+            ) up ON true
+            LEFT JOIN LATERAL (
+                SELECT json_agg(json_build_object(
+                    'symbol', d.symbol,
+                    'amount', d.amount
+                ) ORDER BY d.symbol COLLATE "C") AS debts
+                FROM (
+                    SELECT DISTINCT ON (symbol) symbol, amount
+                    FROM user_debts
+                    WHERE identity = u.identity
+                    ORDER BY symbol, commit_id DESC
+                ) d
+                WHERE d.amount <> 0
+            ) ud ON true
+            LEFT JOIN LATERAL (
+                SELECT json_agg(json_build_object(
+                    'id', pw.withdrawal_id,
+                    'symbol', pw.symbol,
+                    'amount', pw.amount,
+                    'destination', json_build_object('network', pw.network, 'address', pw.address),
+                    'available_at', pw.available_at,
+                    'approved', pw.status = 'approved'
+                ) ORDER BY pw.withdrawal_id) AS pending_withdrawals
+                FROM (
+                    SELECT DISTINCT ON (withdrawal_id) withdrawal_id, symbol, amount, network,
+                        address, available_at, status
+                    FROM user_pending_withdrawals
+                    WHERE identity = u.identity
+                    ORDER BY withdrawal_id, commit_id DESC
+                ) pw
+                WHERE pw.status IN ('queued', 'approved')
+            ) upw ON true
             WHERE u.identity = $1
-            ",
+            "#,
         )
         .bind(user)
         .fetch_one(&self.pool)
@@ -64,6 +146,16 @@ impl UserService {
             session_keys: row
                 .get::<Option<Vec<Vec<u8>>>, _>("session_keys")
                 .unwrap_or_default(),
+            session_key_scopes: row
+                .get::<Option<Json<Vec<SessionKeyScope>>>, _>("session_key_scopes")
+                .map(|scopes| scopes.0)
+                .unwrap_or_default(),
+            fee_tier: fee_tier_from_row(&row),
+            withdrawal_windows: withdrawal_windows_from_row(&row),
+            positions: positions_from_row(&row),
+            debts: debts_from_row(&row),
+            pending_withdrawals: pending_withdrawals_from_row(&row),
+            sub_account: is_sub_account(&row),
         })
     }
 
@@ -172,24 +264,103 @@ impl UserService {
         debug!("Fetching all users from the database");
         // TODO this query might need to be optimized
         let rows = sqlx::query(
-            "
-            SELECT u.identity, u.salt, uen.nonce, 
-                   usk.session_keys as session_keys
+            r#"
+            SELECT u.identity, u.salt, u.parent_identity, uen.nonce, 
+                   usk.session_keys as session_keys,
+                   usk.session_key_scopes as session_key_scopes,
+                   uft.tier, uft.maker_fee_bps, uft.taker_fee_bps,
+                   uww.withdrawal_windows,
+                   up.positions,
+                   ud.debts,
+                   upw.pending_withdrawals
             FROM users u
             LEFT JOIN user_session_keys usk ON u.identity = usk.identity
             LEFT JOIN user_events_nonces uen ON u.identity = uen.identity
+            LEFT JOIN LATERAL (
+                SELECT tier, maker_fee_bps, taker_fee_bps
+                FROM user_fee_tiers
+                WHERE identity = u.identity
+                AND commit_id <= $1
+                ORDER BY commit_id DESC
+                LIMIT 1
+            ) uft ON true
+            LEFT JOIN LATERAL (
+                SELECT json_agg(json_build_object(
+                    'symbol', w.symbol,
+                    'window_start', w.window_start,
+                    'withdrawn', w.withdrawn
+                ) ORDER BY w.symbol COLLATE "C") AS withdrawal_windows
+                FROM (
+                    SELECT DISTINCT ON (symbol) symbol, window_start, withdrawn
+                    FROM user_withdrawal_windows
+                    WHERE identity = u.identity
+                    AND commit_id <= $1
+                    ORDER BY symbol, commit_id DESC
+                ) w
+            ) uww ON true
+            LEFT JOIN LATERAL (
+                SELECT json_agg(json_build_object(
+                    'pair', json_build_array(split_part(p.symbol, '/', 1), split_part(p.symbol, '/', 2)),
+                    'size', p.size,
+                    'entry_price', p.entry_price,
+                    'margin', p.margin,
+                    'funding_index', p.funding_index
+                )) AS positions
+                FROM (
+                    SELECT DISTINCT ON (symbol) symbol, size, entry_price, margin, funding_index
+                    FROM user_positions
+                    WHERE identity = u.identity
+                    AND commit_id <= $1
+                    ORDER BY symbol, commit_id DESC
+                ) p
+                WHERE p.size <> 0
+            ) up ON true
+            LEFT JOIN LATERAL (
+                SELECT json_agg(json_build_object(
+                    'symbol', d.symbol,
+                    'amount', d.amount
+                ) ORDER BY d.symbol COLLATE "C") AS debts
+                FROM (
+                    SELECT DISTINCT ON (symbol) symbol, amount
+                    FROM user_debts
+                    WHERE identity = u.identity
+                    AND commit_id <= $1
+                    ORDER BY symbol, commit_id DESC
+                ) d
+                WHERE d.amount <> 0
+            ) ud ON true
+            LEFT JOIN LATERAL (
+                SELECT json_agg(json_build_object(
+                    'id', pw.withdrawal_id,
+                    'symbol', pw.symbol,
+                    'amount', pw.amount,
+                    'destination', json_build_object('network', pw.network, 'address', pw.address),
+                    'available_at', pw.available_at,
+                    'approved', pw.status = 'approved'
+                ) ORDER BY pw.withdrawal_id) AS pending_withdrawals
+                FROM (
+                    SELECT DISTINCT ON (withdrawal_id) withdrawal_id, symbol, amount, network,
+                        address, available_at, status
+                    FROM user_pending_withdrawals
+                    WHERE identity = u.identity
+                    AND commit_id <= $1
+                    ORDER BY withdrawal_id, commit_id DESC
+                ) pw
+                WHERE pw.status IN ('queued', 'approved')
+            ) upw ON true
             WHERE 
-                usk.commit_id = 
+                -- Users without session keys (e.g. the orderbook account) are kept
+                (usk.commit_id IS NULL OR usk.commit_id = 
                     (SELECT MAX(commit_id) FROM user_session_keys 
                         WHERE identity = u.identity
                         AND commit_id <= $1
-                    )
+                    ))
                 AND uen.commit_id = 
                     (SELECT MAX(commit_id) FROM user_events_nonces 
                         WHERE identity = u.identity
                         AND commit_id <= $1
                     )
-        ",
+        "#,
         )
         .bind(commit_id)
         .fetch_all(&self.pool)
@@ -205,7 +376,19 @@ impl UserService {
                         user: row.get("identity"),
                         salt: row.get("salt"),
                         nonce: row.get::<i64, _>("nonce") as u32,
-                        session_keys: row.get("session_keys"),
+                        session_keys: row
+                            .get::<Option<Vec<Vec<u8>>>, _>("session_keys")
+                            .unwrap_or_default(),
+                        session_key_scopes: row
+                            .get::<Option<Json<Vec<SessionKeyScope>>>, _>("session_key_scopes")
+                            .map(|scopes| scopes.0)
+                            .unwrap_or_default(),
+                        fee_tier: fee_tier_from_row(row),
+                        withdrawal_windows: withdrawal_windows_from_row(row),
+                        positions: positions_from_row(row),
+                        debts: debts_from_row(row),
+                        pending_withdrawals: pending_withdrawals_from_row(row),
+                        sub_account: is_sub_account(row),
                     },
                 )
             })
@@ -213,4 +396,82 @@ impl UserService {
 
         users_map
     }
+
+    /// Get the rolling traded volume of every user, in whole units of `volume_asset`.
+    /// Users that did not trade during the window have a volume of 0.
+    pub async fn get_rolling_volumes(
+        &self,
+        window_days: u32,
+        volume_asset: &str,
+    ) -> Result<HashMap<String, u64>, AppError> {
+        let rows = sqlx::query(
+            "
+            SELECT u.identity, COALESCE(v.volume, 0)::bigint as volume
+            FROM users u
+            LEFT JOIN get_rolling_user_volumes(make_interval(days => $1), $2) v
+                ON v.identity = u.identity
+            ",
+        )
+        .bind(window_days as i32)
+        .bind(volume_asset)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                (
+                    row.get("identity"),
+                    row.get::<i64, _>("volume").max(0) as u64,
+                )
+            })
+            .collect())
+    }
+}
+
+fn fee_tier_from_row(row: &PgRow) -> FeeTier {
+    match row.get::<Option<i16>, _>("tier") {
+        Some(tier) => FeeTier {
+            tier: tier as u8,
+            maker_fee_bps: row.get::<i32, _>("maker_fee_bps") as u16,
+            taker_fee_bps: row.get::<i32, _>("taker_fee_bps") as u16,
+        },
+        None => FeeTier::default(),
+    }
+}
+
+fn withdrawal_windows_from_row(row: &PgRow) -> Vec<WithdrawalWindow> {
+    row.get::<Option<Json<Vec<WithdrawalWindow>>>, _>("withdrawal_windows")
+        .map(|windows| windows.0)
+        .unwrap_or_default()
+}
+
+/// Open positions, sorted by pair like in the committed user info
+fn positions_from_row(row: &PgRow) -> Vec<Position> {
+    let mut positions = row
+        .get::<Option<Json<Vec<Position>>>, _>("positions")
+        .map(|positions| positions.0)
+        .unwrap_or_default();
+    positions.sort_by(|a, b| a.pair.cmp(&b.pair));
+    positions
+}
+
+/// Outstanding debts, sorted by symbol like in the committed user info
+fn debts_from_row(row: &PgRow) -> Vec<Debt> {
+    row.get::<Option<Json<Vec<Debt>>>, _>("debts")
+        .map(|debts| debts.0)
+        .unwrap_or_default()
+}
+
+/// Users registered as sub-accounts have their parent stored, unlike the identities containing
+/// the separator that were registered before sub-accounts existed
+fn is_sub_account(row: &PgRow) -> bool {
+    row.get::<Option<String>, _>("parent_identity").is_some()
+}
+
+/// Withdrawals still in the queue, sorted by id like in the committed user info
+fn pending_withdrawals_from_row(row: &PgRow) -> Vec<PendingWithdrawal> {
+    row.get::<Option<Json<Vec<PendingWithdrawal>>>, _>("pending_withdrawals")
+        .map(|withdrawals| withdrawals.0)
+        .unwrap_or_default()
 }
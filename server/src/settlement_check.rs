@@ -0,0 +1,272 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use hyli_modules::{
+    bus::SharedMessageBus, log_error, module_bus_client, module_handle_messages, modules::Module,
+};
+use opentelemetry::metrics::Gauge;
+use orderbook::zk::FullState;
+use sdk::{ContractName, NodeStateEvent, StateCommitment, StatefulEvent};
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::{debug, error, info};
+use utoipa::ToSchema;
+
+use crate::{
+    conf::SettlementCheckConfig,
+    replay::{fetch_commit_events, log_state_diff, query_onchain_commitments},
+};
+
+/// Outcome of the settlement check, served by `GET /settlement/status`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SettlementStatus {
+    /// "ok" while every settled commitment matched, "diverged" otherwise
+    pub status: &'static str,
+    /// Last commit whose settled commitment matched the local one
+    pub settled_commit_id: i64,
+    /// First commit whose settled commitment differs from the local one
+    pub diverged_at: Option<i64>,
+    /// Settled txs of the orderbook waiting for their proof output to be indexed
+    pub pending_txs: usize,
+}
+
+/// Settlement status shared between the module checking the settled txs and the API, which
+/// refuses new actions once the settled state diverged from the local one
+pub struct SettlementCheck {
+    halt_on_divergence: bool,
+    diverged: AtomicBool,
+    status: Mutex<SettlementStatus>,
+    settled_commit: Gauge<u64>,
+}
+
+impl SettlementCheck {
+    pub fn new(config: &SettlementCheckConfig, settled_commit_id: i64) -> Self {
+        let meter = opentelemetry::global::meter("app");
+        SettlementCheck {
+            halt_on_divergence: config.halt_on_divergence,
+            diverged: AtomicBool::new(false),
+            status: Mutex::new(SettlementStatus {
+                status: "ok",
+                settled_commit_id,
+                diverged_at: None,
+                pending_txs: 0,
+            }),
+            settled_commit: meter
+                .u64_gauge("settlement.checked_commit_id")
+                .with_description("Last commit whose settled commitment matched the local one")
+                .build(),
+        }
+    }
+
+    pub fn status(&self) -> SettlementStatus {
+        self.status
+            .lock()
+            .expect("settlement status poisoned")
+            .clone()
+    }
+
+    /// Fails once the settled state diverged, if new actions are to be halted then
+    pub fn check_active(&self) -> Result<()> {
+        if self.halt_on_divergence && self.diverged.load(Ordering::Relaxed) {
+            bail!("The settled state diverged from the orderbook, new actions are halted");
+        }
+        Ok(())
+    }
+
+    fn settled(&self, commit_id: i64) {
+        let mut status = self.status.lock().expect("settlement status poisoned");
+        status.settled_commit_id = commit_id;
+        self.settled_commit.record(commit_id.max(0) as u64, &[]);
+    }
+
+    fn set_pending_txs(&self, pending_txs: usize) {
+        let mut status = self.status.lock().expect("settlement status poisoned");
+        status.pending_txs = pending_txs;
+    }
+
+    fn diverged(&self, commit_id: i64) {
+        let mut status = self.status.lock().expect("settlement status poisoned");
+        status.status = "diverged";
+        status.diverged_at = Some(commit_id);
+        self.diverged.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Catches up on the blocks of the DA and checks each orderbook tx they settle: the events of
+/// the commits it carries are applied to the full state of the last checked commit, and the
+/// commitment rebuilt from them must match the next state of its proof, read from the indexer.
+/// The first mismatch is logged with the parts of the state that differ, and stops the check.
+pub struct SettlementCheckModule {
+    bus: SettlementCheckModuleBusClient,
+    ctx: Arc<SettlementCheckModuleCtx>,
+    indexer_pool: PgPool,
+    /// Full state at `commit_id`
+    state: FullState,
+    commit_id: i64,
+    /// Settled orderbook txs, in settlement order, whose proof output is not indexed yet
+    pending_txs: VecDeque<String>,
+}
+
+pub struct SettlementCheckModuleCtx {
+    pub check: Arc<SettlementCheck>,
+    pub pool: PgPool,
+    pub indexer_database_url: String,
+    pub orderbook_cn: ContractName,
+    /// Full state at `settled_commit_id`, the last settled commit when the server started
+    pub state: FullState,
+    pub settled_commit_id: i64,
+}
+
+module_bus_client! {
+#[derive(Debug)]
+pub struct SettlementCheckModuleBusClient {
+    receiver(NodeStateEvent),
+}
+}
+
+impl Module for SettlementCheckModule {
+    type Context = Arc<SettlementCheckModuleCtx>;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = SettlementCheckModuleBusClient::new_from_bus(bus.new_handle()).await;
+        let indexer_pool = PgPool::connect(&ctx.indexer_database_url)
+            .await
+            .context("connecting to indexer database")?;
+
+        Ok(SettlementCheckModule {
+            bus,
+            indexer_pool,
+            state: ctx.state.clone(),
+            commit_id: ctx.settled_commit_id,
+            pending_txs: VecDeque::new(),
+            ctx,
+        })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        module_handle_messages! {
+            on_self self,
+            listen<NodeStateEvent> event => {
+                _ = log_error!(self.handle_node_state_event(event).await, "handle node state event")
+            }
+        };
+
+        Ok(())
+    }
+}
+
+impl SettlementCheckModule {
+    async fn handle_node_state_event(&mut self, event: NodeStateEvent) -> Result<()> {
+        // The local state is not rebuilt past a divergence
+        if self.ctx.check.status().diverged_at.is_some() {
+            return Ok(());
+        }
+
+        match event {
+            NodeStateEvent::NewBlock(block) => {
+                for (_, stateful_event) in block.stateful_events.events.iter() {
+                    if let StatefulEvent::SettledTx(unsettled) = stateful_event {
+                        if unsettled
+                            .tx
+                            .blobs
+                            .iter()
+                            .any(|blob| blob.contract_name == self.ctx.orderbook_cn)
+                        {
+                            self.pending_txs.push_back(unsettled.tx_id.1 .0.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        // Txs are checked in settlement order, each from the state of the previous one
+        while let Some(tx_hash) = self.pending_txs.front().cloned() {
+            if !self.check_settled_tx(&tx_hash).await? {
+                break;
+            }
+            self.pending_txs.pop_front();
+            if self.ctx.check.status().diverged_at.is_some() {
+                self.pending_txs.clear();
+                break;
+            }
+        }
+        self.ctx.check.set_pending_txs(self.pending_txs.len());
+        Ok(())
+    }
+
+    /// Checks the commitment settled by `tx_hash`. Returns false while its proof output is not
+    /// indexed yet.
+    async fn check_settled_tx(&mut self, tx_hash: &str) -> Result<bool> {
+        let commitments =
+            query_onchain_commitments(&self.indexer_pool, &self.ctx.orderbook_cn.0, Some(tx_hash))
+                .await?;
+        // Actions batched in the same blob tx settle together: the last blob holds the state
+        // once they all applied
+        let Some(((_, blob_index), onchain)) = commitments
+            .iter()
+            .filter(|(_, commitment)| commitment.success)
+            .max_by_key(|((_, blob_index), _)| *blob_index)
+        else {
+            debug!("Proof output of settled tx {tx_hash} not indexed yet");
+            return Ok(false);
+        };
+
+        let commit_id: Option<i64> = sqlx::query_scalar(
+            "
+            SELECT max(c.commit_id)
+            FROM commits c
+            LEFT JOIN blob_tx_outbox o ON o.commit_id = c.commit_id
+            WHERE COALESCE(o.sent_tx_hash, c.tx_hash) = $1
+            ",
+        )
+        .bind(tx_hash)
+        .fetch_one(&self.ctx.pool)
+        .await
+        .context("fetching the commit of the settled tx")?;
+        let Some(commit_id) = commit_id else {
+            // The contract moved on without a local commit
+            error!("❌ Settled tx {tx_hash} of the orderbook is not a local commit");
+            self.ctx.check.diverged(self.commit_id + 1);
+            return Ok(true);
+        };
+        if commit_id <= self.commit_id {
+            debug!("Settled tx {tx_hash} was already checked at commit {commit_id}");
+            return Ok(true);
+        }
+
+        for commit in
+            fetch_commit_events(&self.ctx.pool, Some(self.commit_id), Some(commit_id)).await?
+        {
+            self.state
+                .apply_events_and_update_roots(&commit.user_info, commit.events)
+                .map_err(|e| {
+                    anyhow!("Could not apply events of commit {}: {e}", commit.commit_id)
+                })?;
+        }
+        self.commit_id = commit_id;
+
+        let local = self.state.commit();
+        if local.0 != onchain.next_state {
+            error!(
+                "❌ Settled state diverged at commit {commit_id} (tx {tx_hash}, blob {blob_index}, block {})",
+                onchain.block_height
+            );
+            log_state_diff(
+                "settled vs local",
+                &StateCommitment(onchain.next_state.clone()),
+                &local,
+            );
+            self.ctx.check.diverged(commit_id);
+        } else {
+            info!("Settled commitment of commit {commit_id} (tx {tx_hash}) matches");
+            self.ctx.check.settled(commit_id);
+        }
+        Ok(true)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::conf::Conf;
+use crate::{conf::Conf, node_client::NodeClient};
 use anyhow::{Context, Result};
 use client_sdk::rest_client::{IndexerApiHttpClient, NodeApiClient, NodeApiHttpClient};
 use sdk::LaneId;
@@ -80,6 +80,8 @@ pub struct ServiceContext {
     pub bridge_service: Option<Arc<RwLock<crate::services::bridge_service::BridgeService>>>,
     pub book_service: Arc<RwLock<crate::services::book_service::BookService>>,
     pub node_client: Arc<NodeApiHttpClient>,
+    /// Node client with timeouts, retries and circuit breaking, shared by the modules
+    pub resilient_node_client: Arc<NodeClient>,
     pub indexer_client: Arc<IndexerApiHttpClient>,
     pub validator_lane_id: LaneId,
 }
@@ -113,6 +115,10 @@ pub async fn setup_services(
     let node_client = Arc::new(
         NodeApiHttpClient::new(config.node_url.clone()).context("Failed to build node client")?,
     );
+    let resilient_node_client = Arc::new(NodeClient::new(
+        node_client.clone(),
+        config.node_client.clone(),
+    ));
 
     // Initialize indexer client
     let indexer_client = Arc::new(
@@ -141,6 +147,7 @@ pub async fn setup_services(
         bridge_service,
         book_service,
         node_client,
+        resilient_node_client,
         indexer_client,
         validator_lane_id,
     })
@@ -0,0 +1,528 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hyli_modules::{
+    bus::SharedMessageBus, log_error, module_bus_client, module_handle_messages, modules::Module,
+};
+use orderbook::{
+    model::{ExecuteState, OrderbookEvent, UserInfo},
+    zk::FullState,
+};
+use sdk::{BlockHeight, LaneId};
+use sha3::{Digest, Sha3_256};
+use sqlx::{PgPool, Row};
+use tracing::{debug, info, warn};
+
+use crate::checkpoint::CheckpointPublisher;
+
+const SNAPSHOT_FILE: &str = "orderbook_state.snapshot";
+const WAL_FILE: &str = "orderbook_state.wal";
+const FULL_STATE_FILE: &str = "orderbook_full_state.checkpoint";
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct StateSnapshot {
+    commit_id: i64,
+    state: ExecuteState,
+    /// Commitment of the full state built from `state`, to detect corrupted snapshots
+    state_commitment: Vec<u8>,
+}
+
+/// Full state at the commit of the snapshot, so that its trees are restored instead of rebuilt
+#[derive(BorshSerialize, BorshDeserialize)]
+struct FullStateCheckpoint {
+    commit_id: i64,
+    /// Borsh encoded `FullState`
+    full_state: Vec<u8>,
+    /// SHA3-256 of `full_state`, to detect corrupted checkpoints
+    integrity_hash: [u8; 32],
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct WalRecord {
+    commit_id: i64,
+    user_info: UserInfo,
+    events: Vec<OrderbookEvent>,
+}
+
+/// Crash-safe snapshots of the light orderbook state, so that the server restarts without
+/// rebuilding it from the database tables.
+///
+/// The data directory holds a borsh snapshot of `ExecuteState` at some settled commit, and a
+/// write-ahead log of the events of the commits persisted since then. Records are length
+/// prefixed, so that a record torn by a crash is ignored. Snapshots are written to a
+/// temporary file and renamed, so a crash never leaves a partially written snapshot.
+///
+/// The `FullState` of the snapshot is checkpointed next to it, SMT stores included, so that
+/// restarts catch the trees up on the WAL instead of rebuilding them with `FullState::from_data`.
+pub struct SnapshotStore {
+    pool: PgPool,
+    secret: Vec<u8>,
+    lane_id: LaneId,
+    snapshot_path: PathBuf,
+    full_state_path: PathBuf,
+    wal_path: PathBuf,
+    wal: Mutex<BufWriter<File>>,
+}
+
+impl SnapshotStore {
+    pub fn open(
+        data_directory: &Path,
+        pool: PgPool,
+        secret: Vec<u8>,
+        lane_id: LaneId,
+    ) -> Result<Self> {
+        let wal_path = data_directory.join(WAL_FILE);
+        let wal = open_wal(&wal_path)?;
+        Ok(SnapshotStore {
+            pool,
+            secret,
+            lane_id,
+            snapshot_path: data_directory.join(SNAPSHOT_FILE),
+            full_state_path: data_directory.join(FULL_STATE_FILE),
+            wal_path,
+            wal: Mutex::new(wal),
+        })
+    }
+
+    /// Appends the events of a commit persisted in the database to the WAL.
+    /// Database workers run concurrently, so records are not necessarily in commit order.
+    pub fn append(
+        &self,
+        commit_id: i64,
+        user_info: &UserInfo,
+        events: &[OrderbookEvent],
+    ) -> Result<()> {
+        let record = borsh::to_vec(&WalRecord {
+            commit_id,
+            user_info: user_info.clone(),
+            events: events.to_vec(),
+        })?;
+        let mut wal = self.wal.lock().map_err(|_| anyhow!("WAL lock poisoned"))?;
+        wal.write_all(&(record.len() as u32).to_le_bytes())?;
+        wal.write_all(&record)?;
+        wal.flush()?;
+        Ok(())
+    }
+
+    /// Restores the state at `commit_id` from the last snapshot and the WAL.
+    /// Returns None when the snapshot is missing or more recent than the requested commit.
+    pub async fn restore(&self, commit_id: i64) -> Result<Option<(ExecuteState, FullState)>> {
+        let Some(snapshot) = self.read_snapshot()? else {
+            info!("🔍 No state snapshot found");
+            return Ok(None);
+        };
+        if snapshot.commit_id > commit_id {
+            warn!(
+                "State snapshot at commit {} is ahead of commit {commit_id}, ignoring it",
+                snapshot.commit_id
+            );
+            return Ok(None);
+        }
+
+        let mut full_state = self.snapshot_full_state(&snapshot)?;
+
+        let records = self.read_wal()?;
+        info!(
+            "🔍 Restoring state from snapshot at commit {} and {} WAL records",
+            snapshot.commit_id,
+            records.range(snapshot.commit_id + 1..=commit_id).count()
+        );
+        let from = snapshot.commit_id;
+        let state = self
+            .replay(snapshot.state, from, &records, commit_id)
+            .await?;
+        replay_full_state(&mut full_state, from, &records, commit_id)?;
+        Ok(Some((state, full_state)))
+    }
+
+    /// State of the snapshot alone, without the WAL: the last settled commit it was moved to.
+    /// Returns None when there is no snapshot.
+    pub fn last_good(&self) -> Result<Option<(i64, ExecuteState, FullState)>> {
+        let Some(snapshot) = self.read_snapshot()? else {
+            return Ok(None);
+        };
+        let full_state = self.snapshot_full_state(&snapshot)?;
+        Ok(Some((snapshot.commit_id, snapshot.state, full_state)))
+    }
+
+    /// Snapshots the state the server booted with, and rebuilds the WAL of the later commits
+    /// from the database, which holds the events of every commit.
+    pub async fn checkpoint(
+        &self,
+        commit_id: i64,
+        state: &ExecuteState,
+        full_state: &FullState,
+    ) -> Result<()> {
+        self.write_snapshot(&StateSnapshot {
+            commit_id,
+            state: state.clone(),
+            state_commitment: full_state.commit().0,
+        })?;
+        self.write_full_state_checkpoint(commit_id, full_state)?;
+
+        let rows = sqlx::query(
+            "SELECT commit_id, user_info, events FROM contract_events WHERE commit_id > $1 ORDER BY commit_id",
+        )
+        .bind(commit_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut records = Vec::with_capacity(rows.len());
+        for row in rows {
+            records.push(WalRecord {
+                commit_id: row.get("commit_id"),
+                user_info: borsh::from_slice(&row.get::<Vec<u8>, _>("user_info"))?,
+                events: borsh::from_slice(&row.get::<Vec<u8>, _>("events"))?,
+            });
+        }
+        info!(
+            "💾 Wrote state snapshot at commit {commit_id}, with {} commits in the WAL",
+            records.len()
+        );
+        self.rewrite_wal(|wal| {
+            for record in &records {
+                write_record(wal, record)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Moves the snapshot forward to the last settled commit, and drops the WAL records it covers.
+    /// Returns the commit and state commitment of the new snapshot, if it moved.
+    pub async fn compact(&self) -> Result<Option<(i64, Vec<u8>)>> {
+        let Some(settled_commit_id) = self.settled_commit_id().await? else {
+            return Ok(None);
+        };
+        let snapshot = self
+            .read_snapshot()?
+            .ok_or_else(|| anyhow!("No state snapshot to compact"))?;
+        if settled_commit_id <= snapshot.commit_id {
+            return Ok(None);
+        }
+
+        let mut full_state = self.snapshot_full_state(&snapshot)?;
+        let records = self.read_wal()?;
+        let from = snapshot.commit_id;
+        let state = self
+            .replay(snapshot.state, from, &records, settled_commit_id)
+            .await?;
+        replay_full_state(&mut full_state, from, &records, settled_commit_id)?;
+        let state_commitment = full_state.commit().0;
+        self.write_snapshot(&StateSnapshot {
+            commit_id: settled_commit_id,
+            state,
+            state_commitment: state_commitment.clone(),
+        })?;
+        self.write_full_state_checkpoint(settled_commit_id, &full_state)?;
+
+        // Records appended while compacting are read again under the lock, so none is lost
+        self.rewrite_wal(|wal| {
+            let mut file = File::open(&self.wal_path)?;
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            for record in parse_records(&bytes).into_values() {
+                if record.commit_id > settled_commit_id {
+                    write_record(wal, &record)?;
+                }
+            }
+            Ok(())
+        })?;
+        debug!("Compacted state snapshot up to commit {settled_commit_id}");
+        Ok(Some((settled_commit_id, state_commitment)))
+    }
+
+    /// Last commit whose transaction settled: settled prover requests are deleted, and blobs are
+    /// sent in commit order.
+    async fn settled_commit_id(&self) -> Result<Option<i64>> {
+        Ok(sqlx::query_scalar::<_, Option<i64>>(
+            "SELECT COALESCE(
+                (SELECT MIN(commit_id) - 1 FROM prover_requests),
+                (SELECT MAX(commit_id) FROM blob_tx_outbox WHERE status = 'sent')
+            )",
+        )
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Applies the WAL records of the commits after `from` up to `to`, after checking
+    /// that they are exactly the commits persisted in the database.
+    async fn replay(
+        &self,
+        mut state: ExecuteState,
+        from: i64,
+        records: &BTreeMap<i64, WalRecord>,
+        to: i64,
+    ) -> Result<ExecuteState> {
+        let db_commit_ids: Vec<i64> = sqlx::query_scalar(
+            "SELECT commit_id FROM contract_events WHERE commit_id > $1 AND commit_id <= $2 ORDER BY commit_id",
+        )
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await?;
+        let wal_commit_ids: Vec<i64> = records.range(from + 1..=to).map(|(id, _)| *id).collect();
+        if wal_commit_ids != db_commit_ids {
+            bail!(
+                "WAL does not match the database between commits {from} and {to}: {} commits in the WAL, {} in the database",
+                wal_commit_ids.len(),
+                db_commit_ids.len()
+            );
+        }
+
+        if let Some(last) = records.range(from + 1..=to).next_back().map(|(_, r)| r) {
+            let db_events: Vec<u8> =
+                sqlx::query_scalar("SELECT events FROM contract_events WHERE commit_id = $1")
+                    .bind(last.commit_id)
+                    .fetch_one(&self.pool)
+                    .await?;
+            if db_events != borsh::to_vec(&last.events)? {
+                bail!(
+                    "Events of commit {} in the WAL do not match the database",
+                    last.commit_id
+                );
+            }
+        }
+
+        for record in records.range(from + 1..=to).map(|(_, r)| r) {
+            state
+                .apply_events(&record.user_info, &record.events)
+                .map_err(|e| anyhow!("Failed to replay commit {}: {e}", record.commit_id))?;
+        }
+        Ok(state)
+    }
+
+    /// Full state of a snapshot, from its checkpoint when it is valid and rebuilt otherwise
+    fn snapshot_full_state(&self, snapshot: &StateSnapshot) -> Result<FullState> {
+        match self.read_full_state_checkpoint() {
+            Ok(Some((commit_id, full_state)))
+                if commit_id == snapshot.commit_id
+                    && full_state.commit().0 == snapshot.state_commitment =>
+            {
+                return Ok(full_state);
+            }
+            Ok(Some((commit_id, _))) => warn!(
+                "Full state checkpoint at commit {commit_id} does not match the snapshot at commit {}, rebuilding it",
+                snapshot.commit_id
+            ),
+            Ok(None) => debug!("No full state checkpoint, rebuilding it"),
+            Err(e) => warn!("⚠️ Ignoring full state checkpoint: {e:#}"),
+        }
+
+        let full_state = self.full_state(&snapshot.state)?;
+        if full_state.commit().0 != snapshot.state_commitment {
+            bail!(
+                "State snapshot at commit {} does not match its commitment",
+                snapshot.commit_id
+            );
+        }
+        Ok(full_state)
+    }
+
+    fn read_full_state_checkpoint(&self) -> Result<Option<(i64, FullState)>> {
+        if !self.full_state_path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(&self.full_state_path).context("reading full state checkpoint")?;
+        let checkpoint: FullStateCheckpoint =
+            borsh::from_slice(&bytes).context("decoding full state checkpoint")?;
+        let hash: [u8; 32] = Sha3_256::digest(&checkpoint.full_state).into();
+        if hash != checkpoint.integrity_hash {
+            bail!(
+                "Full state checkpoint at commit {} does not match its integrity hash",
+                checkpoint.commit_id
+            );
+        }
+        let full_state = borsh::from_slice(&checkpoint.full_state)
+            .context("decoding checkpointed full state")?;
+        Ok(Some((checkpoint.commit_id, full_state)))
+    }
+
+    fn write_full_state_checkpoint(&self, commit_id: i64, full_state: &FullState) -> Result<()> {
+        let full_state = borsh::to_vec(full_state)?;
+        let checkpoint = FullStateCheckpoint {
+            commit_id,
+            integrity_hash: Sha3_256::digest(&full_state).into(),
+            full_state,
+        };
+        let tmp_path = self.full_state_path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&borsh::to_vec(&checkpoint)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.full_state_path).context("renaming full state checkpoint")?;
+        Ok(())
+    }
+
+    fn full_state(&self, state: &ExecuteState) -> Result<FullState> {
+        FullState::from_data(
+            state,
+            self.secret.clone(),
+            self.lane_id.clone(),
+            BlockHeight::default(),
+        )
+        .map_err(|e| anyhow!("Failed to build full state: {e}"))
+    }
+
+    fn read_snapshot(&self) -> Result<Option<StateSnapshot>> {
+        if !self.snapshot_path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(&self.snapshot_path).context("reading state snapshot")?;
+        Ok(Some(
+            borsh::from_slice(&bytes).context("decoding state snapshot")?,
+        ))
+    }
+
+    fn write_snapshot(&self, snapshot: &StateSnapshot) -> Result<()> {
+        let tmp_path = self.snapshot_path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&borsh::to_vec(snapshot)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.snapshot_path).context("renaming state snapshot")?;
+        Ok(())
+    }
+
+    fn read_wal(&self) -> Result<BTreeMap<i64, WalRecord>> {
+        let mut wal = self.wal.lock().map_err(|_| anyhow!("WAL lock poisoned"))?;
+        wal.flush()?;
+        let bytes = fs::read(&self.wal_path).context("reading state WAL")?;
+        Ok(parse_records(&bytes))
+    }
+
+    fn rewrite_wal(&self, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+        let mut wal = self.wal.lock().map_err(|_| anyhow!("WAL lock poisoned"))?;
+        wal.flush()?;
+
+        let tmp_path = self.wal_path.with_extension("tmp");
+        let mut tmp = BufWriter::new(File::create(&tmp_path)?);
+        write(&mut tmp)?;
+        tmp.flush()?;
+        tmp.get_ref().sync_all()?;
+        fs::rename(&tmp_path, &self.wal_path).context("renaming state WAL")?;
+
+        *wal = open_wal(&self.wal_path)?;
+        Ok(())
+    }
+}
+
+fn open_wal(path: &Path) -> Result<BufWriter<File>> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context("opening state WAL")?;
+    Ok(BufWriter::new(file))
+}
+
+/// Catches the trees of a full state up on the WAL records of the commits after `from` up to
+/// `to`, which `SnapshotStore::replay` checked against the database
+fn replay_full_state(
+    full_state: &mut FullState,
+    from: i64,
+    records: &BTreeMap<i64, WalRecord>,
+    to: i64,
+) -> Result<()> {
+    for record in records.range(from + 1..=to).map(|(_, r)| r) {
+        full_state
+            .apply_events_and_update_roots(&record.user_info, record.events.clone())
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to replay commit {} on the full state: {e}",
+                    record.commit_id
+                )
+            })?;
+    }
+    Ok(())
+}
+
+fn write_record(wal: &mut impl Write, record: &WalRecord) -> Result<()> {
+    let bytes = borsh::to_vec(record)?;
+    wal.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    wal.write_all(&bytes)?;
+    Ok(())
+}
+
+/// Decodes WAL records by commit id, stopping at the first torn or corrupted record
+fn parse_records(mut bytes: &[u8]) -> BTreeMap<i64, WalRecord> {
+    let mut records = BTreeMap::new();
+    while bytes.len() >= 4 {
+        let len = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        let Some(record) = bytes.get(4..4 + len) else {
+            warn!("Ignoring a torn record at the end of the state WAL");
+            break;
+        };
+        let Ok(record) = borsh::from_slice::<WalRecord>(record) else {
+            warn!("Ignoring a corrupted record in the state WAL and the ones after it");
+            break;
+        };
+        records.insert(record.commit_id, record);
+        bytes = &bytes[4 + len..];
+    }
+    records
+}
+
+/// Periodically moves the state snapshot forward, keeping the WAL short
+pub struct SnapshotModule {
+    bus: SnapshotModuleBusClient,
+    store: Arc<SnapshotStore>,
+    interval_secs: u64,
+    checkpoints: Option<Arc<CheckpointPublisher>>,
+}
+
+pub struct SnapshotModuleCtx {
+    pub store: Arc<SnapshotStore>,
+    pub interval_secs: u64,
+    /// Publishes a signed checkpoint of each new snapshot
+    pub checkpoints: Option<Arc<CheckpointPublisher>>,
+}
+
+module_bus_client! {
+#[derive(Debug)]
+pub struct SnapshotModuleBusClient {
+}
+}
+
+impl Module for SnapshotModule {
+    type Context = Arc<SnapshotModuleCtx>;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let bus = SnapshotModuleBusClient::new_from_bus(bus.new_handle()).await;
+
+        Ok(SnapshotModule {
+            bus,
+            store: ctx.store.clone(),
+            interval_secs: ctx.interval_secs,
+            checkpoints: ctx.checkpoints.clone(),
+        })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.interval_secs.max(1)));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        module_handle_messages! {
+            on_self self,
+            _ = interval.tick() => {
+                if let Ok(Some((commit_id, state_commitment))) =
+                    log_error!(self.store.compact().await, "compact state snapshot")
+                {
+                    if let Some(checkpoints) = &self.checkpoints {
+                        _ = log_error!(
+                            checkpoints.publish(commit_id, state_commitment).await,
+                            "publish state checkpoint"
+                        );
+                    }
+                }
+            }
+        };
+
+        Ok(())
+    }
+}
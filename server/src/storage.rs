@@ -0,0 +1,137 @@
+//! Storage the database module writes the applied actions to, and the notifications sent once
+//! their writes are committed, for the feeds to read them.
+//!
+//! [`crate::database::DatabaseService`] stores them in PostgreSQL, external or embedded.
+//! Notifications go through `pg_notify`, listened to by the API server, or through broadcast
+//! channels of the server process when nothing listens to the database.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::sync::broadcast;
+
+use crate::database::{DatabaseAggregator, DatabaseRequest};
+
+/// Number of notifications buffered for slow in-process subscribers
+const NOTIFICATION_CAPACITY: usize = 1_024;
+
+/// Storage of the actions applied by the fast path
+pub trait Storage: Send + Sync {
+    /// Persists the events of an action, with its blob transaction and prover request, and
+    /// sends the notifications of what it changed
+    fn write_events(&self, request: DatabaseRequest) -> BoxFuture<'_, Result<()>>;
+    /// Persists the order statuses and balances aggregated since the last call, and sends the
+    /// notifications of the orders, trades and books they changed
+    fn write_aggregates<'a>(
+        &'a self,
+        aggregator: &'a mut DatabaseAggregator,
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Change committed to the storage, for the feeds to read it again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    Trades,
+    Orders,
+    /// The book of the pair changed
+    Book {
+        symbol: String,
+    },
+    Instruments,
+    /// Users were liquidated on the pair
+    Liquidations {
+        symbol: String,
+    },
+}
+
+impl Notification {
+    /// Name of the `pg_notify` channel
+    pub fn channel(&self) -> &'static str {
+        match self {
+            Notification::Trades => "trades",
+            Notification::Orders => "orders",
+            Notification::Book { .. } => "book",
+            Notification::Instruments => "instruments",
+            Notification::Liquidations { .. } => "liquidations",
+        }
+    }
+
+    pub fn payload(&self) -> &str {
+        match self {
+            Notification::Book { symbol } | Notification::Liquidations { symbol } => symbol,
+            other => other.channel(),
+        }
+    }
+}
+
+/// Sends the notifications of the storage
+pub trait Notifier: Send + Sync {
+    fn notify(&self, notification: Notification) -> BoxFuture<'_, Result<()>>;
+    /// Receives the notifications in the server process, if they are published there
+    fn subscribe(&self) -> Option<broadcast::Receiver<Notification>> {
+        None
+    }
+}
+
+/// How notifications are sent
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifierKind {
+    /// `pg_notify`, listened to by the API server
+    #[default]
+    Postgres,
+    /// Broadcast channels of the server process
+    InProcess,
+}
+
+/// Notifier selected in the configuration
+pub fn notifier(kind: NotifierKind, pool: &PgPool) -> Arc<dyn Notifier> {
+    match kind {
+        NotifierKind::Postgres => Arc::new(PgNotifier { pool: pool.clone() }),
+        NotifierKind::InProcess => Arc::new(BroadcastNotifier::default()),
+    }
+}
+
+pub struct PgNotifier {
+    pool: PgPool,
+}
+
+impl Notifier for PgNotifier {
+    fn notify(&self, notification: Notification) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            sqlx::query("select pg_notify($1, $2)")
+                .bind(notification.channel())
+                .bind(notification.payload())
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+/// Publishes notifications to the subscribers of the server process
+pub struct BroadcastNotifier {
+    sender: broadcast::Sender<Notification>,
+}
+
+impl Default for BroadcastNotifier {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(NOTIFICATION_CAPACITY);
+        BroadcastNotifier { sender }
+    }
+}
+
+impl Notifier for BroadcastNotifier {
+    fn notify(&self, notification: Notification) -> BoxFuture<'_, Result<()>> {
+        // Sending only fails when nobody is subscribed
+        let _ = self.sender.send(notification);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn subscribe(&self) -> Option<broadcast::Receiver<Notification>> {
+        Some(self.sender.subscribe())
+    }
+}
@@ -0,0 +1,109 @@
+//! Logs and traces of the server and the autoprover. Spans are exported over OTLP with the
+//! endpoint, sampling ratio and service name of `TracingConfig`. The context of an action is
+//! carried across the bus and in its prover request, stored in the database, so that its trace
+//! follows it from the API to its proof, even when proven by another process. The filter of the
+//! logs can be replaced at runtime, see [`set_log_filter`].
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use anyhow::{bail, Context as _, Result};
+use opentelemetry::{global, trace::TracerProvider as _, Context};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    trace::{Sampler, SdkTracer, SdkTracerProvider},
+    Resource,
+};
+use tracing_subscriber::{
+    layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+use crate::conf::TracingConfig;
+
+/// Filter of the logs installed by `setup_tracing`
+static LOG_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Sets up the logs, filtered by `log_filter`, and the export of the spans when enabled by the
+/// configuration or `force`, i.e. the `--tracing` flag. Logs are JSON with the "json" format,
+/// and plain otherwise.
+pub fn setup_tracing(
+    log_format: &str,
+    log_filter: &str,
+    config: &TracingConfig,
+    force: bool,
+) -> Result<()> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let (filter, handle) = reload::Layer::new(env_filter(log_filter)?);
+    let otel = if config.enabled || force {
+        Some(tracing_opentelemetry::layer().with_tracer(tracer(config)?))
+    } else {
+        None
+    };
+
+    let registry = tracing_subscriber::registry().with(filter).with(otel);
+    match log_format {
+        "json" => registry
+            .with(tracing_subscriber::fmt::layer().json())
+            .try_init(),
+        _ => registry.with(tracing_subscriber::fmt::layer()).try_init(),
+    }
+    .context("installing the tracing subscriber")?;
+    _ = LOG_FILTER.set(handle);
+    Ok(())
+}
+
+/// Filter of the logs of the directives of `log_filter`, e.g. "info,server::database=debug",
+/// or of `RUST_LOG` when empty, "info" by default
+pub fn env_filter(log_filter: &str) -> Result<EnvFilter> {
+    if log_filter.is_empty() {
+        return Ok(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")));
+    }
+    EnvFilter::try_new(log_filter).with_context(|| format!("invalid log filter {log_filter:?}"))
+}
+
+/// Replaces the filter of the logs installed by `setup_tracing`
+pub fn set_log_filter(log_filter: &str) -> Result<()> {
+    let filter = env_filter(log_filter)?;
+    let Some(handle) = LOG_FILTER.get() else {
+        bail!("The logs were not set up by the server, their filter cannot change");
+    };
+    handle
+        .reload(filter)
+        .context("replacing the filter of the logs")
+}
+
+fn tracer(config: &TracingConfig) -> Result<SdkTracer> {
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(&config.endpoint)
+        .build()
+        .context("building the OTLP span exporter")?;
+    // Sampled once per trace, where it starts: spans continued from another service follow the
+    // decision of their parent
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sampling_ratio,
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer(config.service_name.clone());
+    global::set_tracer_provider(provider);
+    Ok(tracer)
+}
+
+/// W3C trace context of `context`, to continue its trace elsewhere
+pub fn inject_context(context: &Context) -> HashMap<String, String> {
+    let mut carrier = HashMap::new();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(context, &mut carrier));
+    carrier
+}
+
+/// Context of a trace injected by `inject_context`, empty when there is none
+pub fn extract_context(carrier: &HashMap<String, String>) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(carrier))
+}
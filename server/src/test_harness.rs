@@ -0,0 +1,74 @@
+//! Programmatic start of the server for integration tests, such as hylix scenarios driving
+//! multi-user flows. Built with the `test-mode` feature, which also freezes the server clock
+//! and adds the `/test/*` endpoints (state injection, clock control).
+//!
+//! The server registers global metrics providers, so a process runs one server at a time.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use tokio::task::JoinHandle;
+
+use crate::{
+    conf::Conf,
+    runner::{run, Args},
+};
+
+/// How long [`TestServer::start`] waits for the server to answer
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Server running in a task of the current runtime, stopped when dropped
+pub struct TestServer {
+    /// Base URL of the REST API, e.g. `http://localhost:9002`
+    pub url: String,
+    handle: JoinHandle<Result<()>>,
+}
+
+impl TestServer {
+    /// Arguments of a server started from a clean data directory and database, proving with
+    /// the test verifier (see `--mock-prover`)
+    pub fn args(config_files: Vec<String>, server_port: u16) -> Args {
+        let mut args = Args::parse_from(["server", "--clean-data-directory", "--clean-db"]);
+        args.config_file = config_files;
+        args.server_port = Some(server_port);
+        args.mock_prover = true;
+        args
+    }
+
+    /// Starts the server, and waits for its REST API to answer
+    pub async fn start(args: Args, config: Conf) -> Result<Self> {
+        let port = args.server_port.unwrap_or(config.rest_server_port);
+        let url = format!("http://localhost:{port}");
+        let mut handle = tokio::spawn(run(args, config));
+
+        let client = reqwest::Client::new();
+        let started = tokio::time::Instant::now();
+        loop {
+            if handle.is_finished() {
+                let result = (&mut handle).await.context("server task panicked")?;
+                result.context("server stopped while starting")?;
+                bail!("server stopped while starting");
+            }
+            if client
+                .get(format!("{url}/_health"))
+                .send()
+                .await
+                .is_ok_and(|response| response.status().is_success())
+            {
+                return Ok(TestServer { url, handle });
+            }
+            if started.elapsed() > STARTUP_TIMEOUT {
+                handle.abort();
+                bail!("server did not answer on {url} within {STARTUP_TIMEOUT:?}");
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
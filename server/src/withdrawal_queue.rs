@@ -0,0 +1,30 @@
+use orderbook::model::{ExecuteState, PendingWithdrawal};
+
+/// Queued withdrawals to release at `block_height`, the oldest first: the approved ones whose
+/// delay is over, and that are not held by the pause of the bridge.
+pub fn releasable_withdrawals(
+    state: &ExecuteState,
+    block_height: u64,
+    max: usize,
+) -> Vec<(String, PendingWithdrawal)> {
+    let mut releasable: Vec<(String, PendingWithdrawal)> = state
+        .users_info
+        .values()
+        .flat_map(|user_info| {
+            user_info
+                .pending_withdrawals
+                .iter()
+                .filter(|withdrawal| {
+                    withdrawal.approved
+                        && withdrawal.available_at <= block_height
+                        && state.ensure_bridge_open(&withdrawal.destination).is_ok()
+                })
+                .map(|withdrawal| (user_info.user.clone(), withdrawal.clone()))
+        })
+        .collect();
+
+    releasable
+        .sort_by(|a, b| (a.1.available_at, &a.0, a.1.id).cmp(&(b.1.available_at, &b.0, b.1.id)));
+    releasable.truncate(max);
+    releasable
+}
@@ -0,0 +1,76 @@
+//! Workspace tooling, run as `cargo hyliquid <command>` once installed with
+//! `cargo install --path tools/cargo-hyliquid`.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+
+use crate::scaffold::Vapp;
+
+mod scaffold;
+
+#[derive(Parser, Debug)]
+#[command(name = "cargo", bin_name = "cargo")]
+enum Cargo {
+    /// Hyliquid workspace tooling
+    Hyliquid(Hyliquid),
+}
+
+#[derive(Args, Debug)]
+#[command(version, about, long_about = None)]
+struct Hyliquid {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Scaffolds a vapp: a contract crate with its zkVM entry points, and a server module
+    /// recording its settled actions in a new table
+    NewVapp(NewVappArgs),
+}
+
+#[derive(Args, Debug)]
+struct NewVappArgs {
+    /// Name of the contract crate, and of its contract
+    name: String,
+
+    /// Rust file defining the action and event types of the contract, copied as its model
+    #[arg(long)]
+    model: PathBuf,
+
+    /// Type of the actions, decoded from the blobs of the contract
+    #[arg(long, default_value = "Action")]
+    action: String,
+
+    /// Type of the events returned by the actions
+    #[arg(long, default_value = "Event")]
+    event: String,
+
+    /// Root of the workspace
+    #[arg(long, default_value = ".")]
+    workspace: PathBuf,
+}
+
+fn main() -> Result<()> {
+    let Cargo::Hyliquid(hyliquid) = Cargo::parse();
+
+    match hyliquid.command {
+        Command::NewVapp(args) => {
+            let model = std::fs::read_to_string(&args.model)
+                .with_context(|| format!("reading model {}", args.model.display()))?;
+            let vapp = Vapp::new(&args.name, &args.action, &args.event, model)?;
+
+            for path in vapp.scaffold(&args.workspace)? {
+                println!("  {}", path.display());
+            }
+            println!(
+                "\nScaffolded {}: implement its state in contracts/{}/src/state.rs, and build its guest with `cargo prove build` in contracts/{}.",
+                args.name, args.name, args.name
+            );
+        }
+    }
+
+    Ok(())
+}
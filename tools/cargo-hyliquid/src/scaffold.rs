@@ -0,0 +1,458 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+/// Crates of the workspace a vapp cannot be named after
+const RESERVED_NAMES: &[&str] = &[
+    "cargo-hyliquid",
+    "client",
+    "contracts",
+    "loadtest",
+    "orderbook",
+    "sdk",
+    "server",
+    "wasm",
+];
+
+/// The other modules are built before this line of the runner
+const RUNNER_ANCHOR: &str =
+    "    // Should come last so the other modules have nested their own routes.\n";
+
+const CONTRACT_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "Cargo.toml",
+        include_str!("../templates/contract/Cargo.toml.tmpl"),
+    ),
+    (
+        "src/lib.rs",
+        include_str!("../templates/contract/lib.rs.tmpl"),
+    ),
+    (
+        "src/state.rs",
+        include_str!("../templates/contract/state.rs.tmpl"),
+    ),
+    (
+        "src/zk.rs",
+        include_str!("../templates/contract/zk.rs.tmpl"),
+    ),
+    (
+        "src/main.rs",
+        include_str!("../templates/contract/main.rs.tmpl"),
+    ),
+    (
+        "src/risc0.rs",
+        include_str!("../templates/contract/risc0.rs.tmpl"),
+    ),
+];
+const MODULE_TEMPLATE: &str = include_str!("../templates/server/module.rs.tmpl");
+const MIGRATION_TEMPLATE: &str = include_str!("../templates/server/migration.sql.tmpl");
+const RUNNER_TEMPLATE: &str = include_str!("../templates/server/runner.rs.tmpl");
+
+/// A vapp to scaffold: its contract crate, and the server module recording its settled actions
+pub struct Vapp {
+    /// Name of the crate and of the contract, e.g. `my-escrow`
+    name: String,
+    /// Name of the crate in Rust paths, e.g. `my_escrow`
+    crate_name: String,
+    /// Prefix of the generated types, e.g. `MyEscrow`
+    type_prefix: String,
+    action: String,
+    event: String,
+    model: String,
+}
+
+impl Vapp {
+    pub fn new(name: &str, action: &str, event: &str, model: String) -> Result<Self> {
+        let valid_name = name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if !valid_name {
+            bail!("Invalid vapp name {name}: expected lowercase letters, digits, '-' or '_'");
+        }
+        if RESERVED_NAMES.contains(&name) {
+            bail!("Vapp name {name} is already used by the workspace");
+        }
+        for ty in [action, event] {
+            let valid_type = ty.starts_with(|c: char| c.is_ascii_uppercase())
+                && ty.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid_type {
+                bail!("Invalid type name {ty}");
+            }
+            if !defines(&model, ty) {
+                bail!("The model does not define a public {ty} enum or struct");
+            }
+        }
+
+        Ok(Vapp {
+            name: name.to_string(),
+            crate_name: name.replace('-', "_"),
+            type_prefix: type_prefix(name),
+            action: action.to_string(),
+            event: event.to_string(),
+            model,
+        })
+    }
+
+    fn render(&self, template: &str) -> String {
+        template
+            .replace("__name__", &self.name)
+            .replace("__crate__", &self.crate_name)
+            .replace("__Name__", &self.type_prefix)
+            .replace("__Action__", &self.action)
+            .replace("__Event__", &self.event)
+    }
+
+    /// Writes the contract crate, the server module and its migration, and wires them in the
+    /// workspace. Nothing is written unless every file can be: existing files are never
+    /// overwritten, and the files to edit must have the expected layout.
+    /// Returns the paths created or edited.
+    pub fn scaffold(&self, workspace: &Path) -> Result<Vec<PathBuf>> {
+        let contract_dir = workspace.join("contracts").join(&self.name);
+        let server_src = workspace.join("server").join("src");
+        let migrations_dir = server_src.join("migrations");
+
+        let mut files = vec![(contract_dir.join("src/model.rs"), self.model.clone())];
+        for (path, template) in CONTRACT_TEMPLATES {
+            files.push((contract_dir.join(path), self.render(template)));
+        }
+        files.push((
+            server_src.join(format!("{}.rs", self.crate_name)),
+            self.render(MODULE_TEMPLATE),
+        ));
+        files.push((
+            migrations_dir.join(format!(
+                "{}_{}.sql",
+                next_migration(&migrations_dir)?,
+                self.crate_name
+            )),
+            self.render(MIGRATION_TEMPLATE),
+        ));
+        for (path, _) in &files {
+            if path.exists() {
+                bail!("{} already exists", path.display());
+            }
+        }
+
+        let workspace_toml = workspace.join("Cargo.toml");
+        let server_toml = workspace.join("server").join("Cargo.toml");
+        let lib_rs = server_src.join("lib.rs");
+        let runner_rs = server_src.join("runner.rs");
+        let edits = vec![
+            (
+                insert_member(&read(&workspace_toml)?, &format!("contracts/{}", self.name))
+                    .with_context(|| format!("editing {}", workspace_toml.display()))?,
+                workspace_toml,
+            ),
+            (
+                insert_dependency(
+                    &read(&server_toml)?,
+                    &format!(
+                        "{} = {{ path = \"../contracts/{}\" }}",
+                        self.name, self.name
+                    ),
+                )
+                .with_context(|| format!("editing {}", server_toml.display()))?,
+                server_toml,
+            ),
+            (insert_mod(&read(&lib_rs)?, &self.crate_name), lib_rs),
+            (
+                insert_before(
+                    &read(&runner_rs)?,
+                    RUNNER_ANCHOR,
+                    &self.render(RUNNER_TEMPLATE),
+                )
+                .with_context(|| format!("editing {}", runner_rs.display()))?,
+                runner_rs,
+            ),
+        ];
+
+        let mut written = Vec::new();
+        for (path, content) in files {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("creating {}", parent.display()))?;
+            }
+            std::fs::write(&path, content)
+                .with_context(|| format!("writing {}", path.display()))?;
+            written.push(path);
+        }
+        for (content, path) in edits {
+            std::fs::write(&path, content)
+                .with_context(|| format!("writing {}", path.display()))?;
+            written.push(path);
+        }
+
+        Ok(written)
+    }
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))
+}
+
+/// `my-escrow` -> `MyEscrow`
+fn type_prefix(name: &str) -> String {
+    name.split(['-', '_'])
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+fn defines(model: &str, ty: &str) -> bool {
+    model.lines().any(|line| {
+        let line = line.trim_start();
+        [format!("pub enum {ty}"), format!("pub struct {ty}")]
+            .iter()
+            .any(|decl| {
+                line.strip_prefix(decl.as_str())
+                    .is_some_and(|rest| rest.starts_with([' ', '{', '<', '(']) || rest.is_empty())
+            })
+    })
+}
+
+/// Migrations are numbered from 1, the new one follows the last
+fn next_migration(migrations_dir: &Path) -> Result<u32> {
+    let mut last = 0;
+    for entry in std::fs::read_dir(migrations_dir)
+        .with_context(|| format!("reading {}", migrations_dir.display()))?
+    {
+        let file_name = entry?.file_name();
+        let number = file_name
+            .to_str()
+            .and_then(|name| name.split('_').next())
+            .and_then(|number| number.parse::<u32>().ok());
+        if let Some(number) = number {
+            last = last.max(number);
+        }
+    }
+    Ok(last + 1)
+}
+
+fn insert_member(cargo_toml: &str, member: &str) -> Result<String> {
+    let Some(start) = cargo_toml.find("members = [") else {
+        bail!("no workspace members");
+    };
+    let Some(end) = cargo_toml[start..].find(']').map(|end| start + end) else {
+        bail!("unterminated workspace members");
+    };
+    let members = &cargo_toml[start..end];
+    let insert = if members.contains('\n') {
+        format!("  \"{member}\",\n")
+    } else {
+        format!(", \"{member}\"")
+    };
+    Ok(format!(
+        "{}{insert}{}",
+        &cargo_toml[..end],
+        &cargo_toml[end..]
+    ))
+}
+
+/// Adds the dependency after the orderbook one
+fn insert_dependency(server_toml: &str, dependency: &str) -> Result<String> {
+    let Some(start) = server_toml.find("\norderbook = ") else {
+        bail!("no orderbook dependency");
+    };
+    let end = server_toml[start + 1..]
+        .find('\n')
+        .map_or(server_toml.len(), |end| start + 1 + end);
+    Ok(format!(
+        "{}\n{dependency}{}",
+        &server_toml[..end],
+        &server_toml[end..]
+    ))
+}
+
+/// Declares the module in alphabetical order, before the attributes of the module it precedes
+fn insert_mod(lib_rs: &str, module: &str) -> String {
+    let mut lines: Vec<&str> = lib_rs.lines().collect();
+    let declaration = format!("pub mod {module};");
+    let mut index = lines
+        .iter()
+        .position(|line| {
+            line.strip_prefix("pub mod ")
+                .is_some_and(|name| name.trim_end_matches(';') > module)
+        })
+        .unwrap_or(lines.len());
+    while index > 0 && lines[index - 1].starts_with("#[") {
+        index -= 1;
+    }
+    lines.insert(index, &declaration);
+    lines.join("\n") + "\n"
+}
+
+fn insert_before(content: &str, anchor: &str, insert: &str) -> Result<String> {
+    let Some(index) = content.find(anchor) else {
+        bail!("anchor {:?} not found", anchor.trim());
+    };
+    Ok(format!(
+        "{}{insert}{}",
+        &content[..index],
+        &content[index..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: &str = "
+use borsh::{BorshDeserialize, BorshSerialize};
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub enum EscrowAction {
+    Lock { amount: u64 },
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub enum EscrowEvent {
+    Locked { amount: u64 },
+}
+";
+
+    fn vapp() -> Vapp {
+        Vapp::new(
+            "my-escrow",
+            "EscrowAction",
+            "EscrowEvent",
+            MODEL.to_string(),
+        )
+        .expect("valid vapp")
+    }
+
+    #[test]
+    fn names_are_derived_from_the_crate_name() {
+        assert_eq!(type_prefix("my-escrow"), "MyEscrow");
+        assert_eq!(type_prefix("swap_v2"), "SwapV2");
+
+        let vapp = vapp();
+        let rendered = vapp.render("__name__ __crate__::__Action__ __Name__Module __Event__");
+        assert_eq!(
+            rendered,
+            "my-escrow my_escrow::EscrowAction MyEscrowModule EscrowEvent"
+        );
+    }
+
+    #[test]
+    fn templates_are_fully_rendered() {
+        let vapp = vapp();
+        let templates = CONTRACT_TEMPLATES
+            .iter()
+            .map(|(_, template)| *template)
+            .chain([MODULE_TEMPLATE, MIGRATION_TEMPLATE, RUNNER_TEMPLATE]);
+        for template in templates {
+            let rendered = vapp.render(template);
+            for placeholder in [
+                "__name__",
+                "__crate__",
+                "__Name__",
+                "__Action__",
+                "__Event__",
+            ] {
+                assert!(
+                    !rendered.contains(placeholder),
+                    "{placeholder} left in {rendered}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_vapps_are_refused() {
+        assert!(Vapp::new("MyEscrow", "EscrowAction", "EscrowEvent", MODEL.into()).is_err());
+        assert!(Vapp::new("orderbook", "EscrowAction", "EscrowEvent", MODEL.into()).is_err());
+        assert!(Vapp::new("escrow", "Escrow", "EscrowEvent", MODEL.into()).is_err());
+        assert!(Vapp::new("escrow", "EscrowAction", "Event", MODEL.into()).is_err());
+    }
+
+    #[test]
+    fn modules_are_declared_in_order() {
+        let lib_rs =
+            "pub mod api;\npub mod setup;\n#[cfg(feature = \"test\")]\npub mod test_harness;\n";
+        assert_eq!(
+            insert_mod(lib_rs, "escrow"),
+            "pub mod api;\npub mod escrow;\npub mod setup;\n#[cfg(feature = \"test\")]\npub mod test_harness;\n"
+        );
+        assert_eq!(
+            insert_mod(lib_rs, "vault"),
+            "pub mod api;\npub mod setup;\n#[cfg(feature = \"test\")]\npub mod test_harness;\npub mod vault;\n"
+        );
+        assert_eq!(
+            insert_mod(lib_rs, "swap"),
+            "pub mod api;\npub mod setup;\npub mod swap;\n#[cfg(feature = \"test\")]\npub mod test_harness;\n"
+        );
+    }
+
+    #[test]
+    fn members_and_dependencies_are_added() {
+        assert_eq!(
+            insert_member("members = [\"server\"]\n", "contracts/escrow").expect("members"),
+            "members = [\"server\", \"contracts/escrow\"]\n"
+        );
+        assert_eq!(
+            insert_member("members = [\n  \"server\",\n]\n", "contracts/escrow").expect("members"),
+            "members = [\n  \"server\",\n  \"contracts/escrow\",\n]\n"
+        );
+        assert_eq!(
+            insert_dependency(
+                "[dependencies]\norderbook = { workspace = true }\nsdk = { workspace = true }\n",
+                "escrow = { path = \"../contracts/escrow\" }"
+            )
+            .expect("dependency"),
+            "[dependencies]\norderbook = { workspace = true }\nescrow = { path = \"../contracts/escrow\" }\nsdk = { workspace = true }\n"
+        );
+    }
+
+    #[test]
+    fn scaffolds_once_in_a_workspace() {
+        let workspace =
+            std::env::temp_dir().join(format!("cargo-hyliquid-test-{}", std::process::id()));
+        let migrations = workspace.join("server/src/migrations");
+        std::fs::create_dir_all(&migrations).expect("create workspace");
+        for (path, content) in [
+            ("Cargo.toml", "[workspace]\nmembers = [\"server\"]\n"),
+            (
+                "server/Cargo.toml",
+                "[dependencies]\norderbook = { workspace = true }\n",
+            ),
+            ("server/src/lib.rs", "pub mod api;\npub mod runner;\n"),
+            ("server/src/runner.rs", RUNNER_ANCHOR),
+            ("server/src/migrations/1_create_table.sql", ""),
+            ("server/src/migrations/9_order_tags.sql", ""),
+        ] {
+            std::fs::write(workspace.join(path), content).expect("write workspace file");
+        }
+
+        let written = vapp().scaffold(&workspace).expect("scaffold vapp");
+        assert!(written.contains(&workspace.join("contracts/my-escrow/src/zk.rs")));
+        assert!(written.contains(&workspace.join("server/src/my_escrow.rs")));
+        assert!(written.contains(&migrations.join("10_my_escrow.sql")));
+        assert_eq!(
+            std::fs::read_to_string(workspace.join("contracts/my-escrow/src/model.rs"))
+                .expect("read model"),
+            MODEL
+        );
+        assert!(
+            std::fs::read_to_string(workspace.join("server/src/runner.rs"))
+                .expect("read runner")
+                .contains("build_module::<crate::my_escrow::MyEscrowModule>")
+        );
+
+        // Nothing is overwritten
+        let lib_rs = std::fs::read_to_string(workspace.join("server/src/lib.rs")).expect("lib");
+        assert!(vapp().scaffold(&workspace).is_err());
+        assert_eq!(
+            std::fs::read_to_string(workspace.join("server/src/lib.rs")).expect("lib"),
+            lib_rs
+        );
+
+        std::fs::remove_dir_all(&workspace).expect("remove workspace");
+    }
+}
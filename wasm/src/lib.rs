@@ -0,0 +1,368 @@
+//! WebAssembly bindings of the action encoding and signature messages of the orderbook contract,
+//! so that web frontends build the exact payloads the server and the zk program verify.
+//!
+//! Built with `wasm-pack build wasm --target web`. Orders, destinations and actions are passed
+//! as plain JS objects, in the JSON format of the API, e.g. `{ order_id, order_side: "bid",
+//! order_type: "limit", price, pair: [base, quote], quantity }`. Encoded payloads and signatures
+//! are returned as `Uint8Array`, or hex strings where the API expects them.
+
+use k256::{
+    ecdsa::{signature::DigestSigner, Signature, SigningKey},
+    SecretKey,
+};
+use orderbook::{
+    eip712,
+    model::{DustSweep, Order, WithdrawDestination},
+    transaction::{
+        CancelOrderPrivateInput, CreateOrderPrivateInput, OrderbookAction,
+        PermissionedOrderbookAction, TransferPrivateInput, WithdrawPrivateInput,
+    },
+    utils::{self, SignedAction},
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use sha3::{Digest, Sha3_256};
+use wasm_bindgen::prelude::*;
+
+/// Action signed by a session key, as passed from JS: `{ action: "create_order", order }`,
+/// `{ action: "cancel", order_id }`, `{ action: "withdraw", symbol, amount }`...
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum UserAction {
+    CreateOrder {
+        order: Order,
+    },
+    CreateOrders {
+        orders: Vec<Order>,
+    },
+    Cancel {
+        order_id: String,
+    },
+    Withdraw {
+        symbol: String,
+        amount: u64,
+    },
+    Transfer {
+        to: String,
+        symbol: String,
+        amount: u64,
+    },
+    SweepDust {
+        quote: String,
+        sweeps: Vec<DustSweep>,
+    },
+}
+
+impl UserAction {
+    /// Runs `f` on the signed action of `user` at `nonce`
+    pub fn with_signed<R>(&self, user: &str, nonce: u32, f: impl FnOnce(&SignedAction) -> R) -> R {
+        let action = match self {
+            UserAction::CreateOrder { order } => SignedAction::CreateOrder { user, nonce, order },
+            UserAction::CreateOrders { orders } => SignedAction::CreateOrders {
+                user,
+                nonce,
+                orders,
+            },
+            UserAction::Cancel { order_id } => SignedAction::CancelOrder {
+                user,
+                nonce,
+                order_id,
+            },
+            UserAction::Withdraw { symbol, amount } => SignedAction::Withdraw {
+                user,
+                nonce,
+                symbol,
+                amount: *amount,
+            },
+            UserAction::Transfer { to, symbol, amount } => SignedAction::Transfer {
+                user,
+                nonce,
+                to,
+                symbol,
+                amount: *amount,
+            },
+            UserAction::SweepDust { quote, sweeps } => SignedAction::SweepDust {
+                user,
+                nonce,
+                quote,
+                sweeps,
+            },
+        };
+        f(&action)
+    }
+}
+
+fn from_js<T: DeserializeOwned>(value: JsValue) -> Result<T, JsError> {
+    serde_wasm_bindgen::from_value(value).map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn encode<T: borsh::BorshSerialize>(value: &T) -> Result<Vec<u8>, JsError> {
+    borsh::to_vec(value).map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn signing_key(secret_key: &str) -> Result<SigningKey, JsError> {
+    let bytes = hex::decode(secret_key.trim_start_matches("0x"))
+        .map_err(|e| JsError::new(&format!("Secret key is not hex encoded: {e}")))?;
+    let secret_key = SecretKey::from_slice(&bytes)
+        .map_err(|e| JsError::new(&format!("Invalid secret key: {e}")))?;
+    Ok(SigningKey::from(secret_key))
+}
+
+// Signature messages
+
+/// Message signed by secp256k1 and Ed25519 session keys to authorize an action
+#[wasm_bindgen(js_name = actionMessage)]
+pub fn action_message(user: &str, nonce: u32, action: JsValue) -> Result<String, JsError> {
+    let action: UserAction = from_js(action)?;
+    Ok(action.with_signed(user, nonce, |action| action.message()))
+}
+
+/// Format: `{user}:{nonce}:create_order:{order_id}`
+#[wasm_bindgen(js_name = createOrderMessage)]
+pub fn create_order_message(user: &str, nonce: u32, order: JsValue) -> Result<String, JsError> {
+    let order: Order = from_js(order)?;
+    Ok(SignedAction::CreateOrder {
+        user,
+        nonce,
+        order: &order,
+    }
+    .message())
+}
+
+/// Format: `{user}:{nonce}:withdraw:{symbol}:{amount}`
+#[wasm_bindgen(js_name = withdrawMessage)]
+pub fn withdraw_message(user: &str, nonce: u32, symbol: &str, amount: u64) -> String {
+    SignedAction::Withdraw {
+        user,
+        nonce,
+        symbol,
+        amount,
+    }
+    .message()
+}
+
+/// Format: `{user}:{nonce}:cancel:{order_id}`
+#[wasm_bindgen(js_name = cancelMessage)]
+pub fn cancel_message(user: &str, nonce: u32, order_id: &str) -> String {
+    SignedAction::CancelOrder {
+        user,
+        nonce,
+        order_id,
+    }
+    .message()
+}
+
+/// Format: `{user}:{nonce}:cancel_on_disconnect:{timeout_secs}:{order_ids joined by commas}`
+#[wasm_bindgen(js_name = cancelOnDisconnectMessage)]
+pub fn cancel_on_disconnect_message(
+    user: &str,
+    nonce: u32,
+    timeout_secs: u64,
+    order_ids: JsValue,
+) -> Result<String, JsError> {
+    let order_ids: Vec<String> = from_js(order_ids)?;
+    Ok(utils::cancel_on_disconnect_message(
+        user,
+        nonce,
+        timeout_secs,
+        &order_ids,
+    ))
+}
+
+#[wasm_bindgen(js_name = saveAddressMessage)]
+pub fn save_address_message(
+    user: &str,
+    destination: JsValue,
+    label: &str,
+) -> Result<String, JsError> {
+    let destination: WithdrawDestination = from_js(destination)?;
+    Ok(utils::save_address_message(user, &destination, label))
+}
+
+#[wasm_bindgen(js_name = deleteAddressMessage)]
+pub fn delete_address_message(user: &str, destination: JsValue) -> Result<String, JsError> {
+    let destination: WithdrawDestination = from_js(destination)?;
+    Ok(utils::delete_address_message(user, &destination))
+}
+
+#[wasm_bindgen(js_name = createApiKeyMessage)]
+pub fn create_api_key_message(user: &str, label: &str) -> String {
+    utils::create_api_key_message(user, label)
+}
+
+#[wasm_bindgen(js_name = revokeApiKeyMessage)]
+pub fn revoke_api_key_message(user: &str, key_id: i64) -> String {
+    utils::revoke_api_key_message(user, key_id)
+}
+
+/// EIP-712 hash of an action, signed by Ethereum wallets registered as session keys
+#[wasm_bindgen(js_name = eip712SigningHash)]
+pub fn eip712_signing_hash(user: &str, nonce: u32, action: JsValue) -> Result<Vec<u8>, JsError> {
+    let action: UserAction = from_js(action)?;
+    Ok(action
+        .with_signed(user, nonce, eip712::signing_hash)
+        .to_vec())
+}
+
+// secp256k1 session keys
+
+/// Hex encoded SEC1 uncompressed public key of a hex encoded secret key, as registered on the user
+#[wasm_bindgen(js_name = publicKey)]
+pub fn public_key(secret_key: &str) -> Result<String, JsError> {
+    let signing_key = signing_key(secret_key)?;
+    Ok(hex::encode(
+        signing_key
+            .verifying_key()
+            .to_encoded_point(false)
+            .as_bytes(),
+    ))
+}
+
+/// Hex encoded signature of a message: ECDSA over its SHA3-256, as the contract verifies it
+#[wasm_bindgen]
+pub fn sign(secret_key: &str, message: &str) -> Result<String, JsError> {
+    let signing_key = signing_key(secret_key)?;
+    let signature: Signature = signing_key.sign_digest(Sha3_256::new_with_prefix(message));
+    Ok(hex::encode(signature.to_bytes()))
+}
+
+#[wasm_bindgen(js_name = signAction)]
+pub fn sign_action(
+    secret_key: &str,
+    user: &str,
+    nonce: u32,
+    action: JsValue,
+) -> Result<String, JsError> {
+    sign(secret_key, &action_message(user, nonce, action)?)
+}
+
+/// Whether a signature of a message was made by a secp256k1 public key
+#[wasm_bindgen(js_name = verifySignature)]
+pub fn verify_signature(signature: Vec<u8>, message: &str, public_key: Vec<u8>) -> bool {
+    utils::verify_signature(&signature, message, &public_key)
+}
+
+// Action encoding
+
+/// Borsh encoded orderbook blob of a permissioned action, passed in its serde JSON format,
+/// e.g. `{ CreateOrder: order }` or `{ Cancel: { order_id } }`
+#[wasm_bindgen(js_name = encodePermissionedAction)]
+pub fn encode_permissioned_action(action: JsValue, action_id: u32) -> Result<Vec<u8>, JsError> {
+    let action: PermissionedOrderbookAction = from_js(action)?;
+    encode(&OrderbookAction::PermissionedOrderbookAction(
+        action, action_id,
+    ))
+}
+
+#[wasm_bindgen(js_name = encodeCreateOrderAction)]
+pub fn encode_create_order_action(order: JsValue, action_id: u32) -> Result<Vec<u8>, JsError> {
+    let order: Order = from_js(order)?;
+    encode(&OrderbookAction::PermissionedOrderbookAction(
+        PermissionedOrderbookAction::CreateOrder(order),
+        action_id,
+    ))
+}
+
+#[wasm_bindgen(js_name = encodeCancelAction)]
+pub fn encode_cancel_action(order_id: &str, action_id: u32) -> Result<Vec<u8>, JsError> {
+    encode(&OrderbookAction::PermissionedOrderbookAction(
+        PermissionedOrderbookAction::Cancel {
+            order_id: order_id.to_string(),
+        },
+        action_id,
+    ))
+}
+
+/// `block_height` is the block the withdraw limits are accounted at
+#[wasm_bindgen(js_name = encodeWithdrawAction)]
+pub fn encode_withdraw_action(
+    symbol: &str,
+    amount: u64,
+    destination: JsValue,
+    block_height: u64,
+    action_id: u32,
+) -> Result<Vec<u8>, JsError> {
+    let destination: WithdrawDestination = from_js(destination)?;
+    encode(&OrderbookAction::PermissionedOrderbookAction(
+        PermissionedOrderbookAction::Withdraw {
+            symbol: symbol.to_string(),
+            amount,
+            destination,
+            block_height,
+        },
+        action_id,
+    ))
+}
+
+// Private inputs
+
+/// `block_height` is the block the circuit breakers are checked at
+#[wasm_bindgen(js_name = encodeCreateOrderPrivateInput)]
+pub fn encode_create_order_private_input(
+    public_key: Vec<u8>,
+    signature: Vec<u8>,
+    block_height: u64,
+) -> Result<Vec<u8>, JsError> {
+    encode(&CreateOrderPrivateInput {
+        signature,
+        public_key,
+        block_height,
+    })
+}
+
+#[wasm_bindgen(js_name = encodeCancelPrivateInput)]
+pub fn encode_cancel_private_input(
+    public_key: Vec<u8>,
+    signature: Vec<u8>,
+) -> Result<Vec<u8>, JsError> {
+    encode(&CancelOrderPrivateInput {
+        signature,
+        public_key,
+    })
+}
+
+#[wasm_bindgen(js_name = encodeWithdrawPrivateInput)]
+pub fn encode_withdraw_private_input(
+    public_key: Vec<u8>,
+    signature: Vec<u8>,
+) -> Result<Vec<u8>, JsError> {
+    encode(&WithdrawPrivateInput {
+        signature,
+        public_key,
+    })
+}
+
+#[wasm_bindgen(js_name = encodeTransferPrivateInput)]
+pub fn encode_transfer_private_input(
+    public_key: Vec<u8>,
+    signature: Vec<u8>,
+) -> Result<Vec<u8>, JsError> {
+    encode(&TransferPrivateInput {
+        signature,
+        public_key,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_messages_verify_with_the_derived_public_key() {
+        let secret_key = hex::encode([7u8; 32]);
+        let public_key = hex::decode(public_key(&secret_key).unwrap()).unwrap();
+
+        let message = withdraw_message("alice", 4, "USDC", 1_000);
+        assert_eq!(message, "alice:4:withdraw:USDC:1000");
+        let signature = hex::decode(sign(&secret_key, &message).unwrap()).unwrap();
+        assert!(verify_signature(
+            signature.clone(),
+            &message,
+            public_key.clone()
+        ));
+        assert!(!verify_signature(
+            signature,
+            &cancel_message("alice", 4, "order_1"),
+            public_key
+        ));
+    }
+}